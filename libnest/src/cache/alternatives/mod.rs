@@ -0,0 +1,316 @@
+//! Module to store the alternatives: links shared by several packages, like `/usr/bin/cc`, each pointing to a
+//! target offered by one of the installed packages.
+//!
+//! Packages declare the targets they offer in their manifest (see [`Alternative`]). A link points to the target
+//! with the highest priority, unless the user selected another one, in which case it keeps pointing to it until
+//! the user sets it back to automatic or the package offering it is removed.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::unix;
+use std::path::{Path, PathBuf};
+
+use failure::{Error, ResultExt};
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::chroot::Chroot;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{Alternative, PackageID};
+
+use super::errors::{AlternativesError, AlternativesErrorKind};
+
+/// A target offered for a link by an installed package
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct AlternativeCandidate {
+    target: PathBuf,
+    provider: PackageID,
+    priority: i32,
+}
+
+impl AlternativeCandidate {
+    /// Returns the path the link points to when this candidate is used
+    #[inline]
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Returns the installed package offering this candidate
+    #[inline]
+    pub fn provider(&self) -> &PackageID {
+        &self.provider
+    }
+
+    /// Returns the priority of this candidate
+    #[inline]
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+}
+
+/// A link shared by several packages, along with the targets they offer for it
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug, Default)]
+pub struct AlternativeLink {
+    candidates: Vec<AlternativeCandidate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    selected: Option<PathBuf>,
+}
+
+impl AlternativeLink {
+    /// Returns the candidates for this link, sorted by target
+    #[inline]
+    pub fn candidates(&self) -> &[AlternativeCandidate] {
+        &self.candidates
+    }
+
+    /// Returns the target selected by the user, if any
+    #[inline]
+    pub fn selected(&self) -> Option<&Path> {
+        self.selected.as_ref().map(PathBuf::as_path)
+    }
+
+    /// Returns the candidate the link points to: the one selected by the user if it is still offered, or else the
+    /// one with the highest priority, the first one by target on ties
+    pub fn current(&self) -> Option<&AlternativeCandidate> {
+        let selected = self.selected.as_ref().and_then(|selected| {
+            self.candidates
+                .iter()
+                .find(|candidate| candidate.target == *selected)
+        });
+
+        selected.or_else(|| {
+            self.candidates
+                .iter()
+                .rev()
+                .max_by_key(|candidate| candidate.priority)
+        })
+    }
+}
+
+/// Structure representing the alternatives, keyed by the path of their link
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Alternatives {
+    links: BTreeMap<PathBuf, AlternativeLink>,
+}
+
+impl Alternatives {
+    /// Loads the alternatives from the cache, or returns an empty list if it doesn't exist yet
+    pub fn load_from_cache<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            let file = File::open(path).with_context(|_| path.display().to_string())?;
+            let alternatives =
+                serde_json::from_reader(&file).with_context(|_| path.display().to_string())?;
+            Ok(alternatives)
+        } else {
+            Ok(Alternatives::default())
+        }
+    }
+
+    /// Saves the alternatives back to the cache
+    pub fn save_to_cache<P: AsRef<Path>>(
+        &self,
+        path: P,
+        _: &LockFileOwnership,
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|_| parent.display().to_string())?;
+        }
+
+        let mut file = File::create(path).with_context(|_| path.display().to_string())?;
+        serde_json::to_writer_pretty(&file, self).with_context(|_| path.display().to_string())?;
+        writeln!(file)?;
+        Ok(())
+    }
+
+    /// Returns the links, sorted by path
+    #[inline]
+    pub fn links(&self) -> impl Iterator<Item = (&Path, &AlternativeLink)> {
+        self.links.iter().map(|(path, link)| (path.as_path(), link))
+    }
+
+    /// Returns the given link, if any installed package offers a target for it
+    #[inline]
+    pub fn link(&self, link: &Path) -> Option<&AlternativeLink> {
+        self.links.get(link)
+    }
+
+    /// Registers the targets offered by the given package, replacing those offered for the same link and target.
+    /// Returns the links they are for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::cache::alternatives::Alternatives;
+    /// use libnest::package::{Alternative, PackageID};
+    ///
+    /// let gcc = PackageID::parse("stable::sys-devel/gcc#9.1.0")?;
+    /// let clang = PackageID::parse("stable::sys-devel/clang#8.0.0")?;
+    /// let cc = Path::new("/usr/bin/cc");
+    ///
+    /// let mut alternatives = Alternatives::default();
+    /// alternatives.register(&gcc, &[Alternative::new(cc.into(), "/usr/bin/gcc".into(), 50)]);
+    /// alternatives.register(&clang, &[Alternative::new(cc.into(), "/usr/bin/clang".into(), 40)]);
+    ///
+    /// let current = |alternatives: &Alternatives| {
+    ///     alternatives.link(cc).and_then(|link| link.current()).map(|candidate| candidate.target().to_path_buf())
+    /// };
+    /// assert_eq!(current(&alternatives), Some(PathBuf::from("/usr/bin/gcc")));
+    ///
+    /// alternatives.select(cc, Path::new("/usr/bin/clang"))?;
+    /// assert_eq!(current(&alternatives), Some(PathBuf::from("/usr/bin/clang")));
+    ///
+    /// // Removing the selected candidate's package gets the link back to automatic
+    /// alternatives.unregister(&clang);
+    /// assert!(alternatives.repair(cc));
+    /// assert_eq!(current(&alternatives), Some(PathBuf::from("/usr/bin/gcc")));
+    ///
+    /// alternatives.unregister(&gcc);
+    /// alternatives.repair(cc);
+    /// assert!(alternatives.link(cc).is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register(&mut self, provider: &PackageID, alternatives: &[Alternative]) -> Vec<PathBuf> {
+        for alternative in alternatives {
+            let link = self
+                .links
+                .entry(alternative.link().to_path_buf())
+                .or_insert_with(AlternativeLink::default);
+
+            link.candidates
+                .retain(|candidate| candidate.target != alternative.target());
+            link.candidates.push(AlternativeCandidate {
+                target: alternative.target().to_path_buf(),
+                provider: provider.clone(),
+                priority: alternative.priority(),
+            });
+            link.candidates
+                .sort_by(|left, right| left.target.cmp(&right.target));
+        }
+
+        alternatives
+            .iter()
+            .map(|alternative| alternative.link().to_path_buf())
+            .collect()
+    }
+
+    /// Unregisters the targets offered by the given package. Returns the links they were for.
+    ///
+    /// The target selected by the user for these links is kept, so that it is still used if it is registered again
+    /// (when the package is upgraded, for example), until the links are repaired (see [`Alternatives::repair()`]).
+    pub fn unregister(&mut self, provider: &PackageID) -> Vec<PathBuf> {
+        let mut links = Vec::new();
+
+        for (path, link) in self.links.iter_mut() {
+            let count = link.candidates.len();
+
+            link.candidates
+                .retain(|candidate| candidate.provider != *provider);
+            if link.candidates.len() != count {
+                links.push(path.clone());
+            }
+        }
+        links
+    }
+
+    /// Points the given link to the given target, until it is set back to automatic
+    pub fn select(&mut self, link: &Path, target: &Path) -> Result<(), AlternativesError> {
+        let alternative = self
+            .links
+            .get_mut(link)
+            .ok_or_else(|| AlternativesErrorKind::UnknownLink(link.to_path_buf()))?;
+
+        if !alternative
+            .candidates
+            .iter()
+            .any(|candidate| candidate.target == target)
+        {
+            return Err(AlternativesErrorKind::UnknownTarget(
+                link.to_path_buf(),
+                target.to_path_buf(),
+            )
+            .into());
+        }
+        alternative.selected = Some(target.to_path_buf());
+        Ok(())
+    }
+
+    /// Points the given link to the target with the highest priority again
+    pub fn set_auto(&mut self, link: &Path) -> Result<(), AlternativesError> {
+        let alternative = self
+            .links
+            .get_mut(link)
+            .ok_or_else(|| AlternativesErrorKind::UnknownLink(link.to_path_buf()))?;
+
+        alternative.selected = None;
+        Ok(())
+    }
+
+    /// Repairs the given link once packages were registered and unregistered: forgets the target selected by the
+    /// user if no installed package offers it anymore, and the link itself if none offers a target for it.
+    ///
+    /// Returns whether the selected target was forgotten.
+    pub fn repair(&mut self, link: &Path) -> bool {
+        let alternative = match self.links.get_mut(link) {
+            Some(alternative) => alternative,
+            None => return false,
+        };
+
+        let forgotten = match &alternative.selected {
+            Some(selected) => !alternative
+                .candidates
+                .iter()
+                .any(|candidate| candidate.target == *selected),
+            None => false,
+        };
+        if forgotten {
+            alternative.selected = None;
+        }
+        if alternative.candidates.is_empty() {
+            self.links.remove(link);
+        }
+        forgotten
+    }
+
+    /// Points the given link to its current target in the given installation root, or removes it if no installed
+    /// package offers a target for it anymore.
+    ///
+    /// A file that isn't a symlink is never replaced nor removed.
+    pub fn apply(&self, link: &Path, root: &Path) -> Result<(), AlternativesError> {
+        let path = root.with_content(link);
+        let target = self
+            .link(link)
+            .and_then(AlternativeLink::current)
+            .map(AlternativeCandidate::target);
+
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if !metadata.file_type().is_symlink() {
+                return Err(AlternativesErrorKind::NotASymlink(link.to_path_buf()).into());
+            }
+            if fs::read_link(&path).ok().as_ref().map(PathBuf::as_path) == target {
+                return Ok(());
+            }
+            fs::remove_file(&path)
+                .context(AlternativesErrorKind::LinkUpdateError(link.to_path_buf()))?;
+        }
+
+        if let Some(target) = target {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .context(AlternativesErrorKind::LinkUpdateError(link.to_path_buf()))?;
+            }
+            unix::fs::symlink(target, &path)
+                .context(AlternativesErrorKind::LinkUpdateError(link.to_path_buf()))?;
+        }
+        Ok(())
+    }
+}
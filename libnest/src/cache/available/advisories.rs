@@ -0,0 +1,239 @@
+//! Storage of the security advisories published by the repositories.
+//!
+//! The security advisories of a repository's packages are optional: they are pulled by `nest audit`, and stored
+//! in `<repository>.advisories`, next to the index of the repository. They tell which versions of a package are
+//! affected by a known vulnerability, and which version fixes it.
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use failure::{Error, ResultExt};
+use semver::{Version, VersionReq};
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::cache::errors::CacheErrorKind;
+use crate::package::{CategoryName, PackageID, PackageName};
+use crate::repository::Repository;
+
+use super::AvailablePackages;
+
+/// The extension of the security advisories of the repositories
+static REPOSITORY_ADVISORIES_EXTENSION: &str = "advisories";
+
+/// The severity of a vulnerability
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The vulnerability has a low impact
+    Low,
+
+    /// The vulnerability has a moderate impact
+    Medium,
+
+    /// The vulnerability has a high impact
+    High,
+
+    /// The vulnerability has a critical impact, and should be fixed immediately
+    Critical,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+            Severity::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+/// A security advisory published by a repository, describing a vulnerability affecting some versions of one of
+/// its packages
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct Advisory {
+    id: String,
+    category: CategoryName,
+    name: PackageName,
+    affected: VersionReq,
+    #[serde(default)]
+    fixed: Option<Version>,
+    #[serde(default)]
+    severity: Option<Severity>,
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+impl Advisory {
+    /// Returns the identifier of the advisory, usually a CVE identifier like `CVE-2019-1543`
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the category of the affected package
+    #[inline]
+    pub fn category(&self) -> &CategoryName {
+        &self.category
+    }
+
+    /// Returns the name of the affected package
+    #[inline]
+    pub fn name(&self) -> &PackageName {
+        &self.name
+    }
+
+    /// Returns the requirement matching the affected versions of the package
+    #[inline]
+    pub fn affected(&self) -> &VersionReq {
+        &self.affected
+    }
+
+    /// Returns the first version of the package fixing the vulnerability, if there is one yet
+    #[inline]
+    pub fn fixed(&self) -> Option<&Version> {
+        self.fixed.as_ref()
+    }
+
+    /// Returns the severity of the vulnerability, if it is known
+    #[inline]
+    pub fn severity(&self) -> Option<Severity> {
+        self.severity
+    }
+
+    /// Returns a short description of the vulnerability
+    #[inline]
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+
+    /// Returns the URL of a detailed description of the vulnerability, if any
+    #[inline]
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_ref().map(String::as_str)
+    }
+
+    /// Returns whether the given package, of the repository that published this advisory, is affected
+    pub fn affects(&self, id: &PackageID) -> bool {
+        *id.category() == self.category
+            && *id.name() == self.name
+            && self.affected.matches(id.version())
+    }
+}
+
+/// An installed package affected by a security advisory
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Vulnerability {
+    package: PackageID,
+    advisory: Advisory,
+}
+
+impl Vulnerability {
+    /// Returns the [`PackageID`] of the affected package
+    #[inline]
+    pub fn package(&self) -> &PackageID {
+        &self.package
+    }
+
+    /// Returns the advisory affecting the package
+    #[inline]
+    pub fn advisory(&self) -> &Advisory {
+        &self.advisory
+    }
+}
+
+/// Returns the path of the security advisories of the given repository
+fn advisories_path(cache_root: &Path, repository: &str) -> PathBuf {
+    cache_root.join(format!(
+        "{}.{}",
+        repository, REPOSITORY_ADVISORIES_EXTENSION
+    ))
+}
+
+impl<'cache_root, 'lock_file> AvailablePackages<'cache_root, 'lock_file> {
+    /// Replaces the security advisories of the given [`Repository`] with the given ones, as pulled: a JSON array
+    /// of advisories
+    pub fn update_repository_advisories(
+        &self,
+        repository: &Repository,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let path = advisories_path(self.cache_root, repository.name());
+
+        serde_json::from_slice::<Vec<Advisory>>(data)
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheParseError)?;
+
+        let res: Result<_, Error> = try {
+            fs::create_dir_all(self.cache_root)?;
+            fs::write(&path, data)?;
+        };
+        Ok(res
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheWriteError)?)
+    }
+
+    /// Returns whether the security advisories of the repository with the given name were pulled
+    pub fn has_advisories(&self, repository: &str) -> bool {
+        advisories_path(self.cache_root, repository).exists()
+    }
+
+    /// Returns the security advisories of the repository with the given name, which are empty if they were never
+    /// pulled
+    pub fn repository_advisories(&self, repository: &str) -> Result<Vec<Advisory>, Error> {
+        let path = advisories_path(self.cache_root, repository);
+
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = File::open(&path)
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheLoadError)?;
+        Ok(serde_json::from_reader(BufReader::new(file))
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheParseError)?)
+    }
+
+    /// Returns the vulnerabilities affecting the given packages, according to the pulled security advisories of
+    /// their repository, sorted by package
+    pub fn vulnerabilities(&self, packages: &[PackageID]) -> Result<Vec<Vulnerability>, Error> {
+        let mut packages = packages.to_vec();
+        packages.sort();
+
+        let mut vulnerabilities = Vec::new();
+        let mut advisories: Option<(String, Vec<Advisory>)> = None;
+
+        for package in packages {
+            // Packages are sorted by repository first, each repository's advisories are loaded once
+            let repository = package.repository().as_str();
+            if advisories
+                .as_ref()
+                .map_or(true, |(name, _)| name != repository)
+            {
+                advisories = Some((
+                    repository.to_string(),
+                    self.repository_advisories(repository)?,
+                ));
+            }
+
+            if let Some((_, advisories)) = &advisories {
+                for advisory in advisories
+                    .iter()
+                    .filter(|advisory| advisory.affects(&package))
+                {
+                    vulnerabilities.push(Vulnerability {
+                        package: package.clone(),
+                        advisory: advisory.clone(),
+                    });
+                }
+            }
+        }
+        Ok(vulnerabilities)
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::vec;
+
+use failure::Error;
+
+use crate::config::RepositoryPreferences;
+use crate::package::{CategoryName, PackageManifest, RepositoryName};
+
+use super::store::RepositoryIndex;
+
+/// Iterator over the packages of the cache of available packages, narrowed by repository, category or name prefix.
+///
+/// Repositories are loaded one at a time, when the packages of the previous one were all returned, so that browsing
+/// a single category or repository doesn't require loading the whole cache. Without a repository to browse, the
+/// repositories are gone through by name, disabled ones excepted; the packages of each one are returned sorted by
+/// category then name.
+///
+/// Failing to load a repository is returned as an error, after which the iteration stops.
+#[derive(Clone, Debug)]
+pub struct AvailablePackagesIter<'cache_root> {
+    cache_root: &'cache_root Path,
+    preferences: RepositoryPreferences<'cache_root>,
+    repository: Option<RepositoryName>,
+    category: Option<CategoryName>,
+    prefix: String,
+    repositories: Option<vec::IntoIter<RepositoryName>>,
+    pending: VecDeque<PackageManifest>,
+    failed: bool,
+}
+
+impl<'cache_root> AvailablePackagesIter<'cache_root> {
+    pub(crate) fn from(
+        cache_root: &'cache_root Path,
+        preferences: RepositoryPreferences<'cache_root>,
+    ) -> Self {
+        AvailablePackagesIter {
+            cache_root,
+            preferences,
+            repository: None,
+            category: None,
+            prefix: String::new(),
+            repositories: None,
+            pending: VecDeque::new(),
+            failed: false,
+        }
+    }
+
+    /// Only returns the packages of the given repository, even if it is disabled
+    #[inline]
+    pub fn set_repository(mut self, repository: RepositoryName) -> Self {
+        self.repository = Some(repository);
+        self
+    }
+
+    /// Only returns the packages of the given category
+    #[inline]
+    pub fn set_category(mut self, category: CategoryName) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    /// Only returns the packages whose name starts with the given prefix
+    #[inline]
+    pub fn set_name_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Returns the repositories left to browse, listing them the first time
+    fn repositories(&mut self) -> Result<&mut vec::IntoIter<RepositoryName>, Error> {
+        if self.repositories.is_none() {
+            let repositories = match &self.repository {
+                Some(repository) => vec![repository.clone()],
+                None => RepositoryIndex::repositories(self.cache_root)?
+                    .into_iter()
+                    .filter(|repository| self.preferences.is_enabled(repository))
+                    .collect(),
+            };
+            self.repositories = Some(repositories.into_iter());
+        }
+        Ok(self.repositories.as_mut().unwrap())
+    }
+
+    /// Loads the packages kept of the next repository, returning whether there was one
+    fn load_next_repository(&mut self) -> Result<bool, Error> {
+        let repository = match self.repositories()?.next() {
+            Some(repository) => repository,
+            None => return Ok(false),
+        };
+        let index = RepositoryIndex::load(self.cache_root, repository.as_str())?;
+        let category = self.category.as_ref().map(|category| category.as_str());
+
+        for package in index.packages_with_prefix(category, &self.prefix) {
+            self.pending.push_back((*package?).clone());
+        }
+        Ok(true)
+    }
+}
+
+impl<'cache_root> Iterator for AvailablePackagesIter<'cache_root> {
+    type Item = Result<PackageManifest, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.failed {
+            match self.load_next_repository() {
+                Ok(true) => (),
+                Ok(false) => return None,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
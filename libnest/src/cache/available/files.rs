@@ -0,0 +1,162 @@
+//! Storage of the file lists published by the repositories.
+//!
+//! The file lists of a repository's packages are optional: they are only pulled on demand, and stored in
+//! `<repository>.files`, next to the index of the repository. They tell which available package provides a
+//! given file, without having to download it.
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use failure::{Error, ResultExt};
+use semver::Version;
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::cache::errors::CacheErrorKind;
+use crate::cache::installed::owners::{path_pattern, PATH_MATCH_OPTIONS};
+use crate::package::{CategoryName, PackageID, PackageName};
+use crate::repository::Repository;
+
+use super::AvailablePackages;
+
+/// The extension of the file lists of the repositories
+static REPOSITORY_FILES_EXTENSION: &str = "files";
+
+/// The files shipped by a version of a package, as published by its repository
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct PackageFileList {
+    category: CategoryName,
+    name: PackageName,
+    version: Version,
+    files: Vec<PathBuf>,
+}
+
+impl PackageFileList {
+    /// Returns the category of the package
+    #[inline]
+    pub fn category(&self) -> &CategoryName {
+        &self.category
+    }
+
+    /// Returns the name of the package
+    #[inline]
+    pub fn name(&self) -> &PackageName {
+        &self.name
+    }
+
+    /// Returns the version of the package
+    #[inline]
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Returns the absolute paths, relative to the installation root, of the files shipped by the package
+    #[inline]
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+/// A file shipped by an available package, according to the file lists of its repository
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct ProvidedFile {
+    path: PathBuf,
+    package: PackageID,
+}
+
+impl ProvidedFile {
+    /// Returns the absolute path of the file, relative to the installation root
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the [`PackageID`] of the package providing the file
+    #[inline]
+    pub fn package(&self) -> &PackageID {
+        &self.package
+    }
+}
+
+/// Returns the path of the file lists of the given repository
+fn file_lists_path(cache_root: &Path, repository: &str) -> PathBuf {
+    cache_root.join(format!("{}.{}", repository, REPOSITORY_FILES_EXTENSION))
+}
+
+impl<'cache_root, 'lock_file> AvailablePackages<'cache_root, 'lock_file> {
+    /// Replaces the file lists of the given [`Repository`] with the given ones, as pulled: a JSON array of
+    /// the file lists of its packages
+    pub fn update_repository_file_lists(
+        &self,
+        repository: &Repository,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let path = file_lists_path(self.cache_root, repository.name());
+
+        serde_json::from_slice::<Vec<PackageFileList>>(data)
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheParseError)?;
+
+        let res: Result<_, Error> = try {
+            fs::create_dir_all(self.cache_root)?;
+            fs::write(&path, data)?;
+        };
+        Ok(res
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheWriteError)?)
+    }
+
+    /// Returns whether the file lists of the repository with the given name were pulled
+    pub fn has_file_lists(&self, repository: &str) -> bool {
+        file_lists_path(self.cache_root, repository).exists()
+    }
+
+    /// Returns the file lists of the packages of the repository with the given name, which are empty if
+    /// they were never pulled
+    pub fn repository_file_lists(&self, repository: &str) -> Result<Vec<PackageFileList>, Error> {
+        let path = file_lists_path(self.cache_root, repository);
+
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = File::open(&path)
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheLoadError)?;
+        Ok(serde_json::from_reader(BufReader::new(file))
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheParseError)?)
+    }
+
+    /// Returns the files matching the given pattern shipped by the packages of the pulled file lists, along with
+    /// the package providing them, sorted by path.
+    ///
+    /// The pattern follows the same rules as the one given to
+    /// [`InstalledPackages::owners`][crate::cache::installed::InstalledPackages::owners].
+    pub fn file_providers(&self, pattern: &str) -> Result<Vec<ProvidedFile>, Error> {
+        let pattern = path_pattern(pattern)?;
+
+        let mut provided_files = Vec::new();
+        for repository in self.repositories()? {
+            for list in self.repository_file_lists(repository.as_str())? {
+                for path in list.files() {
+                    if pattern.matches_path_with(path, PATH_MATCH_OPTIONS) {
+                        provided_files.push(ProvidedFile {
+                            path: path.clone(),
+                            package: PackageID::from(
+                                repository.clone(),
+                                list.category.clone(),
+                                list.name.clone(),
+                                list.version.clone(),
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
+        provided_files.sort();
+        Ok(provided_files)
+    }
+}
@@ -0,0 +1,33 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::cache::depgraph::GroupName;
+use crate::package::PackageRequirement;
+
+/// A group of packages defined by a repository, like `@base` or `@devel`, that can be installed as a unit
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RepositoryGroup {
+    name: GroupName,
+    #[serde(default)]
+    description: String,
+    packages: Vec<PackageRequirement>,
+}
+
+impl RepositoryGroup {
+    /// Returns the name of the group
+    #[inline]
+    pub fn name(&self) -> &GroupName {
+        &self.name
+    }
+
+    /// Returns the description of the group
+    #[inline]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns the requirements on the packages making up the group
+    #[inline]
+    pub fn packages(&self) -> &[PackageRequirement] {
+        &self.packages
+    }
+}
@@ -0,0 +1,169 @@
+//! Inverted index of the words found in the names, categories and descriptions of the packages of a repository.
+//!
+//! The index is built when a repository is pulled and stored in the repository's index file, so that searching
+//! the available packages for words doesn't require matching all of them.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::package::{CategoryName, PackageManifest, PackageName};
+
+use super::search::SearchField;
+
+/// A package referenced by the index
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct IndexedPackage {
+    category: CategoryName,
+    name: PackageName,
+}
+
+impl IndexedPackage {
+    /// Returns the category of the package
+    #[inline]
+    pub fn category(&self) -> &CategoryName {
+        &self.category
+    }
+
+    /// Returns the name of the package
+    #[inline]
+    pub fn name(&self) -> &PackageName {
+        &self.name
+    }
+}
+
+/// An occurrence of a word in a field of a package
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+struct Posting {
+    package: usize,
+    field: SearchField,
+}
+
+/// An inverted index mapping words to the packages of a repository containing them
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug, Default)]
+pub struct SearchIndex {
+    packages: Vec<IndexedPackage>,
+    words: BTreeMap<String, Vec<Posting>>,
+}
+
+/// Splits a text into lowercase words, made of alphanumeric characters
+pub(crate) fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+impl SearchIndex {
+    /// Builds the index of the given packages
+    pub fn build<'a, I: IntoIterator<Item = &'a PackageManifest>>(manifests: I) -> Self {
+        let mut index = SearchIndex::default();
+
+        for manifest in manifests {
+            let package = index.packages.len();
+            index.packages.push(IndexedPackage {
+                category: manifest.category().clone(),
+                name: manifest.name().clone(),
+            });
+
+            let fields = [
+                (SearchField::Name, manifest.name().as_str()),
+                (SearchField::Category, manifest.category().as_str()),
+                (SearchField::Description, manifest.metadata().description()),
+            ];
+            for (field, text) in fields.iter() {
+                for word in tokenize(text) {
+                    let postings = index.words.entry(word).or_insert_with(Vec::new);
+                    let posting = Posting {
+                        package,
+                        field: *field,
+                    };
+
+                    if !postings.contains(&posting) {
+                        postings.push(posting);
+                    }
+                }
+            }
+        }
+        index
+    }
+
+    /// Returns the packages referenced by the index
+    #[inline]
+    pub fn packages(&self) -> &[IndexedPackage] {
+        &self.packages
+    }
+
+    /// Looks for the packages containing all the words of the given text in the given fields, and returns them
+    /// along with their score. The last word may be incomplete: it matches all the words starting with it.
+    ///
+    /// The score of a package is the sum of the weights of the fields each word was found in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate serde_json;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::cache::available::{SearchField, SearchIndex};
+    /// use libnest::package::PackageManifest;
+    ///
+    /// let manifest: PackageManifest = serde_json::from_str(r#"{
+    ///     "name": "coreutils",
+    ///     "category": "sys-bin",
+    ///     "repository": "stable",
+    ///     "metadata": {
+    ///         "description": "The basic file, shell and text manipulation utilities",
+    ///         "tags": [],
+    ///         "maintainer": "nest@raven-os.org",
+    ///         "licenses": [],
+    ///         "upstream_url": null
+    ///     },
+    ///     "versions": {}
+    /// }"#)?;
+    /// let index = SearchIndex::build(vec![&manifest]);
+    /// let fields = [SearchField::Name, SearchField::Category, SearchField::Description];
+    ///
+    /// assert_eq!(index.query("shell util", &fields).len(), 1);
+    /// assert_eq!(index.query("shell kernel", &fields).len(), 0);
+    /// assert_eq!(index.query("shell", &[SearchField::Name]).len(), 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn query(&self, text: &str, fields: &[SearchField]) -> Vec<(&IndexedPackage, u32)> {
+        let words: Vec<_> = tokenize(text).collect();
+        let mut scores: HashMap<usize, (usize, u32)> = HashMap::new();
+
+        for (i, word) in words.iter().enumerate() {
+            let mut word_scores: HashMap<usize, u32> = HashMap::new();
+
+            let postings: Box<dyn Iterator<Item = &Posting>> = if i + 1 == words.len() {
+                Box::new(
+                    self.words
+                        .range(word.clone()..)
+                        .take_while(|(indexed, _)| indexed.starts_with(word.as_str()))
+                        .flat_map(|(_, postings)| postings.iter()),
+                )
+            } else {
+                Box::new(self.words.get(word).into_iter().flatten())
+            };
+
+            for posting in postings.filter(|posting| fields.contains(&posting.field)) {
+                let score = word_scores.entry(posting.package).or_insert(0);
+                *score = (*score).max(posting.field.weight());
+            }
+
+            for (package, score) in word_scores {
+                let entry = scores.entry(package).or_insert((0, 0));
+                entry.0 += 1;
+                entry.1 += score;
+            }
+        }
+
+        scores
+            .into_iter()
+            .filter(|(_, (matched_words, _))| *matched_words == words.len())
+            .map(|(package, (_, score))| (&self.packages[package], score))
+            .collect()
+    }
+}
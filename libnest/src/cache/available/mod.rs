@@ -1,40 +1,66 @@
 //! Module to query and manipulate the cache of available packages
 //! This cache is populated and updated by pull operations.
 
+mod advisories;
+mod browse;
+mod files;
+mod group;
+mod index;
 mod query;
+mod search;
+mod store;
 
+pub use self::advisories::{Advisory, Severity, Vulnerability};
+pub use self::browse::AvailablePackagesIter;
+pub use self::files::{PackageFileList, ProvidedFile};
+pub use self::group::RepositoryGroup;
+pub use self::index::{IndexedPackage, SearchIndex};
 pub use self::query::{
     AvailablePackagesCacheQuery, AvailablePackagesCacheQueryStrategy, QueryResult,
 };
+pub use self::search::{AvailablePackagesSearch, SearchField, SearchMode, SearchResult};
+pub use self::store::{RepositoryChange, RepositoryChanges, RepositoryDiff, RepositoryIndex};
 
 use super::errors::*;
 
-use std::fs::{self, File};
-use std::io::Write;
+use std::cmp::Ordering;
+use std::fs;
 use std::marker::PhantomData;
 use std::path::Path;
+use std::sync::Arc;
 
 use failure::{Error, ResultExt};
-use serde_json;
+use url::Url;
 
+use crate::cache::depgraph::GroupName;
+use crate::config::{ArchitecturePreferences, RepositoryPreferences};
 use crate::lock_file::LockFileOwnership;
-use crate::package::{PackageManifest, SoftPackageRequirement};
-use crate::repository::Repository;
+use crate::package::{
+    CategoryName, ChangelogEntry, Checksum, Manifest, PackageID, PackageManifest, PackageShortName,
+    RepositoryName, SegmentChecksums, SoftPackageRequirement,
+};
+use crate::repository::{Repository, LOCAL_REPOSITORY};
 
 /// Structure representing the cache of available packages
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct AvailablePackages<'cache_root, 'lock_file> {
     cache_root: &'cache_root Path,
+    preferences: RepositoryPreferences<'cache_root>,
+    architectures: ArchitecturePreferences<'cache_root>,
     phantom: PhantomData<&'lock_file LockFileOwnership>,
 }
 
 impl<'cache_root, 'lock_file> AvailablePackages<'cache_root, 'lock_file> {
     pub(crate) fn from(
         cache_root: &'cache_root Path,
+        preferences: RepositoryPreferences<'cache_root>,
+        architectures: ArchitecturePreferences<'cache_root>,
         phantom: PhantomData<&'lock_file LockFileOwnership>,
     ) -> Self {
         AvailablePackages {
             cache_root,
+            preferences,
+            architectures,
             phantom,
         }
     }
@@ -51,36 +77,263 @@ impl<'cache_root, 'lock_file> AvailablePackages<'cache_root, 'lock_file> {
 
     /// Erases a given [`Repository`] from the cache
     pub fn erase_repository(&self, repository: &Repository) -> Result<(), Error> {
-        let path = self.cache_root.join(repository.name());
+        let path = RepositoryIndex::path(self.cache_root, repository.name());
 
         if path.exists() {
-            fs::remove_dir_all(&path)
+            fs::remove_file(&path)
                 .context(path.display().to_string())
                 .context(CacheErrorKind::CacheClearError)?;
         }
+
+        // Caches made before repositories were stored in a single file hold one file per package
+        let legacy_path = self.cache_root.join(repository.name());
+        if legacy_path.is_dir() {
+            fs::remove_dir_all(&legacy_path)
+                .context(legacy_path.display().to_string())
+                .context(CacheErrorKind::CacheClearError)?;
+        }
         Ok(())
     }
 
-    /// Creates or updates the cache entry for a given [`Package`]
-    pub fn update(&self, package: &PackageManifest) -> Result<(), Error> {
-        let cache_path = self
-            .cache_root
-            .join(package.repository().as_str())
-            .join(package.category().as_str())
-            .join(package.name().as_str());
+    /// Returns the names of the repositories in the cache, which are those that were pulled
+    pub fn repositories(&self) -> Result<Vec<RepositoryName>, Error> {
+        RepositoryIndex::repositories(self.cache_root)
+    }
 
-        let res: Result<_, Error> = try {
-            if let Some(parent) = cache_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
+    /// Returns the names of the repositories in the cache whose packages are considered, disabled ones excepted
+    fn enabled_repositories(&self) -> Result<Vec<RepositoryName>, Error> {
+        Ok(self
+            .repositories()?
+            .into_iter()
+            .filter(|repository| self.preferences.is_enabled(repository))
+            .collect())
+    }
+
+    /// Returns the index holding the packages of the repository with the given name, which is empty if the
+    /// repository was never pulled
+    pub fn repository_index(&self, repository: &str) -> Result<Arc<RepositoryIndex>, Error> {
+        RepositoryIndex::load(self.cache_root, repository)
+    }
 
-            let mut file = File::create(&cache_path)?;
-            file.write_all(serde_json::to_string_pretty(package)?.as_bytes())?;
-            file.write_all(&[b'\n'])?;
+    /// Returns an [`AvailablePackagesIter`] going through all the packages of the cache, disabled repositories
+    /// excepted, loading the repositories one at a time
+    #[inline]
+    pub fn packages(&self) -> AvailablePackagesIter<'cache_root> {
+        AvailablePackagesIter::from(self.cache_root, self.preferences)
+    }
+
+    /// Returns an [`AvailablePackagesIter`] going through the packages of the given repository, without loading
+    /// the other ones
+    #[inline]
+    pub fn packages_of_repository(
+        &self,
+        repository: RepositoryName,
+    ) -> AvailablePackagesIter<'cache_root> {
+        self.packages().set_repository(repository)
+    }
+
+    /// Returns an [`AvailablePackagesIter`] going through the packages of the given category, in each repository
+    #[inline]
+    pub fn packages_of_category(
+        &self,
+        category: CategoryName,
+    ) -> AvailablePackagesIter<'cache_root> {
+        self.packages().set_category(category)
+    }
+
+    /// Returns an [`AvailablePackagesIter`] going through the packages whose name starts with the given prefix, in
+    /// each repository
+    #[inline]
+    pub fn packages_with_prefix(&self, prefix: &str) -> AvailablePackagesIter<'cache_root> {
+        self.packages().set_name_prefix(prefix)
+    }
+
+    /// Replaces the cache entry of the given [`Repository`] with the given packages and groups, which must be all
+    /// those of the repository, as of the given serial number of the repository's journal, if it is known
+    pub fn update_repository(
+        &self,
+        repository: &Repository,
+        packages: &[PackageManifest],
+        groups: &[RepositoryGroup],
+        serial: Option<u64>,
+    ) -> Result<(), Error> {
+        RepositoryIndex::build(packages, serial)
+            .with_groups(groups)
+            .save(self.cache_root, repository.name())
+    }
+
+    /// Adds the given package, whose NPF has the given checksum, to the cache entry of the [`LOCAL_REPOSITORY`],
+    /// replacing the version of the package it may already hold
+    pub(crate) fn add_local_package(
+        &self,
+        manifest: &Manifest,
+        checksum: Checksum,
+    ) -> Result<(), Error> {
+        let index = RepositoryIndex::load(self.cache_root, LOCAL_REPOSITORY)?;
+        let mut packages = index
+            .packages()
+            .map(|package| package.map(|package| (*package).clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let groups: Vec<RepositoryGroup> = index.groups().cloned().collect();
+
+        let mut version_data = manifest.version_data();
+        *version_data.checksum_mut() = Some(checksum);
+
+        let position = packages.iter().position(|package| {
+            package.category() == manifest.category() && package.name() == manifest.name()
+        });
+        let package = match position {
+            Some(position) => &mut packages[position],
+            None => {
+                packages.push(PackageManifest::new(
+                    manifest.name().clone(),
+                    manifest.category().clone(),
+                    RepositoryName::parse(LOCAL_REPOSITORY)?,
+                    manifest.metadata().clone(),
+                ));
+                packages.last_mut().unwrap()
+            }
         };
-        res.context(cache_path.display().to_string())
-            .context(CacheErrorKind::CacheWriteError)?;
-        Ok(())
+        *package.metadata_mut() = manifest.metadata().clone();
+        package
+            .versions_mut()
+            .insert(manifest.version().clone(), version_data);
+
+        RepositoryIndex::build(&packages, None)
+            .with_groups(&groups)
+            .save(self.cache_root, LOCAL_REPOSITORY)
+    }
+
+    /// Returns the serial number of the journal of the given [`Repository`] the cache is up to date with.
+    ///
+    /// [`None`] is returned if the repository was never pulled, or if it was pulled without knowing its serial number.
+    pub fn repository_serial(&self, repository: &Repository) -> Result<Option<u64>, Error> {
+        Ok(RepositoryIndex::load(self.cache_root, repository.name())?.serial())
+    }
+
+    /// Applies the given changes to the cache entry of the given [`Repository`].
+    ///
+    /// The repository must have been pulled before, and the changes must have been made since the serial number
+    /// the cache is up to date with.
+    pub fn apply_repository_changes(
+        &self,
+        repository: &Repository,
+        changes: &RepositoryChanges,
+    ) -> Result<(), Error> {
+        let mut index = (*RepositoryIndex::load(self.cache_root, repository.name())?).clone();
+
+        index.apply(changes)?;
+        index.save(self.cache_root, repository.name())
+    }
+
+    /// Returns the checksum advertised by the repository for the NPF of the given package.
+    ///
+    /// [`None`] is returned if the package is not in the cache, or if its checksum is unknown.
+    pub fn package_checksum(&self, package: &PackageID) -> Result<Option<Checksum>, Error> {
+        let index = RepositoryIndex::load(self.cache_root, package.repository().as_str())?;
+
+        Ok(index
+            .package(package.category().as_str(), package.name().as_str())?
+            .as_ref()
+            .and_then(|manifest| manifest.versions().get(package.version()))
+            .and_then(|version_data| *version_data.checksum()))
+    }
+
+    /// Returns the magnet URI of the torrent the NPF of the given package can be downloaded with.
+    ///
+    /// [`None`] is returned if the package is not in the cache, or if its repository doesn't seed it.
+    pub fn package_torrent(&self, package: &PackageID) -> Result<Option<Url>, Error> {
+        let index = RepositoryIndex::load(self.cache_root, package.repository().as_str())?;
+
+        Ok(index
+            .package(package.category().as_str(), package.name().as_str())?
+            .as_ref()
+            .and_then(|manifest| manifest.versions().get(package.version()))
+            .and_then(|version_data| version_data.torrent().cloned()))
+    }
+
+    /// Returns the checksums of the segments of the NPF of the given package, letting them be downloaded from
+    /// several mirrors at once.
+    ///
+    /// [`None`] is returned if the package is not in the cache, or if its repository doesn't advertise them.
+    pub fn package_segments(&self, package: &PackageID) -> Result<Option<SegmentChecksums>, Error> {
+        let index = RepositoryIndex::load(self.cache_root, package.repository().as_str())?;
+
+        Ok(index
+            .package(package.category().as_str(), package.name().as_str())?
+            .as_ref()
+            .and_then(|manifest| manifest.versions().get(package.version()))
+            .and_then(|version_data| version_data.segments().cloned()))
+    }
+
+    /// Returns the most recent entries of the changelog of the given package, as indexed by its repository.
+    ///
+    /// They are empty if the package is not in the cache, or if its repository doesn't index its changelog.
+    pub fn package_changelog(&self, package: &PackageID) -> Result<Vec<ChangelogEntry>, Error> {
+        let index = RepositoryIndex::load(self.cache_root, package.repository().as_str())?;
+
+        Ok(index
+            .package(package.category().as_str(), package.name().as_str())?
+            .as_ref()
+            .and_then(|manifest| manifest.versions().get(package.version()))
+            .map(|version_data| version_data.changelog().to_vec())
+            .unwrap_or_default())
+    }
+
+    /// Returns the packages providing the given virtual package, in their most recent version providing it that can
+    /// be installed on this system, sorted by full name
+    pub fn providers(&self, virtual_name: &PackageShortName) -> Result<Vec<QueryResult>, Error> {
+        let mut providers = Vec::new();
+
+        for repository in self.enabled_repositories()? {
+            let index = RepositoryIndex::load(self.cache_root, repository.as_str())?;
+
+            for package in index.packages() {
+                let package = package?;
+                let version = package
+                    .versions()
+                    .iter()
+                    .filter(|(_, version_data)| {
+                        version_data.provides().contains(virtual_name)
+                            && self
+                                .architectures
+                                .is_compatible(version_data.architecture())
+                    })
+                    .map(|(version, _)| version)
+                    .max();
+
+                if let Some(manifest) =
+                    version.and_then(|version| package.get_manifest_for_version(version.clone()))
+                {
+                    providers.push(QueryResult::from(repository.clone(), manifest));
+                }
+            }
+        }
+
+        providers.sort_by_key(|provider| provider.full_name().to_string());
+        Ok(providers)
+    }
+
+    /// Returns the group with the given name, as defined by the preferred repository defining it.
+    ///
+    /// [`None`] is returned if no repository defines the group.
+    pub fn group(&self, name: &GroupName) -> Result<Option<RepositoryGroup>, Error> {
+        let mut found: Option<(RepositoryName, RepositoryGroup)> = None;
+
+        for repository in self.enabled_repositories()? {
+            let index = RepositoryIndex::load(self.cache_root, repository.as_str())?;
+
+            if let Some(group) = index.group(name) {
+                let is_preferred = found.as_ref().map_or(true, |(other, _)| {
+                    self.preferences.compare_repositories(&repository, other) == Ordering::Less
+                });
+
+                if is_preferred {
+                    found = Some((repository, group.clone()));
+                }
+            }
+        }
+        Ok(found.map(|(_, group)| group))
     }
 
     /// Returns an [`AvailablePackagesCacheQuery`] allowing to browse the cache according to the given [`PackageRequirement`]
@@ -89,6 +342,21 @@ impl<'cache_root, 'lock_file> AvailablePackages<'cache_root, 'lock_file> {
         &self,
         requirement: &'pkg_req SoftPackageRequirement,
     ) -> AvailablePackagesCacheQuery<'cache_root, 'pkg_req> {
-        AvailablePackagesCacheQuery::from(&self.cache_root, requirement)
+        AvailablePackagesCacheQuery::from(
+            &self.cache_root,
+            self.preferences,
+            self.architectures,
+            requirement,
+        )
+    }
+
+    /// Returns an [`AvailablePackagesSearch`] looking for the given pattern in the names, categories and descriptions
+    /// of the packages of the cache
+    #[inline]
+    pub fn search<'pattern>(
+        &self,
+        pattern: &'pattern str,
+    ) -> AvailablePackagesSearch<'cache_root, 'pattern> {
+        AvailablePackagesSearch::from(&self.cache_root, pattern)
     }
 }
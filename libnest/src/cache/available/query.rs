@@ -1,13 +1,14 @@
-use std::fs;
 use std::path::Path;
 
-use failure::{Error, ResultExt};
+use failure::Error;
 
+use crate::config::{ArchitecturePreferences, RepositoryPreferences};
 use crate::package::{
-    CategoryName, Manifest, PackageFullName, PackageID, PackageManifest, RepositoryName,
-    SoftPackageRequirement,
+    Manifest, PackageFullName, PackageID, RepositoryName, SoftPackageRequirement,
 };
 
+use super::store::RepositoryIndex;
+
 /// The result of a query to the packages cache
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct QueryResult {
@@ -58,7 +59,9 @@ impl QueryResult {
 /// The strategy to use when looking for packages in this cache.
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum AvailablePackagesCacheQueryStrategy {
-    /// This strategy can be used to obtain only the most recent version of each package
+    /// This strategy can be used to obtain only the most recent version of each package.
+    ///
+    /// When the package is offered by several repositories, only the one of the preferred repository is kept.
     BestMatch,
 
     /// This strategy can be used to obtain all the packages matching the requirements, unsorted
@@ -73,9 +76,18 @@ pub enum AvailablePackagesCacheQueryStrategy {
 ///
 /// It can be constructed from a [`PackageRequirement`] and a strategy and will look for all
 /// the packages matching the given requirement, following the given strategy.
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+///
+/// When the same package is offered by several repositories, the results of the preferred repository come first
+/// (see [`RepositoryPreferences`]), unless the results are unsorted.
+///
+/// Only the packages built for an architecture that can be installed are considered, and those of a foreign
+/// architecture are only selected when no package of the native architecture matches (see
+/// [`ArchitecturePreferences`]).
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct AvailablePackagesCacheQuery<'a, 'b> {
     cache_root: &'a Path,
+    preferences: RepositoryPreferences<'a>,
+    architectures: ArchitecturePreferences<'a>,
     requirement: &'b SoftPackageRequirement,
     strategy: AvailablePackagesCacheQueryStrategy,
 }
@@ -84,10 +96,14 @@ impl<'a, 'b> AvailablePackagesCacheQuery<'a, 'b> {
     #[inline]
     pub(crate) fn from(
         cache_root: &'a Path,
+        preferences: RepositoryPreferences<'a>,
+        architectures: ArchitecturePreferences<'a>,
         requirement: &'b SoftPackageRequirement,
     ) -> AvailablePackagesCacheQuery<'a, 'b> {
         AvailablePackagesCacheQuery {
             cache_root,
+            preferences,
+            architectures,
             requirement,
             strategy: AvailablePackagesCacheQueryStrategy::BestMatch,
         }
@@ -100,115 +116,93 @@ impl<'a, 'b> AvailablePackagesCacheQuery<'a, 'b> {
         self
     }
 
-    fn get_cache_entries(path: &Path) -> Result<impl Iterator<Item = String>, Error> {
-        let mut results = Vec::new();
-
-        if path.exists() {
-            for entry in fs::read_dir(path).with_context(|_| path.display().to_string())? {
-                let entry = entry.with_context(|_| path.display().to_string())?;
-                if let Ok(name) = entry.file_name().into_string() {
-                    results.push(name);
-                }
-            }
-        }
-        Ok(results.into_iter())
-    }
-
     /// Perform the query
     pub fn perform(&self) -> Result<Vec<QueryResult>, Error> {
         let mut results = Vec::new();
 
-        let repositories = Self::get_cache_entries(&self.cache_root)?
+        let repositories = RepositoryIndex::repositories(&self.cache_root)?
+            .into_iter()
+            .filter(|repo| self.preferences.is_enabled(repo))
             .filter(|repo| match self.requirement.repository() {
-                Some(required_repo) => required_repo.as_str() == repo,
+                Some(required_repo) => required_repo == repo,
                 _ => true,
-            })
-            .map(|name| {
-                RepositoryName::parse(&name).expect("invalid repository name found in the cache")
             });
 
         for repo in repositories {
-            let repo_cache_path = self.cache_root.join(repo.as_str());
+            let index = RepositoryIndex::load(&self.cache_root, repo.as_str())?;
 
-            let categories = Self::get_cache_entries(&repo_cache_path)?
-                .filter(|category| match self.requirement.category() {
-                    Some(required_category) => required_category.as_str() == category,
-                    _ => true,
-                })
-                .map(|name| {
-                    CategoryName::parse(&name).expect("invalid category name found in the cache")
-                });
+            let categories: Vec<_> = match self.requirement.category() {
+                Some(required_category) => vec![required_category.as_str()],
+                _ => index.categories().collect(),
+            };
 
             for category in categories {
-                let category_cache_path = repo_cache_path.join(category.as_str());
-
                 // TODO: at the moment, we match the package name exactly. This should be configurable.
-                let packages = Self::get_cache_entries(&category_cache_path)?
-                    .filter(|package_name| self.requirement.name().as_str() == package_name);
-
-                for package in packages {
-                    let package_cache_path = category_cache_path.join(package);
-                    let package_manifest = PackageManifest::load_from_cache(package_cache_path)?;
-                    let mut versions = package_manifest.versions().keys().collect::<Vec<_>>();
+                if let Some(package_manifest) =
+                    index.package(category, self.requirement.name().as_str())?
+                {
+                    // Versions are ranked by architecture, the most preferred first
+                    let mut versions: Vec<_> = package_manifest
+                        .versions()
+                        .iter()
+                        .filter(|(version, version_data)| {
+                            self.requirement.version_requirement().matches(version)
+                                && self.requirement.matches_slot(version_data.slot())
+                        })
+                        .filter_map(|(version, version_data)| {
+                            self.architectures
+                                .rank(version_data.architecture())
+                                .map(|rank| (rank, version))
+                        })
+                        .collect();
 
                     match self.strategy {
                         AvailablePackagesCacheQueryStrategy::BestMatch => {
-                            versions.sort_unstable_by(|a, b| b.cmp(a));
-                            let result = versions.iter().find(|version| {
-                                self.requirement.version_requirement().matches(version)
+                            versions.sort_unstable_by(|(a_rank, a), (b_rank, b)| {
+                                a_rank.cmp(b_rank).then_with(|| b.cmp(a))
                             });
-                            if let Some(version) = result {
-                                // FIXME: having to ask for a version that we already know exists is meh
-                                results.push(QueryResult::from(
-                                    repo.clone(),
-                                    package_manifest
-                                        .get_manifest_for_version((*version).clone())
-                                        .unwrap(),
-                                ));
-                            }
+                            versions.truncate(1);
                         }
                         AvailablePackagesCacheQueryStrategy::AllMatchesSorted => {
-                            versions.sort_unstable_by(|a, b| b.cmp(a));
-                            results.append(
-                                &mut versions
-                                    .iter()
-                                    .filter(|version| {
-                                        self.requirement.version_requirement().matches(&version)
-                                    })
-                                    .map(|version| {
-                                        QueryResult::from(
-                                            repo.clone(),
-                                            package_manifest
-                                                .get_manifest_for_version((*version).clone())
-                                                .unwrap(),
-                                        )
-                                    })
-                                    .collect::<Vec<_>>(),
-                            );
-                        }
-                        AvailablePackagesCacheQueryStrategy::AllMatchesUnsorted => {
-                            results.append(
-                                &mut versions
-                                    .iter()
-                                    .filter(|version| {
-                                        self.requirement.version_requirement().matches(&version)
-                                    })
-                                    .map(|version| {
-                                        QueryResult::from(
-                                            repo.clone(),
-                                            package_manifest
-                                                .get_manifest_for_version((*version).clone())
-                                                .unwrap(),
-                                        )
-                                    })
-                                    .collect::<Vec<_>>(),
-                            );
+                            versions.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
                         }
+                        AvailablePackagesCacheQueryStrategy::AllMatchesUnsorted => (),
                     }
+
+                    results.extend(versions.into_iter().map(|(_, version)| {
+                        QueryResult::from(
+                            repo.clone(),
+                            package_manifest
+                                .get_manifest_for_version(version.clone())
+                                .unwrap(),
+                        )
+                    }));
                 }
             }
         }
 
+        // Results are sorted by version within each repository, which the stable sort preserves
+        if self.strategy != AvailablePackagesCacheQueryStrategy::AllMatchesUnsorted {
+            results.sort_by(|a, b| {
+                let (a_name, b_name) = (a.manifest().short_name(), b.manifest().short_name());
+
+                a_name
+                    .cmp(&b_name)
+                    .then_with(|| {
+                        let rank = |result: &QueryResult| {
+                            self.architectures.rank(result.manifest().architecture())
+                        };
+                        rank(a).cmp(&rank(b))
+                    })
+                    .then_with(|| {
+                        self.preferences
+                            .compare(&a_name, a.repository(), b.repository())
+                    })
+            });
+        }
+        if self.strategy == AvailablePackagesCacheQueryStrategy::BestMatch {
+            results.dedup_by(|a, b| a.manifest().short_name() == b.manifest().short_name());
+        }
         Ok(results)
     }
 }
@@ -0,0 +1,320 @@
+use std::cmp::Ordering;
+use std::path::Path;
+
+use failure::{Error, ResultExt};
+use regex::{Regex, RegexBuilder};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::cache::errors::CacheErrorKind;
+use crate::package::{PackageManifest, RepositoryName};
+
+use super::store::RepositoryIndex;
+
+/// How the pattern of a search is matched against the fields of the packages
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SearchMode {
+    /// The field must contain the pattern, ignoring case
+    Substring,
+
+    /// The field must match the pattern, a regular expression, ignoring case
+    Regex,
+
+    /// The field must contain all the characters of the pattern in the same order, ignoring case
+    Fuzzy,
+
+    /// The fields must contain all the words of the pattern, the last one being possibly incomplete, ignoring case.
+    ///
+    /// This uses the search index of each repository, and doesn't require loading all the packages.
+    FullText,
+}
+
+/// The fields of the packages a search looks into
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum SearchField {
+    /// The name of the package
+    Name,
+
+    /// The category of the package
+    Category,
+
+    /// The description of the package
+    Description,
+}
+
+impl SearchField {
+    /// Returns how much a match on this field weighs in the score of a package
+    pub(crate) fn weight(self) -> u32 {
+        match self {
+            SearchField::Name => 4,
+            SearchField::Category => 2,
+            SearchField::Description => 1,
+        }
+    }
+}
+
+/// A package found by a search, along with how well it matched the pattern
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SearchResult {
+    package: PackageManifest,
+    score: u32,
+}
+
+impl SearchResult {
+    /// Returns the package that was found, with all its versions
+    #[inline]
+    pub fn package(&self) -> &PackageManifest {
+        &self.package
+    }
+
+    /// Returns how well the package matched the pattern. The higher, the better.
+    #[inline]
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+}
+
+/// A compiled search pattern
+enum Matcher {
+    Substring(String),
+    Regex(Regex),
+    Fuzzy(Vec<char>),
+}
+
+impl Matcher {
+    fn new(pattern: &str, mode: SearchMode) -> Result<Self, Error> {
+        Ok(match mode {
+            SearchMode::Substring => Matcher::Substring(pattern.to_lowercase()),
+            SearchMode::Regex => Matcher::Regex(
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .context(pattern.to_string())
+                    .context(CacheErrorKind::InvalidSearchPattern)?,
+            ),
+            SearchMode::Fuzzy | SearchMode::FullText => {
+                Matcher::Fuzzy(pattern.to_lowercase().chars().collect())
+            }
+        })
+    }
+
+    /// Returns how well the given text matches the pattern, between 1 and 100, or [`None`] if it doesn't match
+    fn score(&self, text: &str) -> Option<u32> {
+        match self {
+            Matcher::Substring(pattern) => {
+                let text = text.to_lowercase();
+
+                if text == *pattern {
+                    Some(100)
+                } else if text.starts_with(pattern.as_str()) {
+                    Some(75)
+                } else if text.contains(pattern.as_str()) {
+                    Some(50)
+                } else {
+                    None
+                }
+            }
+            Matcher::Regex(regex) => regex.find(text).map(|found| {
+                if found.start() == 0 && found.end() == text.len() {
+                    100
+                } else if found.start() == 0 {
+                    75
+                } else {
+                    50
+                }
+            }),
+            Matcher::Fuzzy(pattern) => fuzzy_score(pattern, &text.to_lowercase()),
+        }
+    }
+}
+
+/// Scores a fuzzy match of the given pattern in the given text: the closer the matched characters
+/// are from each other and from the start of the text, the higher the score.
+fn fuzzy_score(pattern: &[char], text: &str) -> Option<u32> {
+    if pattern.is_empty() {
+        return Some(1);
+    }
+
+    let mut chars = pattern.iter().peekable();
+    let mut first = None;
+    let mut last = 0;
+
+    for (i, c) in text.chars().enumerate() {
+        if chars.peek() == Some(&&c) {
+            chars.next();
+            first.get_or_insert(i);
+            last = i;
+            if chars.peek().is_none() {
+                break;
+            }
+        }
+    }
+
+    if chars.peek().is_some() {
+        return None;
+    }
+
+    // Characters of the text between the first and the last matched ones that aren't part of the pattern
+    let gaps = (last - first.unwrap_or(0) + 1 - pattern.len()) as u32;
+    let offset = first.unwrap_or(0) as u32;
+    Some(100u32.saturating_sub(gaps * 5 + offset).max(1))
+}
+
+/// Structure representing a search in the [`AvailablePackages`][1] cache.
+///
+/// Unlike an [`AvailablePackagesCacheQuery`][2], which looks for packages matching a requirement, a search looks
+/// for a pattern in the names, categories and descriptions of the packages. The results are sorted from the best
+/// match to the worst, and then by name.
+///
+/// [1]: struct.AvailablePackages.html
+/// [2]: struct.AvailablePackagesCacheQuery.html
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct AvailablePackagesSearch<'a, 'b> {
+    cache_root: &'a Path,
+    pattern: &'b str,
+    mode: SearchMode,
+    fields: Vec<SearchField>,
+    repository: Option<RepositoryName>,
+}
+
+impl<'a, 'b> AvailablePackagesSearch<'a, 'b> {
+    #[inline]
+    pub(crate) fn from(cache_root: &'a Path, pattern: &'b str) -> AvailablePackagesSearch<'a, 'b> {
+        AvailablePackagesSearch {
+            cache_root,
+            pattern,
+            mode: SearchMode::Substring,
+            fields: vec![
+                SearchField::Name,
+                SearchField::Category,
+                SearchField::Description,
+            ],
+            repository: None,
+        }
+    }
+
+    /// Sets how the pattern is matched. By default, packages containing the pattern are searched for.
+    #[inline]
+    pub fn set_mode(mut self, mode: SearchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the fields the pattern is looked for in. By default, all of them are.
+    #[inline]
+    pub fn set_fields(mut self, fields: Vec<SearchField>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Restricts the search to the packages of the given repository
+    #[inline]
+    pub fn set_repository(mut self, repository: RepositoryName) -> Self {
+        self.repository = Some(repository);
+        self
+    }
+
+    /// Returns how well the given package matches the search, or [`None`] if it doesn't
+    fn score(&self, matcher: &Matcher, package: &PackageManifest) -> Option<u32> {
+        let scores = self.fields.iter().filter_map(|field| {
+            let text = match field {
+                SearchField::Name => package.name().as_str(),
+                SearchField::Category => package.category().as_str(),
+                SearchField::Description => package.metadata().description(),
+            };
+            matcher.score(text).map(|score| score * field.weight())
+        });
+
+        scores.fold(None, |total, score| Some(total.unwrap_or(0) + score))
+    }
+
+    /// Searches the packages of the given repository by matching all of them
+    fn perform_scan(&self, index: &RepositoryIndex) -> Result<Vec<SearchResult>, Error> {
+        let matcher = Matcher::new(self.pattern, self.mode)?;
+
+        let mut results = Vec::new();
+
+        for package in index.packages() {
+            let package = package?;
+
+            if let Some(score) = self.score(&matcher, &package) {
+                results.push(SearchResult {
+                    package: (*package).clone(),
+                    score,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// Searches the packages of the given repository using its search index
+    fn perform_full_text(&self, index: &RepositoryIndex) -> Result<Vec<SearchResult>, Error> {
+        let mut results = Vec::new();
+
+        for (package, score) in index.search_index()?.query(self.pattern, &self.fields) {
+            let package = index.package(package.category().as_str(), package.name().as_str())?;
+
+            if let Some(package) = package {
+                results.push(SearchResult {
+                    package: (*package).clone(),
+                    score,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// Performs the search
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::cache::available::SearchMode;
+    /// use libnest::config::Config;
+    ///
+    /// let config = Config::load()?;
+    /// let lock_file_ownership = config.acquire_lock_file_ownership(false)?;
+    ///
+    /// let results = config
+    ///     .available_packages_cache(&lock_file_ownership)
+    ///     .search("crutl")
+    ///     .set_mode(SearchMode::Fuzzy)
+    ///     .perform()?;
+    /// for result in results {
+    ///     println!("{} ({})", result.package().full_name(), result.score());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn perform(&self) -> Result<Vec<SearchResult>, Error> {
+        let mut results = Vec::new();
+
+        for repository in RepositoryIndex::repositories(self.cache_root)? {
+            if let Some(required) = &self.repository {
+                if *required != repository {
+                    continue;
+                }
+            }
+
+            let index = RepositoryIndex::load(self.cache_root, repository.as_str())?;
+            if self.mode == SearchMode::FullText {
+                results.append(&mut self.perform_full_text(&index)?);
+            } else {
+                results.append(&mut self.perform_scan(&index)?);
+            }
+        }
+
+        results.sort_by(|a, b| match b.score.cmp(&a.score) {
+            Ordering::Equal => a
+                .package
+                .full_name()
+                .to_string()
+                .cmp(&b.package.full_name().to_string()),
+            ordering => ordering,
+        });
+        Ok(results)
+    }
+}
@@ -0,0 +1,845 @@
+//! Storage of the available packages of a repository in a single index file.
+//!
+//! Each repository pulled is stored in `<repository>.index`, a binary file made of a table locating the manifest of
+//! each of its packages in the file, followed by the groups it defines, its search index and the manifests
+//! themselves. Loading a repository thus only requires one read and the decoding of the table: a manifest is only
+//! decoded the first time it is looked up. A loaded repository is kept in memory until its index file is written
+//! again, so that the many queries made while resolving dependencies don't need to read it again.
+//!
+//! The index also holds the serial number of the repository's journal it is up to date with, so that the next pull
+//! only has to apply the changes made to the repository since then.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::ops::{Bound, Range};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use failure::{format_err, Error, ResultExt};
+use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::cache::depgraph::GroupName;
+use crate::cache::errors::CacheErrorKind;
+use crate::package::{CategoryName, PackageManifest, PackageName, RepositoryName};
+
+use super::group::RepositoryGroup;
+use super::index::SearchIndex;
+
+/// The extension of the index files of the repositories
+static REPOSITORY_INDEX_EXTENSION: &str = "index";
+
+/// The magic number starting the index files of the repositories, followed by the version of their format
+static INDEX_MAGIC_NUMBER: &[u8] = b"NESTIDX";
+
+/// The version of the format of the index files written by this version of Nest
+const INDEX_FORMAT_VERSION: u8 = 1;
+
+lazy_static! {
+    /// The index files loaded by this process, along with their modification date when they were loaded
+    static ref LOADED_INDEXES: Mutex<HashMap<PathBuf, (Option<SystemTime>, Arc<RepositoryIndex>)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// A change made to the packages of a repository, as recorded in the repository's journal
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepositoryChange {
+    /// A package was added, or its versions or metadata changed
+    Update(PackageManifest),
+
+    /// A package was removed
+    Remove {
+        /// The category of the removed package
+        category: CategoryName,
+
+        /// The name of the removed package
+        name: PackageName,
+    },
+
+    /// A group was added, or its packages or description changed
+    UpdateGroup(RepositoryGroup),
+
+    /// A group was removed
+    RemoveGroup {
+        /// The name of the removed group
+        name: GroupName,
+    },
+}
+
+/// The changes made to the packages of a repository since a given serial number of its journal
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct RepositoryChanges {
+    serial: u64,
+    changes: Vec<RepositoryChange>,
+}
+
+impl RepositoryChanges {
+    /// Returns the serial number of the journal once the changes are applied
+    #[inline]
+    pub fn serial(&self) -> u64 {
+        self.serial
+    }
+
+    /// Returns the changes, in the order they must be applied
+    #[inline]
+    pub fn changes(&self) -> &[RepositoryChange] {
+        &self.changes
+    }
+}
+
+/// The number of packages added, updated and removed between two versions of the index of a repository
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct RepositoryDiff {
+    added: usize,
+    updated: usize,
+    removed: usize,
+}
+
+impl RepositoryDiff {
+    /// Returns the number of packages that were added to the repository
+    #[inline]
+    pub fn added(&self) -> usize {
+        self.added
+    }
+
+    /// Returns the number of packages whose versions or metadata changed
+    #[inline]
+    pub fn updated(&self) -> usize {
+        self.updated
+    }
+
+    /// Returns the number of packages that were removed from the repository
+    #[inline]
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// Returns whether no package was added, updated or removed
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.updated == 0 && self.removed == 0
+    }
+}
+
+/// A value stored in an index file, decoded from its record the first time it is needed
+#[derive(Debug)]
+struct Record<T> {
+    record: Option<Range<usize>>,
+    value: Mutex<Option<Arc<T>>>,
+}
+
+impl<T: Serialize + DeserializeOwned> Record<T> {
+    /// Wraps a value that isn't stored in an index file yet
+    fn decoded(value: T) -> Self {
+        Record {
+            record: None,
+            value: Mutex::new(Some(Arc::new(value))),
+        }
+    }
+
+    /// Refers to a value stored in the given range of an index file, without decoding it
+    fn encoded(record: Range<usize>) -> Self {
+        Record {
+            record: Some(record),
+            value: Mutex::new(None),
+        }
+    }
+
+    /// Returns the value, decoding it from the given content of the index file if it wasn't yet
+    fn get(&self, data: &[u8]) -> Result<Arc<T>, Error> {
+        let mut value = self.value.lock().expect("the repository index is poisoned");
+
+        if let Some(value) = &*value {
+            return Ok(value.clone());
+        }
+
+        let record = self
+            .record
+            .clone()
+            .expect("a value without a record is always decoded");
+        let decoded: Arc<T> = Arc::new(
+            serde_json::from_slice(&data[record]).context(CacheErrorKind::CacheParseError)?,
+        );
+        *value = Some(decoded.clone());
+        Ok(decoded)
+    }
+
+    /// Returns the encoded value, reusing its record in the given content of the index file if it has one
+    fn encode<'a>(&self, data: &'a [u8]) -> Result<Cow<'a, [u8]>, Error> {
+        match &self.record {
+            Some(record) => Ok(Cow::Borrowed(&data[record.clone()])),
+            None => Ok(Cow::Owned(serde_json::to_vec(&*self.get(data)?)?)),
+        }
+    }
+}
+
+impl<T> Clone for Record<T> {
+    fn clone(&self) -> Self {
+        Record {
+            record: self.record.clone(),
+            value: Mutex::new(
+                self.value
+                    .lock()
+                    .expect("the repository index is poisoned")
+                    .clone(),
+            ),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Default> Default for Record<T> {
+    fn default() -> Self {
+        Record::decoded(T::default())
+    }
+}
+
+/// A reader going through the binary content of an index file
+struct IndexReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> IndexReader<'a> {
+    /// Reads the given number of bytes
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| format_err!("the index file is truncated"))?;
+        let bytes = &self.data[self.position..end];
+
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// Reads a little-endian `u64`
+    fn u64(&mut self) -> Result<u64, Error> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Reads a length, stored as a little-endian `u64`
+    fn len(&mut self) -> Result<usize, Error> {
+        Ok(usize::try_from(self.u64()?)?)
+    }
+
+    /// Reads a string, stored as its length followed by its bytes
+    fn string(&mut self) -> Result<&'a str, Error> {
+        let len = self.len()?;
+        Ok(std::str::from_utf8(self.bytes(len)?)?)
+    }
+
+    /// Reads a record, stored as its length followed by its bytes, returning its range in the index file
+    fn record(&mut self) -> Result<Range<usize>, Error> {
+        let len = self.len()?;
+        let start = self.position;
+
+        self.bytes(len)?;
+        Ok(start..self.position)
+    }
+}
+
+/// Writes a length as a little-endian `u64`
+fn write_len(buffer: &mut Vec<u8>, len: usize) {
+    buffer.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+/// The layout of the index files written before they were stored in a binary format, as a single JSON document
+#[derive(Deserialize)]
+struct JsonRepositoryIndex {
+    #[serde(default)]
+    serial: Option<u64>,
+    packages: BTreeMap<String, BTreeMap<String, PackageManifest>>,
+    #[serde(default)]
+    groups: BTreeMap<GroupName, RepositoryGroup>,
+    search_index: SearchIndex,
+}
+
+/// The available packages of a repository, along with their search index.
+///
+/// When loaded from an index file, the manifests of the packages and the search index are only decoded when they
+/// are first needed, which is why accessing them may fail.
+#[derive(Clone, Debug, Default)]
+pub struct RepositoryIndex {
+    serial: Option<u64>,
+    packages: BTreeMap<String, BTreeMap<String, Record<PackageManifest>>>,
+    groups: BTreeMap<GroupName, RepositoryGroup>,
+    search_index: Record<SearchIndex>,
+    data: Arc<Vec<u8>>,
+}
+
+impl RepositoryIndex {
+    /// Builds the index of a repository made of the given packages, up to date with the given serial number
+    /// of the repository's journal, if it is known
+    pub fn build(manifests: &[PackageManifest], serial: Option<u64>) -> Self {
+        let mut index = RepositoryIndex {
+            serial,
+            ..RepositoryIndex::default()
+        };
+
+        for manifest in manifests {
+            index.insert(manifest.clone());
+        }
+        index.search_index = Record::decoded(SearchIndex::build(manifests));
+        index
+    }
+
+    /// Adds the given groups to the index, replacing those with the same name
+    pub fn with_groups(mut self, groups: &[RepositoryGroup]) -> Self {
+        for group in groups {
+            self.groups.insert(group.name().clone(), group.clone());
+        }
+        self
+    }
+
+    fn insert(&mut self, manifest: PackageManifest) {
+        self.packages
+            .entry(manifest.category().to_string())
+            .or_insert_with(BTreeMap::new)
+            .insert(manifest.name().to_string(), Record::decoded(manifest));
+    }
+
+    /// Applies the given changes to the index, making it up to date with the serial number of the changes
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate serde_json;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::cache::available::{RepositoryChanges, RepositoryIndex};
+    ///
+    /// let changes: RepositoryChanges = serde_json::from_str(r#"{
+    ///     "serial": 42,
+    ///     "changes": [
+    ///         { "remove": { "category": "sys-bin", "name": "coreutils" } }
+    ///     ]
+    /// }"#)?;
+    ///
+    /// let mut index = RepositoryIndex::build(&[], Some(41));
+    /// index.apply(&changes)?;
+    /// assert_eq!(index.serial(), Some(42));
+    /// assert!(index.package("sys-bin", "coreutils")?.is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn apply(&mut self, changes: &RepositoryChanges) -> Result<(), Error> {
+        for change in &changes.changes {
+            match change {
+                RepositoryChange::Update(manifest) => self.insert(manifest.clone()),
+                RepositoryChange::Remove { category, name } => {
+                    if let Some(packages) = self.packages.get_mut(category.as_str()) {
+                        packages.remove(name.as_str());
+                        if packages.is_empty() {
+                            self.packages.remove(category.as_str());
+                        }
+                    }
+                }
+                RepositoryChange::UpdateGroup(group) => {
+                    self.groups.insert(group.name().clone(), group.clone());
+                }
+                RepositoryChange::RemoveGroup { name } => {
+                    self.groups.remove(name);
+                }
+            }
+        }
+
+        let manifests = self.packages().collect::<Result<Vec<_>, _>>()?;
+        self.search_index = Record::decoded(SearchIndex::build(
+            manifests.iter().map(|manifest| &**manifest),
+        ));
+        self.serial = Some(changes.serial);
+        Ok(())
+    }
+
+    /// Returns the serial number of the repository's journal the index is up to date with, if it is known
+    #[inline]
+    pub fn serial(&self) -> Option<u64> {
+        self.serial
+    }
+
+    /// Counts the packages added, updated and removed since the given previous version of the index
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate serde_json;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::cache::available::RepositoryIndex;
+    /// use libnest::package::PackageManifest;
+    ///
+    /// let manifest: PackageManifest = serde_json::from_str(r#"{
+    ///     "name": "coreutils",
+    ///     "category": "sys-bin",
+    ///     "repository": "stable",
+    ///     "metadata": {
+    ///         "description": "The basic file, shell and text manipulation utilities",
+    ///         "tags": [],
+    ///         "maintainer": "nest@raven-os.org",
+    ///         "licenses": [],
+    ///         "upstream_url": null
+    ///     },
+    ///     "versions": {}
+    /// }"#)?;
+    ///
+    /// let previous = RepositoryIndex::build(&[], None);
+    /// let index = RepositoryIndex::build(&[manifest], Some(1));
+    ///
+    /// let diff = index.diff(&previous)?;
+    /// assert_eq!((diff.added(), diff.updated(), diff.removed()), (1, 0, 0));
+    /// assert_eq!(previous.diff(&index)?.removed(), 1);
+    /// assert!(index.diff(&index)?.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn diff(&self, previous: &RepositoryIndex) -> Result<RepositoryDiff, Error> {
+        let mut diff = RepositoryDiff::default();
+
+        for (category, packages) in &self.packages {
+            for (name, manifest) in packages {
+                match previous.record(category, name) {
+                    None => diff.added += 1,
+                    Some(previous_manifest) => {
+                        if manifest.get(&self.data)? != previous_manifest.get(&previous.data)? {
+                            diff.updated += 1;
+                        }
+                    }
+                }
+            }
+        }
+        diff.removed = previous
+            .packages
+            .iter()
+            .flat_map(|(category, packages)| packages.keys().map(move |name| (category, name)))
+            .filter(|(category, name)| self.record(category, name).is_none())
+            .count();
+        Ok(diff)
+    }
+
+    /// Encodes the index in the binary format of the index files.
+    ///
+    /// The manifests of the packages are stored as records located by a table at the beginning of the file,
+    /// so that looking up a package doesn't require decoding the others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate serde_json;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::cache::available::RepositoryIndex;
+    /// use libnest::package::PackageManifest;
+    ///
+    /// let manifest = |name, description| -> Result<PackageManifest, serde_json::Error> {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "name": name,
+    ///         "category": "sys-bin",
+    ///         "repository": "stable",
+    ///         "metadata": {
+    ///             "description": description,
+    ///             "tags": [],
+    ///             "maintainer": "nest@raven-os.org",
+    ///             "licenses": [],
+    ///             "upstream_url": null
+    ///         },
+    ///         "versions": {}
+    ///     }))
+    /// };
+    ///
+    /// let index = RepositoryIndex::build(
+    ///     &[manifest("bash", "The shell")?, manifest("coreutils", "The basic utilities")?],
+    ///     Some(7),
+    /// );
+    /// let mut data = index.to_bytes()?;
+    ///
+    /// // Corrupt the record holding the manifest of bash, the last place its description appears in
+    /// let description = br#""The shell""#;
+    /// let position = data
+    ///     .windows(description.len())
+    ///     .rposition(|window| window == &description[..])
+    ///     .unwrap();
+    /// data[position] = b'{';
+    ///
+    /// // Only the table is decoded when loading the index, and the lookup of a package only decodes its record
+    /// let loaded = RepositoryIndex::from_bytes(data)?;
+    /// assert_eq!(loaded.serial(), Some(7));
+    /// assert_eq!(loaded.packages().count(), 2);
+    /// assert!(loaded.package("sys-bin", "coreutils")?.is_some());
+    /// assert!(loaded.package("sys-bin", "bash").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        let mut table = Vec::new();
+        let mut records = Vec::new();
+
+        for (category, packages) in &self.packages {
+            for (name, manifest) in packages {
+                let record = manifest.encode(&self.data)?;
+
+                write_len(&mut table, category.len());
+                table.extend_from_slice(category.as_bytes());
+                write_len(&mut table, name.len());
+                table.extend_from_slice(name.as_bytes());
+                write_len(&mut table, records.len());
+                write_len(&mut table, record.len());
+                records.extend_from_slice(&record);
+            }
+        }
+
+        let groups = serde_json::to_vec(&self.groups)?;
+        let search_index = self.search_index.encode(&self.data)?;
+
+        data.extend_from_slice(INDEX_MAGIC_NUMBER);
+        data.push(INDEX_FORMAT_VERSION);
+        match self.serial {
+            Some(serial) => {
+                data.push(1);
+                data.extend_from_slice(&serial.to_le_bytes());
+            }
+            None => data.push(0),
+        }
+        write_len(&mut data, groups.len());
+        data.extend_from_slice(&groups);
+        write_len(&mut data, search_index.len());
+        data.extend_from_slice(&search_index);
+        write_len(&mut data, self.packages.values().map(BTreeMap::len).sum());
+        data.extend_from_slice(&table);
+        write_len(&mut data, records.len());
+        data.extend_from_slice(&records);
+        Ok(data)
+    }
+
+    /// Decodes an index from the content of an index file, only decoding the table locating the manifests of the
+    /// packages.
+    ///
+    /// Index files written before they were stored in a binary format are fully decoded.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        if !data.starts_with(INDEX_MAGIC_NUMBER) {
+            let index: JsonRepositoryIndex = serde_json::from_slice(&data)?;
+            let mut packages = BTreeMap::new();
+
+            for (category, manifests) in index.packages {
+                let manifests = manifests
+                    .into_iter()
+                    .map(|(name, manifest)| (name, Record::decoded(manifest)))
+                    .collect();
+                packages.insert(category, manifests);
+            }
+            return Ok(RepositoryIndex {
+                serial: index.serial,
+                packages,
+                groups: index.groups,
+                search_index: Record::decoded(index.search_index),
+                data: Arc::default(),
+            });
+        }
+
+        let mut reader = IndexReader {
+            data: &data,
+            position: INDEX_MAGIC_NUMBER.len(),
+        };
+
+        let version = reader.bytes(1)?[0];
+        if version != INDEX_FORMAT_VERSION {
+            return Err(format_err!(
+                "the index file was written in version {} of the format, which isn't supported",
+                version
+            ));
+        }
+
+        let serial = match reader.bytes(1)?[0] {
+            0 => None,
+            _ => Some(reader.u64()?),
+        };
+        let groups = serde_json::from_slice(&data[reader.record()?])?;
+        let search_index = Record::encoded(reader.record()?);
+
+        let mut table = Vec::new();
+        for _ in 0..reader.len()? {
+            let category = reader.string()?;
+            let name = reader.string()?;
+            let offset = reader.len()?;
+            let len = reader.len()?;
+
+            table.push((category, name, offset, len));
+        }
+
+        let records = reader.record()?;
+        let mut packages: BTreeMap<String, BTreeMap<String, Record<PackageManifest>>> =
+            BTreeMap::new();
+        for (category, name, offset, len) in table {
+            let start = records.start + offset;
+            if offset
+                .checked_add(len)
+                .map_or(true, |end| end > records.len())
+            {
+                return Err(format_err!("the index file is truncated"));
+            }
+
+            packages
+                .entry(category.to_string())
+                .or_insert_with(BTreeMap::new)
+                .insert(name.to_string(), Record::encoded(start..start + len));
+        }
+
+        Ok(RepositoryIndex {
+            serial,
+            packages,
+            groups,
+            search_index,
+            data: Arc::new(data),
+        })
+    }
+
+    /// Returns the path of the index file of the given repository
+    pub(crate) fn path(cache_root: &Path, repository: &str) -> PathBuf {
+        cache_root.join(format!("{}.{}", repository, REPOSITORY_INDEX_EXTENSION))
+    }
+
+    /// Returns the names of the repositories with an index file in the given cache folder
+    pub(crate) fn repositories(cache_root: &Path) -> Result<Vec<RepositoryName>, Error> {
+        let mut repositories = Vec::new();
+
+        if cache_root.exists() {
+            for entry in
+                fs::read_dir(cache_root).with_context(|_| cache_root.display().to_string())?
+            {
+                let path = entry
+                    .with_context(|_| cache_root.display().to_string())?
+                    .path();
+
+                if path
+                    .extension()
+                    .map_or(false, |ext| ext == REPOSITORY_INDEX_EXTENSION)
+                {
+                    let name = path.file_stem().and_then(|stem| stem.to_str());
+
+                    if let Some(name) = name.and_then(|name| RepositoryName::parse(name).ok()) {
+                        repositories.push(name);
+                    }
+                }
+            }
+        }
+        repositories.sort();
+        Ok(repositories)
+    }
+
+    /// Loads the index of the given repository, reusing the one loaded previously if its file didn't change.
+    ///
+    /// A repository that was never pulled has an empty index.
+    pub(crate) fn load(cache_root: &Path, repository: &str) -> Result<Arc<Self>, Error> {
+        let path = Self::path(cache_root, repository);
+
+        if !path.exists() {
+            return Ok(Arc::new(RepositoryIndex::default()));
+        }
+
+        let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+        let mut loaded = LOADED_INDEXES
+            .lock()
+            .expect("the loaded indexes are poisoned");
+
+        if let Some((loaded_modified, index)) = loaded.get(&path) {
+            if modified.is_some() && *loaded_modified == modified {
+                return Ok(index.clone());
+            }
+        }
+
+        let data = fs::read(&path)
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheLoadError)?;
+        let index = Arc::new(
+            Self::from_bytes(data)
+                .context(path.display().to_string())
+                .context(CacheErrorKind::CacheParseError)?,
+        );
+
+        loaded.insert(path, (modified, index.clone()));
+        Ok(index)
+    }
+
+    /// Saves the index of the given repository
+    pub(crate) fn save(&self, cache_root: &Path, repository: &str) -> Result<(), Error> {
+        let path = Self::path(cache_root, repository);
+
+        LOADED_INDEXES
+            .lock()
+            .expect("the loaded indexes are poisoned")
+            .remove(&path);
+
+        // The index is written next to the previous one, which it then replaces atomically, so that an interrupted
+        // write can't leave a truncated index behind
+        let temporary = path.with_extension(format!("{}.tmp", REPOSITORY_INDEX_EXTENSION));
+        let res: Result<_, Error> = try {
+            let data = self.to_bytes()?;
+            fs::create_dir_all(cache_root)?;
+
+            let mut writer = BufWriter::new(File::create(&temporary)?);
+            writer.write_all(&data)?;
+            writer.flush()?;
+            writer.get_ref().sync_data()?;
+            fs::rename(&temporary, &path)?;
+        };
+        if res.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        Ok(res
+            .context(path.display().to_string())
+            .context(CacheErrorKind::CacheWriteError)?)
+    }
+
+    /// Returns the record of the package with the given category and name, if any
+    fn record(&self, category: &str, name: &str) -> Option<&Record<PackageManifest>> {
+        self.packages
+            .get(category)
+            .and_then(|packages| packages.get(name))
+    }
+
+    /// Returns the names of the categories of the repository
+    #[inline]
+    pub fn categories(&self) -> impl Iterator<Item = &str> {
+        self.packages.keys().map(String::as_str)
+    }
+
+    /// Returns the packages of the given category
+    #[inline]
+    pub fn category_packages<'a>(
+        &'a self,
+        category: &str,
+    ) -> impl Iterator<Item = Result<Arc<PackageManifest>, Error>> + 'a {
+        self.packages
+            .get(category)
+            .into_iter()
+            .flat_map(|packages| packages.values())
+            .map(move |manifest| manifest.get(&self.data))
+    }
+
+    /// Returns the packages whose name starts with the given prefix, sorted by category then name, only looking into
+    /// the given category if there is one.
+    ///
+    /// The packages are browsed in order, so only the matching ones are gone through and decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate serde_json;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::cache::available::RepositoryIndex;
+    /// use libnest::package::PackageManifest;
+    ///
+    /// let manifest = |category, name| -> Result<PackageManifest, serde_json::Error> {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "name": name,
+    ///         "category": category,
+    ///         "repository": "stable",
+    ///         "metadata": {
+    ///             "description": "",
+    ///             "tags": [],
+    ///             "maintainer": "nest@raven-os.org",
+    ///             "licenses": [],
+    ///             "upstream_url": null
+    ///         },
+    ///         "versions": {}
+    ///     }))
+    /// };
+    ///
+    /// let index = RepositoryIndex::build(
+    ///     &[
+    ///         manifest("sys-devel", "gcc")?,
+    ///         manifest("sys-devel", "gdb")?,
+    ///         manifest("sys-devel", "make")?,
+    ///         manifest("sys-libs", "gcc-libs")?,
+    ///     ],
+    ///     None,
+    /// );
+    ///
+    /// let names = |category, prefix| -> Result<Vec<String>, failure::Error> {
+    ///     index
+    ///         .packages_with_prefix(category, prefix)
+    ///         .map(|package| Ok(package?.name().to_string()))
+    ///         .collect()
+    /// };
+    /// assert_eq!(names(Some("sys-devel"), "g")?, vec!["gcc", "gdb"]);
+    /// assert_eq!(names(None, "gcc")?, vec!["gcc", "gcc-libs"]);
+    /// assert_eq!(names(Some("sys-devel"), "")?, vec!["gcc", "gdb", "make"]);
+    /// assert!(names(Some("sys-apps"), "")?.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn packages_with_prefix<'a>(
+        &'a self,
+        category: Option<&'a str>,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = Result<Arc<PackageManifest>, Error>> + 'a {
+        let categories = match category {
+            Some(category) => (Bound::Included(category), Bound::Included(category)),
+            None => (Bound::Unbounded, Bound::Unbounded),
+        };
+
+        self.packages
+            .range::<str, _>(categories)
+            .flat_map(move |(_, packages)| {
+                packages
+                    .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+                    .take_while(move |(name, _)| name.starts_with(prefix))
+                    .map(move |(_, package)| package.get(&self.data))
+            })
+    }
+
+    /// Returns all the packages of the repository
+    #[inline]
+    pub fn packages(&self) -> impl Iterator<Item = Result<Arc<PackageManifest>, Error>> + '_ {
+        self.packages
+            .values()
+            .flat_map(|packages| packages.values())
+            .map(move |manifest| manifest.get(&self.data))
+    }
+
+    /// Returns the package with the given category and name, if any
+    #[inline]
+    pub fn package(
+        &self,
+        category: &str,
+        name: &str,
+    ) -> Result<Option<Arc<PackageManifest>>, Error> {
+        self.record(category, name)
+            .map(|manifest| manifest.get(&self.data))
+            .transpose()
+    }
+
+    /// Returns the groups defined by the repository, sorted by name
+    #[inline]
+    pub fn groups(&self) -> impl Iterator<Item = &RepositoryGroup> {
+        self.groups.values()
+    }
+
+    /// Returns the group with the given name, if the repository defines it
+    #[inline]
+    pub fn group(&self, name: &GroupName) -> Option<&RepositoryGroup> {
+        self.groups.get(name)
+    }
+
+    /// Returns the search index of the repository
+    #[inline]
+    pub fn search_index(&self) -> Result<Arc<SearchIndex>, Error> {
+        self.search_index.get(&self.data)
+    }
+}
@@ -0,0 +1,112 @@
+//! Module to gather the changes made by the versions of a package more recent than the installed one.
+//!
+//! The changes come from the changelog of the package file of the candidate version if it is downloaded already,
+//! or else from the recent entries of its changelog indexed by its repository.
+
+use failure::Error;
+
+use crate::cache::available::AvailablePackagesCacheQueryStrategy;
+use crate::cache::query::PackageQuery;
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{Changelog, ChangelogEntry, PackageFullName, PackageID};
+
+/// Structure holding the changes between the installed version of a package and its candidate version
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PackageChangelog {
+    installed: Option<PackageID>,
+    candidate: PackageID,
+    entries: Vec<ChangelogEntry>,
+}
+
+impl PackageChangelog {
+    /// Gathers the changes of the packages matching the given query, sorted by package.
+    ///
+    /// The candidate version of a package is its most recent available version, as it would be installed. If a
+    /// version of the package is installed, only the changes made since its most recent installed version are
+    /// kept, otherwise all the known changes up to the candidate version are.
+    pub fn query(
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        query: &PackageQuery,
+    ) -> Result<Vec<PackageChangelog>, Error> {
+        let available = config.available_packages_cache(lock_ownership);
+        let downloaded = config.downloaded_packages_cache(lock_ownership);
+        let installed = config.installed_packages_cache(lock_ownership).packages()?;
+
+        let mut changelogs = Vec::new();
+        for result in query.available(
+            config,
+            lock_ownership,
+            AvailablePackagesCacheQueryStrategy::BestMatch,
+        )? {
+            let candidate = result.id();
+            let full_name = result.full_name();
+            let installed = installed
+                .iter()
+                .filter(|id| {
+                    let installed_name: PackageFullName = (*id).clone().into();
+                    installed_name == full_name
+                })
+                .max_by(|a, b| a.version().cmp(b.version()))
+                .cloned();
+
+            // The downloaded package file holds the whole changelog, the repository only its recent entries
+            let changelog = match downloaded.explore_package(&candidate) {
+                Ok(explorer) => explorer.load_changelog()?,
+                Err(_) => None,
+            };
+            let changelog = match changelog {
+                Some(changelog) => changelog,
+                None => Changelog::from(available.package_changelog(&candidate)?),
+            };
+
+            let entries = changelog
+                .between(
+                    installed.as_ref().map(PackageID::version),
+                    candidate.version(),
+                )
+                .into_iter()
+                .cloned()
+                .collect();
+
+            changelogs.push(PackageChangelog {
+                installed,
+                candidate,
+                entries,
+            });
+        }
+
+        changelogs.sort_by(|a, b| a.candidate.cmp(&b.candidate));
+        Ok(changelogs)
+    }
+
+    /// Returns the [`PackageID`] of the most recent installed version of the package, if it is installed
+    #[inline]
+    pub fn installed(&self) -> Option<&PackageID> {
+        self.installed.as_ref()
+    }
+
+    /// Returns the [`PackageID`] of the candidate version of the package
+    #[inline]
+    pub fn candidate(&self) -> &PackageID {
+        &self.candidate
+    }
+
+    /// Returns whether the installed version of the package is at least as recent as the candidate version
+    #[inline]
+    pub fn is_up_to_date(&self) -> bool {
+        self.installed.as_ref().map_or(false, |installed| {
+            installed.version() >= self.candidate.version()
+        })
+    }
+
+    /// Returns the entries of the changelog between the installed version and the candidate version, the most
+    /// recent version first.
+    ///
+    /// It is empty if the package is up to date, or if no changelog is known for its candidate version.
+    #[inline]
+    pub fn entries(&self) -> &[ChangelogEntry] {
+        &self.entries
+    }
+}
@@ -0,0 +1,214 @@
+//! Export of a part of the dependency graph, for tooling and documentation.
+
+use std::fmt::Write;
+
+use serde_derive::Serialize;
+use serde_json;
+
+/// The kind of an exported node
+#[derive(Serialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportedNodeKind {
+    /// The node is a group
+    Group,
+
+    /// The node is a package
+    Package,
+}
+
+/// A node of an exported dependency graph
+#[derive(Serialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ExportedNode {
+    name: String,
+    kind: ExportedNodeKind,
+    installed: bool,
+    depth: usize,
+}
+
+impl ExportedNode {
+    /// Returns the name of the node: the name of the group, or the [`PackageID`][1] of the package.
+    ///
+    /// The optional dependencies that aren't installed are named after their requirement.
+    ///
+    /// [1]: ../../package/struct.PackageID.html
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the kind of the node
+    #[inline]
+    pub fn kind(&self) -> ExportedNodeKind {
+        self.kind
+    }
+
+    /// Returns whether the node is part of the dependency graph, which is only false for optional dependencies
+    /// that aren't installed
+    #[inline]
+    pub fn installed(&self) -> bool {
+        self.installed
+    }
+
+    /// Returns the distance between the exported node and this one
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+/// A requirement of a node of an exported dependency graph
+#[derive(Serialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ExportedEdge {
+    from: String,
+    to: String,
+    requirement: String,
+    optional: bool,
+}
+
+impl ExportedEdge {
+    /// Returns the name of the node holding the requirement
+    #[inline]
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    /// Returns the name of the node fulfilling the requirement
+    #[inline]
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    /// Returns the requirement, as written in the manifest or the group
+    #[inline]
+    pub fn requirement(&self) -> &str {
+        &self.requirement
+    }
+
+    /// Returns whether the requirement is an optional dependency
+    #[inline]
+    pub fn optional(&self) -> bool {
+        self.optional
+    }
+}
+
+/// A node of the [`DependencyGraph`][1] along with its dependencies, as returned by
+/// [`DependencyGraph::export()`][2].
+///
+/// It can be serialized to JSON, or converted to the DOT language of Graphviz with [`to_dot()`][3].
+///
+/// [1]: struct.DependencyGraph.html
+/// [2]: struct.DependencyGraph.html#method.export
+/// [3]: #method.to_dot
+#[derive(Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct DependencyGraphExport {
+    nodes: Vec<ExportedNode>,
+    edges: Vec<ExportedEdge>,
+}
+
+/// Quotes the given text as a DOT identifier
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl DependencyGraphExport {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        DependencyGraphExport {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add_node(
+        &mut self,
+        name: String,
+        kind: ExportedNodeKind,
+        installed: bool,
+        depth: usize,
+    ) {
+        if !self.nodes.iter().any(|node| node.name == name) {
+            self.nodes.push(ExportedNode {
+                name,
+                kind,
+                installed,
+                depth,
+            });
+        }
+    }
+
+    pub(crate) fn add_edge(
+        &mut self,
+        from: String,
+        to: String,
+        requirement: String,
+        optional: bool,
+    ) {
+        self.edges.push(ExportedEdge {
+            from,
+            to,
+            requirement,
+            optional,
+        });
+    }
+
+    /// Returns the exported nodes, the exported one first, then by increasing depth
+    #[inline]
+    pub fn nodes(&self) -> &[ExportedNode] {
+        &self.nodes
+    }
+
+    /// Returns the requirements linking the exported nodes
+    #[inline]
+    pub fn edges(&self) -> &[ExportedEdge] {
+        &self.edges
+    }
+
+    /// Serializes the exported graph to pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("an exported graph can always be serialized")
+    }
+
+    /// Converts the exported graph to the DOT language of Graphviz.
+    ///
+    /// Groups are drawn as boxes, optional dependencies with dashed edges, and optional dependencies
+    /// that aren't installed with dashed nodes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n");
+
+        for node in &self.nodes {
+            let mut attributes = Vec::new();
+
+            if node.kind == ExportedNodeKind::Group {
+                attributes.push("shape=box");
+            }
+            if !node.installed {
+                attributes.push("style=dashed");
+            }
+
+            if attributes.is_empty() {
+                writeln!(dot, "    {};", quote(&node.name)).unwrap();
+            } else {
+                writeln!(
+                    dot,
+                    "    {} [{}];",
+                    quote(&node.name),
+                    attributes.join(", ")
+                )
+                .unwrap();
+            }
+        }
+        for edge in &self.edges {
+            writeln!(
+                dot,
+                "    {} -> {} [label={}{}];",
+                quote(&edge.from),
+                quote(&edge.to),
+                quote(&edge.requirement),
+                if edge.optional { ", style=dashed" } else { "" },
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
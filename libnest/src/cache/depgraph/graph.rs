@@ -1,19 +1,25 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::{self, File};
 use std::io::Write;
 use std::marker::PhantomData;
 use std::path::Path;
 
-use failure::{format_err, Error, ResultExt};
+use failure::{format_err, Context, Error, ResultExt};
 use serde_derive::{Deserialize, Serialize};
 use serde_json;
 
 use crate::cache::available::{AvailablePackagesCacheQueryStrategy, QueryResult};
+use crate::cache::query::PackageQuery;
+use crate::cache::world::World;
 use crate::config::Config;
 use crate::lock_file::LockFileOwnership;
-use crate::package::{PackageFullName, PackageRequirement};
+use crate::package::{
+    PackageFullName, PackageID, PackageRequirement, PackageShortName, SoftPackageRequirement,
+};
+use crate::solver::{self, Solver};
 
 use super::super::errors::DependencyGraphErrorKind;
+use super::export::{DependencyGraphExport, ExportedNodeKind};
 use super::node::{GroupName, Node, NodeID, NodeKind, NodeName, ROOT_ID};
 use super::requirement::{
     Requirement, RequirementID, RequirementKind, RequirementManagementMethod,
@@ -147,15 +153,32 @@ impl<'lock_file> DependencyGraph<'lock_file> {
     }
 
     /// Returns the [`NodeID`] of a given package
+    /// If the package is in several slots, the node of the lowest slot is returned.
     /// If no such ID is found, a [`DependencyGraphError`] is returned
     pub fn get_package_node_id(&self, name: &PackageFullName) -> Result<NodeID, Error> {
-        let node_name = NodeName::Package(name.clone());
+        self.get_package_node_ids(name)
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                format_err!("{}", name)
+                    .context(DependencyGraphErrorKind::UnknownPackage)
+                    .into()
+            })
+    }
+
+    /// Returns the [`NodeID`]s of a given package, one for each slot it is in, sorted by slot
+    pub fn get_package_node_ids(&self, name: &PackageFullName) -> Vec<NodeID> {
+        let mut found: Vec<_> = self
+            .node_names
+            .iter()
+            .filter_map(|(node_name, node_id)| match node_name {
+                NodeName::Package(full_name, slot) if full_name == name => Some((slot, *node_id)),
+                _ => None,
+            })
+            .collect();
 
-        self.node_names.get(&node_name).cloned().ok_or_else(|| {
-            format_err!("{}", name)
-                .context(DependencyGraphErrorKind::UnknownPackage)
-                .into()
-        })
+        found.sort();
+        found.into_iter().map(|(_, node_id)| node_id).collect()
     }
 
     /// Returns a reference to the [`Node`] of a given package
@@ -296,9 +319,209 @@ impl<'lock_file> DependencyGraph<'lock_file> {
         self.requirements.remove(&requirement_id);
     }
 
+    /// Returns the [`NodeID`]s of the nodes having a requirement fulfilled by the given node
+    pub fn dependent_nodes(&self, node_id: NodeID) -> Vec<NodeID> {
+        let mut dependents: Vec<_> = self
+            .nodes
+            .get(&node_id)
+            .expect("invalid node id")
+            .dependents()
+            .iter()
+            .map(|requirement_id| self.requirements[requirement_id].fulfilled_node_id())
+            .collect();
+
+        dependents.sort();
+        dependents.dedup();
+        dependents
+    }
+
+    /// Returns the [`NodeID`]s of the package nodes matching the given query, sorted
+    pub fn find_package_nodes(&self, query: &PackageQuery) -> Vec<NodeID> {
+        let mut found: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| match node.kind() {
+                NodeKind::Package { id, .. } => query.matches(id),
+                NodeKind::Group { .. } => false,
+            })
+            .map(|(node_id, _)| *node_id)
+            .collect();
+
+        found.sort();
+        found
+    }
+
+    /// Returns the [`NodeID`]s of the nodes depending on the given node, sorted.
+    ///
+    /// If `recursive` is true, the nodes depending on them are returned too, and so on.
+    pub fn reverse_dependencies(&self, node_id: NodeID, recursive: bool) -> Vec<NodeID> {
+        if !recursive {
+            return self.dependent_nodes(node_id);
+        }
+
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(node_id);
+
+        while let Some(current) = queue.pop_front() {
+            for dependent in self.dependent_nodes(current) {
+                if dependent != node_id && visited.insert(dependent) {
+                    queue.push_back(dependent);
+                }
+            }
+        }
+
+        let mut dependents: Vec<_> = visited.into_iter().collect();
+        dependents.sort();
+        dependents
+    }
+
+    /// Explains why the given node is part of the graph.
+    ///
+    /// Returns, for each explicitly required node the given one is a dependency of, the shortest chain of nodes
+    /// going from it down to the given node, each one requiring the next. If the given node is explicitly
+    /// required itself, one of the chains is made of that node only.
+    ///
+    /// The chains are sorted by the [`NodeID`] of the explicitly required node they start from.
+    pub fn requirement_chains(&self, node_id: NodeID) -> Vec<Vec<NodeID>> {
+        // For each node reached, the node it requires on the way down to the given node
+        let mut next: HashMap<NodeID, Option<NodeID>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        let mut origins = Vec::new();
+
+        next.insert(node_id, None);
+        queue.push_back(node_id);
+
+        while let Some(current) = queue.pop_front() {
+            if self.is_explicitly_required(current) {
+                origins.push(current);
+            }
+
+            for dependent in self.dependent_nodes(current) {
+                let is_package = match self.nodes[&dependent].kind() {
+                    NodeKind::Package { .. } => true,
+                    NodeKind::Group { .. } => false,
+                };
+
+                if is_package && !next.contains_key(&dependent) {
+                    next.insert(dependent, Some(current));
+                    queue.push_back(dependent);
+                }
+            }
+        }
+
+        origins.sort();
+        origins
+            .into_iter()
+            .map(|origin| {
+                let mut chain = vec![origin];
+                while let Some(Some(required)) = next.get(chain.last().unwrap()) {
+                    chain.push(*required);
+                }
+                chain
+            })
+            .collect()
+    }
+
+    /// Exports the given node along with its dependencies, down to the given depth if any.
+    ///
+    /// If `optional` is true, the optional dependencies of the exported packages are exported too, whether
+    /// they are installed or not. They are looked for in the available packages cache.
+    pub fn export(
+        &self,
+        config: &Config,
+        node_id: NodeID,
+        max_depth: Option<usize>,
+        optional: bool,
+    ) -> Result<DependencyGraphExport, Error> {
+        let mut export = DependencyGraphExport::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+
+        visited.insert(node_id);
+        queue.push_back((node_id, 0));
+
+        while let Some((current, depth)) = queue.pop_front() {
+            let node = &self.nodes[&current];
+            let kind = match node.kind() {
+                NodeKind::Group { .. } => ExportedNodeKind::Group,
+                NodeKind::Package { .. } => ExportedNodeKind::Package,
+            };
+            export.add_node(node.to_string(), kind, true, depth);
+
+            if max_depth.map_or(false, |max_depth| depth >= max_depth) {
+                continue;
+            }
+
+            let mut requirement_ids: Vec<_> = node.requirements().iter().cloned().collect();
+            requirement_ids.sort();
+
+            for requirement_id in requirement_ids {
+                let requirement = &self.requirements[&requirement_id];
+
+                if let Some(child_id) = requirement.fulfilling_node_id() {
+                    export.add_edge(
+                        node.to_string(),
+                        self.nodes[child_id].to_string(),
+                        requirement.kind().to_string(),
+                        false,
+                    );
+                    if visited.insert(*child_id) {
+                        queue.push_back((*child_id, depth + 1));
+                    }
+                }
+            }
+
+            if let (true, NodeKind::Package { id, .. }) = (optional, node.kind()) {
+                let results = config
+                    .available_packages_cache_internal(self.phantom)
+                    .query(&SoftPackageRequirement::from_id(id))
+                    .perform()?;
+
+                let mut optional_dependencies: Vec<_> = results
+                    .iter()
+                    .find(|result| result.id() == *id)
+                    .map(|result| result.manifest().optional_dependencies().iter().collect())
+                    .unwrap_or_default();
+                optional_dependencies.sort();
+
+                for dependency in optional_dependencies {
+                    let installed = self
+                        .find_nodes_for_requirement(dependency)
+                        .into_iter()
+                        .find(|child_id| match self.nodes[child_id].kind() {
+                            NodeKind::Package { id, .. } => dependency.matches(id),
+                            NodeKind::Group { .. } => false,
+                        });
+
+                    let child_name = match installed {
+                        Some(child_id) => {
+                            if visited.insert(child_id) {
+                                queue.push_back((child_id, depth + 1));
+                            }
+                            self.nodes[&child_id].to_string()
+                        }
+                        None => {
+                            export.add_node(
+                                dependency.to_string(),
+                                ExportedNodeKind::Package,
+                                false,
+                                depth + 1,
+                            );
+                            dependency.to_string()
+                        }
+                    };
+                    export.add_edge(node.to_string(), child_name, dependency.to_string(), true);
+                }
+            }
+        }
+        Ok(export)
+    }
+
     /// Creates a new node with the given package
     pub fn add_package_node(&mut self, package: QueryResult) -> Result<NodeID, Error> {
-        let node_name = NodeName::Package(package.full_name());
+        let slot = package.manifest().slot().clone();
+        let node_name = NodeName::Package(package.full_name(), slot.clone());
 
         if self.node_names.contains_key(&node_name) {
             Err(format_err!("{}", &node_name)
@@ -307,8 +530,13 @@ impl<'lock_file> DependencyGraph<'lock_file> {
         } else {
             let node_id = self.next_node_id();
 
-            self.nodes
-                .insert(node_id, Node::from(NodeKind::Package { id: package.id() }));
+            self.nodes.insert(
+                node_id,
+                Node::from(NodeKind::Package {
+                    id: package.id(),
+                    slot,
+                }),
+            );
 
             for dependency in package.manifest().dependencies() {
                 let kind = RequirementKind::Package {
@@ -344,6 +572,98 @@ impl<'lock_file> DependencyGraph<'lock_file> {
         }
     }
 
+    /// Requires the given group from the root of the graph, creating its node if needed.
+    ///
+    /// If a repository defines the group, the requirements of the group are replaced with the packages it is
+    /// made of, as defined by the preferred repository. Otherwise, the group must already be part of the graph.
+    pub fn require_group(&mut self, config: &Config, name: &GroupName) -> Result<NodeID, Error> {
+        let definition = config
+            .available_packages_cache_internal(self.phantom)
+            .group(name)?;
+
+        let group_id = match (self.node_names.get(&name.clone().into()), &definition) {
+            (Some(group_id), _) => *group_id,
+            (None, Some(_)) => self.add_group_node(name.clone())?,
+            (None, None) => {
+                return Err(format_err!("{}", name.as_str())
+                    .context(DependencyGraphErrorKind::GroupNotFound)
+                    .into());
+            }
+        };
+
+        if let Some(definition) = definition {
+            let kinds: Vec<_> = definition
+                .packages()
+                .iter()
+                .map(|package_req| RequirementKind::Package {
+                    package_req: package_req.clone(),
+                })
+                .collect();
+
+            let outdated: Vec<_> = self.nodes[&group_id]
+                .requirements()
+                .iter()
+                .filter(|requirement_id| !kinds.contains(self.requirements[requirement_id].kind()))
+                .cloned()
+                .collect();
+            for requirement_id in outdated {
+                self.remove_requirement(requirement_id);
+            }
+
+            for kind in kinds {
+                if !self.node_has_requirement(&self.nodes[&group_id], &kind) {
+                    self.node_add_requirement(group_id, kind, RequirementManagementMethod::Static);
+                }
+            }
+        }
+
+        // The group is linked to the root right away, so that it isn't dropped as an orphan before being solved
+        let kind = RequirementKind::Group { name: name.clone() };
+        if !self.node_has_requirement(&self.nodes[&ROOT_ID], &kind) {
+            let requirement_id =
+                self.node_add_requirement(ROOT_ID, kind, RequirementManagementMethod::Static);
+            self.solve_requirement(config, requirement_id)?;
+        }
+        Ok(group_id)
+    }
+
+    /// Reconciles the requirements of the root of the graph with the given [`World`] set: the explicit
+    /// requirements that aren't part of it are removed, the missing ones are added, and its groups are
+    /// updated to match the definition of the repositories (see [`DependencyGraph::require_group()`]).
+    ///
+    /// The graph must be solved afterwards.
+    pub fn apply_world(&mut self, config: &Config, world: &World) -> Result<(), Error> {
+        let removed: Vec<_> = self.nodes[&ROOT_ID]
+            .requirements()
+            .iter()
+            .filter(|requirement_id| {
+                let requirement = &self.requirements[requirement_id];
+
+                requirement.management_method() == RequirementManagementMethod::Static
+                    && !world.contains(requirement.kind())
+            })
+            .cloned()
+            .collect();
+        for requirement_id in removed {
+            self.remove_requirement(requirement_id);
+        }
+
+        for package_req in world.packages() {
+            let kind = RequirementKind::Package {
+                package_req: package_req.clone(),
+            };
+
+            if !self.node_has_requirement(&self.nodes[&ROOT_ID], &kind) {
+                self.node_add_requirement(ROOT_ID, kind, RequirementManagementMethod::Static);
+            }
+        }
+
+        for name in world.groups() {
+            self.require_group(config, name)?;
+        }
+        Ok(())
+    }
+
     /// Removes a node from the dependency graph, and all requirements linked from/to it
     pub fn remove_node(&mut self, node_id: NodeID) {
         let dependents = self
@@ -407,9 +727,9 @@ impl<'lock_file> DependencyGraph<'lock_file> {
             NodeKind::Group { name } => {
                 self.node_names.remove(&NodeName::Group(name.clone()));
             }
-            NodeKind::Package { id } => {
+            NodeKind::Package { id, slot } => {
                 self.node_names
-                    .remove(&NodeName::Package(id.clone().into()));
+                    .remove(&NodeName::Package(id.clone().into(), slot.clone()));
             }
         }
 
@@ -431,36 +751,244 @@ impl<'lock_file> DependencyGraph<'lock_file> {
         }
     }
 
-    /// Removes orphan nodes from the dependency graph, that is, nodes not fulfilling any requirement
-    fn remove_orphan_nodes(&mut self) {
+    /// Returns the [`NodeID`]s of the orphan nodes of the dependency graph, that is, nodes that
+    /// can't be reached from the root through requirements
+    pub fn orphan_nodes(&self) -> Vec<NodeID> {
         let mut to_keep = HashSet::new();
 
         self.remove_orphans_rec(&mut to_keep, ROOT_ID);
 
-        let to_remove: Vec<_> = self
+        let mut orphans: Vec<_> = self
             .nodes
             .keys()
             .filter(|node_id| !to_keep.contains(node_id))
             .cloned()
             .collect();
+        orphans.sort();
+        orphans
+    }
 
-        to_remove
+    /// Removes orphan nodes from the dependency graph, that is, nodes not fulfilling any requirement
+    fn remove_orphan_nodes(&mut self) {
+        self.orphan_nodes()
             .into_iter()
             .for_each(|node_id| self.remove_node(node_id));
     }
 
-    fn find_node_for_requirement(&self, requirement: &PackageRequirement) -> Option<NodeID> {
-        self.node_names
+    /// Tests whether a node was explicitly required, that is, whether it fulfills a static
+    /// requirement instead of being pulled as a dependency of another package
+    pub fn is_explicitly_required(&self, node_id: NodeID) -> bool {
+        self.nodes[&node_id]
+            .dependents()
+            .iter()
+            .any(|requirement_id| {
+                self.requirements[requirement_id].management_method()
+                    == RequirementManagementMethod::Static
+            })
+    }
+
+    /// Returns the packages that are installed but no longer required by any explicitly
+    /// required package, ordered so that dependents come before their dependencies.
+    ///
+    /// These are the packages of the orphan nodes of the graph, followed by the given installed
+    /// packages that aren't part of the graph at all.
+    pub fn orphan_packages(&self, installed: &[PackageID]) -> Vec<PackageID> {
+        let orphans: HashSet<_> = self.orphan_nodes().into_iter().collect();
+
+        // Dependencies are visited before the nodes requiring them, so the order is reversed afterwards
+        let mut visited = HashSet::new();
+        let mut ordered = Vec::new();
+        let mut roots: Vec<_> = orphans.iter().cloned().collect();
+        roots.sort();
+        for node_id in roots {
+            self.visit_orphan(node_id, &orphans, &mut visited, &mut ordered);
+        }
+        ordered.reverse();
+
+        let mut packages: Vec<_> = ordered
+            .into_iter()
+            .filter_map(|node_id| match self.nodes[&node_id].kind() {
+                NodeKind::Package { id, .. } => Some(id.clone()),
+                NodeKind::Group { .. } => None,
+            })
+            .collect();
+
+        let in_graph: HashSet<_> = self
+            .nodes
+            .values()
+            .filter_map(|node| match node.kind() {
+                NodeKind::Package { id, .. } => Some(id),
+                NodeKind::Group { .. } => None,
+            })
+            .collect();
+
+        for id in installed {
+            if !in_graph.contains(id) && !packages.contains(id) {
+                packages.push(id.clone());
+            }
+        }
+        packages
+    }
+
+    fn visit_orphan(
+        &self,
+        node_id: NodeID,
+        orphans: &HashSet<NodeID>,
+        visited: &mut HashSet<NodeID>,
+        ordered: &mut Vec<NodeID>,
+    ) {
+        if !orphans.contains(&node_id) || !visited.insert(node_id) {
+            return;
+        }
+
+        for requirement_id in self.nodes[&node_id].requirements() {
+            if let Some(child_id) = self.requirements[requirement_id].fulfilling_node_id() {
+                self.visit_orphan(*child_id, orphans, visited, ordered);
+            }
+        }
+        ordered.push(node_id);
+    }
+
+    /// Returns the nodes of the package targeted by the given requirement, one for each slot it is in, or only the
+    /// one of the slot it requires if any, sorted by slot
+    fn find_nodes_for_requirement(&self, requirement: &PackageRequirement) -> Vec<NodeID> {
+        let mut found: Vec<_> = self
+            .node_names
             .iter()
-            .find(|(node_name, _)| {
-                if let Some(full_name) = node_name.package_name() {
-                    requirement.name() == full_name.name()
+            .filter_map(|(node_name, node_id)| match node_name {
+                NodeName::Package(full_name, slot)
+                    if requirement.name() == full_name.name()
                         && requirement.category() == full_name.category()
-                } else {
-                    false
+                        && requirement.matches_slot(slot) =>
+                {
+                    Some((slot, *node_id))
+                }
+                _ => None,
+            })
+            .collect();
+
+        found.sort();
+        found.into_iter().map(|(_, node_id)| node_id).collect()
+    }
+
+    /// Returns the requirements on the package of the given node that are held by its dependents, which the package
+    /// replacing it in its slot must fulfill too, along with the node holding each of them
+    fn held_requirements(
+        &self,
+        node_id: NodeID,
+        requirement: &PackageRequirement,
+    ) -> Vec<(PackageRequirement, NodeID)> {
+        let requirements = self.nodes[&node_id]
+            .dependents()
+            .iter()
+            .map(|requirement_id| &self.requirements[requirement_id]);
+
+        // Requirements on the virtual packages it provides don't depend on the version of the package
+        let mut held: Vec<_> = requirements
+            .filter_map(|held| match held.kind() {
+                RequirementKind::Package { package_req }
+                    if package_req.category() == requirement.category()
+                        && package_req.name() == requirement.name() =>
+                {
+                    Some((package_req.clone(), held.fulfilled_node_id()))
                 }
+                _ => None,
+            })
+            .collect();
+
+        held.sort();
+        held
+    }
+
+    /// Builds the error returned when no version of a package fulfills the given requirement along with the
+    /// requirements held on the versions already in the graph, naming the nodes holding them
+    fn requirement_solving_error(
+        &self,
+        requirement: &PackageRequirement,
+        held: &[(PackageRequirement, NodeID)],
+    ) -> Error {
+        let conflicts: Vec<_> = held
+            .iter()
+            .filter(|(held_req, _)| held_req != requirement)
+            .map(|(held_req, node_id)| {
+                format!("'{}' (required by {})", held_req, self.nodes[node_id])
             })
-            .map(|(_, id)| *id)
+            .collect();
+
+        let description = if conflicts.is_empty() {
+            requirement.to_string()
+        } else {
+            format!(
+                "{}, which conflicts with {}",
+                requirement,
+                conflicts.join(" and ")
+            )
+        };
+        format_err!("{}", description)
+            .context(DependencyGraphErrorKind::RequirementSolvingError)
+            .into()
+    }
+
+    /// Selects the package providing the virtual package targeted by the given requirement, and returns a
+    /// requirement on it.
+    ///
+    /// A provider already in the graph is kept, otherwise the most preferred one according to the configuration is
+    /// selected, or the only one if there is no preference. [`None`] is returned if no package provides it.
+    fn select_provider(
+        &self,
+        config: &Config,
+        requirement: &PackageRequirement,
+    ) -> Result<Option<PackageRequirement>, Error> {
+        let virtual_name =
+            PackageShortName::from(requirement.category().clone(), requirement.name().clone());
+
+        let mut providers = config
+            .available_packages_cache_internal(self.phantom)
+            .providers(&virtual_name)?;
+        if let Some(repository) = requirement.repository() {
+            providers.retain(|provider| provider.repository() == repository);
+        }
+
+        let preferred = config
+            .providers()
+            .get(&virtual_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let selected = providers
+            .iter()
+            .find(|provider| !self.get_package_node_ids(&provider.full_name()).is_empty())
+            .or_else(|| {
+                preferred.iter().find_map(|preferred| {
+                    providers
+                        .iter()
+                        .find(|provider| provider.manifest().short_name() == *preferred)
+                })
+            });
+
+        match (selected, providers.len()) {
+            (Some(provider), _) => Ok(Some(
+                PackageRequirement::from_id(&provider.id()).any_version(),
+            )),
+            (None, 0) => Ok(None),
+            (None, 1) => Ok(Some(
+                PackageRequirement::from_id(&providers[0].id()).any_version(),
+            )),
+            (None, _) => {
+                let names: Vec<_> = providers
+                    .iter()
+                    .map(|provider| provider.full_name())
+                    .collect();
+                let list: Vec<_> = names.iter().map(ToString::to_string).collect();
+
+                Err(format_err!("{}", list.join(", "))
+                    .context(DependencyGraphErrorKind::AmbiguousProvider(
+                        virtual_name,
+                        names,
+                    ))
+                    .into())
+            }
+        }
     }
 
     fn solve_package_requirement(
@@ -468,46 +996,39 @@ impl<'lock_file> DependencyGraph<'lock_file> {
         config: &Config,
         requirement: PackageRequirement,
     ) -> Result<NodeID, Error> {
-        // The list of requirements the package must fulfill.
-        let mut requirements = Vec::new();
-        let node_id_opt = self.find_node_for_requirement(&requirement);
-
-        // Test whether a package with the same PackageFullName is already within the dependency graph
-        if let Some(package_node_id) = node_id_opt {
-            let node = &self.nodes[&package_node_id];
+        // The nodes of the package already within the dependency graph, one for each slot it is in
+        let node_ids = self.find_nodes_for_requirement(&requirement);
 
-            // Since a version of the package is already in the graph, test whether it matches the new requirement
-            if let NodeKind::Package { id } = node.kind() {
+        // Test whether one of them matches the new requirement
+        for node_id in &node_ids {
+            if let NodeKind::Package { id, .. } = self.nodes[node_id].kind() {
                 if requirement.matches(id) {
                     // If that's the case, we can stop here, as the requirement is already fulfilled
-                    return Ok(package_node_id);
-                }
-            }
-
-            // At this point, a version of the package is already in the graph, but it does not match the new requirement.
-            // Therefore, the version of the package is going to change in order to solve the new requirement.
-            //
-            // However, the old requirements on the installed version of the package should be preserved,
-            // thus we add them to the requirements to fulfill.
-
-            let requirement_kinds = node
-                .dependents()
-                .iter()
-                .map(|requirement_id| &self.requirements[requirement_id])
-                .map(|requirement| requirement.kind());
-
-            for requirement_kind in requirement_kinds {
-                if let RequirementKind::Package { package_req } = requirement_kind {
-                    requirements.push(package_req.clone());
+                    return Ok(*node_id);
                 }
             }
         }
 
-        // We add the new requirement to the requirements to fulfill
-        requirements.push(requirement.clone());
+        // At this point, the versions of the package already in the graph do not match the new requirement.
+        // Therefore, either the version of the package in the slot of the selected version is going to change in
+        // order to solve the new requirement, or the selected version is added in its own slot.
+        //
+        // However, the old requirements on the version in that slot should be preserved, thus they are added to the
+        // requirements to fulfill.
+        let slots: Vec<_> = node_ids
+            .iter()
+            .filter_map(|node_id| match self.nodes[node_id].kind() {
+                NodeKind::Package { slot, .. } => Some((
+                    slot.clone(),
+                    *node_id,
+                    self.held_requirements(*node_id, &requirement),
+                )),
+                NodeKind::Group { .. } => None,
+            })
+            .collect();
 
-        // Look for the newest version matching all the requirements
-        let find_matching_packages = || -> Result<Option<QueryResult>, Error> {
+        // Look for the newest version matching all the requirements on its slot
+        let find_matching_packages = || -> Result<Option<(QueryResult, Option<NodeID>)>, Error> {
             let available_packages = config
                 .available_packages_cache_internal(self.phantom)
                 .query(&requirement.clone().any_version().into())
@@ -515,29 +1036,64 @@ impl<'lock_file> DependencyGraph<'lock_file> {
                 .perform();
 
             for package in available_packages? {
-                let is_valid = requirements
-                    .iter()
-                    .all(|requirement| requirement.matches(&package.id()));
+                let id = package.id();
+                let slot = package.manifest().slot();
+                let replaced = slots.iter().find(|(node_slot, _, _)| node_slot == slot);
+
+                let is_valid = requirement.matches(&id)
+                    && replaced.map_or(true, |(_, _, requirements)| {
+                        requirements.iter().all(|(requirement, _)| {
+                            requirement.matches(&id) && requirement.matches_slot(slot)
+                        })
+                    });
                 if is_valid {
-                    return Ok(Some(package));
+                    let node_id = replaced.map(|(_, node_id, _)| *node_id);
+                    return Ok(Some((package, node_id)));
                 }
             }
             Ok(None)
         };
 
-        let package = find_matching_packages()?.ok_or_else(|| {
-            format_err!("{}", requirement)
-                .context(DependencyGraphErrorKind::RequirementSolvingError)
-        })?;
+        let (package, node_id_opt) = match find_matching_packages()? {
+            Some(found) => found,
+            None => {
+                // The requirement may be on a virtual package, fulfilled by the package providing it
+                if node_ids.is_empty() {
+                    if let Some(provider) = self.select_provider(config, &requirement)? {
+                        return self.solve_package_requirement(config, provider);
+                    }
+                }
+                let held: Vec<_> = slots
+                    .iter()
+                    .flat_map(|(_, _, requirements)| requirements.iter().cloned())
+                    .collect();
+                return Err(self.requirement_solving_error(&requirement, &held));
+            }
+        };
 
-        // If the new version is different from the old one, remove the old one
+        // If the new version is different from the old one of its slot, replace the old one, along with its
+        // dependencies, whether the new version is more recent or older
         if let Some(node_id) = node_id_opt {
             let node = self.nodes.get_mut(&node_id).expect("invalid node id");
-            let id = package.id();
+            let kind = NodeKind::Package {
+                id: package.id(),
+                slot: package.manifest().slot().clone(),
+            };
+
+            if *node.kind() != kind {
+                *node.kind_mut() = kind;
+
+                let old_requirements: Vec<_> = node.requirements().iter().cloned().collect();
+                for requirement_id in old_requirements {
+                    self.remove_requirement(requirement_id);
+                }
+                for dependency in package.manifest().dependencies() {
+                    let kind = RequirementKind::Package {
+                        package_req: dependency.clone(),
+                    };
+                    self.node_add_requirement(node_id, kind, RequirementManagementMethod::Auto);
+                }
 
-            if (*node.kind() != NodeKind::Package { id: id.clone() }) {
-                *node.kind_mut() = NodeKind::Package { id };
-                node.requirements_mut().clear();
                 self.solve_node(config, node_id)?;
                 Ok(node_id)
             } else {
@@ -604,23 +1160,140 @@ impl<'lock_file> DependencyGraph<'lock_file> {
         Ok(())
     }
 
+    /// Checks that no package of the graph conflicts with another one, according to the manifests of the
+    /// available packages cache
+    fn check_conflicts(&self, config: &Config) -> Result<(), Error> {
+        let available = config.available_packages_cache_internal(self.phantom);
+        let mut packages: Vec<_> = self
+            .nodes
+            .values()
+            .filter_map(|node| match node.kind() {
+                NodeKind::Package { id, .. } => Some(id),
+                NodeKind::Group { .. } => None,
+            })
+            .collect();
+        packages.sort();
+
+        for package in &packages {
+            let manifest = available
+                .query(&SoftPackageRequirement::from_id(package))
+                .perform()?
+                .into_iter()
+                .find(|result| result.id() == **package)
+                .map(|result| result.manifest().clone());
+
+            let conflicts = manifest.iter().flat_map(|manifest| manifest.conflicts());
+            for conflict in conflicts {
+                let conflicting = packages
+                    .iter()
+                    .find(|other| *other != package && conflict.matches(other));
+
+                if let Some(other) = conflicting {
+                    return Err(format_err!("{} conflicts with {}", package, other)
+                        .context(DependencyGraphErrorKind::ConflictingPackages)
+                        .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Solves the graph (attempts to fulfill every requirement)
+    ///
+    /// The most recent version matching the requirements on a package is selected, unless a version of it is already
+    /// in the graph. When this leads to a dead end, because a selected package requires a version of another one that
+    /// a third package doesn't support, the versions of all the packages are selected again by the [`Solver`], which
+    /// backtracks on such conflicts.
+    #[inline]
     pub fn solve(&mut self, config: &Config) -> Result<(), Error> {
-        self.solve_node(config, ROOT_ID)?;
+        self.solve_with_pins(config, &[])
+    }
+
+    /// Solves the graph like [`DependencyGraph::solve()`], keeping the packages fulfilling the given requirements,
+    /// which must already be solved, in their version.
+    ///
+    /// If the [`Solver`] is needed, the graph is rebuilt from its requirements, and the given ones are solved again
+    /// before the others.
+    fn solve_with_pins(&mut self, config: &Config, pins: &[RequirementID]) -> Result<(), Error> {
+        // The solver prefers the versions already in the graph, which may be replaced while solving it
+        let current: Vec<_> = self
+            .nodes
+            .values()
+            .filter_map(|node| match node.kind() {
+                NodeKind::Package { id, .. } => Some(id.clone()),
+                NodeKind::Group { .. } => None,
+            })
+            .collect();
+
+        if let Err(e) = self.solve_node(config, ROOT_ID) {
+            // Only dead ends are worth backtracking, the other failures would happen again
+            let is_dead_end = e
+                .downcast_ref::<Context<DependencyGraphErrorKind>>()
+                .map_or(false, |context| {
+                    *context.get_context() == DependencyGraphErrorKind::RequirementSolvingError
+                });
+            if !is_dead_end {
+                return Err(e);
+            }
+
+            self.unsolve();
+            for pin in pins {
+                self.solve_requirement(config, *pin)?;
+            }
+            self.solve_with_solver(config, current)?;
+        }
         self.remove_orphan_nodes();
+        self.check_conflicts(config)
+    }
+
+    /// Solves the graph with the versions selected by the [`Solver`] to fulfill its static requirements.
+    ///
+    /// The graph must be unsolved, apart from the pins of its caller. The selected versions are pinned while the graph
+    /// is solved again, dependencies first, so that each package finds its dependencies in the version selected for
+    /// them. The given versions are preferred, and virtual packages are left to the graph, which selects their
+    /// provider.
+    fn solve_with_solver(
+        &mut self,
+        config: &Config,
+        preferred: Vec<PackageID>,
+    ) -> Result<(), Error> {
+        let available = config.available_packages_cache_internal(self.phantom);
+
+        let mut targets = Vec::new();
+        for requirement in self.requirements.values() {
+            if let (RequirementManagementMethod::Static, RequirementKind::Package { package_req }) =
+                (requirement.management_method(), requirement.kind())
+            {
+                if solver::is_available(&available, package_req)? {
+                    targets.push(package_req.clone());
+                }
+            }
+        }
+        targets.sort();
+        targets.dedup();
+
+        let solution = Solver::from(&available)
+            .with_installed(preferred)
+            .solve(&targets)?;
+
+        let pins = self.pin(config, solution.install_order())?;
+        self.solve_node(config, ROOT_ID)?;
+        self.unpin(pins);
         Ok(())
     }
 
-    /// Updates the graph by removing automatic requirements, and solving again
-    pub fn update(&mut self, config: &Config) -> Result<(), Error> {
-        // First, remove auto requirements. Static requirements against packages are set as unsolved.
+    /// Removes the automatic requirements and the nodes left orphaned, and sets the static requirements on packages
+    /// as unsolved
+    fn unsolve(&mut self) {
         let mut marks = HashSet::new();
         for (requirement_id, requirement) in &mut self.requirements {
-            match requirement.management_method() {
-                RequirementManagementMethod::Auto => {
+            match (requirement.management_method(), requirement.kind()) {
+                (RequirementManagementMethod::Auto, _) => {
                     marks.insert(*requirement_id);
                 }
-                RequirementManagementMethod::Static => {
+                // Groups are kept, as they can't be solved again once orphaned
+                (RequirementManagementMethod::Static, RequirementKind::Group { .. }) => (),
+                (RequirementManagementMethod::Static, RequirementKind::Package { .. }) => {
                     // Unsolve it
                     if let Some(child_id) = requirement.fulfilling_node_id() {
                         let child = self.nodes.get_mut(&child_id).expect("invalid node id");
@@ -635,11 +1308,82 @@ impl<'lock_file> DependencyGraph<'lock_file> {
             self.remove_requirement(requirement_id);
         }
 
-        // Then, remove orphan nodes
         // We should only have groups left, roughly.
         self.remove_orphan_nodes();
+    }
+
+    /// Pins the given packages to their version, solving the pins in the given order, and returns the requirements
+    /// pinning them.
+    ///
+    /// Once pinned, the other requirements on these packages are fulfilled by their pinned version.
+    fn pin<'a, I>(&mut self, config: &Config, ids: I) -> Result<Vec<RequirementID>, Error>
+    where
+        I: IntoIterator<Item = &'a PackageID>,
+    {
+        let mut pins = Vec::new();
+        for id in ids {
+            let kind = RequirementKind::Package {
+                package_req: PackageRequirement::from_id(id),
+            };
+            let pin = self.node_add_requirement(ROOT_ID, kind, RequirementManagementMethod::Static);
+
+            self.solve_requirement(config, pin)?;
+            pins.push(pin);
+        }
+        Ok(pins)
+    }
+
+    /// Removes the given pins, leaving the packages that aren't required anymore as orphans
+    fn unpin(&mut self, pins: Vec<RequirementID>) {
+        for requirement_id in pins {
+            self.remove_requirement(requirement_id);
+        }
+    }
 
-        // Solve the graph
-        self.solve(config)
+    /// Updates the graph by removing automatic requirements, and solving again.
+    ///
+    /// The packages held (see [`Config::held_packages()`]) keep their current version.
+    #[inline]
+    pub fn update(&mut self, config: &Config) -> Result<(), Error> {
+        self.update_packages(config, |_| true)
+    }
+
+    /// Updates the graph like [`DependencyGraph::update()`], but only lets the packages for which the given
+    /// predicate returns true change their version.
+    ///
+    /// The other packages keep their current version, like the held ones, so the update fails if a selected package
+    /// requires a more recent version of one of them.
+    pub fn update_packages<F>(&mut self, config: &Config, selected: F) -> Result<(), Error>
+    where
+        F: Fn(&PackageID) -> bool,
+    {
+        // Collect the versions of the held packages before their nodes are removed
+        let held_packages = config.held_packages()?;
+        let mut held: Vec<_> = self
+            .nodes
+            .values()
+            .filter_map(|node| match node.kind() {
+                NodeKind::Package { id, .. } if held_packages.is_held(id) || !selected(id) => {
+                    Some(id.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        held.sort();
+
+        // First, remove auto requirements. Static requirements against packages are set as unsolved.
+        self.unsolve();
+
+        // Pin the held packages to their version while the graph is solved. The pins are solved first, so that
+        // the other requirements on the held packages are fulfilled by their current version.
+        let pins = self.pin(config, &held)?;
+
+        // Solve the graph, solving the pins again first if it has to be rebuilt
+        self.solve_with_pins(config, &pins)?;
+
+        // The held packages that aren't required anymore are left as orphans, and removed
+        self.unpin(pins);
+        self.remove_orphan_nodes();
+        Ok(())
     }
 }
@@ -1,11 +1,13 @@
 //! Module to manipulate the dependency graph
 
 mod diff;
+mod export;
 mod graph;
 mod node;
 mod requirement;
 
 pub use self::diff::DependencyGraphDiff;
+pub use self::export::{DependencyGraphExport, ExportedEdge, ExportedNode, ExportedNodeKind};
 pub use self::graph::DependencyGraph;
 pub use self::node::{GroupName, NodeID, NodeKind, NodeName};
 pub use self::requirement::{
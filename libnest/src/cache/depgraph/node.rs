@@ -6,7 +6,7 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use serde_derive::{Deserialize, Serialize};
 
-use crate::package::{PackageFullName, PackageID};
+use crate::package::{split_slot, PackageFullName, PackageID, Slot};
 
 use super::super::errors::{GroupNameError, GroupNameErrorKind};
 use super::RequirementID;
@@ -68,6 +68,10 @@ pub enum NodeKind {
     Package {
         /// The [`PackageID`] of this node.
         id: PackageID,
+
+        /// The slot of the package, which lets other versions of the package be installed in other slots
+        #[serde(default, skip_serializing_if = "Slot::is_empty")]
+        slot: Slot,
     },
 }
 
@@ -147,14 +151,14 @@ impl std::fmt::Display for Node {
     }
 }
 
-/// The name of a node (that is, the [`GroupName`] or the [`PackageFullName`] for this node)
+/// The name of a node (that is, the [`GroupName`] or the [`PackageFullName`] and [`Slot`] for this node)
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum NodeName {
     /// The node name describes a group
     Group(GroupName),
 
-    /// The node name describes a package
-    Package(PackageFullName),
+    /// The node name describes a package, in the given slot
+    Package(PackageFullName, Slot),
 }
 
 impl NodeName {
@@ -169,7 +173,7 @@ impl NodeName {
 
     /// Retrieves the [`PackageFullName`] if the node name describes a package
     pub fn package_name(&self) -> Option<&PackageFullName> {
-        if let NodeName::Package(full_name) = self {
+        if let NodeName::Package(full_name, _) = self {
             Some(full_name)
         } else {
             None
@@ -181,7 +185,8 @@ impl std::fmt::Display for NodeName {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
             NodeName::Group(group_name) => f.write_str(group_name.as_str()),
-            NodeName::Package(full_name) => f.write_fmt(format_args!("{}", full_name)),
+            NodeName::Package(full_name, slot) if slot.is_empty() => write!(f, "{}", full_name),
+            NodeName::Package(full_name, slot) => write!(f, "{}:{}", full_name, slot),
         }
     }
 }
@@ -190,7 +195,7 @@ impl From<NodeKind> for NodeName {
     fn from(kind: NodeKind) -> Self {
         match kind {
             NodeKind::Group { name } => NodeName::Group(name),
-            NodeKind::Package { id } => NodeName::Package(id.into()),
+            NodeKind::Package { id, slot } => NodeName::Package(id.into(), slot),
         }
     }
 }
@@ -203,7 +208,7 @@ impl From<GroupName> for NodeName {
 
 impl From<PackageFullName> for NodeName {
     fn from(full_name: PackageFullName) -> Self {
-        NodeName::Package(full_name)
+        NodeName::Package(full_name, Slot::default())
     }
 }
 
@@ -214,7 +219,7 @@ impl serde::Serialize for NodeName {
     {
         match self {
             NodeName::Group(name) => serializer.serialize_str(name),
-            NodeName::Package(full_name) => full_name.serialize(serializer),
+            NodeName::Package(..) => serializer.serialize_str(&self.to_string()),
         }
     }
 }
@@ -235,16 +240,20 @@ impl<'de> serde::de::Visitor<'de> for NodeNameDeserializeVisitor {
         E: serde::de::Error,
     {
         match value.chars().next() {
-            Some('@') => GroupName::from_str(value).map(NodeName::Group).map_err(|_| {
-                E::custom(
-                    "the group's name doesn't follow the convention `@name`",
-                )
-            }),
-            _ => PackageFullName::from_str(value).map(NodeName::Package).map_err(|_| {
-                E::custom(
-                    "the package's full name doesn't follow the convention `repository::category/name`",
-                )
-            }),
+            Some('@') => GroupName::from_str(value)
+                .map(NodeName::Group)
+                .map_err(|_| E::custom("the group's name doesn't follow the convention `@name`")),
+            _ => {
+                let (full_name, slot) = split_slot(value);
+                let slot = slot.map_or_else(|| Ok(Slot::default()), Slot::parse);
+
+                match (PackageFullName::from_str(&full_name), slot) {
+                    (Ok(full_name), Ok(slot)) => Ok(NodeName::Package(full_name, slot)),
+                    _ => Err(E::custom(
+                        "the package's full name doesn't follow the convention `repository::category/name:slot`",
+                    )),
+                }
+            }
         }
     }
 }
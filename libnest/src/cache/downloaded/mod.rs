@@ -1,26 +1,96 @@
 //! Module to query and manipulate the cache of downloaded packages
 
-use std::fs;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+use failure::{Error, ResultExt};
+use log::warn;
+use semver::Version;
+
+use crate::cache::available::AvailablePackages;
+use crate::cache::errors::CacheErrorKind;
+use crate::cache::keys::Keystore;
+use crate::config::{DownloadCacheConfig, RepositoryConfig};
 use crate::lock_file::LockFileOwnership;
-use crate::package::{NPFExplorationError, NPFExplorer, PackageID};
+use crate::package::{
+    CategoryName, Checksum, NPFExplorationError, NPFExplorationErrorKind, NPFExplorer,
+    PackageFullName, PackageID, PackageName, RepositoryName, TrustedKey,
+};
+use crate::repository::{Repository, LOCAL_REPOSITORY};
+
+/// A package whose NPF is in the cache of downloaded packages
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct CachedPackage {
+    id: PackageID,
+    size: u64,
+    accessed: SystemTime,
+}
+
+impl CachedPackage {
+    /// Returns the package
+    #[inline]
+    pub fn id(&self) -> &PackageID {
+        &self.id
+    }
+
+    /// Returns the size of the package's NPF, in bytes
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns when the package's NPF was last used, or last modified if the filesystem doesn't record accesses
+    #[inline]
+    pub fn accessed(&self) -> SystemTime {
+        self.accessed
+    }
+}
+
+/// Lists the entries of the given directory that are directories themselves, along with their name
+fn sub_directories(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut dirs = Vec::new();
+
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                    dirs.push((name.to_string(), path.clone()));
+                }
+            }
+        }
+    }
+    Ok(dirs)
+}
 
 /// Structure representing the cache of downloaded packages
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct DownloadedPackages<'cache_root, 'lock_file> {
     cache_root: &'cache_root Path,
+    repositories: &'cache_root HashMap<String, RepositoryConfig>,
+    keystore: Keystore,
+    available: AvailablePackages<'cache_root, 'lock_file>,
     phantom: PhantomData<&'lock_file LockFileOwnership>,
 }
 
 impl<'cache_root, 'lock_file> DownloadedPackages<'cache_root, 'lock_file> {
     pub(crate) fn from(
         cache_root: &'cache_root Path,
+        repositories: &'cache_root HashMap<String, RepositoryConfig>,
+        keystore: Keystore,
+        available: AvailablePackages<'cache_root, 'lock_file>,
         phantom: PhantomData<&'lock_file LockFileOwnership>,
     ) -> Self {
         Self {
             cache_root,
+            repositories,
+            keystore,
+            available,
             phantom,
         }
     }
@@ -38,9 +108,159 @@ impl<'cache_root, 'lock_file> DownloadedPackages<'cache_root, 'lock_file> {
         self.package_path(package).exists()
     }
 
-    /// Opens a downloaded package for exploration
+    /// Checks that the downloaded NPF of a given package matches the checksum advertised by its repository.
+    ///
+    /// A package whose checksum is unknown is only considered valid if its repository allows unsigned packages, or
+    /// if it was added from a local NPF, whose signature is checked when it is added.
+    pub fn verify_package(&self, package: &PackageID) -> Result<(), NPFExplorationError> {
+        let path = self.package_path(package);
+        let expected = self
+            .available
+            .package_checksum(package)
+            .map_err(|_| NPFExplorationErrorKind::FileIOError(path.clone()))?;
+
+        let expected = match expected {
+            Some(expected) => expected,
+            None => {
+                let repository = package.repository().as_str();
+                let may_be_unknown = match self.repositories.get(repository) {
+                    Some(config) => config.allow_unsigned(),
+                    None => repository == LOCAL_REPOSITORY,
+                };
+
+                if may_be_unknown {
+                    return Ok(());
+                }
+                return Err(NPFExplorationErrorKind::UnknownChecksum.into());
+            }
+        };
+
+        let checksum = File::open(&path)
+            .and_then(|mut file| Checksum::from_reader(&mut file))
+            .map_err(|_| NPFExplorationErrorKind::FileIOError(path.clone()))?;
+
+        if checksum != expected {
+            return Err(NPFExplorationErrorKind::ChecksumMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// Opens a downloaded package for exploration, after verifying its checksum.
+    ///
+    /// The package is read in place, without being unpacked.
+    ///
+    /// The package must be signed by one of the keys trusted by its repository, either in the configuration or in the
+    /// keystore, unless the repository allows unsigned packages. The packages added from local NPFs, whose signature
+    /// is checked when they are added, are an exception.
     pub fn explore_package(&self, package: &PackageID) -> Result<NPFExplorer, NPFExplorationError> {
-        NPFExplorer::from(self.package_path(package))
+        let repository = package.repository().as_str();
+        let trusted_keys = match self.repositories.get(repository) {
+            Some(config) if config.allow_unsigned() => None,
+            Some(config) => Some(self.keystore.trusted_keys(repository, config)),
+            None if repository == LOCAL_REPOSITORY => None,
+            None => Some(Vec::new()),
+        };
+
+        self.verify_package(package)?;
+        NPFExplorer::open_streaming(
+            self.package_path(package),
+            trusted_keys.as_ref().map(Vec::as_slice),
+        )
+    }
+
+    /// Adds a locally built NPF to the cache, as a package of the [`LOCAL_REPOSITORY`], and returns its [`PackageID`].
+    ///
+    /// The NPF is explored first, to check that it is valid and, if `trusted_keys` is not [`None`], that it is signed
+    /// by one of the given keys. Its manifest is then added to the cache of available packages, so that it can be
+    /// installed like the packages of the other repositories.
+    pub fn add_local_package<P: AsRef<Path>>(
+        &self,
+        npf_path: P,
+        trusted_keys: Option<&[TrustedKey]>,
+    ) -> Result<PackageID, Error> {
+        Ok(self.add_local_npf(npf_path.as_ref(), trusted_keys)?.0)
+    }
+
+    /// Adds a locally built NPF to the cache (see [`add_local_package()`]), and returns its [`PackageID`] along with
+    /// whether the cache changed, which it doesn't if the same NPF was already added.
+    ///
+    /// [`add_local_package()`]: #method.add_local_package
+    fn add_local_npf(
+        &self,
+        npf_path: &Path,
+        trusted_keys: Option<&[TrustedKey]>,
+    ) -> Result<(PackageID, bool), Error> {
+        let manifest = NPFExplorer::open_streaming(npf_path, trusted_keys)
+            .with_context(|_| npf_path.display().to_string())?
+            .manifest()
+            .clone();
+        let checksum = File::open(npf_path)
+            .and_then(|mut file| Checksum::from_reader(&mut file))
+            .with_context(|_| npf_path.display().to_string())?;
+
+        let id = manifest.id(RepositoryName::parse(LOCAL_REPOSITORY)?);
+        let path = self.package_path(&id);
+
+        if path.exists() && self.available.package_checksum(&id)? == Some(checksum) {
+            return Ok((id, false));
+        }
+
+        // The NPF is copied next to its final path first, so that the cache never holds a partial copy
+        let res: Result<_, io::Error> = try {
+            let partial_path = path.with_extension("tmp");
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(npf_path, &partial_path)?;
+            fs::rename(&partial_path, &path)?;
+        };
+        res.context(path.display().to_string())
+            .context(CacheErrorKind::CacheWriteError)?;
+
+        self.available.add_local_package(&manifest, checksum)?;
+        Ok((id, true))
+    }
+
+    /// Adds the NPFs of the given local overlay folder to the cache, as packages of the [`LOCAL_REPOSITORY`] (see
+    /// [`add_local_package()`]), and returns the packages that are new or were modified since they were last added.
+    ///
+    /// The NPFs that can't be added are skipped with a warning, so that a broken package being worked on doesn't
+    /// prevent the others from being used.
+    ///
+    /// [`add_local_package()`]: #method.add_local_package
+    pub fn index_local_overlay<P: AsRef<Path>>(&self, overlay: P) -> Result<Vec<PackageID>, Error> {
+        let overlay = overlay.as_ref();
+        let mut indexed = Vec::new();
+
+        if !overlay.is_dir() {
+            return Ok(indexed);
+        }
+
+        let mut npf_paths = Vec::new();
+        for entry in fs::read_dir(overlay).with_context(|_| overlay.display().to_string())? {
+            let path = entry
+                .with_context(|_| overlay.display().to_string())?
+                .path();
+
+            if path.is_file() && path.extension().map_or(false, |ext| ext == "nest") {
+                npf_paths.push(path);
+            }
+        }
+        npf_paths.sort();
+
+        for npf_path in npf_paths {
+            match self.add_local_npf(&npf_path, None) {
+                Ok((id, true)) => indexed.push(id),
+                Ok((_, false)) => (),
+                Err(e) => warn!(
+                    "ignoring the local package {}: {}",
+                    npf_path.display(),
+                    e.find_root_cause()
+                ),
+            }
+        }
+        Ok(indexed)
     }
 
     /// Removes the NPF for a given package
@@ -49,4 +269,159 @@ impl<'cache_root, 'lock_file> DownloadedPackages<'cache_root, 'lock_file> {
 
         fs::remove_file(&path)
     }
+
+    /// Erases the whole cache, including partial downloads and deltas
+    pub fn erase(&self) -> Result<(), Error> {
+        if self.cache_root.exists() {
+            fs::remove_dir_all(self.cache_root)
+                .context(self.cache_root.display().to_string())
+                .context(CacheErrorKind::CacheClearError)?;
+        }
+        Ok(())
+    }
+
+    /// Erases the packages of a given [`Repository`] from the cache, including partial downloads and deltas
+    pub fn erase_repository(&self, repository: &Repository) -> Result<(), Error> {
+        let path = self.cache_root.join(repository.name());
+
+        if path.is_dir() {
+            fs::remove_dir_all(&path)
+                .context(path.display().to_string())
+                .context(CacheErrorKind::CacheClearError)?;
+        }
+        Ok(())
+    }
+
+    /// Lists the packages whose NPF is in the cache, sorted by [`PackageID`].
+    ///
+    /// Partial downloads and deltas are ignored.
+    pub fn packages(&self) -> Result<Vec<CachedPackage>, std::io::Error> {
+        let mut packages = Vec::new();
+
+        for (repository, repository_dir) in sub_directories(self.cache_root)? {
+            for (category, category_dir) in sub_directories(&repository_dir)? {
+                for (name, package_dir) in sub_directories(&category_dir)? {
+                    let full_name = match (
+                        RepositoryName::parse(&repository),
+                        CategoryName::parse(&category),
+                        PackageName::parse(&name),
+                    ) {
+                        (Ok(repository), Ok(category), Ok(name)) => {
+                            PackageFullName::from(repository, category, name)
+                        }
+                        _ => continue,
+                    };
+
+                    for entry in fs::read_dir(&package_dir)? {
+                        let path = entry?.path();
+                        let version = path
+                            .file_name()
+                            .and_then(|file_name| file_name.to_str())
+                            .filter(|file_name| file_name.ends_with(".nest"))
+                            .and_then(|file_name| {
+                                let stem = &file_name[..file_name.len() - ".nest".len()];
+                                let prefix = format!("{}-", name);
+
+                                if stem.starts_with(&prefix) {
+                                    Version::parse(&stem[prefix.len()..]).ok()
+                                } else {
+                                    None
+                                }
+                            });
+
+                        if let Some(version) = version {
+                            let metadata = fs::metadata(&path)?;
+
+                            packages.push(CachedPackage {
+                                id: PackageID::from_full_name(full_name.clone(), version),
+                                size: metadata.len(),
+                                accessed: metadata.accessed().or_else(|_| metadata.modified())?,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        packages.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(packages)
+    }
+
+    /// Returns the packages that must be evicted from the cache to respect the given limits.
+    ///
+    /// The oldest versions of each package beyond the number of versions to keep are evicted first, and then
+    /// the least recently used packages, until the cache fits within its maximum size.
+    ///
+    /// The given installed packages are never evicted, even if a more recent version was downloaded, as their NPF is
+    /// needed to remove them. Neither are the packages added from local NPFs, which can't be downloaded again.
+    pub fn select_evictions(
+        &self,
+        limits: &DownloadCacheConfig,
+        installed: &[PackageID],
+    ) -> Result<Vec<CachedPackage>, std::io::Error> {
+        let (protected, mut kept): (Vec<_>, Vec<_>) =
+            self.packages()?.into_iter().partition(|package| {
+                installed.contains(&package.id)
+                    || package.id.repository().as_str() == LOCAL_REPOSITORY
+            });
+        let mut evicted = Vec::new();
+
+        if let Some(keep_versions) = limits.keep_versions() {
+            let mut versions: HashMap<PackageFullName, Vec<Version>> = HashMap::new();
+
+            for package in &kept {
+                versions
+                    .entry(package.id.clone().into())
+                    .or_insert_with(Vec::new)
+                    .push(package.id.version().clone());
+            }
+            for package_versions in versions.values_mut() {
+                package_versions.sort_by(|a, b| b.cmp(a));
+                package_versions.truncate(keep_versions);
+            }
+
+            let (keep, evict) = kept.into_iter().partition(|package| {
+                let full_name: PackageFullName = package.id.clone().into();
+
+                versions[&full_name].contains(package.id.version())
+            });
+            kept = keep;
+            evicted = evict;
+        }
+
+        if let Some(max_size) = limits.max_size() {
+            let mut size: u64 = kept
+                .iter()
+                .chain(protected.iter())
+                .map(|package| package.size)
+                .sum();
+
+            kept.sort_by_key(|package| package.accessed);
+            for package in kept {
+                if size <= max_size {
+                    break;
+                }
+                size -= package.size;
+                evicted.push(package);
+            }
+        }
+
+        evicted.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(evicted)
+    }
+
+    /// Evicts packages from the cache to respect the given limits (see [`select_evictions()`]), and returns them
+    ///
+    /// [`select_evictions()`]: #method.select_evictions
+    pub fn evict(
+        &self,
+        limits: &DownloadCacheConfig,
+        installed: &[PackageID],
+    ) -> Result<Vec<CachedPackage>, std::io::Error> {
+        let evicted = self.select_evictions(limits, installed)?;
+
+        for package in &evicted {
+            self.remove_package(&package.id)?;
+        }
+        Ok(evicted)
+    }
 }
@@ -2,6 +2,8 @@
 
 use failure::{Context, Fail};
 
+use crate::package::{PackageFullName, PackageID, PackageShortName};
+
 /// Error type for cache-related errors
 #[derive(Debug)]
 pub struct CacheError {
@@ -26,6 +28,10 @@ pub enum CacheErrorKind {
     /// Some data could not be cleared from the cache
     #[fail(display = "unable to clear data from the cache")]
     CacheClearError,
+
+    /// The pattern of a search is not a valid regular expression
+    #[fail(display = "invalid search pattern")]
+    InvalidSearchPattern,
 }
 
 use_as_error!(CacheError, CacheErrorKind);
@@ -53,7 +59,7 @@ pub struct DependencyGraphError {
 }
 
 /// Error kind describing a kind of error related to the dependency graph
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Clone, Eq, PartialEq, Debug, Fail)]
 pub enum DependencyGraphErrorKind {
     /// The requested package is unknown and cannot be found
     #[fail(display = "unknown package")]
@@ -74,6 +80,66 @@ pub enum DependencyGraphErrorKind {
     /// The given group cannot be found
     #[fail(display = "group not found")]
     GroupNotFound,
+
+    /// Two packages of the graph conflict with each other
+    #[fail(display = "conflicting packages")]
+    ConflictingPackages,
+
+    /// Several packages provide the given virtual package, and none of them is preferred
+    #[fail(display = "several packages provide {}", _0)]
+    AmbiguousProvider(PackageShortName, Vec<PackageFullName>),
 }
 
 use_as_error!(DependencyGraphError, DependencyGraphErrorKind);
+
+/// Error type for errors related to the resolution of the packages designated by users
+#[derive(Debug)]
+pub struct PackageQueryError {
+    inner: Context<PackageQueryErrorKind>,
+}
+
+/// Error kind describing a kind of error related to the resolution of the packages designated by users
+#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+pub enum PackageQueryErrorKind {
+    /// A part of the query is not a valid glob pattern
+    #[fail(display = "invalid package pattern '{}'", _0)]
+    InvalidPattern(String),
+
+    /// No package matches the query
+    #[fail(display = "no package found matching '{}'", _0)]
+    NoMatchingPackage(String),
+
+    /// Several packages match a query that must designate a single one
+    #[fail(display = "'{}' designates several packages", _0)]
+    AmbiguousPackage(String, Vec<PackageID>),
+}
+
+use_as_error!(PackageQueryError, PackageQueryErrorKind);
+
+/// Error type for errors related to the alternatives
+#[derive(Debug)]
+pub struct AlternativesError {
+    inner: Context<AlternativesErrorKind>,
+}
+
+/// Error kind describing a kind of error related to the alternatives
+#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+pub enum AlternativesErrorKind {
+    /// No installed package offers a target for the given link
+    #[fail(display = "{:?}: unknown alternative", _0)]
+    UnknownLink(std::path::PathBuf),
+
+    /// The given target isn't offered by any installed package for the given link
+    #[fail(display = "{:?}: not a candidate for {:?}", _1, _0)]
+    UnknownTarget(std::path::PathBuf, std::path::PathBuf),
+
+    /// The given link exists but isn't a symlink, and can't be replaced
+    #[fail(display = "{:?}: file exists and isn't a symlink", _0)]
+    NotASymlink(std::path::PathBuf),
+
+    /// The given link could not be created, updated or removed
+    #[fail(display = "{:?}: unable to update the link", _0)]
+    LinkUpdateError(std::path::PathBuf),
+}
+
+use_as_error!(AlternativesError, AlternativesErrorKind);
@@ -0,0 +1,152 @@
+//! Module to list the files shipped by a package.
+//!
+//! The files of an installed package are the ones recorded in its log when it was installed, while the files of
+//! a package that isn't installed are read from the data tarball of its downloaded package file.
+
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error, ResultExt};
+use tar::Archive;
+
+use crate::cache::installed::log::FileType;
+use crate::chroot::Chroot;
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{Kind, PackageID};
+
+/// Where the file list of a package was read from
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum PackageFilesSource {
+    /// The log written when the package was installed
+    Installed,
+
+    /// The data tarball of the downloaded package file
+    Downloaded,
+}
+
+/// A file shipped by a package
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct PackageFile {
+    path: PathBuf,
+    file_type: FileType,
+}
+
+impl PackageFile {
+    /// Returns the absolute path of the file, relative to the installation root
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the type of the file
+    #[inline]
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+}
+
+/// Structure holding the files shipped by a package
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PackageFiles {
+    id: PackageID,
+    source: PackageFilesSource,
+    files: Vec<PackageFile>,
+}
+
+impl PackageFiles {
+    /// Lists the files of the given package, sorted by path.
+    ///
+    /// Returns [`None`] if the package is neither installed nor downloaded, as its files are unknown until its
+    /// package file is downloaded.
+    pub fn query(
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        id: &PackageID,
+    ) -> Result<Option<PackageFiles>, Error> {
+        let installed = config.installed_packages_cache(lock_ownership);
+        let downloaded = config.downloaded_packages_cache(lock_ownership);
+
+        let (source, mut files) = if installed.is_installed(id) {
+            let log = installed
+                .package_log(id)
+                .with_context(|_| format_err!("unable to read the log of '{}'", id))?;
+            let files = log
+                .files()
+                .iter()
+                .map(|entry| PackageFile {
+                    path: entry.path().to_path_buf(),
+                    file_type: *entry.file_type(),
+                })
+                .collect();
+            (PackageFilesSource::Installed, files)
+        } else if downloaded.has_package(id) {
+            let files = Self::read_package_file(config, lock_ownership, id)
+                .with_context(|_| format_err!("unable to read the package file of '{}'", id))?;
+            (PackageFilesSource::Downloaded, files)
+        } else {
+            return Ok(None);
+        };
+
+        files.sort();
+        Ok(Some(PackageFiles {
+            id: id.clone(),
+            source,
+            files,
+        }))
+    }
+
+    /// Reads the entries of the data tarball of the given downloaded package
+    fn read_package_file(
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        id: &PackageID,
+    ) -> Result<Vec<PackageFile>, Error> {
+        let npf_explorer = config
+            .downloaded_packages_cache(lock_ownership)
+            .explore_package(id)?;
+        let mut files = Vec::new();
+        let prefix = config
+            .architecture_preferences()
+            .installation_prefix(npf_explorer.manifest().architecture());
+
+        if npf_explorer.manifest().kind() == Kind::Effective {
+            let mut tarball = npf_explorer
+                .open_data()?
+                .ok_or_else(|| format_err!("the package file has no data tarball"))?;
+            let mut archive = Archive::new(
+                npf_explorer
+                    .manifest()
+                    .compression()
+                    .decoder(&mut tarball)?,
+            );
+
+            for entry in archive.entries()? {
+                let entry = entry?;
+
+                files.push(PackageFile {
+                    path: prefix.with_content(&entry.path()?),
+                    file_type: entry.header().entry_type().into(),
+                });
+            }
+        }
+        Ok(files)
+    }
+
+    /// Returns the [`PackageID`] of the package
+    #[inline]
+    pub fn id(&self) -> &PackageID {
+        &self.id
+    }
+
+    /// Returns where the file list was read from
+    #[inline]
+    pub fn source(&self) -> PackageFilesSource {
+        self.source
+    }
+
+    /// Returns the files shipped by the package, sorted by path
+    #[inline]
+    pub fn files(&self) -> &[PackageFile] {
+        &self.files
+    }
+}
@@ -0,0 +1,100 @@
+//! Module to store the packages held by the user, whose version must not change when the system is upgraded.
+
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use failure::{Error, ResultExt};
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::lock_file::LockFileOwnership;
+use crate::package::{PackageID, PackageShortName};
+
+/// Structure representing the packages held at their installed version
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug, Default)]
+pub struct HeldPackages {
+    packages: BTreeSet<PackageShortName>,
+}
+
+impl HeldPackages {
+    /// Loads the held packages from the cache, or returns an empty list if it doesn't exist yet
+    pub fn load_from_cache<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            let file = File::open(path).with_context(|_| path.display().to_string())?;
+            let held =
+                serde_json::from_reader(&file).with_context(|_| path.display().to_string())?;
+            Ok(held)
+        } else {
+            Ok(HeldPackages::default())
+        }
+    }
+
+    /// Saves the held packages back to the cache
+    pub fn save_to_cache<P: AsRef<Path>>(
+        &self,
+        path: P,
+        _: &LockFileOwnership,
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|_| parent.display().to_string())?;
+        }
+
+        let mut file = File::create(path).with_context(|_| path.display().to_string())?;
+        serde_json::to_writer_pretty(&file, self).with_context(|_| path.display().to_string())?;
+        writeln!(file)?;
+        Ok(())
+    }
+
+    /// Returns the held packages, sorted by name
+    #[inline]
+    pub fn packages(&self) -> impl Iterator<Item = &PackageShortName> {
+        self.packages.iter()
+    }
+
+    /// Holds the given package. Returns false if it was already held.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::cache::held::HeldPackages;
+    /// use libnest::package::{PackageID, PackageShortName};
+    ///
+    /// let mut held = HeldPackages::default();
+    /// let coreutils = PackageShortName::parse("sys-bin/coreutils")?;
+    ///
+    /// assert!(held.hold(coreutils.clone()));
+    /// assert!(!held.hold(coreutils.clone()));
+    /// assert!(held.is_held(&PackageID::parse("stable::sys-bin/coreutils#1.0.0")?));
+    /// assert!(held.unhold(&coreutils));
+    /// assert!(!held.unhold(&coreutils));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn hold(&mut self, package: PackageShortName) -> bool {
+        self.packages.insert(package)
+    }
+
+    /// Releases the given package. Returns false if it wasn't held.
+    #[inline]
+    pub fn unhold(&mut self, package: &PackageShortName) -> bool {
+        self.packages.remove(package)
+    }
+
+    /// Returns true if the given package is held, whatever its repository and version
+    #[inline]
+    pub fn is_held(&self, id: &PackageID) -> bool {
+        self.packages
+            .iter()
+            .any(|package| package.category() == id.category() && package.name() == id.name())
+    }
+}
@@ -0,0 +1,251 @@
+//! Module to record the transactions performed on the system, so that they can be listed and undone.
+//!
+//! Each entry of the history is stored in its own file, named after its number, next to a copy of
+//! the dependency graph as it was before the transactions were performed.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use failure::{format_err, Error, ResultExt};
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::cache::depgraph::DependencyGraph;
+use crate::lock_file::LockFileOwnership;
+use crate::package::PackageID;
+use crate::transaction::{
+    InstallTransaction, ReinstallTransaction, RemoveTransaction, Transaction, UpgradeTransaction,
+};
+
+/// A change made to the installed packages, as recorded in the history
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum HistoryAction {
+    /// A package was installed
+    Install(PackageID),
+
+    /// A package was removed
+    Remove(PackageID),
+
+    /// A package was upgraded from a version to another
+    Upgrade {
+        /// The package that was replaced
+        from: PackageID,
+        /// The package that replaced it
+        to: PackageID,
+    },
+
+    /// The files of a package were extracted again
+    Reinstall(PackageID),
+}
+
+impl HistoryAction {
+    /// Returns the [`HistoryAction`] matching a performed [`Transaction`], if it changes the installed packages
+    pub fn from_transaction(transaction: &Transaction) -> Option<Self> {
+        match transaction {
+            Transaction::Pull(_) => None,
+            Transaction::Install(install) => Some(HistoryAction::Install(install.target().clone())),
+            Transaction::Remove(remove) => Some(HistoryAction::Remove(remove.target().clone())),
+            Transaction::Upgrade(upgrade) => Some(HistoryAction::Upgrade {
+                from: upgrade.old_target().clone(),
+                to: upgrade.new_target().clone(),
+            }),
+            Transaction::Reinstall(reinstall) => {
+                Some(HistoryAction::Reinstall(reinstall.target().clone()))
+            }
+        }
+    }
+
+    /// Returns the [`Transaction`] performing this action
+    pub fn to_transaction(&self) -> Transaction<'static, 'static> {
+        match self {
+            HistoryAction::Install(id) => {
+                Transaction::Install(InstallTransaction::from(id.clone()))
+            }
+            HistoryAction::Remove(id) => Transaction::Remove(RemoveTransaction::from(id.clone())),
+            HistoryAction::Upgrade { from, to } => {
+                Transaction::Upgrade(UpgradeTransaction::from(from.clone(), to.clone()))
+            }
+            HistoryAction::Reinstall(id) => {
+                Transaction::Reinstall(ReinstallTransaction::from(id.clone()))
+            }
+        }
+    }
+
+    /// Returns the [`Transaction`] undoing this action, if it changed the installed packages
+    pub fn to_reverse_transaction(&self) -> Option<Transaction<'static, 'static>> {
+        match self {
+            HistoryAction::Install(id) => {
+                Some(Transaction::Remove(RemoveTransaction::from(id.clone())))
+            }
+            HistoryAction::Remove(id) => {
+                Some(Transaction::Install(InstallTransaction::from(id.clone())))
+            }
+            HistoryAction::Upgrade { from, to } => Some(Transaction::Upgrade(
+                UpgradeTransaction::from(to.clone(), from.clone()),
+            )),
+            HistoryAction::Reinstall(_) => None,
+        }
+    }
+}
+
+/// An entry of the history: the changes made by a set of transactions performed together
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct HistoryEntry {
+    id: usize,
+    date: DateTime<Utc>,
+    actions: Vec<HistoryAction>,
+    #[serde(default)]
+    undoes: Option<usize>,
+    #[serde(default)]
+    snapshot: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Returns the number of this entry, incremented for each new entry
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the date at which the transactions were performed
+    #[inline]
+    pub fn date(&self) -> &DateTime<Utc> {
+        &self.date
+    }
+
+    /// Returns a slice of the changes made by the transactions, in the order they were made
+    #[inline]
+    pub fn actions(&self) -> &[HistoryAction] {
+        &self.actions
+    }
+
+    /// Returns the number of the entry undone by this one, if any
+    #[inline]
+    pub fn undoes(&self) -> Option<usize> {
+        self.undoes
+    }
+
+    /// Returns the name of the snapshot of the system taken before the transactions, if any
+    #[inline]
+    pub fn snapshot(&self) -> Option<&str> {
+        self.snapshot.as_ref().map(String::as_str)
+    }
+}
+
+/// Structure representing the history of the transactions performed on the system
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct History<'cache_root, 'lock_file> {
+    cache_root: &'cache_root Path,
+    phantom: PhantomData<&'lock_file LockFileOwnership>,
+}
+
+impl<'cache_root, 'lock_file> History<'cache_root, 'lock_file> {
+    pub(crate) fn from(
+        cache_root: &'cache_root Path,
+        phantom: PhantomData<&'lock_file LockFileOwnership>,
+    ) -> Self {
+        Self {
+            cache_root,
+            phantom,
+        }
+    }
+
+    fn entry_path(&self, id: usize) -> PathBuf {
+        self.cache_root.join(format!("{}.json", id))
+    }
+
+    fn dependency_graph_path(&self, id: usize) -> PathBuf {
+        self.cache_root.join(format!("{}.depgraph", id))
+    }
+
+    /// Returns all the entries of the history, from the oldest to the most recent
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>, Error> {
+        let mut entries = Vec::new();
+
+        if !self.cache_root.exists() {
+            return Ok(entries);
+        }
+
+        for dir_entry in
+            fs::read_dir(self.cache_root).with_context(|_| self.cache_root.display().to_string())?
+        {
+            let path = dir_entry?.path();
+
+            if path.extension().map_or(false, |ext| ext == "json") {
+                let file = File::open(&path).with_context(|_| path.display().to_string())?;
+                let entry: HistoryEntry =
+                    serde_json::from_reader(&file).with_context(|_| path.display().to_string())?;
+                entries.push(entry);
+            }
+        }
+
+        entries.sort_by_key(HistoryEntry::id);
+        Ok(entries)
+    }
+
+    /// Returns the most recent entry that can be undone, that is, the most recent entry that
+    /// neither undoes another one nor was undone itself
+    pub fn last_undoable_entry(&self) -> Result<Option<HistoryEntry>, Error> {
+        let mut undone = Vec::new();
+
+        for entry in self.entries()?.into_iter().rev() {
+            if let Some(id) = entry.undoes() {
+                undone.push(id);
+            } else if !undone.contains(&entry.id()) {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Loads the dependency graph as it was before the transactions of the given entry were performed
+    pub fn previous_dependency_graph(
+        &self,
+        entry: &HistoryEntry,
+    ) -> Result<DependencyGraph<'lock_file>, Error> {
+        let path = self.dependency_graph_path(entry.id());
+
+        if !path.exists() {
+            return Err(format_err!(
+                "no dependency graph was recorded for the history entry #{}",
+                entry.id()
+            ));
+        }
+        DependencyGraph::load_from_cache(path, self.phantom)
+    }
+
+    /// Records a new entry in the history, made of the given changes performed on top of the given dependency graph.
+    ///
+    /// If the changes undo a previous entry, its number must be given, as well as the name of the snapshot of the
+    /// system taken before them, if any.
+    pub fn record(
+        &self,
+        actions: Vec<HistoryAction>,
+        previous_graph: &DependencyGraph,
+        undoes: Option<usize>,
+        snapshot: Option<String>,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<HistoryEntry, Error> {
+        fs::create_dir_all(self.cache_root)
+            .with_context(|_| self.cache_root.display().to_string())?;
+
+        let entry = HistoryEntry {
+            id: self.entries()?.last().map_or(0, |last| last.id() + 1),
+            date: Utc::now(),
+            actions,
+            undoes,
+            snapshot,
+        };
+
+        previous_graph.save_to_cache(self.dependency_graph_path(entry.id()), lock_ownership)?;
+
+        let path = self.entry_path(entry.id());
+        let mut file = File::create(&path).with_context(|_| path.display().to_string())?;
+        serde_json::to_writer_pretty(&file, &entry).with_context(|_| path.display().to_string())?;
+        writeln!(file)?;
+        Ok(entry)
+    }
+}
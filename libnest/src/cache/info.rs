@@ -0,0 +1,173 @@
+//! Module to gather the details of a package, whether it is installed or only available.
+//!
+//! The details of a package come from the cache of available packages, or, for an installed package that is no
+//! longer offered by its repository, from its downloaded package file.
+
+use failure::Error;
+
+use crate::cache::available::AvailablePackagesCacheQueryStrategy;
+use crate::cache::depgraph::NodeKind;
+use crate::cache::query::PackageQuery;
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{Manifest, PackageFullName, PackageID, SoftPackageRequirement};
+use crate::transaction::TransactionPlan;
+
+/// Finds the manifest of the given installed package, in the cache of available packages or, if its repository no
+/// longer offers it, in its downloaded package file
+pub(crate) fn installed_manifest(
+    config: &Config,
+    lock_ownership: &LockFileOwnership,
+    id: &PackageID,
+) -> Result<Option<Manifest>, Error> {
+    let manifest = config
+        .available_packages_cache(lock_ownership)
+        .query(&SoftPackageRequirement::from_id(id))
+        .set_strategy(AvailablePackagesCacheQueryStrategy::AllMatchesUnsorted)
+        .perform()?
+        .into_iter()
+        .find(|result| result.id() == *id)
+        .map(|result| result.manifest().clone())
+        .or_else(|| {
+            config
+                .downloaded_packages_cache(lock_ownership)
+                .explore_package(id)
+                .ok()
+                .map(|explorer| explorer.manifest().clone())
+        });
+    Ok(manifest)
+}
+
+/// Structure holding the details of a package
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PackageInfo {
+    id: PackageID,
+    manifest: Manifest,
+    installed: bool,
+    explicitly_installed: bool,
+    required_by: Vec<NodeKind>,
+    installed_size: Option<u64>,
+}
+
+impl PackageInfo {
+    /// Gathers the details of the packages matching the given query.
+    ///
+    /// The installed packages matching the query come first, sorted, followed by the most recent version
+    /// of each matching package that isn't installed.
+    pub fn query(
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        query: &PackageQuery,
+    ) -> Result<Vec<PackageInfo>, Error> {
+        let graph = config.dependency_graph(lock_ownership)?;
+        let downloaded = config.downloaded_packages_cache(lock_ownership);
+        let installed = config.installed_packages_cache(lock_ownership);
+
+        let mut infos = Vec::new();
+
+        for node_id in graph.find_package_nodes(query) {
+            let id = match graph.nodes()[&node_id].kind() {
+                NodeKind::Package { id, .. } => id.clone(),
+                NodeKind::Group { .. } => continue,
+            };
+
+            let manifest = match installed_manifest(config, lock_ownership, &id)? {
+                Some(manifest) => manifest,
+                None => continue,
+            };
+
+            let required_by = graph
+                .reverse_dependencies(node_id, false)
+                .into_iter()
+                .filter(|dependent| *dependent != graph.root_id())
+                .map(|dependent| graph.nodes()[&dependent].kind().clone())
+                .collect();
+            let installed_size = installed.package_log(&id).ok().map(|log| {
+                log.installed_size()
+                    .unwrap_or_else(|| log.disk_usage(config.paths().root()))
+            });
+
+            infos.push(PackageInfo {
+                explicitly_installed: graph.is_explicitly_required(node_id),
+                id,
+                manifest,
+                installed: true,
+                required_by,
+                installed_size,
+            });
+        }
+
+        for result in query.available(
+            config,
+            lock_ownership,
+            AvailablePackagesCacheQueryStrategy::BestMatch,
+        )? {
+            let full_name = result.full_name();
+
+            let is_installed = infos.iter().any(|info: &PackageInfo| {
+                let installed_name: PackageFullName = info.id().clone().into();
+                installed_name == full_name
+            });
+            if is_installed {
+                continue;
+            }
+
+            // The size of a package is known once it is downloaded, or if its repository advertises it
+            let id = result.id();
+            let installed_size = if downloaded.has_package(&id) {
+                let mut plan = TransactionPlan::default();
+                plan.plan_installation(config, lock_ownership, &id)?;
+                Some(plan.installed_size())
+            } else {
+                result.manifest().installed_size()
+            };
+
+            infos.push(PackageInfo {
+                id,
+                manifest: result.manifest().clone(),
+                installed: false,
+                explicitly_installed: false,
+                required_by: Vec::new(),
+                installed_size,
+            });
+        }
+        Ok(infos)
+    }
+
+    /// Returns the [`PackageID`] of the package
+    #[inline]
+    pub fn id(&self) -> &PackageID {
+        &self.id
+    }
+
+    /// Returns the manifest of the package, holding its metadata and its dependencies
+    #[inline]
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Returns whether the package is installed
+    #[inline]
+    pub fn installed(&self) -> bool {
+        self.installed
+    }
+
+    /// Returns whether the package was explicitly installed, instead of being pulled as a dependency
+    #[inline]
+    pub fn explicitly_installed(&self) -> bool {
+        self.explicitly_installed
+    }
+
+    /// Returns the installed packages and groups requiring the package, sorted
+    #[inline]
+    pub fn required_by(&self) -> &[NodeKind] {
+        &self.required_by
+    }
+
+    /// Returns the total size, in bytes, of the files of the package, or [`None`] if it isn't known because
+    /// the package isn't downloaded and its repository doesn't advertise it
+    #[inline]
+    pub fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+}
@@ -1,12 +1,15 @@
 //! Module to query and manipulate the log files for installed packages
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use serde_derive::{Deserialize, Serialize};
 use tar::EntryType;
 
+use crate::chroot::Chroot;
+use crate::package::{Alternative, Checksum, Slot};
+
 /// Enumeration representing the different installable file types
 #[derive(Serialize, Deserialize, Copy, Clone, Ord, PartialOrd, PartialEq, Eq, Hash, Debug)]
 pub enum FileType {
@@ -84,17 +87,46 @@ impl From<EntryType> for FileType {
     }
 }
 
-/// Structure representing a file entry in a log
+/// Structure representing a file entry in a log.
+///
+/// Along with its path and type, an entry records the state of the file when it was installed:
+/// its permissions, its modification time and, for regular files, the [`Checksum`] of its content.
+/// These are missing for entries written by older versions of Nest.
 #[derive(Serialize, Deserialize, Clone, Ord, PartialOrd, PartialEq, Eq, Hash, Debug)]
 pub struct FileLogEntry {
     path: PathBuf,
     file_type: FileType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum: Option<Checksum>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mtime: Option<u64>,
 }
 
 impl FileLogEntry {
     /// Creates a new entry given a path and a file type
     pub fn new(path: PathBuf, file_type: FileType) -> Self {
-        FileLogEntry { path, file_type }
+        FileLogEntry {
+            path,
+            file_type,
+            checksum: None,
+            mode: None,
+            mtime: None,
+        }
+    }
+
+    /// Sets the permissions and the modification time of the file, as installed
+    pub fn with_metadata(mut self, mode: u32, mtime: u64) -> Self {
+        self.mode = Some(mode);
+        self.mtime = Some(mtime);
+        self
+    }
+
+    /// Sets the [`Checksum`] of the content of the file, as installed
+    pub fn with_checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = Some(checksum);
+        self
     }
 
     /// Returns a reference over the path for this entry
@@ -107,6 +139,24 @@ impl FileLogEntry {
         &self.file_type
     }
 
+    /// Returns the [`Checksum`] of the content of the file, if it is a regular file
+    #[inline]
+    pub fn checksum(&self) -> Option<&Checksum> {
+        self.checksum.as_ref()
+    }
+
+    /// Returns the permissions of the file (without its type bits), if they were recorded
+    #[inline]
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    /// Returns the modification time of the file, in seconds since the Unix epoch, if it was recorded
+    #[inline]
+    pub fn mtime(&self) -> Option<u64> {
+        self.mtime
+    }
+
     /// Returns a mutable reference over the path for this entry
     pub fn path_mut(&mut self) -> &mut PathBuf {
         &mut self.path
@@ -116,12 +166,36 @@ impl FileLogEntry {
     pub fn file_type_mut(&mut self) -> &mut FileType {
         &mut self.file_type
     }
+
+    /// Returns a mutable reference over the checksum for this entry
+    #[inline]
+    pub fn checksum_mut(&mut self) -> &mut Option<Checksum> {
+        &mut self.checksum
+    }
+
+    /// Returns a mutable reference over the permissions for this entry
+    #[inline]
+    pub fn mode_mut(&mut self) -> &mut Option<u32> {
+        &mut self.mode
+    }
+
+    /// Returns a mutable reference over the modification time for this entry
+    #[inline]
+    pub fn mtime_mut(&mut self) -> &mut Option<u64> {
+        &mut self.mtime
+    }
 }
 
 /// Structure representing the log for an installed package
 #[derive(Serialize, Deserialize, Clone, Ord, PartialOrd, PartialEq, Eq, Hash, Debug)]
 pub struct Log {
     files: Vec<FileLogEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    installed_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Slot::is_empty")]
+    slot: Slot,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<Alternative>,
 }
 
 impl Log {
@@ -144,11 +218,64 @@ impl Log {
 
     /// Creates a new Log
     pub fn new(files: Vec<FileLogEntry>) -> Self {
-        Self { files }
+        Self {
+            files,
+            installed_size: None,
+            slot: Slot::default(),
+            alternatives: Vec::new(),
+        }
+    }
+
+    /// Sets the total size, in bytes, of the regular files of the log, as they were installed
+    pub fn with_installed_size(mut self, installed_size: u64) -> Self {
+        self.installed_size = Some(installed_size);
+        self
+    }
+
+    /// Sets the [`Slot`] the package was installed in
+    pub fn with_slot(mut self, slot: Slot) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    /// Sets the alternatives the package offers a target for
+    pub fn with_alternatives(mut self, alternatives: Vec<Alternative>) -> Self {
+        self.alternatives = alternatives;
+        self
     }
 
     /// Returns a slice of the file entries in the log
     pub fn files(&self) -> &[FileLogEntry] {
         &self.files
     }
+
+    /// Returns the total size, in bytes, of the regular files of the log, as they were installed.
+    ///
+    /// It is unknown for packages installed by older versions of Nest.
+    pub fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+
+    /// Returns the [`Slot`] the package was installed in.
+    ///
+    /// It is the default slot for packages installed by older versions of Nest.
+    pub fn slot(&self) -> &Slot {
+        &self.slot
+    }
+
+    /// Returns the alternatives the package offers a target for
+    pub fn alternatives(&self) -> &[Alternative] {
+        &self.alternatives
+    }
+
+    /// Returns the total size, in bytes, of the regular files of the log, as they currently are in the
+    /// given installation root. Missing files are ignored.
+    pub fn disk_usage(&self, root: &Path) -> u64 {
+        self.files
+            .iter()
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| fs::symlink_metadata(root.with_content(entry.path())).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
 }
@@ -0,0 +1,97 @@
+//! Module to find and resolve the configuration files left to merge by upgrades.
+//!
+//! When a package is upgraded, the configuration files modified locally are kept, and their new version is
+//! installed next to them, with the `.nest-new` suffix. They are then left to the user to merge.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error, ResultExt};
+
+use crate::chroot::Chroot;
+use crate::package::PackageID;
+
+use super::log::FileType;
+use super::InstalledPackages;
+
+/// The suffix of the new version of a configuration file modified locally
+pub const NEW_CONFIG_FILE_SUFFIX: &str = ".nest-new";
+
+/// Returns the path of the new version of the given configuration file
+pub(crate) fn new_config_file_path(path: &Path) -> PathBuf {
+    let mut new_path = OsString::from(path.as_os_str());
+    new_path.push(NEW_CONFIG_FILE_SUFFIX);
+    PathBuf::from(new_path)
+}
+
+/// A configuration file modified locally, whose new version was installed next to it by an upgrade
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct PendingMerge {
+    path: PathBuf,
+    package: PackageID,
+}
+
+impl PendingMerge {
+    /// Returns the absolute path of the configuration file, relative to the installation root
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the absolute path of the new version of the configuration file, relative to the installation root
+    #[inline]
+    pub fn new_path(&self) -> PathBuf {
+        new_config_file_path(&self.path)
+    }
+
+    /// Returns the [`PackageID`] of the package owning the configuration file
+    #[inline]
+    pub fn package(&self) -> &PackageID {
+        &self.package
+    }
+
+    /// Replaces the configuration file with its new version, discarding the local modifications
+    pub fn accept(&self, root: &Path) -> Result<(), Error> {
+        let new_path = root.with_content(self.new_path());
+
+        fs::rename(&new_path, root.with_content(&self.path))
+            .with_context(|_| format_err!("unable to replace '{}'", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Removes the new version of the configuration file, keeping the local modifications
+    pub fn discard(&self, root: &Path) -> Result<(), Error> {
+        let new_path = self.new_path();
+
+        fs::remove_file(root.with_content(&new_path))
+            .with_context(|_| format_err!("unable to remove '{}'", new_path.display()))?;
+        Ok(())
+    }
+}
+
+impl<'cache_root, 'lock_file> InstalledPackages<'cache_root, 'lock_file> {
+    /// Returns the configuration files of the installed packages whose new version is waiting to be merged,
+    /// sorted by path
+    pub fn pending_merges(&self, root: &Path) -> Result<Vec<PendingMerge>, Error> {
+        let mut merges = Vec::new();
+
+        for package in self.packages()? {
+            for entry in self.package_log(&package)?.files() {
+                let is_pending = *entry.file_type() == FileType::File
+                    && fs::symlink_metadata(root.with_content(new_config_file_path(entry.path())))
+                        .is_ok();
+
+                if is_pending {
+                    merges.push(PendingMerge {
+                        path: entry.path().to_path_buf(),
+                        package: package.clone(),
+                    });
+                }
+            }
+        }
+
+        merges.sort();
+        Ok(merges)
+    }
+}
@@ -1,13 +1,19 @@
 //! Module to query and manipulate the cache of installed packages
 
 pub mod log;
+pub mod merges;
+pub mod owners;
+pub mod verify;
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::marker::PhantomData;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use semver::Version;
 
 use crate::lock_file::LockFileOwnership;
-use crate::package::PackageID;
+use crate::package::{CategoryName, PackageFullName, PackageID, PackageName, RepositoryName, Slot};
 
 use self::log::Log;
 
@@ -29,16 +35,18 @@ impl<'cache_root, 'lock_file> InstalledPackages<'cache_root, 'lock_file> {
         }
     }
 
-    /// Loads the log of installed files for a given package
-    pub fn package_log(&self, package: &PackageID) -> Result<Log, std::io::Error> {
-        let path = self
-            .cache_root
+    /// Returns the path of the log of installed files for a given package
+    pub(crate) fn package_log_path(&self, package: &PackageID) -> PathBuf {
+        self.cache_root
             .join(package.repository().as_str())
             .join(package.category().as_str())
             .join(package.name().as_str())
-            .join(package.version().to_string());
+            .join(package.version().to_string())
+    }
 
-        Log::load_from_file(path)
+    /// Loads the log of installed files for a given package
+    pub fn package_log(&self, package: &PackageID) -> Result<Log, std::io::Error> {
+        Log::load_from_file(self.package_log_path(package))
     }
 
     /// Saves the log of installed files for a given package
@@ -57,13 +65,79 @@ impl<'cache_root, 'lock_file> InstalledPackages<'cache_root, 'lock_file> {
 
     /// Removes the log of installed files for a given package
     pub fn remove_package_log(&self, package: &PackageID) -> Result<(), std::io::Error> {
-        let path = self
-            .cache_root
-            .join(package.repository().as_str())
-            .join(package.category().as_str())
-            .join(package.name().as_str())
-            .join(package.version().to_string());
+        fs::remove_file(self.package_log_path(package))
+    }
+
+    /// Tests whether a log of installed files exists for a given package
+    pub fn is_installed(&self, package: &PackageID) -> bool {
+        self.package_log_path(package).is_file()
+    }
+
+    /// Returns the [`PackageID`]s of all the packages having a log of installed files, sorted.
+    ///
+    /// Entries of the cache that don't match a valid [`PackageID`] are ignored.
+    pub fn packages(&self) -> Result<Vec<PackageID>, std::io::Error> {
+        let mut packages = Vec::new();
 
-        fs::remove_file(&path)
+        if !self.cache_root.exists() {
+            return Ok(packages);
+        }
+
+        for (repository, repository_path) in subfolders(self.cache_root, RepositoryName::parse)? {
+            for (category, category_path) in subfolders(&repository_path, CategoryName::parse)? {
+                for (name, name_path) in subfolders(&category_path, PackageName::parse)? {
+                    for entry in fs::read_dir(&name_path)? {
+                        let entry = entry?;
+                        let version = entry.file_name().to_str().map(Version::parse);
+
+                        if let (true, Some(Ok(version))) = (entry.file_type()?.is_file(), version) {
+                            packages.push(PackageID::from(
+                                repository.clone(),
+                                category.clone(),
+                                name.clone(),
+                                version,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        packages.sort();
+        Ok(packages)
+    }
+
+    /// Returns the [`PackageID`]s of all the packages having a log of installed files, keyed by package and by the
+    /// [`Slot`] they were installed in.
+    ///
+    /// Packages whose log can't be read are ignored.
+    pub fn slots(&self) -> Result<BTreeMap<(PackageFullName, Slot), PackageID>, std::io::Error> {
+        let mut slots = BTreeMap::new();
+
+        for package in self.packages()? {
+            if let Ok(log) = self.package_log(&package) {
+                slots.insert((package.clone().into(), log.slot().clone()), package);
+            }
+        }
+        Ok(slots)
+    }
+}
+
+/// Lists the subfolders of the given folder whose name can be parsed with the given function
+fn subfolders<T, E, F>(path: &Path, parse: F) -> Result<Vec<(T, PathBuf)>, std::io::Error>
+where
+    F: Fn(&str) -> Result<T, E>,
+{
+    let mut subfolders = Vec::new();
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_dir() {
+            if let Some(Ok(value)) = entry.file_name().to_str().map(&parse) {
+                subfolders.push((value, entry.path()));
+            }
+        }
     }
+    Ok(subfolders)
 }
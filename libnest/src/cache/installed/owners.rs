@@ -0,0 +1,88 @@
+//! Module to find the installed packages owning files
+
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error, ResultExt};
+use glob::{MatchOptions, Pattern};
+
+use crate::package::PackageID;
+
+use super::log::FileType;
+use super::InstalledPackages;
+
+/// The options used to match paths against the patterns given to [`path_pattern`]
+pub(crate) const PATH_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Compiles a pattern matching absolute paths, relative to the installation root.
+///
+/// Paths are compared without their trailing slash, like they are stored in the logs.
+pub(crate) fn path_pattern(pattern: &str) -> Result<Pattern, Error> {
+    if !pattern.starts_with('/') {
+        return Err(format_err!("'{}' isn't an absolute path", pattern));
+    }
+
+    let trimmed = pattern.trim_end_matches('/');
+    Ok(Pattern::new(if trimmed.is_empty() { "/" } else { trimmed })
+        .with_context(|_| format_err!("invalid pattern '{}'", pattern))?)
+}
+
+/// An installed file, along with the package owning it
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct OwnedFile {
+    path: PathBuf,
+    package: PackageID,
+    file_type: FileType,
+}
+
+impl OwnedFile {
+    /// Returns the absolute path of the file, relative to the installation root
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the [`PackageID`] of the package owning the file
+    #[inline]
+    pub fn package(&self) -> &PackageID {
+        &self.package
+    }
+
+    /// Returns the type of the file, as it was installed
+    #[inline]
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+}
+
+impl<'cache_root, 'lock_file> InstalledPackages<'cache_root, 'lock_file> {
+    /// Returns the installed files matching the given pattern, along with the package owning them, sorted by path.
+    ///
+    /// The pattern is an absolute path, relative to the installation root, which may contain wildcards:
+    /// `?` and `*` match any character and any sequence of characters except `/`, `**` matches any
+    /// sequence of directories, and `[...]` matches any of the given characters.
+    ///
+    /// Directories are usually shared by several packages, in which case they are returned once for each of them.
+    pub fn owners(&self, pattern: &str) -> Result<Vec<OwnedFile>, Error> {
+        let pattern = path_pattern(pattern)?;
+
+        let mut owned_files = Vec::new();
+        for package in self.packages()? {
+            for entry in self.package_log(&package)?.files() {
+                if pattern.matches_path_with(entry.path(), PATH_MATCH_OPTIONS) {
+                    owned_files.push(OwnedFile {
+                        path: entry.path().to_path_buf(),
+                        package: package.clone(),
+                        file_type: *entry.file_type(),
+                    });
+                }
+            }
+        }
+
+        owned_files.sort();
+        Ok(owned_files)
+    }
+}
@@ -0,0 +1,173 @@
+//! Module to compare the installed files of packages with their state when they were installed
+
+use std::collections::{BTreeSet, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::chroot::Chroot;
+use crate::package::{Checksum, PackageID};
+
+use super::log::{FileLogEntry, FileType, Log};
+use super::merges::new_config_file_path;
+use super::InstalledPackages;
+
+/// The kind of difference found between an installed file and its state when it was installed
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum FileIssueKind {
+    /// The file doesn't exist anymore
+    Missing,
+
+    /// The file was replaced by a file of another type
+    TypeChanged,
+
+    /// The content of the file changed
+    Modified,
+
+    /// The permissions of the file changed
+    PermissionsChanged,
+
+    /// The file is in a directory installed by the package, but doesn't belong to any installed package
+    Extraneous,
+}
+
+impl std::fmt::Display for FileIssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FileIssueKind::Missing => write!(f, "missing"),
+            FileIssueKind::TypeChanged => write!(f, "type changed"),
+            FileIssueKind::Modified => write!(f, "modified"),
+            FileIssueKind::PermissionsChanged => write!(f, "permissions changed"),
+            FileIssueKind::Extraneous => write!(f, "extraneous"),
+        }
+    }
+}
+
+/// A difference found between an installed file and its state when it was installed
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct FileIssue {
+    package: PackageID,
+    path: PathBuf,
+    kind: FileIssueKind,
+}
+
+impl FileIssue {
+    /// Returns the [`PackageID`] of the package the file was installed by
+    #[inline]
+    pub fn package(&self) -> &PackageID {
+        &self.package
+    }
+
+    /// Returns the absolute path of the file, relative to the installation root
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the kind of difference that was found
+    #[inline]
+    pub fn kind(&self) -> FileIssueKind {
+        self.kind
+    }
+}
+
+impl<'cache_root, 'lock_file> InstalledPackages<'cache_root, 'lock_file> {
+    /// Checks the files installed by the given packages in the given root folder against the
+    /// content of their logs, returning the differences that were found.
+    ///
+    /// Files whose checksum or permissions weren't recorded when they were installed are only
+    /// checked for existence.
+    pub fn verify(&self, root: &Path, packages: &[PackageID]) -> io::Result<Vec<FileIssue>> {
+        // Files in directories shared by several packages are extraneous only if no package owns them,
+        // and the new versions of configuration files waiting to be merged belong to their package
+        let mut owned_paths = HashSet::new();
+        for package in self.packages()? {
+            for entry in self.package_log(&package)?.files() {
+                owned_paths.insert(entry.path().to_path_buf());
+                if entry.file_type().is_file() {
+                    owned_paths.insert(new_config_file_path(entry.path()));
+                }
+            }
+        }
+
+        let mut issues = BTreeSet::new();
+        for package in packages {
+            let log: Log = self.package_log(package)?;
+
+            for entry in log.files() {
+                if let Some(kind) = verify_file(root, entry)? {
+                    issues.insert(FileIssue {
+                        package: package.clone(),
+                        path: entry.path().to_path_buf(),
+                        kind,
+                    });
+                    continue;
+                }
+
+                // Directories replaced by a symlink are scanned through the package owning their target
+                let path = root.with_content(entry.path());
+                if entry.file_type().is_dir() && fs::symlink_metadata(&path)?.is_dir() {
+                    for child in fs::read_dir(&path)? {
+                        let path = entry.path().join(child?.file_name());
+
+                        if !owned_paths.contains(&path) {
+                            issues.insert(FileIssue {
+                                package: package.clone(),
+                                path,
+                                kind: FileIssueKind::Extraneous,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(issues.into_iter().collect())
+    }
+}
+
+/// Compares a single installed file with its log entry
+fn verify_file(root: &Path, entry: &FileLogEntry) -> io::Result<Option<FileIssueKind>> {
+    let path = root.with_content(entry.path());
+
+    let metadata = match fs::symlink_metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Some(FileIssueKind::Missing));
+        }
+        Err(e) => return Err(e),
+    };
+
+    let file_type = metadata.file_type();
+    let same_type = match entry.file_type() {
+        FileType::Directory => file_type.is_dir(),
+        FileType::Symlink => file_type.is_symlink(),
+        FileType::File | FileType::Link => file_type.is_file(),
+        _ => !file_type.is_dir() && !file_type.is_symlink() && !file_type.is_file(),
+    };
+
+    // Directories may be shared by several packages, and may have a symlink installed instead
+    if entry.file_type().is_dir() {
+        return Ok(match (same_type, fs::metadata(&path)) {
+            (true, _) => None,
+            (false, Ok(ref metadata)) if metadata.is_dir() => None,
+            _ => Some(FileIssueKind::TypeChanged),
+        });
+    }
+    if !same_type {
+        return Ok(Some(FileIssueKind::TypeChanged));
+    }
+
+    if let Some(checksum) = entry.checksum() {
+        if Checksum::from_reader(&mut File::open(&path)?)? != *checksum {
+            return Ok(Some(FileIssueKind::Modified));
+        }
+    }
+
+    if let Some(mode) = entry.mode() {
+        if !file_type.is_symlink() && metadata.permissions().mode() & 0o7777 != mode & 0o7777 {
+            return Ok(Some(FileIssueKind::PermissionsChanged));
+        }
+    }
+    Ok(None)
+}
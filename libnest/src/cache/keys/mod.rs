@@ -0,0 +1,207 @@
+//! Module to store the keys imported for each repository, in addition to the ones of the configuration.
+//!
+//! Revoked keys are kept in the keystore, so that they are no longer trusted to sign the packages
+//! of their repository, even if they are still listed in the configuration.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use failure::{Error, ResultExt};
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::config::RepositoryConfig;
+use crate::lock_file::LockFileOwnership;
+use crate::package::TrustedKey;
+
+/// A key imported in the keystore
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct StoredKey {
+    key: TrustedKey,
+    imported_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl StoredKey {
+    /// Returns the imported key
+    #[inline]
+    pub fn key(&self) -> &TrustedKey {
+        &self.key
+    }
+
+    /// Returns the date the key was imported at
+    #[inline]
+    pub fn imported_at(&self) -> &DateTime<Utc> {
+        &self.imported_at
+    }
+
+    /// Returns the date the key was revoked at, if it was
+    #[inline]
+    pub fn revoked_at(&self) -> Option<&DateTime<Utc>> {
+        self.revoked_at.as_ref()
+    }
+
+    /// Returns true if the key was revoked
+    #[inline]
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+/// Structure representing the keys imported for each repository
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Keystore {
+    repositories: HashMap<String, Vec<StoredKey>>,
+}
+
+impl Keystore {
+    /// Loads the keystore from the cache, or returns an empty keystore if it doesn't exist yet
+    pub fn load_from_cache<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            let file = File::open(path).with_context(|_| path.display().to_string())?;
+            let keystore =
+                serde_json::from_reader(&file).with_context(|_| path.display().to_string())?;
+            Ok(keystore)
+        } else {
+            Ok(Keystore::default())
+        }
+    }
+
+    /// Saves the keystore back to the cache
+    pub fn save_to_cache<P: AsRef<Path>>(
+        &self,
+        path: P,
+        _: &LockFileOwnership,
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|_| parent.display().to_string())?;
+        }
+
+        let mut file = File::create(path).with_context(|_| path.display().to_string())?;
+        serde_json::to_writer_pretty(&file, self).with_context(|_| path.display().to_string())?;
+        writeln!(file)?;
+        Ok(())
+    }
+
+    /// Returns the keys imported for the given repository, including the revoked ones
+    #[inline]
+    pub fn keys(&self, repository: &str) -> &[StoredKey] {
+        self.repositories
+            .get(repository)
+            .map_or(&[], |keys| keys.as_slice())
+    }
+
+    /// Returns the key of the given repository with the given fingerprint, if any
+    pub fn find(&self, repository: &str, fingerprint: &str) -> Option<&StoredKey> {
+        self.keys(repository)
+            .iter()
+            .find(|stored| stored.key.fingerprint().eq_ignore_ascii_case(fingerprint))
+    }
+
+    /// Imports a key for the given repository. Importing a revoked key trusts it again.
+    ///
+    /// Returns false if the key was already imported and isn't revoked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// use libnest::cache::keys::Keystore;
+    /// use libnest::package::SigningKey;
+    ///
+    /// let key = SigningKey::generate().trusted_key();
+    /// let mut keystore = Keystore::default();
+    ///
+    /// assert!(keystore.import("stable", key));
+    /// assert!(!keystore.import("stable", key));
+    /// assert_eq!(keystore.revoke("stable", &key.fingerprint()), Some(key));
+    /// assert!(keystore.is_revoked("stable", &key));
+    /// assert!(keystore.import("stable", key));
+    /// assert!(!keystore.is_revoked("stable", &key));
+    /// ```
+    pub fn import(&mut self, repository: &str, key: TrustedKey) -> bool {
+        let keys = self
+            .repositories
+            .entry(repository.to_string())
+            .or_insert_with(Vec::new);
+
+        match keys.iter_mut().find(|stored| stored.key == key) {
+            Some(stored) if stored.is_revoked() => {
+                stored.imported_at = Utc::now();
+                stored.revoked_at = None;
+                true
+            }
+            Some(_) => false,
+            None => {
+                keys.push(StoredKey {
+                    key,
+                    imported_at: Utc::now(),
+                    revoked_at: None,
+                });
+                true
+            }
+        }
+    }
+
+    /// Revokes the key of the given repository with the given fingerprint, and returns it.
+    ///
+    /// Returns [`None`] if there is no such key, or if it was already revoked.
+    pub fn revoke(&mut self, repository: &str, fingerprint: &str) -> Option<TrustedKey> {
+        let stored = self
+            .repositories
+            .get_mut(repository)?
+            .iter_mut()
+            .find(|stored| stored.key.fingerprint().eq_ignore_ascii_case(fingerprint))?;
+
+        if stored.is_revoked() {
+            None
+        } else {
+            stored.revoked_at = Some(Utc::now());
+            Some(stored.key)
+        }
+    }
+
+    /// Revokes the given key for the given repository, adding it to the keystore if needed.
+    ///
+    /// This is used to revoke the keys listed in the configuration. Returns false if the key was already revoked.
+    pub fn revoke_key(&mut self, repository: &str, key: TrustedKey) -> bool {
+        if self.is_revoked(repository, &key) {
+            return false;
+        }
+        self.import(repository, key);
+        self.revoke(repository, &key.fingerprint()).is_some()
+    }
+
+    /// Returns true if the given key was revoked for the given repository
+    #[inline]
+    pub fn is_revoked(&self, repository: &str, key: &TrustedKey) -> bool {
+        self.keys(repository)
+            .iter()
+            .any(|stored| stored.key == *key && stored.is_revoked())
+    }
+
+    /// Returns the keys trusted to sign the packages of the given repository: the keys of its configuration
+    /// and the keys imported in the keystore, minus the revoked ones.
+    pub fn trusted_keys(&self, repository: &str, config: &RepositoryConfig) -> Vec<TrustedKey> {
+        let mut trusted_keys: Vec<_> = config
+            .trusted_keys()
+            .iter()
+            .filter(|key| !self.is_revoked(repository, key))
+            .cloned()
+            .collect();
+
+        for stored in self.keys(repository) {
+            if !stored.is_revoked() && !trusted_keys.contains(&stored.key) {
+                trusted_keys.push(stored.key);
+            }
+        }
+        trusted_keys
+    }
+}
@@ -0,0 +1,92 @@
+//! Module to report the licenses of the installed packages, checked against the license policy of the
+//! configuration.
+
+use failure::Error;
+
+use crate::cache::info::installed_manifest;
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{License, LicenseExpression, PackageID};
+
+/// Structure holding the licenses of an installed package
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PackageLicense {
+    id: PackageID,
+    license: Option<LicenseExpression>,
+    licenses: Vec<License>,
+    denied: Vec<String>,
+}
+
+impl PackageLicense {
+    /// Gathers the licenses of all the installed packages, sorted by package.
+    ///
+    /// Installed packages whose manifest can't be found anymore are skipped.
+    pub fn query(
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<Vec<PackageLicense>, Error> {
+        let mut ids = config.installed_packages_cache(lock_ownership).packages()?;
+        ids.sort();
+
+        let mut licenses = Vec::new();
+        for id in ids {
+            let manifest = match installed_manifest(config, lock_ownership, &id)? {
+                Some(manifest) => manifest,
+                None => continue,
+            };
+            let metadata = manifest.metadata();
+            let license = metadata.license().clone();
+
+            // Only the packages that can't avoid the denied licenses are flagged
+            let denied = match &license {
+                Some(license) if !config.licenses().allows(license) => license
+                    .licenses()
+                    .into_iter()
+                    .filter(|id| config.licenses().is_denied(id))
+                    .map(String::from)
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            licenses.push(PackageLicense {
+                id,
+                license,
+                licenses: metadata.licenses().clone(),
+                denied,
+            });
+        }
+        Ok(licenses)
+    }
+
+    /// Returns the [`PackageID`] of the package
+    #[inline]
+    pub fn id(&self) -> &PackageID {
+        &self.id
+    }
+
+    /// Returns the SPDX license expression of the package, if its manifest declares one
+    #[inline]
+    pub fn license(&self) -> Option<&LicenseExpression> {
+        self.license.as_ref()
+    }
+
+    /// Returns the licenses listed by the manifest of the package without an SPDX expression, if any
+    #[inline]
+    pub fn licenses(&self) -> &[License] {
+        &self.licenses
+    }
+
+    /// Returns the denied licenses the package is under, and that can't be avoided.
+    ///
+    /// It is empty if the package complies with the license policy of the configuration.
+    #[inline]
+    pub fn denied(&self) -> &[String] {
+        &self.denied
+    }
+
+    /// Returns whether the package is under a denied license that can't be avoided
+    #[inline]
+    pub fn is_denied(&self) -> bool {
+        !self.denied.is_empty()
+    }
+}
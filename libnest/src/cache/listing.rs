@@ -0,0 +1,247 @@
+//! Module to list the installed or available packages, filtered by repository, category or state.
+
+use std::collections::HashSet;
+
+use failure::Error;
+use semver::{Version, VersionReq};
+
+use crate::cache::available::AvailablePackagesCacheQueryStrategy;
+use crate::cache::depgraph::{NodeKind, RequirementManagementMethod};
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{
+    CategoryName, PackageFullName, PackageID, RepositoryName, SoftPackageRequirement,
+};
+
+/// The packages a [`PackageListing`] goes through
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum PackageListingSource {
+    /// The installed packages
+    Installed,
+
+    /// The most recent version of each package of the repositories' caches
+    Available,
+}
+
+/// A package returned by a [`PackageListing`]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct ListedPackage {
+    id: PackageID,
+    installed_version: Option<Version>,
+    explicitly_installed: bool,
+    upgrade: Option<Version>,
+}
+
+impl ListedPackage {
+    /// Returns the [`PackageID`] of the package: the installed one when listing the installed packages, the most
+    /// recent one when listing the available packages
+    #[inline]
+    pub fn id(&self) -> &PackageID {
+        &self.id
+    }
+
+    /// Returns the installed version of the package, if it is installed
+    #[inline]
+    pub fn installed_version(&self) -> Option<&Version> {
+        self.installed_version.as_ref()
+    }
+
+    /// Returns whether the package was explicitly installed, instead of being pulled as a dependency
+    #[inline]
+    pub fn explicitly_installed(&self) -> bool {
+        self.explicitly_installed
+    }
+
+    /// Returns the most recent version of the package offered by its repository, if it is installed and more
+    /// recent than the installed one
+    #[inline]
+    pub fn upgrade(&self) -> Option<&Version> {
+        self.upgrade.as_ref()
+    }
+}
+
+/// Structure representing a listing of the installed or available packages.
+///
+/// It goes through the packages of the given source, and only keeps the ones matching all the filters
+/// that were set. The results are sorted by name, so that they can be relied on by scripts.
+#[derive(Clone, Debug)]
+pub struct PackageListing<'a, 'b> {
+    config: &'a Config,
+    lock_ownership: &'b LockFileOwnership,
+    source: PackageListingSource,
+    repository: Option<RepositoryName>,
+    category: Option<CategoryName>,
+    name_prefix: String,
+    explicit_only: bool,
+    orphaned_only: bool,
+    upgradable_only: bool,
+}
+
+impl<'a, 'b> PackageListing<'a, 'b> {
+    /// Creates a [`PackageListing`] of the packages of the given source, without any filter
+    #[inline]
+    pub fn from(
+        config: &'a Config,
+        lock_ownership: &'b LockFileOwnership,
+        source: PackageListingSource,
+    ) -> Self {
+        PackageListing {
+            config,
+            lock_ownership,
+            source,
+            repository: None,
+            category: None,
+            name_prefix: String::new(),
+            explicit_only: false,
+            orphaned_only: false,
+            upgradable_only: false,
+        }
+    }
+
+    /// Only keeps the packages of the given repository
+    #[inline]
+    pub fn set_repository(mut self, repository: RepositoryName) -> Self {
+        self.repository = Some(repository);
+        self
+    }
+
+    /// Only keeps the packages of the given category
+    #[inline]
+    pub fn set_category(mut self, category: CategoryName) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    /// Only keeps the packages whose name starts with the given prefix
+    #[inline]
+    pub fn set_name_prefix(mut self, prefix: &str) -> Self {
+        self.name_prefix = prefix.to_string();
+        self
+    }
+
+    /// Only keeps the packages that were explicitly installed
+    #[inline]
+    pub fn set_explicit_only(mut self, explicit_only: bool) -> Self {
+        self.explicit_only = explicit_only;
+        self
+    }
+
+    /// Only keeps the installed packages that are no longer required by any explicitly installed package
+    #[inline]
+    pub fn set_orphaned_only(mut self, orphaned_only: bool) -> Self {
+        self.orphaned_only = orphaned_only;
+        self
+    }
+
+    /// Only keeps the installed packages for which a more recent version is offered by their repository
+    #[inline]
+    pub fn set_upgradable_only(mut self, upgradable_only: bool) -> Self {
+        self.upgradable_only = upgradable_only;
+        self
+    }
+
+    /// Performs the listing
+    pub fn perform(&self) -> Result<Vec<ListedPackage>, Error> {
+        let graph = self.config.dependency_graph(self.lock_ownership)?;
+        let available = self.config.available_packages_cache(self.lock_ownership);
+        let installed = self
+            .config
+            .installed_packages_cache(self.lock_ownership)
+            .packages()?;
+
+        // The packages fulfilling the requirements of the root, as other static requirements come from groups
+        let explicit: HashSet<_> = graph.nodes()[&graph.root_id()]
+            .requirements()
+            .iter()
+            .map(|requirement_id| &graph.requirements()[requirement_id])
+            .filter(|requirement| {
+                requirement.management_method() == RequirementManagementMethod::Static
+            })
+            .filter_map(|requirement| *requirement.fulfilling_node_id())
+            .filter_map(|node_id| match graph.nodes()[&node_id].kind() {
+                NodeKind::Package { id, .. } => Some(id.clone()),
+                NodeKind::Group { .. } => None,
+            })
+            .collect();
+        let orphans: HashSet<_> = graph.orphan_packages(&installed).into_iter().collect();
+
+        let candidates: Vec<PackageID> = match self.source {
+            PackageListingSource::Installed => installed.clone(),
+            PackageListingSource::Available => {
+                // Only the repositories and categories kept are loaded
+                let mut browsed = available.packages().set_name_prefix(&self.name_prefix);
+                if let Some(repository) = &self.repository {
+                    browsed = browsed.set_repository(repository.clone());
+                }
+                if let Some(category) = &self.category {
+                    browsed = browsed.set_category(category.clone());
+                }
+
+                let mut latest = Vec::new();
+                for package in browsed {
+                    let package = package?;
+                    if let Some(version) = package.versions().keys().max() {
+                        latest.push(PackageID::from_full_name(
+                            package.full_name(),
+                            version.clone(),
+                        ));
+                    }
+                }
+                latest
+            }
+        };
+
+        let mut packages = Vec::new();
+        for id in candidates {
+            let other_repository = self
+                .repository
+                .as_ref()
+                .map_or(false, |repository| repository != id.repository());
+            let other_category = self
+                .category
+                .as_ref()
+                .map_or(false, |category| category != id.category());
+            if other_repository || other_category || !id.name().starts_with(&self.name_prefix) {
+                continue;
+            }
+
+            let full_name: PackageFullName = id.clone().into();
+            let installed_id = installed.iter().find(|installed_id| {
+                let installed_name: PackageFullName = (*installed_id).clone().into();
+                installed_name == full_name
+            });
+
+            let upgrade = match installed_id {
+                Some(installed_id) => available
+                    .query(&SoftPackageRequirement::from(
+                        full_name.clone(),
+                        VersionReq::any(),
+                    ))
+                    .set_strategy(AvailablePackagesCacheQueryStrategy::BestMatch)
+                    .perform()?
+                    .into_iter()
+                    .map(|result| result.manifest().version().clone())
+                    .find(|version| version > installed_id.version()),
+                None => None,
+            };
+
+            let listed = ListedPackage {
+                explicitly_installed: installed_id.map_or(false, |id| explicit.contains(id)),
+                installed_version: installed_id.map(|id| id.version().clone()),
+                upgrade,
+                id,
+            };
+
+            if (self.explicit_only && !listed.explicitly_installed)
+                || (self.orphaned_only && installed_id.map_or(true, |id| !orphans.contains(id)))
+                || (self.upgradable_only && listed.upgrade.is_none())
+            {
+                continue;
+            }
+            packages.push(listed);
+        }
+
+        packages.sort();
+        Ok(packages)
+    }
+}
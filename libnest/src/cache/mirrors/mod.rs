@@ -0,0 +1,228 @@
+//! Module to measure the health of repository mirrors, and rank them accordingly.
+//!
+//! The results of the last measures are kept in a cache file, so that the fastest reachable
+//! mirror of a repository can be tried first without having to measure it again.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use failure::{Error, ResultExt};
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::config::MirrorUrl;
+use crate::lock_file::LockFileOwnership;
+
+/// The amount of data used as a reference to compare the speed of two mirrors
+static REFERENCE_DOWNLOAD_SIZE: u64 = 1024 * 1024;
+
+/// The health of a mirror, as measured during its last check
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MirrorHealth {
+    latency: Option<Duration>,
+    throughput: Option<u64>,
+    checked_at: DateTime<Utc>,
+}
+
+impl MirrorHealth {
+    /// Creates a [`MirrorHealth`] from a latency and a throughput, in bytes per second.
+    ///
+    /// A latency of [`None`] means the mirror is unreachable.
+    #[inline]
+    pub fn from(latency: Option<Duration>, throughput: Option<u64>) -> Self {
+        Self {
+            latency,
+            throughput,
+            checked_at: Utc::now(),
+        }
+    }
+
+    /// Measures the latency of a mirror, which is the time needed to open a connection to it.
+    ///
+    /// The mirror is considered unreachable if no connection could be opened within the given timeout.
+    /// The throughput of the mirror is left unknown.
+    ///
+    /// Local mirrors have no latency, and are unreachable if their directory doesn't exist.
+    pub fn measure(mirror: &MirrorUrl, timeout: Duration) -> Self {
+        if mirror.scheme() == "file" {
+            let exists = mirror.to_file_path().map_or(false, |path| path.is_dir());
+
+            return Self::from(
+                if exists {
+                    Some(Duration::default())
+                } else {
+                    None
+                },
+                None,
+            );
+        }
+
+        let addrs = mirror
+            .host_str()
+            .and_then(|host| Some((host, mirror.port_or_known_default()?)))
+            .and_then(|addr| addr.to_socket_addrs().ok());
+
+        let latency = addrs.and_then(|addrs| {
+            addrs
+                .filter_map(|addr| {
+                    let start = Instant::now();
+                    TcpStream::connect_timeout(&addr, timeout)
+                        .ok()
+                        .map(|_| start.elapsed())
+                })
+                .next()
+        });
+
+        Self::from(latency, None)
+    }
+
+    /// Returns the latency of the mirror, or [`None`] if it was unreachable
+    #[inline]
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    /// Returns the throughput of the mirror in bytes per second, if it is known
+    #[inline]
+    pub fn throughput(&self) -> Option<u64> {
+        self.throughput
+    }
+
+    /// Returns a mutable reference over the throughput of the mirror, in bytes per second
+    #[inline]
+    pub fn throughput_mut(&mut self) -> &mut Option<u64> {
+        &mut self.throughput
+    }
+
+    /// Returns the date of the check
+    #[inline]
+    pub fn checked_at(&self) -> &DateTime<Utc> {
+        &self.checked_at
+    }
+
+    /// Returns true if the mirror was reachable
+    #[inline]
+    pub fn is_reachable(&self) -> bool {
+        self.latency.is_some()
+    }
+
+    /// Returns the estimated time needed to download a reference amount of data from this mirror,
+    /// or [`None`] if it was unreachable.
+    ///
+    /// When the throughput is unknown, only the latency is taken into account.
+    pub fn estimated_time(&self) -> Option<Duration> {
+        let latency = self.latency?;
+
+        match self.throughput {
+            Some(throughput) if throughput > 0 => {
+                let transfer_ms = REFERENCE_DOWNLOAD_SIZE * 1000 / throughput;
+                Some(latency + Duration::from_millis(transfer_ms))
+            }
+            _ => Some(latency),
+        }
+    }
+}
+
+/// Structure representing the cache of the health of all known mirrors
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug, Default)]
+pub struct MirrorsHealth {
+    mirrors: HashMap<String, MirrorHealth>,
+}
+
+impl MirrorsHealth {
+    /// Loads the health of the mirrors from the cache, or returns an empty cache if it doesn't exist yet
+    pub fn load_from_cache<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            let file = File::open(path).with_context(|_| path.display().to_string())?;
+            let health =
+                serde_json::from_reader(&file).with_context(|_| path.display().to_string())?;
+            Ok(health)
+        } else {
+            Ok(MirrorsHealth::default())
+        }
+    }
+
+    /// Saves the health of the mirrors back to the cache
+    pub fn save_to_cache<P: AsRef<Path>>(
+        &self,
+        path: P,
+        _: &LockFileOwnership,
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|_| parent.display().to_string())?;
+        }
+
+        let mut file = File::create(path).with_context(|_| path.display().to_string())?;
+        serde_json::to_writer_pretty(&file, self).with_context(|_| path.display().to_string())?;
+        writeln!(file)?;
+        Ok(())
+    }
+
+    /// Returns the last known health of the given mirror, if any
+    #[inline]
+    pub fn health(&self, mirror: &MirrorUrl) -> Option<&MirrorHealth> {
+        self.mirrors.get(mirror.as_str())
+    }
+
+    /// Updates the health of the given mirror
+    #[inline]
+    pub fn update(&mut self, mirror: &MirrorUrl, health: MirrorHealth) {
+        self.mirrors.insert(mirror.as_str().to_string(), health);
+    }
+
+    /// Sorts the given mirrors so that the fastest reachable mirror comes first.
+    ///
+    /// Mirrors that were never checked come after the reachable ones, and unreachable mirrors come last.
+    /// Mirrors that can't be told apart keep their original order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate url;
+    /// # extern crate url_serde;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::time::Duration;
+    ///
+    /// use libnest::cache::mirrors::{MirrorHealth, MirrorsHealth};
+    /// use url::Url;
+    /// use url_serde::Serde;
+    ///
+    /// let slow = Serde(Url::parse("https://slow.raven-os.org")?);
+    /// let fast = Serde(Url::parse("https://fast.raven-os.org")?);
+    /// let down = Serde(Url::parse("https://down.raven-os.org")?);
+    /// let unknown = Serde(Url::parse("https://unknown.raven-os.org")?);
+    ///
+    /// let mut health = MirrorsHealth::default();
+    /// health.update(&slow, MirrorHealth::from(Some(Duration::from_millis(300)), None));
+    /// health.update(&fast, MirrorHealth::from(Some(Duration::from_millis(20)), None));
+    /// health.update(&down, MirrorHealth::from(None, None));
+    ///
+    /// let mirrors = vec![down.clone(), unknown.clone(), slow.clone(), fast.clone()];
+    /// assert_eq!(health.rank(&mirrors), vec![fast, slow, unknown, down]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rank(&self, mirrors: &[MirrorUrl]) -> Vec<MirrorUrl> {
+        let mut ranked = mirrors.to_vec();
+
+        ranked.sort_by_key(|mirror| match self.health(mirror) {
+            Some(health) => match health.estimated_time() {
+                Some(time) => (0, time),
+                None => (2, Duration::default()),
+            },
+            None => (1, Duration::default()),
+        });
+        ranked
+    }
+}
@@ -1,9 +1,22 @@
 //! Module to represent an manipulate the nest cache, that is, data stored on the filesystem
 
+pub mod alternatives;
 pub mod available;
+pub mod changelog;
 pub mod depgraph;
 pub mod downloaded;
 mod errors;
+pub mod files;
+pub mod held;
+pub mod history;
+pub mod info;
 pub mod installed;
+pub mod keys;
+pub mod licenses;
+pub mod listing;
+pub mod mirrors;
+pub mod query;
+pub mod store;
+pub mod world;
 
 pub use self::errors::*;
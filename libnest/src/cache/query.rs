@@ -0,0 +1,462 @@
+//! Module to resolve the packages designated by users against the installed and available packages.
+//!
+//! Users designate packages with strings like `gcc`, `sys-devel/gcc`, `stable::sys-devel/gcc` or
+//! `sys-devel/gcc#^8.0`, whose parts may be glob patterns, like `dev-python/*`. They may also be narrowed to a slot,
+//! like `sys-devel/gcc:9`. A [`PackageQuery`] parses them once, so that every frontend command matches them the same
+//! way. Users may also select packages with a regular
+//! expression matched against their full name (see [`PackageQuery::regex()`]).
+
+use std::collections::BTreeSet;
+
+use failure::{Context, Error, ResultExt};
+use glob::Pattern;
+use regex::Regex;
+use semver::VersionReq;
+
+use crate::cache::available::{AvailablePackagesCacheQueryStrategy, QueryResult};
+use crate::cache::errors::{PackageQueryError, PackageQueryErrorKind};
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{
+    split_slot, PackageFullName, PackageID, PackageShortName, Slot, SoftPackageRequirement,
+    SoftPackageRequirementParseErrorKind, REGEX_PACKAGE_ID,
+};
+
+/// Where a package matched by a [`PackageQuery`] was found
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum PackageMatchSource {
+    /// The package is installed
+    Installed,
+
+    /// The package isn't installed, but is offered by a repository
+    Available,
+}
+
+/// A package matched by a [`PackageQuery`]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct PackageMatch {
+    source: PackageMatchSource,
+    id: PackageID,
+}
+
+impl PackageMatch {
+    /// Returns the [`PackageID`] of the package: the installed one for installed packages, the best available
+    /// one otherwise
+    #[inline]
+    pub fn id(&self) -> &PackageID {
+        &self.id
+    }
+
+    /// Returns where the package was found
+    #[inline]
+    pub fn source(&self) -> PackageMatchSource {
+        self.source
+    }
+
+    /// Returns whether the package is installed
+    #[inline]
+    pub fn is_installed(&self) -> bool {
+        self.source == PackageMatchSource::Installed
+    }
+}
+
+/// A part of a [`PackageQuery`]: either an exact name or a glob pattern
+#[derive(Clone, Debug)]
+enum NamePattern {
+    Exact(String),
+    Glob(Pattern),
+}
+
+impl NamePattern {
+    fn parse(repr: &str) -> Result<Self, Error> {
+        if repr.contains(|c| c == '*' || c == '?' || c == '[') {
+            Ok(NamePattern::Glob(Pattern::new(repr).context(
+                PackageQueryErrorKind::InvalidPattern(repr.to_string()),
+            )?))
+        } else {
+            Ok(NamePattern::Exact(repr.to_string()))
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            NamePattern::Exact(exact) => exact == name,
+            NamePattern::Glob(pattern) => pattern.matches(name),
+        }
+    }
+
+    fn is_glob(&self) -> bool {
+        match self {
+            NamePattern::Exact(_) => false,
+            NamePattern::Glob(_) => true,
+        }
+    }
+}
+
+/// A package, or a pattern of packages, designated by a user.
+///
+/// Its string representation is the one of a [`SoftPackageRequirement`],
+/// `[repository::][category/]name[:slot][#version]`, where the version requirement may also directly follow the name
+/// or the slot, like `coreutils>=1.2`. The repository, the category and the name may be glob patterns.
+#[derive(Clone, Debug)]
+pub struct PackageQuery {
+    repr: String,
+    regex: Option<Regex>,
+    repository: Option<NamePattern>,
+    category: Option<NamePattern>,
+    name: NamePattern,
+    version_requirement: VersionReq,
+    slot: Option<Slot>,
+    requirement: Option<SoftPackageRequirement>,
+}
+
+impl PackageQuery {
+    /// Parses the given string into a [`PackageQuery`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::cache::query::PackageQuery;
+    /// use libnest::package::PackageID;
+    ///
+    /// let query = PackageQuery::parse("sys-devel/gcc>=8.0")?;
+    /// assert!(!query.is_pattern());
+    /// assert!(query.matches(&PackageID::parse("stable::sys-devel/gcc#8.3.0")?));
+    /// assert!(!query.matches(&PackageID::parse("stable::sys-devel/gcc#7.4.0")?));
+    ///
+    /// let query = PackageQuery::parse("dev-python/*")?;
+    /// assert!(query.is_pattern());
+    /// assert!(query.requirement().is_none());
+    /// assert!(query.matches(&PackageID::parse("stable::dev-python/pip#19.0.0")?));
+    /// assert!(!query.matches(&PackageID::parse("stable::sys-devel/gcc#8.3.0")?));
+    ///
+    /// let query = PackageQuery::parse("sys-devel/gcc:9>=9.1")?;
+    /// assert_eq!(query.slot().map(|slot| slot.as_ref()), Some("9"));
+    /// assert!(query.matches(&PackageID::parse("stable::sys-devel/gcc#9.2.0")?));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse(repr: &str) -> Result<PackageQuery, Error> {
+        let normalized = match repr.find(|c| c == '=' || c == '<' || c == '>') {
+            Some(pos) if !repr.contains('#') => format!("{}#{}", &repr[..pos], &repr[pos..]),
+            _ => repr.to_string(),
+        };
+
+        let (unslotted, slot) = split_slot(&normalized);
+        let slot = match slot {
+            Some(slot) => {
+                Some(Slot::parse(slot).map_err(SoftPackageRequirementParseErrorKind::InvalidSlot)?)
+            }
+            None => None,
+        };
+
+        let captures = REGEX_PACKAGE_ID
+            .captures(&unslotted)
+            .ok_or_else(|| Context::from(repr.to_string()))
+            .context(SoftPackageRequirementParseErrorKind::InvalidFormat(
+                repr.to_string(),
+            ))?;
+
+        let repository = match captures.name("repository") {
+            Some(repository) => Some(NamePattern::parse(repository.as_str())?),
+            None => None,
+        };
+        let category = match captures.name("category") {
+            Some(category) => Some(NamePattern::parse(category.as_str())?),
+            None => None,
+        };
+        let name = NamePattern::parse(captures.name("package").unwrap().as_str())?;
+
+        let is_pattern = name.is_glob()
+            || repository.as_ref().map_or(false, NamePattern::is_glob)
+            || category.as_ref().map_or(false, NamePattern::is_glob);
+
+        // Queries without patterns must be valid requirements, which also validates their names
+        let (requirement, version_requirement) = if is_pattern {
+            let version_requirement = match captures.name("version") {
+                Some(version) => VersionReq::parse(version.as_str())
+                    .context(repr.to_string())
+                    .context(SoftPackageRequirementParseErrorKind::InvalidVersion)?,
+                None => VersionReq::any(),
+            };
+            (None, version_requirement)
+        } else {
+            let requirement = SoftPackageRequirement::parse(&normalized)?;
+            let version_requirement = requirement.version_requirement().clone();
+            (Some(requirement), version_requirement)
+        };
+
+        Ok(PackageQuery {
+            repr: repr.to_string(),
+            regex: None,
+            repository,
+            category,
+            name,
+            version_requirement,
+            slot,
+            requirement,
+        })
+    }
+
+    /// Creates a [`PackageQuery`] matching the packages whose full name, like `stable::sys-kernel/linux-lts`,
+    /// matches the given regular expression, anywhere unless it is anchored
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::cache::query::PackageQuery;
+    /// use libnest::package::PackageID;
+    ///
+    /// let query = PackageQuery::regex("^stable::sys-kernel/linux(-lts)?$")?;
+    /// assert!(query.is_pattern());
+    /// assert!(query.matches(&PackageID::parse("stable::sys-kernel/linux-lts#4.19.0")?));
+    /// assert!(!query.matches(&PackageID::parse("stable::sys-kernel/linux-firmware#1.0.0")?));
+    ///
+    /// assert!(PackageQuery::regex("linux(").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn regex(pattern: &str) -> Result<PackageQuery, Error> {
+        let regex = Regex::new(pattern)
+            .context(PackageQueryErrorKind::InvalidPattern(pattern.to_string()))?;
+
+        Ok(PackageQuery {
+            repr: pattern.to_string(),
+            regex: Some(regex),
+            // The parts of the name are left unused, the regular expression matching the whole of it
+            repository: None,
+            category: None,
+            name: NamePattern::Glob(Pattern::new("*").unwrap()),
+            version_requirement: VersionReq::any(),
+            slot: None,
+            requirement: None,
+        })
+    }
+
+    /// Returns the string the query was parsed from
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.repr
+    }
+
+    /// Returns whether the query is a regular expression, or if its repository, its category or its name is a glob
+    /// pattern
+    #[inline]
+    pub fn is_pattern(&self) -> bool {
+        self.requirement.is_none()
+    }
+
+    /// Returns the requirement equivalent to the query, or [`None`] if the query holds a glob pattern
+    #[inline]
+    pub fn requirement(&self) -> Option<&SoftPackageRequirement> {
+        self.requirement.as_ref()
+    }
+
+    /// Returns the version requirement that the packages' version must match
+    #[inline]
+    pub fn version_requirement(&self) -> &VersionReq {
+        &self.version_requirement
+    }
+
+    /// Returns whether the query restricts the version of the packages
+    #[inline]
+    pub fn has_version_requirement(&self) -> bool {
+        self.version_requirement != VersionReq::any()
+    }
+
+    /// Returns the slot that the packages must be in, if any
+    #[inline]
+    pub fn slot(&self) -> Option<&Slot> {
+        self.slot.as_ref()
+    }
+
+    /// Returns whether the given [`PackageShortName`] matches the category and the name of the query.
+    ///
+    /// Regular expressions are matched against the short name, like `sys-kernel/linux-lts`.
+    pub fn matches_short_name(&self, name: &PackageShortName) -> bool {
+        if let Some(regex) = &self.regex {
+            return regex.is_match(&name.to_string());
+        }
+        self.category
+            .as_ref()
+            .map_or(true, |category| category.matches(name.category().as_str()))
+            && self.name.matches(name.name().as_str())
+    }
+
+    /// Returns whether the given [`PackageFullName`] matches the repository, the category and the name of the query
+    pub fn matches_full_name(&self, name: &PackageFullName) -> bool {
+        if let Some(regex) = &self.regex {
+            return regex.is_match(&name.to_string());
+        }
+        self.repository.as_ref().map_or(true, |repository| {
+            repository.matches(name.repository().as_str())
+        }) && self
+            .category
+            .as_ref()
+            .map_or(true, |category| category.matches(name.category().as_str()))
+            && self.name.matches(name.name().as_str())
+    }
+
+    /// Returns whether the given [`PackageID`] matches the query, including its version requirement
+    #[inline]
+    pub fn matches(&self, id: &PackageID) -> bool {
+        self.matches_full_name(&id.clone().into()) && self.version_requirement.matches(id.version())
+    }
+
+    /// Returns the installed packages matching the query, sorted
+    pub fn installed(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<Vec<PackageID>, Error> {
+        let installed = config.installed_packages_cache(lock_ownership);
+
+        // The slot of installed packages is only known from their log
+        let packages = match &self.slot {
+            Some(slot) => installed
+                .slots()?
+                .into_iter()
+                .filter(|((_, package_slot), _)| package_slot == slot)
+                .map(|(_, id)| id)
+                .collect(),
+            None => installed.packages()?,
+        };
+
+        let mut packages: Vec<_> = packages.into_iter().filter(|id| self.matches(id)).collect();
+        packages.sort();
+        Ok(packages)
+    }
+
+    /// Returns the available packages matching the query, selected and sorted following the given strategy (see
+    /// [`AvailablePackagesCacheQuery`](crate::cache::available::AvailablePackagesCacheQuery))
+    pub fn available(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        strategy: AvailablePackagesCacheQueryStrategy,
+    ) -> Result<Vec<QueryResult>, Error> {
+        let available = config.available_packages_cache(lock_ownership);
+
+        if let Some(requirement) = &self.requirement {
+            return available
+                .query(requirement)
+                .set_strategy(strategy)
+                .perform();
+        }
+
+        // Patterns are expanded to the names they match, each of them being queried on its own
+        let mut names = BTreeSet::new();
+        for repository in available.repositories()? {
+            let index = available.repository_index(&repository)?;
+
+            for package in index.packages() {
+                let full_name = package?.full_name();
+
+                if self.matches_full_name(&full_name) {
+                    names.insert(Into::<PackageShortName>::into(full_name));
+                }
+            }
+        }
+
+        let mut results = Vec::new();
+        for name in names {
+            let requirement =
+                SoftPackageRequirement::from_short_name(name, self.version_requirement.clone())
+                    .with_slot(self.slot.clone());
+
+            results.extend(
+                available
+                    .query(&requirement)
+                    .set_strategy(strategy)
+                    .perform()?
+                    .into_iter()
+                    .filter(|result| self.matches_full_name(&result.full_name())),
+            );
+        }
+        Ok(results)
+    }
+
+    /// Resolves the query against both the installed and the available packages.
+    ///
+    /// The installed packages matching the query come first, sorted, followed by the best available version of
+    /// each matching package that isn't installed.
+    pub fn resolve(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<Vec<PackageMatch>, Error> {
+        let installed = self.installed(config, lock_ownership)?;
+        let installed_names: BTreeSet<PackageFullName> =
+            installed.iter().cloned().map(Into::into).collect();
+
+        let mut matches: Vec<_> = installed
+            .into_iter()
+            .map(|id| PackageMatch {
+                source: PackageMatchSource::Installed,
+                id,
+            })
+            .collect();
+        matches.extend(
+            self.available(
+                config,
+                lock_ownership,
+                AvailablePackagesCacheQueryStrategy::BestMatch,
+            )?
+            .into_iter()
+            .filter(|result| !installed_names.contains(&result.full_name()))
+            .map(|result| PackageMatch {
+                source: PackageMatchSource::Available,
+                id: result.id(),
+            }),
+        );
+        Ok(matches)
+    }
+
+    /// Resolves the query to a single package: the matching installed package, or else the best matching available
+    /// package.
+    ///
+    /// A [`PackageQueryErrorKind`] error is returned if no package matches the query, or if several do.
+    pub fn resolve_one(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<PackageMatch, Error> {
+        let mut matches = self.resolve(config, lock_ownership)?;
+
+        if matches.iter().any(PackageMatch::is_installed) {
+            matches.retain(PackageMatch::is_installed);
+        }
+        self.select_one(matches, |found| found.id().clone())
+    }
+
+    /// Selects the only element of the given list of matches of this query, failing with a
+    /// [`PackageQueryErrorKind`] error if the list is empty or holds several matches, which are described by their
+    /// [`PackageID`]s
+    pub fn select_one<T, F>(&self, mut matches: Vec<T>, id: F) -> Result<T, Error>
+    where
+        F: Fn(&T) -> PackageID,
+    {
+        match matches.len() {
+            0 => Err(
+                PackageQueryError::from(PackageQueryErrorKind::NoMatchingPackage(
+                    self.repr.clone(),
+                ))
+                .into(),
+            ),
+            1 => Ok(matches.remove(0)),
+            _ => Err(
+                PackageQueryError::from(PackageQueryErrorKind::AmbiguousPackage(
+                    self.repr.clone(),
+                    matches.iter().map(id).collect(),
+                ))
+                .into(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,399 @@
+//! Module to manage the content-addressed store of the files installed by the packages.
+//!
+//! The regular files of the packages are stored once per content and attributes, as objects named after their
+//! checksum and their [`ObjectAttributes`], and are hardlinked at their place in the installation root. Identical files shipped by
+//! several packages, or by several versions of a package, thus take up space once, and upgrading a package mostly
+//! swaps links.
+//!
+//! An object that isn't linked anywhere anymore is unused, and is removed by [`ContentStore::collect_garbage()`].
+
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use filetime::FileTime;
+use log::warn;
+use tar::Entry;
+
+use crate::lock_file::LockFileOwnership;
+use crate::package::Checksum;
+
+/// The number of objects written so far by this process, to give each of them its own temporary file
+static WRITTEN_OBJECTS: AtomicUsize = AtomicUsize::new(0);
+
+/// The prefix of the temporary files objects are written to, before being moved in place
+const TEMPORARY_PREFIX: &str = ".tmp-";
+
+/// The prefix of the PAX records holding the extended attributes of a file
+const PAX_XATTR_PREFIX: &[u8] = b"SCHILY.xattr.";
+
+/// The attributes of a file, which are shared by all the hardlinks to its object and thus part of its identity
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct ObjectAttributes {
+    mode: u32,
+    owner: Option<(u64, u64)>,
+    xattrs: Vec<(OsString, Vec<u8>)>,
+}
+
+impl ObjectAttributes {
+    /// Creates the attributes of a file with the given mode, whose owner and extended attributes are left as is
+    #[inline]
+    pub fn from(mode: u32) -> Self {
+        ObjectAttributes {
+            mode,
+            owner: None,
+            xattrs: Vec::new(),
+        }
+    }
+
+    /// Gives the file to the given user and group
+    #[inline]
+    pub fn with_owner(mut self, uid: u64, gid: u64) -> Self {
+        self.owner = Some((uid, gid));
+        self
+    }
+
+    /// Sets the given extended attributes on the file, given as pairs of names and values
+    #[inline]
+    pub fn with_xattrs(mut self, mut xattrs: Vec<(OsString, Vec<u8>)>) -> Self {
+        xattrs.sort();
+        self.xattrs = xattrs;
+        self
+    }
+
+    /// Returns the attributes the given entry of a package's data is unpacked with.
+    ///
+    /// The owner, the extended attributes and the special permission bits of the entry are only kept if they
+    /// are to be restored, like tar does.
+    pub(crate) fn from_entry<R: Read>(entry: &mut Entry<R>, restore: bool) -> io::Result<Self> {
+        let mode = entry.header().mode()?;
+        if !restore {
+            return Ok(ObjectAttributes::from(mode & 0o777));
+        }
+
+        let mut xattrs = Vec::new();
+        if let Some(extensions) = entry.pax_extensions()? {
+            for extension in extensions {
+                let extension = extension?;
+                let key = extension.key_bytes();
+
+                if key.starts_with(PAX_XATTR_PREFIX) {
+                    xattrs.push((
+                        OsString::from_vec(key[PAX_XATTR_PREFIX.len()..].to_vec()),
+                        extension.value_bytes().to_vec(),
+                    ));
+                }
+            }
+        }
+
+        let header = entry.header();
+        Ok(ObjectAttributes::from(mode & 0o7777)
+            .with_owner(header.uid()?, header.gid()?)
+            .with_xattrs(xattrs))
+    }
+
+    /// Returns the mode of the file
+    #[inline]
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// Returns the user and group owning the file, if they are set
+    #[inline]
+    pub fn owner(&self) -> Option<(u64, u64)> {
+        self.owner
+    }
+
+    /// Returns the extended attributes of the file, sorted by name
+    #[inline]
+    pub fn xattrs(&self) -> &[(OsString, Vec<u8>)] {
+        &self.xattrs
+    }
+
+    /// Returns the suffix of the names of the objects with these attributes: the mode, then the owner and a digest
+    /// of the extended attributes, if any
+    fn suffix(&self) -> io::Result<String> {
+        let mut suffix = format!("-{:o}", self.mode);
+
+        if let Some((uid, gid)) = self.owner {
+            suffix.push_str(&format!("-{}-{}", uid, gid));
+        }
+        if !self.xattrs.is_empty() {
+            let mut encoded = Vec::new();
+            for (name, value) in &self.xattrs {
+                encoded.extend_from_slice(name.as_bytes());
+                encoded.push(0);
+                encoded.extend_from_slice(&(value.len() as u64).to_le_bytes());
+                encoded.extend_from_slice(value);
+            }
+            let digest = Checksum::from_reader(&mut encoded.as_slice())?.to_string();
+            suffix.push_str(&format!("-{}", &digest[..16]));
+        }
+        Ok(suffix)
+    }
+
+    /// Sets these attributes on the given file.
+    ///
+    /// The owner is set first, as changing it clears the special permission bits and the file capabilities.
+    fn apply(&self, path: &Path) -> io::Result<()> {
+        if let Some((uid, gid)) = self.owner {
+            std::os::unix::fs::chown(path, Some(uid as u32), Some(gid as u32))?;
+        }
+        fs::set_permissions(path, fs::Permissions::from_mode(self.mode))?;
+        for (name, value) in &self.xattrs {
+            xattr::set(path, name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The number of objects of a [`ContentStore`] and the space they take up
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct StoreUsage {
+    objects: usize,
+    size: u64,
+}
+
+impl StoreUsage {
+    /// Returns the number of objects
+    #[inline]
+    pub fn objects(&self) -> usize {
+        self.objects
+    }
+
+    /// Returns the total size of the objects, in bytes
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Structure representing the content-addressed store of the installed files
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ContentStore<'cache_root, 'lock_file> {
+    cache_root: &'cache_root Path,
+    phantom: PhantomData<&'lock_file LockFileOwnership>,
+}
+
+impl<'cache_root, 'lock_file> ContentStore<'cache_root, 'lock_file> {
+    pub(crate) fn from(
+        cache_root: &'cache_root Path,
+        phantom: PhantomData<&'lock_file LockFileOwnership>,
+    ) -> Self {
+        Self {
+            cache_root,
+            phantom,
+        }
+    }
+
+    /// Returns the path of the object holding a file with the given checksum and attributes.
+    ///
+    /// Objects are spread in subfolders named after the first two digits of their checksum.
+    pub fn object_path(
+        &self,
+        checksum: &Checksum,
+        attributes: &ObjectAttributes,
+    ) -> io::Result<PathBuf> {
+        let checksum = checksum.to_string();
+
+        Ok(self.cache_root.join(&checksum[..2]).join(format!(
+            "{}{}",
+            checksum,
+            attributes.suffix()?
+        )))
+    }
+
+    /// Returns whether the object at the given path exists and still holds the content with the given checksum.
+    ///
+    /// Installed files are hardlinks to their object, so editing one of them in place alters the object too.
+    fn is_intact(path: &Path, checksum: &Checksum) -> bool {
+        match File::open(path) {
+            Ok(mut file) => Checksum::from_reader(&mut file)
+                .map(|actual| actual == *checksum)
+                .unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    /// Writes the object holding a file with the given checksum and attributes with the given function, unless it
+    /// exists already and its content matches the checksum, and returns its path.
+    ///
+    /// The object is written to a temporary file first, which is then linked in place, so that neither an
+    /// interrupted write nor concurrent writes of the same object leave a corrupted object behind. An object whose
+    /// content doesn't match its checksum anymore is replaced by the new one, the files still linked to it being
+    /// left as they are.
+    fn write_object<F>(
+        &self,
+        checksum: &Checksum,
+        attributes: &ObjectAttributes,
+        write: F,
+    ) -> io::Result<PathBuf>
+    where
+        F: FnOnce(&Path) -> io::Result<()>,
+    {
+        let path = self.object_path(checksum, attributes)?;
+        let exists = fs::symlink_metadata(&path).is_ok();
+
+        if !exists || !Self::is_intact(&path, checksum) {
+            let dir = path.parent().unwrap_or(self.cache_root);
+            fs::create_dir_all(dir)?;
+
+            let temporary = dir.join(format!(
+                "{}{}-{}",
+                TEMPORARY_PREFIX,
+                process::id(),
+                WRITTEN_OBJECTS.fetch_add(1, Ordering::SeqCst)
+            ));
+            let res = write(&temporary).and_then(|_| {
+                if exists {
+                    warn!(
+                        "{} doesn't match its checksum anymore, replacing it",
+                        path.display()
+                    );
+                    return fs::rename(&temporary, &path);
+                }
+                match fs::hard_link(&temporary, &path) {
+                    Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+                    res => res,
+                }
+            });
+            let _ = fs::remove_file(&temporary);
+            res?;
+        }
+        Ok(path)
+    }
+
+    /// Stores the content of the given entry of a package's data, whose checksum and attributes are given, unless
+    /// an object holds it already, and returns the path of its object.
+    ///
+    /// The entry is unpacked as its archive is set up to, which must match the given attributes.
+    pub(crate) fn insert<R: Read>(
+        &self,
+        entry: &mut Entry<R>,
+        checksum: &Checksum,
+        attributes: &ObjectAttributes,
+    ) -> io::Result<PathBuf> {
+        self.write_object(checksum, attributes, |temporary| {
+            entry.unpack(temporary).map(|_| ())
+        })
+    }
+
+    /// Stores the given content of a file with the given checksum, attributes and modification time, unless an
+    /// object holds it already, and returns the path of its object.
+    pub(crate) fn insert_data(
+        &self,
+        data: &[u8],
+        checksum: &Checksum,
+        attributes: &ObjectAttributes,
+        mtime: u64,
+    ) -> io::Result<PathBuf> {
+        self.write_object(checksum, attributes, |temporary| {
+            fs::write(temporary, data)?;
+            attributes.apply(temporary)?;
+            filetime::set_file_mtime(temporary, FileTime::from_unix_time(mtime as i64, 0))
+        })
+    }
+
+    /// Places the given object at the given path, as a hardlink, creating its missing parent folders.
+    ///
+    /// Like when unpacking an archive, an error is returned if the path, once the symlinks of its parent folders are
+    /// resolved, lies outside of the given folder. The object is copied instead if it can't be linked, like when the
+    /// store and the path are on different filesystems, and the given attributes of the object are then set on the
+    /// copy.
+    pub(crate) fn link(
+        &self,
+        object: &Path,
+        attributes: &ObjectAttributes,
+        dir: &Path,
+        path: &Path,
+    ) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+            if !parent.canonicalize()?.starts_with(dir.canonicalize()?) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{} lies outside of {}", path.display(), dir.display()),
+                ));
+            }
+        }
+        if fs::hard_link(object, path).is_err() {
+            fs::copy(object, path)?;
+            attributes.apply(path)?;
+        }
+        Ok(())
+    }
+
+    /// Calls the given function with the path and the metadata of each file of the store
+    fn for_each_file<F>(&self, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(&Path, &fs::Metadata) -> io::Result<()>,
+    {
+        if !self.cache_root.exists() {
+            return Ok(());
+        }
+
+        for dir in fs::read_dir(self.cache_root)? {
+            let dir = dir?.path();
+            if !dir.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(&dir)? {
+                let path = file?.path();
+                let metadata = fs::symlink_metadata(&path)?;
+                f(&path, &metadata)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of objects in the store and the space they take up
+    pub fn usage(&self) -> io::Result<StoreUsage> {
+        let mut usage = StoreUsage::default();
+
+        self.for_each_file(|_, metadata| {
+            usage.objects += 1;
+            usage.size += metadata.len();
+            Ok(())
+        })?;
+        Ok(usage)
+    }
+
+    /// Returns whether the given file of the store is an object that isn't linked anywhere else, or a temporary
+    /// file left by an interrupted write
+    fn is_garbage(path: &Path, metadata: &fs::Metadata) -> bool {
+        metadata.nlink() <= 1
+            || path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with(TEMPORARY_PREFIX))
+    }
+
+    /// Removes the objects that aren't linked anywhere anymore, and returns the number of objects removed and the
+    /// space freed
+    pub fn collect_garbage(&self) -> io::Result<StoreUsage> {
+        let mut freed = StoreUsage::default();
+
+        self.for_each_file(|path, metadata| {
+            if Self::is_garbage(path, metadata) {
+                fs::remove_file(path)?;
+                freed.objects += 1;
+                freed.size += metadata.len();
+            }
+            Ok(())
+        })?;
+
+        // Subfolders left empty are removed too, which fails harmlessly for the others
+        if freed.objects > 0 {
+            for dir in fs::read_dir(self.cache_root)? {
+                let _ = fs::remove_dir(dir?.path());
+            }
+        }
+        Ok(freed)
+    }
+}
@@ -0,0 +1,130 @@
+//! Module to store the world set: the packages and groups explicitly installed by the user.
+//!
+//! The world set records the intent of the user, independently of how it was solved. It is written each time
+//! the dependency graph changes, and can be edited by hand: `nest upgrade` then reconciles the dependency graph
+//! with it.
+
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use failure::{Error, ResultExt};
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::cache::depgraph::{
+    DependencyGraph, GroupName, RequirementKind, RequirementManagementMethod,
+};
+use crate::lock_file::LockFileOwnership;
+use crate::package::PackageRequirement;
+
+/// Structure representing the packages and groups explicitly installed by the user
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug, Default)]
+pub struct World {
+    #[serde(default)]
+    packages: BTreeSet<PackageRequirement>,
+    #[serde(default)]
+    groups: BTreeSet<GroupName>,
+}
+
+impl World {
+    /// Builds the world set made of the requirements of the root of the given dependency graph
+    pub fn from_graph(graph: &DependencyGraph) -> Self {
+        let mut world = World::default();
+
+        for requirement_id in graph.nodes()[&graph.root_id()].requirements() {
+            let requirement = &graph.requirements()[requirement_id];
+
+            if requirement.management_method() != RequirementManagementMethod::Static {
+                continue;
+            }
+            match requirement.kind() {
+                RequirementKind::Package { package_req } => {
+                    world.packages.insert(package_req.clone());
+                }
+                RequirementKind::Group { name } => {
+                    world.groups.insert(name.clone());
+                }
+            }
+        }
+        world
+    }
+
+    /// Loads the world set from the cache, or returns [`None`] if it doesn't exist yet
+    pub fn load_from_cache<P: AsRef<Path>>(path: P) -> Result<Option<Self>, Error> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            let file = File::open(path).with_context(|_| path.display().to_string())?;
+            let world =
+                serde_json::from_reader(&file).with_context(|_| path.display().to_string())?;
+            Ok(Some(world))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Saves the world set back to the cache
+    pub fn save_to_cache<P: AsRef<Path>>(
+        &self,
+        path: P,
+        _: &LockFileOwnership,
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|_| parent.display().to_string())?;
+        }
+
+        let mut file = File::create(path).with_context(|_| path.display().to_string())?;
+        serde_json::to_writer_pretty(&file, self).with_context(|_| path.display().to_string())?;
+        writeln!(file)?;
+        Ok(())
+    }
+
+    /// Returns the requirements on the packages explicitly installed, sorted
+    #[inline]
+    pub fn packages(&self) -> impl Iterator<Item = &PackageRequirement> {
+        self.packages.iter()
+    }
+
+    /// Returns the groups explicitly installed, sorted by name
+    #[inline]
+    pub fn groups(&self) -> impl Iterator<Item = &GroupName> {
+        self.groups.iter()
+    }
+
+    /// Returns true if the given requirement is part of the world set
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate serde_json;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::str::FromStr;
+    /// use libnest::cache::depgraph::{GroupName, RequirementKind};
+    /// use libnest::cache::world::World;
+    /// use libnest::package::PackageRequirement;
+    ///
+    /// let world: World = serde_json::from_str(r#"{
+    ///     "packages": ["sys-bin/coreutils"],
+    ///     "groups": ["@base"]
+    /// }"#)?;
+    ///
+    /// let coreutils = PackageRequirement::parse("sys-bin/coreutils")?;
+    /// assert!(world.contains(&RequirementKind::Package { package_req: coreutils }));
+    /// assert!(world.contains(&RequirementKind::Group { name: GroupName::from_str("@base")? }));
+    /// assert!(!world.contains(&RequirementKind::Group { name: GroupName::from_str("@devel")? }));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn contains(&self, kind: &RequirementKind) -> bool {
+        match kind {
+            RequirementKind::Package { package_req } => self.packages.contains(package_req),
+            RequirementKind::Group { name } => self.groups.contains(name),
+        }
+    }
+}
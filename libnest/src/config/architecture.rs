@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use crate::package::Architecture;
+
+/// The architectures of the packages that can be installed, and where they are installed.
+///
+/// Packages built for the native architecture, or for any architecture, are installed in the root folder. Packages
+/// built for one of the foreign architectures are installed in `/usr/<architecture>`, so that they make up a sysroot
+/// that can be used for cross-compilation. Their instructions are never run, as their binaries can't be executed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ArchitecturePreferences<'a> {
+    native: &'a Architecture,
+    foreign: &'a [Architecture],
+}
+
+impl<'a> ArchitecturePreferences<'a> {
+    /// Creates [`ArchitecturePreferences`] from the native architecture and the foreign architectures whose
+    /// packages can be installed too
+    #[inline]
+    pub fn from(native: &'a Architecture, foreign: &'a [Architecture]) -> Self {
+        ArchitecturePreferences { native, foreign }
+    }
+
+    /// Returns the native architecture
+    #[inline]
+    pub fn native(&self) -> &Architecture {
+        self.native
+    }
+
+    /// Returns the foreign architectures whose packages can be installed, in order of preference
+    #[inline]
+    pub fn foreign(&self) -> &[Architecture] {
+        self.foreign
+    }
+
+    /// Ranks the given architecture: the lower, the more preferred. Packages built for the native architecture
+    /// or for any architecture come first, then those of the foreign architectures, in order.
+    ///
+    /// [`None`] is returned if packages built for the given architecture can't be installed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::ArchitecturePreferences;
+    /// use libnest::package::Architecture;
+    ///
+    /// let native = Architecture::parse("x86_64")?;
+    /// let foreign = [Architecture::parse("aarch64")?];
+    /// let preferences = ArchitecturePreferences::from(&native, &foreign);
+    ///
+    /// assert_eq!(preferences.rank(&native), Some(0));
+    /// assert_eq!(preferences.rank(&Architecture::any()), Some(0));
+    /// assert_eq!(preferences.rank(&foreign[0]), Some(1));
+    /// assert_eq!(preferences.rank(&Architecture::parse("riscv64")?), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rank(&self, architecture: &Architecture) -> Option<usize> {
+        if architecture.is_any() || architecture == self.native {
+            Some(0)
+        } else {
+            self.foreign
+                .iter()
+                .position(|foreign| foreign == architecture)
+                .map(|position| position + 1)
+        }
+    }
+
+    /// Returns whether packages built for the given architecture can be installed
+    #[inline]
+    pub fn is_compatible(&self, architecture: &Architecture) -> bool {
+        self.rank(architecture).is_some()
+    }
+
+    /// Returns whether packages built for the given architecture are installed as foreign packages
+    #[inline]
+    pub fn is_foreign(&self, architecture: &Architecture) -> bool {
+        self.rank(architecture).map_or(false, |rank| rank > 0)
+    }
+
+    /// Returns the folder, relative to the root folder, where the files of the packages built for the given
+    /// architecture are installed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ArchitecturePreferences;
+    /// use libnest::package::Architecture;
+    ///
+    /// let native = Architecture::parse("x86_64")?;
+    /// let foreign = [Architecture::parse("aarch64")?];
+    /// let preferences = ArchitecturePreferences::from(&native, &foreign);
+    ///
+    /// assert_eq!(preferences.installation_prefix(&native), Path::new("/"));
+    /// assert_eq!(preferences.installation_prefix(&foreign[0]), Path::new("/usr/aarch64"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn installation_prefix(&self, architecture: &Architecture) -> PathBuf {
+        if self.is_foreign(architecture) {
+            Path::new("/usr").join(architecture.as_ref() as &str)
+        } else {
+            PathBuf::from("/")
+        }
+    }
+}
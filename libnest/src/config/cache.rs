@@ -0,0 +1,106 @@
+use serde::{Deserialize, Deserializer};
+use serde_derive::{Deserialize, Serialize};
+
+/// Parses a size, either a number of bytes or a number followed by a binary unit (`K`, `M`, `G` or `T`,
+/// optionally followed by `iB` or `B`).
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libnest;
+/// use libnest::config::parse_size;
+///
+/// assert_eq!(parse_size("4096"), Some(4096));
+/// assert_eq!(parse_size("512M"), Some(512 * 1024 * 1024));
+/// assert_eq!(parse_size("2 GiB"), Some(2 * 1024 * 1024 * 1024));
+/// assert_eq!(parse_size("two gigs"), None);
+/// ```
+pub fn parse_size(repr: &str) -> Option<u64> {
+    let repr = repr.trim();
+    let digits = repr
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or_else(|| repr.len());
+    let value: u64 = repr[..digits].parse().ok()?;
+
+    let shift = match repr[digits..].trim().to_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => return None,
+    };
+    value.checked_mul(1 << shift)
+}
+
+/// Deserializes an optional size, given either as a number of bytes or as a string (see [`parse_size()`])
+pub(crate) fn deserialize_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Human(String),
+    }
+
+    match Option::<Size>::deserialize(deserializer)? {
+        Some(Size::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Size::Human(repr)) => parse_size(&repr)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid size '{}'", repr))),
+        None => Ok(None),
+    }
+}
+
+/// Structure holding the limits of the cache of downloaded packages.
+///
+/// Downloaded packages are evicted after each transaction, so that no more than `keep_versions` versions of
+/// each package are kept, and then, if the cache is still larger than `max_size`, the packages that were least
+/// recently used are evicted until it fits. Installed packages are never evicted. The cache is unlimited by default.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(default)]
+pub struct DownloadCacheConfig {
+    #[serde(deserialize_with = "deserialize_size")]
+    max_size: Option<u64>,
+    keep_versions: Option<usize>,
+}
+
+impl DownloadCacheConfig {
+    /// Creates a new [`DownloadCacheConfig`], where the cache is unlimited.
+    #[inline]
+    pub fn new() -> DownloadCacheConfig {
+        DownloadCacheConfig::default()
+    }
+
+    /// Returns the maximum size of the cache in bytes, if any
+    #[inline]
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    /// Returns a mutable reference over the maximum size of the cache in bytes
+    #[inline]
+    pub fn max_size_mut(&mut self) -> &mut Option<u64> {
+        &mut self.max_size
+    }
+
+    /// Returns the number of versions of each package that are kept, the most recent ones, if limited
+    #[inline]
+    pub fn keep_versions(&self) -> Option<usize> {
+        self.keep_versions
+    }
+
+    /// Returns a mutable reference over the number of versions of each package that are kept
+    #[inline]
+    pub fn keep_versions_mut(&mut self) -> &mut Option<usize> {
+        &mut self.keep_versions
+    }
+
+    /// Returns whether the cache is limited in any way
+    #[inline]
+    pub fn is_limited(&self) -> bool {
+        self.max_size.is_some() || self.keep_versions.is_some()
+    }
+}
@@ -0,0 +1,48 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// How the questions asked before modifying the system are answered
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfirmationPolicy {
+    /// The user is asked each question
+    Ask,
+
+    /// Each question is answered with "yes", without asking the user
+    Yes,
+
+    /// Each question is answered with its default answer, without asking the user
+    Default,
+}
+
+impl Default for ConfirmationPolicy {
+    fn default() -> Self {
+        ConfirmationPolicy::Ask
+    }
+}
+
+/// Structure holding the settings of the confirmations asked before modifying the system.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(default)]
+pub struct ConfirmationConfig {
+    policy: ConfirmationPolicy,
+}
+
+impl ConfirmationConfig {
+    /// Creates a new [`ConfirmationConfig`], where the user is asked each question.
+    #[inline]
+    pub fn new() -> ConfirmationConfig {
+        ConfirmationConfig::default()
+    }
+
+    /// Returns how the questions are answered
+    #[inline]
+    pub fn policy(&self) -> ConfirmationPolicy {
+        self.policy
+    }
+
+    /// Returns a mutable reference over how the questions are answered
+    #[inline]
+    pub fn policy_mut(&mut self) -> &mut ConfirmationPolicy {
+        &mut self.policy
+    }
+}
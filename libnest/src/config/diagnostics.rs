@@ -0,0 +1,244 @@
+//! Diagnostics of the configuration files: the location of parse errors, and the keys that aren't understood
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use failure::ResultExt;
+use toml::value::{Table, Value};
+
+use super::errors::{ConfigError, ConfigErrorKind};
+use super::Config;
+
+/// The largest number of edits turning an unknown key into a known one for the latter to be suggested
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// A configuration file, as read and parsed
+#[derive(Clone, Debug)]
+pub(crate) struct TomlFile {
+    path: PathBuf,
+    content: String,
+    value: Value,
+}
+
+impl TomlFile {
+    /// Reads and parses the TOML file located at the given path, without interpreting it
+    pub(crate) fn read(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path)
+            .context(path.display().to_string())
+            .context(ConfigErrorKind::ConfigLoadError)?;
+        let value = toml::from_str(&content).map_err(|e| located_error(path, &content, &e))?;
+
+        Ok(TomlFile {
+            path: path.to_path_buf(),
+            content,
+            value,
+        })
+    }
+
+    /// Returns the path of the file
+    #[inline]
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the settings held by the file
+    #[inline]
+    pub(crate) fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Returns a mutable reference to the settings held by the file
+    #[inline]
+    pub(crate) fn value_mut(&mut self) -> &mut Value {
+        &mut self.value
+    }
+
+    /// Interprets the file on its own, returning the located error it contains, if any.
+    ///
+    /// Errors are only located in the files they come from, as merging the files loses the positions of their values.
+    pub(crate) fn find_error(&self) -> Option<ConfigError> {
+        toml::from_str::<Config>(&self.content)
+            .err()
+            .map(|e| located_error(&self.path, &self.content, &e))
+    }
+}
+
+/// Returns a parse error prefixed by the position of the error in the given file, like `path:line:column`.
+///
+/// When the parser doesn't know the position of the error, the key it concerns is looked for in the file.
+fn located_error(path: &Path, content: &str, error: &toml::de::Error) -> ConfigError {
+    let mut message = error.to_string();
+
+    let position = match error.line_col() {
+        Some((line, column)) => {
+            let suffix = format!(" at line {}", line + 1);
+            if message.ends_with(&suffix) {
+                message.truncate(message.len() - suffix.len());
+            }
+            Some((line + 1, column + 1))
+        }
+        None => message
+            .rsplit(" for key `")
+            .next()
+            .filter(|_| message.contains(" for key `"))
+            .and_then(|key| key.split('`').next())
+            .and_then(|key| locate_key(content, &key.split('.').collect::<Vec<_>>())),
+    };
+
+    let location = match position {
+        Some((line, column)) => format!("{}:{}:{}", path.display(), line, column),
+        None => path.display().to_string(),
+    };
+    failure::err_msg(format!("{}: {}", location, message))
+        .context(ConfigErrorKind::ConfigParseError)
+        .into()
+}
+
+/// Splits a dotted TOML key into its parts, removing the quotes around them
+pub(crate) fn split_key(key: &str) -> Vec<String> {
+    key.split('.')
+        .map(|part| part.trim().trim_matches('"').trim_matches('\'').to_string())
+        .collect()
+}
+
+/// Returns the 1-based line and column where the given key is defined in the given TOML document, if it can be
+/// found.
+///
+/// Only table headers and `key = value` lines are looked at, which covers the way configuration files are written.
+fn locate_key(content: &str, key: &[&str]) -> Option<(usize, usize)> {
+    let mut table = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let column = line.len() - trimmed.len() + 1;
+
+        if trimmed.starts_with('#') {
+            continue;
+        } else if trimmed.starts_with('[') {
+            let header = trimmed.trim_start_matches('[');
+            table = split_key(header.split(']').next().unwrap_or_default());
+            if table == key {
+                return Some((i + 1, column));
+            }
+        } else if let Some(name) = trimmed.split('=').next().filter(|_| trimmed.contains('=')) {
+            let mut full_key = table.clone();
+            full_key.extend(split_key(name));
+            if full_key == key {
+                return Some((i + 1, column));
+            }
+        }
+    }
+    None
+}
+
+/// A key of a configuration file that doesn't match any setting, and is thus ignored
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct UnknownKey {
+    path: PathBuf,
+    key: String,
+    position: Option<(usize, usize)>,
+    suggestion: Option<String>,
+}
+
+impl UnknownKey {
+    /// Returns the path of the file the key is defined in
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the full name of the key, dotted
+    #[inline]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the 1-based line and column where the key is defined, if they could be found
+    #[inline]
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.position
+    }
+
+    /// Returns the name of the known key that was most likely meant instead, if there is a close enough one
+    #[inline]
+    pub fn suggestion(&self) -> Option<&str> {
+        self.suggestion.as_ref().map(String::as_str)
+    }
+}
+
+impl std::fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some((line, column)) = self.position {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        write!(f, ": unknown key `{}`", self.key)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean `{}`?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the known key closest to the given unknown one, if it is close enough to be a typo
+pub(crate) fn suggest(key: &str, known: &Table) -> Option<String> {
+    known
+        .keys()
+        .map(|candidate| (strsim::levenshtein(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min()
+        .map(|(_, candidate)| candidate.clone())
+}
+
+/// Collects the keys of the given table that aren't in the known one, recursively
+fn collect_unknown_keys(
+    file: &TomlFile,
+    table: &Table,
+    known: &Table,
+    prefix: &mut Vec<String>,
+    unknown_keys: &mut Vec<UnknownKey>,
+) {
+    for (key, value) in table {
+        prefix.push(key.clone());
+
+        match (value, known.get(key)) {
+            (Value::Table(table), Some(Value::Table(known))) => {
+                collect_unknown_keys(file, table, known, prefix, unknown_keys)
+            }
+            (_, Some(_)) => (),
+            (_, None) => unknown_keys.push(UnknownKey {
+                path: file.path.clone(),
+                key: prefix.join("."),
+                position: locate_key(
+                    &file.content,
+                    &prefix.iter().map(String::as_str).collect::<Vec<_>>(),
+                ),
+                suggestion: suggest(key, known),
+            }),
+        }
+        prefix.pop();
+    }
+}
+
+/// Returns the keys of the given files that don't match any setting of the configuration they make up.
+///
+/// The known keys are those of the configuration once serialized back: maps like the repositories keep the keys
+/// they were given, while unknown settings are dropped when the files are deserialized.
+pub(crate) fn find_unknown_keys(config: &Config, files: &[TomlFile]) -> Vec<UnknownKey> {
+    let known = match Value::try_from(config) {
+        Ok(Value::Table(known)) => known,
+        _ => return Vec::new(),
+    };
+
+    let mut unknown_keys = Vec::new();
+    for file in files {
+        if let Value::Table(table) = &file.value {
+            let mut file_keys = Vec::new();
+
+            collect_unknown_keys(file, table, &known, &mut Vec::new(), &mut file_keys);
+            file_keys.sort_by_key(|key| key.position);
+            unknown_keys.extend(file_keys);
+        }
+    }
+    unknown_keys
+}
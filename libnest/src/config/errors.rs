@@ -18,6 +18,39 @@ pub enum ConfigErrorKind {
     /// The data in the configuration file could not be parsed
     #[fail(display = "unable to parse the configuration file")]
     ConfigParseError,
+
+    /// The configuration files hold keys that don't match any setting, and `deny_unknown_keys` is set
+    #[fail(display = "the configuration holds unknown keys")]
+    UnknownKeys,
+
+    /// A key given to read or modify the configuration doesn't match any setting
+    #[fail(display = "no setting has the given key")]
+    UnknownKey,
+
+    /// A configuration file was written for a layout of the configuration newer than the one this version supports
+    #[fail(display = "the configuration file was written for a newer version of Nest")]
+    UnsupportedVersion,
+
+    /// The configuration file could not be written
+    #[fail(display = "unable to write the configuration file")]
+    ConfigWriteError,
+
+    /// An environment variable overriding the configuration has an invalid value
+    #[fail(display = "invalid configuration override in the environment")]
+    EnvOverrideError,
+
+    /// The credentials of a repository could not be loaded
+    #[fail(display = "unable to load the credentials of the repository")]
+    CredentialsLoadError,
+
+    /// The file holding the credentials of a repository can be accessed by other users than root
+    #[fail(display = "the credentials file must only be accessible by root")]
+    InsecureCredentialsFile,
 }
 
 use_as_error!(ConfigError, ConfigErrorKind);
+
+/// Strong type to represent an error message related to the parsing of a target triple
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Fail)]
+#[fail(display = "{}: invalid target triple", 0)]
+pub struct TargetTripleParseError(pub String);
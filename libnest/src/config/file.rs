@@ -0,0 +1,420 @@
+//! Edition of the files the configuration is loaded from
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use failure::ResultExt;
+use toml::value::{Table, Value};
+use toml_edit::{DocumentMut, InlineTable, Item, TableLike};
+
+use super::diagnostics::suggest;
+use super::errors::{ConfigError, ConfigErrorKind};
+use super::migration::{self, MigrationStep};
+use super::{merge_toml, Config, MirrorUrl};
+
+/// A file the configuration is loaded from, either the configuration file itself or one of its fragments, opened
+/// to be modified.
+///
+/// Only the settings that are changed are touched: they are edited in place with `toml_edit`, keeping the comments
+/// and the layout of the file. Migrating the file (see [`ConfigFile::migrate()`]) makes it be written back from its
+/// parsed content instead, losing them.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfigFile {
+    path: PathBuf,
+    original: Option<String>,
+    value: Value,
+    content: Option<String>,
+}
+
+impl ConfigFile {
+    /// Loads the configuration file at the given path, to be modified. A missing file is loaded as an empty one,
+    /// and is created when saved.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+
+        let original = if path.exists() {
+            Some(
+                fs::read_to_string(path)
+                    .context(path.display().to_string())
+                    .context(ConfigErrorKind::ConfigLoadError)?,
+            )
+        } else {
+            None
+        };
+        let value = match &original {
+            Some(content) => toml::from_str(content)
+                .context(path.display().to_string())
+                .context(ConfigErrorKind::ConfigParseError)?,
+            None => Value::Table(Table::new()),
+        };
+
+        Ok(ConfigFile {
+            path: path.to_path_buf(),
+            content: Some(original.clone().unwrap_or_default()),
+            original,
+            value,
+        })
+    }
+
+    /// Returns the path of the file
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the content of the file when it was loaded, or [`None`] if it didn't exist
+    #[inline]
+    pub fn original(&self) -> Option<&str> {
+        self.original.as_ref().map(String::as_str)
+    }
+
+    /// Returns the settings held by the file, as modified
+    #[inline]
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Returns the table of the repositories, if the file defines any
+    fn repositories(&self) -> Option<&Table> {
+        self.value.get("repositories").and_then(Value::as_table)
+    }
+
+    /// Returns the table at the given path of the settings, created where missing. Values that aren't tables are
+    /// replaced.
+    fn table_mut(&mut self, path: &[&str]) -> &mut Table {
+        let mut current = self
+            .value
+            .as_table_mut()
+            .expect("a TOML document is always a table");
+
+        for part in path {
+            let next = current
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+
+            if !next.is_table() {
+                *next = Value::Table(Table::new());
+            }
+            current = next.as_table_mut().unwrap();
+        }
+        current
+    }
+
+    /// Applies the given edit to the document of the file, keeping its comments and layout. If the document can't be
+    /// edited, the file is written back from its parsed content.
+    fn edit_document<F: FnOnce(&mut DocumentMut)>(&mut self, edit: F) {
+        self.content = self
+            .content
+            .as_ref()
+            .and_then(|content| content.parse::<DocumentMut>().ok())
+            .map(|mut document| {
+                edit(&mut document);
+                document.to_string()
+            });
+    }
+
+    /// Sets the value of the key at the given path, creating the tables holding it if needed
+    fn set_path(&mut self, path: &[&str], value: Value) {
+        let (last, tables) = path.split_last().expect("a key has at least one part");
+
+        self.edit_document(|document| {
+            set_item(document_table_mut(document, tables), last, to_item(&value))
+        });
+        self.table_mut(tables).insert(last.to_string(), value);
+    }
+
+    /// Returns the value of the given dotted key, if the file defines it
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        key.split('.')
+            .try_fold(&self.value, |value, part| value.get(part))
+    }
+
+    /// Sets the value of the given dotted key, creating the tables holding it if needed.
+    ///
+    /// The value defining the key is replaced, keeping its comments, or a new one is added to the table holding it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate toml;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::ConfigFile;
+    /// use toml::Value;
+    ///
+    /// let path = std::env::temp_dir().join("nest-config-file-set.toml");
+    /// std::fs::write(&path, "# Downloads\n[network]\nmax_download_rate = \"2M\" # At night\n")?;
+    ///
+    /// let mut file = ConfigFile::load(&path)?;
+    /// file.set("network.max_download_rate", Value::String("5M".to_string()));
+    /// file.set("snapshot.backend", Value::String("btrfs".to_string()));
+    ///
+    /// assert_eq!(
+    ///     file.to_toml()?,
+    ///     "# Downloads\n[network]\nmax_download_rate = \"5M\" # At night\n\n[snapshot]\nbackend = \"btrfs\"\n"
+    /// );
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set(&mut self, key: &str, value: Value) {
+        let parts: Vec<_> = key.split('.').collect();
+        self.set_path(&parts, value);
+    }
+
+    /// Returns whether the file defines settings for the given repository
+    pub fn defines_repository(&self, name: &str) -> bool {
+        self.repositories()
+            .map_or(false, |repositories| repositories.contains_key(name))
+    }
+
+    /// Returns whether the file defines the mirrors of the given repository
+    pub fn defines_mirrors(&self, name: &str) -> bool {
+        self.repositories()
+            .and_then(|repositories| repositories.get(name))
+            .and_then(|repository| repository.get("mirrors"))
+            .is_some()
+    }
+
+    /// Defines the given repository in the file, with the given mirrors
+    pub fn add_repository(&mut self, name: &str, mirrors: &[MirrorUrl]) {
+        self.set_path(&["repositories", name], Value::Table(Table::new()));
+        self.set_mirrors(name, mirrors);
+    }
+
+    /// Removes the settings of the given repository from the file, returning whether it defined any
+    pub fn remove_repository(&mut self, name: &str) -> bool {
+        if !self.defines_repository(name) {
+            return false;
+        }
+        self.edit_document(|document| {
+            document_table_mut(document, &["repositories"]).remove(name);
+        });
+        self.table_mut(&["repositories"]).remove(name).is_some()
+    }
+
+    /// Sets whether the given repository is disabled
+    pub fn set_disabled(&mut self, name: &str, disabled: bool) {
+        self.set_path(
+            &["repositories", name, "disabled"],
+            Value::Boolean(disabled),
+        );
+    }
+
+    /// Sets the mirrors of the given repository, replacing those the file defines
+    pub fn set_mirrors(&mut self, name: &str, mirrors: &[MirrorUrl]) {
+        let mirrors = mirrors
+            .iter()
+            .map(|mirror| Value::String(mirror.as_str().to_string()))
+            .collect();
+
+        self.set_path(&["repositories", name, "mirrors"], Value::Array(mirrors));
+    }
+
+    /// Migrates the file to the current layout of the configuration, returning the changes that were made
+    pub fn migrate(&mut self) -> Result<Vec<MigrationStep>, ConfigError> {
+        let steps = migration::migrate(&self.path, &mut self.value)?;
+
+        if self.is_modified() {
+            self.content = None;
+        }
+        Ok(steps)
+    }
+
+    /// Returns the content of the file, as modified
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        if let Some(content) = &self.content {
+            return Ok(content.clone());
+        }
+        Ok(toml::to_string(&self.value)
+            .context(self.path.display().to_string())
+            .context(ConfigErrorKind::ConfigWriteError)?)
+    }
+
+    /// Returns whether the file was modified since it was loaded
+    pub fn is_modified(&self) -> bool {
+        let original: Option<Value> = self
+            .original
+            .as_ref()
+            .and_then(|content| toml::from_str(content).ok());
+
+        original.as_ref() != Some(&self.value)
+    }
+
+    /// Writes the file back, replacing it at once so that it is never left partially written
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let content = self.to_toml()?;
+        let partial_path = self.path.with_extension("toml.tmp");
+
+        let res: Result<_, std::io::Error> = try {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let mut file = fs::File::create(&partial_path)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+
+            // The permissions of the file are kept, as it may hold credentials
+            if self.path.exists() {
+                fs::set_permissions(&partial_path, fs::metadata(&self.path)?.permissions())?;
+            }
+            fs::rename(&partial_path, &self.path)?;
+        };
+        Ok(res
+            .context(self.path.display().to_string())
+            .context(ConfigErrorKind::ConfigWriteError)?)
+    }
+}
+
+/// Converts the given value to an item of a TOML document being edited. Tables are written as sections, except
+/// within arrays and inline tables.
+fn to_item(value: &Value) -> Item {
+    match value {
+        Value::Table(table) => {
+            let mut section = toml_edit::Table::new();
+            for (key, value) in table {
+                section.insert(key, to_item(value));
+            }
+            Item::Table(section)
+        }
+        value => Item::Value(to_value(value)),
+    }
+}
+
+/// Converts the given value to a value of a TOML document being edited
+fn to_value(value: &Value) -> toml_edit::Value {
+    match value {
+        Value::String(string) => string.as_str().into(),
+        Value::Integer(integer) => (*integer).into(),
+        Value::Float(float) => (*float).into(),
+        Value::Boolean(boolean) => (*boolean).into(),
+        Value::Datetime(datetime) => {
+            let datetime = datetime.to_string();
+            datetime
+                .parse::<toml_edit::Datetime>()
+                .map(toml_edit::Value::from)
+                .unwrap_or_else(|_| datetime.as_str().into())
+        }
+        Value::Array(array) => toml_edit::Value::Array(array.iter().map(to_value).collect()),
+        Value::Table(table) => toml_edit::Value::InlineTable(
+            table
+                .iter()
+                .map(|(key, value)| (key.as_str(), to_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Returns the table at the given path of the given document, created where missing like with
+/// [`ConfigFile::table_mut()`]. The sections created are implicit, so that they only get a header if they hold
+/// values, and the tables created within inline tables are inline too.
+fn document_table_mut<'a>(document: &'a mut DocumentMut, path: &[&str]) -> &'a mut dyn TableLike {
+    let mut current: &mut dyn TableLike = document.as_table_mut();
+    let mut inline = false;
+
+    for part in path {
+        let next = current.entry(part).or_insert(Item::None);
+
+        if !next.is_table_like() {
+            *next = if inline {
+                Item::Value(toml_edit::Value::InlineTable(InlineTable::new()))
+            } else {
+                let mut section = toml_edit::Table::new();
+                section.set_implicit(true);
+                Item::Table(section)
+            };
+        }
+        inline = next.is_inline_table();
+        current = next.as_table_like_mut().unwrap();
+    }
+    current
+}
+
+/// Sets the given key of the given table of a document to the given item. A value that is replaced by another one
+/// keeps its comments.
+fn set_item(table: &mut dyn TableLike, key: &str, item: Item) {
+    let item = match (table.get_mut(key), item) {
+        (Some(Item::Value(existing)), Item::Value(mut value)) => {
+            *value.decor_mut() = existing.decor().clone();
+            *existing = value;
+            return;
+        }
+        (_, item) => item,
+    };
+    table.insert(key, item);
+}
+
+impl Config {
+    /// Returns the value of the setting with the given dotted key, as the merged configuration defines it with its
+    /// defaults, or [`None`] if the setting is unset or if no setting has this key.
+    pub fn get(&self, key: &str) -> Option<Value> {
+        key.split('.')
+            .try_fold(Value::try_from(self).ok()?, |value, part| match value {
+                Value::Table(mut table) => table.remove(part),
+                _ => None,
+            })
+    }
+
+    /// Returns the key of the setting that was most likely meant instead of the given one, if there is a close
+    /// enough one
+    pub fn suggest_key(&self, key: &str) -> Option<String> {
+        let (parent, name) = match key.rfind('.') {
+            Some(dot) => (Some(&key[..dot]), &key[dot + 1..]),
+            None => (None, key),
+        };
+        let siblings = match parent {
+            Some(parent) => self.get(parent)?,
+            None => Value::try_from(self).ok()?,
+        };
+
+        let suggestion = suggest(name, siblings.as_table()?)?;
+        Some(match parent {
+            Some(parent) => format!("{}.{}", parent, suggestion),
+            None => suggestion,
+        })
+    }
+
+    /// Checks that the given dotted key is that of a setting, suggesting the closest one otherwise.
+    ///
+    /// As unset settings can't be told apart from unknown ones, the key must have been given a value.
+    pub fn check_key(&self, key: &str) -> Result<(), ConfigError> {
+        if self.get(key).is_some() {
+            return Ok(());
+        }
+
+        let message = match self.suggest_key(key) {
+            Some(suggestion) => format!("`{}`, did you mean `{}`?", key, suggestion),
+            None => format!("`{}`", key),
+        };
+        Err(failure::err_msg(message)
+            .context(ConfigErrorKind::UnknownKey)
+            .into())
+    }
+
+    /// Returns the configuration the given files would make up, merged in order, to check that they are valid
+    /// before they are saved.
+    ///
+    /// Like with [`Config::load()`], the values given by the environment override those of the files.
+    pub fn from_files(files: &[ConfigFile]) -> Result<Config, ConfigError> {
+        let mut value = Value::Table(Table::new());
+
+        for file in files {
+            merge_toml(&mut value, file.value.clone());
+        }
+
+        let mut config: Config = value
+            .try_into()
+            .context(
+                files
+                    .first()
+                    .map(|file| file.path.display().to_string())
+                    .unwrap_or_default(),
+            )
+            .context(ConfigErrorKind::ConfigParseError)?;
+        config.override_from_env()?;
+        Ok(config)
+    }
+}
@@ -0,0 +1,65 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::package::LicenseExpression;
+
+/// Structure holding the license policy of the system: the licenses the installed packages shouldn't be under.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(default)]
+pub struct LicenseConfig {
+    deny: Vec<String>,
+}
+
+impl LicenseConfig {
+    /// Creates a new [`LicenseConfig`], without any denied license.
+    #[inline]
+    pub fn new() -> LicenseConfig {
+        LicenseConfig::default()
+    }
+
+    /// Returns the SPDX identifiers of the denied licenses
+    #[inline]
+    pub fn deny(&self) -> &[String] {
+        &self.deny
+    }
+
+    /// Returns a mutable reference over the SPDX identifiers of the denied licenses
+    #[inline]
+    pub fn deny_mut(&mut self) -> &mut Vec<String> {
+        &mut self.deny
+    }
+
+    /// Returns whether the license with the given SPDX identifier is denied.
+    ///
+    /// SPDX identifiers are compared case-insensitively, as the specification requires.
+    #[inline]
+    pub fn is_denied(&self, id: &str) -> bool {
+        self.deny
+            .iter()
+            .any(|denied| denied.eq_ignore_ascii_case(id))
+    }
+
+    /// Returns whether a package under the given license expression complies with this policy, that is whether
+    /// its licenses can be complied with without using any denied license
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::LicenseConfig;
+    /// use libnest::package::LicenseExpression;
+    ///
+    /// let mut licenses = LicenseConfig::new();
+    /// licenses.deny_mut().push(String::from("AGPL-3.0-only"));
+    ///
+    /// assert!(licenses.allows(&LicenseExpression::parse("MIT OR AGPL-3.0-only")?));
+    /// assert!(!licenses.allows(&LicenseExpression::parse("agpl-3.0-only")?));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn allows(&self, expression: &LicenseExpression) -> bool {
+        expression.is_satisfiable_without(&|id| self.is_denied(id))
+    }
+}
@@ -0,0 +1,53 @@
+use serde_derive::{Deserialize, Serialize};
+
+use super::cache::deserialize_size;
+
+/// The size from which the log file is rotated, unless configured otherwise
+const DEFAULT_LOG_MAX_SIZE: u64 = 1024 * 1024;
+
+/// The number of rotated log files kept, unless configured otherwise
+const DEFAULT_LOG_ROTATIONS: usize = 3;
+
+/// Structure holding the settings of the log file.
+///
+/// Once the log file is larger than `max_size`, it is renamed with the `.1` suffix, the previous rotated files
+/// being shifted to `.2`, `.3` and so on, and only the `rotations` most recent rotated files are kept.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(default)]
+pub struct LogConfig {
+    #[serde(deserialize_with = "deserialize_size")]
+    max_size: Option<u64>,
+    rotations: Option<usize>,
+}
+
+impl LogConfig {
+    /// Creates a new [`LogConfig`], with the default settings.
+    #[inline]
+    pub fn new() -> LogConfig {
+        LogConfig::default()
+    }
+
+    /// Returns the size, in bytes, from which the log file is rotated
+    #[inline]
+    pub fn max_size(&self) -> u64 {
+        self.max_size.unwrap_or(DEFAULT_LOG_MAX_SIZE)
+    }
+
+    /// Returns a mutable reference over the size, in bytes, from which the log file is rotated
+    #[inline]
+    pub fn max_size_mut(&mut self) -> &mut Option<u64> {
+        &mut self.max_size
+    }
+
+    /// Returns the number of rotated log files kept
+    #[inline]
+    pub fn rotations(&self) -> usize {
+        self.rotations.unwrap_or(DEFAULT_LOG_ROTATIONS)
+    }
+
+    /// Returns a mutable reference over the number of rotated log files kept
+    #[inline]
+    pub fn rotations_mut(&mut self) -> &mut Option<usize> {
+        &mut self.rotations
+    }
+}
@@ -0,0 +1,176 @@
+//! Migration of the configuration files written for an older layout of the configuration
+//!
+//! Each file records the version of the layout it was written for in its `version` key, files without one being
+//! written for the first layout. Files written for an older layout are migrated when they are loaded, and written
+//! back only on demand.
+
+use std::path::Path;
+
+use toml::value::{Table, Value};
+
+use super::errors::{ConfigError, ConfigErrorKind};
+
+/// The version of the current layout of the configuration
+pub static CONFIG_VERSION: u32 = 2;
+
+/// The version of the layout of the files that don't record one
+const INITIAL_VERSION: u32 = 1;
+
+/// A change of the layout of the configuration, from a version to the next one
+struct Migration {
+    /// The version the files are migrated from
+    from: u32,
+
+    /// The keys that are moved, given as dotted paths from the root of the file, along with their new location.
+    /// Renamed keys and moved sections are both moves.
+    moves: &'static [(&'static str, &'static str)],
+}
+
+/// The changes of the layout of the configuration, in order
+static MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    moves: &[("paths.lockfile_path", "paths.lock_file")],
+}];
+
+/// A change made to a configuration file to migrate it to the current layout
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MigrationStep {
+    from: String,
+    to: String,
+    dropped: bool,
+}
+
+impl MigrationStep {
+    /// Returns the dotted path of the key that was moved
+    #[inline]
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    /// Returns the dotted path the key was moved to
+    #[inline]
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    /// Returns whether the key was dropped, because the file already defines its new location
+    #[inline]
+    pub fn dropped(&self) -> bool {
+        self.dropped
+    }
+}
+
+impl std::fmt::Display for MigrationStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.dropped {
+            write!(
+                f,
+                "`{}` removed, as `{}` is already defined",
+                self.from, self.to
+            )
+        } else {
+            write!(f, "`{}` moved to `{}`", self.from, self.to)
+        }
+    }
+}
+
+/// Removes the value at the given dotted path of the given table, if any
+fn take(table: &mut Table, path: &str) -> Option<Value> {
+    let mut parts: Vec<_> = path.split('.').collect();
+    let last = parts.pop()?;
+
+    let mut current = table;
+    for part in parts {
+        current = current.get_mut(part)?.as_table_mut()?;
+    }
+    current.remove(last)
+}
+
+/// Inserts the given value at the given dotted path of the given table, creating the missing tables, unless a value
+/// is already defined there. Returns whether the value was inserted.
+fn put(table: &mut Table, path: &str, value: Value) -> bool {
+    let mut parts: Vec<_> = path.split('.').collect();
+    let last = match parts.pop() {
+        Some(last) => last,
+        None => return false,
+    };
+
+    let mut current = table;
+    for part in parts {
+        let next = current
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+
+        current = match next.as_table_mut() {
+            Some(next) => next,
+            None => return false,
+        };
+    }
+
+    if current.contains_key(last) {
+        return false;
+    }
+    current.insert(last.to_string(), value);
+    true
+}
+
+/// Returns the version of the layout the configuration file at the given path was written for
+fn version_of(path: &Path, table: &Table) -> Result<u32, ConfigError> {
+    match table.get("version") {
+        None => Ok(INITIAL_VERSION),
+        Some(Value::Integer(version))
+            if *version >= i64::from(INITIAL_VERSION) && *version <= i64::from(CONFIG_VERSION) =>
+        {
+            Ok(*version as u32)
+        }
+        Some(version) => Err(
+            failure::err_msg(format!("{}: version {}", path.display(), version))
+                .context(ConfigErrorKind::UnsupportedVersion)
+                .into(),
+        ),
+    }
+}
+
+/// Returns the version of the current layout, that of the configuration when no file records one
+#[inline]
+pub(crate) fn current_version() -> u32 {
+    CONFIG_VERSION
+}
+
+/// Migrates the content of the configuration file at the given path to the current layout, returning the changes
+/// that were made.
+///
+/// The file then records the current version, unless it was already up to date.
+pub(crate) fn migrate(path: &Path, value: &mut Value) -> Result<Vec<MigrationStep>, ConfigError> {
+    let table = match value.as_table_mut() {
+        Some(table) => table,
+        None => return Ok(Vec::new()),
+    };
+
+    let version = version_of(path, table)?;
+    if version == CONFIG_VERSION {
+        return Ok(Vec::new());
+    }
+
+    let mut steps = Vec::new();
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.from >= version)
+    {
+        for (from, to) in migration.moves {
+            if let Some(moved) = take(table, from) {
+                steps.push(MigrationStep {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    dropped: !put(table, to, moved),
+                });
+            }
+        }
+    }
+
+    table.insert(
+        "version".to_string(),
+        Value::Integer(i64::from(CONFIG_VERSION)),
+    );
+    Ok(steps)
+}
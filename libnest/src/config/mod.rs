@@ -12,36 +12,99 @@
 //!
 //! It also provides a way to load a `Config` from a TOML file.
 
+mod architecture;
+mod cache;
+mod confirmation;
+mod diagnostics;
 pub mod errors;
+mod file;
+mod license;
+mod log;
+mod migration;
+mod network;
 mod paths;
 mod repository;
-
+mod sandbox;
+mod snapshot;
+mod sysroot;
+mod tls;
+mod torrent;
+mod triggers;
+mod validate;
+
+pub use self::architecture::ArchitecturePreferences;
+pub use self::cache::{parse_size, DownloadCacheConfig};
+pub use self::confirmation::{ConfirmationConfig, ConfirmationPolicy};
+pub use self::diagnostics::UnknownKey;
 pub use self::errors::*;
+pub use self::file::ConfigFile;
+pub use self::license::LicenseConfig;
+pub use self::log::LogConfig;
+pub use self::migration::{MigrationStep, CONFIG_VERSION};
+pub use self::network::{NetworkConfig, ProxyUrl};
 pub use self::paths::ConfigPaths;
-pub use self::repository::{MirrorUrl, RepositoryConfig};
+pub use self::repository::{
+    parse_mirror_url, Credentials, MirrorUrl, RepositoryConfig, RepositoryPreferences,
+    RepositoryTrust,
+};
+pub use self::sandbox::{SandboxConfig, Sandboxing};
+pub use self::snapshot::{SnapshotBackend, SnapshotConfig};
+pub use self::sysroot::TargetTriple;
+pub use self::tls::TlsConfig;
+pub use self::torrent::TorrentConfig;
+pub use self::triggers::TriggersConfig;
+pub use self::validate::{ConfigIssue, ConfigIssueKind};
 
 use failure::*;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
+use std::env;
+use std::fs;
 use std::marker::PhantomData;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use lazy_static::lazy_static;
 use serde_derive::{Deserialize, Serialize};
-use toml;
+use toml::{self, Value};
+use url::Url;
+use url_serde::Serde;
 
+use crate::cache::alternatives::Alternatives;
 use crate::cache::available::AvailablePackages;
 use crate::cache::depgraph::DependencyGraph;
 use crate::cache::downloaded::DownloadedPackages;
+use crate::cache::held::HeldPackages;
+use crate::cache::history::History;
 use crate::cache::installed::InstalledPackages;
-use crate::lock_file::LockFileOwnership;
+use crate::cache::keys::Keystore;
+use crate::cache::mirrors::MirrorsHealth;
+use crate::cache::store::ContentStore;
+use crate::cache::world::World;
+use crate::lock_file::{LockFileOwnership, LockMode, LockWait};
+use crate::package::{Architecture, PackageShortName, RepositoryName};
 use crate::repository::Repository;
 
+use self::diagnostics::{find_unknown_keys, TomlFile};
+
 lazy_static! {
     static ref NEST_PATH_CONFIG: &'static Path = Path::new("/etc/nest/config.toml");
+    static ref NEST_PATH_CONFIG_DIR: &'static Path = Path::new("/etc/nest/config.d/");
 }
 
+/// Environment variable overriding the path of the configuration file
+pub static NEST_ENV_CONFIG: &str = "NEST_CONFIG";
+
+/// Environment variable overriding the directory holding all caches
+pub static NEST_ENV_CACHE_DIR: &str = "NEST_CACHE_DIR";
+
+/// Environment variable overriding the root path where packages are installed (see [`ConfigPaths::with_install_root()`])
+pub static NEST_ENV_INSTALL_DIR: &str = "NEST_INSTALL_DIR";
+
+/// Environment variable overriding the proxy used for both HTTP and HTTPS requests
+pub static NEST_ENV_PROXY: &str = "NEST_PROXY";
+
+/// Environment variable overriding the native architecture, that of the packages installed in the root folder
+pub static NEST_ENV_ARCH: &str = "NEST_ARCH";
+
 /// A handle to represent a configuration for Nest.
 ///
 /// This handle is given as parameter to each libnest function so they can use a custom configuration even in an asynchronous context.
@@ -61,14 +124,61 @@ lazy_static! {
 /// ```
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default = "migration::current_version")]
+    version: u32,
     #[serde(default)]
     paths: ConfigPaths,
     #[serde(default)]
+    network: NetworkConfig,
+    #[serde(default)]
     repositories: HashMap<String, RepositoryConfig>,
+    #[serde(default)]
+    sandbox: SandboxConfig,
+    #[serde(default)]
+    download_cache: DownloadCacheConfig,
+    #[serde(default)]
+    confirmation: ConfirmationConfig,
+    #[serde(default)]
+    licenses: LicenseConfig,
+    #[serde(default)]
+    log: LogConfig,
+    #[serde(default)]
+    torrent: TorrentConfig,
+    #[serde(default)]
+    snapshot: SnapshotConfig,
+    #[serde(default)]
+    triggers: TriggersConfig,
+    #[serde(default)]
+    providers: HashMap<PackageShortName, Vec<PackageShortName>>,
+    #[serde(default)]
+    pins: HashMap<PackageShortName, RepositoryName>,
+    #[serde(default = "Architecture::host")]
+    architecture: Architecture,
+    #[serde(default)]
+    foreign_architectures: Vec<Architecture>,
+    #[serde(default)]
+    jobs: Option<usize>,
+    #[serde(default)]
+    deny_unknown_keys: bool,
+    #[serde(skip)]
+    dry_run: bool,
+    #[serde(skip)]
+    lock_wait: LockWait,
+    #[serde(skip)]
+    sysroot: Option<TargetTriple>,
+    #[serde(skip)]
+    unknown_keys: Vec<UnknownKey>,
+    #[serde(skip)]
+    outdated_files: Vec<PathBuf>,
 }
 
 impl Config {
-    /// Loads the configuration located at the default path
+    /// Loads the configuration located at the default path, merged with the configuration
+    /// fragments located in the default fragments directory.
+    ///
+    /// The path of the configuration file can be overridden using the `NEST_CONFIG` environment
+    /// variable, and the loaded values are then overridden by the environment as described
+    /// in [`Config::override_from_env()`].
     ///
     /// # Examples
     ///
@@ -83,7 +193,88 @@ impl Config {
     /// ```
     #[inline]
     pub fn load() -> Result<Config, ConfigError> {
-        Config::load_from(*NEST_PATH_CONFIG)
+        let mut config = match env::var_os(NEST_ENV_CONFIG) {
+            Some(path) => Config::load_from(path)?,
+            None => Config::load_layered(*NEST_PATH_CONFIG, *NEST_PATH_CONFIG_DIR)?,
+        };
+
+        config.override_from_env()?;
+        Ok(config)
+    }
+
+    /// Returns the paths of the files [`Config::load()`] loads the configuration from, in the order they are merged:
+    /// the file given by `NEST_CONFIG` if it is set, or else the configuration file followed by its fragments.
+    pub fn files() -> Result<Vec<PathBuf>, ConfigError> {
+        match env::var_os(NEST_ENV_CONFIG) {
+            Some(path) => Ok(vec![PathBuf::from(path)]),
+            None => {
+                let mut files = vec![NEST_PATH_CONFIG.to_path_buf()];
+
+                files.extend(Config::list_fragments(*NEST_PATH_CONFIG_DIR)?);
+                Ok(files)
+            }
+        }
+    }
+
+    /// Returns the directory holding the fragments [`Config::load()`] merges over the configuration file, or
+    /// [`None`] if `NEST_CONFIG` is set, in which case no fragment is loaded
+    pub fn fragments_dir() -> Option<&'static Path> {
+        if env::var_os(NEST_ENV_CONFIG).is_some() {
+            None
+        } else {
+            Some(*NEST_PATH_CONFIG_DIR)
+        }
+    }
+
+    /// Overrides the values of this configuration with those given by the environment, if any:
+    ///
+    /// - `NEST_CACHE_DIR`: the directory holding all caches (see [`ConfigPaths::with_cache_dir()`])
+    /// - `NEST_INSTALL_DIR`: the root path where packages are installed (see [`ConfigPaths::with_install_root()`])
+    /// - `NEST_PROXY`: the proxy used for both HTTP and HTTPS requests
+    /// - `NEST_ARCH`: the native architecture (see [`Config::architecture()`])
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::Config;
+    ///
+    /// let mut config = Config::load_from("./config.toml")?;
+    /// config.override_from_env()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn override_from_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(cache_dir) = env::var_os(NEST_ENV_CACHE_DIR) {
+            self.paths = self.paths.with_cache_dir(cache_dir);
+        }
+
+        if let Some(install_dir) = env::var_os(NEST_ENV_INSTALL_DIR) {
+            self.paths = self.paths.with_install_root(install_dir);
+        }
+
+        if let Some(proxy) = env::var_os(NEST_ENV_PROXY) {
+            let proxy = proxy
+                .into_string()
+                .map_err(|_| format_err!("{}", NEST_ENV_PROXY))
+                .and_then(|proxy| {
+                    Url::parse(&proxy).map_err(|_| format_err!("{}: {}", NEST_ENV_PROXY, proxy))
+                })
+                .context(ConfigErrorKind::EnvOverrideError)?;
+
+            *self.network.http_proxy_mut() = Some(Serde(proxy.clone()));
+            *self.network.https_proxy_mut() = Some(Serde(proxy));
+        }
+
+        if let Some(arch) = env::var_os(NEST_ENV_ARCH) {
+            self.architecture = arch
+                .to_str()
+                .and_then(|arch| Architecture::parse(arch).ok())
+                .ok_or_else(|| format_err!("{}: {}", NEST_ENV_ARCH, arch.to_string_lossy()))
+                .context(ConfigErrorKind::EnvOverrideError)?;
+        }
+        Ok(())
     }
 
     /// Loads the configuration file located at the given path
@@ -101,24 +292,113 @@ impl Config {
     /// ```
     #[inline]
     pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
-        let path = path.as_ref();
-        let mut file = File::open(path)
-            .context(path.display().to_string())
-            .context(ConfigErrorKind::ConfigLoadError)?;
+        Config::load_files(&[path.as_ref().to_path_buf()])
+    }
 
-        // Allocate a string long enough to hold the entire file
-        let mut s = file
-            .metadata()
-            .map(|m| String::with_capacity(m.len() as usize))
-            .unwrap_or_default();
+    /// Loads the configuration file located at the given path, and merges over it all the
+    /// `*.toml` fragments found in the given directory, in lexical order.
+    ///
+    /// Tables are merged recursively, while any other value found in a fragment replaces the one
+    /// previously defined. A missing fragments directory is not an error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::Config;
+    ///
+    /// let config = Config::load_layered("./config.toml", "./config.d/")?;
+    /// # Ok(()) }
+    /// ```
+    pub fn load_layered<P: AsRef<Path>, Q: AsRef<Path>>(
+        path: P,
+        fragments_dir: Q,
+    ) -> Result<Config, ConfigError> {
+        let mut paths = vec![path.as_ref().to_path_buf()];
+
+        paths.extend(Config::list_fragments(fragments_dir.as_ref())?);
+        Config::load_files(&paths)
+    }
+
+    /// Loads the given configuration files, merged in order.
+    ///
+    /// Files written for an older layout of the configuration are migrated, without being modified (see
+    /// [`Config::outdated_files()`]). Unknown keys are recorded to be reported (see [`Config::unknown_keys()`]), unless `deny_unknown_keys` is set,
+    /// in which case they are refused.
+    fn load_files(paths: &[PathBuf]) -> Result<Config, ConfigError> {
+        let mut files = paths
+            .iter()
+            .map(|path| TomlFile::read(path))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        file.read_to_string(&mut s)
-            .context(path.display().to_string())
-            .context(ConfigErrorKind::ConfigLoadError)?;
+        let mut outdated_files = Vec::new();
+        for file in &mut files {
+            let path = file.path().to_path_buf();
 
-        Ok(toml::from_str(&s)
-            .context(path.display().to_string())
-            .context(ConfigErrorKind::ConfigParseError)?)
+            if !migration::migrate(&path, file.value_mut())?.is_empty() {
+                outdated_files.push(path);
+            }
+        }
+
+        let mut value = Value::Table(toml::value::Table::new());
+        for file in &files {
+            merge_toml(&mut value, file.value().clone());
+        }
+
+        let mut config: Config = match value.try_into() {
+            Ok(config) => config,
+            Err(e) => {
+                // The files are interpreted one by one to find where the invalid value is
+                return Err(match files.iter().find_map(TomlFile::find_error) {
+                    Some(located) => located,
+                    None => e
+                        .context(files[0].path().display().to_string())
+                        .context(ConfigErrorKind::ConfigParseError)
+                        .into(),
+                });
+            }
+        };
+
+        config.outdated_files = outdated_files;
+        config.unknown_keys = find_unknown_keys(&config, &files);
+        if config.deny_unknown_keys && !config.unknown_keys.is_empty() {
+            let keys: Vec<_> = config
+                .unknown_keys
+                .iter()
+                .map(UnknownKey::to_string)
+                .collect();
+
+            return Err(failure::err_msg(keys.join("\n"))
+                .context(ConfigErrorKind::UnknownKeys)
+                .into());
+        }
+        Ok(config)
+    }
+
+    /// Returns the sorted list of the `*.toml` files contained in the given directory
+    fn list_fragments(dir: &Path) -> Result<Vec<PathBuf>, ConfigError> {
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut fragments = Vec::new();
+        for entry in fs::read_dir(dir)
+            .context(dir.display().to_string())
+            .context(ConfigErrorKind::ConfigLoadError)?
+        {
+            let path = entry
+                .context(dir.display().to_string())
+                .context(ConfigErrorKind::ConfigLoadError)?
+                .path();
+
+            if path.is_file() && path.extension().map_or(false, |ext| ext == "toml") {
+                fragments.push(path);
+            }
+        }
+        fragments.sort();
+        Ok(fragments)
     }
 
     /// Returns a reference to an intermediate structure holding all important paths that are used by `libnest`.
@@ -133,6 +413,337 @@ impl Config {
         &mut self.paths
     }
 
+    /// Returns a reference to an intermediate structure holding the network settings, like proxies.
+    #[inline]
+    pub fn network(&self) -> &NetworkConfig {
+        &self.network
+    }
+
+    /// Returns a mutable reference to an intermediate structure holding the network settings, like proxies.
+    #[inline]
+    pub fn network_mut(&mut self) -> &mut NetworkConfig {
+        &mut self.network
+    }
+
+    /// Returns a reference to an intermediate structure holding the settings of the sandbox of `instructions.sh` scripts.
+    #[inline]
+    pub fn sandbox(&self) -> &SandboxConfig {
+        &self.sandbox
+    }
+
+    /// Returns a mutable reference to an intermediate structure holding the settings of the sandbox of `instructions.sh` scripts.
+    #[inline]
+    pub fn sandbox_mut(&mut self) -> &mut SandboxConfig {
+        &mut self.sandbox
+    }
+
+    /// Returns a reference to an intermediate structure holding the settings of the confirmations asked before modifying the system.
+    #[inline]
+    pub fn confirmation(&self) -> &ConfirmationConfig {
+        &self.confirmation
+    }
+
+    /// Returns a mutable reference to an intermediate structure holding the settings of the confirmations asked before modifying the system.
+    #[inline]
+    pub fn confirmation_mut(&mut self) -> &mut ConfirmationConfig {
+        &mut self.confirmation
+    }
+
+    /// Returns a reference to an intermediate structure holding the license policy of the system.
+    #[inline]
+    pub fn licenses(&self) -> &LicenseConfig {
+        &self.licenses
+    }
+
+    /// Returns a mutable reference to an intermediate structure holding the license policy of the system.
+    #[inline]
+    pub fn licenses_mut(&mut self) -> &mut LicenseConfig {
+        &mut self.licenses
+    }
+
+    /// Returns a reference to an intermediate structure holding the settings of the log file.
+    #[inline]
+    pub fn log(&self) -> &LogConfig {
+        &self.log
+    }
+
+    /// Returns a mutable reference to an intermediate structure holding the settings of the log file.
+    #[inline]
+    pub fn log_mut(&mut self) -> &mut LogConfig {
+        &mut self.log
+    }
+
+    /// Returns a reference to an intermediate structure holding the settings of the downloads with torrents.
+    #[inline]
+    pub fn torrent(&self) -> &TorrentConfig {
+        &self.torrent
+    }
+
+    /// Returns a mutable reference to an intermediate structure holding the settings of the downloads with torrents.
+    #[inline]
+    pub fn torrent_mut(&mut self) -> &mut TorrentConfig {
+        &mut self.torrent
+    }
+
+    /// Returns a reference to an intermediate structure holding the settings of the snapshots taken before the
+    /// transactions.
+    #[inline]
+    pub fn snapshot(&self) -> &SnapshotConfig {
+        &self.snapshot
+    }
+
+    /// Returns a mutable reference to an intermediate structure holding the settings of the snapshots taken before
+    /// the transactions.
+    #[inline]
+    pub fn snapshot_mut(&mut self) -> &mut SnapshotConfig {
+        &mut self.snapshot
+    }
+
+    /// Returns a reference to an intermediate structure holding the settings of the built-in hooks executed after
+    /// the transactions changing the kernels.
+    #[inline]
+    pub fn triggers(&self) -> &TriggersConfig {
+        &self.triggers
+    }
+
+    /// Returns a mutable reference to an intermediate structure holding the settings of the built-in hooks executed
+    /// after the transactions changing the kernels.
+    #[inline]
+    pub fn triggers_mut(&mut self) -> &mut TriggersConfig {
+        &mut self.triggers
+    }
+
+    /// Returns a reference to an intermediate structure holding the limits of the cache of downloaded packages.
+    #[inline]
+    pub fn download_cache(&self) -> &DownloadCacheConfig {
+        &self.download_cache
+    }
+
+    /// Returns a mutable reference to an intermediate structure holding the limits of the cache of downloaded packages.
+    #[inline]
+    pub fn download_cache_mut(&mut self) -> &mut DownloadCacheConfig {
+        &mut self.download_cache
+    }
+
+    /// Returns a hashmap mapping virtual packages with the packages providing them, in order of preference.
+    #[inline]
+    pub fn providers(&self) -> &HashMap<PackageShortName, Vec<PackageShortName>> {
+        &self.providers
+    }
+
+    /// Returns a mutable reference to a hashmap mapping virtual packages with the packages providing them, in order
+    /// of preference.
+    #[inline]
+    pub fn providers_mut(&mut self) -> &mut HashMap<PackageShortName, Vec<PackageShortName>> {
+        &mut self.providers
+    }
+
+    /// Returns a hashmap mapping packages with the repository they are pinned to, which is preferred over the
+    /// other repositories offering them, whatever their priority.
+    #[inline]
+    pub fn pins(&self) -> &HashMap<PackageShortName, RepositoryName> {
+        &self.pins
+    }
+
+    /// Returns a mutable reference to a hashmap mapping packages with the repository they are pinned to
+    #[inline]
+    pub fn pins_mut(&mut self) -> &mut HashMap<PackageShortName, RepositoryName> {
+        &mut self.pins
+    }
+
+    /// Returns the preferences between the repositories offering the same package, according to their priority
+    /// and to the pins
+    #[inline]
+    pub fn repository_preferences(&self) -> RepositoryPreferences {
+        RepositoryPreferences::from(&self.repositories, &self.pins)
+    }
+
+    /// Returns the native architecture, that of the packages installed in the root folder.
+    ///
+    /// It defaults to the architecture of the host.
+    #[inline]
+    pub fn architecture(&self) -> &Architecture {
+        &self.architecture
+    }
+
+    /// Returns a mutable reference to the native architecture
+    #[inline]
+    pub fn architecture_mut(&mut self) -> &mut Architecture {
+        &mut self.architecture
+    }
+
+    /// Returns the foreign architectures whose packages can be installed too, in `/usr/<architecture>`.
+    #[inline]
+    pub fn foreign_architectures(&self) -> &[Architecture] {
+        &self.foreign_architectures
+    }
+
+    /// Returns a mutable reference to the foreign architectures whose packages can be installed too
+    #[inline]
+    pub fn foreign_architectures_mut(&mut self) -> &mut Vec<Architecture> {
+        &mut self.foreign_architectures
+    }
+
+    /// Returns the number of threads a package is extracted with.
+    ///
+    /// It defaults to the number of CPUs. With a single job, packages are extracted by the current thread only.
+    #[inline]
+    pub fn jobs(&self) -> usize {
+        self.jobs.unwrap_or_else(num_cpus::get).max(1)
+    }
+
+    /// Returns a mutable reference to the number of threads a package is extracted with
+    #[inline]
+    pub fn jobs_mut(&mut self) -> &mut Option<usize> {
+        &mut self.jobs
+    }
+
+    /// Returns the version of the layout of the configuration, [`CONFIG_VERSION`] once the files are migrated
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns the configuration files that were written for an older layout of the configuration, and were migrated
+    /// when they were loaded.
+    ///
+    /// They aren't modified until they are migrated explicitly (see [`ConfigFile::migrate()`]).
+    #[inline]
+    pub fn outdated_files(&self) -> &[PathBuf] {
+        &self.outdated_files
+    }
+
+    /// Returns whether the configuration files are refused when they hold keys that don't match any setting,
+    /// instead of having them reported by [`Config::unknown_keys()`]
+    #[inline]
+    pub fn deny_unknown_keys(&self) -> bool {
+        self.deny_unknown_keys
+    }
+
+    /// Returns a mutable reference to whether the configuration files are refused when they hold unknown keys
+    #[inline]
+    pub fn deny_unknown_keys_mut(&mut self) -> &mut bool {
+        &mut self.deny_unknown_keys
+    }
+
+    /// Returns the keys of the configuration files that don't match any setting, and were thus ignored.
+    ///
+    /// They are usually typos, and should be reported to the user.
+    #[inline]
+    pub fn unknown_keys(&self) -> &[UnknownKey] {
+        &self.unknown_keys
+    }
+
+    /// Returns the architectures of the packages that can be installed, and where they are installed
+    #[inline]
+    pub fn architecture_preferences(&self) -> ArchitecturePreferences {
+        ArchitecturePreferences::from(&self.architecture, &self.foreign_architectures)
+    }
+
+    /// Returns whether the `instructions.sh` scripts of the packages built for the given architecture are run when
+    /// they are installed or removed.
+    ///
+    /// They aren't for the packages installed for a foreign architecture, nor for those installed in a sysroot,
+    /// as their binaries can't be executed.
+    #[inline]
+    pub fn runs_instructions(&self, architecture: &Architecture) -> bool {
+        self.sysroot.is_none() && !self.architecture_preferences().is_foreign(architecture)
+    }
+
+    /// Returns the target triple of the sysroot this configuration operates on, if any (see [`Config::for_sysroot()`])
+    #[inline]
+    pub fn sysroot(&self) -> Option<&TargetTriple> {
+        self.sysroot.as_ref()
+    }
+
+    /// Returns a copy of this configuration operating on the sysroot of the given target triple instead of the
+    /// system.
+    ///
+    /// The sysroot lives in its own folder of [`ConfigPaths::sysroots()`], with its own installed packages and lock
+    /// file (see [`ConfigPaths::with_sysroot()`]), and only holds the packages built for the architecture of the
+    /// target triple, or for any architecture. The repositories, their caches and their keys are shared with the system.
+    pub fn for_sysroot(&self, target: &TargetTriple) -> Config {
+        let mut config = self.clone();
+
+        config.paths = self
+            .paths
+            .with_sysroot(self.paths.sysroots().join(target.as_ref()));
+        config.architecture = target.architecture();
+        config.foreign_architectures = Vec::new();
+        config.sysroot = Some(target.clone());
+        config
+    }
+
+    /// Returns the target triples of the existing sysroots, sorted
+    pub fn sysroots(&self) -> Result<Vec<TargetTriple>, Error> {
+        let dir = self.paths.sysroots();
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut targets = Vec::new();
+        for entry in fs::read_dir(dir).with_context(|_| dir.display().to_string())? {
+            let entry = entry.with_context(|_| dir.display().to_string())?;
+
+            if entry.path().is_dir() {
+                if let Some(target) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| TargetTriple::parse(name).ok())
+                {
+                    targets.push(target);
+                }
+            }
+        }
+        targets.sort();
+        Ok(targets)
+    }
+
+    /// Returns how the `instructions.sh` scripts of the packages of the given repository must be isolated from the host
+    pub fn instructions_sandboxing(&self, repository: &str) -> Sandboxing {
+        match self.repositories.get(repository) {
+            Some(repository) if repository.unsandboxed_instructions() => Sandboxing::Disabled,
+            _ if self.sandbox.required() => Sandboxing::Required,
+            _ => Sandboxing::BestEffort,
+        }
+    }
+
+    /// Returns how much the packages of the given repository are trusted when they are extracted
+    pub fn repository_trust(&self, repository: &str) -> RepositoryTrust {
+        self.repositories
+            .get(repository)
+            .map_or_else(RepositoryTrust::default, RepositoryConfig::trust)
+    }
+
+    /// Returns whether the configuration is in dry-run mode.
+    ///
+    /// In dry-run mode, transactions are planned but not performed: the system is left untouched.
+    /// This setting is never read from the configuration file.
+    #[inline]
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Returns a mutable reference to whether the configuration is in dry-run mode.
+    #[inline]
+    pub fn dry_run_mut(&mut self) -> &mut bool {
+        &mut self.dry_run
+    }
+
+    /// Returns how to behave when the lock file is held by another process.
+    ///
+    /// By default, acquiring the lock file fails immediately. This setting is never read from the configuration file.
+    #[inline]
+    pub fn lock_wait(&self) -> LockWait {
+        self.lock_wait
+    }
+
+    /// Returns a mutable reference to how to behave when the lock file is held by another process
+    #[inline]
+    pub fn lock_wait_mut(&mut self) -> &mut LockWait {
+        &mut self.lock_wait
+    }
+
     /// Returns a hashmap of mapping a [`RepositoryConfig`] with the name of the repository.
     #[inline]
     pub fn repositories_config(&self) -> &HashMap<String, RepositoryConfig> {
@@ -145,20 +756,68 @@ impl Config {
         &mut self.repositories
     }
 
-    /// Returns a vector containing a description of each [`Repository`]
+    /// Returns a vector containing a description of each [`Repository`], disabled ones excepted
     #[inline]
     pub fn repositories(&self) -> Vec<Repository> {
         self.repositories
             .iter()
+            .filter(|(_, config)| !config.disabled())
             .map(|(name, config)| Repository::from(name, config))
             .collect()
     }
 
+    /// Returns the last known health of the mirrors, or an error if it could not be loaded
+    #[inline]
+    pub fn mirrors_health(&self) -> Result<MirrorsHealth, Error> {
+        MirrorsHealth::load_from_cache(self.paths.mirrors())
+    }
+
+    /// Returns the keys imported for each repository, or an error if they could not be loaded
+    #[inline]
+    pub fn keystore(&self) -> Result<Keystore, Error> {
+        Keystore::load_from_cache(self.paths.keys())
+    }
+
+    /// Returns the packages held at their installed version, or an error if they could not be loaded
+    #[inline]
+    pub fn held_packages(&self) -> Result<HeldPackages, Error> {
+        HeldPackages::load_from_cache(self.paths.held())
+    }
+
+    /// Returns the alternatives, the links shared by several packages, or an error if they could not be loaded
+    #[inline]
+    pub fn alternatives(&self) -> Result<Alternatives, Error> {
+        Alternatives::load_from_cache(self.paths.alternatives())
+    }
+
+    /// Returns the world set, the packages and groups explicitly installed by the user.
+    ///
+    /// If it wasn't saved yet, it is made of the requirements of the root of the given dependency graph.
+    pub fn world(&self, graph: &DependencyGraph) -> Result<World, Error> {
+        Ok(World::load_from_cache(self.paths.world())?.unwrap_or_else(|| World::from_graph(graph)))
+    }
+
+    /// Returns the mirrors of the given repository, sorted so that the fastest reachable mirror
+    /// comes first according to the last known health of the mirrors.
+    ///
+    /// If the health of the mirrors could not be loaded, the order of the configuration is kept.
+    pub fn ranked_mirrors(&self, repository: &RepositoryConfig) -> Vec<MirrorUrl> {
+        match self.mirrors_health() {
+            Ok(health) => health.rank(repository.mirrors()),
+            Err(_) => repository.mirrors().clone(),
+        }
+    }
+
     pub(crate) fn available_packages_cache_internal<'a, 'b>(
         &'b self,
         phantom: PhantomData<&'a LockFileOwnership>,
     ) -> AvailablePackages<'b, 'a> {
-        AvailablePackages::from(self.paths().available(), phantom)
+        AvailablePackages::from(
+            self.paths().available(),
+            self.repository_preferences(),
+            self.architecture_preferences(),
+            phantom,
+        )
     }
 
     /// Returns a handle over the cache containing available packages
@@ -220,11 +879,31 @@ impl Config {
         self.installed_packages_cache_internal(phantom)
     }
 
+    /// Returns a handle over the content-addressed store of the files installed by the packages
+    pub fn content_store<'a, 'b>(&'b self, _: &'a LockFileOwnership) -> ContentStore<'b, 'a> {
+        let phantom: PhantomData<&'a LockFileOwnership> = PhantomData;
+
+        ContentStore::from(self.paths().store(), phantom)
+    }
+
+    /// Returns a handle over the history of the performed transactions
+    pub fn history<'a, 'b>(&'b self, _: &'a LockFileOwnership) -> History<'b, 'a> {
+        let phantom: PhantomData<&'a LockFileOwnership> = PhantomData;
+
+        History::from(self.paths().history(), phantom)
+    }
+
     pub(crate) fn downloaded_packages_cache_internal<'a, 'b>(
         &'b self,
         phantom: PhantomData<&'a LockFileOwnership>,
     ) -> DownloadedPackages<'b, 'a> {
-        DownloadedPackages::from(self.paths().downloaded(), phantom)
+        DownloadedPackages::from(
+            self.paths().downloaded(),
+            &self.repositories,
+            self.keystore().unwrap_or_default(),
+            self.available_packages_cache_internal(phantom),
+            phantom,
+        )
     }
 
     /// Returns a handle over the cache containing downloaded packages
@@ -237,14 +916,61 @@ impl Config {
         self.downloaded_packages_cache_internal(phantom)
     }
 
-    /// Acquire the ownership over Nest's lock file
+    /// Acquire the exclusive ownership over Nest's lock file, to modify the system.
+    ///
+    /// If the lock file is held by another process, it is waited for as described by [`Config::lock_wait()`],
+    /// unless `should_wait` is false, in which case an error is returned immediately.
     pub fn acquire_lock_file_ownership(
         &self,
         should_wait: bool,
     ) -> Result<LockFileOwnership, Error> {
+        self.acquire_lock_file(LockMode::Exclusive, should_wait)
+    }
+
+    /// Acquire a shared ownership over Nest's lock file, to read the system.
+    ///
+    /// Any number of processes can share the lock file, but not while another one holds it exclusively.
+    /// It is then waited for like with [`Config::acquire_lock_file_ownership()`].
+    pub fn acquire_shared_lock_file_ownership(
+        &self,
+        should_wait: bool,
+    ) -> Result<LockFileOwnership, Error> {
+        self.acquire_lock_file(LockMode::Shared, should_wait)
+    }
+
+    fn acquire_lock_file(
+        &self,
+        mode: LockMode,
+        should_wait: bool,
+    ) -> Result<LockFileOwnership, Error> {
+        let wait = if should_wait {
+            self.lock_wait
+        } else {
+            LockWait::Never
+        };
+
         Ok(
-            LockFileOwnership::acquire(self.paths.lock_file(), should_wait)
+            LockFileOwnership::acquire(self.paths.lock_file(), mode, wait)
                 .with_context(|_| format_err!("unable to acquire lock file"))?,
         )
     }
 }
+
+/// Merges the `overlay` TOML value over the `base` one.
+///
+/// Tables are merged recursively, any other value in `overlay` replaces the one in `base`.
+fn merge_toml(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base), Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_toml(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
@@ -0,0 +1,300 @@
+use serde_derive::{Deserialize, Serialize};
+use url::Url;
+use url_serde::SerdeUrl;
+
+use super::cache::deserialize_size;
+
+/// Represents the URL of a proxy server
+pub type ProxyUrl = SerdeUrl;
+
+/// Structure holding all the network-related configuration: proxies, hosts that should be reached directly, etc.
+///
+/// Repositories can override these settings with their own (see [`NetworkConfig::overridden_by()`]).
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(default)]
+pub struct NetworkConfig {
+    http_proxy: Option<ProxyUrl>,
+    https_proxy: Option<ProxyUrl>,
+    socks5_proxy: Option<ProxyUrl>,
+    no_proxy: Vec<String>,
+    max_parallel_downloads: Option<usize>,
+    #[serde(deserialize_with = "deserialize_size")]
+    max_download_rate: Option<u64>,
+    connect_timeout: Option<u64>,
+    stall_timeout: Option<u64>,
+    segment_connections: Option<usize>,
+    max_mirror_transfers: Option<usize>,
+}
+
+impl NetworkConfig {
+    /// Creates a new [`NetworkConfig`] with no proxy configured.
+    #[inline]
+    pub fn new() -> NetworkConfig {
+        NetworkConfig::default()
+    }
+
+    /// Returns a reference over the proxy used for HTTP requests, if any
+    #[inline]
+    pub fn http_proxy(&self) -> &Option<ProxyUrl> {
+        &self.http_proxy
+    }
+
+    /// Returns a mutable reference over the proxy used for HTTP requests
+    #[inline]
+    pub fn http_proxy_mut(&mut self) -> &mut Option<ProxyUrl> {
+        &mut self.http_proxy
+    }
+
+    /// Returns a reference over the proxy used for HTTPS requests, if any
+    #[inline]
+    pub fn https_proxy(&self) -> &Option<ProxyUrl> {
+        &self.https_proxy
+    }
+
+    /// Returns a mutable reference over the proxy used for HTTPS requests
+    #[inline]
+    pub fn https_proxy_mut(&mut self) -> &mut Option<ProxyUrl> {
+        &mut self.https_proxy
+    }
+
+    /// Returns a reference over the SOCKS5 proxy, used when no protocol-specific proxy is set
+    #[inline]
+    pub fn socks5_proxy(&self) -> &Option<ProxyUrl> {
+        &self.socks5_proxy
+    }
+
+    /// Returns a mutable reference over the SOCKS5 proxy, used when no protocol-specific proxy is set
+    #[inline]
+    pub fn socks5_proxy_mut(&mut self) -> &mut Option<ProxyUrl> {
+        &mut self.socks5_proxy
+    }
+
+    /// Returns a reference over the list of hosts that should be reached without going through a proxy
+    #[inline]
+    pub fn no_proxy(&self) -> &Vec<String> {
+        &self.no_proxy
+    }
+
+    /// Returns a mutable reference over the list of hosts that should be reached without going through a proxy
+    #[inline]
+    pub fn no_proxy_mut(&mut self) -> &mut Vec<String> {
+        &mut self.no_proxy
+    }
+
+    /// Returns the maximum number of packages that can be downloaded at the same time, if it is limited.
+    ///
+    /// When [`None`], frontends are free to pick a sensible default.
+    #[inline]
+    pub fn max_parallel_downloads(&self) -> Option<usize> {
+        self.max_parallel_downloads
+    }
+
+    /// Returns a mutable reference over the maximum number of packages that can be downloaded at the same time
+    #[inline]
+    pub fn max_parallel_downloads_mut(&mut self) -> &mut Option<usize> {
+        &mut self.max_parallel_downloads
+    }
+
+    /// Returns the maximum rate, in bytes per second, at which packages and repositories are downloaded, shared
+    /// by all the downloads running at the same time, or [`None`] if it isn't limited.
+    ///
+    /// A rate of `0` lifts the limit, so that a repository can ignore the one of the global settings.
+    #[inline]
+    pub fn max_download_rate(&self) -> Option<u64> {
+        self.max_download_rate.filter(|rate| *rate > 0)
+    }
+
+    /// Returns a mutable reference over the maximum rate, in bytes per second, at which packages and repositories
+    /// are downloaded
+    #[inline]
+    pub fn max_download_rate_mut(&mut self) -> &mut Option<u64> {
+        &mut self.max_download_rate
+    }
+
+    /// Returns the number of seconds after which connecting to a mirror is given up, or [`None`] to use the default
+    /// of the transport.
+    ///
+    /// A timeout of `0` is the same as no timeout.
+    #[inline]
+    pub fn connect_timeout(&self) -> Option<u64> {
+        self.connect_timeout.filter(|timeout| *timeout > 0)
+    }
+
+    /// Returns a mutable reference over the number of seconds after which connecting to a mirror is given up
+    #[inline]
+    pub fn connect_timeout_mut(&mut self) -> &mut Option<u64> {
+        &mut self.connect_timeout
+    }
+
+    /// Returns the number of seconds after which a download receiving no data is given up, or [`None`] if stalled
+    /// downloads are waited for.
+    ///
+    /// A timeout of `0` is the same as no timeout.
+    #[inline]
+    pub fn stall_timeout(&self) -> Option<u64> {
+        self.stall_timeout.filter(|timeout| *timeout > 0)
+    }
+
+    /// Returns a mutable reference over the number of seconds after which a download receiving no data is given up
+    #[inline]
+    pub fn stall_timeout_mut(&mut self) -> &mut Option<u64> {
+        &mut self.stall_timeout
+    }
+
+    /// Returns the number of connections used to download a package in segments, spread over the mirrors of its
+    /// repository, when the repository advertises the checksums of its segments.
+    ///
+    /// When [`None`], frontends are free to pick a sensible default, while `1` disables segmented downloads.
+    #[inline]
+    pub fn segment_connections(&self) -> Option<usize> {
+        self.segment_connections
+    }
+
+    /// Returns a mutable reference over the number of connections used to download a package in segments
+    #[inline]
+    pub fn segment_connections_mut(&mut self) -> &mut Option<usize> {
+        &mut self.segment_connections
+    }
+
+    /// Returns the maximum number of transfers running at the same time from a single mirror, or [`None`] if it
+    /// isn't limited.
+    ///
+    /// The transfers from a mirror supporting HTTP/2 share a single connection. A limit of `0` is the same as no
+    /// limit.
+    #[inline]
+    pub fn max_mirror_transfers(&self) -> Option<usize> {
+        self.max_mirror_transfers.filter(|limit| *limit > 0)
+    }
+
+    /// Returns a mutable reference over the maximum number of transfers running at the same time from a single
+    /// mirror
+    #[inline]
+    pub fn max_mirror_transfers_mut(&mut self) -> &mut Option<usize> {
+        &mut self.max_mirror_transfers
+    }
+
+    /// Returns these settings with the ones of the given overrides, usually those of a repository, applied over
+    /// them.
+    ///
+    /// Each setting given by the overrides replaces the one of these settings, the others being kept. A list of
+    /// hosts reached without proxy replaces the whole list, and `no_proxy = ["*"]` reaches every host directly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate toml;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::NetworkConfig;
+    ///
+    /// let network: NetworkConfig = toml::from_str(r#"
+    ///     https_proxy = "http://proxy.local:3128"
+    ///     max_download_rate = "1M"
+    ///     max_parallel_downloads = 2
+    /// "#)?;
+    /// let lan_mirror: NetworkConfig = toml::from_str(r#"
+    ///     max_download_rate = 0
+    ///     max_parallel_downloads = 16
+    /// "#)?;
+    ///
+    /// let network = network.overridden_by(&lan_mirror);
+    /// assert!(network.https_proxy().is_some());
+    /// assert_eq!(network.max_download_rate(), None);
+    /// assert_eq!(network.max_parallel_downloads(), Some(16));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn overridden_by(&self, overrides: &NetworkConfig) -> NetworkConfig {
+        NetworkConfig {
+            http_proxy: overrides
+                .http_proxy
+                .clone()
+                .or_else(|| self.http_proxy.clone()),
+            https_proxy: overrides
+                .https_proxy
+                .clone()
+                .or_else(|| self.https_proxy.clone()),
+            socks5_proxy: overrides
+                .socks5_proxy
+                .clone()
+                .or_else(|| self.socks5_proxy.clone()),
+            no_proxy: if overrides.no_proxy.is_empty() {
+                self.no_proxy.clone()
+            } else {
+                overrides.no_proxy.clone()
+            },
+            max_parallel_downloads: overrides
+                .max_parallel_downloads
+                .or(self.max_parallel_downloads),
+            max_download_rate: overrides.max_download_rate.or(self.max_download_rate),
+            connect_timeout: overrides.connect_timeout.or(self.connect_timeout),
+            stall_timeout: overrides.stall_timeout.or(self.stall_timeout),
+            segment_connections: overrides.segment_connections.or(self.segment_connections),
+            max_mirror_transfers: overrides.max_mirror_transfers.or(self.max_mirror_transfers),
+        }
+    }
+
+    /// Returns true if at least one proxy is configured
+    #[inline]
+    pub fn has_proxy(&self) -> bool {
+        self.http_proxy.is_some() || self.https_proxy.is_some() || self.socks5_proxy.is_some()
+    }
+
+    /// Tests whether the given host is part of the no-proxy list.
+    ///
+    /// An entry matches the host itself and all its subdomains, and `*` matches every host.
+    pub fn bypasses_proxy(&self, host: &str) -> bool {
+        self.no_proxy.iter().any(|entry| {
+            let entry = entry.trim_start_matches('.');
+
+            entry == "*"
+                || host == entry
+                || (host.ends_with(entry) && host[..host.len() - entry.len()].ends_with('.'))
+        })
+    }
+
+    /// Returns the proxy that should be used to reach the given URL, or [`None`] if it should be reached directly.
+    ///
+    /// The protocol-specific proxy is preferred, and the SOCKS5 proxy is used as a fallback.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate toml;
+    /// # extern crate url;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::NetworkConfig;
+    /// use url::Url;
+    ///
+    /// let network: NetworkConfig = toml::from_str(r#"
+    ///     https_proxy = "http://proxy.local:3128"
+    ///     no_proxy = ["raven-os.org"]
+    /// "#)?;
+    ///
+    /// let url = Url::parse("https://example.org/api/pull")?;
+    /// assert_eq!(network.proxy_for(&url).unwrap().as_str(), "http://proxy.local:3128/");
+    ///
+    /// let url = Url::parse("https://stable.raven-os.org/api/pull")?;
+    /// assert!(network.proxy_for(&url).is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy_for(&self, url: &Url) -> Option<&ProxyUrl> {
+        if url
+            .host_str()
+            .map_or(false, |host| self.bypasses_proxy(host))
+        {
+            return None;
+        }
+
+        let proxy = match url.scheme() {
+            "http" => &self.http_proxy,
+            "https" => &self.https_proxy,
+            _ => &None,
+        };
+        proxy.as_ref().or_else(|| self.socks5_proxy.as_ref())
+    }
+}
@@ -8,9 +8,24 @@ lazy_static! {
     static ref NEST_PATH_CACHE: &'static Path = Path::new("/var/nest/available/");
     static ref NEST_PATH_DOWNLOADED: &'static Path = Path::new("/var/nest/downloaded/");
     static ref NEST_PATH_INSTALLED: &'static Path = Path::new("/var/nest/installed/");
+    static ref NEST_PATH_STORE: &'static Path = Path::new("/var/nest/store/");
+    static ref NEST_PATH_STAGING: &'static Path = Path::new("/var/nest/staging/");
+    static ref NEST_PATH_SCRATCH: &'static Path = Path::new("/var/run/nest/");
+    static ref NEST_PATH_LOCAL_REPOSITORY: &'static Path = Path::new("/var/nest/local/");
     static ref NEST_PATH_DEPGRAPH: &'static Path = Path::new("/var/nest/depgraph");
     static ref NEST_PATH_SCRATCH_DEPGRAPH: &'static Path = Path::new("/var/nest/scratch_depgraph");
+    static ref NEST_PATH_MIRRORS: &'static Path = Path::new("/var/nest/mirrors");
+    static ref NEST_PATH_KEYS: &'static Path = Path::new("/var/nest/keys");
+    static ref NEST_PATH_JOURNAL: &'static Path = Path::new("/var/nest/journal");
+    static ref NEST_PATH_HISTORY: &'static Path = Path::new("/var/nest/history/");
+    static ref NEST_PATH_PENDING: &'static Path = Path::new("/var/nest/pending/");
+    static ref NEST_PATH_HELD: &'static Path = Path::new("/var/nest/held");
+    static ref NEST_PATH_WORLD: &'static Path = Path::new("/var/nest/world");
+    static ref NEST_PATH_ALTERNATIVES: &'static Path = Path::new("/var/nest/alternatives");
+    static ref NEST_PATH_SYSROOTS: &'static Path = Path::new("/var/nest/sysroots/");
+    static ref NEST_PATH_HOOKS: &'static Path = Path::new("/etc/nest/hooks.d/");
     static ref NEST_PATH_LOCKFILE: &'static Path = Path::new("/var/lock/nest.lock");
+    static ref NEST_PATH_LOG_FILE: &'static Path = Path::new("/var/log/nest.log");
 }
 
 /// A structure holding all important paths for libnest. It's a sub member of [`Config`][1].
@@ -23,9 +38,24 @@ pub struct ConfigPaths {
     available: PathBuf,
     downloaded: PathBuf,
     installed: PathBuf,
+    store: PathBuf,
+    staging: PathBuf,
+    scratch: PathBuf,
+    local_repository: PathBuf,
     depgraph: PathBuf,
     scratch_depgraph: PathBuf,
-    lockfile_path: PathBuf,
+    mirrors: PathBuf,
+    keys: PathBuf,
+    journal: PathBuf,
+    history: PathBuf,
+    pending: PathBuf,
+    held: PathBuf,
+    world: PathBuf,
+    alternatives: PathBuf,
+    sysroots: PathBuf,
+    hooks: PathBuf,
+    lock_file: PathBuf,
+    log_file: PathBuf,
 }
 
 impl ConfigPaths {
@@ -36,9 +66,24 @@ impl ConfigPaths {
             available: PathBuf::from(*NEST_PATH_CACHE),
             downloaded: PathBuf::from(*NEST_PATH_DOWNLOADED),
             installed: PathBuf::from(*NEST_PATH_INSTALLED),
+            store: PathBuf::from(*NEST_PATH_STORE),
+            staging: PathBuf::from(*NEST_PATH_STAGING),
+            scratch: PathBuf::from(*NEST_PATH_SCRATCH),
+            local_repository: PathBuf::from(*NEST_PATH_LOCAL_REPOSITORY),
             depgraph: PathBuf::from(*NEST_PATH_DEPGRAPH),
             scratch_depgraph: PathBuf::from(*NEST_PATH_SCRATCH_DEPGRAPH),
-            lockfile_path: PathBuf::from(*NEST_PATH_LOCKFILE),
+            mirrors: PathBuf::from(*NEST_PATH_MIRRORS),
+            keys: PathBuf::from(*NEST_PATH_KEYS),
+            journal: PathBuf::from(*NEST_PATH_JOURNAL),
+            history: PathBuf::from(*NEST_PATH_HISTORY),
+            pending: PathBuf::from(*NEST_PATH_PENDING),
+            held: PathBuf::from(*NEST_PATH_HELD),
+            world: PathBuf::from(*NEST_PATH_WORLD),
+            alternatives: PathBuf::from(*NEST_PATH_ALTERNATIVES),
+            sysroots: PathBuf::from(*NEST_PATH_SYSROOTS),
+            hooks: PathBuf::from(*NEST_PATH_HOOKS),
+            lock_file: PathBuf::from(*NEST_PATH_LOCKFILE),
+            log_file: PathBuf::from(*NEST_PATH_LOG_FILE),
         }
     }
 
@@ -59,8 +104,13 @@ impl ConfigPaths {
     /// assert_eq!(paths.available(), Path::new("/chroot/var/nest/available"));
     /// assert_eq!(paths.downloaded(), Path::new("/chroot/var/nest/downloaded"));
     /// assert_eq!(paths.installed(), Path::new("/chroot/var/nest/installed"));
+    /// assert_eq!(paths.scratch(), Path::new("/chroot/var/run/nest"));
+    /// assert_eq!(paths.local_repository(), Path::new("/chroot/var/nest/local"));
     /// assert_eq!(paths.depgraph(), Path::new("/chroot/var/nest/depgraph"));
+    /// assert_eq!(paths.sysroots(), Path::new("/chroot/var/nest/sysroots"));
+    /// assert_eq!(paths.hooks(), Path::new("/chroot/etc/nest/hooks.d"));
     /// assert_eq!(paths.lock_file(), Path::new("/chroot/var/lock/nest.lock"));
+    /// assert_eq!(paths.log_file(), Path::new("/chroot/var/log/nest.log"));
     /// # Ok(())
     /// # }
     /// ```
@@ -73,9 +123,230 @@ impl ConfigPaths {
             available: self.available.with_root(root.as_ref()),
             downloaded: self.downloaded.with_root(root.as_ref()),
             installed: self.installed.with_root(root.as_ref()),
+            store: self.store.with_root(root.as_ref()),
+            staging: self.staging.with_root(root.as_ref()),
+            scratch: self.scratch.with_root(root.as_ref()),
+            local_repository: self.local_repository.with_root(root.as_ref()),
             depgraph: self.depgraph.with_root(root.as_ref()),
             scratch_depgraph: self.scratch_depgraph.with_root(root.as_ref()),
-            lockfile_path: self.lockfile_path.with_root(root.as_ref()),
+            mirrors: self.mirrors.with_root(root.as_ref()),
+            keys: self.keys.with_root(root.as_ref()),
+            journal: self.journal.with_root(root.as_ref()),
+            history: self.history.with_root(root.as_ref()),
+            pending: self.pending.with_root(root.as_ref()),
+            held: self.held.with_root(root.as_ref()),
+            world: self.world.with_root(root.as_ref()),
+            alternatives: self.alternatives.with_root(root.as_ref()),
+            sysroots: self.sysroots.with_root(root.as_ref()),
+            hooks: self.hooks.with_root(root.as_ref()),
+            lock_file: self.lock_file.with_root(root.as_ref()),
+            log_file: self.log_file.with_root(root.as_ref()),
+        }
+    }
+
+    /// Changes the paths of all caches (available, downloaded and installed packages, dependency graphs, mirrors),
+    /// of the store of the installed files, of the staging folder, of the keystore, of the transaction journal, history and pending transactions, of the held packages, of the world set, of the
+    /// alternatives and of the sysroots to make them live in the given directory, keeping their file name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::ConfigPaths;
+    /// use std::path::Path;
+    ///
+    /// let paths = ConfigPaths::default().with_cache_dir("/tmp/nest/");
+    /// assert_eq!(paths.root(), Path::new("/"));
+    /// assert_eq!(paths.available(), Path::new("/tmp/nest/available"));
+    /// assert_eq!(paths.downloaded(), Path::new("/tmp/nest/downloaded"));
+    /// assert_eq!(paths.installed(), Path::new("/tmp/nest/installed"));
+    /// assert_eq!(paths.store(), Path::new("/tmp/nest/store"));
+    /// assert_eq!(paths.staging(), Path::new("/tmp/nest/staging"));
+    /// assert_eq!(paths.depgraph(), Path::new("/tmp/nest/depgraph"));
+    /// assert_eq!(paths.scratch_depgraph(), Path::new("/tmp/nest/scratch_depgraph"));
+    /// assert_eq!(paths.mirrors(), Path::new("/tmp/nest/mirrors"));
+    /// assert_eq!(paths.keys(), Path::new("/tmp/nest/keys"));
+    /// assert_eq!(paths.journal(), Path::new("/tmp/nest/journal"));
+    /// assert_eq!(paths.history(), Path::new("/tmp/nest/history"));
+    /// assert_eq!(paths.pending(), Path::new("/tmp/nest/pending"));
+    /// assert_eq!(paths.held(), Path::new("/tmp/nest/held"));
+    /// assert_eq!(paths.world(), Path::new("/tmp/nest/world"));
+    /// assert_eq!(paths.alternatives(), Path::new("/tmp/nest/alternatives"));
+    /// assert_eq!(paths.sysroots(), Path::new("/tmp/nest/sysroots"));
+    /// assert_eq!(paths.scratch(), Path::new("/var/run/nest"));
+    /// assert_eq!(paths.local_repository(), Path::new("/var/nest/local"));
+    /// assert_eq!(paths.lock_file(), Path::new("/var/lock/nest.lock"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn with_cache_dir<P: AsRef<Path>>(&self, cache_dir: P) -> ConfigPaths {
+        let cache_dir = cache_dir.as_ref();
+        let relocate = |path: &Path| match path.file_name() {
+            Some(file_name) => cache_dir.join(file_name),
+            None => cache_dir.to_path_buf(),
+        };
+
+        ConfigPaths {
+            root: self.root.clone(),
+            available: relocate(&self.available),
+            downloaded: relocate(&self.downloaded),
+            installed: relocate(&self.installed),
+            store: relocate(&self.store),
+            staging: relocate(&self.staging),
+            scratch: self.scratch.clone(),
+            local_repository: self.local_repository.clone(),
+            depgraph: relocate(&self.depgraph),
+            scratch_depgraph: relocate(&self.scratch_depgraph),
+            mirrors: relocate(&self.mirrors),
+            keys: relocate(&self.keys),
+            journal: relocate(&self.journal),
+            history: relocate(&self.history),
+            pending: relocate(&self.pending),
+            held: relocate(&self.held),
+            world: relocate(&self.world),
+            alternatives: relocate(&self.alternatives),
+            sysroots: relocate(&self.sysroots),
+            hooks: self.hooks.clone(),
+            lock_file: self.lock_file.clone(),
+            log_file: self.log_file.clone(),
+        }
+    }
+
+    /// Changes the root path where packages are installed, along with the paths of everything
+    /// describing the installed system (installed packages, store of the installed files, staging folder,
+    /// dependency graphs, transaction journal, history and pending transactions, held packages, world set,
+    /// alternatives), which are moved inside the new root.
+    ///
+    /// Unlike [`ConfigPaths::chroot()`], the caches of available and downloaded packages, the health of
+    /// the mirrors, the keystore, the sysroots, the scratch folder, the local repository, the lock file and the log file
+    /// are kept on the host. This is used to bootstrap a new system from a host.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::ConfigPaths;
+    /// use std::path::Path;
+    ///
+    /// let paths = ConfigPaths::default().with_install_root("/mnt");
+    /// assert_eq!(paths.root(), Path::new("/mnt"));
+    /// assert_eq!(paths.installed(), Path::new("/mnt/var/nest/installed"));
+    /// assert_eq!(paths.store(), Path::new("/mnt/var/nest/store"));
+    /// assert_eq!(paths.staging(), Path::new("/mnt/var/nest/staging"));
+    /// assert_eq!(paths.depgraph(), Path::new("/mnt/var/nest/depgraph"));
+    /// assert_eq!(paths.journal(), Path::new("/mnt/var/nest/journal"));
+    /// assert_eq!(paths.history(), Path::new("/mnt/var/nest/history"));
+    /// assert_eq!(paths.pending(), Path::new("/mnt/var/nest/pending"));
+    /// assert_eq!(paths.held(), Path::new("/mnt/var/nest/held"));
+    /// assert_eq!(paths.world(), Path::new("/mnt/var/nest/world"));
+    /// assert_eq!(paths.alternatives(), Path::new("/mnt/var/nest/alternatives"));
+    /// assert_eq!(paths.available(), Path::new("/var/nest/available"));
+    /// assert_eq!(paths.downloaded(), Path::new("/var/nest/downloaded"));
+    /// assert_eq!(paths.scratch(), Path::new("/var/run/nest"));
+    /// assert_eq!(paths.local_repository(), Path::new("/var/nest/local"));
+    /// assert_eq!(paths.hooks(), Path::new("/etc/nest/hooks.d"));
+    /// assert_eq!(paths.lock_file(), Path::new("/var/lock/nest.lock"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn with_install_root<P: AsRef<Path>>(&self, root: P) -> ConfigPaths {
+        use crate::chroot::Chroot;
+
+        ConfigPaths {
+            root: root.as_ref().to_path_buf(),
+            available: self.available.clone(),
+            downloaded: self.downloaded.clone(),
+            installed: self.installed.with_root(root.as_ref()),
+            store: self.store.with_root(root.as_ref()),
+            staging: self.staging.with_root(root.as_ref()),
+            scratch: self.scratch.clone(),
+            local_repository: self.local_repository.clone(),
+            depgraph: self.depgraph.with_root(root.as_ref()),
+            scratch_depgraph: self.scratch_depgraph.with_root(root.as_ref()),
+            mirrors: self.mirrors.clone(),
+            keys: self.keys.clone(),
+            journal: self.journal.with_root(root.as_ref()),
+            history: self.history.with_root(root.as_ref()),
+            pending: self.pending.with_root(root.as_ref()),
+            held: self.held.with_root(root.as_ref()),
+            world: self.world.with_root(root.as_ref()),
+            alternatives: self.alternatives.with_root(root.as_ref()),
+            sysroots: self.sysroots.clone(),
+            hooks: self.hooks.clone(),
+            lock_file: self.lock_file.clone(),
+            log_file: self.log_file.clone(),
+        }
+    }
+
+    /// Changes the root path where packages are installed to the given sysroot, along with the paths of everything
+    /// describing the installed system, the hooks and the lock file, which are moved inside the sysroot, at the same
+    /// place relative to it as relative to the current root path.
+    ///
+    /// Like with [`ConfigPaths::with_install_root()`], the caches of available and downloaded packages, the health
+    /// of the mirrors, the keystore, the scratch folder, the local repository and the log file are shared with the host, but the sysroot has its own lock file,
+    /// so that it can be modified while the host is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::ConfigPaths;
+    /// use std::path::Path;
+    ///
+    /// let paths = ConfigPaths::default().with_sysroot("/var/nest/sysroots/aarch64-linux-gnu");
+    /// assert_eq!(paths.root(), Path::new("/var/nest/sysroots/aarch64-linux-gnu"));
+    /// assert_eq!(paths.installed(), Path::new("/var/nest/sysroots/aarch64-linux-gnu/var/nest/installed"));
+    /// assert_eq!(paths.store(), Path::new("/var/nest/sysroots/aarch64-linux-gnu/var/nest/store"));
+    /// assert_eq!(paths.staging(), Path::new("/var/nest/sysroots/aarch64-linux-gnu/var/nest/staging"));
+    /// assert_eq!(paths.hooks(), Path::new("/var/nest/sysroots/aarch64-linux-gnu/etc/nest/hooks.d"));
+    /// assert_eq!(paths.lock_file(), Path::new("/var/nest/sysroots/aarch64-linux-gnu/var/lock/nest.lock"));
+    /// assert_eq!(paths.available(), Path::new("/var/nest/available"));
+    /// assert_eq!(paths.downloaded(), Path::new("/var/nest/downloaded"));
+    ///
+    /// let paths = ConfigPaths::default().with_install_root("/mnt").with_sysroot("/mnt/sysroot");
+    /// assert_eq!(paths.installed(), Path::new("/mnt/sysroot/var/nest/installed"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_sysroot<P: AsRef<Path>>(&self, root: P) -> ConfigPaths {
+        use crate::chroot::Chroot;
+
+        // Paths that live inside the current root are moved at the same place inside the sysroot
+        let root = root.as_ref();
+        let reroot = |path: &Path| {
+            path.strip_prefix(&self.root)
+                .unwrap_or(path)
+                .with_root(root)
+        };
+
+        ConfigPaths {
+            root: root.to_path_buf(),
+            available: self.available.clone(),
+            downloaded: self.downloaded.clone(),
+            installed: reroot(&self.installed),
+            store: reroot(&self.store),
+            staging: reroot(&self.staging),
+            scratch: self.scratch.clone(),
+            local_repository: self.local_repository.clone(),
+            depgraph: reroot(&self.depgraph),
+            scratch_depgraph: reroot(&self.scratch_depgraph),
+            mirrors: self.mirrors.clone(),
+            keys: self.keys.clone(),
+            journal: reroot(&self.journal),
+            history: reroot(&self.history),
+            pending: reroot(&self.pending),
+            held: reroot(&self.held),
+            world: reroot(&self.world),
+            alternatives: reroot(&self.alternatives),
+            sysroots: self.sysroots.clone(),
+            hooks: reroot(&self.hooks),
+            lock_file: reroot(&self.lock_file),
+            log_file: self.log_file.clone(),
         }
     }
 
@@ -249,6 +520,188 @@ impl ConfigPaths {
         &mut self.installed
     }
 
+    /// Returns a reference to the path where the files installed by the packages are stored, once per content.
+    ///
+    /// It must be on the same filesystem as the root path, for the files to be hardlinked from it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.store(), Path::new("/var/nest/store"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn store(&self) -> &Path {
+        &self.store
+    }
+
+    /// Returns a mutable reference to the path where the files installed by the packages are stored, once per
+    /// content.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.store_mut() = PathBuf::from("/tmp/store");
+    /// assert_eq!(paths.store(), Path::new("/tmp/store"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn store_mut(&mut self) -> &mut PathBuf {
+        &mut self.store
+    }
+
+    /// Returns a reference to the path of the folder where the files of a package are extracted, before they are
+    /// moved in place.
+    ///
+    /// It must be on the same filesystem as the root path, for the files to be moved atomically.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.staging(), Path::new("/var/nest/staging"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn staging(&self) -> &Path {
+        &self.staging
+    }
+
+    /// Returns a mutable reference to the path of the folder where the files of a package are extracted, before
+    /// they are moved in place.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.staging_mut() = PathBuf::from("/tmp/staging");
+    /// assert_eq!(paths.staging(), Path::new("/tmp/staging"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn staging_mut(&mut self) -> &mut PathBuf {
+        &mut self.staging
+    }
+
+    /// Returns a reference to the path of the folder where NPFs are unpacked to be explored.
+    ///
+    /// It is preferably a tmpfs: when it lacks room for a package, a disk-backed folder is used instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.scratch(), Path::new("/var/run/nest"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn scratch(&self) -> &Path {
+        &self.scratch
+    }
+
+    /// Returns a mutable reference to the path of the folder where NPFs are unpacked to be explored.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.scratch_mut() = PathBuf::from("/tmp/scratch");
+    /// assert_eq!(paths.scratch(), Path::new("/tmp/scratch"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn scratch_mut(&mut self) -> &mut PathBuf {
+        &mut self.scratch
+    }
+
+    /// Returns a reference to the path of the folder of the local repository, whose NPFs are added to the caches as
+    /// packages of the `local` repository, to be installed like the packages of the other repositories.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.local_repository(), Path::new("/var/nest/local"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn local_repository(&self) -> &Path {
+        &self.local_repository
+    }
+
+    /// Returns a mutable reference to the path of the folder of the local repository.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.local_repository_mut() = PathBuf::from("/home/user/packages");
+    /// assert_eq!(paths.local_repository(), Path::new("/home/user/packages"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn local_repository_mut(&mut self) -> &mut PathBuf {
+        &mut self.local_repository
+    }
+
     /// Returns a reference to the file's path where the dependency graph is stored
     ///
     /// # Examples
@@ -335,7 +788,7 @@ impl ConfigPaths {
         &mut self.scratch_depgraph
     }
 
-    /// Returns a reference to the file's path where the lock file is stored
+    /// Returns a reference to the file's path where the health of the mirrors is stored
     ///
     /// # Examples
     ///
@@ -347,16 +800,16 @@ impl ConfigPaths {
     /// use libnest::config::ConfigPaths;
     ///
     /// let paths = ConfigPaths::default();
-    /// assert_eq!(paths.lock_file(), Path::new("/var/lock/nest.lock"));
+    /// assert_eq!(paths.mirrors(), Path::new("/var/nest/mirrors"));
     /// # Ok(())
     /// # }
     /// ```
     #[inline]
-    pub fn lock_file(&self) -> &Path {
-        &self.lockfile_path
+    pub fn mirrors(&self) -> &Path {
+        &self.mirrors
     }
 
-    /// Returns a mutable reference to the file's path where the lock file is stored
+    /// Returns a mutable reference to the file's path where the health of the mirrors is stored
     ///
     /// # Examples
     ///
@@ -368,14 +821,491 @@ impl ConfigPaths {
     /// use libnest::config::ConfigPaths;
     ///
     /// let mut paths = ConfigPaths::default();
-    /// *paths.lock_file_mut() = PathBuf::from("/tmp/nest.lock");
-    /// assert_eq!(paths.lock_file(), Path::new("/tmp/nest.lock"));
+    /// *paths.mirrors_mut() = PathBuf::from("/tmp/mirrors");
+    /// assert_eq!(paths.mirrors(), Path::new("/tmp/mirrors"));
     /// # Ok(())
     /// # }
     /// ```
     #[inline]
-    pub fn lock_file_mut(&mut self) -> &mut PathBuf {
-        &mut self.lockfile_path
+    pub fn mirrors_mut(&mut self) -> &mut PathBuf {
+        &mut self.mirrors
+    }
+
+    /// Returns a reference to the file's path where the keys imported for each repository are stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.keys(), Path::new("/var/nest/keys"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn keys(&self) -> &Path {
+        &self.keys
+    }
+
+    /// Returns a mutable reference to the file's path where the keys imported for each repository are stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.keys_mut() = PathBuf::from("/tmp/keys");
+    /// assert_eq!(paths.keys(), Path::new("/tmp/keys"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn keys_mut(&mut self) -> &mut PathBuf {
+        &mut self.keys
+    }
+
+    /// Returns a reference to the file's path where the journal of the running transaction is stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.journal(), Path::new("/var/nest/journal"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn journal(&self) -> &Path {
+        &self.journal
+    }
+
+    /// Returns a mutable reference to the file's path where the journal of the running transaction is stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.journal_mut() = PathBuf::from("/tmp/journal");
+    /// assert_eq!(paths.journal(), Path::new("/tmp/journal"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn journal_mut(&mut self) -> &mut PathBuf {
+        &mut self.journal
+    }
+
+    /// Returns a reference to the folder's path where the history of the performed transactions is stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.history(), Path::new("/var/nest/history"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn history(&self) -> &Path {
+        &self.history
+    }
+
+    /// Returns a mutable reference to the folder's path where the history of the performed transactions is stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.history_mut() = PathBuf::from("/tmp/history");
+    /// assert_eq!(paths.history(), Path::new("/tmp/history"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn history_mut(&mut self) -> &mut PathBuf {
+        &mut self.history
+    }
+
+    /// Returns a reference to the folder's path where the transactions being performed are recorded, so that
+    /// they can be resumed if they are interrupted
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.pending(), Path::new("/var/nest/pending"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn pending(&self) -> &Path {
+        &self.pending
+    }
+
+    /// Returns a mutable reference to the folder's path where the transactions being performed are recorded, so
+    /// that they can be resumed if they are interrupted
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.pending_mut() = PathBuf::from("/tmp/pending");
+    /// assert_eq!(paths.pending(), Path::new("/tmp/pending"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn pending_mut(&mut self) -> &mut PathBuf {
+        &mut self.pending
+    }
+
+    /// Returns a reference to the file's path where the held packages are stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.held(), Path::new("/var/nest/held"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn held(&self) -> &Path {
+        &self.held
+    }
+
+    /// Returns a mutable reference to the file's path where the held packages are stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.held_mut() = PathBuf::from("/tmp/held");
+    /// assert_eq!(paths.held(), Path::new("/tmp/held"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn held_mut(&mut self) -> &mut PathBuf {
+        &mut self.held
+    }
+
+    /// Returns a reference to the file's path where the world set, the packages and groups explicitly
+    /// installed by the user, is stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.world(), Path::new("/var/nest/world"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn world(&self) -> &Path {
+        &self.world
+    }
+
+    /// Returns a mutable reference to the file's path where the world set is stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.world_mut() = PathBuf::from("/tmp/world");
+    /// assert_eq!(paths.world(), Path::new("/tmp/world"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn world_mut(&mut self) -> &mut PathBuf {
+        &mut self.world
+    }
+
+    /// Returns a reference to the file's path where the alternatives, the links shared by several packages and
+    /// the targets they point to, are stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.alternatives(), Path::new("/var/nest/alternatives"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn alternatives(&self) -> &Path {
+        &self.alternatives
+    }
+
+    /// Returns a mutable reference to the file's path where the alternatives are stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.alternatives_mut() = PathBuf::from("/tmp/alternatives");
+    /// assert_eq!(paths.alternatives(), Path::new("/tmp/alternatives"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn alternatives_mut(&mut self) -> &mut PathBuf {
+        &mut self.alternatives
+    }
+
+    /// Returns a reference to the path of the directory holding the sysroots, one per target triple
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.sysroots(), Path::new("/var/nest/sysroots"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn sysroots(&self) -> &Path {
+        &self.sysroots
+    }
+
+    /// Returns a mutable reference to the path of the directory holding the sysroots
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.sysroots_mut() = PathBuf::from("/tmp/sysroots");
+    /// assert_eq!(paths.sysroots(), Path::new("/tmp/sysroots"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn sysroots_mut(&mut self) -> &mut PathBuf {
+        &mut self.sysroots
+    }
+
+    /// Returns a reference to the folder's path where the transaction hooks are declared
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.hooks(), Path::new("/etc/nest/hooks.d"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn hooks(&self) -> &Path {
+        &self.hooks
+    }
+
+    /// Returns a mutable reference to the folder's path where the transaction hooks are declared
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.hooks_mut() = PathBuf::from("/tmp/hooks.d");
+    /// assert_eq!(paths.hooks(), Path::new("/tmp/hooks.d"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn hooks_mut(&mut self) -> &mut PathBuf {
+        &mut self.hooks
+    }
+
+    /// Returns a reference to the file's path where the lock file is stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.lock_file(), Path::new("/var/lock/nest.lock"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn lock_file(&self) -> &Path {
+        &self.lock_file
+    }
+
+    /// Returns a mutable reference to the file's path where the lock file is stored
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.lock_file_mut() = PathBuf::from("/tmp/nest.lock");
+    /// assert_eq!(paths.lock_file(), Path::new("/tmp/nest.lock"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn lock_file_mut(&mut self) -> &mut PathBuf {
+        &mut self.lock_file
+    }
+
+    /// Returns a reference to the path of the file where the operations of Nest are logged
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::Path;
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let paths = ConfigPaths::default();
+    /// assert_eq!(paths.log_file(), Path::new("/var/log/nest.log"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn log_file(&self) -> &Path {
+        &self.log_file
+    }
+
+    /// Returns a mutable reference to the path of the file where the operations of Nest are logged
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::path::{Path, PathBuf};
+    /// use libnest::config::ConfigPaths;
+    ///
+    /// let mut paths = ConfigPaths::default();
+    /// *paths.log_file_mut() = PathBuf::from("/tmp/nest.log");
+    /// assert_eq!(paths.log_file(), Path::new("/tmp/nest.log"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn log_file_mut(&mut self) -> &mut PathBuf {
+        &mut self.log_file
     }
 }
 
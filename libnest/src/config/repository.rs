@@ -1,13 +1,173 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use failure::ResultExt;
+use serde::{Deserialize, Deserializer};
 use serde_derive::{Deserialize, Serialize};
-use url_serde::SerdeUrl;
+use url::{ParseError, Url};
+use url_serde::{Serde, SerdeUrl};
+
+use crate::package::{PackageShortName, RepositoryName, TrustedKey};
+
+use super::errors::{ConfigError, ConfigErrorKind};
+use super::network::NetworkConfig;
+use super::tls::TlsConfig;
 
 /// Represents the URL pointing to a repository mirror
 pub type MirrorUrl = SerdeUrl;
 
+/// Parses the URL of a repository mirror.
+///
+/// Besides URLs, absolute paths to local directories are accepted and turned into `file://` URLs.
+/// The path of a `file://` URL always ends with a slash, so that routes are looked for inside the directory.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libnest;
+/// # extern crate failure;
+/// # fn main() -> Result<(), failure::Error> {
+/// use libnest::config::parse_mirror_url;
+///
+/// assert_eq!(parse_mirror_url("/srv/nest")?.as_str(), "file:///srv/nest/");
+/// assert_eq!(parse_mirror_url("file:///srv/nest")?.as_str(), "file:///srv/nest/");
+/// assert_eq!(parse_mirror_url("https://stable.raven-os.org/")?.as_str(), "https://stable.raven-os.org/");
+/// assert!(parse_mirror_url("srv/nest").is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_mirror_url(repr: &str) -> Result<MirrorUrl, ParseError> {
+    let mut url = if repr.starts_with('/') {
+        Url::from_directory_path(repr).map_err(|_| ParseError::RelativeUrlWithoutBase)?
+    } else {
+        Url::parse(repr)?
+    };
+
+    if url.scheme() == "file" && !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(Serde(url))
+}
+
+/// Deserializes the mirrors of a repository, accepting local paths as well as URLs
+fn deserialize_mirrors<'de, D>(deserializer: D) -> Result<Vec<MirrorUrl>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|repr| parse_mirror_url(repr).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// The credentials sent to the mirrors of a private repository
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+#[serde(untagged)]
+pub enum Credentials {
+    /// HTTP basic authentication, with a username and a password
+    Basic {
+        /// The name of the user
+        username: String,
+
+        /// The password of the user
+        password: String,
+    },
+
+    /// HTTP bearer authentication, with a token
+    Bearer {
+        /// The token sent in the `Authorization` header
+        token: String,
+    },
+}
+
+impl Credentials {
+    /// Loads the credentials stored in the given TOML file.
+    ///
+    /// As the file holds secrets, it must belong to root and must not be accessible by any other user.
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        let metadata = fs::metadata(path)
+            .context(path.display().to_string())
+            .context(ConfigErrorKind::CredentialsLoadError)?;
+
+        if metadata.uid() != 0 || metadata.permissions().mode() & 0o077 != 0 {
+            return Err(failure::err_msg(path.display().to_string())
+                .context(ConfigErrorKind::InsecureCredentialsFile)
+                .into());
+        }
+
+        let content = fs::read_to_string(path)
+            .context(path.display().to_string())
+            .context(ConfigErrorKind::CredentialsLoadError)?;
+        Ok(toml::from_str(&content)
+            .context(path.display().to_string())
+            .context(ConfigErrorKind::CredentialsLoadError)?)
+    }
+}
+
+// Secrets are never printed in debug output
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Credentials::Basic { username, .. } => fmt
+                .debug_struct("Basic")
+                .field("username", username)
+                .finish(),
+            Credentials::Bearer { .. } => fmt.debug_struct("Bearer").finish(),
+        }
+    }
+}
+
+/// How much the packages of a repository are trusted when they are extracted.
+///
+/// Whatever the trust level, entries leaving the installation root, through `..` or through symbolic and hard
+/// links, are always rejected.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RepositoryTrust {
+    /// The packages may contain device nodes, named pipes and setuid or setgid files
+    Trusted,
+
+    /// The packages are rejected if they contain device nodes, named pipes or setuid or setgid files
+    Untrusted,
+}
+
+impl Default for RepositoryTrust {
+    fn default() -> Self {
+        RepositoryTrust::Trusted
+    }
+}
+
 /// Structure holding all the configuration for a single repository: mirrors, proxy, etc...
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
 pub struct RepositoryConfig {
+    #[serde(deserialize_with = "deserialize_mirrors")]
     mirrors: Vec<MirrorUrl>,
+    #[serde(default)]
+    trusted_keys: Vec<TrustedKey>,
+    #[serde(default)]
+    trusted_fingerprints: Vec<String>,
+    #[serde(default)]
+    allow_unsigned: bool,
+    #[serde(default)]
+    unsandboxed_instructions: bool,
+    #[serde(default)]
+    trust: RepositoryTrust,
+    #[serde(default)]
+    credentials: Option<Credentials>,
+    #[serde(default)]
+    credentials_file: Option<PathBuf>,
+    #[serde(default)]
+    tls: TlsConfig,
+    #[serde(default)]
+    network: NetworkConfig,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    disabled: bool,
 }
 
 impl RepositoryConfig {
@@ -16,11 +176,24 @@ impl RepositoryConfig {
     pub fn new() -> RepositoryConfig {
         RepositoryConfig {
             mirrors: Vec::new(),
+            trusted_keys: Vec::new(),
+            trusted_fingerprints: Vec::new(),
+            allow_unsigned: false,
+            unsandboxed_instructions: false,
+            trust: RepositoryTrust::Trusted,
+            credentials: None,
+            credentials_file: None,
+            tls: TlsConfig::new(),
+            network: NetworkConfig::new(),
+            priority: 0,
+            disabled: false,
         }
     }
 
     /// Returns a reference over a vector of [`SerdeUrl`], which are the mirrors of this repository.
     /// They are sorted by order of importance: the first one should be used in priority etc.
+    ///
+    /// Local mirrors are `file://` URLs pointing to a directory (see [`parse_mirror_url()`]).
     #[inline]
     pub fn mirrors(&self) -> &Vec<MirrorUrl> {
         &self.mirrors
@@ -32,4 +205,260 @@ impl RepositoryConfig {
     pub fn mirrors_mut(&mut self) -> &mut Vec<MirrorUrl> {
         &mut self.mirrors
     }
+
+    /// Returns a reference over the keys trusted to sign the packages of this repository.
+    #[inline]
+    pub fn trusted_keys(&self) -> &Vec<TrustedKey> {
+        &self.trusted_keys
+    }
+
+    /// Returns a mutable reference over the keys trusted to sign the packages of this repository.
+    #[inline]
+    pub fn trusted_keys_mut(&mut self) -> &mut Vec<TrustedKey> {
+        &mut self.trusted_keys
+    }
+
+    /// Returns a reference over the fingerprints of the keys that can be imported in the keystore for this repository
+    /// without asking for confirmation (see [`TrustedKey::fingerprint()`]).
+    #[inline]
+    pub fn trusted_fingerprints(&self) -> &Vec<String> {
+        &self.trusted_fingerprints
+    }
+
+    /// Returns a mutable reference over the fingerprints of the keys that can be imported in the keystore for this
+    /// repository without asking for confirmation.
+    #[inline]
+    pub fn trusted_fingerprints_mut(&mut self) -> &mut Vec<String> {
+        &mut self.trusted_fingerprints
+    }
+
+    /// Returns whether the given key is one of the keys whose fingerprint is trusted for this repository
+    #[inline]
+    pub fn has_trusted_fingerprint(&self, key: &TrustedKey) -> bool {
+        let fingerprint = key.fingerprint();
+
+        self.trusted_fingerprints
+            .iter()
+            .any(|trusted| trusted.eq_ignore_ascii_case(&fingerprint))
+    }
+
+    /// Returns whether packages of this repository can be used without checking their signature.
+    ///
+    /// This is meant for local testing only.
+    #[inline]
+    pub fn allow_unsigned(&self) -> bool {
+        self.allow_unsigned
+    }
+
+    /// Returns a mutable reference over whether packages of this repository can be used without checking their signature.
+    #[inline]
+    pub fn allow_unsigned_mut(&mut self) -> &mut bool {
+        &mut self.allow_unsigned
+    }
+
+    /// Returns whether the `instructions.sh` scripts of the packages of this repository are executed
+    /// outside of the sandbox, even if it is required.
+    ///
+    /// This is meant for trusted repositories whose scripts need a full access to the host.
+    #[inline]
+    pub fn unsandboxed_instructions(&self) -> bool {
+        self.unsandboxed_instructions
+    }
+
+    /// Returns a mutable reference over whether the `instructions.sh` scripts of the packages of this repository
+    /// are executed outside of the sandbox.
+    #[inline]
+    pub fn unsandboxed_instructions_mut(&mut self) -> &mut bool {
+        &mut self.unsandboxed_instructions
+    }
+
+    /// Returns how much the packages of this repository are trusted when they are extracted,
+    /// [`RepositoryTrust::Trusted`] by default
+    #[inline]
+    pub fn trust(&self) -> RepositoryTrust {
+        self.trust
+    }
+
+    /// Returns a mutable reference over how much the packages of this repository are trusted when they are extracted
+    #[inline]
+    pub fn trust_mut(&mut self) -> &mut RepositoryTrust {
+        &mut self.trust
+    }
+
+    /// Returns the credentials sent to the mirrors of this repository, written directly in the configuration
+    #[inline]
+    pub fn credentials(&self) -> &Option<Credentials> {
+        &self.credentials
+    }
+
+    /// Returns a mutable reference over the credentials sent to the mirrors of this repository
+    #[inline]
+    pub fn credentials_mut(&mut self) -> &mut Option<Credentials> {
+        &mut self.credentials
+    }
+
+    /// Returns the path of the file holding the credentials sent to the mirrors of this repository, if any
+    #[inline]
+    pub fn credentials_file(&self) -> &Option<PathBuf> {
+        &self.credentials_file
+    }
+
+    /// Returns a mutable reference over the path of the file holding the credentials sent to the mirrors
+    /// of this repository
+    #[inline]
+    pub fn credentials_file_mut(&mut self) -> &mut Option<PathBuf> {
+        &mut self.credentials_file
+    }
+
+    /// Loads the credentials sent to the mirrors of this repository, if any.
+    ///
+    /// The credentials file takes precedence over the credentials written in the configuration, which
+    /// is usually readable by all users.
+    pub fn load_credentials(&self) -> Result<Option<Credentials>, ConfigError> {
+        match &self.credentials_file {
+            Some(path) => Credentials::load_from(path).map(Some),
+            None => Ok(self.credentials.clone()),
+        }
+    }
+
+    /// Returns a reference over the TLS settings used to connect to the mirrors of this repository
+    #[inline]
+    pub fn tls(&self) -> &TlsConfig {
+        &self.tls
+    }
+
+    /// Returns a mutable reference over the TLS settings used to connect to the mirrors of this repository
+    #[inline]
+    pub fn tls_mut(&mut self) -> &mut TlsConfig {
+        &mut self.tls
+    }
+
+    /// Returns the network settings of this repository, overriding the global ones when reaching its mirrors (see
+    /// [`NetworkConfig::overridden_by()`])
+    #[inline]
+    pub fn network(&self) -> &NetworkConfig {
+        &self.network
+    }
+
+    /// Returns a mutable reference over the network settings of this repository, overriding the global ones
+    #[inline]
+    pub fn network_mut(&mut self) -> &mut NetworkConfig {
+        &mut self.network
+    }
+
+    /// Returns the priority of this repository, `0` by default.
+    ///
+    /// When several repositories offer the same package, the one with the highest priority is preferred, even if
+    /// another one offers a more recent version.
+    #[inline]
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Returns a mutable reference over the priority of this repository
+    #[inline]
+    pub fn priority_mut(&mut self) -> &mut i32 {
+        &mut self.priority
+    }
+
+    /// Returns whether this repository is disabled: it isn't pulled anymore, and its packages aren't considered
+    /// when resolving requirements.
+    #[inline]
+    pub fn disabled(&self) -> bool {
+        self.disabled
+    }
+
+    /// Returns a mutable reference over whether this repository is disabled
+    #[inline]
+    pub fn disabled_mut(&mut self) -> &mut bool {
+        &mut self.disabled
+    }
+}
+
+/// The preferences between the repositories offering the same package.
+///
+/// The repository a package is pinned to comes first, then the repositories with the highest priority. Repositories
+/// with the same priority are sorted by name, so that the choice is always the same.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RepositoryPreferences<'a> {
+    repositories: &'a HashMap<String, RepositoryConfig>,
+    pins: &'a HashMap<PackageShortName, RepositoryName>,
+}
+
+impl<'a> RepositoryPreferences<'a> {
+    /// Creates [`RepositoryPreferences`] from the configuration of the repositories and the repositories the
+    /// packages are pinned to
+    #[inline]
+    pub fn from(
+        repositories: &'a HashMap<String, RepositoryConfig>,
+        pins: &'a HashMap<PackageShortName, RepositoryName>,
+    ) -> Self {
+        RepositoryPreferences { repositories, pins }
+    }
+
+    /// Compares two repositories offering the given package: the preferred one is the lesser.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::cmp::Ordering;
+    /// use std::collections::HashMap;
+    /// use libnest::config::{RepositoryConfig, RepositoryPreferences};
+    /// use libnest::package::{PackageShortName, RepositoryName};
+    ///
+    /// let mut stable = RepositoryConfig::new();
+    /// *stable.priority_mut() = 10;
+    ///
+    /// let mut repositories = HashMap::new();
+    /// repositories.insert("stable".to_string(), stable);
+    /// repositories.insert("beta".to_string(), RepositoryConfig::new());
+    ///
+    /// let gcc = PackageShortName::parse("sys-devel/gcc")?;
+    /// let mut pins = HashMap::new();
+    /// pins.insert(gcc.clone(), RepositoryName::parse("beta")?);
+    ///
+    /// let preferences = RepositoryPreferences::from(&repositories, &pins);
+    /// let stable = RepositoryName::parse("stable")?;
+    /// let beta = RepositoryName::parse("beta")?;
+    ///
+    /// let coreutils = PackageShortName::parse("sys-bin/coreutils")?;
+    /// assert_eq!(preferences.compare(&coreutils, &stable, &beta), Ordering::Less);
+    /// assert_eq!(preferences.compare(&gcc, &stable, &beta), Ordering::Greater);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compare(
+        &self,
+        package: &PackageShortName,
+        a: &RepositoryName,
+        b: &RepositoryName,
+    ) -> Ordering {
+        let pinned = self.pins.get(package);
+
+        (pinned != Some(a))
+            .cmp(&(pinned != Some(b)))
+            .then_with(|| self.compare_repositories(a, b))
+    }
+
+    /// Compares two repositories regardless of the pins, by priority then by name: the preferred one is the lesser
+    pub fn compare_repositories(&self, a: &RepositoryName, b: &RepositoryName) -> Ordering {
+        let priority = |repository: &RepositoryName| {
+            self.repositories
+                .get(repository.as_str())
+                .map_or(0, RepositoryConfig::priority)
+        };
+
+        priority(b).cmp(&priority(a)).then_with(|| a.cmp(b))
+    }
+
+    /// Returns whether the packages of the given repository are considered, which they are unless the repository
+    /// is disabled
+    pub fn is_enabled(&self, repository: &RepositoryName) -> bool {
+        self.repositories
+            .get(repository.as_str())
+            .map_or(true, |config| !config.disabled())
+    }
 }
@@ -0,0 +1,46 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// How the `instructions.sh` scripts of packages are isolated from the host
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Sandboxing {
+    /// The scripts are executed without any isolation, other than a chroot in the installation root
+    Disabled,
+
+    /// The scripts are executed in a sandbox if one can be set up, and without it otherwise
+    BestEffort,
+
+    /// The scripts are executed in a sandbox, and fail if it can't be set up
+    Required,
+}
+
+/// Structure holding the settings of the sandbox in which the `instructions.sh` scripts of packages are executed.
+///
+/// The sandbox runs the scripts in their own mount, UTS, IPC and network namespaces, chrooted in the
+/// installation root with a private `/tmp`, with only the capabilities needed to set the owner and the permissions
+/// of files, without the ability to gain new privileges, and with system calls that could alter the host (mounting
+/// filesystems, loading kernel modules, creating device nodes, rebooting...) denied.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(default)]
+pub struct SandboxConfig {
+    required: bool,
+}
+
+impl SandboxConfig {
+    /// Creates a new [`SandboxConfig`], where the sandbox is used only when it can be set up.
+    #[inline]
+    pub fn new() -> SandboxConfig {
+        SandboxConfig::default()
+    }
+
+    /// Returns whether the scripts must fail if the sandbox can't be set up
+    #[inline]
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    /// Returns a mutable reference over whether the scripts must fail if the sandbox can't be set up
+    #[inline]
+    pub fn required_mut(&mut self) -> &mut bool {
+        &mut self.required
+    }
+}
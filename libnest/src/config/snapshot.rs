@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+
+lazy_static! {
+    static ref NEST_PATH_SNAPSHOTS: &'static Path = Path::new("/.snapshots");
+}
+
+/// How the snapshots of the system are taken and restored
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotBackend {
+    /// The root is a btrfs subvolume, snapshotted with the `btrfs` tool
+    Btrfs,
+
+    /// The snapshots are taken and restored by the configured commands
+    Command,
+}
+
+/// Structure holding the settings of the snapshots of the filesystem taken before the transactions.
+///
+/// When a backend is configured, a snapshot of the system is taken before any transaction modifying it, and
+/// recorded in the history, so that the system can be brought back to its state with `nest rollback --snapshot`.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    backend: Option<SnapshotBackend>,
+    directory: Option<PathBuf>,
+    create_command: Option<String>,
+    restore_command: Option<String>,
+}
+
+impl SnapshotConfig {
+    /// Creates a new [`SnapshotConfig`], where no snapshot is taken.
+    #[inline]
+    pub fn new() -> SnapshotConfig {
+        SnapshotConfig::default()
+    }
+
+    /// Returns how the snapshots are taken, or [`None`] if they aren't
+    #[inline]
+    pub fn backend(&self) -> Option<SnapshotBackend> {
+        self.backend
+    }
+
+    /// Returns a mutable reference over how the snapshots are taken
+    #[inline]
+    pub fn backend_mut(&mut self) -> &mut Option<SnapshotBackend> {
+        &mut self.backend
+    }
+
+    /// Returns the folder of the installation root where the btrfs snapshots are created, `/.snapshots` by default.
+    ///
+    /// It must be on the same btrfs filesystem as the root.
+    #[inline]
+    pub fn directory(&self) -> &Path {
+        self.directory
+            .as_ref()
+            .map_or(*NEST_PATH_SNAPSHOTS, PathBuf::as_path)
+    }
+
+    /// Returns a mutable reference over the folder where the btrfs snapshots are created
+    #[inline]
+    pub fn directory_mut(&mut self) -> &mut Option<PathBuf> {
+        &mut self.directory
+    }
+
+    /// Returns the command taking a snapshot with the `command` backend, if any.
+    ///
+    /// Its arguments are separated by whitespaces, and `{name}` and `{root}` are replaced by the name of the
+    /// snapshot and by the installation root.
+    #[inline]
+    pub fn create_command(&self) -> Option<&str> {
+        self.create_command.as_ref().map(String::as_str)
+    }
+
+    /// Returns a mutable reference over the command taking a snapshot with the `command` backend
+    #[inline]
+    pub fn create_command_mut(&mut self) -> &mut Option<String> {
+        &mut self.create_command
+    }
+
+    /// Returns the command restoring a snapshot with the `command` backend, if any.
+    ///
+    /// Its arguments are separated by whitespaces, and `{name}` and `{root}` are replaced by the name of the
+    /// snapshot and by the installation root.
+    #[inline]
+    pub fn restore_command(&self) -> Option<&str> {
+        self.restore_command.as_ref().map(String::as_str)
+    }
+
+    /// Returns a mutable reference over the command restoring a snapshot with the `command` backend
+    #[inline]
+    pub fn restore_command_mut(&mut self) -> &mut Option<String> {
+        &mut self.restore_command
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt::{self, Display, Formatter};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::TargetTripleParseError;
+use crate::package::Architecture;
+
+lazy_static! {
+    static ref REGEX_TARGET_TRIPLE: Regex =
+        Regex::new(r"^(?P<architecture>[a-z0-9_]+)(?:-[a-z0-9_.]+){1,3}$").unwrap();
+}
+
+/// A target triple, like `aarch64-linux-gnu` or `armv7-unknown-linux-gnueabihf`, naming the system a sysroot is
+/// populated for.
+///
+/// Each sysroot is a separate install root, with its own installed packages and lock file, holding the packages
+/// built for the architecture of its target triple. They are used as sysroots for cross-compilation.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct TargetTriple(String);
+
+impl TargetTriple {
+    /// Parses the string representation of a [`TargetTriple`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// use libnest::config::TargetTriple;
+    ///
+    /// assert!(TargetTriple::parse("aarch64-linux-gnu").is_ok());
+    /// assert!(TargetTriple::parse("x86_64-unknown-linux-musl").is_ok());
+    /// assert!(TargetTriple::parse("aarch64").is_err());
+    /// assert!(TargetTriple::parse("../aarch64-linux-gnu").is_err());
+    /// ```
+    pub fn parse(repr: &str) -> Result<Self, TargetTripleParseError> {
+        if REGEX_TARGET_TRIPLE.is_match(repr) {
+            Ok(TargetTriple(repr.to_string()))
+        } else {
+            Err(TargetTripleParseError(repr.to_string()))
+        }
+    }
+
+    /// Returns the architecture of the packages the sysroot of this target triple is made of, its first component
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::config::TargetTriple;
+    /// use libnest::package::Architecture;
+    ///
+    /// let target = TargetTriple::parse("aarch64-linux-gnu")?;
+    /// assert_eq!(target.architecture(), Architecture::parse("aarch64")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn architecture(&self) -> Architecture {
+        let captures = REGEX_TARGET_TRIPLE.captures(&self.0).unwrap();
+
+        // The first component of a target triple is always a valid architecture
+        Architecture::parse(&captures["architecture"]).unwrap()
+    }
+}
+
+impl AsRef<str> for TargetTriple {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for TargetTriple {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Structure holding the TLS settings used to connect to the mirrors of a repository.
+///
+/// A client certificate and its private key can be presented to mirrors requiring mutual TLS authentication,
+/// and the certificates of the mirrors can be checked against a custom bundle of certificate authorities
+/// instead of the system's.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(default)]
+pub struct TlsConfig {
+    client_certificate: Option<PathBuf>,
+    client_key: Option<PathBuf>,
+    ca_bundle: Option<PathBuf>,
+    insecure: bool,
+}
+
+impl TlsConfig {
+    /// Creates a new [`TlsConfig`], using the system's certificate authorities and no client certificate.
+    #[inline]
+    pub fn new() -> TlsConfig {
+        TlsConfig::default()
+    }
+
+    /// Returns the path of the client certificate presented to the mirrors, in PEM format, if any
+    #[inline]
+    pub fn client_certificate(&self) -> &Option<PathBuf> {
+        &self.client_certificate
+    }
+
+    /// Returns a mutable reference over the path of the client certificate presented to the mirrors
+    #[inline]
+    pub fn client_certificate_mut(&mut self) -> &mut Option<PathBuf> {
+        &mut self.client_certificate
+    }
+
+    /// Returns the path of the private key of the client certificate, in PEM format, if any.
+    ///
+    /// If the client certificate is given without a key, the key is expected in the same file.
+    #[inline]
+    pub fn client_key(&self) -> &Option<PathBuf> {
+        &self.client_key
+    }
+
+    /// Returns a mutable reference over the path of the private key of the client certificate
+    #[inline]
+    pub fn client_key_mut(&mut self) -> &mut Option<PathBuf> {
+        &mut self.client_key
+    }
+
+    /// Returns the path of the bundle of certificate authorities the certificates of the mirrors are checked
+    /// against, instead of the system's, if any
+    #[inline]
+    pub fn ca_bundle(&self) -> &Option<PathBuf> {
+        &self.ca_bundle
+    }
+
+    /// Returns a mutable reference over the path of the bundle of certificate authorities the certificates of
+    /// the mirrors are checked against
+    #[inline]
+    pub fn ca_bundle_mut(&mut self) -> &mut Option<PathBuf> {
+        &mut self.ca_bundle
+    }
+
+    /// Returns whether the certificates of the mirrors are accepted without being checked.
+    ///
+    /// This is meant for local testing only: packages and metadata are still verified with the repository's keys,
+    /// but the credentials of the repository, if any, may be sent to anyone.
+    #[inline]
+    pub fn insecure(&self) -> bool {
+        self.insecure
+    }
+
+    /// Returns a mutable reference over whether the certificates of the mirrors are accepted without being checked
+    #[inline]
+    pub fn insecure_mut(&mut self) -> &mut bool {
+        &mut self.insecure
+    }
+}
@@ -0,0 +1,54 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// The command downloading a torrent, unless configured otherwise
+const DEFAULT_TORRENT_COMMAND: &str =
+    "aria2c --quiet=true --seed-time=0 --bt-stop-timeout=300 --dir={dir} {uri}";
+
+/// Structure holding the settings of the downloads of the packages seeded by their repository.
+///
+/// When enabled, the packages whose torrent is indexed by their repository are downloaded with the configured
+/// BitTorrent client, and from the mirrors if that fails.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(default)]
+pub struct TorrentConfig {
+    enabled: bool,
+    command: Option<String>,
+}
+
+impl TorrentConfig {
+    /// Creates a new [`TorrentConfig`], with torrents disabled.
+    #[inline]
+    pub fn new() -> TorrentConfig {
+        TorrentConfig::default()
+    }
+
+    /// Returns whether packages are downloaded with their torrent, when they have one
+    #[inline]
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns a mutable reference over whether packages are downloaded with their torrent
+    #[inline]
+    pub fn enabled_mut(&mut self) -> &mut bool {
+        &mut self.enabled
+    }
+
+    /// Returns the command downloading a torrent.
+    ///
+    /// Its arguments are separated by whitespaces, and `{uri}` and `{dir}` are replaced by the magnet URI of the
+    /// torrent and by the folder to download it in. The command must exit successfully once the single file of
+    /// the torrent is downloaded in that folder.
+    #[inline]
+    pub fn command(&self) -> &str {
+        self.command
+            .as_ref()
+            .map_or(DEFAULT_TORRENT_COMMAND, String::as_str)
+    }
+
+    /// Returns a mutable reference over the command downloading a torrent
+    #[inline]
+    pub fn command_mut(&mut self) -> &mut Option<String> {
+        &mut self.command
+    }
+}
@@ -0,0 +1,97 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// The command updating the dependencies of the modules of a kernel, unless configured otherwise
+const DEFAULT_DEPMOD_COMMAND: &str = "depmod {kernel}";
+
+/// The command generating the initramfs image of a kernel, unless configured otherwise
+const DEFAULT_INITRAMFS_COMMAND: &str = "mkinitcpio -k {kernel} -g /boot/initramfs-{kernel}.img";
+
+/// Structure holding the settings of the built-in hooks executed after the transactions changing the kernels.
+///
+/// When a transaction creates or removes files in the modules folder of a kernel (`/usr/lib/modules/<kernel>/`),
+/// the dependencies of its modules are updated and its initramfs image is generated again. Changes in `/boot`
+/// generate the initramfs image of all the installed kernels again.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(default)]
+pub struct TriggersConfig {
+    depmod: bool,
+    depmod_command: Option<String>,
+    initramfs: bool,
+    initramfs_command: Option<String>,
+}
+
+impl Default for TriggersConfig {
+    fn default() -> Self {
+        TriggersConfig {
+            depmod: true,
+            depmod_command: None,
+            initramfs: true,
+            initramfs_command: None,
+        }
+    }
+}
+
+impl TriggersConfig {
+    /// Creates a new [`TriggersConfig`], with both built-in hooks enabled.
+    #[inline]
+    pub fn new() -> TriggersConfig {
+        TriggersConfig::default()
+    }
+
+    /// Returns whether the dependencies of the modules of the changed kernels are updated
+    #[inline]
+    pub fn depmod(&self) -> bool {
+        self.depmod
+    }
+
+    /// Returns a mutable reference over whether the dependencies of the modules of the changed kernels are updated
+    #[inline]
+    pub fn depmod_mut(&mut self) -> &mut bool {
+        &mut self.depmod
+    }
+
+    /// Returns the shell command updating the dependencies of the modules of a kernel, `depmod {kernel}` by default.
+    ///
+    /// `{kernel}` is replaced by the version of the kernel, the name of its folder in `/usr/lib/modules`.
+    #[inline]
+    pub fn depmod_command(&self) -> &str {
+        self.depmod_command
+            .as_ref()
+            .map_or(DEFAULT_DEPMOD_COMMAND, String::as_str)
+    }
+
+    /// Returns a mutable reference over the shell command updating the dependencies of the modules of a kernel
+    #[inline]
+    pub fn depmod_command_mut(&mut self) -> &mut Option<String> {
+        &mut self.depmod_command
+    }
+
+    /// Returns whether the initramfs images of the changed kernels are generated again
+    #[inline]
+    pub fn initramfs(&self) -> bool {
+        self.initramfs
+    }
+
+    /// Returns a mutable reference over whether the initramfs images of the changed kernels are generated again
+    #[inline]
+    pub fn initramfs_mut(&mut self) -> &mut bool {
+        &mut self.initramfs
+    }
+
+    /// Returns the shell command generating the initramfs image of a kernel,
+    /// `mkinitcpio -k {kernel} -g /boot/initramfs-{kernel}.img` by default.
+    ///
+    /// `{kernel}` is replaced by the version of the kernel, the name of its folder in `/usr/lib/modules`.
+    #[inline]
+    pub fn initramfs_command(&self) -> &str {
+        self.initramfs_command
+            .as_ref()
+            .map_or(DEFAULT_INITRAMFS_COMMAND, String::as_str)
+    }
+
+    /// Returns a mutable reference over the shell command generating the initramfs image of a kernel
+    #[inline]
+    pub fn initramfs_command_mut(&mut self) -> &mut Option<String> {
+        &mut self.initramfs_command
+    }
+}
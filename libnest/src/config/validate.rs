@@ -0,0 +1,301 @@
+//! Checks of the configuration that can't be made when it is parsed, as they depend on the state of the system
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::package::RepositoryName;
+
+use super::Config;
+
+/// The kind of problem found in the configuration
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ConfigIssueKind {
+    /// The name of a repository isn't a valid repository name
+    InvalidRepositoryName,
+
+    /// A repository has no mirror, or one of its mirrors can't be used
+    InvalidMirror,
+
+    /// Several repositories share a mirror, and are thus the same repository configured twice
+    DuplicateRepository,
+
+    /// A path doesn't exist or has the wrong type
+    InvalidPath,
+
+    /// A path can't be written, or created, by the current user
+    ReadOnlyPath,
+
+    /// The credentials of a repository can't be loaded
+    InvalidCredentials,
+
+    /// No key is trusted to sign the packages of a repository
+    MissingKeys,
+}
+
+impl std::fmt::Display for ConfigIssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigIssueKind::InvalidRepositoryName => write!(f, "invalid name"),
+            ConfigIssueKind::InvalidMirror => write!(f, "invalid mirror"),
+            ConfigIssueKind::DuplicateRepository => write!(f, "duplicate repository"),
+            ConfigIssueKind::InvalidPath => write!(f, "invalid path"),
+            ConfigIssueKind::ReadOnlyPath => write!(f, "read-only path"),
+            ConfigIssueKind::InvalidCredentials => write!(f, "invalid credentials"),
+            ConfigIssueKind::MissingKeys => write!(f, "missing keys"),
+        }
+    }
+}
+
+/// A problem found in the configuration
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct ConfigIssue {
+    kind: ConfigIssueKind,
+    subject: String,
+    description: String,
+}
+
+impl ConfigIssue {
+    fn from<S: ToString, D: ToString>(kind: ConfigIssueKind, subject: S, description: D) -> Self {
+        ConfigIssue {
+            kind,
+            subject: subject.to_string(),
+            description: description.to_string(),
+        }
+    }
+
+    /// Returns the kind of problem that was found
+    #[inline]
+    pub fn kind(&self) -> ConfigIssueKind {
+        self.kind
+    }
+
+    /// Returns what the problem is about: the name of a repository, or a path
+    #[inline]
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Returns a description of the problem
+    #[inline]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.subject, self.description)
+    }
+}
+
+/// Returns whether the current user can write the given path, or create it if it doesn't exist
+fn is_writable(path: &Path) -> bool {
+    // A missing path can be created if its closest existing ancestor can be written
+    let existing = match path.ancestors().find(|ancestor| ancestor.exists()) {
+        Some(existing) => existing,
+        None => return false,
+    };
+
+    match CString::new(existing.as_os_str().as_bytes()) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+impl Config {
+    /// Checks the parts of the configuration that depend on the state of the system, returning the problems that
+    /// were found, sorted.
+    ///
+    /// The mirrors of the repositories, the paths used by Nest, the credentials of the repositories and the keys
+    /// trusted to sign their packages are checked. Nothing is modified, and the mirrors aren't contacted.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+
+        self.validate_repositories(&mut issues);
+        self.validate_paths(&mut issues);
+        self.validate_keys(&mut issues);
+
+        issues.sort();
+        issues
+    }
+
+    fn validate_repositories(&self, issues: &mut Vec<ConfigIssue>) {
+        let mut mirror_owners: HashMap<&str, &str> = HashMap::new();
+        let mut repositories: Vec<_> = self.repositories.iter().collect();
+        repositories.sort_by_key(|(name, _)| *name);
+
+        for (name, repository) in repositories {
+            if let Err(e) = RepositoryName::parse(name) {
+                issues.push(ConfigIssue::from(
+                    ConfigIssueKind::InvalidRepositoryName,
+                    name,
+                    e,
+                ));
+            }
+
+            if repository.mirrors().is_empty() {
+                issues.push(ConfigIssue::from(
+                    ConfigIssueKind::InvalidMirror,
+                    name,
+                    "the repository has no mirror",
+                ));
+            }
+
+            for mirror in repository.mirrors() {
+                match mirror.scheme() {
+                    "http" | "https" if mirror.host().is_none() => issues.push(ConfigIssue::from(
+                        ConfigIssueKind::InvalidMirror,
+                        name,
+                        format!("{} has no host", mirror.as_str()),
+                    )),
+                    "http" | "https" => (),
+                    "file" => match mirror.to_file_path() {
+                        Ok(ref path) if path.is_dir() => (),
+                        _ => issues.push(ConfigIssue::from(
+                            ConfigIssueKind::InvalidMirror,
+                            name,
+                            format!("{} isn't an existing directory", mirror.as_str()),
+                        )),
+                    },
+                    scheme => issues.push(ConfigIssue::from(
+                        ConfigIssueKind::InvalidMirror,
+                        name,
+                        format!(
+                            "{} uses the unsupported scheme '{}'",
+                            mirror.as_str(),
+                            scheme
+                        ),
+                    )),
+                }
+
+                if let Some(other) = mirror_owners.insert(mirror.as_str(), name) {
+                    issues.push(ConfigIssue::from(
+                        ConfigIssueKind::DuplicateRepository,
+                        name,
+                        format!(
+                            "the mirror {} is also a mirror of the repository '{}'",
+                            mirror.as_str(),
+                            other
+                        ),
+                    ));
+                }
+            }
+
+            if let (Some(path), Err(e)) =
+                (repository.credentials_file(), repository.load_credentials())
+            {
+                issues.push(ConfigIssue::from(
+                    ConfigIssueKind::InvalidCredentials,
+                    path.display(),
+                    e,
+                ));
+            }
+
+            let tls = repository.tls();
+            for path in [tls.client_certificate(), tls.client_key(), tls.ca_bundle()]
+                .iter()
+                .filter_map(|path| path.as_ref())
+            {
+                if !path.is_file() {
+                    issues.push(ConfigIssue::from(
+                        ConfigIssueKind::InvalidPath,
+                        path.display(),
+                        format!("the TLS file of the repository '{}' doesn't exist", name),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn validate_paths(&self, issues: &mut Vec<ConfigIssue>) {
+        let paths = &self.paths;
+
+        if !paths.root().is_dir() {
+            issues.push(ConfigIssue::from(
+                ConfigIssueKind::InvalidPath,
+                paths.root().display(),
+                "the installation root isn't an existing directory",
+            ));
+        }
+
+        // Folders that are only read, or whose failures are tolerated, are only checked for their type
+        let directories = [
+            (paths.available(), true),
+            (paths.downloaded(), true),
+            (paths.installed(), true),
+            (paths.store(), true),
+            (paths.staging(), true),
+            (paths.history(), true),
+            (paths.pending(), true),
+            (paths.sysroots(), true),
+            (paths.scratch(), false),
+            (paths.local_repository(), false),
+            (paths.hooks(), false),
+        ];
+        let files = [
+            (paths.depgraph(), true),
+            (paths.scratch_depgraph(), true),
+            (paths.mirrors(), true),
+            (paths.keys(), true),
+            (paths.journal(), true),
+            (paths.held(), true),
+            (paths.world(), true),
+            (paths.alternatives(), true),
+            (paths.lock_file(), true),
+            (paths.log_file(), false),
+        ];
+
+        for (path, is_dir, written) in directories
+            .iter()
+            .map(|(path, written)| (path, true, written))
+            .chain(files.iter().map(|(path, written)| (path, false, written)))
+        {
+            if path.exists() && path.is_dir() != is_dir {
+                issues.push(ConfigIssue::from(
+                    ConfigIssueKind::InvalidPath,
+                    path.display(),
+                    if is_dir {
+                        "a directory was expected"
+                    } else {
+                        "a file was expected"
+                    },
+                ));
+            } else if *written && !is_writable(path) {
+                issues.push(ConfigIssue::from(
+                    ConfigIssueKind::ReadOnlyPath,
+                    path.display(),
+                    "the path can't be written by the current user",
+                ));
+            }
+        }
+    }
+
+    fn validate_keys(&self, issues: &mut Vec<ConfigIssue>) {
+        let keystore = match self.keystore() {
+            Ok(keystore) => keystore,
+            Err(e) => {
+                issues.push(ConfigIssue::from(
+                    ConfigIssueKind::InvalidPath,
+                    self.paths.keys().display(),
+                    format!("the keystore can't be loaded: {}", e),
+                ));
+                return;
+            }
+        };
+
+        for (name, repository) in &self.repositories {
+            if !repository.disabled()
+                && !repository.allow_unsigned()
+                && keystore.trusted_keys(name, repository).is_empty()
+            {
+                issues.push(ConfigIssue::from(
+                    ConfigIssueKind::MissingKeys,
+                    name,
+                    "no key is trusted to sign the packages of the repository, it is asked for when pulling",
+                ));
+            }
+        }
+    }
+}
@@ -16,4 +16,5 @@ pub mod config;
 pub mod lock_file;
 pub mod package;
 pub mod repository;
+pub mod solver;
 pub mod transaction;
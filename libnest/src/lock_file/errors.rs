@@ -0,0 +1,23 @@
+//! Errors that can be returned when acquiring the lock file
+
+use failure::{Context, Fail};
+
+/// Error type for errors related to the lock file
+#[derive(Debug)]
+pub struct LockFileError {
+    inner: Context<LockFileErrorKind>,
+}
+
+/// Error kind describing a kind of error related to the lock file
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum LockFileErrorKind {
+    /// The lock file is held by another process
+    #[fail(display = "the lock file is held by another process")]
+    AlreadyHeld,
+
+    /// The lock file was still held by another process once the timeout expired
+    #[fail(display = "timed out waiting for the lock file")]
+    TimedOut,
+}
+
+use_as_error!(LockFileError, LockFileErrorKind);
@@ -1,34 +1,203 @@
 //! Simple file-based locking to prevent race conditions when running multiple instances of Nest
+//!
+//! The lock file is either shared by processes only reading the system, or held exclusively by a single
+//! process modifying it. The process holding it exclusively writes its PID in it, so that the processes
+//! waiting for it can tell which one they are waiting for.
 
-use std::fs::{self, File};
+mod errors;
+
+pub use self::errors::*;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::ops::Drop;
 use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use failure::{Error, ResultExt};
+use failure::{format_err, Error, ResultExt};
 use fs2::FileExt;
 
+/// The interval at which the lock file is polled while waiting for it with a timeout
+const LOCK_FILE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How to behave when the lock file is held by another process
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LockWait {
+    /// Fail immediately
+    Never,
+
+    /// Wait until the lock file is released
+    Forever,
+
+    /// Wait until the lock file is released, or fail once the given duration has elapsed
+    Timeout(Duration),
+}
+
+impl Default for LockWait {
+    fn default() -> Self {
+        LockWait::Never
+    }
+}
+
+/// The way the lock file is held
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LockMode {
+    /// The lock file is held to read the system: any number of processes can hold it this way at the same time
+    Shared,
+
+    /// The lock file is held to modify the system: no other process can hold it at the same time
+    Exclusive,
+}
+
+impl LockMode {
+    fn try_lock(self, lock_file: &File) -> bool {
+        match self {
+            LockMode::Shared => lock_file.try_lock_shared().is_ok(),
+            LockMode::Exclusive => lock_file.try_lock_exclusive().is_ok(),
+        }
+    }
+
+    fn lock(self, lock_file: &File) -> Result<(), Error> {
+        match self {
+            LockMode::Shared => lock_file.lock_shared()?,
+            LockMode::Exclusive => lock_file.lock_exclusive()?,
+        }
+        Ok(())
+    }
+}
+
 /// A handle representing ownership over Nest's lock file
 #[derive(Debug)]
 pub struct LockFileOwnership {
     lock_file: File,
+    mode: LockMode,
 }
 
 impl LockFileOwnership {
-    pub(crate) fn acquire(path: &Path, should_wait: bool) -> Result<Self, Error> {
+    /// Opens the lock file without truncating it, as it holds the PID of the process holding it.
+    ///
+    /// To be shared, the lock file is only opened for reading, so that users who can't write it can still read the
+    /// system. It is only created if it doesn't exist yet.
+    fn open(path: &Path, mode: LockMode) -> Result<File, Error> {
+        if mode == LockMode::Shared {
+            match File::open(path) {
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                res => return Ok(res.with_context(|_| path.display().to_string())?),
+            }
+        }
         if let Some(parent_path) = path.parent() {
             fs::create_dir_all(&parent_path).with_context(|_| parent_path.display().to_string())?;
         }
-        let f = File::create(path)?;
+        Ok(OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path)
+            .with_context(|_| path.display().to_string())?)
+    }
+
+    /// Takes ownership of the locked file.
+    ///
+    /// When held exclusively, the PID of the current process is recorded in it.
+    fn own(mut lock_file: File, mode: LockMode) -> Result<Self, Error> {
+        if mode == LockMode::Exclusive {
+            lock_file.set_len(0)?;
+            lock_file.seek(SeekFrom::Start(0))?;
+            write!(lock_file, "{}", std::process::id())?;
+            lock_file.flush()?;
+        }
+        Ok(LockFileOwnership { lock_file, mode })
+    }
+
+    pub(crate) fn acquire(path: &Path, mode: LockMode, wait: LockWait) -> Result<Self, Error> {
+        match wait {
+            LockWait::Never => {
+                let lock_file = Self::open(path, mode)?;
+
+                if !mode.try_lock(&lock_file) {
+                    let holder = Self::holder(path).map_or_else(
+                        || format_err!("other instances of Nest reading the system"),
+                        |pid| format_err!("process {}", pid),
+                    );
+                    return Err(holder.context(LockFileErrorKind::AlreadyHeld).into());
+                }
+                Self::own(lock_file, mode)
+            }
+            LockWait::Forever => Self::acquire_blocking(path, mode, None),
+            LockWait::Timeout(timeout) => Self::acquire_blocking(path, mode, Some(timeout)),
+        }
+    }
+
+    /// Acquires the lock file at the given path, waiting for the processes holding it to release it.
+    ///
+    /// If a timeout is given, an error is returned if the lock file is still held once it has elapsed.
+    pub(crate) fn acquire_blocking(
+        path: &Path,
+        mode: LockMode,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error> {
+        let lock_file = Self::open(path, mode)?;
+
+        match timeout {
+            None => mode.lock(&lock_file)?,
+            Some(timeout) => {
+                let deadline = Instant::now() + timeout;
+
+                while !mode.try_lock(&lock_file) {
+                    if Instant::now() >= deadline {
+                        return Err(LockFileError::from(LockFileErrorKind::TimedOut).into());
+                    }
+                    thread::sleep(LOCK_FILE_POLL_INTERVAL);
+                }
+            }
+        }
+        Self::own(lock_file, mode)
+    }
+
+    /// Returns the way the lock file is held
+    #[inline]
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
 
-        if should_wait {
-            f.lock_exclusive()?;
-        } else {
-            f.try_lock_exclusive()?;
+    /// Returns the PID of the process holding the lock file exclusively at the given path, if it is held
+    /// this way
+    pub fn holder<P: AsRef<Path>>(path: P) -> Option<u32> {
+        let mut lock_file = File::open(path.as_ref()).ok()?;
+
+        // The lock file is held exclusively if it can't be shared
+        if lock_file.try_lock_shared().is_ok() {
+            let _ = lock_file.unlock();
+            return None;
         }
-        Ok(LockFileOwnership { lock_file: f })
+
+        let mut pid = String::new();
+        lock_file.read_to_string(&mut pid).ok()?;
+        pid.trim().parse().ok()
+    }
+
+    /// Returns the PID recorded in the lock file at the given path while it isn't held exclusively anymore, which
+    /// means that the process that held it exited without releasing it, usually because it crashed
+    pub fn stale_holder<P: AsRef<Path>>(path: P) -> Option<u32> {
+        let mut lock_file = File::open(path.as_ref()).ok()?;
+
+        if lock_file.try_lock_shared().is_err() {
+            return None;
+        }
+        let _ = lock_file.unlock();
+
+        let mut pid = String::new();
+        lock_file.read_to_string(&mut pid).ok()?;
+        pid.trim().parse().ok()
     }
 
     fn release(&mut self) {
+        // Forget the PID of the current process, so that it isn't taken for the holder of the lock file once
+        // it is shared
+        if self.mode == LockMode::Exclusive {
+            let _ = self.lock_file.set_len(0);
+        }
         self.lock_file
             .unlock()
             .expect("unable to release the lock file");
@@ -0,0 +1,395 @@
+//! Creation of NPFs from a manifest, a staged file tree, an optional `instructions.sh` and an optional
+//! `changelog.toml`.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use failure::ResultExt;
+use flate2::write::GzEncoder;
+use tar::{Builder, EntryType, Header};
+
+use super::changelog::Changelog;
+use super::error::{NPFBuildError, NPFBuildErrorKind};
+use super::manifest::{Compression, Kind, Manifest};
+use super::seekable::FramedEncoder;
+use super::signature::{self, SigningKey, SIGNATURE_FILE};
+use super::Checksum;
+
+/// Structure to create an NPF, the archive format of packages.
+///
+/// The files of the package are taken from a staging folder, whose content is installed in the root folder
+/// when the package is installed. The data archive is compressed as declared in the manifest.
+///
+/// The files keep their owner, their permissions and the extended attributes of the regular files, file
+/// capabilities included, which are recorded as PAX extended headers. The SELinux labels are left out, as they are
+/// given by the policy of the system the package is installed on.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate libnest;
+/// # extern crate failure;
+/// # fn main() -> Result<(), failure::Error> {
+/// use libnest::package::{Manifest, NPFBuilder, SigningKey};
+///
+/// let manifest: Manifest = toml::from_str(&std::fs::read_to_string("manifest.toml")?)?;
+/// let checksum = NPFBuilder::new(manifest)
+///     .with_data_dir("./staging/")
+///     .with_instructions("after_install() { ldconfig; }")
+///     .with_signing_key(SigningKey::parse("bzDBRTpcy0Mkm07GqWHOQwHtz9QbDzqVpGfhcyP9mQY=")?)
+///     .build("hello-1.0.0.nest")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct NPFBuilder {
+    manifest: Manifest,
+    data_dir: Option<PathBuf>,
+    instructions: Option<String>,
+    changelog: Option<Changelog>,
+    signing_key: Option<SigningKey>,
+}
+
+impl NPFBuilder {
+    /// Creates a new [`NPFBuilder`] for a package described by the given manifest.
+    ///
+    /// By default, the package contains no file, no `instructions.sh`, no `changelog.toml` and isn't signed.
+    #[inline]
+    pub fn new(manifest: Manifest) -> Self {
+        Self {
+            manifest,
+            data_dir: None,
+            instructions: None,
+            changelog: None,
+            signing_key: None,
+        }
+    }
+
+    /// Sets the staging folder holding the files of the package, as they must be installed relative to the root folder
+    #[inline]
+    pub fn with_data_dir<P: AsRef<Path>>(mut self, data_dir: P) -> Self {
+        self.data_dir = Some(data_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the content of the `instructions.sh` script of the package
+    #[inline]
+    pub fn with_instructions<S: Into<String>>(mut self, instructions: S) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Sets the changelog of the package, shipped in its `changelog.toml`
+    #[inline]
+    pub fn with_changelog(mut self, changelog: Changelog) -> Self {
+        self.changelog = Some(changelog);
+        self
+    }
+
+    /// Sets the key the package is signed with
+    #[inline]
+    pub fn with_signing_key(mut self, signing_key: SigningKey) -> Self {
+        self.signing_key = Some(signing_key);
+        self
+    }
+
+    /// Returns the manifest of the package
+    #[inline]
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Creates the NPF at the given path, and returns its checksum, as advertised by repositories.
+    pub fn build<P: AsRef<Path>>(&self, npf_path: P) -> Result<Checksum, NPFBuildError> {
+        let npf_path = npf_path.as_ref();
+        let write_error = || NPFBuildErrorKind::NPFWriteError(npf_path.to_path_buf());
+
+        // The installed size is computed from the staging folder, unless it was given explicitly
+        let mut manifest = self.manifest.clone();
+        if let (None, Some(data_dir)) = (manifest.installed_size(), &self.data_dir) {
+            *manifest.installed_size_mut() =
+                Some(tree_size(data_dir).context(NPFBuildErrorKind::DataArchiveError)?);
+        }
+
+        // Tables must come last in TOML, which only a Value guarantees when serialized
+        let manifest = toml::Value::try_from(&manifest)
+            .and_then(|value| toml::to_string(&value))
+            .map_err(|_| NPFBuildErrorKind::InvalidManifest)?;
+
+        let changelog = match &self.changelog {
+            Some(changelog) => {
+                Some(toml::to_string(changelog).map_err(|_| NPFBuildErrorKind::InvalidChangelog)?)
+            }
+            None => None,
+        };
+
+        if self.manifest.kind() == Kind::Virtual && self.data_dir.is_some() {
+            return Err(NPFBuildErrorKind::DataInVirtualPackage.into());
+        }
+
+        // The data archive may be large, so it is staged next to the NPF rather than in memory
+        let data_path = match self.manifest.kind() {
+            Kind::Effective => Some(staged_data_path(npf_path)),
+            Kind::Virtual => None,
+        };
+        let res = self.write_npf(
+            npf_path,
+            &manifest,
+            changelog.as_ref().map(String::as_str),
+            data_path.as_ref().map(PathBuf::as_path),
+        );
+        if let Some(data_path) = &data_path {
+            let _ = fs::remove_file(data_path);
+        }
+        res?;
+
+        Ok(File::open(npf_path)
+            .and_then(|mut file| Checksum::from_reader(&mut file))
+            .context(write_error())?)
+    }
+
+    fn write_npf(
+        &self,
+        npf_path: &Path,
+        manifest: &str,
+        changelog: Option<&str>,
+        data_path: Option<&Path>,
+    ) -> Result<(), NPFBuildError> {
+        let write_error = || NPFBuildErrorKind::NPFWriteError(npf_path.to_path_buf());
+        let compression = self.manifest.compression();
+
+        let data_checksum = match data_path {
+            Some(data_path) => {
+                write_data_archive(
+                    data_path,
+                    self.data_dir.as_ref().map(PathBuf::as_path),
+                    compression,
+                )
+                .context(NPFBuildErrorKind::DataArchiveError)?;
+
+                Some(
+                    File::open(data_path)
+                        .and_then(|mut file| Checksum::from_reader(&mut file))
+                        .context(NPFBuildErrorKind::DataArchiveError)?,
+                )
+            }
+            None => None,
+        };
+
+        let res: Result<_, io::Error> = try {
+            let mtime = self.manifest.wrap_date().timestamp() as u64;
+            let mut builder = Builder::new(File::create(npf_path)?);
+
+            append_bytes(&mut builder, "manifest.toml", manifest.as_bytes(), mtime)?;
+            if let Some(data_path) = data_path {
+                builder.append_path_with_name(data_path, compression.data_file_name())?;
+            }
+            if let Some(instructions) = &self.instructions {
+                append_bytes(
+                    &mut builder,
+                    "instructions.sh",
+                    instructions.as_bytes(),
+                    mtime,
+                )?;
+            }
+            if let Some(changelog) = changelog {
+                append_bytes(&mut builder, "changelog.toml", changelog.as_bytes(), mtime)?;
+            }
+
+            if let Some(signing_key) = &self.signing_key {
+                let message = signature::signed_message_from(|file_name| {
+                    Ok(match file_name {
+                        "manifest.toml" => Some(Checksum::from_reader(&mut manifest.as_bytes())?),
+                        "instructions.sh" => match &self.instructions {
+                            Some(instructions) => {
+                                Some(Checksum::from_reader(&mut instructions.as_bytes())?)
+                            }
+                            None => None,
+                        },
+                        "changelog.toml" => match changelog {
+                            Some(changelog) => {
+                                Some(Checksum::from_reader(&mut changelog.as_bytes())?)
+                            }
+                            None => None,
+                        },
+                        _ if file_name == compression.data_file_name() => data_checksum,
+                        _ => None,
+                    })
+                })?;
+                append_bytes(
+                    &mut builder,
+                    SIGNATURE_FILE,
+                    &signing_key.sign(&message),
+                    mtime,
+                )?;
+            }
+
+            builder.into_inner()?.flush()?;
+        };
+        Ok(res.context(write_error())?)
+    }
+}
+
+/// Returns the path of the file the data archive of the given NPF is staged in
+fn staged_data_path(npf_path: &Path) -> PathBuf {
+    let mut file_name = npf_path.file_name().unwrap_or_default().to_os_string();
+
+    file_name.push(".data");
+    npf_path.with_file_name(file_name)
+}
+
+/// Appends an in-memory file to the given archive
+fn append_bytes<W: Write>(
+    builder: &mut Builder<W>,
+    name: &str,
+    content: &[u8],
+    mtime: u64,
+) -> io::Result<()> {
+    let mut header = Header::new_gnu();
+
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    header.set_cksum();
+    builder.append_data(&mut header, name, content)
+}
+
+/// Writes the compressed data archive of a package, containing the files of the given staging folder, if any.
+///
+/// Zstd archives are split in independent frames, so they can be decompressed on several threads.
+fn write_data_archive(
+    data_path: &Path,
+    data_dir: Option<&Path>,
+    compression: Compression,
+) -> io::Result<()> {
+    let file = File::create(data_path)?;
+
+    match compression {
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(file, flate2::Compression::default());
+            write_tree(&mut encoder, data_dir)?;
+            encoder.finish()?.flush()
+        }
+        Compression::Zstd => {
+            let mut encoder = FramedEncoder::new(file);
+            write_tree(&mut encoder, data_dir)?;
+            encoder.finish()?.flush()
+        }
+    }
+}
+
+/// Returns the total size, in bytes, of the regular files of the given folder and its sub-folders
+fn tree_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let metadata = fs::symlink_metadata(&path)?;
+
+        if metadata.is_dir() {
+            size += tree_size(&path)?;
+        } else if metadata.is_file() {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// The prefix of the PAX records holding the extended attributes of a file
+const PAX_XATTR_PREFIX: &str = "SCHILY.xattr.";
+
+/// The extended attribute holding the SELinux label of a file, which isn't recorded
+const SELINUX_XATTR: &str = "security.selinux";
+
+/// Returns the PAX record holding the given key and value, prefixed by its own length
+fn pax_record(key: &[u8], value: &[u8]) -> Vec<u8> {
+    // The length of the record includes the digits of the length itself
+    let content_len = key.len() + value.len() + 3;
+    let mut len = content_len + 1;
+    while len != content_len + len.to_string().len() {
+        len = content_len + len.to_string().len();
+    }
+
+    let mut record = format!("{} ", len).into_bytes();
+    record.extend_from_slice(key);
+    record.push(b'=');
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
+
+/// Appends a PAX extended header holding the extended attributes of the given file, if it has any, so that they
+/// apply to the entry appended next
+fn append_xattrs<W: Write>(builder: &mut Builder<W>, path: &Path, name: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut records = Vec::new();
+    let mut attributes: Vec<_> = match xattr::list(path) {
+        Ok(attributes) => attributes
+            .filter(|attribute| **attribute != *SELINUX_XATTR)
+            .collect(),
+        // The filesystem of the staging folder doesn't support extended attributes
+        Err(ref e) if e.raw_os_error() == Some(libc::ENOTSUP) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    attributes.sort();
+
+    for attribute in attributes {
+        if let Some(value) = xattr::get(path, &attribute)? {
+            let mut key = PAX_XATTR_PREFIX.as_bytes().to_vec();
+            key.extend_from_slice(attribute.as_bytes());
+            records.extend(pax_record(&key, &value));
+        }
+    }
+
+    if !records.is_empty() {
+        let mut header = Header::new_ustar();
+
+        header.set_entry_type(EntryType::XHeader);
+        header.set_size(records.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(
+            &mut header,
+            Path::new("PaxHeaders").join(name),
+            records.as_slice(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes a tar archive of the content of the given folder, if any, to the given writer
+fn write_tree<W: Write>(writer: W, dir: Option<&Path>) -> io::Result<()> {
+    let mut builder = Builder::new(writer);
+
+    builder.follow_symlinks(false);
+    if let Some(dir) = dir {
+        append_tree(&mut builder, dir, dir)?;
+    }
+    builder.finish()
+}
+
+/// Recursively appends the content of a folder to the given archive, sorted by name so that
+/// the archive only depends on the content of the folder
+fn append_tree<W: Write>(builder: &mut Builder<W>, root: &Path, dir: &Path) -> io::Result<()> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    for path in paths {
+        let name = path.strip_prefix(root).unwrap_or(&path);
+
+        let metadata = fs::symlink_metadata(&path)?;
+
+        if metadata.is_file() {
+            append_xattrs(builder, &path, name)?;
+        }
+        builder.append_path_with_name(&path, name)?;
+        if metadata.is_dir() {
+            append_tree(builder, root, &path)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+//! Changelogs shipped by NPFs in `changelog.toml`, describing the changes made by each version of a package.
+
+use chrono::NaiveDate;
+use semver::Version;
+use serde_derive::{Deserialize, Serialize};
+
+/// An entry of a [`Changelog`], listing the changes made by a version of a package
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ChangelogEntry {
+    version: Version,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
+    #[serde(default)]
+    changes: Vec<String>,
+}
+
+impl ChangelogEntry {
+    /// Creates a new [`ChangelogEntry`] for the given version, listing the given changes
+    #[inline]
+    pub fn from(version: Version, changes: Vec<String>) -> Self {
+        Self {
+            version,
+            date: None,
+            changes,
+        }
+    }
+
+    /// Sets the date the version was released
+    #[inline]
+    pub fn with_date(mut self, date: NaiveDate) -> Self {
+        self.date = Some(date);
+        self
+    }
+
+    /// Returns the version of the package this entry describes
+    #[inline]
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Returns the date the version was released, if it is known
+    #[inline]
+    pub fn date(&self) -> Option<NaiveDate> {
+        self.date
+    }
+
+    /// Returns the changes made by the version, one per line
+    #[inline]
+    pub fn changes(&self) -> &[String] {
+        &self.changes
+    }
+}
+
+/// The changelog of a package, as shipped by its NPF in `changelog.toml`:
+///
+/// ```toml
+/// [[entries]]
+/// version = "1.1.0"
+/// date = "2019-06-01"
+/// changes = ["Add the --quiet option", "Fix a crash on empty input"]
+/// ```
+///
+/// Its entries are kept sorted, the most recent version first.
+#[derive(Serialize, Deserialize, Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub struct Changelog {
+    #[serde(default)]
+    entries: Vec<ChangelogEntry>,
+}
+
+impl Changelog {
+    /// Creates a new [`Changelog`] made of the given entries
+    pub fn from(mut entries: Vec<ChangelogEntry>) -> Self {
+        entries.sort_by(|a, b| b.version.cmp(&a.version));
+        Self { entries }
+    }
+
+    /// Returns the entries of the changelog, the most recent version first
+    #[inline]
+    pub fn entries(&self) -> &[ChangelogEntry] {
+        &self.entries
+    }
+
+    /// Returns the entries of the given number of most recent versions, as indexed by repositories
+    pub fn recent(&self, count: usize) -> &[ChangelogEntry] {
+        &self.entries[..count.min(self.entries.len())]
+    }
+
+    /// Returns the entries of the versions more recent than `from`, if it is given, up to `to` included, the
+    /// most recent version first
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate semver;
+    /// use libnest::package::{Changelog, ChangelogEntry};
+    /// use semver::Version;
+    ///
+    /// let changelog = Changelog::from(
+    ///     ["1.0.0", "1.1.0", "1.2.0", "2.0.0"]
+    ///         .iter()
+    ///         .map(|version| ChangelogEntry::from(Version::parse(version).unwrap(), Vec::new()))
+    ///         .collect(),
+    /// );
+    /// let entries = changelog.between(Some(&Version::new(1, 0, 0)), &Version::new(1, 2, 0));
+    /// assert_eq!(
+    ///     entries.iter().map(|entry| entry.version().to_string()).collect::<Vec<_>>(),
+    ///     vec!["1.2.0", "1.1.0"]
+    /// );
+    /// ```
+    pub fn between(&self, from: Option<&Version>, to: &Version) -> Vec<&ChangelogEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.version <= *to && from.map_or(true, |from| entry.version > *from))
+            .collect()
+    }
+}
@@ -0,0 +1,218 @@
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read};
+
+use serde::de::Visitor;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::error::ChecksumParseError;
+
+/// The SHA-256 checksum of an NPF.
+///
+/// Its string representation is the lowercase hexadecimal encoding of the digest.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Checksum([u8; 32]);
+
+impl Checksum {
+    /// Parses the string representation of a [`Checksum`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::package::Checksum;
+    ///
+    /// let checksum = Checksum::from_reader(&mut "nest".as_bytes())?;
+    /// assert_eq!(Checksum::parse(&checksum.to_string())?, checksum);
+    ///
+    /// assert!(Checksum::parse("not a checksum").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse(repr: &str) -> Result<Self, ChecksumParseError> {
+        Self::try_from(repr)
+    }
+
+    /// Computes the [`Checksum`] of all the data that can be read from the given reader
+    pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut hasher = Sha256::new();
+        let mut checksum = [0; 32];
+
+        io::copy(reader, &mut hasher)?;
+        checksum.copy_from_slice(&hasher.result());
+        Ok(Checksum(checksum))
+    }
+
+    /// Returns the raw bytes of the digest
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl TryFrom<&str> for Checksum {
+    type Error = ChecksumParseError;
+
+    fn try_from(repr: &str) -> Result<Self, Self::Error> {
+        if repr.len() != 64 || !repr.is_ascii() {
+            return Err(ChecksumParseError(repr.to_string()));
+        }
+
+        let mut checksum = [0; 32];
+        for (i, byte) in checksum.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&repr[i * 2..i * 2 + 2], 16)
+                .map_err(|_| ChecksumParseError(repr.to_string()))?;
+        }
+        Ok(Checksum(checksum))
+    }
+}
+
+impl Display for Checksum {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(fmt, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+struct ChecksumVisitor;
+
+impl<'de> Visitor<'de> for ChecksumVisitor {
+    type Value = Checksum;
+
+    #[inline]
+    fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("a checksum")
+    }
+
+    #[inline]
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Checksum::parse(value)
+            .map_err(|_| E::custom("the checksum isn't a hex-encoded SHA-256 digest"))
+    }
+}
+
+impl_serde_visitor!(Checksum, ChecksumVisitor);
+
+/// The checksums of the consecutive segments of an NPF, advertised by repositories so that the segments can be
+/// downloaded from several mirrors at once, each of them being verified on its own.
+///
+/// Every segment is `segment_size` bytes long, except the last one which may be shorter.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SegmentChecksums {
+    size: u64,
+    segment_size: u64,
+    checksums: Vec<Checksum>,
+}
+
+impl SegmentChecksums {
+    /// Computes the checksums of the segments of the given size of all the data that can be read from the given
+    /// reader.
+    ///
+    /// A segment size of `0` is treated as a segment size of `1`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::package::SegmentChecksums;
+    ///
+    /// let segments = SegmentChecksums::from_reader(&mut "raven-os".as_bytes(), 3)?;
+    /// assert_eq!(segments.len(), 3);
+    /// assert_eq!(segments.segment(2), Some((6, 2)));
+    /// assert!(segments.verify(1, b"en-"));
+    /// assert!(!segments.verify(1, b"os"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_reader<R: Read>(reader: &mut R, segment_size: u64) -> io::Result<Self> {
+        let segment_size = segment_size.max(1);
+        let mut size = 0;
+        let mut checksums = Vec::new();
+
+        loop {
+            let mut segment = Vec::new();
+            let read = reader.take(segment_size).read_to_end(&mut segment)?;
+            if read == 0 {
+                break;
+            }
+            size += read as u64;
+            checksums.push(Checksum::from_reader(&mut &segment[..])?);
+        }
+        Ok(SegmentChecksums {
+            size,
+            segment_size,
+            checksums,
+        })
+    }
+
+    /// Returns the size of the whole NPF, in bytes
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the size of the segments, in bytes
+    #[inline]
+    pub fn segment_size(&self) -> u64 {
+        self.segment_size
+    }
+
+    /// Returns the checksums of the segments, in order
+    #[inline]
+    pub fn checksums(&self) -> &[Checksum] {
+        &self.checksums
+    }
+
+    /// Returns the number of segments
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.checksums.len()
+    }
+
+    /// Returns whether there is no segment, which is the case of empty files only
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.checksums.is_empty()
+    }
+
+    /// Returns whether there is exactly one checksum for each segment of the NPF
+    pub fn is_consistent(&self) -> bool {
+        self.segment_size > 0
+            && self.checksums.len() as u64
+                == (self.size + self.segment_size - 1) / self.segment_size
+    }
+
+    /// Returns the offset and the length of the segment at the given index, or [`None`] if there is no such
+    /// segment
+    pub fn segment(&self, index: usize) -> Option<(u64, u64)> {
+        if index >= self.checksums.len() {
+            return None;
+        }
+
+        let offset = index as u64 * self.segment_size;
+        Some((
+            offset,
+            self.segment_size.min(self.size.saturating_sub(offset)),
+        ))
+    }
+
+    /// Returns whether the given data is the segment at the given index, as its checksum tells
+    pub fn verify(&self, index: usize, data: &[u8]) -> bool {
+        match (self.segment(index), Checksum::from_reader(&mut &data[..])) {
+            (Some((_, length)), Ok(checksum)) => {
+                data.len() as u64 == length && checksum == self.checksums[index]
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Deltas between the NPFs of two versions of a package.
+//!
+//! A delta (`.ndelta`) is a tar archive holding a `delta.toml` file, describing the versions it goes from and to
+//! along with the size and checksum of the NPF it produces, and a `patch.zst` file, the zstd-compressed bsdiff
+//! patch turning the old NPF into the new one. Upgrading a package using a delta only requires downloading the
+//! patch, as long as the NPF of the installed version is still downloaded.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use failure::ResultExt;
+use serde_derive::{Deserialize, Serialize};
+use tar::{Archive, Builder, Header};
+
+use super::error::{DeltaError, DeltaErrorKind};
+use super::{Checksum, PackageID};
+
+static DELTA_MANIFEST_FILE: &str = "delta.toml";
+static DELTA_PATCH_FILE: &str = "patch.zst";
+
+/// Structure describing a delta between the NPFs of two versions of a package
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct NPFDelta {
+    from: PackageID,
+    to: PackageID,
+    size: u64,
+    checksum: Checksum,
+}
+
+impl NPFDelta {
+    /// Creates a delta at the given path, turning the NPF of the package `from` into the one of the package `to`.
+    pub fn create<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(
+        from: PackageID,
+        old_npf: P,
+        to: PackageID,
+        new_npf: Q,
+        delta_path: R,
+    ) -> Result<NPFDelta, DeltaError> {
+        let old = read_file(old_npf.as_ref())?;
+        let new = read_file(new_npf.as_ref())?;
+
+        let delta = NPFDelta {
+            from,
+            to,
+            size: new.len() as u64,
+            checksum: Checksum::from_reader(&mut new.as_slice())
+                .context(DeltaErrorKind::PatchError)?,
+        };
+        let manifest = toml::to_string(&delta).context(DeltaErrorKind::InvalidDelta)?;
+
+        let mut encoder =
+            zstd::stream::write::Encoder::new(Vec::new(), 0).context(DeltaErrorKind::PatchError)?;
+        bsdiff::diff::diff(&old, &new, &mut encoder).context(DeltaErrorKind::PatchError)?;
+        let patch = encoder.finish().context(DeltaErrorKind::PatchError)?;
+
+        let delta_path = delta_path.as_ref();
+        let res: Result<_, io::Error> = try {
+            let mut builder = Builder::new(File::create(delta_path)?);
+
+            append_bytes(&mut builder, DELTA_MANIFEST_FILE, manifest.as_bytes())?;
+            append_bytes(&mut builder, DELTA_PATCH_FILE, &patch)?;
+            builder.into_inner()?.flush()?;
+        };
+        res.context(DeltaErrorKind::FileIOError(delta_path.to_path_buf()))?;
+
+        Ok(delta)
+    }
+
+    /// Applies the delta at the given path to the NPF of the package `base`, writing the resulting NPF at `new_npf`.
+    ///
+    /// The delta must go from the package `base`, and the resulting NPF must match the checksum recorded in the delta.
+    pub fn apply<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(
+        delta_path: P,
+        base: &PackageID,
+        old_npf: Q,
+        new_npf: R,
+    ) -> Result<NPFDelta, DeltaError> {
+        let delta_path = delta_path.as_ref();
+        let (delta, patch) = Self::load(delta_path)?;
+
+        if delta.from != *base {
+            return Err(DeltaErrorKind::BaseMismatch(delta.from).into());
+        }
+
+        let old = read_file(old_npf.as_ref())?;
+        let mut new = vec![0; delta.size as usize];
+        let res: Result<_, io::Error> = try {
+            let mut decoder = zstd::stream::read::Decoder::new(patch.as_slice())?;
+            bsdiff::patch::patch(&old, &mut decoder, &mut new)?;
+        };
+        res.context(DeltaErrorKind::PatchError)?;
+
+        if Checksum::from_reader(&mut new.as_slice()).context(DeltaErrorKind::PatchError)?
+            != delta.checksum
+        {
+            return Err(DeltaErrorKind::ChecksumMismatch.into());
+        }
+
+        let new_npf = new_npf.as_ref();
+        fs::write(new_npf, &new).context(DeltaErrorKind::FileIOError(new_npf.to_path_buf()))?;
+        Ok(delta)
+    }
+
+    /// Loads the description and the compressed patch of the delta at the given path
+    fn load(delta_path: &Path) -> Result<(NPFDelta, Vec<u8>), DeltaError> {
+        let mut manifest = None;
+        let mut patch = None;
+
+        let res: Result<_, io::Error> = try {
+            let mut archive = Archive::new(File::open(delta_path)?);
+
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let name = entry.path()?.into_owned();
+
+                if name == Path::new(DELTA_MANIFEST_FILE) {
+                    let mut content = String::new();
+                    entry.read_to_string(&mut content)?;
+                    manifest = Some(content);
+                } else if name == Path::new(DELTA_PATCH_FILE) {
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content)?;
+                    patch = Some(content);
+                }
+            }
+        };
+        res.context(DeltaErrorKind::FileIOError(delta_path.to_path_buf()))?;
+
+        match (manifest, patch) {
+            (Some(manifest), Some(patch)) => Ok((
+                toml::from_str(&manifest).context(DeltaErrorKind::InvalidDelta)?,
+                patch,
+            )),
+            _ => Err(DeltaErrorKind::InvalidDelta.into()),
+        }
+    }
+
+    /// Returns the package whose NPF the delta applies to
+    #[inline]
+    pub fn from(&self) -> &PackageID {
+        &self.from
+    }
+
+    /// Returns the package whose NPF the delta produces
+    #[inline]
+    pub fn to(&self) -> &PackageID {
+        &self.to
+    }
+
+    /// Returns the size, in bytes, of the NPF the delta produces
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the checksum of the NPF the delta produces
+    #[inline]
+    pub fn checksum(&self) -> &Checksum {
+        &self.checksum
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, DeltaError> {
+    Ok(fs::read(path).context(DeltaErrorKind::FileIOError(path.to_path_buf()))?)
+}
+
+/// Appends an in-memory file to the given archive
+fn append_bytes<W: Write>(builder: &mut Builder<W>, name: &str, content: &[u8]) -> io::Result<()> {
+    let mut header = Header::new_gnu();
+
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, name, content)
+}
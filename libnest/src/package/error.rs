@@ -103,7 +103,7 @@ pub struct SoftPackageRequirementParseError {
 pub enum SoftPackageRequirementParseErrorKind {
     /// The given string does not follow the format for package requirements
     #[fail(
-        display = "\"{}\" doesn't follow the `repository::category/name#version` format",
+        display = "\"{}\" doesn't follow the `repository::category/name:slot#version` format",
         _0
     )]
     InvalidFormat(String),
@@ -123,6 +123,10 @@ pub enum SoftPackageRequirementParseErrorKind {
     /// The version component of the package requirement is not a valid version
     #[fail(display = "invalid version syntax")]
     InvalidVersion,
+
+    /// The slot component of the package requirement has invalid characters
+    #[fail(display = "{}", _0)]
+    InvalidSlot(#[cause] SlotParseError),
 }
 
 use_as_error!(
@@ -141,7 +145,7 @@ pub struct PackageRequirementParseError {
 pub enum PackageRequirementParseErrorKind {
     /// The given string does not follow the format for package requirements
     #[fail(
-        display = "\"{}\" doesn't follow the `repository::category/name#version` format",
+        display = "\"{}\" doesn't follow the `repository::category/name:slot#version` format",
         _0
     )]
     InvalidFormat(String),
@@ -161,6 +165,10 @@ pub enum PackageRequirementParseErrorKind {
     /// The version component of the package requirement is not a valid version
     #[fail(display = "invalid version syntax")]
     InvalidVersion,
+
+    /// The slot component of the package requirement has invalid characters
+    #[fail(display = "{}", _0)]
+    InvalidSlot(#[cause] SlotParseError),
 }
 
 use_as_error!(
@@ -193,11 +201,43 @@ pub struct TagParseError(pub String);
 #[fail(display = "{}: invalid license", 0)]
 pub struct LicenseParseError(pub String);
 
+/// Strong type to represent an error message related to the parsing of an SPDX license expression
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Fail)]
+#[fail(display = "{}: invalid license expression", 0)]
+pub struct LicenseExpressionParseError(pub String);
+
 /// Strong type to represent an error message related to the parsing of a package slot
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Fail)]
 #[fail(display = "{}: invalid slot", 0)]
 pub struct SlotParseError(pub String);
 
+/// Strong type to represent an error message related to the parsing of a manifest version
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Fail)]
+#[fail(display = "{}: invalid manifest version", 0)]
+pub struct ManifestVersionParseError(pub String);
+
+/// Strong type to represent an error message related to the parsing of an architecture
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Fail)]
+#[fail(display = "{}: invalid architecture", 0)]
+pub struct ArchitectureParseError(pub String);
+
+/// Strong type to represent an error message related to the parsing of a trusted key
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Fail)]
+#[fail(display = "{}: invalid trusted key", 0)]
+pub struct TrustedKeyParseError(pub String);
+
+/// Type to represent an error related to the parsing of a signing key.
+///
+/// Unlike other parse errors, it doesn't hold the invalid string, as it may be a secret.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Fail)]
+#[fail(display = "invalid signing key")]
+pub struct SigningKeyParseError;
+
+/// Strong type to represent an error message related to the parsing of a checksum
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Fail)]
+#[fail(display = "{}: invalid checksum", 0)]
+pub struct ChecksumParseError(pub String);
+
 /// Type for errors related to the exploration of an NPF file
 #[derive(Debug)]
 pub struct NPFExplorationError {
@@ -219,6 +259,17 @@ pub enum NPFExplorationErrorKind {
     #[fail(display = "invalid manifest.toml")]
     InvalidManifest,
 
+    /// The manifest of an NPF follows a major version of the format this version of Nest doesn't support
+    #[fail(
+        display = "the package's manifest has version {}, which this version of Nest can't read: upgrade Nest to use it",
+        _0
+    )]
+    UnsupportedManifestVersion(crate::package::ManifestVersion),
+
+    /// The optional changelog file of an NPF was found, but is invalid
+    #[fail(display = "invalid changelog.toml")]
+    InvalidChangelog,
+
     /// A requested file could not be found in the NPF
     #[fail(display = "the requested file not found in the NPF: {:?}", _0)]
     FileNotFound(std::path::PathBuf),
@@ -229,6 +280,90 @@ pub enum NPFExplorationErrorKind {
     )]
     /// A requested file was found in an NPF, but could not be used
     FileIOError(std::path::PathBuf),
+
+    /// The NPF has no signature, but one was required
+    #[fail(display = "the package is not signed")]
+    MissingSignature,
+
+    /// The checksum of the NPF doesn't match the one advertised by its repository
+    #[fail(display = "the package's checksum doesn't match the one advertised by its repository")]
+    ChecksumMismatch,
+
+    /// The repository of the NPF doesn't advertise its checksum, and doesn't allow unsigned packages
+    #[fail(display = "the package's repository doesn't advertise its checksum")]
+    UnknownChecksum,
+
+    /// The signature of the NPF doesn't match any of the trusted keys
+    #[fail(display = "the package's signature doesn't match any trusted key")]
+    InvalidSignature,
+
+    /// The temporary directory an NPF was unpacked in could not be removed
+    #[fail(display = "unable to remove the unpacked NPF: {:?}", _0)]
+    CleanupError(std::path::PathBuf),
 }
 
 use_as_error!(NPFExplorationError, NPFExplorationErrorKind);
+
+/// Type for errors related to the creation of an NPF file
+#[derive(Debug)]
+pub struct NPFBuildError {
+    inner: Context<NPFBuildErrorKind>,
+}
+
+/// Kind for errors related to the creation of an NPF file
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Fail)]
+pub enum NPFBuildErrorKind {
+    /// The manifest could not be serialized
+    #[fail(display = "invalid manifest")]
+    InvalidManifest,
+
+    /// The changelog could not be serialized
+    #[fail(display = "invalid changelog")]
+    InvalidChangelog,
+
+    /// Data was given for a virtual package
+    #[fail(display = "a virtual package can't contain any data")]
+    DataInVirtualPackage,
+
+    /// The data archive could not be created from the staged files
+    #[fail(display = "unable to create the data archive")]
+    DataArchiveError,
+
+    /// The NPF file could not be written
+    #[fail(display = "unable to write the NPF: {:?}", _0)]
+    NPFWriteError(std::path::PathBuf),
+}
+
+use_as_error!(NPFBuildError, NPFBuildErrorKind);
+
+/// Type for errors related to the creation or application of a delta between two NPFs
+#[derive(Debug)]
+pub struct DeltaError {
+    inner: Context<DeltaErrorKind>,
+}
+
+/// Kind for errors related to the creation or application of a delta between two NPFs
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Fail)]
+pub enum DeltaErrorKind {
+    /// A file could not be read or written
+    #[fail(display = "unable to access {:?}", _0)]
+    FileIOError(std::path::PathBuf),
+
+    /// The delta is malformed
+    #[fail(display = "invalid delta")]
+    InvalidDelta,
+
+    /// The delta doesn't apply to the NPF it was given
+    #[fail(display = "the delta applies to {}", _0)]
+    BaseMismatch(super::PackageID),
+
+    /// The patch could not be created or applied
+    #[fail(display = "unable to create or apply the patch")]
+    PatchError,
+
+    /// The NPF produced by the delta doesn't match its expected checksum
+    #[fail(display = "the patched package's checksum doesn't match the one recorded in the delta")]
+    ChecksumMismatch,
+}
+
+use_as_error!(DeltaError, DeltaErrorKind);
@@ -0,0 +1,299 @@
+use std::fmt::{self, Display, Formatter};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::de::Visitor;
+
+use super::error::LicenseExpressionParseError;
+
+lazy_static! {
+    static ref REGEX_LICENSE_ID: Regex =
+        Regex::new(r"^(?:DocumentRef-[A-Za-z0-9.\-]+:)?[A-Za-z0-9.\-]+$").unwrap();
+}
+
+/// An SPDX license expression, like `MIT OR Apache-2.0` or `GPL-2.0-or-later WITH Classpath-exception-2.0`,
+/// describing the licenses a package is distributed under.
+///
+/// `AND` binds tighter than `OR`, and parentheses can be used to group sub-expressions. Only the syntax of the
+/// expression is checked: license identifiers that aren't part of the SPDX license list should be written
+/// `LicenseRef-<name>`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LicenseExpression {
+    /// A single license, optionally followed by `+` to include its later versions, and an exception
+    License {
+        /// The SPDX identifier of the license
+        id: String,
+        /// Whether later versions of the license can be used too
+        or_later: bool,
+        /// The SPDX identifier of the exception to the license, if any
+        exception: Option<String>,
+    },
+
+    /// Licenses that must all be complied with
+    And(Vec<LicenseExpression>),
+
+    /// Licenses among which one can be chosen
+    Or(Vec<LicenseExpression>),
+}
+
+/// A token of a license expression
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Token<'a> {
+    Open,
+    Close,
+    And,
+    Or,
+    With,
+    Word(&'a str),
+}
+
+/// Splits a license expression into tokens
+fn tokenize(repr: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+
+    for word in repr.split_whitespace() {
+        let mut word = word;
+        while !word.is_empty() {
+            let end = word.find(&['(', ')'][..]).unwrap_or(word.len());
+            let token = match &word[..end] {
+                "" => {
+                    tokens.push(if word.starts_with('(') {
+                        Token::Open
+                    } else {
+                        Token::Close
+                    });
+                    word = &word[1..];
+                    continue;
+                }
+                "AND" | "and" => Token::And,
+                "OR" | "or" => Token::Or,
+                "WITH" | "with" => Token::With,
+                other => Token::Word(other),
+            };
+            tokens.push(token);
+            word = &word[end..];
+        }
+    }
+    tokens
+}
+
+/// A recursive descent parser of license expressions
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Option<LicenseExpression> {
+        let mut terms = vec![self.parse_and()?];
+        while self.peek() == Some(Token::Or) {
+            self.next();
+            terms.push(self.parse_and()?);
+        }
+        Some(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            LicenseExpression::Or(terms)
+        })
+    }
+
+    fn parse_and(&mut self) -> Option<LicenseExpression> {
+        let mut terms = vec![self.parse_term()?];
+        while self.peek() == Some(Token::And) {
+            self.next();
+            terms.push(self.parse_term()?);
+        }
+        Some(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            LicenseExpression::And(terms)
+        })
+    }
+
+    fn parse_term(&mut self) -> Option<LicenseExpression> {
+        match self.next()? {
+            Token::Open => {
+                let expression = self.parse_or()?;
+                match self.next()? {
+                    Token::Close => Some(expression),
+                    _ => None,
+                }
+            }
+            Token::Word(word) => {
+                let (id, or_later) = match word {
+                    _ if word.ends_with('+') => (&word[..word.len() - 1], true),
+                    _ => (word, false),
+                };
+                if !REGEX_LICENSE_ID.is_match(id) {
+                    return None;
+                }
+
+                let exception = if self.peek() == Some(Token::With) {
+                    self.next();
+                    match self.next()? {
+                        Token::Word(exception) if REGEX_LICENSE_ID.is_match(exception) => {
+                            Some(exception.to_string())
+                        }
+                        _ => return None,
+                    }
+                } else {
+                    None
+                };
+
+                Some(LicenseExpression::License {
+                    id: id.to_string(),
+                    or_later,
+                    exception,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl LicenseExpression {
+    /// Parses the string representation of a [`LicenseExpression`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::package::LicenseExpression;
+    ///
+    /// let expression = LicenseExpression::parse("(MIT OR Apache-2.0) AND GPL-2.0+ WITH Classpath-exception-2.0")?;
+    /// assert_eq!(expression.licenses(), vec!["MIT", "Apache-2.0", "GPL-2.0"]);
+    ///
+    /// assert!(LicenseExpression::parse("MIT OR").is_err());
+    /// assert!(LicenseExpression::parse("(MIT").is_err());
+    /// assert!(LicenseExpression::parse("GNU GPL").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse(repr: &str) -> Result<Self, LicenseExpressionParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(repr),
+            position: 0,
+        };
+
+        match parser.parse_or() {
+            Some(expression) if parser.peek().is_none() => Ok(expression),
+            _ => Err(LicenseExpressionParseError(repr.to_string())),
+        }
+    }
+
+    /// Returns the identifiers of the licenses appearing in this expression, in order
+    pub fn licenses(&self) -> Vec<&str> {
+        match self {
+            LicenseExpression::License { id, .. } => vec![id.as_str()],
+            LicenseExpression::And(terms) | LicenseExpression::Or(terms) => {
+                terms.iter().flat_map(LicenseExpression::licenses).collect()
+            }
+        }
+    }
+
+    /// Returns whether the licenses can be complied with without using any of the licenses for which `is_denied`
+    /// returns `true`, by choosing the right alternatives
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::package::LicenseExpression;
+    ///
+    /// let is_denied = |id: &str| id == "GPL-3.0-only";
+    /// assert!(LicenseExpression::parse("MIT OR GPL-3.0-only")?.is_satisfiable_without(&is_denied));
+    /// assert!(!LicenseExpression::parse("MIT AND GPL-3.0-only")?.is_satisfiable_without(&is_denied));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_satisfiable_without(&self, is_denied: &dyn Fn(&str) -> bool) -> bool {
+        match self {
+            LicenseExpression::License { id, .. } => !is_denied(id),
+            LicenseExpression::And(terms) => terms
+                .iter()
+                .all(|term| term.is_satisfiable_without(is_denied)),
+            LicenseExpression::Or(terms) => terms
+                .iter()
+                .any(|term| term.is_satisfiable_without(is_denied)),
+        }
+    }
+}
+
+impl Display for LicenseExpression {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LicenseExpression::License {
+                id,
+                or_later,
+                exception,
+            } => {
+                write!(f, "{}", id)?;
+                if *or_later {
+                    write!(f, "+")?;
+                }
+                if let Some(exception) = exception {
+                    write!(f, " WITH {}", exception)?;
+                }
+                Ok(())
+            }
+            LicenseExpression::And(terms) => {
+                for (i, term) in terms.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " AND ")?;
+                    }
+                    // OR binds looser than AND, its sub-expressions must be kept together
+                    match term {
+                        LicenseExpression::Or(_) => write!(f, "({})", term)?,
+                        _ => write!(f, "{}", term)?,
+                    }
+                }
+                Ok(())
+            }
+            LicenseExpression::Or(terms) => {
+                for (i, term) in terms.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " OR ")?;
+                    }
+                    write!(f, "{}", term)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+struct LicenseExpressionVisitor;
+
+impl<'de> Visitor<'de> for LicenseExpressionVisitor {
+    type Value = LicenseExpression;
+
+    #[inline]
+    fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("an SPDX license expression")
+    }
+
+    #[inline]
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        LicenseExpression::parse(value)
+            .map_err(|_| E::custom("the license isn't a valid SPDX license expression"))
+    }
+}
+
+impl_serde_visitor!(LicenseExpression, LicenseExpressionVisitor);
@@ -1,24 +1,26 @@
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt::{self, Display, Formatter};
-use std::fs::File;
+use std::io::{self, Read};
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use chrono::{DateTime, Utc};
-use failure::{Error, ResultExt};
+use flate2::read::GzDecoder;
 use lazy_static::lazy_static;
 use regex::Regex;
 use semver::Version;
 use serde::de::Visitor;
 use serde_derive::{Deserialize, Serialize};
+use url::Url;
+use url_serde::SerdeUrl;
 
-use super::error::SlotParseError;
-use super::Metadata;
+use super::error::{ArchitectureParseError, ManifestVersionParseError, SlotParseError};
 use super::{
     CategoryName, PackageFullName, PackageID, PackageName, PackageRequirement, PackageShortName,
     RepositoryName,
 };
+use super::{ChangelogEntry, Checksum, Metadata, SegmentChecksums};
 
 /// A manifest that aggregates all versions of a package in one, compact structure.
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
@@ -49,14 +51,6 @@ impl PackageManifest {
         }
     }
 
-    #[inline]
-    pub(crate) fn load_from_cache<P: AsRef<Path>>(cache_path: P) -> Result<Self, Error> {
-        let file =
-            File::open(cache_path.as_ref()).context(cache_path.as_ref().display().to_string())?;
-
-        Ok(serde_json::from_reader(&file).context(cache_path.as_ref().display().to_string())?)
-    }
-
     /// Returns a reference over the name of the package
     #[inline]
     pub fn name(&self) -> &PackageName {
@@ -162,8 +156,13 @@ impl PackageManifest {
 }
 
 /// A manifest that represent a unique package and its metadata.
+///
+/// The version of its format is given by its `manifest-version` field. Fields this version of Nest doesn't know
+/// about are ignored, so that manifests of a more recent minor version can still be read.
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub struct Manifest {
+    #[serde(rename = "manifest-version", default)]
+    manifest_version: ManifestVersion,
     name: PackageName,
     category: CategoryName,
     version: Version,
@@ -172,8 +171,26 @@ pub struct Manifest {
     slot: Slot,
     #[serde(default)]
     kind: Kind,
+    #[serde(default)]
+    architecture: Architecture,
+    #[serde(default)]
+    compression: Compression,
     wrap_date: DateTime<Utc>,
     dependencies: HashSet<PackageRequirement>,
+    #[serde(default)]
+    optional_dependencies: HashSet<PackageRequirement>,
+    #[serde(default)]
+    suggests: HashSet<PackageRequirement>,
+    #[serde(default)]
+    provides: HashSet<PackageShortName>,
+    #[serde(default)]
+    conflicts: HashSet<PackageRequirement>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    backup: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<Alternative>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    installed_size: Option<u64>,
 }
 
 impl Manifest {
@@ -187,17 +204,33 @@ impl Manifest {
         version_data: VersionData,
     ) -> Self {
         Self {
+            manifest_version: MANIFEST_VERSION,
             name,
             category,
             version,
             metadata,
             slot: version_data.slot,
             kind: version_data.kind,
+            architecture: version_data.architecture,
+            compression: Compression::default(),
             wrap_date: version_data.wrap_date,
             dependencies: version_data.dependencies,
+            optional_dependencies: version_data.optional_dependencies,
+            suggests: version_data.suggests,
+            provides: version_data.provides,
+            conflicts: version_data.conflicts,
+            backup: version_data.backup,
+            alternatives: version_data.alternatives,
+            installed_size: version_data.installed_size,
         }
     }
 
+    /// Returns the version of the format of the manifest
+    #[inline]
+    pub fn manifest_version(&self) -> ManifestVersion {
+        self.manifest_version
+    }
+
     /// Returns a reference over the name of the package
     #[inline]
     pub fn name(&self) -> &PackageName {
@@ -258,6 +291,30 @@ impl Manifest {
         &mut self.kind
     }
 
+    /// Returns a reference over the architecture the package is built for
+    #[inline]
+    pub fn architecture(&self) -> &Architecture {
+        &self.architecture
+    }
+
+    /// Returns a mutable reference over the architecture the package is built for
+    #[inline]
+    pub fn architecture_mut(&mut self) -> &mut Architecture {
+        &mut self.architecture
+    }
+
+    /// Returns the compression of the package's data archive
+    #[inline]
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Returns a mutable reference over the compression of the package's data archive
+    #[inline]
+    pub fn compression_mut(&mut self) -> &mut Compression {
+        &mut self.compression
+    }
+
     /// Returns a reference over the metadata of the package
     #[inline]
     pub fn metadata(&self) -> &Metadata {
@@ -294,11 +351,128 @@ impl Manifest {
         &mut self.dependencies
     }
 
+    /// Returns a reference over the package's optional dependencies.
+    ///
+    /// Optional dependencies extend the package's features but aren't installed along with it.
+    #[inline]
+    pub fn optional_dependencies(&self) -> &HashSet<PackageRequirement> {
+        &self.optional_dependencies
+    }
+
+    /// Returns a mutable reference over the package's optional dependencies
+    #[inline]
+    pub fn optional_dependencies_mut(&mut self) -> &mut HashSet<PackageRequirement> {
+        &mut self.optional_dependencies
+    }
+
+    /// Returns a reference over the packages suggested along with the package.
+    ///
+    /// Suggested packages are loosely related to the package, and are never installed along with it.
+    #[inline]
+    pub fn suggests(&self) -> &HashSet<PackageRequirement> {
+        &self.suggests
+    }
+
+    /// Returns a mutable reference over the packages suggested along with the package
+    #[inline]
+    pub fn suggests_mut(&mut self) -> &mut HashSet<PackageRequirement> {
+        &mut self.suggests
+    }
+
+    /// Returns a reference over the virtual packages provided by the package.
+    ///
+    /// A requirement on a virtual package, like `virtual/ssh-server`, is fulfilled by any package providing it.
+    #[inline]
+    pub fn provides(&self) -> &HashSet<PackageShortName> {
+        &self.provides
+    }
+
+    /// Returns a mutable reference over the virtual packages provided by the package
+    #[inline]
+    pub fn provides_mut(&mut self) -> &mut HashSet<PackageShortName> {
+        &mut self.provides
+    }
+
+    /// Returns a reference over the packages that can't be installed along with the package
+    #[inline]
+    pub fn conflicts(&self) -> &HashSet<PackageRequirement> {
+        &self.conflicts
+    }
+
+    /// Returns a mutable reference over the packages that can't be installed along with the package
+    #[inline]
+    pub fn conflicts_mut(&mut self) -> &mut HashSet<PackageRequirement> {
+        &mut self.conflicts
+    }
+
+    /// Returns a reference over the configuration files of the package, as absolute paths relative to the
+    /// installation root, on top of the files in `/etc`.
+    ///
+    /// Configuration files modified locally are kept when the package is upgraded.
+    #[inline]
+    pub fn backup(&self) -> &[PathBuf] {
+        &self.backup
+    }
+
+    /// Returns a mutable reference over the configuration files of the package
+    #[inline]
+    pub fn backup_mut(&mut self) -> &mut Vec<PathBuf> {
+        &mut self.backup
+    }
+
+    /// Returns a reference over the alternatives the package offers a target for, like `/usr/bin/cc`
+    #[inline]
+    pub fn alternatives(&self) -> &[Alternative] {
+        &self.alternatives
+    }
+
+    /// Returns a mutable reference over the alternatives the package offers a target for
+    #[inline]
+    pub fn alternatives_mut(&mut self) -> &mut Vec<Alternative> {
+        &mut self.alternatives
+    }
+
+    /// Returns the total size, in bytes, of the files of the package once installed, if it is known
+    #[inline]
+    pub fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+
+    /// Returns a mutable reference over the total size, in bytes, of the files of the package once installed
+    #[inline]
+    pub fn installed_size_mut(&mut self) -> &mut Option<u64> {
+        &mut self.installed_size
+    }
+
     /// Generates the [`PackageShortName`] of this package
     pub fn short_name(&self) -> PackageShortName {
         PackageShortName::from(self.category().clone(), self.name().clone())
     }
 
+    /// Generates the [`VersionData`] describing this version of the package in a [`PackageManifest`].
+    ///
+    /// The checksum of the NPF is left unknown.
+    pub fn version_data(&self) -> VersionData {
+        VersionData {
+            slot: self.slot.clone(),
+            kind: self.kind,
+            architecture: self.architecture.clone(),
+            wrap_date: self.wrap_date,
+            dependencies: self.dependencies.clone(),
+            optional_dependencies: self.optional_dependencies.clone(),
+            suggests: self.suggests.clone(),
+            provides: self.provides.clone(),
+            conflicts: self.conflicts.clone(),
+            backup: self.backup.clone(),
+            alternatives: self.alternatives.clone(),
+            installed_size: self.installed_size,
+            checksum: None,
+            changelog: Vec::new(),
+            torrent: None,
+            segments: None,
+        }
+    }
+
     /// Generates the [`PackageID`] of this package given its missing piece: the [`RepositoryName`].
     #[inline]
     pub fn id(&self, repository_name: RepositoryName) -> PackageID {
@@ -328,12 +502,38 @@ pub struct VersionData {
     slot: Slot,
     #[serde(default)]
     kind: Kind,
+    #[serde(default)]
+    architecture: Architecture,
     wrap_date: DateTime<Utc>,
     dependencies: HashSet<PackageRequirement>,
+    #[serde(default)]
+    optional_dependencies: HashSet<PackageRequirement>,
+    #[serde(default)]
+    suggests: HashSet<PackageRequirement>,
+    #[serde(default)]
+    provides: HashSet<PackageShortName>,
+    #[serde(default)]
+    conflicts: HashSet<PackageRequirement>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    backup: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<Alternative>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    installed_size: Option<u64>,
+    #[serde(default)]
+    checksum: Option<Checksum>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    changelog: Vec<ChangelogEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    torrent: Option<SerdeUrl>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    segments: Option<SegmentChecksums>,
 }
 
 impl VersionData {
     /// Creates a new [`VersionData`] from a wrap date and a list of dependencies.
+    ///
+    /// The checksum of the NPF is left unknown.
     #[inline]
     pub fn from(
         slot: Slot,
@@ -344,8 +544,20 @@ impl VersionData {
         Self {
             slot,
             kind,
+            architecture: Architecture::default(),
             wrap_date,
             dependencies,
+            optional_dependencies: HashSet::new(),
+            suggests: HashSet::new(),
+            provides: HashSet::new(),
+            conflicts: HashSet::new(),
+            backup: Vec::new(),
+            alternatives: Vec::new(),
+            installed_size: None,
+            checksum: None,
+            changelog: Vec::new(),
+            torrent: None,
+            segments: None,
         }
     }
 
@@ -373,6 +585,18 @@ impl VersionData {
         &mut self.kind
     }
 
+    /// Returns a reference over the architecture the package is built for
+    #[inline]
+    pub fn architecture(&self) -> &Architecture {
+        &self.architecture
+    }
+
+    /// Returns a mutable reference over the architecture the package is built for
+    #[inline]
+    pub fn architecture_mut(&mut self) -> &mut Architecture {
+        &mut self.architecture
+    }
+
     /// Returns a reference over the wrap date of the package
     #[inline]
     pub fn wrap_date(&self) -> &DateTime<Utc> {
@@ -396,6 +620,149 @@ impl VersionData {
     pub fn dependencies_mut(&mut self) -> &mut HashSet<PackageRequirement> {
         &mut self.dependencies
     }
+
+    /// Returns a reference over the package's optional dependencies.
+    ///
+    /// Optional dependencies extend the package's features but aren't installed along with it.
+    #[inline]
+    pub fn optional_dependencies(&self) -> &HashSet<PackageRequirement> {
+        &self.optional_dependencies
+    }
+
+    /// Returns a mutable reference over the package's optional dependencies
+    #[inline]
+    pub fn optional_dependencies_mut(&mut self) -> &mut HashSet<PackageRequirement> {
+        &mut self.optional_dependencies
+    }
+
+    /// Returns a reference over the packages suggested along with the package.
+    ///
+    /// Suggested packages are loosely related to the package, and are never installed along with it.
+    #[inline]
+    pub fn suggests(&self) -> &HashSet<PackageRequirement> {
+        &self.suggests
+    }
+
+    /// Returns a mutable reference over the packages suggested along with the package
+    #[inline]
+    pub fn suggests_mut(&mut self) -> &mut HashSet<PackageRequirement> {
+        &mut self.suggests
+    }
+
+    /// Returns a reference over the virtual packages provided by the package.
+    ///
+    /// A requirement on a virtual package, like `virtual/ssh-server`, is fulfilled by any package providing it.
+    #[inline]
+    pub fn provides(&self) -> &HashSet<PackageShortName> {
+        &self.provides
+    }
+
+    /// Returns a mutable reference over the virtual packages provided by the package
+    #[inline]
+    pub fn provides_mut(&mut self) -> &mut HashSet<PackageShortName> {
+        &mut self.provides
+    }
+
+    /// Returns a reference over the packages that can't be installed along with the package
+    #[inline]
+    pub fn conflicts(&self) -> &HashSet<PackageRequirement> {
+        &self.conflicts
+    }
+
+    /// Returns a mutable reference over the packages that can't be installed along with the package
+    #[inline]
+    pub fn conflicts_mut(&mut self) -> &mut HashSet<PackageRequirement> {
+        &mut self.conflicts
+    }
+
+    /// Returns a reference over the configuration files of the package, as absolute paths relative to the
+    /// installation root, on top of the files in `/etc`.
+    ///
+    /// Configuration files modified locally are kept when the package is upgraded.
+    #[inline]
+    pub fn backup(&self) -> &[PathBuf] {
+        &self.backup
+    }
+
+    /// Returns a mutable reference over the configuration files of the package
+    #[inline]
+    pub fn backup_mut(&mut self) -> &mut Vec<PathBuf> {
+        &mut self.backup
+    }
+
+    /// Returns a reference over the alternatives the package offers a target for, like `/usr/bin/cc`
+    #[inline]
+    pub fn alternatives(&self) -> &[Alternative] {
+        &self.alternatives
+    }
+
+    /// Returns a mutable reference over the alternatives the package offers a target for
+    #[inline]
+    pub fn alternatives_mut(&mut self) -> &mut Vec<Alternative> {
+        &mut self.alternatives
+    }
+
+    /// Returns the total size, in bytes, of the files of the package once installed, if it is known
+    #[inline]
+    pub fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+
+    /// Returns a mutable reference over the total size, in bytes, of the files of the package once installed
+    #[inline]
+    pub fn installed_size_mut(&mut self) -> &mut Option<u64> {
+        &mut self.installed_size
+    }
+
+    /// Returns a reference over the checksum of the package's NPF, if it is known
+    #[inline]
+    pub fn checksum(&self) -> &Option<Checksum> {
+        &self.checksum
+    }
+
+    /// Returns a mutable reference over the checksum of the package's NPF
+    #[inline]
+    pub fn checksum_mut(&mut self) -> &mut Option<Checksum> {
+        &mut self.checksum
+    }
+
+    /// Returns the most recent entries of the package's changelog, as indexed by its repository, the most recent
+    /// version first
+    #[inline]
+    pub fn changelog(&self) -> &[ChangelogEntry] {
+        &self.changelog
+    }
+
+    /// Returns a mutable reference over the most recent entries of the package's changelog
+    #[inline]
+    pub fn changelog_mut(&mut self) -> &mut Vec<ChangelogEntry> {
+        &mut self.changelog
+    }
+
+    /// Returns the magnet URI of the torrent the package's NPF can be downloaded with, if the repository seeds it
+    #[inline]
+    pub fn torrent(&self) -> Option<&Url> {
+        self.torrent.as_ref().map(|torrent| &**torrent)
+    }
+
+    /// Returns a mutable reference over the magnet URI of the torrent the package's NPF can be downloaded with
+    #[inline]
+    pub fn torrent_mut(&mut self) -> &mut Option<SerdeUrl> {
+        &mut self.torrent
+    }
+
+    /// Returns the checksums of the segments of the package's NPF, if the repository advertises them so that its
+    /// segments can be downloaded from several mirrors at once
+    #[inline]
+    pub fn segments(&self) -> Option<&SegmentChecksums> {
+        self.segments.as_ref()
+    }
+
+    /// Returns a mutable reference over the checksums of the segments of the package's NPF
+    #[inline]
+    pub fn segments_mut(&mut self) -> &mut Option<SegmentChecksums> {
+        &mut self.segments
+    }
 }
 
 /// A package's kind.
@@ -423,6 +790,279 @@ impl Default for Kind {
     }
 }
 
+/// The architecture a package is built for, like `x86_64` or `aarch64`.
+///
+/// Packages that don't depend on the architecture, like those holding only scripts or data, are built for the
+/// `any` architecture, which is also the architecture of the packages whose manifest doesn't specify one.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Architecture(String);
+
+impl Architecture {
+    /// Parses the string representation of an [`Architecture`].
+    pub fn parse(repr: &str) -> Result<Self, ArchitectureParseError> {
+        Self::try_from(repr)
+    }
+
+    /// Returns the architecture of the packages that can be installed on any architecture
+    #[inline]
+    pub fn any() -> Self {
+        Self(String::from("any"))
+    }
+
+    /// Returns the architecture of the host, as detected when Nest was compiled
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// use libnest::package::Architecture;
+    ///
+    /// assert_eq!(Architecture::host().as_ref(), std::env::consts::ARCH);
+    /// assert!(!Architecture::host().is_any());
+    /// ```
+    #[inline]
+    pub fn host() -> Self {
+        Self(String::from(std::env::consts::ARCH))
+    }
+
+    /// Returns whether packages built for this architecture can be installed on any architecture
+    #[inline]
+    pub fn is_any(&self) -> bool {
+        self.0 == "any"
+    }
+}
+
+strong_name_impl!(Architecture, r"^[a-z0-9_\-]+$", ArchitectureParseError);
+
+impl Default for Architecture {
+    fn default() -> Self {
+        Self::any()
+    }
+}
+
+struct ArchitectureVisitor;
+
+impl<'de> Visitor<'de> for ArchitectureVisitor {
+    type Value = Architecture;
+
+    #[inline]
+    fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("an architecture")
+    }
+
+    #[inline]
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Architecture::parse(value).map_err(|_| E::custom("the architecture isn't valid"))
+    }
+}
+
+impl_serde_visitor!(Architecture, ArchitectureVisitor);
+
+/// The compression of the data archive of an NPF.
+///
+/// The archive is named after its compression, `data.tar.gz` for gzip and `data.tar.zst` for zstd.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
+pub enum Compression {
+    #[serde(rename = "gzip")]
+    /// The data archive is compressed with gzip.
+    Gzip,
+
+    #[serde(rename = "zstd")]
+    /// The data archive is compressed with zstd.
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the name of the data archive of an NPF using this compression
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// use libnest::package::Compression;
+    ///
+    /// assert_eq!(Compression::Gzip.data_file_name(), "data.tar.gz");
+    /// assert_eq!(Compression::Zstd.data_file_name(), "data.tar.zst");
+    /// ```
+    #[inline]
+    pub fn data_file_name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "data.tar.gz",
+            Compression::Zstd => "data.tar.zst",
+        }
+    }
+
+    /// Wraps the given reader over a compressed data archive into a reader over the uncompressed archive
+    pub fn decoder<'a, R: Read + 'a>(self, reader: R) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(GzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        })
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::Gzip
+    }
+}
+
+/// The version of the format of the manifests written by this version of Nest
+pub const MANIFEST_VERSION: ManifestVersion = ManifestVersion { major: 1, minor: 0 };
+
+/// The version of the format of a [`Manifest`], made of a major and a minor number, like `1.0`.
+///
+/// The minor number is bumped when fields are added, which older versions of Nest ignore, while the major one is
+/// bumped when the meaning of existing fields changes, which older versions of Nest can't be trusted with.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct ManifestVersion {
+    major: u32,
+    minor: u32,
+}
+
+impl ManifestVersion {
+    /// Creates a [`ManifestVersion`] from its major and minor numbers
+    #[inline]
+    pub fn new(major: u32, minor: u32) -> Self {
+        ManifestVersion { major, minor }
+    }
+
+    /// Parses the string representation of a [`ManifestVersion`], like `1.0`.
+    pub fn parse(repr: &str) -> Result<Self, ManifestVersionParseError> {
+        Self::try_from(repr)
+    }
+
+    /// Returns the major number of the version
+    #[inline]
+    pub fn major(self) -> u32 {
+        self.major
+    }
+
+    /// Returns the minor number of the version
+    #[inline]
+    pub fn minor(self) -> u32 {
+        self.minor
+    }
+
+    /// Returns whether manifests of this version can be read by this version of Nest, which they can if they share
+    /// the major number of [`MANIFEST_VERSION`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::package::{ManifestVersion, MANIFEST_VERSION};
+    ///
+    /// assert!(MANIFEST_VERSION.is_supported());
+    /// assert!(ManifestVersion::new(MANIFEST_VERSION.major(), MANIFEST_VERSION.minor() + 1).is_supported());
+    /// assert!(!ManifestVersion::new(MANIFEST_VERSION.major() + 1, 0).is_supported());
+    /// assert_eq!(ManifestVersion::parse("1.2")?, ManifestVersion::new(1, 2));
+    /// assert!(ManifestVersion::parse("1").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn is_supported(self) -> bool {
+        self.major == MANIFEST_VERSION.major
+    }
+}
+
+impl Default for ManifestVersion {
+    /// Manifests lacking a version predate versioning, and follow the first version of the format
+    fn default() -> Self {
+        ManifestVersion::new(1, 0)
+    }
+}
+
+impl Display for ManifestVersion {
+    #[inline]
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl TryFrom<&str> for ManifestVersion {
+    type Error = ManifestVersionParseError;
+
+    fn try_from(repr: &str) -> Result<Self, Self::Error> {
+        let mut numbers = repr.splitn(2, '.').map(str::parse);
+
+        match (numbers.next(), numbers.next()) {
+            (Some(Ok(major)), Some(Ok(minor))) => Ok(ManifestVersion::new(major, minor)),
+            _ => Err(ManifestVersionParseError(repr.to_string())),
+        }
+    }
+}
+
+struct ManifestVersionVisitor;
+
+impl<'de> Visitor<'de> for ManifestVersionVisitor {
+    type Value = ManifestVersion;
+
+    #[inline]
+    fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("a manifest version")
+    }
+
+    #[inline]
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        ManifestVersion::try_from(value).map_err(|_| E::custom("the manifest version isn't valid"))
+    }
+}
+
+impl_serde_visitor!(ManifestVersion, ManifestVersionVisitor);
+
+/// A candidate offered by a package for a link shared by several packages, like `/usr/bin/cc`.
+///
+/// The link points to the target of the candidate with the highest priority among the installed packages, unless
+/// another one was selected by the user (see [`Alternatives`](crate::cache::alternatives::Alternatives)). Both
+/// paths are absolute paths relative to the installation root.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Alternative {
+    link: PathBuf,
+    target: PathBuf,
+    #[serde(default)]
+    priority: i32,
+}
+
+impl Alternative {
+    /// Creates an [`Alternative`] offering the given target for the given link, with the given priority
+    #[inline]
+    pub fn new(link: PathBuf, target: PathBuf, priority: i32) -> Self {
+        Self {
+            link,
+            target,
+            priority,
+        }
+    }
+
+    /// Returns the path of the shared link
+    #[inline]
+    pub fn link(&self) -> &Path {
+        &self.link
+    }
+
+    /// Returns the path the link points to when this candidate is used
+    #[inline]
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Returns the priority of this candidate, the highest one being used by default
+    #[inline]
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+}
+
 /// A version's slot.
 ///
 /// A slot is an identifier shared by multiple versions to show that they are not compatible with
@@ -436,6 +1076,12 @@ impl Slot {
     pub fn parse(repr: &str) -> Result<Self, SlotParseError> {
         Self::try_from(repr)
     }
+
+    /// Returns whether this is the default slot, the one of packages that don't declare any
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl Display for Slot {
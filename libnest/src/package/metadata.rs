@@ -7,6 +7,7 @@ use serde_derive::{Deserialize, Serialize};
 use url_serde::SerdeUrl;
 
 use super::error::{LicenseParseError, TagParseError};
+use super::license::LicenseExpression;
 
 /// A package's metadata, like its description, tags, maintainer etc.
 #[derive(Default, Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
@@ -15,6 +16,8 @@ pub struct Metadata {
     tags: Vec<Tag>,
     maintainer: Maintainer,
     licenses: Vec<License>,
+    #[serde(default)]
+    license: Option<LicenseExpression>,
     upstream_url: Option<UpstreamURL>,
 }
 
@@ -59,6 +62,16 @@ impl Metadata {
         &mut self.licenses
     }
 
+    /// Returns a reference over the SPDX license expression of the package, if it has one
+    pub fn license(&self) -> &Option<LicenseExpression> {
+        &self.license
+    }
+
+    /// Returns a mutable reference over the SPDX license expression of the package
+    pub fn license_mut(&mut self) -> &mut Option<LicenseExpression> {
+        &mut self.license
+    }
+
     /// Returns a reference over the upstream_url of the package
     pub fn upstream_url(&self) -> &Option<UpstreamURL> {
         &self.upstream_url
@@ -68,25 +68,44 @@ macro_rules! strong_name_impl {
     };
 }
 
+mod builder;
+mod changelog;
+mod checksum;
+mod delta;
 mod error;
 mod identification;
+mod license;
 mod manifest;
 mod metadata;
 mod npf;
 mod requirement;
+mod seekable;
+mod signature;
 
+pub use builder::NPFBuilder;
+pub use changelog::{Changelog, ChangelogEntry};
+pub use checksum::{Checksum, SegmentChecksums};
+pub use delta::NPFDelta;
 pub use error::*;
 pub use identification::{
     CategoryName, PackageFullName, PackageID, PackageName, PackageShortName, RepositoryName,
 };
-pub use manifest::{Kind, Manifest, PackageManifest, VersionData};
+pub use license::LicenseExpression;
+pub use manifest::{
+    Alternative, Architecture, Compression, Kind, Manifest, ManifestVersion, PackageManifest, Slot,
+    VersionData, MANIFEST_VERSION,
+};
 pub use metadata::{License, Maintainer, Metadata, Tag, UpstreamURL};
 pub use npf::{NPFExplorer, NPFFile};
 pub use requirement::{HardPackageRequirement, PackageRequirement, SoftPackageRequirement};
+pub use signature::{SigningKey, TrustedKey};
+
+pub(crate) use requirement::split_slot;
+pub(crate) use seekable::{read_seek_table, Frame};
 
 lazy_static::lazy_static! {
     /// A regular expression to match and parse a package's string representation
-    static ref REGEX_PACKAGE_ID: regex::Regex = regex::Regex::new(
+    pub(crate) static ref REGEX_PACKAGE_ID: regex::Regex = regex::Regex::new(
         r"^(?:(?P<repository>[^:/#]+)::)?(?:(?P<category>[^:/#]+)/)?(?P<package>[^:/#]+)(?:#(?P<version>.+))?$"
     ).unwrap();
 }
@@ -1,43 +1,184 @@
+use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::Read;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::marker::PhantomData;
-use std::path::{Path, PathBuf};
+use std::mem;
+use std::path::{Component, Path, PathBuf};
+use std::process;
 
+use log::{debug, warn};
 use tar::Archive;
 use toml;
 
+use super::changelog::Changelog;
 use super::error::{NPFExplorationError, NPFExplorationErrorKind};
-use super::manifest::{Kind::Effective, Manifest};
+use super::manifest::{Kind::Effective, Manifest, ManifestVersion};
+use super::signature::{self, TrustedKey, SIGNATURE_FILE};
+use super::Checksum;
+use crate::config::ConfigPaths;
 use crate::transaction::InstructionsExecutor;
 
-/// Structure representing a handle over a file contained in an NPF
+/// Structure representing a handle over a file contained in an NPF, opened for reading.
+///
+/// The content of the file is read through the [`Read`] and [`Seek`] implementations of the handle,
+/// as the file may be a section of the NPF itself.
 #[derive(Debug)]
 pub struct NPFFile<'explorer> {
     file: File,
+    start: u64,
+    len: u64,
+    pos: u64,
     phantom: PhantomData<&'explorer NPFExplorer>,
 }
 
 impl<'explorer> NPFFile<'explorer> {
-    pub(crate) fn from(file: File, phantom: PhantomData<&'explorer NPFExplorer>) -> Self {
-        Self { file, phantom }
+    pub(crate) fn from(
+        mut file: File,
+        start: u64,
+        len: u64,
+        phantom: PhantomData<&'explorer NPFExplorer>,
+    ) -> io::Result<Self> {
+        file.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            file,
+            start,
+            len,
+            pos: 0,
+            phantom,
+        })
     }
 
-    /// Retrieves the file associated with this handle, opened for reading
-    pub fn file(&self) -> &File {
-        &self.file
+    /// Returns the size of the file, in bytes
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
     }
 
-    /// Retrieves the file associated with this handle, opened for reading
-    pub fn file_mut(&mut self) -> &mut File {
-        &mut self.file
+    /// Returns whether the file is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 }
 
+impl<'explorer> Read for NPFFile<'explorer> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        let max = (buf.len() as u64).min(remaining) as usize;
+        let read = self.file.read(&mut buf[..max])?;
+
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<'explorer> Seek for NPFFile<'explorer> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => offset_by(self.len, offset),
+            SeekFrom::Current(offset) => offset_by(self.pos, offset),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        self.file.seek(SeekFrom::Start(self.start + pos))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+fn offset_by(base: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.wrapping_neg() as u64)
+    }
+}
+
+/// The prefix of the names of the temporary directories NPFs are unpacked in.
+///
+/// It is followed by the PID of the process that unpacked the NPF, so that the directories left behind by a crashed
+/// process can be told apart from those still in use.
+const UNPACKED_DIR_PREFIX: &str = "nest_";
+
+/// Returns the PID of the process owning the given temporary directory, if it is one of those NPFs are unpacked in
+fn unpacked_dir_owner(path: &Path) -> Option<i32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(UNPACKED_DIR_PREFIX)?
+        .split('_')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Returns whether the process of the given PID is still running
+fn is_running(pid: i32) -> bool {
+    let res = unsafe { libc::kill(pid, 0) };
+
+    // Processes of other users can't be signaled, but are running all the same
+    res == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// The disk-backed folder, relative to the root path, NPFs are unpacked in when the scratch folder lacks room for them
+const DISK_SCRATCH_DIR: &str = "/var/tmp/nest/";
+
+/// Returns the space available in the given directory, in bytes, and whether it is on a tmpfs
+fn available_space(dir: &Path) -> io::Result<(u64, bool)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut vfs: libc::statvfs = unsafe { mem::zeroed() };
+    let mut fs: libc::statfs = unsafe { mem::zeroed() };
+
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut vfs) } != 0
+        || unsafe { libc::statfs(c_path.as_ptr(), &mut fs) } != 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((
+        vfs.f_bavail as u64 * vfs.f_frsize as u64,
+        fs.f_type as i64 == libc::TMPFS_MAGIC,
+    ))
+}
+
+/// Returns whether the given directory has room for an NPF of the given size to be unpacked in it
+fn has_room_for(dir: &Path, npf_size: u64) -> bool {
+    let available = fs::create_dir_all(dir).and_then(|_| available_space(dir));
+
+    match available {
+        // A tmpfs takes its space from the memory, so at least half of what's left of it is kept free
+        Ok((available, true)) => npf_size.saturating_mul(2) <= available,
+        Ok((available, false)) => npf_size <= available,
+        Err(_) => false,
+    }
+}
+
+/// Where the files of an explored NPF are read from
+#[derive(Debug)]
+enum NPFSource {
+    /// The NPF was unpacked in a temporary directory, removed once the exploration is over
+    Unpacked(PathBuf),
+
+    /// The NPF is read in place, each file being a section of the archive
+    Archive {
+        path: PathBuf,
+        sections: HashMap<PathBuf, (u64, u64)>,
+    },
+}
+
 /// Structure representing an NPF to allow interacting with it
 #[derive(Debug)]
 pub struct NPFExplorer {
     manifest: Manifest,
-    path: PathBuf,
+    source: NPFSource,
 }
 
 impl NPFExplorer {
@@ -51,9 +192,32 @@ impl NPFExplorer {
         file.read_to_string(&mut content)
             .map_err(|_| NPFExplorationErrorKind::FileIOError(path.to_path_buf()))?;
 
-        Ok(toml::from_str(&content).map_err(|_| NPFExplorationErrorKind::InvalidManifest)?)
+        Self::parse_manifest(&content)
     }
 
+    fn parse_manifest(content: &str) -> Result<Manifest, NPFExplorationError> {
+        let value: toml::Value =
+            toml::from_str(content).map_err(|_| NPFExplorationErrorKind::InvalidManifest)?;
+
+        // The version is checked first, as manifests of another major version may not even be parsed
+        let version = match value.get("manifest-version") {
+            Some(version) => version
+                .as_str()
+                .and_then(|version| ManifestVersion::parse(version).ok())
+                .ok_or(NPFExplorationErrorKind::InvalidManifest)?,
+            None => ManifestVersion::default(),
+        };
+        if !version.is_supported() {
+            return Err(NPFExplorationErrorKind::UnsupportedManifestVersion(version).into());
+        }
+
+        Ok(value
+            .try_into()
+            .map_err(|_| NPFExplorationErrorKind::InvalidManifest)?)
+    }
+
+    /// Generates the path of a temporary directory to unpack an NPF in, inside the given directory and named
+    /// after the current process
     fn gen_tmp_filename<P: AsRef<Path>>(base_dir: P) -> PathBuf {
         use rand::distributions::Alphanumeric;
         use rand::{thread_rng, Rng};
@@ -65,15 +229,119 @@ impl NPFExplorer {
             .take(10)
             .collect();
 
-        base_dir.as_ref().join(&format!("nest_{}", name))
+        base_dir.as_ref().join(&format!(
+            "{}{}_{}",
+            UNPACKED_DIR_PREFIX,
+            process::id(),
+            name
+        ))
+    }
+
+    /// Removes the temporary directories left in the given directory by the processes that unpacked NPFs in it
+    /// and were killed before removing them, returning how many were removed.
+    ///
+    /// This is done each time an NPF is unpacked with [`NPFExplorer::open_at()`], and can be done on startup by
+    /// the programs using it.
+    pub fn purge_abandoned_dirs<P: AsRef<Path>>(extract_dir: P) -> io::Result<usize> {
+        let extract_dir = extract_dir.as_ref();
+        let mut purged = 0;
+
+        if !extract_dir.exists() {
+            return Ok(0);
+        }
+
+        for entry in fs::read_dir(extract_dir)? {
+            let path = entry?.path();
+
+            match unpacked_dir_owner(&path) {
+                Some(pid) if !is_running(pid) => {
+                    debug!("removing the abandoned extracted NPF {}", path.display());
+                    fs::remove_dir_all(&path)?;
+                    purged += 1;
+                }
+                _ => (),
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Removes the temporary directory the NPF was unpacked in, if any
+    fn remove_unpacked_dir(&mut self) -> io::Result<()> {
+        // The source is replaced by an empty one, so that the directory isn't removed again on drop
+        let source = mem::replace(
+            &mut self.source,
+            NPFSource::Archive {
+                path: PathBuf::new(),
+                sections: HashMap::new(),
+            },
+        );
+
+        match source {
+            NPFSource::Unpacked(path) => fs::remove_dir_all(path),
+            NPFSource::Archive { .. } => Ok(()),
+        }
+    }
+
+    /// Ends the exploration of the NPF, removing the temporary directory it was unpacked in, if any.
+    ///
+    /// Unlike dropping the explorer, which only logs the failures to remove the directory, this reports them.
+    pub fn close(mut self) -> Result<(), NPFExplorationError> {
+        if let NPFSource::Unpacked(path) = &self.source {
+            let path = path.clone();
+            self.remove_unpacked_dir()
+                .map_err(|_| NPFExplorationErrorKind::CleanupError(path))?;
+        }
+        Ok(())
+    }
+
+    fn verify_signature(
+        path: &Path,
+        trusted_keys: &[TrustedKey],
+    ) -> Result<(), NPFExplorationError> {
+        let mut signature = Vec::new();
+
+        File::open(path.join(SIGNATURE_FILE))
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => NPFExplorationErrorKind::MissingSignature,
+                _ => NPFExplorationErrorKind::FileIOError(PathBuf::from(SIGNATURE_FILE)),
+            })?
+            .read_to_end(&mut signature)
+            .map_err(|_| NPFExplorationErrorKind::FileIOError(PathBuf::from(SIGNATURE_FILE)))?;
+
+        let message = signature::signed_message(path)
+            .map_err(|_| NPFExplorationErrorKind::FileIOError(path.to_path_buf()))?;
+
+        Self::check_signature(&message, &signature, trusted_keys)
+    }
+
+    fn check_signature(
+        message: &[u8],
+        signature: &[u8],
+        trusted_keys: &[TrustedKey],
+    ) -> Result<(), NPFExplorationError> {
+        if trusted_keys
+            .iter()
+            .any(|key| key.verify(message, signature))
+        {
+            Ok(())
+        } else {
+            Err(NPFExplorationErrorKind::InvalidSignature.into())
+        }
     }
 
     /// Create an NPFExplorer from a path to an NPF archive and the path to the directory in which
-    /// it should be extracted
+    /// it should be extracted.
+    ///
+    /// If `trusted_keys` is not [`None`], the NPF must be signed by one of the given keys.
     pub fn open_at<P: AsRef<Path>, Q: AsRef<Path>>(
         npf_path: P,
         extract_dir: Q,
+        trusted_keys: Option<&[TrustedKey]>,
     ) -> Result<Self, NPFExplorationError> {
+        // The directories left behind by crashed processes are removed first, as nothing else would remove them
+        if let Err(e) = Self::purge_abandoned_dirs(&extract_dir) {
+            warn!("unable to remove the abandoned extracted NPFs: {}", e);
+        }
         let path = Self::gen_tmp_filename(extract_dir);
 
         // Create a directory to extract the NPF
@@ -87,27 +355,165 @@ impl NPFExplorer {
             })
             .map_err(|_| NPFExplorationErrorKind::UnpackError)?;
 
-        let manifest = Self::load_manifest(&path)?;
+        if let Some(trusted_keys) = trusted_keys {
+            if let Err(e) = Self::verify_signature(&path, trusted_keys) {
+                let _ = fs::remove_dir_all(&path);
+                return Err(e);
+            }
+        }
+
+        let manifest = match Self::load_manifest(&path) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&path);
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            manifest,
+            source: NPFSource::Unpacked(path),
+        })
+    }
+
+    /// Create an NPFExplorer reading the files of an NPF archive in place, without unpacking it.
+    ///
+    /// The archive is read once to locate its files, load its manifest and, if `trusted_keys` is not [`None`],
+    /// verify that it is signed by one of the given keys. The files are then read directly from the archive.
+    pub fn open_streaming<P: AsRef<Path>>(
+        npf_path: P,
+        trusted_keys: Option<&[TrustedKey]>,
+    ) -> Result<Self, NPFExplorationError> {
+        let npf_path = npf_path.as_ref();
+        let mut sections = HashMap::new();
+        let mut digests = HashMap::new();
+        let mut manifest = None;
+        let mut signature = None;
+
+        let res: Result<_, io::Error> = try {
+            let file = File::open(npf_path)?;
+            let mut archive = Archive::new(&file);
+
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+
+                // Names are normalized so that "./manifest.toml" is found as "manifest.toml"
+                let name: PathBuf = entry
+                    .path()?
+                    .components()
+                    .filter(|component| *component != Component::CurDir)
+                    .collect();
+                sections.insert(name.clone(), (entry.raw_file_position(), entry.size()));
+
+                if name == Path::new("manifest.toml") {
+                    let mut content = String::new();
+                    entry.read_to_string(&mut content)?;
+                    digests.insert(name, Checksum::from_reader(&mut content.as_bytes())?);
+                    manifest = Some(content);
+                } else if name == Path::new(SIGNATURE_FILE) {
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content)?;
+                    signature = Some(content);
+                } else if trusted_keys.is_some() {
+                    digests.insert(name, Checksum::from_reader(&mut entry)?);
+                }
+            }
+        };
+        res.map_err(|_| NPFExplorationErrorKind::UnpackError)?;
+
+        if let Some(trusted_keys) = trusted_keys {
+            let signature = signature.ok_or(NPFExplorationErrorKind::MissingSignature)?;
+            let message = signature::signed_message_from(|file_name| {
+                Ok(digests.get(Path::new(file_name)).cloned())
+            })
+            .map_err(|_| NPFExplorationErrorKind::FileIOError(npf_path.to_path_buf()))?;
+
+            Self::check_signature(&message, &signature, trusted_keys)?;
+        }
+
+        let manifest = manifest.ok_or(NPFExplorationErrorKind::MissingManifest)?;
+
+        Ok(Self {
+            manifest: Self::parse_manifest(&manifest)?,
+            source: NPFSource::Archive {
+                path: npf_path.to_path_buf(),
+                sections,
+            },
+        })
+    }
+
+    /// Create an NPFExplorer from a path to an NPF archive, unpacking it in the scratch folder of the given paths.
+    ///
+    /// The NPF is unpacked in a disk-backed folder instead if the scratch folder doesn't have room for it, which
+    /// happens with big packages when it is a tmpfs.
+    ///
+    /// If `trusted_keys` is not [`None`], the NPF must be signed by one of the given keys.
+    pub fn open_in_scratch<P: AsRef<Path>>(
+        npf_path: P,
+        paths: &ConfigPaths,
+        trusted_keys: Option<&[TrustedKey]>,
+    ) -> Result<Self, NPFExplorationError> {
+        use crate::chroot::Chroot;
+
+        let npf_path = npf_path.as_ref();
+        let npf_size = fs::metadata(npf_path)
+            .map_err(|_| NPFExplorationErrorKind::FileIOError(npf_path.to_path_buf()))?
+            .len();
 
-        Ok(Self { path, manifest })
+        if has_room_for(paths.scratch(), npf_size) {
+            Self::open_at(npf_path, paths.scratch(), trusted_keys)
+        } else {
+            let disk_dir = Path::new(DISK_SCRATCH_DIR).with_root(paths.root());
+            debug!(
+                "not enough room in {} to unpack {}, using {} instead",
+                paths.scratch().display(),
+                npf_path.display(),
+                disk_dir.display()
+            );
+            Self::open_at(npf_path, disk_dir, trusted_keys)
+        }
     }
 
-    /// Create an NPFExplorer from a path to an NPF archive
-    pub fn from<P: AsRef<Path>>(npf_path: P) -> Result<Self, NPFExplorationError> {
-        Self::open_at(npf_path, "/var/run/nest/")
+    /// Create an NPFExplorer from a path to an NPF archive, unpacking it in the default scratch folder.
+    ///
+    /// If `trusted_keys` is not [`None`], the NPF must be signed by one of the given keys.
+    pub fn from<P: AsRef<Path>>(
+        npf_path: P,
+        trusted_keys: Option<&[TrustedKey]>,
+    ) -> Result<Self, NPFExplorationError> {
+        Self::open_in_scratch(npf_path, &ConfigPaths::default(), trusted_keys)
     }
 
     /// Retrieves a handle over a file in the NPF
     fn open_file<P: AsRef<Path>>(&self, path: P) -> Result<NPFFile, NPFExplorationError> {
         let path = path.as_ref();
-        let file = File::open(self.path.join(path)).map_err(|err| match err.kind() {
-            std::io::ErrorKind::NotFound => {
-                NPFExplorationErrorKind::FileNotFound(path.to_path_buf())
+        let not_found = || NPFExplorationErrorKind::FileNotFound(path.to_path_buf());
+        let io_error = |_| NPFExplorationErrorKind::FileIOError(path.to_path_buf());
+
+        let file = match &self.source {
+            NPFSource::Unpacked(npf_dir) => {
+                let file = File::open(npf_dir.join(path)).map_err(|err| match err.kind() {
+                    std::io::ErrorKind::NotFound => not_found(),
+                    _ => io_error(err),
+                })?;
+                let len = file.metadata().map_err(io_error)?.len();
+
+                NPFFile::from(file, 0, len, PhantomData)
             }
-            _ => NPFExplorationErrorKind::FileIOError(path.to_path_buf()),
-        })?;
+            NPFSource::Archive {
+                path: npf_path,
+                sections,
+            } => {
+                let (start, len) = *sections.get(path).ok_or_else(not_found)?;
+                let file = File::open(npf_path).map_err(io_error)?;
 
-        Ok(NPFFile::from(file, PhantomData))
+                NPFFile::from(file, start, len, PhantomData)
+            }
+        };
+        Ok(file.map_err(io_error)?)
     }
 
     /// Retrieves the NPF's manifest
@@ -123,22 +529,41 @@ impl NPFExplorer {
         self.open_file("manifest.toml")
     }
 
-    /// Retrieves a handle over the NPF's data.tar.gz
+    /// Retrieves a handle over the NPF's data archive, `data.tar.gz` or `data.tar.zst` depending on the
+    /// compression declared in the manifest.
+    ///
+    /// Its content can be decompressed with [`Compression::decoder()`][1].
+    ///
+    /// [1]: enum.Compression.html#method.decoder
     pub fn open_data(&self) -> Result<Option<NPFFile>, NPFExplorationError> {
-        self.open_file("data.tar.gz").map_or_else(
+        self.open_file(self.manifest.compression().data_file_name())
+            .map_or_else(
+                |e| match e.kind() {
+                    NPFExplorationErrorKind::FileNotFound(_)
+                        if self.manifest.kind() != Effective =>
+                    {
+                        Ok(None)
+                    }
+                    _ => Err(e),
+                },
+                |o| Ok(Some(o)),
+            )
+    }
+
+    /// Retrieves a handle over the NPF's instructions.sh, if one exists
+    pub fn open_instructions(&self) -> Result<Option<NPFFile>, NPFExplorationError> {
+        self.open_file("instructions.sh").map_or_else(
             |e| match e.kind() {
-                NPFExplorationErrorKind::FileNotFound(_) if self.manifest.kind() != Effective => {
-                    Ok(None)
-                }
+                NPFExplorationErrorKind::FileNotFound(_) => Ok(None),
                 _ => Err(e),
             },
             |o| Ok(Some(o)),
         )
     }
 
-    /// Retrieves a handle over the NPF's instructions.sh, if one exists
-    pub fn open_instructions(&self) -> Result<Option<NPFFile>, NPFExplorationError> {
-        self.open_file("instructions.sh").map_or_else(
+    /// Retrieves a handle over the NPF's changelog.toml, if one exists
+    pub fn open_changelog(&self) -> Result<Option<NPFFile>, NPFExplorationError> {
+        self.open_file("changelog.toml").map_or_else(
             |e| match e.kind() {
                 NPFExplorationErrorKind::FileNotFound(_) => Ok(None),
                 _ => Err(e),
@@ -147,15 +572,31 @@ impl NPFExplorer {
         )
     }
 
+    /// Loads and parses the NPF's changelog.toml, if one exists
+    pub fn load_changelog(&self) -> Result<Option<Changelog>, NPFExplorationError> {
+        let mut file = match self.open_changelog()? {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)
+            .map_err(|_| NPFExplorationErrorKind::FileIOError(PathBuf::from("changelog.toml")))?;
+        let changelog: Changelog =
+            toml::from_str(&content).map_err(|_| NPFExplorationErrorKind::InvalidChangelog)?;
+
+        // The entries are sorted, whatever their order in the file
+        Ok(Some(Changelog::from(changelog.entries().to_vec())))
+    }
+
     /// Loads the NPF's instructions.sh file for execution, if one exists
     pub fn load_instructions(&self) -> Result<Option<InstructionsExecutor>, NPFExplorationError> {
         let mut file = self.open_instructions()?;
 
         if let Some(file) = &mut file {
-            let executor =
-                InstructionsExecutor::from_script_file(file.file_mut()).map_err(|_| {
-                    NPFExplorationErrorKind::FileIOError(PathBuf::from("instructions.sh"))
-                })?;
+            let executor = InstructionsExecutor::from_script_file(file).map_err(|_| {
+                NPFExplorationErrorKind::FileIOError(PathBuf::from("instructions.sh"))
+            })?;
 
             Ok(Some(executor))
         } else {
@@ -164,8 +605,19 @@ impl NPFExplorer {
     }
 }
 
+// Dropping an explorer never panics, as it may happen while unwinding: the failures are only logged, and the
+// directories left behind are removed by [`NPFExplorer::purge_abandoned_dirs()`] once their process is over
 impl Drop for NPFExplorer {
     fn drop(&mut self) {
-        fs::remove_dir_all(&self.path).expect("unable to cleanup an extracted NPF");
+        if let NPFSource::Unpacked(path) = &self.source {
+            let path = path.clone();
+            if let Err(e) = self.remove_unpacked_dir() {
+                warn!(
+                    "unable to remove the extracted NPF {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
     }
 }
@@ -2,14 +2,35 @@
 
 use failure::{Context, Error, ResultExt};
 use semver::VersionReq;
-use serde_derive::{Deserialize, Serialize};
 use serde::de::Visitor;
+use serde_derive::{Deserialize, Serialize};
 
 use super::error::*;
-use super::identification::{PackageFullName, PackageID};
+use super::identification::{PackageFullName, PackageID, PackageShortName};
+use super::manifest::Slot;
 use super::REGEX_PACKAGE_ID;
 use super::{CategoryName, PackageName, RepositoryName};
 
+/// Splits the slot off the given representation of a package requirement, like `sys-devel/gcc:9#^9.1`, returning
+/// the representation without it, and the slot if there was one.
+///
+/// The slot follows the name of the package, after a single colon, as opposed to the double colon following the
+/// repository.
+pub(crate) fn split_slot(repr: &str) -> (String, Option<&str>) {
+    let end = repr.find('#').unwrap_or_else(|| repr.len());
+    let colon = repr[..end]
+        .rfind(':')
+        .filter(|pos| *pos == 0 || !repr[..*pos].ends_with(':'));
+
+    match colon {
+        Some(pos) => (
+            format!("{}{}", &repr[..pos], &repr[end..]),
+            Some(&repr[pos + 1..end]),
+        ),
+        None => (repr.to_string(), None),
+    }
+}
+
 /// A structure representing a soft package requirement: parts of a package name and a
 /// version requirement.
 ///
@@ -23,6 +44,8 @@ pub struct SoftPackageRequirement {
     category: Option<CategoryName>,
     name: PackageName,
     version_requirement: VersionReq,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    slot: Option<Slot>,
 }
 
 impl SoftPackageRequirement {
@@ -35,6 +58,23 @@ impl SoftPackageRequirement {
             category: Some(category),
             name,
             version_requirement: version_req,
+            slot: None,
+        }
+    }
+
+    /// Creates a package requirement that matches the given [`PackageShortName`], in any repository, and version
+    /// requirement
+    #[inline]
+    pub fn from_short_name(
+        short_name: PackageShortName,
+        version_req: VersionReq,
+    ) -> SoftPackageRequirement {
+        SoftPackageRequirement {
+            repository: None,
+            category: Some(short_name.category().clone()),
+            name: short_name.name().clone(),
+            version_requirement: version_req,
+            slot: None,
         }
     }
 
@@ -46,6 +86,7 @@ impl SoftPackageRequirement {
             category: Some(id.category().clone()),
             name: id.name().clone(),
             version_requirement: VersionReq::exact(id.version()),
+            slot: None,
         }
     }
 
@@ -65,15 +106,29 @@ impl SoftPackageRequirement {
     /// assert_eq!(*req.category(), Some(CategoryName::parse("sys-bin")?));
     /// assert_eq!(req.name().as_str(), "coreutils");
     /// assert_eq!(req.version_requirement().to_string(), "^1.0");
+    /// assert!(req.slot().is_none());
+    ///
+    /// let req = SoftPackageRequirement::parse("stable::gcc:9")?;
+    /// assert_eq!(req.name().as_str(), "gcc");
+    /// assert_eq!(req.slot().as_ref().map(|slot| slot.as_ref()), Some("9"));
     ///
     /// assert!(SoftPackageRequirement::parse("sys-bin/coreutils#not_a_version").is_err());
+    /// assert!(SoftPackageRequirement::parse("sys-bin/coreutils:Not_A_Slot").is_err());
     /// # Ok(())
     /// # }
     /// ```
     #[inline]
     pub fn parse(repr: &str) -> Result<SoftPackageRequirement, Error> {
+        let (unslotted, slot) = split_slot(repr);
+        let slot = match slot {
+            Some(slot) => {
+                Some(Slot::parse(slot).map_err(SoftPackageRequirementParseErrorKind::InvalidSlot)?)
+            }
+            None => None,
+        };
+
         let matches = REGEX_PACKAGE_ID
-            .captures(repr)
+            .captures(&unslotted)
             .ok_or_else(|| Context::from(repr.to_string()))
             .context(SoftPackageRequirementParseErrorKind::InvalidFormat(
                 repr.to_string(),
@@ -112,6 +167,7 @@ impl SoftPackageRequirement {
             category,
             name: PackageName::parse(matches.name("package").unwrap().as_str())?,
             version_requirement: version_req,
+            slot,
         })
     }
 
@@ -146,6 +202,26 @@ impl SoftPackageRequirement {
         &self.version_requirement
     }
 
+    /// Returns the slot the target package must be in, if any
+    #[inline]
+    pub fn slot(&self) -> &Option<Slot> {
+        &self.slot
+    }
+
+    /// Changes the slot the target package must be in
+    #[inline]
+    pub fn with_slot(mut self, slot: Option<Slot>) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    /// Tests if a package in the given slot matches the slot of this requirement, which any slot does if the
+    /// requirement has none
+    #[inline]
+    pub fn matches_slot(&self, slot: &Slot) -> bool {
+        self.slot.as_ref().map_or(true, |required| required == slot)
+    }
+
     /// Tests if a given [`PackageID`] matches this package requirement, matching the name imprecisely
     /// The name of the package only needs to contain the name of the requirement to match
     ///
@@ -215,7 +291,11 @@ impl std::fmt::Display for SoftPackageRequirement {
         if let Some(category) = &self.category {
             write!(f, "{}/", category)?;
         }
-        write!(f, "{}#{}", self.name, self.version_requirement)
+        write!(f, "{}", self.name)?;
+        if let Some(slot) = &self.slot {
+            write!(f, ":{}", slot)?;
+        }
+        write!(f, "#{}", self.version_requirement)
     }
 }
 
@@ -226,6 +306,7 @@ impl From<PackageRequirement> for SoftPackageRequirement {
             category: Some(package_req.category),
             name: package_req.name,
             version_requirement: package_req.version_requirement,
+            slot: package_req.slot,
         }
     }
 }
@@ -243,6 +324,7 @@ pub struct PackageRequirement {
     category: CategoryName,
     name: PackageName,
     version_requirement: VersionReq,
+    slot: Option<Slot>,
 }
 
 impl PackageRequirement {
@@ -256,6 +338,7 @@ impl PackageRequirement {
             category,
             name,
             version_requirement: version_req,
+            slot: None,
         }
     }
 
@@ -267,6 +350,7 @@ impl PackageRequirement {
             category: id.category().clone(),
             name: id.name().clone(),
             version_requirement: VersionReq::exact(id.version()),
+            slot: None,
         }
     }
 
@@ -287,14 +371,26 @@ impl PackageRequirement {
     /// assert_eq!(req.name().as_str(), "coreutils");
     /// assert_eq!(req.version_requirement().to_string(), "^1.0");
     ///
+    /// let req = PackageRequirement::parse("stable::sys-devel/gcc:9#^9.1")?;
+    /// assert_eq!(req.slot().as_ref().map(|slot| slot.as_ref()), Some("9"));
+    /// assert_eq!(req.to_string(), "stable::sys-devel/gcc:9#^9.1");
+    ///
     /// assert!(PackageRequirement::parse("sys-bin/coreutils#not_a_version").is_err());
     /// # Ok(())
     /// # }
     /// ```
     #[inline]
     pub fn parse(repr: &str) -> Result<PackageRequirement, PackageRequirementParseError> {
+        let (unslotted, slot) = split_slot(repr);
+        let slot = match slot {
+            Some(slot) => {
+                Some(Slot::parse(slot).map_err(PackageRequirementParseErrorKind::InvalidSlot)?)
+            }
+            None => None,
+        };
+
         let matches = REGEX_PACKAGE_ID
-            .captures(repr)
+            .captures(&unslotted)
             .ok_or_else(|| Context::from(repr.to_string()))
             .context(PackageRequirementParseErrorKind::InvalidFormat(
                 repr.to_string(),
@@ -331,6 +427,7 @@ impl PackageRequirement {
                     category,
                     name,
                     version_requirement: version_req,
+                    slot,
                 })
             }
             _ => Err(PackageRequirementParseErrorKind::InvalidFormat(repr.to_string()).into()),
@@ -368,6 +465,26 @@ impl PackageRequirement {
         &self.version_requirement
     }
 
+    /// Returns the slot the target package must be in, if any
+    #[inline]
+    pub fn slot(&self) -> &Option<Slot> {
+        &self.slot
+    }
+
+    /// Changes the slot the target package must be in
+    #[inline]
+    pub fn with_slot(mut self, slot: Option<Slot>) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    /// Tests if a package in the given slot matches the slot of this requirement, which any slot does if the
+    /// requirement has none
+    #[inline]
+    pub fn matches_slot(&self, slot: &Slot) -> bool {
+        self.slot.as_ref().map_or(true, |required| required == slot)
+    }
+
     /// Tests if a given [`PackageFullName`] matches this package requirement, matching the name imprecisely
     /// The name of the package only needs to contain the name of the requirement to match
     #[inline]
@@ -454,11 +571,11 @@ impl std::fmt::Display for PackageRequirement {
         if let Some(repository) = &self.repository {
             write!(f, "{}::", repository)?;
         }
-        write!(
-            f,
-            "{}/{}#{}",
-            self.category, self.name, self.version_requirement
-        )
+        write!(f, "{}/{}", self.category, self.name)?;
+        if let Some(slot) = &self.slot {
+            write!(f, ":{}", slot)?;
+        }
+        write!(f, "#{}", self.version_requirement)
     }
 }
 
@@ -474,8 +591,8 @@ impl<'de> Visitor<'de> for PackageRequirementVisitor {
 
     #[inline]
     fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
-        where
-            E: serde::de::Error,
+    where
+        E: serde::de::Error,
     {
         PackageRequirement::parse(value).map_err(|_| {
             E::custom("the package requirement doesn't follow the convention `repository::category/name#version_requirement`")
@@ -490,6 +607,7 @@ impl From<HardPackageRequirement> for PackageRequirement {
         let HardPackageRequirement {
             full_name,
             version_requirement,
+            slot,
         } = package_req;
         let (repository, category, name) = full_name.into_tuple();
 
@@ -498,6 +616,7 @@ impl From<HardPackageRequirement> for PackageRequirement {
             category,
             name,
             version_requirement,
+            slot,
         }
     }
 }
@@ -509,6 +628,8 @@ impl From<HardPackageRequirement> for PackageRequirement {
 pub struct HardPackageRequirement {
     full_name: PackageFullName,
     version_requirement: VersionReq,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    slot: Option<Slot>,
 }
 
 impl HardPackageRequirement {
@@ -517,6 +638,7 @@ impl HardPackageRequirement {
         HardPackageRequirement {
             full_name,
             version_requirement,
+            slot: None,
         }
     }
 
@@ -533,6 +655,13 @@ impl HardPackageRequirement {
         self
     }
 
+    /// Changes the slot the target package must be in
+    #[inline]
+    pub fn with_slot(mut self, slot: Option<Slot>) -> Self {
+        self.slot = slot;
+        self
+    }
+
     /// Returns whether the given [`PackageID`] matches this requirement
     #[inline]
     pub fn matches(&self, id: &PackageID) -> bool {
@@ -542,12 +671,16 @@ impl HardPackageRequirement {
 
 impl std::fmt::Display for HardPackageRequirement {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}#{}", self.full_name, self.version_requirement)
+        write!(f, "{}", self.full_name)?;
+        if let Some(slot) = &self.slot {
+            write!(f, ":{}", slot)?;
+        }
+        write!(f, "#{}", self.version_requirement)
     }
 }
 
 impl std::convert::Into<SoftPackageRequirement> for HardPackageRequirement {
     fn into(self) -> SoftPackageRequirement {
-        SoftPackageRequirement::from(self.full_name, self.version_requirement)
+        SoftPackageRequirement::from(self.full_name, self.version_requirement).with_slot(self.slot)
     }
 }
@@ -0,0 +1,197 @@
+//! The zstd data archives of the NPFs, made of independent frames followed by a seek table.
+//!
+//! The layout is the one of zstd's seekable format: the archive is split in frames of at most [`FRAME_SIZE`]
+//! decompressed bytes, and a skippable frame listing the compressed and decompressed sizes of each frame is
+//! appended. Any zstd decoder reads such an archive as a regular stream, while the extraction of a package uses
+//! the seek table to decompress the frames on several threads.
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// The number of decompressed bytes of each frame of a data archive
+pub(crate) const FRAME_SIZE: usize = 1024 * 1024;
+
+/// The largest number of decompressed bytes of a frame that the seek table of a data archive may announce.
+///
+/// Archives announcing bigger frames are decompressed as a single stream.
+pub(crate) const MAX_FRAME_SIZE: usize = 16 * FRAME_SIZE;
+
+/// The magic number of the skippable frame holding the seek table
+const SKIPPABLE_MAGIC_NUMBER: u32 = 0x184D_2A5E;
+
+/// The magic number ending the seek table
+const SEEKABLE_MAGIC_NUMBER: u32 = 0x8F92_EAB1;
+
+/// The size of the header of a skippable frame: its magic number and its size
+const SKIPPABLE_HEADER_SIZE: u64 = 8;
+
+/// The size of the footer of the seek table: the number of frames, the descriptor and the magic number
+const FOOTER_SIZE: u64 = 9;
+
+/// The bit of the descriptor of the seek table telling whether each entry ends with a checksum
+const CHECKSUM_FLAG: u8 = 0x80;
+
+/// The bits of the descriptor of the seek table that must be unset
+const RESERVED_BITS: u8 = 0x7C;
+
+/// A frame of a data archive, as listed in its seek table
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub(crate) struct Frame {
+    compressed_size: u32,
+    decompressed_size: u32,
+}
+
+impl Frame {
+    /// Returns the size of the frame in the archive, in bytes
+    #[inline]
+    pub(crate) fn compressed_size(self) -> usize {
+        self.compressed_size as usize
+    }
+
+    /// Returns the size of the content of the frame, in bytes
+    #[inline]
+    pub(crate) fn decompressed_size(self) -> usize {
+        self.decompressed_size as usize
+    }
+}
+
+/// A writer compressing its content in independent zstd frames, ending with a seek table once finished
+#[derive(Debug)]
+pub(crate) struct FramedEncoder<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    frames: Vec<Frame>,
+}
+
+impl<W: Write> FramedEncoder<W> {
+    /// Creates an encoder writing a data archive to the given writer
+    pub(crate) fn new(writer: W) -> Self {
+        FramedEncoder {
+            writer,
+            buffer: Vec::with_capacity(FRAME_SIZE),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Compresses the buffered content as a frame of its own
+    fn write_frame(&mut self) -> io::Result<()> {
+        let compressed = zstd::block::compress(&self.buffer, 0)?;
+        let compressed_size = u32::try_from(compressed.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "the frame is too big"))?;
+
+        self.writer.write_all(&compressed)?;
+        self.frames.push(Frame {
+            compressed_size,
+            decompressed_size: self.buffer.len() as u32,
+        });
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Writes the remaining content and the seek table, and returns the underlying writer
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        if !self.buffer.is_empty() || self.frames.is_empty() {
+            self.write_frame()?;
+        }
+
+        let mut table = Vec::with_capacity(
+            SKIPPABLE_HEADER_SIZE as usize + self.frames.len() * 8 + FOOTER_SIZE as usize,
+        );
+        let table_size = self.frames.len() * 8 + FOOTER_SIZE as usize;
+        table.extend_from_slice(&SKIPPABLE_MAGIC_NUMBER.to_le_bytes());
+        table.extend_from_slice(&(table_size as u32).to_le_bytes());
+        for frame in &self.frames {
+            table.extend_from_slice(&frame.compressed_size.to_le_bytes());
+            table.extend_from_slice(&frame.decompressed_size.to_le_bytes());
+        }
+        table.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        table.push(0);
+        table.extend_from_slice(&SEEKABLE_MAGIC_NUMBER.to_le_bytes());
+
+        self.writer.write_all(&table)?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for FramedEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(FRAME_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == FRAME_SIZE {
+            self.write_frame()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads a little-endian `u32` at the given offset of the given buffer
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buffer[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// Reads the seek table of the given zstd data archive, and seeks back to its beginning.
+///
+/// `None` is returned if the archive has no seek table, was written with frames bigger than [`MAX_FRAME_SIZE`], or
+/// if its frames don't add up to the size of the archive, in which case it can only be decompressed as a stream.
+pub(crate) fn read_seek_table<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<Frame>>> {
+    let res = seek_table(reader);
+    reader.seek(SeekFrom::Start(0))?;
+    res
+}
+
+fn seek_table<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<Frame>>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len < SKIPPABLE_HEADER_SIZE + FOOTER_SIZE {
+        return Ok(None);
+    }
+
+    let mut footer = [0; FOOTER_SIZE as usize];
+    reader.seek(SeekFrom::Start(len - FOOTER_SIZE))?;
+    reader.read_exact(&mut footer)?;
+
+    let descriptor = footer[4];
+    if read_u32(&footer, 5) != SEEKABLE_MAGIC_NUMBER || descriptor & RESERVED_BITS != 0 {
+        return Ok(None);
+    }
+    let entry_size = if descriptor & CHECKSUM_FLAG != 0 {
+        12
+    } else {
+        8
+    };
+    let table_size = u64::from(read_u32(&footer, 0)) * entry_size + FOOTER_SIZE;
+    if len < SKIPPABLE_HEADER_SIZE + table_size {
+        return Ok(None);
+    }
+
+    let mut table = vec![0; (SKIPPABLE_HEADER_SIZE + table_size - FOOTER_SIZE) as usize];
+    reader.seek(SeekFrom::Start(len - SKIPPABLE_HEADER_SIZE - table_size))?;
+    reader.read_exact(&mut table)?;
+    if read_u32(&table, 0) != SKIPPABLE_MAGIC_NUMBER || u64::from(read_u32(&table, 4)) != table_size
+    {
+        return Ok(None);
+    }
+
+    let frames = table[SKIPPABLE_HEADER_SIZE as usize..]
+        .chunks(entry_size as usize)
+        .map(|entry| Frame {
+            compressed_size: read_u32(entry, 0),
+            decompressed_size: read_u32(entry, 4),
+        })
+        .collect::<Vec<_>>();
+
+    let compressed_size: u64 = frames.iter().map(|f| u64::from(f.compressed_size)).sum();
+    if compressed_size + SKIPPABLE_HEADER_SIZE + table_size != len
+        || frames
+            .iter()
+            .any(|f| f.decompressed_size() > MAX_FRAME_SIZE)
+    {
+        return Ok(None);
+    }
+    Ok(Some(frames))
+}
@@ -0,0 +1,243 @@
+//! Ed25519 signatures of NPFs.
+//!
+//! The `signature` file of an NPF holds the raw 64-byte Ed25519 signature of the concatenation of
+//! the SHA-256 digests of `manifest.toml`, `data.tar.gz` and `instructions.sh`, in that order.
+//! A file absent from the NPF is represented by a digest filled with zeros.
+//!
+//! If the NPF contains a `data.tar.zst` or a `changelog.toml`, each of them appends its name, a NUL byte and its
+//! digest to the message, in that order, so that the digest of one can't be taken for the digest of the other.
+//! They are omitted otherwise, so that the signatures of NPFs made before they were supported remain valid.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
+use serde::de::Visitor;
+
+use super::error::{SigningKeyParseError, TrustedKeyParseError};
+use super::Checksum;
+
+/// The names of the files covered by the signature of an NPF, in the order they are hashed
+pub(crate) static SIGNED_FILES: [&str; 3] = ["manifest.toml", "data.tar.gz", "instructions.sh"];
+
+/// The names of the files covered by the signature of an NPF only when they are present, in the order they are hashed
+pub(crate) static OPTIONALLY_SIGNED_FILES: [&str; 2] = ["data.tar.zst", "changelog.toml"];
+
+/// The name of the file holding the signature of an NPF
+pub(crate) static SIGNATURE_FILE: &str = "signature";
+
+/// An Ed25519 public key trusted to sign the packages of a repository.
+///
+/// Its string representation is the base64 encoding of the 32 bytes of the key.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TrustedKey([u8; 32]);
+
+impl TrustedKey {
+    /// Parses the string representation of a [`TrustedKey`].
+    pub fn parse(repr: &str) -> Result<Self, TrustedKeyParseError> {
+        Self::try_from(repr)
+    }
+
+    /// Returns the raw bytes of this key
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Returns the fingerprint of this key, used to identify it.
+    ///
+    /// The fingerprint is the lowercase hexadecimal encoding of the first 16 bytes of the SHA-256 digest of the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// use libnest::package::SigningKey;
+    ///
+    /// let key = SigningKey::generate().trusted_key();
+    /// assert_eq!(key.fingerprint().len(), 32);
+    /// assert!(key.fingerprint().chars().all(|c| c.is_ascii_hexdigit()));
+    /// ```
+    pub fn fingerprint(&self) -> String {
+        Checksum::from_reader(&mut &self.0[..])
+            .expect("hashing in-memory data can't fail")
+            .as_bytes()[..16]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Tests whether the given signature of the given message was made by this key
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match (
+            PublicKey::from_bytes(&self.0),
+            Signature::try_from(signature),
+        ) {
+            (Ok(key), Ok(signature)) => key.verify(message, &signature).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<&str> for TrustedKey {
+    type Error = TrustedKeyParseError;
+
+    fn try_from(repr: &str) -> Result<Self, Self::Error> {
+        let bytes = base64::decode(repr).map_err(|_| TrustedKeyParseError(repr.to_string()))?;
+
+        if bytes.len() != 32 || PublicKey::from_bytes(&bytes).is_err() {
+            return Err(TrustedKeyParseError(repr.to_string()));
+        }
+
+        let mut key = [0; 32];
+        key.copy_from_slice(&bytes);
+        Ok(TrustedKey(key))
+    }
+}
+
+impl std::fmt::Display for TrustedKey {
+    #[inline]
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", base64::encode(&self.0))
+    }
+}
+
+struct TrustedKeyVisitor;
+
+impl<'de> Visitor<'de> for TrustedKeyVisitor {
+    type Value = TrustedKey;
+
+    #[inline]
+    fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("a trusted key")
+    }
+
+    #[inline]
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        TrustedKey::parse(value)
+            .map_err(|_| E::custom("the trusted key isn't a base64-encoded Ed25519 public key"))
+    }
+}
+
+impl_serde_visitor!(TrustedKey, TrustedKeyVisitor);
+
+/// An Ed25519 secret key used to sign NPFs.
+///
+/// Its string representation is the base64 encoding of the 32 bytes of the key.
+#[derive(Clone, Eq, PartialEq)]
+pub struct SigningKey([u8; 32]);
+
+impl SigningKey {
+    /// Parses the string representation of a [`SigningKey`].
+    pub fn parse(repr: &str) -> Result<Self, SigningKeyParseError> {
+        Self::try_from(repr)
+    }
+
+    /// Generates a new, random [`SigningKey`].
+    pub fn generate() -> Self {
+        use rand::{thread_rng, Rng};
+
+        let mut key = [0; 32];
+        thread_rng().fill(&mut key);
+        SigningKey(key)
+    }
+
+    fn keypair(&self) -> Keypair {
+        let secret = SecretKey::from_bytes(&self.0).expect("invalid signing key");
+        let public = PublicKey::from(&secret);
+
+        Keypair { secret, public }
+    }
+
+    /// Returns the [`TrustedKey`] verifying the signatures made with this key
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// use libnest::package::SigningKey;
+    ///
+    /// let signing_key = SigningKey::generate();
+    /// let signature = signing_key.sign(b"message");
+    /// assert!(signing_key.trusted_key().verify(b"message", &signature));
+    /// assert!(!signing_key.trusted_key().verify(b"another message", &signature));
+    /// ```
+    pub fn trusted_key(&self) -> TrustedKey {
+        TrustedKey(self.keypair().public.to_bytes())
+    }
+
+    /// Signs the given message, returning the raw bytes of the signature
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        self.keypair().sign(message).to_bytes()
+    }
+}
+
+impl TryFrom<&str> for SigningKey {
+    type Error = SigningKeyParseError;
+
+    fn try_from(repr: &str) -> Result<Self, Self::Error> {
+        let bytes = base64::decode(repr).map_err(|_| SigningKeyParseError)?;
+
+        if bytes.len() != 32 {
+            return Err(SigningKeyParseError);
+        }
+
+        let mut key = [0; 32];
+        key.copy_from_slice(&bytes);
+        Ok(SigningKey(key))
+    }
+}
+
+impl std::fmt::Display for SigningKey {
+    #[inline]
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", base64::encode(&self.0))
+    }
+}
+
+// The secret key itself is never printed in debug output
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.debug_tuple("SigningKey")
+            .field(&self.trusted_key())
+            .finish()
+    }
+}
+
+/// Computes the message signed by the signature of the NPF extracted in the given directory
+pub(crate) fn signed_message(npf_dir: &Path) -> io::Result<Vec<u8>> {
+    signed_message_from(|file_name| match File::open(npf_dir.join(file_name)) {
+        Ok(mut file) => Checksum::from_reader(&mut file).map(Some),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    })
+}
+
+/// Computes the message signed by the signature of an NPF, given a function returning the digest
+/// of each of its files, or `None` if the file is absent from the NPF
+pub(crate) fn signed_message_from<F>(mut digest: F) -> io::Result<Vec<u8>>
+where
+    F: FnMut(&str) -> io::Result<Option<Checksum>>,
+{
+    let mut message = Vec::with_capacity(32 * SIGNED_FILES.len());
+
+    for file_name in SIGNED_FILES.iter() {
+        match digest(file_name)? {
+            Some(checksum) => message.extend_from_slice(checksum.as_bytes()),
+            None => message.extend_from_slice(&[0; 32]),
+        }
+    }
+    for file_name in OPTIONALLY_SIGNED_FILES.iter() {
+        if let Some(checksum) = digest(file_name)? {
+            message.extend_from_slice(file_name.as_bytes());
+            message.push(0);
+            message.extend_from_slice(checksum.as_bytes());
+        }
+    }
+    Ok(message)
+}
@@ -0,0 +1,49 @@
+//! Errors that can be returned by the repository module
+
+use chrono::{DateTime, Utc};
+use failure::{Context, Fail};
+
+use super::MetadataRole;
+
+/// Error type for errors related to the signed metadata of a repository
+#[derive(Debug)]
+pub struct MetadataError {
+    inner: Context<MetadataErrorKind>,
+}
+
+/// Error kind describing a kind of error related to the signed metadata of a repository
+#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+pub enum MetadataErrorKind {
+    /// The metadata could not be parsed
+    #[fail(display = "invalid {} metadata", _0)]
+    InvalidMetadata(MetadataRole),
+
+    /// The metadata isn't signed by any of the keys trusted for the repository
+    #[fail(display = "the {} metadata isn't signed by a trusted key", _0)]
+    InvalidSignature(MetadataRole),
+
+    /// The repository has no trusted key to verify its metadata
+    #[fail(display = "no trusted key to verify the metadata")]
+    NoTrustedKey,
+
+    /// The metadata expired, meaning the mirror may be serving frozen metadata
+    #[fail(display = "the {} metadata expired on {}", _0, _1)]
+    Expired(MetadataRole, DateTime<Utc>),
+
+    /// The metadata is older than the one pulled previously, meaning the mirror may be serving rolled back metadata
+    #[fail(
+        display = "the {} metadata is older than the one pulled previously (serial {} < {})",
+        _0, _1, _2
+    )]
+    Rollback(MetadataRole, u64, u64),
+
+    /// The timestamp and the snapshot don't refer to the same serial number
+    #[fail(display = "the timestamp and the snapshot metadata don't match")]
+    InconsistentMetadata,
+
+    /// Some data pulled doesn't match the checksum listed in the metadata
+    #[fail(display = "the pulled data doesn't match the snapshot metadata")]
+    ChecksumMismatch,
+}
+
+use_as_error!(MetadataError, MetadataErrorKind);
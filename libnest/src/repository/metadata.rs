@@ -0,0 +1,333 @@
+//! Signed metadata of a repository, protecting pulls against tampered, frozen or rolled back package lists.
+//!
+//! Following the timestamp and snapshot roles of TUF, a repository publishes two signed documents:
+//!
+//! * The snapshot (`api/snapshot`), holding the serial number of the repository's journal, the checksum of the
+//!   package list at this serial (`api/pull`), and the checksums of the changes leading to it from previous
+//!   serials (`api/pull/since/<serial>`).
+//! * The timestamp (`api/timestamp`), holding the checksum of the current snapshot. It is short-lived and signed
+//!   again regularly by the repository, even if nothing changed.
+//!
+//! Both documents expire, so that a mirror can't keep serving the same metadata forever, and their serial
+//! can't be lower than the one pulled previously, so that a mirror can't serve older metadata.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use chrono::{DateTime, Utc};
+use failure::ResultExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+
+use crate::package::{Checksum, SigningKey, TrustedKey};
+
+use super::errors::{MetadataError, MetadataErrorKind};
+
+/// The roles of the signed metadata of a repository
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum MetadataRole {
+    /// The short-lived metadata pointing to the current snapshot
+    Timestamp,
+
+    /// The metadata describing the current state of the repository
+    Snapshot,
+}
+
+impl Display for MetadataRole {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            MetadataRole::Timestamp => write!(fmt, "timestamp"),
+            MetadataRole::Snapshot => write!(fmt, "snapshot"),
+        }
+    }
+}
+
+/// A metadata document, along with the signature of the repository.
+///
+/// The document is kept as the exact text that was signed, so that verifying it doesn't depend on how it is
+/// serialized. The signature is the base64 encoding of the Ed25519 signature of this text.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SignedMetadata {
+    signed: String,
+    signature: String,
+}
+
+impl SignedMetadata {
+    /// Serializes and signs the given metadata with the given key
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate chrono;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use chrono::{Duration, Utc};
+    /// use libnest::package::{Checksum, SigningKey};
+    /// use libnest::repository::{MetadataRole, SignedMetadata, Timestamp};
+    ///
+    /// let key = SigningKey::generate();
+    /// let snapshot = Checksum::from_reader(&mut "snapshot".as_bytes())?;
+    /// let timestamp = Timestamp::new(42, Utc::now() + Duration::days(1), snapshot);
+    /// let signed = SignedMetadata::sign(&timestamp, &key)?;
+    ///
+    /// let verified: Timestamp = signed.verify(MetadataRole::Timestamp, &[key.trusted_key()])?;
+    /// assert_eq!(verified, timestamp);
+    ///
+    /// let other_key = SigningKey::generate();
+    /// assert!(signed
+    ///     .verify::<Timestamp>(MetadataRole::Timestamp, &[other_key.trusted_key()])
+    ///     .is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sign<T: Serialize>(metadata: &T, key: &SigningKey) -> Result<Self, serde_json::Error> {
+        let signed = serde_json::to_string(metadata)?;
+        let signature = base64::encode(&key.sign(signed.as_bytes())[..]);
+
+        Ok(SignedMetadata { signed, signature })
+    }
+
+    /// Parses a [`SignedMetadata`] from the given data, as served by the mirrors of a repository
+    pub fn parse(data: &[u8], role: MetadataRole) -> Result<Self, MetadataError> {
+        Ok(serde_json::from_slice(data).context(MetadataErrorKind::InvalidMetadata(role))?)
+    }
+
+    /// Verifies that the metadata is signed by one of the given keys, and returns it
+    pub fn verify<T: DeserializeOwned>(
+        &self,
+        role: MetadataRole,
+        keys: &[TrustedKey],
+    ) -> Result<T, MetadataError> {
+        if keys.is_empty() {
+            return Err(MetadataErrorKind::NoTrustedKey.into());
+        }
+
+        let signature =
+            base64::decode(&self.signature).context(MetadataErrorKind::InvalidSignature(role))?;
+        if !keys
+            .iter()
+            .any(|key| key.verify(self.signed.as_bytes(), &signature))
+        {
+            return Err(MetadataErrorKind::InvalidSignature(role).into());
+        }
+
+        Ok(serde_json::from_str(&self.signed).context(MetadataErrorKind::InvalidMetadata(role))?)
+    }
+}
+
+/// Checks that metadata with the given serial and expiration date is neither expired nor older than the
+/// metadata pulled previously
+fn check_freshness(
+    role: MetadataRole,
+    serial: u64,
+    expires: DateTime<Utc>,
+    last_serial: Option<u64>,
+) -> Result<(), MetadataError> {
+    if expires < Utc::now() {
+        return Err(MetadataErrorKind::Expired(role, expires).into());
+    }
+    match last_serial {
+        Some(last_serial) if serial < last_serial => {
+            Err(MetadataErrorKind::Rollback(role, serial, last_serial).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The short-lived metadata of a repository, pointing to its current [`Snapshot`]
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Timestamp {
+    serial: u64,
+    expires: DateTime<Utc>,
+    snapshot: Checksum,
+}
+
+impl Timestamp {
+    /// Creates a [`Timestamp`] pointing to the snapshot with the given checksum
+    #[inline]
+    pub fn new(serial: u64, expires: DateTime<Utc>, snapshot: Checksum) -> Self {
+        Timestamp {
+            serial,
+            expires,
+            snapshot,
+        }
+    }
+
+    /// Returns the serial number of the repository's journal
+    #[inline]
+    pub fn serial(&self) -> u64 {
+        self.serial
+    }
+
+    /// Returns the date after which this timestamp can't be trusted anymore
+    #[inline]
+    pub fn expires(&self) -> DateTime<Utc> {
+        self.expires
+    }
+
+    /// Returns the checksum of the signed snapshot
+    #[inline]
+    pub fn snapshot(&self) -> &Checksum {
+        &self.snapshot
+    }
+
+    /// Checks that this timestamp didn't expire and isn't older than the serial pulled previously, if any
+    pub fn check(&self, last_serial: Option<u64>) -> Result<(), MetadataError> {
+        check_freshness(
+            MetadataRole::Timestamp,
+            self.serial,
+            self.expires,
+            last_serial,
+        )
+    }
+
+    /// Verifies the given signed snapshot against this timestamp, and returns it
+    pub fn verify_snapshot(
+        &self,
+        data: &[u8],
+        keys: &[TrustedKey],
+    ) -> Result<Snapshot, MetadataError> {
+        let checksum = Checksum::from_reader(&mut &data[..])
+            .context(MetadataErrorKind::InvalidMetadata(MetadataRole::Snapshot))?;
+        if checksum != self.snapshot {
+            return Err(MetadataErrorKind::ChecksumMismatch.into());
+        }
+
+        let snapshot: Snapshot = SignedMetadata::parse(data, MetadataRole::Snapshot)?
+            .verify(MetadataRole::Snapshot, keys)?;
+        if snapshot.serial != self.serial {
+            return Err(MetadataErrorKind::InconsistentMetadata.into());
+        }
+        check_freshness(
+            MetadataRole::Snapshot,
+            snapshot.serial,
+            snapshot.expires,
+            None,
+        )?;
+        Ok(snapshot)
+    }
+}
+
+/// The metadata describing the state of a repository at a given serial number of its journal
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Snapshot {
+    serial: u64,
+    expires: DateTime<Utc>,
+    packages: Checksum,
+    #[serde(default)]
+    changes: BTreeMap<u64, Checksum>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    files: Option<Checksum>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    advisories: Option<Checksum>,
+}
+
+impl Snapshot {
+    /// Creates a [`Snapshot`] of a repository whose package list has the given checksum
+    #[inline]
+    pub fn new(serial: u64, expires: DateTime<Utc>, packages: Checksum) -> Self {
+        Snapshot {
+            serial,
+            expires,
+            packages,
+            changes: Default::default(),
+            files: None,
+            advisories: None,
+        }
+    }
+
+    /// Returns the serial number of the repository's journal
+    #[inline]
+    pub fn serial(&self) -> u64 {
+        self.serial
+    }
+
+    /// Returns the date after which this snapshot can't be trusted anymore
+    #[inline]
+    pub fn expires(&self) -> DateTime<Utc> {
+        self.expires
+    }
+
+    /// Returns the checksum of the package list of the repository
+    #[inline]
+    pub fn packages(&self) -> &Checksum {
+        &self.packages
+    }
+
+    /// Returns the checksums of the changes made to the repository since previous serial numbers
+    #[inline]
+    pub fn changes(&self) -> &BTreeMap<u64, Checksum> {
+        &self.changes
+    }
+
+    /// Returns a mutable reference over the checksums of the changes made to the repository since previous
+    /// serial numbers
+    #[inline]
+    pub fn changes_mut(&mut self) -> &mut BTreeMap<u64, Checksum> {
+        &mut self.changes
+    }
+
+    /// Returns the checksum of the file lists of the repository's packages, if the repository publishes them
+    #[inline]
+    pub fn files(&self) -> Option<&Checksum> {
+        self.files.as_ref()
+    }
+
+    /// Returns a mutable reference over the checksum of the file lists of the repository's packages
+    #[inline]
+    pub fn files_mut(&mut self) -> &mut Option<Checksum> {
+        &mut self.files
+    }
+
+    /// Returns the checksum of the security advisories of the repository's packages, if the repository publishes them
+    #[inline]
+    pub fn advisories(&self) -> Option<&Checksum> {
+        self.advisories.as_ref()
+    }
+
+    /// Returns a mutable reference over the checksum of the security advisories of the repository's packages
+    #[inline]
+    pub fn advisories_mut(&mut self) -> &mut Option<Checksum> {
+        &mut self.advisories
+    }
+
+    /// Checks that the given package list is the one described by this snapshot
+    pub fn verify_packages(&self, data: &[u8]) -> Result<(), MetadataError> {
+        verify_checksum(data, &self.packages)
+    }
+
+    /// Checks that the given changes since the given serial are the ones described by this snapshot
+    pub fn verify_changes(&self, since: u64, data: &[u8]) -> Result<(), MetadataError> {
+        match self.changes.get(&since) {
+            Some(checksum) => verify_checksum(data, checksum),
+            None => Err(MetadataErrorKind::ChecksumMismatch.into()),
+        }
+    }
+
+    /// Checks that the given file lists are the ones described by this snapshot
+    pub fn verify_files(&self, data: &[u8]) -> Result<(), MetadataError> {
+        match &self.files {
+            Some(checksum) => verify_checksum(data, checksum),
+            None => Err(MetadataErrorKind::ChecksumMismatch.into()),
+        }
+    }
+
+    /// Checks that the given security advisories are the ones described by this snapshot
+    pub fn verify_advisories(&self, data: &[u8]) -> Result<(), MetadataError> {
+        match &self.advisories {
+            Some(checksum) => verify_checksum(data, checksum),
+            None => Err(MetadataErrorKind::ChecksumMismatch.into()),
+        }
+    }
+}
+
+/// Checks that the given data has the given checksum
+fn verify_checksum(data: &[u8], checksum: &Checksum) -> Result<(), MetadataError> {
+    match Checksum::from_reader(&mut &data[..]) {
+        Ok(ref actual) if actual == checksum => Ok(()),
+        _ => Err(MetadataErrorKind::ChecksumMismatch.into()),
+    }
+}
@@ -1,7 +1,18 @@
-//! Repository: wrapper around a name and a [`RepositoryConfig`]
+//! Repository: wrapper around a name and a [`RepositoryConfig`], and the signed metadata it publishes
+
+mod errors;
+mod metadata;
+
+pub use self::errors::*;
+pub use self::metadata::{MetadataRole, SignedMetadata, Snapshot, Timestamp};
 
 use crate::config::RepositoryConfig;
 
+/// The name of the repository the packages added to the caches from local NPFs belong to.
+///
+/// Their origin is thus recorded in the cache of installed packages, like for the packages of the other repositories.
+pub static LOCAL_REPOSITORY: &str = "local";
+
 /// A repository
 ///
 /// Wraps a reference over a name and a repository configuration
@@ -0,0 +1,27 @@
+//! Errors that can be returned by the solver module
+
+use failure::{Context, Fail};
+
+/// Error type for errors related to the resolution of version constraints
+#[derive(Debug)]
+pub struct SolverError {
+    inner: Context<SolverErrorKind>,
+}
+
+/// Error kind describing a kind of error related to the resolution of version constraints
+#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+pub enum SolverErrorKind {
+    /// The versions of a package could not be retrieved from the package source
+    #[fail(display = "unable to retrieve the versions of {}", _0)]
+    SourceError(String),
+
+    /// No set of versions satisfies all the constraints
+    #[fail(display = "unable to find a consistent set of packages: {}", _0)]
+    Unsatisfiable(String),
+
+    /// The search was abandoned because it took too many steps
+    #[fail(display = "unable to find a consistent set of packages in a reasonable time")]
+    SearchLimitReached,
+}
+
+use_as_error!(SolverError, SolverErrorKind);
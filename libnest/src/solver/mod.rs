@@ -0,0 +1,411 @@
+//! Solver for the version constraints between packages.
+//!
+//! Given a set of requirements, the [`Solver`] looks for a version of each needed package so
+//! that the requirements of every selected package are fulfilled. Unlike a greedy resolution,
+//! it backtracks when a choice leads to a conflict, which is needed to handle diamond
+//! dependencies with version ranges.
+//!
+//! The search is a depth-first search with conflict-directed backjumping: the most constrained
+//! package is decided first, and when a conflict is found, the search goes back directly to the
+//! last decision that took part in it. When no solution exists, the conflict that was found first
+//! is explained in terms of the requirements involved.
+//!
+//! The dependency graph relies on the solver when selecting the most recent version of each package
+//! leads to a dead end (see [`DependencyGraph::solve()`](crate::cache::depgraph::DependencyGraph::solve)).
+
+mod errors;
+mod source;
+
+pub use self::errors::*;
+pub use self::source::{Candidate, PackageSource};
+
+pub(crate) use self::source::is_available;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use log::{debug, trace};
+
+use crate::package::{CategoryName, PackageID, PackageName, PackageRequirement};
+
+/// Maximum number of versions tried before the search is abandoned
+static MAX_STEPS: usize = 100_000;
+
+/// Key identifying a package regardless of its repository and version
+type PackageKey = (CategoryName, PackageName);
+
+fn package_key(id: &PackageID) -> PackageKey {
+    (id.category().clone(), id.name().clone())
+}
+
+fn requirement_key(requirement: &PackageRequirement) -> PackageKey {
+    (requirement.category().clone(), requirement.name().clone())
+}
+
+/// A requirement on a package, along with the package requiring it ([`None`] for the targets)
+type Constraint = (PackageRequirement, Option<PackageID>);
+
+/// The partial solution being built by the search
+#[derive(Clone, Default, Debug)]
+struct State {
+    constraints: BTreeMap<PackageKey, Vec<Constraint>>,
+    assignments: BTreeMap<PackageKey, Candidate>,
+}
+
+/// The reason why a branch of the search failed
+enum Failure {
+    /// The decisions on the given packages lead to a conflict
+    Conflict(BTreeSet<PackageKey>),
+
+    /// The search can't go on
+    Error(SolverError),
+}
+
+/// Structure looking for a consistent set of package versions fulfilling a set of requirements
+#[derive(Debug)]
+pub struct Solver<'a, S: PackageSource> {
+    source: &'a S,
+    installed: Vec<PackageID>,
+}
+
+impl<'a, S: PackageSource> Solver<'a, S> {
+    /// Creates a [`Solver`] picking packages from the given source
+    #[inline]
+    pub fn from(source: &'a S) -> Self {
+        Self {
+            source,
+            installed: Vec::new(),
+        }
+    }
+
+    /// Sets the packages already installed on the system.
+    ///
+    /// The installed version of a package is preferred over the others, as long as it fulfills
+    /// all the requirements on this package.
+    #[inline]
+    pub fn with_installed<I: IntoIterator<Item = PackageID>>(mut self, installed: I) -> Self {
+        self.installed = installed.into_iter().collect();
+        self
+    }
+
+    /// Looks for a version of each needed package so that all the requirements are fulfilled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use libnest::package::{PackageID, PackageRequirement};
+    /// use libnest::solver::{Candidate, PackageSource, Solver};
+    ///
+    /// struct Packages(Vec<Candidate>);
+    ///
+    /// impl PackageSource for Packages {
+    ///     fn candidates(&self, req: &PackageRequirement) -> Result<Vec<Candidate>, failure::Error> {
+    ///         Ok(self.0.iter().filter(|c| req.clone().any_version().matches(c.id())).cloned().collect())
+    ///     }
+    /// }
+    ///
+    /// let package = |id: &str, deps: &[&str]| -> Result<Candidate, failure::Error> {
+    ///     let deps = deps.iter().map(|dep| PackageRequirement::parse(dep)).collect::<Result<_, _>>()?;
+    ///     Ok(Candidate::from(PackageID::parse(id)?, deps))
+    /// };
+    ///
+    /// // The most recent version of 'b' needs a version of 'd' that 'c' doesn't support
+    /// let packages = Packages(vec![
+    ///     package("stable::sys-lib/a#1.0.0", &["sys-lib/b#^1.0", "sys-lib/c#^1.0"])?,
+    ///     package("stable::sys-lib/b#1.1.0", &["sys-lib/d#^2.0"])?,
+    ///     package("stable::sys-lib/b#1.0.0", &["sys-lib/d#^1.0"])?,
+    ///     package("stable::sys-lib/c#1.0.0", &["sys-lib/d#^1.0"])?,
+    ///     package("stable::sys-lib/d#2.0.0", &[])?,
+    ///     package("stable::sys-lib/d#1.0.0", &[])?,
+    /// ]);
+    ///
+    /// let solution = Solver::from(&packages).solve(&[PackageRequirement::parse("sys-lib/a")?])?;
+    /// let order: Vec<_> = solution.install_order().iter().map(|id| id.to_string()).collect();
+    /// assert_eq!(order, vec![
+    ///     "stable::sys-lib/d#1.0.0",
+    ///     "stable::sys-lib/b#1.0.0",
+    ///     "stable::sys-lib/c#1.0.0",
+    ///     "stable::sys-lib/a#1.0.0",
+    /// ]);
+    ///
+    /// // No version of 'd' can satisfy both requirements
+    /// let targets = [
+    ///     PackageRequirement::parse("sys-lib/b#=1.1.0")?,
+    ///     PackageRequirement::parse("sys-lib/c")?,
+    /// ];
+    /// let error = Solver::from(&packages).solve(&targets).unwrap_err();
+    /// assert!(error.to_string().contains("no version of sys-lib/d matches both"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn solve(&self, targets: &[PackageRequirement]) -> Result<Solution, SolverError> {
+        let mut search = Search {
+            source: self.source,
+            installed: &self.installed,
+            candidates: HashMap::new(),
+            steps: 0,
+            conflicts: Vec::new(),
+        };
+
+        let mut state = State::default();
+        for target in targets {
+            state
+                .constraints
+                .entry(requirement_key(target))
+                .or_default()
+                .push((target.clone(), None));
+        }
+
+        let res = search.solve(state);
+        debug!(
+            "searched {} version(s), {} conflict(s) found",
+            search.steps,
+            search.conflicts.len()
+        );
+        match res {
+            Ok(state) => Ok(Solution {
+                roots: targets.iter().map(requirement_key).collect(),
+                packages: state.assignments,
+            }),
+            Err(Failure::Conflict(_)) => {
+                let mut explanation = search
+                    .conflicts
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| "the requirements can't be fulfilled".to_string());
+
+                if search.conflicts.len() > 1 {
+                    explanation += &format!(
+                        " (and {} other conflict{})",
+                        search.conflicts.len() - 1,
+                        if search.conflicts.len() > 2 { "s" } else { "" }
+                    );
+                }
+                Err(SolverErrorKind::Unsatisfiable(explanation).into())
+            }
+            Err(Failure::Error(e)) => Err(e),
+        }
+    }
+}
+
+/// The state of a search for a solution
+struct Search<'a, S: PackageSource> {
+    source: &'a S,
+    installed: &'a [PackageID],
+    candidates: HashMap<PackageKey, Vec<Candidate>>,
+    steps: usize,
+    conflicts: Vec<String>,
+}
+
+impl<'a, S: PackageSource> Search<'a, S> {
+    /// Returns all the versions of the package targeted by the given requirement, the installed one first
+    fn candidates(&mut self, requirement: &PackageRequirement) -> Result<&[Candidate], Failure> {
+        let key = requirement_key(requirement);
+
+        if !self.candidates.contains_key(&key) {
+            let mut candidates = self.source.candidates(requirement).map_err(|_| {
+                Failure::Error(SolverErrorKind::SourceError(requirement.to_string()).into())
+            })?;
+
+            if let Some(installed) = self.installed.iter().find(|id| package_key(id) == key) {
+                if let Some(pos) = candidates.iter().position(|c| c.id() == installed) {
+                    let candidate = candidates.remove(pos);
+                    candidates.insert(0, candidate);
+                }
+            }
+            self.candidates.insert(key.clone(), candidates);
+        }
+        Ok(&self.candidates[&key])
+    }
+
+    /// Returns the versions of a package fulfilling all the given constraints
+    fn matching_candidates(
+        &mut self,
+        constraints: &[Constraint],
+    ) -> Result<Vec<Candidate>, Failure> {
+        Ok(self
+            .candidates(&constraints[0].0)?
+            .iter()
+            .filter(|candidate| {
+                constraints
+                    .iter()
+                    .all(|(req, _)| req.matches(candidate.id()))
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Records the explanation of a conflict between the given constraints
+    fn record_conflict(&mut self, key: &PackageKey, constraints: &[Constraint]) {
+        let requirements: Vec<_> = constraints
+            .iter()
+            .map(|(requirement, by)| match by {
+                Some(by) => format!("'{}' (required by {})", requirement, by),
+                None => format!("'{}' (requested)", requirement),
+            })
+            .collect();
+
+        let explanation = if requirements.len() == 1 {
+            format!(
+                "no version of {}/{} matches {}",
+                key.0.as_str(),
+                key.1.as_str(),
+                requirements[0]
+            )
+        } else {
+            format!(
+                "no version of {}/{} matches both {}",
+                key.0.as_str(),
+                key.1.as_str(),
+                requirements.join(" and ")
+            )
+        };
+        trace!("conflict: {}", explanation);
+        self.conflicts.push(explanation);
+    }
+
+    fn solve(&mut self, state: State) -> Result<State, Failure> {
+        // Pick the undecided package with the fewest versions left
+        let mut next = None;
+        for (key, constraints) in &state.constraints {
+            if constraints.is_empty() || state.assignments.contains_key(key) {
+                continue;
+            }
+
+            let matching = self.matching_candidates(constraints)?;
+            if next
+                .as_ref()
+                .map_or(true, |(_, best): &(PackageKey, Vec<Candidate>)| {
+                    matching.len() < best.len()
+                })
+            {
+                next = Some((key.clone(), matching));
+            }
+        }
+
+        // Every needed package has been decided
+        let (key, matching) = match next {
+            Some(next) => next,
+            None => return Ok(state),
+        };
+
+        // The packages that restricted the versions of this one are responsible for any failure
+        let culprits: BTreeSet<_> = state.constraints[&key]
+            .iter()
+            .filter_map(|(_, by)| by.as_ref().map(package_key))
+            .collect();
+
+        if matching.is_empty() {
+            self.record_conflict(&key, &state.constraints[&key]);
+            return Err(Failure::Conflict(culprits));
+        }
+
+        let mut conflict_set = culprits;
+        for candidate in matching {
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                return Err(Failure::Error(SolverErrorKind::SearchLimitReached.into()));
+            }
+
+            // Check the requirements of the candidate against the packages already decided
+            let incompatible: Vec<_> = candidate
+                .dependencies()
+                .iter()
+                .filter_map(|dep| {
+                    let dep_key = requirement_key(dep);
+                    state
+                        .assignments
+                        .get(&dep_key)
+                        .filter(|assigned| !dep.matches(assigned.id()))
+                        .map(|_| dep_key)
+                })
+                .collect();
+
+            if !incompatible.is_empty() {
+                for dep_key in incompatible {
+                    let mut constraints = state.constraints[&dep_key].clone();
+                    constraints.extend(
+                        candidate
+                            .dependencies()
+                            .iter()
+                            .filter(|dep| requirement_key(dep) == dep_key)
+                            .map(|dep| (dep.clone(), Some(candidate.id().clone()))),
+                    );
+                    self.record_conflict(&dep_key, &constraints);
+                    conflict_set.insert(dep_key);
+                }
+                continue;
+            }
+
+            trace!("trying {}", candidate.id());
+            let mut next_state = state.clone();
+            for dep in candidate.dependencies() {
+                next_state
+                    .constraints
+                    .entry(requirement_key(dep))
+                    .or_default()
+                    .push((dep.clone(), Some(candidate.id().clone())));
+            }
+            next_state.assignments.insert(key.clone(), candidate);
+
+            match self.solve(next_state) {
+                Ok(solution) => return Ok(solution),
+                Err(Failure::Conflict(set)) => {
+                    // The conflict doesn't involve this package: trying another version is pointless
+                    if !set.contains(&key) {
+                        return Err(Failure::Conflict(set));
+                    }
+                    conflict_set.extend(set.into_iter().filter(|k| *k != key));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Failure::Conflict(conflict_set))
+    }
+}
+
+/// A consistent set of package versions, as found by the [`Solver`]
+#[derive(Clone, Debug)]
+pub struct Solution {
+    roots: Vec<PackageKey>,
+    packages: BTreeMap<PackageKey, Candidate>,
+}
+
+impl Solution {
+    /// Returns an iterator over the selected packages
+    #[inline]
+    pub fn packages(&self) -> impl Iterator<Item = &PackageID> {
+        self.packages.values().map(Candidate::id)
+    }
+
+    /// Returns the selected packages, ordered so that the dependencies of a package come before it
+    pub fn install_order(&self) -> Vec<&PackageID> {
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+
+        for root in &self.roots {
+            self.visit(root, &mut visited, &mut order);
+        }
+        order
+    }
+
+    fn visit<'a>(
+        &'a self,
+        key: &PackageKey,
+        visited: &mut HashSet<PackageKey>,
+        order: &mut Vec<&'a PackageID>,
+    ) {
+        if !visited.insert(key.clone()) {
+            return;
+        }
+
+        if let Some(candidate) = self.packages.get(key) {
+            for dependency in candidate.dependencies() {
+                self.visit(&requirement_key(dependency), visited, order);
+            }
+            order.push(candidate.id());
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Sources of the packages considered by the solver
+
+use failure::Error;
+
+use crate::cache::available::{AvailablePackages, AvailablePackagesCacheQueryStrategy};
+use crate::package::{PackageID, PackageRequirement};
+
+/// A version of a package that can be selected by the solver, along with its dependencies
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Candidate {
+    id: PackageID,
+    dependencies: Vec<PackageRequirement>,
+}
+
+impl Candidate {
+    /// Creates a [`Candidate`] from a [`PackageID`] and the requirements of this package
+    #[inline]
+    pub fn from(id: PackageID, dependencies: Vec<PackageRequirement>) -> Self {
+        Self { id, dependencies }
+    }
+
+    /// Returns a reference over the [`PackageID`] of this candidate
+    #[inline]
+    pub fn id(&self) -> &PackageID {
+        &self.id
+    }
+
+    /// Returns a reference over the requirements of this candidate
+    #[inline]
+    pub fn dependencies(&self) -> &Vec<PackageRequirement> {
+        &self.dependencies
+    }
+}
+
+/// Trait for the types providing the packages considered by the solver
+pub trait PackageSource {
+    /// Returns all the versions of the package targeted by the given requirement, whatever its
+    /// version requirement, sorted from the most to the least preferred (usually the most recent first).
+    fn candidates(&self, requirement: &PackageRequirement) -> Result<Vec<Candidate>, Error>;
+}
+
+/// Returns whether a package targeted by the given requirement is available, whatever its version.
+///
+/// Requirements on virtual packages target no available package: they are fulfilled by the package providing them.
+pub(crate) fn is_available(
+    available: &AvailablePackages,
+    requirement: &PackageRequirement,
+) -> Result<bool, Error> {
+    let results = available
+        .query(&requirement.clone().any_version().into())
+        .set_strategy(AvailablePackagesCacheQueryStrategy::BestMatch)
+        .perform()?;
+    Ok(!results.is_empty())
+}
+
+/// The candidates are the versions of the package available in the enabled repositories.
+///
+/// The requirements on virtual packages are left out of their dependencies, as the solver doesn't select the package
+/// providing them.
+impl<'a, 'b> PackageSource for AvailablePackages<'a, 'b> {
+    fn candidates(&self, requirement: &PackageRequirement) -> Result<Vec<Candidate>, Error> {
+        let results = self
+            .query(&requirement.clone().any_version().into())
+            .set_strategy(AvailablePackagesCacheQueryStrategy::AllMatchesSorted)
+            .perform()?;
+
+        let mut candidates = Vec::new();
+        for result in results {
+            let mut dependencies = Vec::new();
+            for dependency in result.manifest().dependencies() {
+                if is_available(self, dependency)? {
+                    dependencies.push(dependency.clone());
+                }
+            }
+            dependencies.sort();
+            candidates.push(Candidate::from(result.id(), dependencies));
+        }
+        Ok(candidates)
+    }
+}
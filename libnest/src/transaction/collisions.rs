@@ -0,0 +1,163 @@
+//! Detection of the files that would be owned by several packages once a set of transactions is performed.
+//!
+//! The files of the packages to install are compared against the log of installed files of every installed
+//! package, and against each other, before anything is extracted, so that a transaction overwriting the files
+//! of another package can be refused as a whole.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use failure::{Error, ResultExt};
+use tar::Archive;
+
+use crate::chroot::Chroot;
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{Kind, PackageID};
+
+use super::extract::unexplorable_package;
+use super::{InstallErrorKind, Transaction};
+
+/// A file that a package to install would share with another package
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct FileCollision {
+    path: PathBuf,
+    package: PackageID,
+    owner: PackageID,
+}
+
+impl FileCollision {
+    /// Returns the path of the file, as an absolute path relative to the installation root
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the package to install containing the file
+    #[inline]
+    pub fn package(&self) -> &PackageID {
+        &self.package
+    }
+
+    /// Returns the package already owning the file: an installed package, or another package to install
+    #[inline]
+    pub fn owner(&self) -> &PackageID {
+        &self.owner
+    }
+}
+
+/// Returns the paths of the files, other than directories, contained in the data of the given downloaded package
+fn package_files(
+    config: &Config,
+    lock_ownership: &LockFileOwnership,
+    package: &PackageID,
+) -> Result<Vec<PathBuf>, Error> {
+    let npf_explorer = config
+        .downloaded_packages_cache(lock_ownership)
+        .explore_package(package)
+        .map_err(unexplorable_package)?;
+    let mut files = Vec::new();
+    let prefix = config
+        .architecture_preferences()
+        .installation_prefix(npf_explorer.manifest().architecture());
+
+    if npf_explorer.manifest().kind() == Kind::Effective {
+        let mut tarball = npf_explorer
+            .open_data()
+            .map_err(|_| InstallErrorKind::InvalidPackageFile)?
+            .unwrap();
+        let mut archive = Archive::new(
+            npf_explorer
+                .manifest()
+                .compression()
+                .decoder(&mut tarball)
+                .map_err(|_| InstallErrorKind::InvalidPackageData)?,
+        );
+
+        for entry in archive
+            .entries()
+            .map_err(|_| InstallErrorKind::InvalidPackageData)?
+        {
+            let entry = entry.map_err(|_| InstallErrorKind::InvalidPackageData)?;
+
+            if !entry.header().entry_type().is_dir() {
+                let path = entry
+                    .path()
+                    .map_err(|_| InstallErrorKind::InvalidPackageData)?;
+                files.push(prefix.with_content(&path));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Looks for the files that would be owned by several packages once the given transactions are performed.
+///
+/// The files of the packages removed or upgraded by the transactions are considered gone. Packages that aren't
+/// downloaded yet are ignored, as their files are unknown.
+pub fn find_file_collisions(
+    config: &Config,
+    lock_ownership: &LockFileOwnership,
+    transactions: &[Transaction],
+) -> Result<Vec<FileCollision>, Error> {
+    let mut removed = HashSet::new();
+    let mut targets = Vec::new();
+
+    for transaction in transactions {
+        match transaction {
+            Transaction::Install(install) => targets.push(install.target()),
+            Transaction::Remove(remove) => {
+                removed.insert(remove.target());
+            }
+            Transaction::Upgrade(upgrade) => {
+                removed.insert(upgrade.old_target());
+                targets.push(upgrade.new_target());
+            }
+            Transaction::Reinstall(reinstall) => targets.push(reinstall.target()),
+            Transaction::Pull(_) => (),
+        }
+    }
+
+    // The owner of each file of the installed packages that are kept
+    let installed_packages = config.installed_packages_cache(lock_ownership);
+    let mut owners: HashMap<PathBuf, PackageID> = HashMap::new();
+
+    for package in installed_packages.packages()? {
+        if removed.contains(&package) {
+            continue;
+        }
+
+        let log = installed_packages
+            .package_log(&package)
+            .with_context(|_| package.to_string())?;
+        for entry in log.files() {
+            if !entry.file_type().is_dir() {
+                owners.insert(entry.path().to_path_buf(), package.clone());
+            }
+        }
+    }
+
+    let downloaded_packages = config.downloaded_packages_cache(lock_ownership);
+    let mut collisions = Vec::new();
+
+    for target in targets {
+        if !downloaded_packages.has_package(target) {
+            continue;
+        }
+
+        for path in package_files(config, lock_ownership, target)? {
+            match owners.get(&path) {
+                Some(owner) if owner != target => collisions.push(FileCollision {
+                    path,
+                    package: target.clone(),
+                    owner: owner.clone(),
+                }),
+                Some(_) => (),
+                None => {
+                    owners.insert(path, target.clone());
+                }
+            }
+        }
+    }
+    Ok(collisions)
+}
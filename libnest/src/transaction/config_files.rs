@@ -0,0 +1,105 @@
+//! Handling of the configuration files modified locally, which upgrades keep instead of overwriting them.
+//!
+//! Configuration files are the files in `/etc`, and those listed in the `backup` field of the manifest of
+//! either version of the upgraded package. When the new version of such a file differs from both the installed
+//! one and the local one, it is installed next to the local one, with the `.nest-new` suffix, to be merged.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::cache::installed::log::FileType;
+use crate::chroot::Chroot;
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{Checksum, Manifest, PackageID};
+
+use super::{RemoveError, RemoveErrorKind::*};
+
+/// The checksums of a configuration file modified locally
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub(crate) struct ModifiedConfigFile {
+    installed: Checksum,
+    current: Checksum,
+}
+
+/// The configuration files modified locally, indexed by their absolute path, relative to the installation root
+pub(crate) type ModifiedConfigFiles = HashMap<PathBuf, ModifiedConfigFile>;
+
+/// What to do with a file of the package being installed
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub(crate) enum ConfigFileAction {
+    /// The file isn't a configuration file modified locally, it is extracted as usual
+    Extract,
+
+    /// The local version of the file is kept as is, as the new version doesn't bring any change
+    Keep,
+
+    /// The local version of the file is kept, and the new version is extracted next to it
+    ExtractNew,
+}
+
+/// Returns whether the given path is a configuration file of a package with one of the given manifests
+pub(crate) fn is_config_file(path: &Path, manifests: &[&Manifest]) -> bool {
+    path.starts_with("/etc")
+        || manifests
+            .iter()
+            .any(|manifest| manifest.backup().iter().any(|backup| backup == path))
+}
+
+/// Returns the configuration files installed by the given package that were modified since then.
+///
+/// Files whose checksum wasn't recorded when they were installed, or that can't be read, are considered unmodified.
+pub(crate) fn find_modified_config_files(
+    config: &Config,
+    lock_ownership: &LockFileOwnership,
+    target_id: &PackageID,
+    manifests: &[&Manifest],
+) -> Result<ModifiedConfigFiles, RemoveError> {
+    let log = config
+        .installed_packages_cache(lock_ownership)
+        .package_log(target_id)
+        .map_err(LogFileLoadError)?;
+    let mut modified = HashMap::new();
+
+    for entry in log.files() {
+        let installed = match entry.checksum() {
+            Some(checksum) if *entry.file_type() == FileType::File => checksum,
+            _ => continue,
+        };
+        if !is_config_file(entry.path(), manifests) {
+            continue;
+        }
+
+        let path = config.paths().root().with_content(entry.path());
+        let current = File::open(&path).and_then(|mut file| Checksum::from_reader(&mut file));
+        if let Ok(current) = current {
+            if current != *installed {
+                modified.insert(
+                    entry.path().to_path_buf(),
+                    ModifiedConfigFile {
+                        installed: installed.clone(),
+                        current,
+                    },
+                );
+            }
+        }
+    }
+    Ok(modified)
+}
+
+/// Decides what to do with the file at the given path of the package being installed, given the checksum of
+/// its new version, if it is a regular file
+pub(crate) fn config_file_action(
+    modified: &ModifiedConfigFiles,
+    path: &Path,
+    new: Option<&Checksum>,
+) -> ConfigFileAction {
+    match (modified.get(path), new) {
+        (None, _) => ConfigFileAction::Extract,
+        (Some(file), Some(new)) if *new == file.installed || *new == file.current => {
+            ConfigFileAction::Keep
+        }
+        (Some(_), _) => ConfigFileAction::ExtractNew,
+    }
+}
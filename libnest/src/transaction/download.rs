@@ -1,45 +1,498 @@
-use std::fs::{self, File};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
 use std::io::{Seek, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use failure::{Error, ResultExt};
+use failure::{format_err, Error, ResultExt};
+use log::{debug, info, warn};
+use url::Url;
 
 use crate::config::Config;
-use crate::package::PackageID;
+use crate::package::{NPFDelta, PackageID, RepositoryName, SegmentChecksums};
 
 /// Structure representing a package download
 #[derive(Clone, Hash, Debug)]
-pub struct PackageDownload(PackageID);
+pub struct PackageDownload {
+    target: PackageID,
+    delta_base: Option<PackageID>,
+    torrent: Option<Url>,
+    segments: Option<SegmentChecksums>,
+}
 
 impl PackageDownload {
     /// Create a download from a [`PackageID`]
     pub fn from(target: PackageID) -> Self {
-        Self(target)
+        Self {
+            target,
+            delta_base: None,
+            torrent: None,
+            segments: None,
+        }
+    }
+
+    /// Sets the package whose NPF may be used as a base to download the target package as a delta
+    #[inline]
+    pub fn with_delta_base(mut self, delta_base: PackageID) -> Self {
+        self.delta_base = Some(delta_base);
+        self
+    }
+
+    /// Sets the magnet URI of the torrent the target package may be downloaded with, instead of the mirrors
+    #[inline]
+    pub fn with_torrent(mut self, torrent: Url) -> Self {
+        self.torrent = Some(torrent);
+        self
+    }
+
+    /// Sets the checksums of the segments of the target package's NPF, letting them be downloaded from several
+    /// mirrors at once
+    #[inline]
+    pub fn with_segments(mut self, segments: SegmentChecksums) -> Self {
+        self.segments = Some(segments);
+        self
     }
 
     /// Retrieves the target package for this download
     pub fn target(&self) -> &PackageID {
-        &self.0
+        &self.target
     }
 
-    /// Creates the download file and returns a handle to it
-    pub fn create_download_file(&self, config: &Config) -> Result<(impl Write + Seek), Error> {
-        // Create target folder and destination file
-        let npf_path = config
+    /// Returns the package whose NPF may be used as a base to download the target package as a delta, if any
+    #[inline]
+    pub fn delta_base(&self) -> Option<&PackageID> {
+        self.delta_base.as_ref()
+    }
+
+    /// Returns the magnet URI of the torrent the target package may be downloaded with, if any
+    #[inline]
+    pub fn torrent(&self) -> Option<&Url> {
+        self.torrent.as_ref()
+    }
+
+    /// Returns the checksums of the segments of the target package's NPF, if its repository advertises them
+    #[inline]
+    pub fn segments(&self) -> Option<&SegmentChecksums> {
+        self.segments.as_ref()
+    }
+
+    /// Returns whether the target package can be downloaded as a delta, which requires the NPF of the
+    /// base package to still be downloaded
+    pub fn can_use_delta(&self, config: &Config) -> bool {
+        self.delta_base.as_ref().map_or(false, |base| {
+            PackageDownload::from(base.clone())
+                .download_path(config)
+                .is_file()
+        })
+    }
+
+    /// Returns the path of the folder holding the downloaded NPFs of the target package
+    fn download_dir(&self, config: &Config) -> PathBuf {
+        config
             .paths()
             .downloaded()
             .join(self.target().repository().as_str())
             .join(self.target().category().as_str())
-            .join(self.target().name().as_str());
-        fs::create_dir_all(&npf_path).with_context(|_| npf_path.display().to_string())?;
-        let tarball_path = npf_path.join(format!(
+            .join(self.target().name().as_str())
+    }
+
+    /// Returns the path the target package's NPF is downloaded to
+    fn download_path(&self, config: &Config) -> PathBuf {
+        self.download_dir(config).join(format!(
             "{}-{}.nest",
             self.target().name(),
             self.target().version()
-        ));
+        ))
+    }
+
+    /// Returns the path of the partial NPF, where the data is written until the download is complete
+    fn partial_download_path(&self, config: &Config) -> PathBuf {
+        self.download_dir(config).join(format!(
+            "{}-{}.nest.part",
+            self.target().name(),
+            self.target().version()
+        ))
+    }
+
+    /// Creates the download file and returns a handle to it
+    pub fn create_download_file(&self, config: &Config) -> Result<(impl Write + Seek), Error> {
+        // Create target folder and destination file
+        let npf_path = self.download_dir(config);
+        fs::create_dir_all(&npf_path).with_context(|_| npf_path.display().to_string())?;
+        let tarball_path = self.download_path(config);
 
         // Open the destination file and return it as the writer handle
         let tarball_file =
             File::create(&tarball_path).with_context(|_| tarball_path.display().to_string())?;
         Ok(tarball_file)
     }
+
+    /// Opens the partial download file, creating it if needed, and returns a handle to it.
+    ///
+    /// The content of a previous interrupted download is kept, so that the download can be resumed.
+    pub fn open_partial_download_file(&self, config: &Config) -> Result<File, Error> {
+        let npf_path = self.download_dir(config);
+        fs::create_dir_all(&npf_path).with_context(|_| npf_path.display().to_string())?;
+        let partial_path = self.partial_download_path(config);
+
+        let partial_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&partial_path)
+            .with_context(|_| partial_path.display().to_string())?;
+        Ok(partial_file)
+    }
+
+    /// Returns the path the delta between the base package's NPF and the target package's NPF is downloaded to
+    fn delta_download_path(&self, config: &Config) -> PathBuf {
+        self.download_dir(config).join(format!(
+            "{}-{}.ndelta",
+            self.target().name(),
+            self.target().version()
+        ))
+    }
+
+    /// Creates the delta download file and returns a handle to it
+    pub fn create_delta_file(&self, config: &Config) -> Result<File, Error> {
+        let npf_path = self.download_dir(config);
+        fs::create_dir_all(&npf_path).with_context(|_| npf_path.display().to_string())?;
+        let delta_path = self.delta_download_path(config);
+
+        let delta_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&delta_path)
+            .with_context(|_| delta_path.display().to_string())?;
+        Ok(delta_file)
+    }
+
+    /// Applies the downloaded delta to the base package's NPF, completing the download of the target package.
+    ///
+    /// The delta file is removed afterwards, whether it could be applied or not.
+    pub fn apply_delta(&self, config: &Config) -> Result<(), Error> {
+        let base = self
+            .delta_base
+            .as_ref()
+            .ok_or_else(|| format_err!("the download of {} has no delta base", self.target))?;
+        let delta_path = self.delta_download_path(config);
+
+        debug!("applying the delta from {} to {}", base, self.target);
+        let res = NPFDelta::apply(
+            &delta_path,
+            base,
+            PackageDownload::from(base.clone()).download_path(config),
+            self.partial_download_path(config),
+        );
+        let _ = fs::remove_file(&delta_path);
+
+        let delta = res?;
+        if *delta.to() != self.target {
+            let _ = fs::remove_file(self.partial_download_path(config));
+            return Err(format_err!(
+                "the downloaded delta leads to {} instead of {}",
+                delta.to(),
+                self.target
+            ));
+        }
+        self.complete_download(config)
+    }
+
+    /// Marks the download as complete, moving the partial download file to its final location
+    pub fn complete_download(&self, config: &Config) -> Result<(), Error> {
+        let partial_path = self.partial_download_path(config);
+        fs::rename(&partial_path, self.download_path(config))
+            .with_context(|_| partial_path.display().to_string())?;
+        info!("downloaded {}", self.target);
+        Ok(())
+    }
+}
+
+/// A flag shared by downloads running concurrently, raised to ask them to stop as soon as possible.
+///
+/// Cancellation is cooperative: the downloads are expected to check the flag regularly, and to fail
+/// once it is raised.
+#[derive(Clone, Default, Debug)]
+pub struct DownloadCancellation(Arc<AtomicBool>);
+
+impl DownloadCancellation {
+    /// Creates a flag that isn't raised
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raises the flag, asking the downloads sharing it to stop
+    #[inline]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether the flag was raised
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// The state of a [`DownloadThrottle`]: the number of bytes that can be received right away, which can be
+/// negative when the downloads are ahead of the rate, and when it was last refilled
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// A token bucket limiting the rate of the downloads sharing it.
+///
+/// The bucket fills at the given rate, up to one second worth of data, and each received chunk of data takes
+/// its size from it. When the bucket runs dry, the downloads wait until it is refilled enough to cover
+/// the data they received.
+#[derive(Clone, Debug)]
+pub struct DownloadThrottle {
+    rate: u64,
+    bucket: Arc<Mutex<TokenBucket>>,
+}
+
+impl DownloadThrottle {
+    /// Creates a throttle limiting the downloads sharing it to the given rate, in bytes per second.
+    ///
+    /// A rate of `0` is treated as a rate of `1`.
+    pub fn from(rate: u64) -> Self {
+        let rate = rate.max(1);
+
+        Self {
+            rate,
+            bucket: Arc::new(Mutex::new(TokenBucket {
+                tokens: rate as f64,
+                refilled_at: Instant::now(),
+            })),
+        }
+    }
+
+    /// Returns the rate of the throttle, in bytes per second
+    #[inline]
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// Takes the given number of bytes from the bucket, blocking until it holds enough of them
+    pub fn consume(&self, bytes: usize) {
+        let wait = {
+            let mut bucket = self
+                .bucket
+                .lock()
+                .expect("the download throttle is poisoned");
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+
+            bucket.tokens = (bucket.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+            bucket.refilled_at = now;
+            bucket.tokens -= bytes as f64;
+
+            // The debt is repaid by the next refills, so the downloads sharing the bucket wait for it in turn
+            if bucket.tokens < 0.0 {
+                Duration::from_secs_f64(-bucket.tokens / self.rate as f64)
+            } else {
+                Duration::from_secs(0)
+            }
+        };
+        thread::sleep(wait);
+    }
+}
+
+/// Structure scheduling the download of multiple packages, with a limited amount of concurrent downloads.
+///
+/// Repositories can be given their own limit, in which case their downloads are performed next to the others
+/// instead of sharing the global limit with them.
+///
+/// The scheduler doesn't know how to download a package by itself: the actual download is
+/// performed by a function given by the caller, which is called from multiple threads.
+#[derive(Clone, Debug)]
+pub struct DownloadScheduler {
+    downloads: Vec<PackageDownload>,
+    max_parallel_downloads: usize,
+    repository_limits: HashMap<RepositoryName, usize>,
+    cancellation: DownloadCancellation,
+}
+
+impl DownloadScheduler {
+    /// Creates a scheduler for the given downloads, performing at most `max_parallel_downloads` of them at once.
+    ///
+    /// A limit of `0` is treated as a limit of `1`.
+    pub fn from(downloads: Vec<PackageDownload>, max_parallel_downloads: usize) -> Self {
+        Self {
+            downloads,
+            max_parallel_downloads: max_parallel_downloads.max(1),
+            repository_limits: HashMap::new(),
+            cancellation: DownloadCancellation::new(),
+        }
+    }
+
+    /// Performs at most `max_parallel_downloads` downloads from the given repository at once, next to the
+    /// downloads from the other repositories.
+    ///
+    /// A limit of `0` is treated as a limit of `1`.
+    pub fn with_repository_limit(
+        mut self,
+        repository: RepositoryName,
+        max_parallel_downloads: usize,
+    ) -> Self {
+        self.repository_limits
+            .insert(repository, max_parallel_downloads.max(1));
+        self
+    }
+
+    /// Returns a reference over the scheduled downloads
+    #[inline]
+    pub fn downloads(&self) -> &Vec<PackageDownload> {
+        &self.downloads
+    }
+
+    /// Returns the maximum number of downloads performed at the same time
+    #[inline]
+    pub fn max_parallel_downloads(&self) -> usize {
+        self.max_parallel_downloads
+    }
+
+    /// Returns the maximum number of downloads performed at the same time from the given repository, if it has its
+    /// own limit
+    #[inline]
+    pub fn repository_limit(&self, repository: &RepositoryName) -> Option<usize> {
+        self.repository_limits.get(repository).cloned()
+    }
+
+    /// Returns the flag cancelling the scheduled downloads, which the download function should check
+    /// regularly to stop early
+    #[inline]
+    pub fn cancellation(&self) -> &DownloadCancellation {
+        &self.cancellation
+    }
+
+    /// Performs all the scheduled downloads using the given function, and waits for them to end.
+    ///
+    /// When a download fails, the [`DownloadCancellation`] of the scheduler is raised: the downloads that
+    /// haven't started yet are skipped, and the running ones are expected to stop. The first error
+    /// encountered is returned once the running downloads are over.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// use libnest::package::PackageID;
+    /// use libnest::transaction::{DownloadScheduler, PackageDownload};
+    ///
+    /// let downloads = vec![
+    ///     PackageDownload::from(PackageID::parse("stable::sys-bin/coreutils#8.31.0")?),
+    ///     PackageDownload::from(PackageID::parse("stable::sys-bin/bash#5.0.0")?),
+    /// ];
+    ///
+    /// let count = Arc::new(AtomicUsize::new(0));
+    /// let counter = count.clone();
+    /// DownloadScheduler::from(downloads, 4).perform(move |_| {
+    ///     counter.fetch_add(1, Ordering::SeqCst);
+    ///     Ok(())
+    /// })?;
+    /// assert_eq!(count.load(Ordering::SeqCst), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn perform<F>(self, download_fn: F) -> Result<(), Error>
+    where
+        F: Fn(&PackageDownload) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        // The downloads from the repositories with their own limit get their own queue and workers
+        let repository_limits = self.repository_limits;
+        let mut queues: HashMap<Option<RepositoryName>, VecDeque<PackageDownload>> = HashMap::new();
+        for download in self.downloads {
+            let repository = download.target().repository();
+            let group = if repository_limits.contains_key(repository) {
+                Some(repository.clone())
+            } else {
+                None
+            };
+
+            queues.entry(group).or_default().push_back(download);
+        }
+
+        let first_error = Arc::new(Mutex::new(None));
+        let cancellation = self.cancellation;
+        let download_fn = Arc::new(download_fn);
+
+        let mut handles = Vec::new();
+        for (group, queue) in queues {
+            let limit = group
+                .as_ref()
+                .and_then(|repository| repository_limits.get(repository))
+                .cloned()
+                .unwrap_or(self.max_parallel_downloads);
+            let workers = limit.min(queue.len());
+
+            debug!(
+                "downloading {} package(s){} with {} worker(s)",
+                queue.len(),
+                group
+                    .as_ref()
+                    .map(|repository| format!(" from {}", repository))
+                    .unwrap_or_default(),
+                workers
+            );
+            let queue = Arc::new(Mutex::new(queue));
+            handles.extend((0..workers).map(|_| {
+                let queue = queue.clone();
+                let first_error = first_error.clone();
+                let cancellation = cancellation.clone();
+                let download_fn = download_fn.clone();
+
+                thread::spawn(move || {
+                    while !cancellation.is_cancelled() {
+                        let download = queue
+                            .lock()
+                            .expect("the download queue is poisoned")
+                            .pop_front();
+
+                        match download {
+                            Some(download) => {
+                                debug!("downloading {}", download.target());
+                                if let Err(e) = download_fn(&download) {
+                                    warn!("failed to download {}: {}", download.target(), e);
+                                    // The downloads failing because of the cancellation come after the one causing it
+                                    first_error
+                                        .lock()
+                                        .expect("the download error is poisoned")
+                                        .get_or_insert(e);
+                                    cancellation.cancel();
+                                }
+                            }
+                            None => return,
+                        }
+                    }
+                })
+            }));
+        }
+
+        for handle in handles {
+            handle.join().expect("a download thread panicked");
+        }
+
+        let first_error = first_error
+            .lock()
+            .expect("the download error is poisoned")
+            .take();
+        match first_error {
+            Some(e) => Err(e),
+            None if cancellation.is_cancelled() => Err(format_err!("the downloads were cancelled")),
+            None => Ok(()),
+        }
+    }
 }
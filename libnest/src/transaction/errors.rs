@@ -3,6 +3,7 @@
 use failure::{Context, Fail};
 
 use super::ExecutionOutput;
+use crate::package::NPFExplorationError;
 
 /// Error type for errors related to package installation
 #[derive(Debug)]
@@ -25,10 +26,18 @@ pub enum InstallErrorKind {
     #[fail(display = "invalid package file")]
     InvalidPackageFile,
 
-    /// The package could not be installed because the contained data.tar.gz was invalid
+    /// The package could not be installed because its checksum or signature could not be verified
+    #[fail(display = "unable to verify the package: {}", _0)]
+    UnverifiedPackage(#[cause] NPFExplorationError),
+
+    /// The package could not be installed because the contained data archive was invalid
     #[fail(display = "invalid package data")]
     InvalidPackageData,
 
+    /// The package could not be installed because one of the entries of its data archive is unsafe to extract
+    #[fail(display = "{:?}: {}", _0, _1)]
+    UnsafePackageEntry(std::path::PathBuf, #[cause] UnsafeEntry),
+
     /// The package could not be installed because its data could not be extracted
     #[fail(display = "unable to extract")]
     ExtractError(#[cause] std::io::Error),
@@ -37,6 +46,10 @@ pub enum InstallErrorKind {
     #[fail(display = "unable to create the log")]
     LogCreationError(#[cause] std::io::Error),
 
+    /// The package could not be installed because the transaction journal could not be written
+    #[fail(display = "unable to write the transaction journal")]
+    JournalError(#[cause] std::io::Error),
+
     /// The package could not be installed its pre-install instructions returned an error
     #[fail(display = "pre-install instructions reported an error: {}", _0)]
     PreInstallInstructionsFailure(#[cause] InstructionsExecutionError),
@@ -48,6 +61,30 @@ pub enum InstallErrorKind {
 
 use_as_error!(InstallError, InstallErrorKind);
 
+/// The reason an entry of the data archive of a package is refused by the extraction
+#[derive(Debug, Fail)]
+pub enum UnsafeEntry {
+    /// The path of the entry goes up with `..`
+    #[fail(display = "the path leaves the installation root")]
+    PathTraversal,
+
+    /// The entry is a symbolic link pointing outside of the installation root
+    #[fail(display = "the symbolic link to {:?} leaves the installation root", _0)]
+    EscapingSymlink(std::path::PathBuf),
+
+    /// The entry is a hard link to a file outside of the installation root
+    #[fail(display = "the hard link to {:?} leaves the installation root", _0)]
+    EscapingHardLink(std::path::PathBuf),
+
+    /// The entry is a device node or a named pipe, refused for the packages of untrusted repositories
+    #[fail(display = "special files are refused from an untrusted repository")]
+    SpecialFile,
+
+    /// The entry has its setuid or setgid bit set, refused for the packages of untrusted repositories
+    #[fail(display = "setuid and setgid files are refused from an untrusted repository")]
+    SetuidFile,
+}
+
 /// Error type for errors related to package removal
 #[derive(Debug)]
 pub struct RemoveError {
@@ -69,6 +106,10 @@ pub enum RemoveErrorKind {
     #[fail(display = "cannot remove log file")]
     LogFileRemoveError,
 
+    /// The package could not be removed because the transaction journal could not be written
+    #[fail(display = "unable to write the transaction journal")]
+    JournalError(#[cause] std::io::Error),
+
     /// The package could not be removed because the previously downloaded NPF is corrupted
     #[fail(display = "corrupted cached package file")]
     InvalidCachedPackageFile,
@@ -105,9 +146,77 @@ pub enum InstructionsExecutionErrorKind {
     #[fail(display = "cannot execute instructions.sh using the chosen shell")]
     CannotExecuteShell,
 
+    /// The sandbox is required but could not be set up
+    #[fail(display = "cannot set up the sandbox required to execute instructions.sh")]
+    SandboxUnavailable,
+
     /// The invoked script exited with a failure status
     #[fail(display = "script exited with a failure status")]
     FailureExitStatus(ExecutionOutput),
 }
 
 use_as_error!(InstructionsExecutionError, InstructionsExecutionErrorKind);
+
+/// Error type for errors related to transaction hooks
+#[derive(Debug)]
+pub struct HookError {
+    inner: Context<HookErrorKind>,
+}
+
+/// Error kind describing a kind of error related to transaction hooks
+#[derive(Debug, Fail)]
+pub enum HookErrorKind {
+    /// A hook file could not be read
+    #[fail(display = "unable to load the hook")]
+    HookLoadError,
+
+    /// A hook file could not be parsed
+    #[fail(display = "unable to parse the hook")]
+    HookParseError,
+
+    /// The changes made by a transaction could not be computed to find the hooks it triggers
+    #[fail(display = "unable to find the hooks triggered by the transaction")]
+    CannotPlanTransaction,
+
+    /// The command of the hook could not be executed
+    #[fail(display = "cannot execute the hook's command")]
+    CannotExecuteHook,
+
+    /// The command of the hook exited with a failure status
+    #[fail(display = "hook exited with a failure status")]
+    FailureExitStatus(ExecutionOutput),
+}
+
+use_as_error!(HookError, HookErrorKind);
+
+/// Error type for errors related to the snapshots of the system
+#[derive(Debug)]
+pub struct SnapshotError {
+    inner: Context<SnapshotErrorKind>,
+}
+
+/// Error kind describing a kind of error related to the snapshots of the system
+#[derive(Debug, Fail)]
+pub enum SnapshotErrorKind {
+    /// No snapshot backend is configured
+    #[fail(display = "snapshots are disabled")]
+    SnapshotsDisabled,
+
+    /// The `command` backend is used, but the needed command isn't configured
+    #[fail(display = "the {} command of the snapshots isn't configured", _0)]
+    MissingCommand(&'static str),
+
+    /// The folder holding the snapshots could not be created
+    #[fail(display = "unable to create the folder of the snapshots")]
+    CannotCreateDirectory,
+
+    /// The command taking or restoring the snapshot could not be executed
+    #[fail(display = "cannot execute {}", _0)]
+    CannotExecuteCommand(String),
+
+    /// The command taking or restoring the snapshot exited with a failure status
+    #[fail(display = "{} exited with a failure status", _0)]
+    FailureExitStatus(String, ExecutionOutput),
+}
+
+use_as_error!(SnapshotError, SnapshotErrorKind);
@@ -1,97 +1,516 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{Seek, SeekFrom};
-use std::path::Path;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::thread;
 
-use flate2::read::GzDecoder;
+use log::{debug, info};
 use tar::Archive;
 
 use crate::cache::installed::log::{FileLogEntry, Log};
+use crate::cache::installed::merges::new_config_file_path;
+use crate::cache::store::{ContentStore, ObjectAttributes};
 use crate::chroot::Chroot;
 use crate::config::Config;
 use crate::lock_file::LockFileOwnership;
-use crate::package::{Kind, NPFExplorer, PackageID};
+use crate::package::{
+    read_seek_table, Checksum, Compression, Kind, NPFExplorationError, NPFExplorationErrorKind,
+    NPFExplorer, PackageID,
+};
 
-use super::{InstallError, InstallErrorKind::*};
+use super::config_files::{
+    config_file_action, is_config_file, ConfigFileAction, ModifiedConfigFiles,
+};
+use super::journal::{move_file, Journal};
+use super::pipeline::{ReadAhead, WritePool};
+use super::progress::{in_phase, ProgressHandler, TransactionPhase};
+use super::safety::{check_entry, ensure_inside, resolve_in_root};
+use super::{InstallError, InstallErrorKind, InstallErrorKind::*};
 
-/// Extract the package from a given [`NPFExplorer`] as a given [`PackageID`]
+/// Converts the error encountered when opening a downloaded package into an [`InstallErrorKind`]
+pub(crate) fn unexplorable_package(error: NPFExplorationError) -> InstallErrorKind {
+    match error.kind() {
+        NPFExplorationErrorKind::ChecksumMismatch
+        | NPFExplorationErrorKind::UnknownChecksum
+        | NPFExplorationErrorKind::MissingSignature
+        | NPFExplorationErrorKind::InvalidSignature => UnverifiedPackage(error),
+        _ => InvalidPackageFile,
+    }
+}
+
+/// Returns whether the owner, the special permission bits and the extended attributes of the extracted files can
+/// be restored, which requires running as root. They are left out otherwise, like tar does.
+fn can_restore_attributes() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Removes the staging folder and what is left in it
+fn clear_staging(staging: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(staging) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+/// Removes the file or symbolic link already at the given path, if any, keeping a backup of it in the journal so
+/// that a rollback restores it, whether a package owns it or not. Folders are left in place, as they are shared.
+fn back_up_existing(journal: &mut Journal, path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(ref metadata) if !metadata.is_dir() => journal.remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+/// The size from which regular files are written by the thread reading the data archive, instead of being
+/// handed over to the writing threads
+const MAX_BATCHED_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// The total size from which a batch of files is handed over to the writing threads
+const MAX_BATCH_SIZE: usize = 1024 * 1024;
+
+/// The number of files from which a batch of files is handed over to the writing threads
+const MAX_BATCH_FILES: usize = 64;
+
+/// A regular file read from the data archive, waiting to be written by one of the writing threads
+#[derive(Debug)]
+struct BatchedFile {
+    staged: PathBuf,
+    data: Vec<u8>,
+    checksum: Checksum,
+    attributes: ObjectAttributes,
+    mtime: u64,
+}
+
+/// A file of a package extracted in the staging folder, waiting to be moved in place
+#[derive(Debug)]
+enum StagedFile {
+    /// A regular file, extracted at the given location of the staging folder
+    Regular { staged: PathBuf, path: PathBuf },
+
+    /// A hard link to the given file of the package, created once its target is in place
+    HardLink { target: PathBuf, path: PathBuf },
+}
+
+/// The files of a package to extract, and where to extract them
+struct Unpacking<'a> {
+    config: &'a Config,
+    prefix: &'a Path,
+    install_dir: &'a Path,
+    staging: &'a Path,
+    actions: &'a HashMap<PathBuf, ConfigFileAction>,
+    stored: &'a HashMap<PathBuf, Checksum>,
+    store: &'a ContentStore<'a, 'a>,
+    restore_attributes: bool,
+    target_id: &'a PackageID,
+    total: usize,
+    progress: &'a dyn ProgressHandler,
+}
+
+impl<'a> Unpacking<'a> {
+    /// Hands the given batch of files over to the writing threads of the given pool, which place them in the
+    /// content store and hardlink them from there in the staging folder
+    fn submit(&self, pool: &WritePool<'a>, batch: Vec<BatchedFile>) -> io::Result<()> {
+        let store = self.store;
+        let staging = self.staging;
+
+        pool.submit(Box::new(move || {
+            for file in batch {
+                let object =
+                    store.insert_data(&file.data, &file.checksum, &file.attributes, file.mtime)?;
+                store.link(&object, &file.attributes, staging, &file.staged)?;
+            }
+            Ok(())
+        }))
+    }
+
+    /// Unpacks the entries of the given data archive, returning the files waiting in the staging folder.
+    ///
+    /// Folders, symbolic links and special files are created in place right away, journaling each of them before it
+    /// is created. Regular files are extracted in the staging folder, and hard links are left for when their target
+    /// is in place: they are moved in place by [`Unpacking::commit()`] once the whole archive is extracted, so that
+    /// no partially written file is ever visible.
+    ///
+    /// Regular files are placed in the content store, and hardlinked from there. If a [`WritePool`] is given, the
+    /// smallest ones are written by its threads, in batches, while the next entries are read.
+    fn unpack<R: Read>(
+        &self,
+        mut archive: Archive<R>,
+        journal: &mut Journal,
+        pool: Option<&WritePool<'a>>,
+    ) -> io::Result<Vec<StagedFile>> {
+        let mut staged_files = Vec::new();
+        let mut batch = Vec::new();
+        let mut batch_size = 0;
+
+        archive.set_preserve_ownerships(self.restore_attributes);
+        archive.set_preserve_permissions(self.restore_attributes);
+        archive.set_unpack_xattrs(self.restore_attributes);
+        for (i, entry) in archive.entries()?.enumerate() {
+            let mut entry = entry?;
+            let abs_path = self.prefix.with_content(&entry.path()?);
+            let rel_path = self.config.paths().root().with_content(&abs_path);
+            let entry_type = entry.header().entry_type();
+            let staged = self.staging.join(i.to_string());
+
+            match self.actions.get(&abs_path) {
+                Some(ConfigFileAction::Keep) => (),
+                Some(ConfigFileAction::ExtractNew) => {
+                    // Replace the new version left by a previous upgrade, if it wasn't merged
+                    let new_path = new_config_file_path(&rel_path);
+                    if fs::symlink_metadata(&new_path).is_ok() {
+                        journal.remove_file(&new_path)?;
+                    }
+                    entry.unpack(&staged)?;
+                    staged_files.push(StagedFile::Regular {
+                        staged,
+                        path: new_path,
+                    });
+                }
+                _ if entry_type.is_file() => {
+                    let size = entry.header().size()?;
+
+                    match (self.stored.get(&abs_path), pool) {
+                        (Some(checksum), Some(pool)) if size < MAX_BATCHED_FILE_SIZE => {
+                            let mut data = Vec::with_capacity(size as usize);
+                            entry.read_to_end(&mut data)?;
+                            batch_size += data.len();
+                            batch.push(BatchedFile {
+                                staged: staged.clone(),
+                                data,
+                                checksum: *checksum,
+                                attributes: ObjectAttributes::from_entry(
+                                    &mut entry,
+                                    self.restore_attributes,
+                                )?,
+                                mtime: entry.header().mtime()?,
+                            });
+
+                            if batch_size >= MAX_BATCH_SIZE || batch.len() >= MAX_BATCH_FILES {
+                                self.submit(pool, mem::replace(&mut batch, Vec::new()))?;
+                                batch_size = 0;
+                            }
+                        }
+                        (Some(checksum), _) => {
+                            let attributes =
+                                ObjectAttributes::from_entry(&mut entry, self.restore_attributes)?;
+                            let object = self.store.insert(&mut entry, checksum, &attributes)?;
+                            self.store
+                                .link(&object, &attributes, self.staging, &staged)?;
+                        }
+                        // Configuration files are edited in place, so they don't go through the store
+                        (None, _) => {
+                            entry.unpack(&staged)?;
+                        }
+                    }
+                    staged_files.push(StagedFile::Regular {
+                        staged,
+                        path: rel_path,
+                    });
+                }
+                _ if entry_type.is_hard_link() => {
+                    if let Some(target) = entry.link_name()? {
+                        let target = self
+                            .config
+                            .paths()
+                            .root()
+                            .with_content(self.prefix.with_content(&target));
+                        staged_files.push(StagedFile::HardLink {
+                            target,
+                            path: rel_path,
+                        });
+                    }
+                }
+                _ => {
+                    if !entry_type.is_dir() {
+                        back_up_existing(journal, &rel_path)?;
+                    }
+                    journal.record_creation(&rel_path)?;
+                    entry.unpack_in(self.install_dir)?;
+                }
+            }
+            self.progress
+                .extraction_progress(self.target_id, i + 1, self.total);
+        }
+
+        if let Some(pool) = pool.filter(|_| !batch.is_empty()) {
+            self.submit(pool, batch)?;
+        }
+        Ok(staged_files)
+    }
+
+    /// Moves the given files from the staging folder in place, in the order of the data archive, journaling each of
+    /// them before it is moved. The files they replace are backed up in the journal.
+    ///
+    /// Hard links may only target the files of the package moved in place before them, once the symbolic links of
+    /// their target are resolved, so that no file of the host is shared with the package.
+    fn commit(&self, staged_files: Vec<StagedFile>, journal: &mut Journal) -> io::Result<()> {
+        let root = self.config.paths().root();
+        let install_dir = resolve_in_root(root, self.install_dir)?;
+        let mut placed = HashSet::new();
+
+        for file in staged_files {
+            let path = match &file {
+                StagedFile::Regular { path, .. } | StagedFile::HardLink { path, .. } => path,
+            };
+
+            ensure_inside(root, &install_dir, path)?;
+            back_up_existing(journal, path)?;
+            journal.record_creation(path)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            match &file {
+                StagedFile::Regular { staged, path } => {
+                    move_file(staged, path)?;
+                    placed.insert(resolve_in_root(root, path)?);
+                }
+                StagedFile::HardLink { target, path } => {
+                    let target = resolve_linked_file(root, &install_dir, target, &placed)?;
+                    fs::hard_link(target, path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the target of a hard link of a package with [`resolve_in_root()`], checking that it is one of the given
+/// files of the package already in place, in the given folder
+fn resolve_linked_file(
+    root: &Path,
+    install_dir: &Path,
+    target: &Path,
+    placed: &HashSet<PathBuf>,
+) -> io::Result<PathBuf> {
+    ensure_inside(root, install_dir, target)?;
+
+    let resolved = resolve_in_root(root, target)?;
+    if !resolved.starts_with(install_dir) || !placed.contains(&resolved) {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "the hard link target {} is not a file of the package",
+                target.display()
+            ),
+        ));
+    }
+    Ok(resolved)
+}
+
+/// Extract the package from a given [`NPFExplorer`] as a given [`PackageID`],
+/// recording every created file in the given [`Journal`].
+///
+/// The given configuration files modified locally are kept, and their new version is extracted next to
+/// them if it differs.
+///
+/// Packages of a foreign architecture are extracted in the folder of their architecture, and their instructions
+/// aren't run, like those of the packages installed in a sysroot.
+///
+/// The progress of the extraction is reported to the given [`ProgressHandler`].
 pub(crate) fn extract_package(
     config: &Config,
     lock_ownership: &LockFileOwnership,
     npf_explorer: NPFExplorer,
     target_id: &PackageID,
+    modified: &ModifiedConfigFiles,
+    journal: &mut Journal,
+    progress: &dyn ProgressHandler,
 ) -> Result<(), InstallError> {
+    let architectures = config.architecture_preferences();
+    let architecture = npf_explorer.manifest().architecture();
+    let prefix = architectures.installation_prefix(architecture);
+    let install_dir = config.paths().root().with_content(&prefix);
+
     let instructions_handle = npf_explorer
         .load_instructions()
-        .map_err(|_| InvalidPackageFile)?;
+        .map_err(|_| InvalidPackageFile)?
+        .filter(|_| config.runs_instructions(architecture))
+        .map(|executor| {
+            executor.with_sandboxing(config.instructions_sandboxing(target_id.repository()))
+        });
+    if !config.runs_instructions(architecture) {
+        debug!("the instructions of {} aren't run", target_id);
+    }
 
     if let Some(executor) = &instructions_handle {
-        executor
-            .execute_before_install(config.paths().root())
-            .map_err(PreInstallInstructionsFailure)?;
+        in_phase(progress, target_id, TransactionPhase::Instructions, || {
+            executor.execute_before_install(config.paths().root())
+        })
+        .map_err(PreInstallInstructionsFailure)?;
     }
 
     if npf_explorer.manifest().kind() == Kind::Effective {
-        let tarball_handle = npf_explorer
+        let mut tarball = npf_explorer
             .open_data()
             .map_err(|_| InvalidPackageFile)?
             .unwrap();
 
-        let mut tarball = tarball_handle.file();
-        let mut archive = Archive::new(GzDecoder::new(tarball));
-        let mut files = Vec::new();
+        let compression = npf_explorer.manifest().compression();
+        let mut actions = HashMap::new();
+        let mut stored = HashMap::new();
+        let mut installed_size = 0;
+        let trust = config.repository_trust(target_id.repository());
+        let files = {
+            let mut archive = Archive::new(
+                compression
+                    .decoder(&mut tarball)
+                    .map_err(|_| InvalidPackageData)?,
+            );
+            let mut files = Vec::new();
 
-        // List all the files in the archive and check whether they already exist
-        for entry in archive.entries().map_err(|_| InvalidPackageData)? {
-            let entry = entry.map_err(|_| InvalidPackageData)?;
-            let entry_path = entry.path().map_err(|_| InvalidPackageData)?;
-            let entry_type = entry.header().entry_type();
+            // List all the files in the archive and check whether they already exist
+            for entry in archive.entries().map_err(|_| InvalidPackageData)? {
+                let mut entry = entry.map_err(|_| InvalidPackageData)?;
+                let entry_path = entry.path().map_err(|_| InvalidPackageData)?.into_owned();
+                let entry_type = entry.header().entry_type();
+                let mode = entry.header().mode().map_err(|_| InvalidPackageData)?;
+                let mtime = entry.header().mtime().map_err(|_| InvalidPackageData)?;
+
+                let abs_path = prefix.with_content(&entry_path);
+                let rel_path = config.paths().root().with_content(&abs_path);
+                check_entry(&entry, &abs_path, trust)?;
 
-            let abs_path = Path::new("/").with_content(&entry_path);
-            let rel_path = config.paths().root().with_content(&entry_path);
+                // Record the state of the file as it will be installed, so that it can be verified later
+                let mut log_entry = FileLogEntry::new(abs_path.to_path_buf(), entry_type.into())
+                    .with_metadata(mode, mtime);
+                if entry_type.is_file() {
+                    let checksum =
+                        Checksum::from_reader(&mut entry).map_err(|_| InvalidPackageData)?;
+                    // Configuration files are edited in place, so they can't share their content
+                    if !is_config_file(&abs_path, &[npf_explorer.manifest()]) {
+                        stored.insert(abs_path.clone(), checksum);
+                    }
+                    log_entry = log_entry.with_checksum(checksum);
+                    installed_size += entry.header().size().map_err(|_| InvalidPackageData)?;
+                }
 
-            // Check whether the target file exists and retrieve its metadata (without following any symlink)
-            if let Ok(metadata) = fs::symlink_metadata(&rel_path) {
-                match (entry_type.is_dir(), metadata.file_type().is_dir()) {
-                    // Both files are directories, there is no conflict
-                    (true, true) => (),
+                // Configuration files modified locally are expected to exist already
+                let action = config_file_action(modified, &abs_path, log_entry.checksum());
+                if action != ConfigFileAction::Extract {
+                    actions.insert(abs_path.clone(), action);
+                    files.push(log_entry);
+                    continue;
+                }
 
-                    // The file to extract is a directory, the existing file is a symlink, check if it resolves to a directory
-                    (true, false) if metadata.file_type().is_symlink() => {
-                        if let Ok(metadata) = fs::metadata(&rel_path) {
-                            if !metadata.is_dir() {
-                                return Err(FileAlreadyExists(abs_path).into());
+                // Check whether the target file exists and retrieve its metadata (without following any symlink)
+                if let Ok(metadata) = fs::symlink_metadata(&rel_path) {
+                    match (entry_type.is_dir(), metadata.file_type().is_dir()) {
+                        // Both files are directories, there is no conflict
+                        (true, true) => (),
+
+                        // The file to extract is a directory, the existing file is a symlink, check if it resolves to a directory
+                        (true, false) if metadata.file_type().is_symlink() => {
+                            if let Ok(metadata) = fs::metadata(&rel_path) {
+                                if !metadata.is_dir() {
+                                    return Err(FileAlreadyExists(abs_path).into());
+                                }
                             }
                         }
-                    }
 
-                    // Otherwise, there are conflicting files, and an error is returned
-                    _ => return Err(FileAlreadyExists(abs_path).into()),
+                        // Otherwise, there are conflicting files, and an error is returned
+                        _ => return Err(FileAlreadyExists(abs_path).into()),
+                    }
                 }
+
+                files.push(log_entry);
             }
-            files.push(FileLogEntry::new(abs_path.to_path_buf(), entry_type.into()));
-        }
+            files
+        };
+
+        let total = files.len();
+        debug!(
+            "extracting {} file(s) of {} in {}",
+            total,
+            target_id,
+            install_dir.display()
+        );
 
         // Log each file to install to the log file
-        config
-            .installed_packages_cache(lock_ownership)
-            .save_package_log(target_id, &Log::new(files))
+        let installed_packages = config.installed_packages_cache(lock_ownership);
+        journal
+            .record_creation(&installed_packages.package_log_path(target_id))
+            .map_err(JournalError)?;
+        installed_packages
+            .save_package_log(
+                target_id,
+                &Log::new(files)
+                    .with_installed_size(installed_size)
+                    .with_slot(npf_explorer.manifest().slot().clone())
+                    .with_alternatives(npf_explorer.manifest().alternatives().to_vec()),
+            )
             .map_err(LogCreationError)?;
 
-        // Extract the tarball in the root folder
-        let res: Result<_, std::io::Error> = try {
+        // Extract the tarball in the staging folder, spreading the work over the configured number of threads, then
+        // move its files in place. Zstd archives split in frames are also decompressed by that many threads.
+        let store = config.content_store(lock_ownership);
+        let staging = config.paths().staging();
+        let unpacking = Unpacking {
+            config,
+            prefix: &prefix,
+            install_dir: &install_dir,
+            staging,
+            actions: &actions,
+            stored: &stored,
+            store: &store,
+            restore_attributes: can_restore_attributes(),
+            target_id,
+            total,
+            progress,
+        };
+        let jobs = config.jobs();
+        debug!("extracting {} with {} job(s)", target_id, jobs);
+
+        progress.phase_started(target_id, TransactionPhase::Extraction);
+        let res: Result<_, io::Error> = try {
+            // The folder of a foreign architecture is kept once created, like the other shared folders
+            fs::create_dir_all(&install_dir)?;
+            clear_staging(staging)?;
+            fs::create_dir_all(staging)?;
             tarball.seek(SeekFrom::Start(0))?;
-            let mut archive = Archive::new(GzDecoder::new(tarball));
-            for entry in archive.entries()? {
-                entry?.unpack_in(config.paths().root())?;
-            }
+            let staged_files = if jobs > 1 {
+                thread::scope(|scope| {
+                    let frames = match compression {
+                        Compression::Zstd => read_seek_table(&mut tarball)?,
+                        Compression::Gzip => None,
+                    };
+                    let data = match frames {
+                        Some(frames) => {
+                            ReadAhead::spawn_parallel(scope, &mut tarball, frames, jobs)
+                        }
+                        None => ReadAhead::spawn(scope, compression, &mut tarball),
+                    };
+                    let pool = WritePool::spawn(scope, jobs);
+
+                    let staged_files =
+                        unpacking.unpack(Archive::new(data), journal, Some(&pool))?;
+                    pool.flush()?;
+                    Ok::<_, io::Error>(staged_files)
+                })?
+            } else {
+                let data = compression.decoder(&mut tarball)?;
+                unpacking.unpack(Archive::new(data), journal, None)?
+            };
+            debug!(
+                "moving {} staged file(s) of {} in place",
+                staged_files.len(),
+                target_id
+            );
+            unpacking.commit(staged_files, journal)?;
         };
+        // The staging folder is emptied whatever happened, the journal taking care of what was moved in place
+        let res = res.and(clear_staging(staging));
+        progress.phase_finished(target_id, TransactionPhase::Extraction);
         res.map_err(ExtractError)?;
+        info!("extracted {} file(s) of {}", total, target_id);
     }
 
     if let Some(executor) = &instructions_handle {
-        executor
-            .execute_after_install(config.paths().root())
-            .map_err(PostInstallInstructionsFailure)?;
+        in_phase(progress, target_id, TransactionPhase::Instructions, || {
+            executor.execute_after_install(config.paths().root())
+        })
+        .map_err(PostInstallInstructionsFailure)?;
     }
 
     Ok(())
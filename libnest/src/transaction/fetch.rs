@@ -0,0 +1,375 @@
+//! Transports used to fetch files from the mirrors of the repositories.
+//!
+//! Each transport is a [`Fetcher`], registered in [`Fetchers`] for the URL schemes it handles. Libnest provides
+//! the [`FileFetcher`], reading local mirrors, and the [`HttpFetcher`](super::HttpFetcher), reaching HTTP(S)
+//! mirrors: frontends register the transports they support, and downstreams can add their own for other schemes.
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use failure::{format_err, Error, ResultExt};
+use url::Url;
+
+use crate::config::{Credentials, NetworkConfig, TlsConfig};
+use crate::package::PackageID;
+
+use super::download::{DownloadCancellation, DownloadThrottle};
+use super::progress::ProgressHandler;
+
+/// A request to fetch a file, along with the settings the transports may need to fetch it
+#[derive(Clone)]
+pub struct FetchRequest<'a> {
+    url: Url,
+    offset: u64,
+    length: Option<u64>,
+    network: &'a NetworkConfig,
+    credentials: Option<&'a Credentials>,
+    tls: Option<&'a TlsConfig>,
+    cancellation: Option<&'a DownloadCancellation>,
+    throttle: Option<&'a DownloadThrottle>,
+    progress: Option<(&'a dyn ProgressHandler, &'a PackageID)>,
+}
+
+impl<'a> FetchRequest<'a> {
+    /// Creates a request to fetch the whole file at the given URL, using the given network settings
+    #[inline]
+    pub fn from(url: Url, network: &'a NetworkConfig) -> Self {
+        FetchRequest {
+            url,
+            offset: 0,
+            length: None,
+            network,
+            credentials: None,
+            tls: None,
+            cancellation: None,
+            throttle: None,
+            progress: None,
+        }
+    }
+
+    /// Only fetches the file from the given offset, to resume a partial download
+    #[inline]
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Only fetches the given number of bytes, from the offset, to download a segment of the file
+    #[inline]
+    pub fn with_length(mut self, length: u64) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    /// Sends the given credentials to the mirror
+    #[inline]
+    pub fn with_credentials(mut self, credentials: &'a Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Connects to the mirror with the given TLS settings
+    #[inline]
+    pub fn with_tls(mut self, tls: &'a TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Makes the fetch fail as soon as possible once the given flag is raised
+    #[inline]
+    pub fn with_cancellation(mut self, cancellation: &'a DownloadCancellation) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Limits the fetch with the given throttle
+    #[inline]
+    pub fn with_throttle(mut self, throttle: &'a DownloadThrottle) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Reports the progress of the fetch to the given [`ProgressHandler`], as the download of the given package
+    #[inline]
+    pub fn with_progress(
+        mut self,
+        progress: &'a dyn ProgressHandler,
+        package: &'a PackageID,
+    ) -> Self {
+        self.progress = Some((progress, package));
+        self
+    }
+
+    /// Returns the URL of the file to fetch
+    #[inline]
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Returns the offset from which the file is fetched
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the number of bytes to fetch from the offset, or [`None`] to fetch the file up to its end
+    #[inline]
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+
+    /// Returns the network settings to fetch the file with
+    #[inline]
+    pub fn network(&self) -> &NetworkConfig {
+        self.network
+    }
+
+    /// Returns the credentials to send to the mirror, if any
+    #[inline]
+    pub fn credentials(&self) -> Option<&Credentials> {
+        self.credentials
+    }
+
+    /// Returns the TLS settings to connect to the mirror with, if any
+    #[inline]
+    pub fn tls(&self) -> Option<&TlsConfig> {
+        self.tls
+    }
+
+    /// Returns whether the fetch was asked to stop
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .map_or(false, DownloadCancellation::is_cancelled)
+    }
+
+    /// Takes the given number of received bytes from the throttle of the fetch, if any, blocking until the
+    /// rate allows it
+    #[inline]
+    pub fn consume(&self, bytes: usize) {
+        if let Some(throttle) = self.throttle {
+            throttle.consume(bytes);
+        }
+    }
+
+    /// Reports the number of bytes of the file fetched so far, the offset included, and the size of the file,
+    /// if known
+    #[inline]
+    pub fn report_progress(&self, fetched: u64, total: Option<u64>) {
+        if let Some((progress, package)) = self.progress {
+            progress.download_progress(package, fetched, total);
+        }
+    }
+}
+
+/// A transport able to fetch files from the mirrors whose URLs have one of the schemes it is registered for.
+///
+/// Transports are shared by the downloads running concurrently, hence the [`Send`] and [`Sync`] requirements.
+pub trait Fetcher: Send + Sync {
+    /// Returns the size of the file targeted by the given request, without fetching it
+    fn fetch_size(&self, request: &FetchRequest) -> Result<u64, Error>;
+
+    /// Fetches the file targeted by the given request, starting at its offset and stopping after its length, if it
+    /// has one, into the given writer. Transports that can't fetch only a part of the file must fail.
+    ///
+    /// The transport is expected to report its progress and to fail soon after the request is cancelled. Transports
+    /// reaching the network also consume the bytes they receive from the throttle of the request.
+    fn fetch(&self, request: &FetchRequest, writer: &mut dyn Write) -> Result<(), Error>;
+}
+
+/// A [`Fetcher`] reading the files of local mirrors, with a `file://` URL
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct FileFetcher;
+
+impl FileFetcher {
+    /// Returns the path of the file targeted by the given request
+    fn path(request: &FetchRequest) -> Result<PathBuf, Error> {
+        request
+            .url()
+            .to_file_path()
+            .map_err(|_| format_err!("invalid path for local mirror {}", request.url()))
+    }
+}
+
+impl Fetcher for FileFetcher {
+    fn fetch_size(&self, request: &FetchRequest) -> Result<u64, Error> {
+        let path = Self::path(request)?;
+        Ok(fs::metadata(&path)
+            .with_context(|_| path.display().to_string())?
+            .len())
+    }
+
+    fn fetch(&self, request: &FetchRequest, writer: &mut dyn Write) -> Result<(), Error> {
+        let path = Self::path(request)?;
+        let mut file = File::open(&path).with_context(|_| path.display().to_string())?;
+        let len = file.metadata()?.len();
+
+        if request.offset() > len {
+            return Err(format_err!(
+                "{}: file is smaller than the data already downloaded",
+                path.display()
+            ));
+        }
+        let end = match request.length() {
+            Some(length) if request.offset() + length > len => {
+                return Err(format_err!(
+                    "{}: file is smaller than the requested segment",
+                    path.display()
+                ));
+            }
+            Some(length) => request.offset() + length,
+            None => len,
+        };
+
+        file.seek(SeekFrom::Start(request.offset()))?;
+        request.report_progress(request.offset(), Some(len));
+
+        let mut buffer = [0; 64 * 1024];
+        let mut position = request.offset();
+        while position < end {
+            if request.is_cancelled() {
+                return Err(format_err!("the download was cancelled"));
+            }
+            let wanted = buffer.len().min((end - position) as usize);
+            let read = file.read(&mut buffer[..wanted])?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read])?;
+            position += read as u64;
+            request.report_progress(position, Some(len));
+        }
+        Ok(())
+    }
+}
+
+/// The transports used to fetch files, keyed by the URL schemes they handle
+#[derive(Clone)]
+pub struct Fetchers {
+    fetchers: HashMap<String, Arc<dyn Fetcher>>,
+}
+
+impl Fetchers {
+    /// Creates a registry where only local mirrors are handled, by the [`FileFetcher`]
+    #[inline]
+    pub fn new() -> Self {
+        Fetchers {
+            fetchers: HashMap::new(),
+        }
+        .with_fetcher(&["file"], FileFetcher)
+    }
+
+    /// Registers the given transport for the given URL schemes, replacing the transports registered for
+    /// them previously, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libnest;
+    /// # extern crate failure;
+    /// # extern crate url;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// use std::io::Write;
+    ///
+    /// use failure::Error;
+    /// use libnest::config::NetworkConfig;
+    /// use libnest::transaction::{FetchRequest, Fetcher, Fetchers};
+    /// use url::Url;
+    ///
+    /// struct EchoFetcher;
+    ///
+    /// impl Fetcher for EchoFetcher {
+    ///     fn fetch_size(&self, request: &FetchRequest) -> Result<u64, Error> {
+    ///         Ok(request.url().path().len() as u64)
+    ///     }
+    ///
+    ///     fn fetch(&self, request: &FetchRequest, writer: &mut dyn Write) -> Result<(), Error> {
+    ///         writer.write_all(request.url().path().as_bytes())?;
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let fetchers = Fetchers::new().with_fetcher(&["echo"], EchoFetcher);
+    /// let network = NetworkConfig::new();
+    ///
+    /// let mut data = Vec::new();
+    /// fetchers.fetch(&FetchRequest::from(Url::parse("echo:hello")?, &network), &mut data)?;
+    /// assert_eq!(data, b"hello");
+    /// assert_eq!(fetchers.schemes(), vec!["echo", "file"]);
+    /// assert!(fetchers.fetch(&FetchRequest::from(Url::parse("rsync://mirror/")?, &network), &mut data).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_fetcher<F: Fetcher + 'static>(mut self, schemes: &[&str], fetcher: F) -> Self {
+        let fetcher: Arc<dyn Fetcher> = Arc::new(fetcher);
+
+        for scheme in schemes {
+            self.fetchers
+                .insert(scheme.to_ascii_lowercase(), fetcher.clone());
+        }
+        self
+    }
+
+    /// Returns the transport registered for the given URL scheme, if any
+    #[inline]
+    pub fn fetcher(&self, scheme: &str) -> Option<&dyn Fetcher> {
+        self.fetchers.get(scheme).map(AsRef::as_ref)
+    }
+
+    /// Returns the URL schemes a transport is registered for, sorted alphabetically
+    pub fn schemes(&self) -> Vec<&str> {
+        let mut schemes: Vec<_> = self.fetchers.keys().map(String::as_str).collect();
+        schemes.sort();
+        schemes
+    }
+
+    /// Returns the transport handling the URL of the given request, or an error if its scheme isn't supported
+    fn fetcher_for(&self, request: &FetchRequest) -> Result<&dyn Fetcher, Error> {
+        self.fetcher(request.url().scheme()).ok_or_else(|| {
+            format_err!(
+                "unsupported URL scheme '{}' for {}",
+                request.url().scheme(),
+                request.url()
+            )
+        })
+    }
+
+    /// Returns the size of the file targeted by the given request, using the transport handling its URL
+    pub fn fetch_size(&self, request: &FetchRequest) -> Result<u64, Error> {
+        self.fetcher_for(request)?.fetch_size(request)
+    }
+
+    /// Fetches the file targeted by the given request into the given writer, using the transport handling
+    /// its URL
+    pub fn fetch(&self, request: &FetchRequest, writer: &mut dyn Write) -> Result<(), Error> {
+        self.fetcher_for(request)?.fetch(request, writer)
+    }
+}
+
+impl Debug for FetchRequest<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("FetchRequest")
+            .field("url", &self.url)
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+impl Default for Fetchers {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for Fetchers {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Fetchers")
+            .field("schemes", &self.schemes())
+            .finish()
+    }
+}
@@ -0,0 +1,450 @@
+//! Hooks executed before or after a set of transactions, when they affect given packages or files.
+//!
+//! Each hook is declared in its own `*.toml` file of the hooks folder (`/etc/nest/hooks.d/` by default),
+//! made of a `[trigger]` table describing the transactions it reacts to, and of an `[action]` table
+//! describing the command to execute:
+//!
+//! ```toml
+//! [trigger]
+//! operations = ["install", "upgrade", "remove"]
+//! paths = ["/usr/lib/modules/"]
+//!
+//! [action]
+//! description = "Updating the kernel modules dependencies"
+//! when = "post-transaction"
+//! exec = "depmod -a"
+//! ```
+//!
+//! A hook is triggered if any of the transactions has one of the given operations, and either targets one of the
+//! given packages or creates or removes a file in one of the given paths. A trigger without packages nor paths
+//! matches all the transactions with one of the given operations. Hooks are executed at most once per set of
+//! transactions, in the lexical order of their file names.
+//!
+//! Besides the declared hooks, built-in hooks configured in the `[triggers]` section of the configuration keep the
+//! kernels bootable: after the transactions creating or removing files in the modules folder of a kernel, the
+//! dependencies of its modules are updated and its initramfs image is generated again, before the declared
+//! `post-transaction` hooks are executed. A declared hook named `depmod` or `initramfs` replaces the built-in one.
+
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use failure::ResultExt;
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::chroot::Chroot;
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::PackageID;
+
+use super::errors::{HookError, HookErrorKind};
+use super::{ExecutionOutput, Transaction, TransactionPlan};
+
+lazy_static! {
+    static ref NEST_PATH_KERNEL_MODULES: &'static Path = Path::new("/usr/lib/modules");
+    static ref NEST_PATH_BOOT: &'static Path = Path::new("/boot");
+}
+
+/// The name of the built-in hook updating the dependencies of the modules of the changed kernels
+const DEPMOD_HOOK: &str = "depmod";
+
+/// The name of the built-in hook generating the initramfs image of the changed kernels again
+const INITRAMFS_HOOK: &str = "initramfs";
+
+/// The operation made by a transaction, as matched by a hook's trigger
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookOperation {
+    /// A package is installed
+    Install,
+
+    /// A package is upgraded
+    Upgrade,
+
+    /// A package is removed
+    Remove,
+}
+
+/// The moment a hook is executed at
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookWhen {
+    /// The hook is executed before any of the transactions is performed. If it fails, the transactions are aborted.
+    PreTransaction,
+
+    /// The hook is executed once all the transactions were performed successfully
+    PostTransaction,
+}
+
+/// The transactions a hook reacts to
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct HookTrigger {
+    operations: Vec<HookOperation>,
+    #[serde(default)]
+    packages: Vec<String>,
+    #[serde(default)]
+    paths: Vec<PathBuf>,
+}
+
+impl HookTrigger {
+    /// Returns the operations the hook reacts to
+    #[inline]
+    pub fn operations(&self) -> &[HookOperation] {
+        &self.operations
+    }
+
+    /// Returns the packages the hook reacts to, either as full names (`repository::category/name`)
+    /// or as short names (`category/name`)
+    #[inline]
+    pub fn packages(&self) -> &[String] {
+        &self.packages
+    }
+
+    /// Returns the paths, relative to the installation root, in which the creation or removal of a file triggers the hook
+    #[inline]
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    fn matches_package(&self, id: &PackageID) -> bool {
+        let full_name = format!("{}::{}/{}", id.repository(), id.category(), id.name());
+        let short_name = format!("{}/{}", id.category(), id.name());
+
+        self.packages
+            .iter()
+            .any(|package| *package == full_name || *package == short_name)
+    }
+
+    fn matches_plan(&self, plan: &TransactionPlan) -> bool {
+        plan.created_files()
+            .iter()
+            .chain(plan.removed_files())
+            .any(|file| self.paths.iter().any(|path| file.starts_with(path)))
+    }
+}
+
+/// The command executed by a hook
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct HookAction {
+    #[serde(default)]
+    description: Option<String>,
+    when: HookWhen,
+    exec: String,
+}
+
+impl HookAction {
+    /// Returns the description of the action, if any
+    #[inline]
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_ref().map(String::as_str)
+    }
+
+    /// Returns the moment the hook is executed at
+    #[inline]
+    pub fn when(&self) -> HookWhen {
+        self.when
+    }
+
+    /// Returns the shell command executed by the hook
+    #[inline]
+    pub fn exec(&self) -> &str {
+        &self.exec
+    }
+}
+
+/// A hook, executed before or after a set of transactions when they match its trigger
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Hook {
+    #[serde(skip)]
+    name: String,
+    trigger: HookTrigger,
+    action: HookAction,
+}
+
+impl Hook {
+    /// Loads the hook declared in the given file. The hook is named after the file.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Hook, HookError> {
+        let path = path.as_ref();
+        let mut content = String::new();
+
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut content))
+            .context(path.display().to_string())
+            .context(HookErrorKind::HookLoadError)?;
+
+        let mut hook: Hook = toml::from_str(&content)
+            .context(path.display().to_string())
+            .context(HookErrorKind::HookParseError)?;
+        hook.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(hook)
+    }
+
+    /// Loads all the hooks declared in the `*.toml` files of the given folder, sorted by name.
+    ///
+    /// A missing folder is not an error.
+    pub fn load_all<P: AsRef<Path>>(dir: P) -> Result<Vec<Hook>, HookError> {
+        let dir = dir.as_ref();
+
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)
+            .context(dir.display().to_string())
+            .context(HookErrorKind::HookLoadError)?
+        {
+            let path = entry
+                .context(dir.display().to_string())
+                .context(HookErrorKind::HookLoadError)?
+                .path();
+
+            if path.is_file() && path.extension().map_or(false, |ext| ext == "toml") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths.iter().map(Hook::load_from).collect()
+    }
+
+    /// Creates the built-in hook with the given name for the given kernel, executing the given command once all the
+    /// transactions are performed, if the kernel's modules folder still exists.
+    ///
+    /// `{kernel}` is replaced in the command by the version of the kernel.
+    fn builtin(name: &str, description: &str, command: &str, kernel: &str) -> Hook {
+        let modules = NEST_PATH_KERNEL_MODULES.join(kernel);
+
+        Hook {
+            name: format!("{} ({})", name, kernel),
+            trigger: HookTrigger {
+                operations: vec![
+                    HookOperation::Install,
+                    HookOperation::Upgrade,
+                    HookOperation::Remove,
+                ],
+                packages: Vec::new(),
+                paths: vec![modules.clone()],
+            },
+            action: HookAction {
+                description: Some(format!("{} of kernel {}", description, kernel)),
+                when: HookWhen::PostTransaction,
+                exec: format!(
+                    "[ -d '{}' ] || exit 0; {}",
+                    modules.display(),
+                    command.replace("{kernel}", kernel)
+                ),
+            },
+        }
+    }
+
+    /// Returns the name of the hook
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the transactions the hook reacts to
+    #[inline]
+    pub fn trigger(&self) -> &HookTrigger {
+        &self.trigger
+    }
+
+    /// Returns the command executed by the hook
+    #[inline]
+    pub fn action(&self) -> &HookAction {
+        &self.action
+    }
+
+    /// Returns whether the given transaction, making the changes described by the given plan, triggers the hook
+    pub fn is_triggered_by(&self, transaction: &Transaction, plan: &TransactionPlan) -> bool {
+        let (operation, targets) = match transaction {
+            Transaction::Pull(_) => return false,
+            Transaction::Install(install) => (HookOperation::Install, vec![install.target()]),
+            Transaction::Remove(remove) => (HookOperation::Remove, vec![remove.target()]),
+            Transaction::Upgrade(upgrade) => (
+                HookOperation::Upgrade,
+                vec![upgrade.old_target(), upgrade.new_target()],
+            ),
+            Transaction::Reinstall(reinstall) => (HookOperation::Install, vec![reinstall.target()]),
+        };
+
+        if !self.trigger.operations.contains(&operation) {
+            return false;
+        }
+        if self.trigger.packages.is_empty() && self.trigger.paths.is_empty() {
+            return true;
+        }
+        targets
+            .into_iter()
+            .any(|target| self.trigger.matches_package(target))
+            || self.trigger.matches_plan(plan)
+    }
+
+    /// Executes the hook's command with a shell chrooted in the given root folder
+    pub fn execute(&self, root: &Path) -> Result<ExecutionOutput, HookError> {
+        let output = Command::new("chroot")
+            .arg(root.display().to_string())
+            .arg("/bin/sh")
+            .arg("-c")
+            .arg(&self.action.exec)
+            .output()
+            .context(HookErrorKind::CannotExecuteHook)?;
+
+        if !output.status.success() {
+            Err(HookErrorKind::FailureExitStatus(output).into())
+        } else {
+            Ok(output)
+        }
+    }
+}
+
+/// The hooks triggered by a set of transactions, in the order they must be executed
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct TriggeredHooks {
+    pre_transaction: Vec<Hook>,
+    post_transaction: Vec<Hook>,
+}
+
+impl TriggeredHooks {
+    /// Finds the hooks among the given ones that are triggered by the given transactions, along with the built-in
+    /// hooks they trigger, executed first after the transactions.
+    ///
+    /// The packages targeted by the transactions must have been downloaded, for the files they
+    /// create to be known.
+    pub fn find(
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        hooks: Vec<Hook>,
+        transactions: &[Transaction],
+    ) -> Result<TriggeredHooks, HookError> {
+        let mut plans = Vec::with_capacity(transactions.len());
+        for transaction in transactions {
+            let plan = match transaction {
+                Transaction::Install(install) => install
+                    .plan(config, lock_ownership)
+                    .map_err(|_| HookErrorKind::CannotPlanTransaction)?,
+                Transaction::Remove(remove) => remove
+                    .plan(config, lock_ownership)
+                    .map_err(|_| HookErrorKind::CannotPlanTransaction)?,
+                Transaction::Upgrade(upgrade) => upgrade
+                    .plan(config, lock_ownership)
+                    .map_err(|_| HookErrorKind::CannotPlanTransaction)?,
+                Transaction::Reinstall(reinstall) => reinstall
+                    .plan(config, lock_ownership)
+                    .map_err(|_| HookErrorKind::CannotPlanTransaction)?,
+                Transaction::Pull(_) => TransactionPlan::default(),
+            };
+            plans.push(plan);
+        }
+
+        let mut triggered = TriggeredHooks {
+            pre_transaction: Vec::new(),
+            post_transaction: kernel_hooks(config, &hooks, &plans),
+        };
+
+        for hook in hooks {
+            let is_triggered = transactions
+                .iter()
+                .zip(&plans)
+                .any(|(transaction, plan)| hook.is_triggered_by(transaction, plan));
+
+            if is_triggered {
+                match hook.action.when {
+                    HookWhen::PreTransaction => triggered.pre_transaction.push(hook),
+                    HookWhen::PostTransaction => triggered.post_transaction.push(hook),
+                }
+            }
+        }
+        Ok(triggered)
+    }
+
+    /// Returns the hooks to execute before the transactions are performed
+    #[inline]
+    pub fn pre_transaction(&self) -> &[Hook] {
+        &self.pre_transaction
+    }
+
+    /// Returns the hooks to execute after the transactions are performed
+    #[inline]
+    pub fn post_transaction(&self) -> &[Hook] {
+        &self.post_transaction
+    }
+}
+
+/// Returns the version of the kernel whose modules folder holds the given file, if any
+fn kernel_of(file: &Path) -> Option<String> {
+    file.strip_prefix(*NEST_PATH_KERNEL_MODULES)
+        .ok()?
+        .components()
+        .next()
+        .map(|version| version.as_os_str().to_string_lossy().into_owned())
+}
+
+/// Returns the versions of the kernels whose modules folder exists in the given installation root
+fn installed_kernels(root: &Path) -> Vec<String> {
+    fs::read_dir(root.with_content(*NEST_PATH_KERNEL_MODULES))
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the enabled built-in hooks triggered by the given plans, unless a declared hook replaces them: `depmod`
+/// for each kernel whose modules changed, then `initramfs` for each of them, or for all the installed kernels if
+/// files changed in `/boot`.
+fn kernel_hooks(config: &Config, hooks: &[Hook], plans: &[TransactionPlan]) -> Vec<Hook> {
+    let triggers = config.triggers();
+    let mut kernels = BTreeSet::new();
+    let mut boot_changed = false;
+
+    for file in plans
+        .iter()
+        .flat_map(|plan| plan.created_files().iter().chain(plan.removed_files()))
+    {
+        match kernel_of(file) {
+            Some(kernel) => {
+                kernels.insert(kernel);
+            }
+            None => boot_changed |= file.starts_with(*NEST_PATH_BOOT),
+        }
+    }
+
+    let is_declared = |name| hooks.iter().any(|hook| hook.name == name);
+    let mut builtins = Vec::new();
+
+    if triggers.depmod() && !is_declared(DEPMOD_HOOK) {
+        builtins.extend(kernels.iter().map(|kernel| {
+            Hook::builtin(
+                DEPMOD_HOOK,
+                "Updating the dependencies of the modules",
+                triggers.depmod_command(),
+                kernel,
+            )
+        }));
+    }
+
+    if triggers.initramfs() && !is_declared(INITRAMFS_HOOK) {
+        if boot_changed {
+            kernels.extend(installed_kernels(config.paths().root()));
+        }
+        builtins.extend(kernels.iter().map(|kernel| {
+            Hook::builtin(
+                INITRAMFS_HOOK,
+                "Generating the initramfs image",
+                triggers.initramfs_command(),
+                kernel,
+            )
+        }));
+    }
+    builtins
+}
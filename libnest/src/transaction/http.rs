@@ -0,0 +1,508 @@
+//! The engine fetching files from HTTP(S) mirrors.
+//!
+//! All the transfers of the process are driven by a single thread, through curl's multi interface: they share
+//! its pool of connections, so that successive downloads from a mirror reuse the same connection, and the
+//! transfers from a mirror supporting HTTP/2 are multiplexed over a single connection. The transfers from each
+//! mirror can be limited (see
+//! [`NetworkConfig::max_mirror_transfers()`](crate::config::NetworkConfig::max_mirror_transfers)), the others
+//! waiting for their turn.
+//!
+//! The [`HttpFetcher`] is the synchronous facade of this engine: each fetch hands its transfer over to the engine,
+//! and receives the data on the calling thread, where it is written, throttled and reported. A transfer whose data
+//! isn't written fast enough is paused until the fetch catches up.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use curl::easy::{Auth, Easy2, Handler, HttpVersion, List, WriteError};
+use curl::multi::{Easy2Handle, Multi};
+use failure::{format_err, Error};
+use lazy_static::lazy_static;
+use log::{debug, warn};
+
+use crate::config::Credentials;
+
+use super::fetch::{FetchRequest, Fetcher};
+
+/// How long the engine and the fetches wait for something to happen before checking for new transfers
+/// and cancellations
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of chunks of data received by a transfer that can wait to be written before the transfer is paused
+const MAX_PENDING_CHUNKS: usize = 64;
+
+/// The identifier given to the next transfer
+static NEXT_TRANSFER_ID: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// The channel to the engine shared by all the transfers of the process, started along with the first of them
+    static ref ENGINE: Mutex<Sender<Command>> = Mutex::new(Engine::start());
+}
+
+/// An order given to the engine
+enum Command {
+    /// Performs the given transfer, once the transfers running from its host are below its limit
+    Start(QueuedTransfer),
+
+    /// Stops the transfer with the given identifier
+    Cancel(usize),
+}
+
+/// An update on a transfer, sent by the engine to the fetch waiting for it
+enum Event {
+    /// The size of the data to receive, once known
+    Size(u64),
+
+    /// A chunk of received data
+    Data(Vec<u8>),
+
+    /// The transfer is over, along with the size of the file it targeted, if known
+    Done(Result<Option<u64>, Error>),
+}
+
+/// The [`Handler`] of a transfer, forwarding the data it receives to the fetch waiting for it
+struct Collector {
+    events: Sender<Event>,
+    pending: Arc<AtomicUsize>,
+    segment: bool,
+    status: u32,
+    size_sent: bool,
+    paused: bool,
+}
+
+impl Handler for Collector {
+    fn header(&mut self, header: &[u8]) -> bool {
+        // The status of the last response is kept, as redirections are followed
+        if let Some(code) = String::from_utf8_lossy(header)
+            .strip_prefix("HTTP/")
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|code| code.parse().ok())
+        {
+            self.status = code;
+        }
+        true
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        // Writing nothing aborts the transfer of a segment sent as a whole file
+        if self.segment && self.status != 206 {
+            return Ok(0);
+        }
+        // Curl delivers the same data again once the transfer is resumed
+        if self.pending.load(Ordering::SeqCst) >= MAX_PENDING_CHUNKS {
+            self.paused = true;
+            return Err(WriteError::Pause);
+        }
+
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        match self.events.send(Event::Data(data.to_vec())) {
+            Ok(()) => Ok(data.len()),
+            Err(_) => Ok(0),
+        }
+    }
+
+    fn progress(&mut self, dltotal: f64, _: f64, _: f64, _: f64) -> bool {
+        if !self.size_sent && dltotal > 0.0 {
+            self.size_sent = true;
+            let _ = self.events.send(Event::Size(dltotal as u64));
+        }
+        true
+    }
+}
+
+/// A transfer waiting for room among the transfers running from its host
+struct QueuedTransfer {
+    id: usize,
+    host: String,
+    limit: Option<usize>,
+    easy: Easy2<Collector>,
+}
+
+/// The thread performing the transfers
+struct Engine {
+    multi: Multi,
+    commands: Receiver<Command>,
+    queued: VecDeque<QueuedTransfer>,
+    running: HashMap<usize, (String, Easy2Handle<Collector>)>,
+}
+
+impl Engine {
+    /// Starts the engine on its own thread, and returns the channel to send it orders
+    fn start() -> Sender<Command> {
+        let (sender, commands) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut multi = Multi::new();
+            // Curl falls back to one connection per transfer if it was built without HTTP/2 support
+            if let Err(e) = multi.pipelining(false, true) {
+                debug!("unable to multiplex the transfers: {}", e);
+            }
+
+            Engine {
+                multi,
+                commands,
+                queued: VecDeque::new(),
+                running: HashMap::new(),
+            }
+            .run()
+        });
+        sender
+    }
+
+    /// Performs the transfers, until the channel to the engine is closed
+    fn run(mut self) {
+        loop {
+            // Sleep until a transfer comes in when there is nothing to do
+            if self.running.is_empty() && self.queued.is_empty() {
+                match self.commands.recv() {
+                    Ok(command) => self.handle(command),
+                    Err(_) => return,
+                }
+            }
+            while let Ok(command) = self.commands.try_recv() {
+                self.handle(command);
+            }
+
+            self.start_queued();
+            self.resume_paused();
+            if let Err(e) = self.multi.perform() {
+                warn!("the transfers failed: {}", e);
+            }
+            self.finish_done();
+
+            if let Err(e) = self.multi.wait(&mut [], POLL_INTERVAL) {
+                warn!("unable to wait for the transfers: {}", e);
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Start(queued) => self.queued.push_back(queued),
+            Command::Cancel(id) => {
+                if let Some(pos) = self.queued.iter().position(|queued| queued.id == id) {
+                    self.queued.remove(pos);
+                }
+                if let Some((_, handle)) = self.running.remove(&id) {
+                    debug!("cancelling transfer {}", id);
+                    let _ = self.multi.remove2(handle);
+                }
+            }
+        }
+    }
+
+    /// Starts the queued transfers whose host has room for them, in the order they came in
+    fn start_queued(&mut self) {
+        let mut waiting = VecDeque::new();
+
+        while let Some(queued) = self.queued.pop_front() {
+            let running = self
+                .running
+                .values()
+                .filter(|(host, _)| *host == queued.host)
+                .count();
+            if queued.limit.map_or(false, |limit| running >= limit) {
+                waiting.push_back(queued);
+                continue;
+            }
+
+            let QueuedTransfer { id, host, easy, .. } = queued;
+            let events = easy.get_ref().events.clone();
+            let res: Result<_, Error> =
+                self.multi
+                    .add2(easy)
+                    .map_err(Error::from)
+                    .and_then(|mut handle| {
+                        handle.set_token(id)?;
+                        Ok(handle)
+                    });
+            match res {
+                Ok(handle) => {
+                    self.running.insert(id, (host, handle));
+                }
+                Err(e) => {
+                    let _ = events.send(Event::Done(Err(e)));
+                }
+            }
+        }
+        self.queued = waiting;
+    }
+
+    /// Resumes the paused transfers whose fetch caught up with the data they received
+    fn resume_paused(&mut self) {
+        for (_, handle) in self.running.values_mut() {
+            let collector = handle.get_ref();
+
+            if collector.paused && collector.pending.load(Ordering::SeqCst) < MAX_PENDING_CHUNKS / 2
+            {
+                handle.get_mut().paused = false;
+                if let Err(e) = handle.unpause_write() {
+                    warn!("unable to resume a transfer: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Removes the transfers that are over, and sends their result to the fetches waiting for them
+    fn finish_done(&mut self) {
+        let mut done = Vec::new();
+        self.multi.messages(|message| {
+            if let (Ok(id), Some(result)) = (message.token(), message.result()) {
+                done.push((id, result));
+            }
+        });
+
+        for (id, result) in done {
+            if let Some((_, handle)) = self.running.remove(&id) {
+                match self.multi.remove2(handle) {
+                    Ok(mut easy) => {
+                        let size = easy
+                            .content_length_download()
+                            .ok()
+                            .filter(|size| *size >= 0.0)
+                            .map(|size| size as u64);
+                        let result = result.map(|_| size).map_err(Error::from);
+                        let _ = easy.get_ref().events.send(Event::Done(result));
+                    }
+                    Err(e) => warn!("unable to remove a transfer: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// A transfer handed over to the engine, seen from the fetch waiting for it
+struct Transfer<'a> {
+    id: usize,
+    request: &'a FetchRequest<'a>,
+    events: Receiver<Event>,
+    pending: Arc<AtomicUsize>,
+    done: bool,
+}
+
+impl<'a> Transfer<'a> {
+    /// Hands the transfer of the given request over to the engine, once set up with the common options and
+    /// the given function
+    fn start<F>(request: &'a FetchRequest<'a>, setup: F) -> Result<Self, Error>
+    where
+        F: FnOnce(&mut Easy2<Collector>) -> Result<(), Error>,
+    {
+        let (sender, events) = mpsc::channel();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let mut easy = Easy2::new(Collector {
+            events: sender,
+            pending: pending.clone(),
+            segment: request.length().is_some(),
+            status: 0,
+            size_sent: false,
+            paused: false,
+        });
+        HttpFetcher::setup(request, &mut easy)?;
+        setup(&mut easy)?;
+
+        let url = request.url();
+        let host = format!(
+            "{}://{}:{}",
+            url.scheme(),
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or_default()
+        );
+        let id = NEXT_TRANSFER_ID.fetch_add(1, Ordering::SeqCst);
+        let limit = request.network().max_mirror_transfers();
+
+        ENGINE
+            .lock()
+            .expect("the download engine is poisoned")
+            .send(Command::Start(QueuedTransfer {
+                id,
+                host,
+                limit,
+                easy,
+            }))
+            .map_err(|_| format_err!("the download engine stopped"))?;
+
+        Ok(Transfer {
+            id,
+            request,
+            events,
+            pending,
+            done: false,
+        })
+    }
+
+    /// Waits for the next update on the transfer, or fails if the request is cancelled meanwhile
+    fn next(&mut self) -> Result<Event, Error> {
+        loop {
+            if self.request.is_cancelled() {
+                return Err(format_err!("the download was cancelled"));
+            }
+
+            match self.events.recv_timeout(POLL_INTERVAL) {
+                Ok(Event::Data(data)) => {
+                    self.pending.fetch_sub(1, Ordering::SeqCst);
+                    return Ok(Event::Data(data));
+                }
+                Ok(Event::Done(result)) => {
+                    self.done = true;
+                    return Ok(Event::Done(result));
+                }
+                Ok(event) => return Ok(event),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.done = true;
+                    return Err(format_err!("the download engine stopped"));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Transfer<'_> {
+    fn drop(&mut self) {
+        // The transfer is stopped if the fetch gave up on it
+        if !self.done {
+            if let Ok(engine) = ENGINE.lock() {
+                let _ = engine.send(Command::Cancel(self.id));
+            }
+        }
+    }
+}
+
+/// A [`Fetcher`] downloading files from HTTP(S) mirrors, the synchronous facade of the engine shared by all the
+/// transfers of the process.
+///
+/// The connections to the mirrors are kept alive and reused by the following transfers, and HTTP/2 is negotiated
+/// with the mirrors supporting it, multiplexing the transfers from the same mirror over a single connection.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct HttpFetcher;
+
+impl HttpFetcher {
+    /// Creates a [`HttpFetcher`]
+    #[inline]
+    pub fn new() -> Self {
+        HttpFetcher
+    }
+
+    /// Sets up the given transfer with the default options of the downloads and the settings of the given request
+    fn setup(request: &FetchRequest, curl: &mut Easy2<Collector>) -> Result<(), Error> {
+        curl.follow_location(true)?;
+        curl.fail_on_error(true)?;
+        // Curl falls back to HTTP/1.1 if it was built without HTTP/2 support
+        let _ = curl.http_version(HttpVersion::V2TLS);
+        // Waiting for a connection to the mirror to be multiplexed is better than opening a new one
+        curl.pipewait(true)?;
+        Self::authenticate(request, curl)?;
+
+        curl.url(request.url().as_str())?;
+        if let Some(timeout) = request.network().connect_timeout() {
+            curl.connect_timeout(Duration::from_secs(timeout))?;
+        }
+        // A download is stalled when it receives less than a byte per second
+        if let Some(timeout) = request.network().stall_timeout() {
+            curl.low_speed_limit(1)?;
+            curl.low_speed_time(Duration::from_secs(timeout))?;
+        }
+        // Only override curl's default behaviour if the configuration has a say on proxies
+        if request.network().has_proxy() {
+            curl.proxy(
+                request
+                    .network()
+                    .proxy_for(request.url())
+                    .map_or("", |proxy| proxy.as_str()),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Configures curl to use the TLS settings of the request and to send its credentials, if any
+    fn authenticate(request: &FetchRequest, curl: &mut Easy2<Collector>) -> Result<(), Error> {
+        if let Some(tls) = request.tls() {
+            if let Some(certificate) = tls.client_certificate() {
+                curl.ssl_cert(certificate)?;
+            }
+            if let Some(key) = tls.client_key() {
+                curl.ssl_key(key)?;
+            }
+            if let Some(ca_bundle) = tls.ca_bundle() {
+                curl.cainfo(ca_bundle)?;
+            }
+            if tls.insecure() {
+                curl.ssl_verify_peer(false)?;
+                curl.ssl_verify_host(false)?;
+            }
+        }
+
+        match request.credentials() {
+            Some(Credentials::Basic { username, password }) => {
+                let mut auth = Auth::new();
+                auth.basic(true);
+
+                curl.http_auth(&auth)?;
+                curl.username(username)?;
+                curl.password(password)?;
+            }
+            Some(Credentials::Bearer { token }) => {
+                let mut headers = List::new();
+                headers.append(&format!("Authorization: Bearer {}", token))?;
+
+                curl.http_headers(headers)?;
+            }
+            None => (),
+        }
+        Ok(())
+    }
+}
+
+impl Fetcher for HttpFetcher {
+    fn fetch_size(&self, request: &FetchRequest) -> Result<u64, Error> {
+        let mut transfer = Transfer::start(request, |curl| Ok(curl.nobody(true)?))?;
+
+        loop {
+            if let Event::Done(result) = transfer.next()? {
+                return result?.ok_or_else(|| {
+                    format_err!("{}: the size of the file is unknown", request.url())
+                });
+            }
+        }
+    }
+
+    fn fetch(&self, request: &FetchRequest, writer: &mut dyn Write) -> Result<(), Error> {
+        let offset = request.offset();
+
+        // Ask for the remaining bytes only. Curl fails if the mirror doesn't honor the range, except for
+        // segments, whose responses are checked by the engine.
+        let mut transfer = Transfer::start(request, |curl| {
+            curl.progress(true)?;
+            match request.length() {
+                Some(length) => {
+                    curl.range(&format!("{}-{}", offset, offset + length.max(1) - 1))?
+                }
+                None => curl.resume_from(offset)?,
+            }
+            Ok(())
+        })?;
+
+        let mut fetched = offset;
+        let mut total = None;
+        loop {
+            match transfer.next()? {
+                Event::Size(size) => total = Some(offset + size),
+                Event::Data(data) => {
+                    writer.write_all(&data)?;
+                    request.consume(data.len());
+                    fetched += data.len() as u64;
+                    if total.is_some() {
+                        request.report_progress(fetched, total);
+                    }
+                }
+                Event::Done(result) => return result.map(|_| ()),
+            }
+        }
+    }
+}
@@ -2,9 +2,13 @@ use crate::config::Config;
 use crate::lock_file::LockFileOwnership;
 use crate::package::PackageID;
 
+use super::config_files::ModifiedConfigFiles;
 use super::download::PackageDownload;
-use super::extract::extract_package;
-use super::{InstallError, InstallErrorKind::*};
+use super::extract::{extract_package, unexplorable_package};
+use super::journal::Journal;
+use super::plan::TransactionPlan;
+use super::progress::ProgressHandler;
+use super::{InstallError, InstallErrorKind::JournalError};
 
 /// Structure representing an "install" transaction
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
@@ -29,17 +33,47 @@ impl InstallTransaction {
         PackageDownload::from(self.target().clone())
     }
 
-    /// Extracts the downloaded file and performs the installation
+    /// Computes the changes the installation would make to the system, without making them
+    pub fn plan(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<TransactionPlan, InstallError> {
+        let mut plan = TransactionPlan::default();
+        plan.plan_installation(config, lock_ownership, self.target())?;
+        Ok(plan)
+    }
+
+    /// Extracts the downloaded file and performs the installation, reporting its progress to the given
+    /// [`ProgressHandler`].
+    ///
+    /// If any step of the installation fails, the extracted files are removed.
+    /// Nothing is done if the configuration is in dry-run mode.
     pub fn extract(
         &self,
         config: &Config,
         lock_ownership: &LockFileOwnership,
+        progress: &dyn ProgressHandler,
     ) -> Result<(), InstallError> {
+        if config.dry_run() {
+            return Ok(());
+        }
+
         let downloaded_packages = config.downloaded_packages_cache(lock_ownership);
         let npf_explorer = downloaded_packages
             .explore_package(self.target())
-            .map_err(|_| InvalidPackageFile)?;
+            .map_err(unexplorable_package)?;
 
-        extract_package(config, lock_ownership, npf_explorer, self.target())
+        let mut journal = Journal::create(config.paths().journal()).map_err(JournalError)?;
+        let res = extract_package(
+            config,
+            lock_ownership,
+            npf_explorer,
+            self.target(),
+            &ModifiedConfigFiles::new(),
+            &mut journal,
+            progress,
+        );
+        journal.conclude(res, |e| JournalError(e).into())
     }
 }
@@ -1,11 +1,14 @@
-use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::process::Command;
 
+use log::{debug, warn};
+
 use crate::chroot::Chroot;
+use crate::config::Sandboxing;
 
 use super::errors::{InstructionsExecutionError, InstructionsExecutionErrorKind::*};
+use super::sandbox::sandboxed_command;
 
 /// Type representing the result of an execution
 /// It contains fields for the exit status, stdout, and stderr
@@ -15,6 +18,7 @@ pub type ExecutionOutput = std::process::Output;
 #[derive(Debug, Clone)]
 pub struct InstructionsExecutor {
     script_source: String,
+    sandboxing: Sandboxing,
 }
 
 impl InstructionsExecutor {
@@ -43,33 +47,100 @@ impl InstructionsExecutor {
     }
 
     /// Creates an [`InstructionsExecutor`] from a script file
-    pub fn from_script_file(
-        file: &mut File,
+    pub fn from_script_file<R: Read>(
+        file: &mut R,
     ) -> Result<InstructionsExecutor, InstructionsExecutionError> {
         let mut script_source = String::new();
 
         file.read_to_string(&mut script_source)
             .map_err(|_| CannotReadInstructions)?;
 
-        Ok(Self { script_source })
+        Ok(Self {
+            script_source,
+            sandboxing: Sandboxing::BestEffort,
+        })
     }
 
-    fn execute_function(
-        &self,
-        func_name: &str,
+    /// Sets how the processes executing the script are isolated from the host.
+    ///
+    /// By default, they are executed in a sandbox if one can be set up.
+    #[inline]
+    pub fn with_sandboxing(mut self, sandboxing: Sandboxing) -> Self {
+        self.sandboxing = sandboxing;
+        self
+    }
+
+    /// Returns how the processes executing the script are isolated from the host
+    #[inline]
+    pub fn sandboxing(&self) -> Sandboxing {
+        self.sandboxing
+    }
+
+    fn execute_sandboxed(
         root: &Path,
-    ) -> Result<ExecutionOutput, InstructionsExecutionError> {
-        let shell = Self::find_suitable_shell(root).ok_or(CannotFindShell)?;
+        shell: &Path,
+        script: &str,
+    ) -> Option<std::io::Result<ExecutionOutput>> {
+        sandboxed_command(root, shell).map(|mut cmd| cmd.arg("-c").arg(script).output())
+    }
+
+    fn execute_unsandboxed(
+        root: &Path,
+        shell: &Path,
+        script: &str,
+    ) -> std::io::Result<ExecutionOutput> {
         let mut cmd = Command::new("chroot");
 
         cmd.arg(root.display().to_string());
         cmd.arg(shell);
         cmd.arg("-c");
-        cmd.arg(format!("{}\n{}", self.script_source, func_name));
+        cmd.arg(script);
+        cmd.output()
+    }
 
-        let output = cmd.output().map_err(|_| CannotExecuteShell)?;
+    fn execute_function(
+        &self,
+        func_name: &str,
+        root: &Path,
+    ) -> Result<ExecutionOutput, InstructionsExecutionError> {
+        let shell = Self::find_suitable_shell(root).ok_or(CannotFindShell)?;
+        let script = format!("{}\n{}", self.script_source, func_name);
+        debug!(
+            "executing {}() with {} in {} ({:?} sandboxing)",
+            func_name,
+            shell.display(),
+            root.display(),
+            self.sandboxing
+        );
+
+        // A sandbox that can't be set up makes the spawn fail, before the script is executed
+        let output = match self.sandboxing {
+            Sandboxing::Disabled => {
+                Self::execute_unsandboxed(root, &shell, &script).map_err(|_| CannotExecuteShell)?
+            }
+            Sandboxing::BestEffort => match Self::execute_sandboxed(root, &shell, &script) {
+                Some(Ok(output)) => output,
+                _ => {
+                    debug!(
+                        "the sandbox can't be set up, executing {}() unsandboxed",
+                        func_name
+                    );
+                    Self::execute_unsandboxed(root, &shell, &script)
+                        .map_err(|_| CannotExecuteShell)?
+                }
+            },
+            Sandboxing::Required => Self::execute_sandboxed(root, &shell, &script)
+                .and_then(Result::ok)
+                .ok_or(SandboxUnavailable)?,
+        };
 
         if !output.status.success() {
+            warn!(
+                "{}() failed ({}): {}",
+                func_name,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
             Err(FailureExitStatus(output).into())
         } else {
             Ok(output)
@@ -0,0 +1,261 @@
+//! Journal of the changes made to the filesystem by a transaction.
+//!
+//! Every file created or removed by a transaction is recorded in the journal before the change
+//! is made, so that the filesystem can be brought back to its pre-transaction state if any step
+//! of the transaction fails, or if the transaction was interrupted.
+//!
+//! The journal is stored on disk as one JSON entry per line. Removed files are moved to a backup
+//! folder next to the journal instead of being deleted, so they can be restored.
+
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+
+/// A change made to the filesystem by a transaction
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum JournalEntry {
+    /// A file or a directory that didn't exist before was created
+    Created(PathBuf),
+
+    /// A file was removed, and moved to the given backup location
+    Moved {
+        /// The original location of the file
+        from: PathBuf,
+        /// The location of the backup
+        to: PathBuf,
+    },
+
+    /// An empty directory was removed
+    RemovedDirectory {
+        /// The location of the directory
+        path: PathBuf,
+        /// The permissions of the directory
+        mode: u32,
+    },
+}
+
+/// Structure representing the journal of a transaction being performed
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Creates a new, empty journal at the given location.
+    ///
+    /// This fails if there is already a journal at this location: the interrupted transaction
+    /// it belongs to must be rolled back before starting a new one.
+    pub(crate) fn create<P: AsRef<Path>>(path: P) -> io::Result<Journal> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        Ok(Journal {
+            path: path.to_path_buf(),
+            file: OpenOptions::new().write(true).create_new(true).open(path)?,
+            entries: Vec::new(),
+        })
+    }
+
+    /// Loads the journal of an interrupted transaction, if there is one at the given location
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Option<Journal>> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            match serde_json::from_str(&line?) {
+                Ok(entry) => entries.push(entry),
+
+                // The last entry may have been partially written if the transaction was interrupted
+                Err(_) => break,
+            }
+        }
+
+        Ok(Some(Journal {
+            path: path.to_path_buf(),
+            file: OpenOptions::new().append(true).open(path)?,
+            entries,
+        }))
+    }
+
+    /// Returns a slice of the changes recorded in the journal
+    #[inline]
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Returns the folder holding the backups of the removed files
+    fn backup_dir(&self) -> PathBuf {
+        self.path.with_extension("backup")
+    }
+
+    /// Records a change in the journal, before it is made
+    pub(crate) fn record(&mut self, entry: JournalEntry) -> io::Result<()> {
+        writeln!(self.file, "{}", serde_json::to_string(&entry)?)?;
+        self.file.sync_data()?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Records the creation of the given path, and of all its missing ancestors
+    pub(crate) fn record_creation(&mut self, path: &Path) -> io::Result<()> {
+        let mut missing: Vec<&Path> = path
+            .ancestors()
+            .take_while(|ancestor| fs::symlink_metadata(ancestor).is_err())
+            .collect();
+
+        // Record the outermost directories first, so they are removed last
+        missing.reverse();
+        for path in missing {
+            self.record(JournalEntry::Created(path.to_path_buf()))?;
+        }
+        Ok(())
+    }
+
+    /// Removes the given file, keeping a backup of it so that it can be restored
+    pub(crate) fn remove_file(&mut self, path: &Path) -> io::Result<()> {
+        let backup_dir = self.backup_dir();
+        fs::create_dir_all(&backup_dir)?;
+
+        let backup = backup_dir.join(self.entries.len().to_string());
+        self.record(JournalEntry::Moved {
+            from: path.to_path_buf(),
+            to: backup.clone(),
+        })?;
+        move_file(path, &backup)
+    }
+
+    /// Removes the given empty directory, keeping track of its permissions so that it can be restored
+    pub(crate) fn remove_dir(&mut self, path: &Path) -> io::Result<()> {
+        let mode = fs::metadata(path)?.permissions().mode();
+
+        self.record(JournalEntry::RemovedDirectory {
+            path: path.to_path_buf(),
+            mode,
+        })?;
+        fs::remove_dir(path)
+    }
+
+    /// Concludes the transaction according to its result: the journal is committed if it
+    /// succeeded, and rolled back otherwise.
+    ///
+    /// A failed rollback doesn't hide the error of the transaction: the journal is then kept on
+    /// disk, so that the rollback can be attempted again later.
+    pub(crate) fn conclude<T, E, F>(self, result: Result<T, E>, journal_error: F) -> Result<T, E>
+    where
+        F: FnOnce(io::Error) -> E,
+    {
+        match result {
+            Ok(value) => {
+                self.commit().map_err(journal_error)?;
+                Ok(value)
+            }
+            Err(e) => {
+                if let Err(rollback_error) = self.rollback() {
+                    warn!("failed to roll back the transaction: {}", rollback_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Ends the transaction successfully, deleting the journal and the backups of the removed files
+    pub fn commit(self) -> io::Result<()> {
+        let backup_dir = self.backup_dir();
+
+        if backup_dir.exists() {
+            fs::remove_dir_all(&backup_dir)?;
+        }
+        fs::remove_file(&self.path)
+    }
+
+    /// Undoes all the changes recorded in the journal, in reverse order, and deletes the journal.
+    ///
+    /// If a change can't be undone, the journal is kept so that the rollback can be attempted again later.
+    pub fn rollback(self) -> io::Result<()> {
+        info!(
+            "rolling back {} change(s) of the transaction",
+            self.entries.len()
+        );
+        for entry in self.entries.iter().rev() {
+            match entry {
+                JournalEntry::Created(path) => match fs::symlink_metadata(path) {
+                    Ok(ref metadata) if metadata.is_dir() => {
+                        // Directories may be shared with files that weren't created by the transaction
+                        if fs::read_dir(path)?.next().is_none() {
+                            fs::remove_dir(path)?;
+                        }
+                    }
+                    Ok(_) => fs::remove_file(path)?,
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                    Err(e) => return Err(e),
+                },
+                JournalEntry::Moved { from, to } => {
+                    if fs::symlink_metadata(to).is_ok() {
+                        move_file(to, from)?;
+                    }
+                }
+                JournalEntry::RemovedDirectory { path, mode } => {
+                    if !path.exists() {
+                        fs::create_dir_all(path)?;
+                        fs::set_permissions(path, Permissions::from_mode(*mode))?;
+                    }
+                }
+            }
+        }
+        self.commit()
+    }
+}
+
+/// Rolls back the transaction that was interrupted before it could be concluded, if any.
+///
+/// Returns true if such a transaction was found and rolled back. This must be done before
+/// performing any new transaction.
+pub fn rollback_interrupted_transaction(
+    config: &Config,
+    _: &LockFileOwnership,
+) -> io::Result<bool> {
+    // The files extracted by the interrupted transaction and not moved in place yet are useless
+    let staging = config.paths().staging();
+    if staging.exists() {
+        fs::remove_dir_all(staging)?;
+    }
+
+    match Journal::load(config.paths().journal())? {
+        Some(journal) => {
+            journal.rollback()?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Moves a file, copying it if it has to cross a filesystem boundary
+pub(crate) fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        symlink(fs::read_link(from)?, to)?;
+    } else {
+        fs::copy(from, to)?;
+    }
+    fs::remove_file(from)
+}
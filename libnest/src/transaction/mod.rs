@@ -5,24 +5,53 @@
 //! - Pull
 //! - Installation
 //! - Removal
+//! - Reinstallation
 //! - Upgrade
 //!
 
+mod collisions;
+mod config_files;
 mod download;
 mod errors;
 mod extract;
+mod fetch;
+mod hooks;
+mod http;
 mod install;
 mod instructions;
+mod journal;
+mod pending;
+mod pipeline;
+mod plan;
+mod progress;
 mod pull;
+mod reinstall;
 mod remove;
+mod safety;
+mod sandbox;
+mod snapshot;
+mod steps;
 mod upgrade;
 
-pub use self::download::PackageDownload;
+pub use self::collisions::{find_file_collisions, FileCollision};
+pub use self::download::{
+    DownloadCancellation, DownloadScheduler, DownloadThrottle, PackageDownload,
+};
 pub use self::errors::*;
+pub use self::fetch::{FetchRequest, Fetcher, Fetchers, FileFetcher};
+pub use self::hooks::{Hook, HookAction, HookOperation, HookTrigger, HookWhen, TriggeredHooks};
+pub use self::http::HttpFetcher;
 pub use self::install::InstallTransaction;
 pub use self::instructions::{ExecutionOutput, InstructionsExecutor};
+pub use self::journal::{rollback_interrupted_transaction, Journal, JournalEntry};
+pub use self::pending::{PendingStatus, PendingStep, PendingTransactions};
+pub use self::plan::TransactionPlan;
+pub use self::progress::{NoProgress, ProgressHandler, TransactionPhase};
 pub use self::pull::PullTransaction;
+pub use self::reinstall::ReinstallTransaction;
 pub use self::remove::RemoveTransaction;
+pub use self::snapshot::{restore_snapshot, take_snapshot};
+pub use self::steps::{verify_package, TransactionStep, TransactionSteps};
 pub use self::upgrade::UpgradeTransaction;
 
 /// The different possible variants of transactions
@@ -39,4 +68,7 @@ pub enum Transaction<'a, 'b> {
 
     /// The transaction is an "upgrade" transaction
     Upgrade(UpgradeTransaction),
+
+    /// The transaction is a "reinstall" transaction
+    Reinstall(ReinstallTransaction),
 }
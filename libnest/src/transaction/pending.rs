@@ -0,0 +1,245 @@
+//! Record of the transactions being performed, so that they can be resumed if Nest is interrupted.
+//!
+//! Before the first transaction is applied, the list of transactions is written to the folder of the pending
+//! transactions, along with the dependency graph as it was before them and as it will be after them. Each
+//! transaction is marked as done as soon as it is applied, and the record is deleted once all of them are
+//! concluded. If Nest is killed in the meantime, the record is left behind: the remaining transactions can
+//! then be resumed, or those that were done can be rolled back.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error, ResultExt};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::cache::depgraph::DependencyGraph;
+use crate::cache::history::HistoryAction;
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+
+use super::Transaction;
+
+/// The status of a pending transaction
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum PendingStatus {
+    /// The transaction wasn't applied yet, or was interrupted and rolled back
+    Pending,
+
+    /// The transaction was applied
+    Done,
+}
+
+/// A transaction of a [`PendingTransactions`] record, along with its status
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PendingStep {
+    action: HistoryAction,
+    status: PendingStatus,
+}
+
+impl PendingStep {
+    /// Returns the action performed by the transaction
+    #[inline]
+    pub fn action(&self) -> &HistoryAction {
+        &self.action
+    }
+
+    /// Returns whether the transaction was applied
+    #[inline]
+    pub fn status(&self) -> PendingStatus {
+        self.status
+    }
+}
+
+/// The record of the transactions being performed
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PendingTransactions {
+    #[serde(skip)]
+    path: PathBuf,
+    steps: Vec<PendingStep>,
+    undoes: Option<usize>,
+    #[serde(default)]
+    snapshot: Option<String>,
+}
+
+impl PendingTransactions {
+    /// Records the given transactions as pending, along with the dependency graph they are computed from, the
+    /// one they lead to, and the number of the history entry they undo, if any.
+    ///
+    /// This fails if transactions are already pending: they must be resumed or rolled back first.
+    pub fn create(
+        config: &Config,
+        transactions: &[Transaction],
+        previous_graph: &DependencyGraph,
+        next_graph: &DependencyGraph,
+        undoes: Option<usize>,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<Self, Error> {
+        let path = config.paths().pending().to_path_buf();
+
+        if record_path(&path).exists() {
+            return Err(format_err!("transactions are already pending"));
+        }
+
+        fs::create_dir_all(&path).with_context(|_| path.display().to_string())?;
+        previous_graph.save_to_cache(previous_graph_path(&path), lock_ownership)?;
+        next_graph.save_to_cache(next_graph_path(&path), lock_ownership)?;
+
+        let pending = PendingTransactions {
+            path,
+            steps: transactions
+                .iter()
+                .filter_map(HistoryAction::from_transaction)
+                .map(|action| PendingStep {
+                    action,
+                    status: PendingStatus::Pending,
+                })
+                .collect(),
+            undoes,
+            snapshot: None,
+        };
+        pending.save()?;
+        Ok(pending)
+    }
+
+    /// Loads the record of the interrupted transactions, if any
+    pub fn load(config: &Config, _: &LockFileOwnership) -> Result<Option<Self>, Error> {
+        let path = config.paths().pending();
+        let record = record_path(path);
+
+        if !record.exists() {
+            return Ok(None);
+        }
+
+        let file = File::open(&record).with_context(|_| record.display().to_string())?;
+        let mut pending: PendingTransactions =
+            serde_json::from_reader(&file).with_context(|_| record.display().to_string())?;
+        pending.path = path.to_path_buf();
+        Ok(Some(pending))
+    }
+
+    /// Returns the recorded transactions, in the order they are applied
+    #[inline]
+    pub fn steps(&self) -> &[PendingStep] {
+        &self.steps
+    }
+
+    /// Returns the number of the history entry undone by the transactions, if any
+    #[inline]
+    pub fn undoes(&self) -> Option<usize> {
+        self.undoes
+    }
+
+    /// Returns the name of the snapshot of the system taken before the transactions, if any
+    #[inline]
+    pub fn snapshot(&self) -> Option<&str> {
+        self.snapshot.as_ref().map(String::as_str)
+    }
+
+    /// Records the name of the snapshot of the system taken before the transactions, saving the record before
+    /// returning
+    pub fn record_snapshot(&mut self, name: String) -> Result<(), Error> {
+        self.snapshot = Some(name);
+        self.save()
+    }
+
+    /// Returns the transactions that weren't applied yet, in order
+    pub fn remaining_transactions(&self) -> Vec<Transaction<'static, 'static>> {
+        self.steps
+            .iter()
+            .filter(|step| step.status == PendingStatus::Pending)
+            .map(|step| step.action.to_transaction())
+            .collect()
+    }
+
+    /// Returns the actions of the transactions that were applied, in order
+    pub fn done_actions(&self) -> Vec<HistoryAction> {
+        self.steps
+            .iter()
+            .filter(|step| step.status == PendingStatus::Done)
+            .map(|step| step.action.clone())
+            .collect()
+    }
+
+    /// Returns the transactions undoing those that were applied, in the order they must be applied
+    pub fn rollback_transactions(&self) -> Vec<Transaction<'static, 'static>> {
+        self.steps
+            .iter()
+            .rev()
+            .filter(|step| step.status == PendingStatus::Done)
+            .filter_map(|step| step.action.to_reverse_transaction())
+            .collect()
+    }
+
+    /// Marks the given transaction as applied, saving the record before returning
+    pub fn mark_done(&mut self, transaction: &Transaction) -> Result<(), Error> {
+        let action = match HistoryAction::from_transaction(transaction) {
+            Some(action) => action,
+            None => return Ok(()),
+        };
+
+        if let Some(step) = self
+            .steps
+            .iter_mut()
+            .find(|step| step.status == PendingStatus::Pending && step.action == action)
+        {
+            step.status = PendingStatus::Done;
+        }
+        self.save()
+    }
+
+    /// Loads the dependency graph the transactions were computed from
+    pub fn previous_dependency_graph<'lock_file>(
+        &self,
+        _: &'lock_file LockFileOwnership,
+    ) -> Result<DependencyGraph<'lock_file>, Error> {
+        let phantom: PhantomData<&'lock_file LockFileOwnership> = PhantomData;
+
+        DependencyGraph::load_from_cache(previous_graph_path(&self.path), phantom)
+    }
+
+    /// Loads the dependency graph of the system once all the transactions are applied
+    pub fn next_dependency_graph<'lock_file>(
+        &self,
+        _: &'lock_file LockFileOwnership,
+    ) -> Result<DependencyGraph<'lock_file>, Error> {
+        let phantom: PhantomData<&'lock_file LockFileOwnership> = PhantomData;
+
+        DependencyGraph::load_from_cache(next_graph_path(&self.path), phantom)
+    }
+
+    /// Deletes the record, once the transactions are concluded
+    pub fn clear(self) -> Result<(), Error> {
+        fs::remove_dir_all(&self.path).with_context(|_| self.path.display().to_string())?;
+        Ok(())
+    }
+
+    /// Writes the record to disk, replacing the previous one atomically so that an interruption can't leave
+    /// it half-written
+    fn save(&self) -> Result<(), Error> {
+        let record = record_path(&self.path);
+        let temporary = record.with_extension("json.tmp");
+
+        let mut file =
+            File::create(&temporary).with_context(|_| temporary.display().to_string())?;
+        serde_json::to_writer_pretty(&file, self)
+            .with_context(|_| temporary.display().to_string())?;
+        writeln!(file)?;
+        file.sync_data()?;
+        fs::rename(&temporary, &record).with_context(|_| record.display().to_string())?;
+        Ok(())
+    }
+}
+
+fn record_path(path: &Path) -> PathBuf {
+    path.join("transactions.json")
+}
+
+fn previous_graph_path(path: &Path) -> PathBuf {
+    path.join("previous.depgraph")
+}
+
+fn next_graph_path(path: &Path) -> PathBuf {
+    path.join("next.depgraph")
+}
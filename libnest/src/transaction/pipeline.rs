@@ -0,0 +1,329 @@
+//! Helpers spreading the extraction of a package over several threads.
+//!
+//! The data archive is decompressed ahead of the thread walking through the archive, either by a thread of its own
+//! or, for zstd archives split in independent frames, by a pool of threads decompressing several frames at once.
+//! The regular files are written in batches by a pool of threads while the next entries of the archive are read.
+
+use std::io::{self, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::Scope;
+
+use crate::package::{Compression, Frame};
+
+/// The size of the chunks of decompressed data handed over by the decompression thread
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// The number of decompressed chunks the decompression thread may be ahead of the reading thread
+const CHUNKS_AHEAD: usize = 16;
+
+/// The number of frames each decompression thread may be ahead of the reading thread
+const FRAMES_PER_THREAD: usize = 2;
+
+/// The number of batches each writing thread may be late on
+const BATCHES_PER_THREAD: usize = 2;
+
+/// Runs the given closure, turning a panic into an error
+fn catch_panic<T, F: FnOnce() -> io::Result<T>>(f: F, what: &str) -> io::Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} panicked", what),
+        ))
+    })
+}
+
+/// The decompressed chunks of a data archive, in the order of the archive
+#[derive(Debug)]
+enum Chunks {
+    /// Chunks decompressed one after the other by a single thread
+    Sequential(Receiver<io::Result<Vec<u8>>>),
+
+    /// Frames decompressed by a pool of threads, each received once decompressed
+    Parallel(Receiver<Receiver<io::Result<Vec<u8>>>>),
+}
+
+impl Chunks {
+    /// Waits for the next chunk, returning `None` once the whole archive was read
+    fn next(&self) -> Option<io::Result<Vec<u8>>> {
+        match self {
+            Chunks::Sequential(receiver) => receiver.recv().ok(),
+            Chunks::Parallel(receiver) => receiver.recv().ok().map(|frame| {
+                frame.recv().unwrap_or_else(|_| {
+                    Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "a decompression thread stopped unexpectedly",
+                    ))
+                })
+            }),
+        }
+    }
+}
+
+/// A reader over a data archive decompressed by other threads
+#[derive(Debug)]
+pub(crate) struct ReadAhead {
+    chunks: Chunks,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl ReadAhead {
+    /// Spawns a thread in the given scope decompressing the given data archive, and returns a reader over the
+    /// decompressed archive.
+    ///
+    /// The thread stops once the archive is fully decompressed, or once the reader is dropped.
+    pub(crate) fn spawn<'scope, R: Read + Send + 'scope>(
+        scope: &'scope Scope<'scope, '_>,
+        compression: Compression,
+        reader: R,
+    ) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(CHUNKS_AHEAD);
+
+        scope.spawn(move || {
+            let mut decoder = match compression.decoder(reader) {
+                Ok(decoder) => decoder,
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+            };
+
+            loop {
+                let res = catch_panic(
+                    || {
+                        let mut chunk = vec![0; CHUNK_SIZE];
+                        loop {
+                            match decoder.read(&mut chunk) {
+                                Ok(len) => {
+                                    chunk.truncate(len);
+                                    break Ok(chunk);
+                                }
+                                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                                Err(e) => break Err(e),
+                            }
+                        }
+                    },
+                    "the decompression of the archive",
+                );
+                if let Ok(chunk) = &res {
+                    if chunk.is_empty() {
+                        break;
+                    }
+                }
+                let failed = res.is_err();
+
+                // The reader was dropped, there is no need to go on
+                if sender.send(res).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        ReadAhead {
+            chunks: Chunks::Sequential(receiver),
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Spawns the given number of threads in the given scope decompressing the frames of the given zstd data
+    /// archive, as listed in its seek table, and returns a reader over the decompressed archive.
+    ///
+    /// The frames are read by a thread of its own and handed over to the decompression threads, which stop once
+    /// the archive is fully decompressed, or once the reader is dropped.
+    pub(crate) fn spawn_parallel<'scope, R: Read + Send + 'scope>(
+        scope: &'scope Scope<'scope, '_>,
+        mut reader: R,
+        frames: Vec<Frame>,
+        threads: usize,
+    ) -> Self {
+        let (order_sender, order_receiver) = mpsc::sync_channel(threads * FRAMES_PER_THREAD);
+        let (frame_sender, frame_receiver) =
+            mpsc::sync_channel::<(Vec<u8>, usize, SyncSender<io::Result<Vec<u8>>>)>(threads);
+        let frame_receiver = Arc::new(Mutex::new(frame_receiver));
+
+        for _ in 0..threads {
+            let frame_receiver = frame_receiver.clone();
+
+            scope.spawn(move || loop {
+                let next = frame_receiver
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .recv();
+                let (compressed, size, sender) = match next {
+                    Ok(frame) => frame,
+                    Err(_) => break,
+                };
+
+                let res = catch_panic(
+                    || {
+                        let content = zstd::block::decompress(&compressed, size)?;
+                        if content.len() == size {
+                            Ok(content)
+                        } else {
+                            Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "the size of a frame doesn't match the seek table",
+                            ))
+                        }
+                    },
+                    "the decompression of a frame",
+                );
+                let _ = sender.send(res);
+            });
+        }
+
+        scope.spawn(move || {
+            for frame in frames {
+                let (sender, receiver) = mpsc::sync_channel(1);
+
+                // The reader was dropped, there is no need to go on
+                if order_sender.send(receiver).is_err() {
+                    break;
+                }
+
+                let mut compressed = vec![0; frame.compressed_size()];
+                if let Err(e) = reader.read_exact(&mut compressed) {
+                    let _ = sender.send(Err(e));
+                    break;
+                }
+                if frame_sender
+                    .send((compressed, frame.decompressed_size(), sender))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        ReadAhead {
+            chunks: Chunks::Parallel(order_receiver),
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl Read for ReadAhead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Frames may be empty, so wait until a chunk has some data left
+        while self.position == self.chunk.len() {
+            match self.chunks.next() {
+                Some(chunk) => {
+                    self.chunk = chunk?;
+                    self.position = 0;
+                }
+                // The whole archive was read
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len() - self.position);
+        buf[..len].copy_from_slice(&self.chunk[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// A batch of writes, performed by one of the threads of a [`WritePool`]
+pub(crate) type WriteBatch<'scope> = Box<dyn FnOnce() -> io::Result<()> + Send + 'scope>;
+
+/// The progress of the batches submitted to a [`WritePool`]
+#[derive(Debug, Default)]
+struct PoolState {
+    pending: usize,
+    error: Option<io::Error>,
+}
+
+/// The completion of a batch submitted to a [`WritePool`], recorded once dropped, whatever happened to the batch
+struct Performed<'a> {
+    state: &'a (Mutex<PoolState>, Condvar),
+    result: io::Result<()>,
+}
+
+impl Drop for Performed<'_> {
+    fn drop(&mut self) {
+        let (state, finished) = self.state;
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        state.pending -= 1;
+        if let Err(e) = std::mem::replace(&mut self.result, Ok(())) {
+            state.error.get_or_insert(e);
+        }
+        finished.notify_all();
+    }
+}
+
+/// A pool of threads performing batches of writes
+pub(crate) struct WritePool<'scope> {
+    sender: SyncSender<WriteBatch<'scope>>,
+    state: Arc<(Mutex<PoolState>, Condvar)>,
+}
+
+impl<'scope> WritePool<'scope> {
+    /// Spawns the given number of writing threads in the given scope.
+    ///
+    /// The threads stop once the pool is dropped.
+    pub(crate) fn spawn(scope: &'scope Scope<'scope, '_>, threads: usize) -> Self {
+        let (sender, receiver) =
+            mpsc::sync_channel::<WriteBatch<'scope>>(threads * BATCHES_PER_THREAD);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let state = Arc::new((Mutex::new(PoolState::default()), Condvar::new()));
+
+        for _ in 0..threads {
+            let receiver = receiver.clone();
+            let state = state.clone();
+
+            scope.spawn(move || loop {
+                let batch = match receiver.lock().expect("the write pool is poisoned").recv() {
+                    Ok(batch) => batch,
+                    Err(_) => break,
+                };
+
+                let mut performed = Performed {
+                    state: &state,
+                    result: Ok(()),
+                };
+                performed.result = catch_panic(batch, "a batch of writes");
+            });
+        }
+
+        WritePool { sender, state }
+    }
+
+    /// Takes the error of the first batch that failed, if any
+    fn take_error(state: &mut PoolState) -> io::Result<()> {
+        match state.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Submits the given batch to the pool, blocking while the threads are late on too many batches.
+    ///
+    /// An error is returned if a batch submitted previously failed.
+    pub(crate) fn submit(&self, batch: WriteBatch<'scope>) -> io::Result<()> {
+        {
+            let mut state = self.state.0.lock().expect("the write pool is poisoned");
+            Self::take_error(&mut state)?;
+            state.pending += 1;
+        }
+        self.sender
+            .send(batch)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "the write pool is stopped"))
+    }
+
+    /// Waits for all the submitted batches to be performed, returning the error of the first one that failed or
+    /// panicked, if any
+    pub(crate) fn flush(&self) -> io::Result<()> {
+        let (state, finished) = &*self.state;
+        let mut state = state.lock().expect("the write pool is poisoned");
+
+        while state.pending > 0 {
+            state = finished.wait(state).expect("the write pool is poisoned");
+        }
+        Self::take_error(&mut state)
+    }
+}
@@ -0,0 +1,201 @@
+//! Description of the changes a transaction would make to the system, computed without making them.
+
+use std::path::PathBuf;
+
+use tar::Archive;
+
+use crate::cache::available::AvailablePackagesCacheQueryStrategy;
+use crate::chroot::Chroot;
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{Kind, PackageID, SoftPackageRequirement};
+
+use super::extract::unexplorable_package;
+use super::{InstallError, InstallErrorKind, RemoveError, RemoveErrorKind};
+
+/// The changes a transaction would make to the system.
+///
+/// The content of a package that isn't downloaded yet is unknown: such a package is listed in
+/// [`downloads()`][TransactionPlan::downloads], but its files and instructions are not part of the plan.
+/// Its installed size is still counted if its repository advertises it, and it is listed in
+/// [`unknown_sizes()`][TransactionPlan::unknown_sizes] otherwise.
+#[derive(Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub struct TransactionPlan {
+    downloads: Vec<PackageID>,
+    created_files: Vec<PathBuf>,
+    removed_files: Vec<PathBuf>,
+    instructions: Vec<(PackageID, &'static str)>,
+    installed_size: u64,
+    removed_size: u64,
+    unknown_sizes: Vec<PackageID>,
+}
+
+impl TransactionPlan {
+    /// Returns the packages that would have to be downloaded
+    #[inline]
+    pub fn downloads(&self) -> &[PackageID] {
+        &self.downloads
+    }
+
+    /// Returns the files that would be created, as absolute paths relative to the installation root
+    #[inline]
+    pub fn created_files(&self) -> &[PathBuf] {
+        &self.created_files
+    }
+
+    /// Returns the files that would be removed, as absolute paths relative to the installation root
+    #[inline]
+    pub fn removed_files(&self) -> &[PathBuf] {
+        &self.removed_files
+    }
+
+    /// Returns the functions of `instructions.sh` that would be executed, along with the package
+    /// they belong to, in the order they would be executed
+    #[inline]
+    pub fn instructions(&self) -> &[(PackageID, &'static str)] {
+        &self.instructions
+    }
+
+    /// Returns the total size, in bytes, of the files that would be created, including the advertised size of
+    /// the packages to download
+    #[inline]
+    pub fn installed_size(&self) -> u64 {
+        self.installed_size
+    }
+
+    /// Returns the total size, in bytes, of the files that would be removed, as they currently are on disk
+    #[inline]
+    pub fn removed_size(&self) -> u64 {
+        self.removed_size
+    }
+
+    /// Returns the packages that would be installed whose size is unknown, because they aren't downloaded and
+    /// their repository doesn't advertise it
+    #[inline]
+    pub fn unknown_sizes(&self) -> &[PackageID] {
+        &self.unknown_sizes
+    }
+
+    /// Adds the changes made by the installation of the given package to this plan
+    pub(crate) fn plan_installation(
+        &mut self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        target: &PackageID,
+    ) -> Result<(), InstallError> {
+        let downloaded_packages = config.downloaded_packages_cache(lock_ownership);
+        if !downloaded_packages.has_package(target) {
+            let advertised_size = config
+                .available_packages_cache(lock_ownership)
+                .query(&SoftPackageRequirement::from_id(target))
+                .set_strategy(AvailablePackagesCacheQueryStrategy::AllMatchesUnsorted)
+                .perform()
+                .ok()
+                .and_then(|results| {
+                    results
+                        .into_iter()
+                        .find(|result| result.id() == *target)
+                        .and_then(|result| result.manifest().installed_size())
+                });
+
+            match advertised_size {
+                Some(size) => self.installed_size += size,
+                None => self.unknown_sizes.push(target.clone()),
+            }
+            self.downloads.push(target.clone());
+            return Ok(());
+        }
+
+        let npf_explorer = downloaded_packages
+            .explore_package(target)
+            .map_err(unexplorable_package)?;
+        let architectures = config.architecture_preferences();
+        let architecture = npf_explorer.manifest().architecture();
+        let prefix = architectures.installation_prefix(architecture);
+
+        // The instructions of the packages of a foreign architecture, or installed in a sysroot, aren't run
+        let has_instructions = config.runs_instructions(architecture)
+            && npf_explorer
+                .open_instructions()
+                .map_err(|_| InstallErrorKind::InvalidPackageFile)?
+                .is_some();
+
+        if has_instructions {
+            self.instructions.push((target.clone(), "before_install"));
+        }
+
+        if npf_explorer.manifest().kind() == Kind::Effective {
+            let mut tarball_handle = npf_explorer
+                .open_data()
+                .map_err(|_| InstallErrorKind::InvalidPackageFile)?
+                .unwrap();
+            let mut archive = Archive::new(
+                npf_explorer
+                    .manifest()
+                    .compression()
+                    .decoder(&mut tarball_handle)
+                    .map_err(|_| InstallErrorKind::InvalidPackageData)?,
+            );
+
+            for entry in archive
+                .entries()
+                .map_err(|_| InstallErrorKind::InvalidPackageData)?
+            {
+                let entry = entry.map_err(|_| InstallErrorKind::InvalidPackageData)?;
+                let entry_path = entry
+                    .path()
+                    .map_err(|_| InstallErrorKind::InvalidPackageData)?;
+
+                self.created_files.push(prefix.with_content(&entry_path));
+                self.installed_size += entry.header().size().unwrap_or(0);
+            }
+        }
+
+        if has_instructions {
+            self.instructions.push((target.clone(), "after_install"));
+        }
+        Ok(())
+    }
+
+    /// Adds the changes made by the removal of the given package to this plan
+    pub(crate) fn plan_removal(
+        &mut self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        target: &PackageID,
+    ) -> Result<(), RemoveError> {
+        let npf_explorer = config
+            .downloaded_packages_cache(lock_ownership)
+            .explore_package(target)
+            .map_err(|_| RemoveErrorKind::InvalidCachedPackageFile)?;
+        let has_instructions = config.runs_instructions(npf_explorer.manifest().architecture())
+            && npf_explorer
+                .open_instructions()
+                .map_err(|_| RemoveErrorKind::InvalidCachedPackageFile)?
+                .is_some();
+
+        if has_instructions {
+            self.instructions.push((target.clone(), "before_remove"));
+        }
+
+        if npf_explorer.manifest().kind() == Kind::Effective {
+            let log = config
+                .installed_packages_cache(lock_ownership)
+                .package_log(target)
+                .map_err(RemoveErrorKind::LogFileLoadError)?;
+
+            self.removed_files.extend(
+                log.files()
+                    .iter()
+                    .rev()
+                    .map(|entry| entry.path().to_path_buf()),
+            );
+            self.removed_size += log.disk_usage(config.paths().root());
+        }
+
+        if has_instructions {
+            self.instructions.push((target.clone(), "after_remove"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+//! Reporting of the progress of the transactions.
+//!
+//! Libnest doesn't render any progress by itself: it reports it to a [`ProgressHandler`] given by the frontend,
+//! which is free to render it however it likes.
+
+use crate::package::PackageID;
+
+/// A phase of the processing of a package by a transaction
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum TransactionPhase {
+    /// The package is being downloaded
+    Download,
+
+    /// The instructions of the package are being run, before or after its files are extracted or removed
+    Instructions,
+
+    /// The files of the package are being extracted
+    Extraction,
+
+    /// The files of the package are being removed
+    Removal,
+}
+
+/// A trait to be notified of the progress of the transactions.
+///
+/// Every method does nothing by default, so that implementors only handle the events they are interested in.
+/// Packages may be downloaded from multiple threads at the same time, hence the [`Sync`] requirement.
+pub trait ProgressHandler: Sync {
+    /// Called when a phase of the processing of the given package starts
+    fn phase_started(&self, _package: &PackageID, _phase: TransactionPhase) {}
+
+    /// Called when a phase of the processing of the given package ends, whether it succeeded or not
+    fn phase_finished(&self, _package: &PackageID, _phase: TransactionPhase) {}
+
+    /// Called as the given package is downloaded, with the number of bytes already downloaded and the size of
+    /// the package, if known
+    fn download_progress(&self, _package: &PackageID, _downloaded: u64, _total: Option<u64>) {}
+
+    /// Called after each entry of the given package is extracted, with the number of entries already extracted
+    /// and the number of entries of the package
+    fn extraction_progress(&self, _package: &PackageID, _extracted: usize, _total: usize) {}
+
+    /// Called after each file of the given package is removed, with the number of files already removed and the
+    /// number of files of the package
+    fn removal_progress(&self, _package: &PackageID, _removed: usize, _total: usize) {}
+}
+
+/// A [`ProgressHandler`] ignoring the progress of the transactions
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct NoProgress;
+
+impl ProgressHandler for NoProgress {}
+
+/// Runs the given function as the given phase of the processing of the given package, reporting its start
+/// and its end to the given [`ProgressHandler`]
+pub(crate) fn in_phase<T, F>(
+    progress: &dyn ProgressHandler,
+    package: &PackageID,
+    phase: TransactionPhase,
+    f: F,
+) -> T
+where
+    F: FnOnce() -> T,
+{
+    progress.phase_started(package, phase);
+    let res = f();
+    progress.phase_finished(package, phase);
+    res
+}
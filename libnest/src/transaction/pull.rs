@@ -3,18 +3,38 @@ use std::io::{Cursor, Seek, Write};
 use std::str;
 
 use failure::{Error, ResultExt};
+use log::{debug, info};
+use serde_derive::Deserialize;
 use serde_json;
 
+use crate::cache::available::{RepositoryChanges, RepositoryGroup};
 use crate::cache::CacheErrorKind;
 use crate::lock_file::LockFileOwnership;
-use crate::package::PackageManifest;
-use crate::repository::Repository;
+use crate::package::{PackageManifest, TrustedKey};
+use crate::repository::{
+    MetadataError, MetadataErrorKind, MetadataRole, Repository, SignedMetadata, Snapshot, Timestamp,
+};
+
+/// The content of a repository, as pulled: either the list of its packages, or its packages along with
+/// the groups it defines
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PulledRepository {
+    Packages(Vec<PackageManifest>),
+    Repository {
+        packages: Vec<PackageManifest>,
+        #[serde(default)]
+        groups: Vec<RepositoryGroup>,
+    },
+}
 
 /// Structure representing a "pull" transaction
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct PullTransaction<'a, 'b> {
     target_repository: Repository<'a, 'b>,
     data: Vec<u8>,
+    serial: Option<u64>,
+    snapshot: Option<Snapshot>,
 }
 
 impl<'a, 'b> PullTransaction<'a, 'b> {
@@ -23,6 +43,8 @@ impl<'a, 'b> PullTransaction<'a, 'b> {
         PullTransaction {
             target_repository: repository,
             data: Vec::new(),
+            serial: None,
+            snapshot: None,
         }
     }
 
@@ -31,8 +53,56 @@ impl<'a, 'b> PullTransaction<'a, 'b> {
         &self.target_repository
     }
 
-    /// Returns a writer to store data
+    /// Returns the serial number of the repository's journal the pulled packages are up to date with, if it is known
+    #[inline]
+    pub fn serial(&self) -> Option<u64> {
+        self.serial
+    }
+
+    /// Returns a mutable reference to the serial number of the repository's journal the pulled packages
+    /// are up to date with
+    #[inline]
+    pub fn serial_mut(&mut self) -> &mut Option<u64> {
+        &mut self.serial
+    }
+
+    /// Returns the verified snapshot of the repository the pulled data must match, if the metadata of the
+    /// repository was verified
+    #[inline]
+    pub fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// Verifies the given signed timestamp and snapshot of the repository with the given keys, making sure
+    /// they are fresh and not older than the serial number pulled previously, if any.
+    ///
+    /// Once verified, the data pulled must match the snapshot to be saved, and the serial number of the
+    /// pulled packages is the one of the snapshot.
+    pub fn verify_metadata(
+        &mut self,
+        timestamp: &[u8],
+        snapshot: &[u8],
+        keys: &[TrustedKey],
+        last_serial: Option<u64>,
+    ) -> Result<(), Error> {
+        let timestamp: Timestamp = SignedMetadata::parse(timestamp, MetadataRole::Timestamp)?
+            .verify(MetadataRole::Timestamp, keys)?;
+        timestamp.check(last_serial)?;
+
+        let snapshot = timestamp.verify_snapshot(snapshot, keys)?;
+        debug!(
+            "verified the metadata of {}, at serial {}",
+            self.target_repository.name(),
+            snapshot.serial()
+        );
+        self.serial = Some(snapshot.serial());
+        self.snapshot = Some(snapshot);
+        Ok(())
+    }
+
+    /// Returns a writer to store data, discarding any data stored previously
     pub fn writer(&mut self) -> impl Write + Seek + '_ {
+        self.data.clear();
         Cursor::new(&mut self.data)
     }
 
@@ -42,22 +112,89 @@ impl<'a, 'b> PullTransaction<'a, 'b> {
         config: &crate::config::Config,
         ownership: &LockFileOwnership,
     ) -> Result<(), Error> {
-        let res: Result<Vec<PackageManifest>, Error> = try {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.verify_packages(&self.data)?;
+        }
+
+        let res: Result<PulledRepository, Error> = try {
             let utf8 = str::from_utf8(&self.data)?;
             serde_json::from_str(utf8)?
         };
 
-        let manifests = res.context(CacheErrorKind::CacheWriteError)?;
+        let (manifests, groups) = match res.context(CacheErrorKind::CacheWriteError)? {
+            PulledRepository::Packages(packages) => (packages, Vec::new()),
+            PulledRepository::Repository { packages, groups } => (packages, groups),
+        };
         let cache = config.available_packages_cache(ownership);
 
         cache.erase_repository(&self.target_repository)?;
+        cache.update_repository(&self.target_repository, &manifests, &groups, self.serial)?;
+        info!(
+            "pulled {} package(s) from {}",
+            manifests.len(),
+            self.target_repository.name()
+        );
+        Ok(())
+    }
 
-        for manifest in manifests {
-            cache
-                .update(&manifest)
-                .with_context(|_| manifest.name().to_string())
-                .context(CacheErrorKind::CacheWriteError)?;
+    /// Save the stored data, the file lists of the repository's packages, to the available packages cache.
+    ///
+    /// If the metadata of the repository was verified, the file lists must be part of the snapshot.
+    pub fn save_file_lists_to_cache(
+        &self,
+        config: &crate::config::Config,
+        ownership: &LockFileOwnership,
+    ) -> Result<(), Error> {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.verify_files(&self.data)?;
         }
-        Ok(())
+
+        config
+            .available_packages_cache(ownership)
+            .update_repository_file_lists(&self.target_repository, &self.data)
+    }
+
+    /// Save the stored data, the security advisories of the repository's packages, to the available packages cache.
+    ///
+    /// If the metadata of the repository was verified, the advisories must be part of the snapshot.
+    pub fn save_advisories_to_cache(
+        &self,
+        config: &crate::config::Config,
+        ownership: &LockFileOwnership,
+    ) -> Result<(), Error> {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.verify_advisories(&self.data)?;
+        }
+
+        config
+            .available_packages_cache(ownership)
+            .update_repository_advisories(&self.target_repository, &self.data)
+    }
+
+    /// Applies the stored data, the changes made to the repository since the last pull, to the available
+    /// packages cache
+    pub fn save_changes_to_cache(
+        &self,
+        config: &crate::config::Config,
+        ownership: &LockFileOwnership,
+    ) -> Result<(), Error> {
+        let res: Result<RepositoryChanges, Error> = try {
+            let utf8 = str::from_utf8(&self.data)?;
+            serde_json::from_str(utf8)?
+        };
+
+        let changes = res.context(CacheErrorKind::CacheWriteError)?;
+        let cache = config.available_packages_cache(ownership);
+
+        if let Some(snapshot) = &self.snapshot {
+            match cache.repository_serial(&self.target_repository)? {
+                Some(since) => snapshot.verify_changes(since, &self.data)?,
+                None => return Err(MetadataError::from(MetadataErrorKind::ChecksumMismatch).into()),
+            }
+            if changes.serial() != snapshot.serial() {
+                return Err(MetadataError::from(MetadataErrorKind::InconsistentMetadata).into());
+            }
+        }
+        cache.apply_repository_changes(&self.target_repository, &changes)
     }
 }
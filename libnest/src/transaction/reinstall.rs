@@ -0,0 +1,97 @@
+use failure::Error;
+
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::{Kind, PackageID};
+
+use super::config_files::ModifiedConfigFiles;
+use super::download::PackageDownload;
+use super::extract::{extract_package, unexplorable_package};
+use super::journal::Journal;
+use super::plan::TransactionPlan;
+use super::progress::ProgressHandler;
+use super::remove::remove_package_files;
+use super::{InstallError, InstallErrorKind::JournalError};
+
+/// Structure representing a "reinstall" transaction: the files of an installed package are extracted
+/// again and its installation instructions are executed again, without changing the installed packages
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ReinstallTransaction {
+    target: PackageID,
+}
+
+impl ReinstallTransaction {
+    /// Creates a [`ReinstallTransaction`] from a given [`PackageID`]
+    #[inline]
+    pub fn from(target: PackageID) -> Self {
+        ReinstallTransaction { target }
+    }
+
+    /// Returns the target [`PackageID`] for this transaction
+    pub fn target(&self) -> &PackageID {
+        &self.target
+    }
+
+    /// Create a download associated to this transaction
+    pub fn associated_download(&self) -> PackageDownload {
+        PackageDownload::from(self.target().clone())
+    }
+
+    /// Computes the changes the reinstallation would make to the system, without making them
+    pub fn plan(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<TransactionPlan, InstallError> {
+        let mut plan = TransactionPlan::default();
+        plan.plan_installation(config, lock_ownership, self.target())?;
+        Ok(plan)
+    }
+
+    /// Perform the reinstallation of the package, reporting its progress to the given [`ProgressHandler`].
+    ///
+    /// The files listed in the log of the package are removed, whatever their state on disk, before
+    /// the downloaded package is extracted again. If any step fails, the removed files are restored.
+    /// Nothing is done if the configuration is in dry-run mode.
+    pub fn perform(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        progress: &dyn ProgressHandler,
+    ) -> Result<(), Error> {
+        if config.dry_run() {
+            return Ok(());
+        }
+
+        let downloaded_packages = config.downloaded_packages_cache(lock_ownership);
+        let npf_explorer = downloaded_packages
+            .explore_package(self.target())
+            .map_err(unexplorable_package)?;
+        let is_effective = npf_explorer.manifest().kind() == Kind::Effective;
+
+        let mut journal = Journal::create(config.paths().journal()).map_err(JournalError)?;
+
+        let res: Result<(), Error> = try {
+            if is_effective {
+                remove_package_files(
+                    config,
+                    lock_ownership,
+                    self.target(),
+                    &ModifiedConfigFiles::new(),
+                    &mut journal,
+                    progress,
+                )?;
+            }
+            extract_package(
+                config,
+                lock_ownership,
+                npf_explorer,
+                self.target(),
+                &ModifiedConfigFiles::new(),
+                &mut journal,
+                progress,
+            )?;
+        };
+        journal.conclude(res, |e| JournalError(e).into())
+    }
+}
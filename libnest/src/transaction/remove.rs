@@ -2,12 +2,17 @@ use std::fs;
 use std::path::Path;
 
 use failure::ResultExt;
+use log::{debug, info};
 
 use crate::chroot::Chroot;
 use crate::config::Config;
 use crate::lock_file::LockFileOwnership;
 use crate::package::{Kind, NPFExplorer, PackageID};
 
+use super::config_files::ModifiedConfigFiles;
+use super::journal::Journal;
+use super::plan::TransactionPlan;
+use super::progress::{in_phase, ProgressHandler, TransactionPhase};
 use super::{RemoveError, RemoveErrorKind::*};
 
 /// Structure representing a "remove" transaction
@@ -27,18 +32,48 @@ impl RemoveTransaction {
         &self.target
     }
 
-    /// Performs the removal of the package
+    /// Computes the changes the removal would make to the system, without making them
+    pub fn plan(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<TransactionPlan, RemoveError> {
+        let mut plan = TransactionPlan::default();
+        plan.plan_removal(config, lock_ownership, self.target())?;
+        Ok(plan)
+    }
+
+    /// Performs the removal of the package, reporting its progress to the given [`ProgressHandler`].
+    ///
+    /// If any step of the removal fails, the removed files are restored.
+    /// Nothing is done if the configuration is in dry-run mode.
     pub fn perform(
         &self,
         config: &Config,
         lock_ownership: &LockFileOwnership,
+        progress: &dyn ProgressHandler,
     ) -> Result<(), RemoveError> {
+        if config.dry_run() {
+            return Ok(());
+        }
+
         let downloaded_packages = config.downloaded_packages_cache(lock_ownership);
         let npf_explorer = downloaded_packages
             .explore_package(self.target())
             .map_err(|_| InvalidCachedPackageFile)?;
 
-        remove_package(config, lock_ownership, npf_explorer, self.target())
+        // Restore the removed files if any step of the removal fails
+        let mut journal = Journal::create(config.paths().journal()).map_err(JournalError)?;
+        let res = remove_package(
+            config,
+            lock_ownership,
+            npf_explorer,
+            self.target(),
+            &ModifiedConfigFiles::new(),
+            &mut journal,
+            progress,
+        );
+        journal.conclude(res, |e| JournalError(e).into())
     }
 }
 
@@ -48,69 +83,139 @@ fn is_empty_directory(dir_path: &Path) -> std::io::Result<bool> {
     Ok(it.next().is_none())
 }
 
-/// Remove the package from a given [`NPFExplorer`], using a given [`PackageID`]'s log
+/// Remove the package from a given [`NPFExplorer`], using a given [`PackageID`]'s log,
+/// recording every removed file in the given [`Journal`] so it can be restored.
+///
+/// The given configuration files modified locally are left in place, and the progress of the removal is
+/// reported to the given [`ProgressHandler`].
 pub(crate) fn remove_package(
     config: &Config,
     lock_ownership: &LockFileOwnership,
     npf_explorer: NPFExplorer,
     target_id: &PackageID,
+    preserved: &ModifiedConfigFiles,
+    journal: &mut Journal,
+    progress: &dyn ProgressHandler,
 ) -> Result<(), RemoveError> {
     let instructions_handle = npf_explorer
         .load_instructions()
-        .map_err(|_| InvalidCachedPackageFile)?;
+        .map_err(|_| InvalidCachedPackageFile)?
+        .filter(|_| config.runs_instructions(npf_explorer.manifest().architecture()))
+        .map(|executor| {
+            executor.with_sandboxing(config.instructions_sandboxing(target_id.repository()))
+        });
 
     if let Some(executor) = &instructions_handle {
-        executor
-            .execute_before_remove(config.paths().root())
-            .map_err(PreRemoveInstructionsFailure)?;
+        in_phase(progress, target_id, TransactionPhase::Instructions, || {
+            executor.execute_before_remove(config.paths().root())
+        })
+        .map_err(PreRemoveInstructionsFailure)?;
     }
 
     // If the package is effective, installed files must be removed
     if npf_explorer.manifest().kind() == Kind::Effective {
-        // Open the log file, and remove all the files listed in it
-        let log = config
-            .installed_packages_cache(lock_ownership)
-            .package_log(target_id)
-            .map_err(LogFileLoadError)?;
-
-        // Iterate backwards to ensure removal of nested files before that of top-level directories
-        for entry in log.files().into_iter().rev() {
-            let abs_path = Path::new("/").with_content(entry.path());
-            let rel_path = config.paths().root().with_content(entry.path());
-
-            if let Ok(metadata) = fs::symlink_metadata(&rel_path) {
-                match (entry.file_type().is_dir(), metadata.file_type().is_dir()) {
-                    // The file to remove is a directory, remove it if it is empty
-                    (true, true) => {
-                        if let Ok(true) = is_empty_directory(&rel_path) {
-                            fs::remove_dir(&rel_path)
-                        } else {
-                            Ok(())
-                        }
-                    }
+        remove_package_files(
+            config,
+            lock_ownership,
+            target_id,
+            preserved,
+            journal,
+            progress,
+        )?;
+    }
 
-                    // The file was expected to be a directory, but is a symlink, leave it
-                    (true, false) if metadata.file_type().is_symlink() => Ok(()),
+    if let Some(executor) = &instructions_handle {
+        in_phase(progress, target_id, TransactionPhase::Instructions, || {
+            executor.execute_after_remove(config.paths().root())
+        })
+        .map_err(PostRemoveInstructionsFailure)?;
+    }
 
-                    // The file to remove is a regular file, remove it
-                    _ => fs::remove_file(&rel_path),
-                }
-                .with_context(|_| FileRemoveError(abs_path))?;
-            }
+    Ok(())
+}
+
+/// Remove the files installed by the given [`PackageID`], listed in its log, and the log itself,
+/// recording every removed file in the given [`Journal`] so it can be restored.
+///
+/// The given configuration files modified locally are left in place, and the progress of the removal is
+/// reported to the given [`ProgressHandler`].
+pub(crate) fn remove_package_files(
+    config: &Config,
+    lock_ownership: &LockFileOwnership,
+    target_id: &PackageID,
+    preserved: &ModifiedConfigFiles,
+    journal: &mut Journal,
+    progress: &dyn ProgressHandler,
+) -> Result<(), RemoveError> {
+    in_phase(progress, target_id, TransactionPhase::Removal, || {
+        remove_logged_files(
+            config,
+            lock_ownership,
+            target_id,
+            preserved,
+            journal,
+            progress,
+        )
+    })
+}
+
+/// Does the actual work of [`remove_package_files()`]
+fn remove_logged_files(
+    config: &Config,
+    lock_ownership: &LockFileOwnership,
+    target_id: &PackageID,
+    preserved: &ModifiedConfigFiles,
+    journal: &mut Journal,
+    progress: &dyn ProgressHandler,
+) -> Result<(), RemoveError> {
+    // Open the log file, and remove all the files listed in it
+    let log = config
+        .installed_packages_cache(lock_ownership)
+        .package_log(target_id)
+        .map_err(LogFileLoadError)?;
+
+    // Iterate backwards to ensure removal of nested files before that of top-level directories
+    let total = log.files().len();
+    debug!("removing {} file(s) of {}", total, target_id);
+    for (i, entry) in log.files().into_iter().rev().enumerate() {
+        progress.removal_progress(target_id, i, total);
+        if preserved.contains_key(entry.path()) {
+            debug!("keeping {}, modified locally", entry.path().display());
+            continue;
         }
 
-        config
-            .installed_packages_cache(lock_ownership)
-            .remove_package_log(target_id)
-            .with_context(|_| target_id.to_string())
-            .with_context(|_| LogFileRemoveError)?;
-    }
+        let abs_path = Path::new("/").with_content(entry.path());
+        let rel_path = config.paths().root().with_content(entry.path());
+
+        if let Ok(metadata) = fs::symlink_metadata(&rel_path) {
+            match (entry.file_type().is_dir(), metadata.file_type().is_dir()) {
+                // The file to remove is a directory, remove it if it is empty
+                (true, true) => {
+                    if let Ok(true) = is_empty_directory(&rel_path) {
+                        journal.remove_dir(&rel_path)
+                    } else {
+                        Ok(())
+                    }
+                }
 
-    if let Some(executor) = &instructions_handle {
-        executor
-            .execute_after_remove(config.paths().root())
-            .map_err(PostRemoveInstructionsFailure)?;
-    }
+                // The file was expected to be a directory, but is a symlink, leave it
+                (true, false) if metadata.file_type().is_symlink() => Ok(()),
 
+                // The file to remove is a regular file, remove it
+                _ => journal.remove_file(&rel_path),
+            }
+            .with_context(|_| FileRemoveError(abs_path))?;
+        }
+    }
+    progress.removal_progress(target_id, total, total);
+
+    let log_path = config
+        .installed_packages_cache(lock_ownership)
+        .package_log_path(target_id);
+    journal
+        .remove_file(&log_path)
+        .with_context(|_| target_id.to_string())
+        .with_context(|_| LogFileRemoveError)?;
+    info!("removed the files of {}", target_id);
     Ok(())
 }
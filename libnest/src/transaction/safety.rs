@@ -0,0 +1,170 @@
+//! Checks protecting the host against malicious data archives.
+//!
+//! Each entry of a data archive is checked before anything is extracted, so that a package is either fully
+//! extracted or not at all. The folders the files are written in are checked again on extraction, as they may
+//! be reached through symbolic links.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use tar::{Entry, EntryType};
+
+use crate::config::RepositoryTrust;
+
+use super::{InstallErrorKind, InstallErrorKind::*, UnsafeEntry};
+
+/// The setuid and setgid permission bits
+const SETID_BITS: u32 = 0o6000;
+
+/// The number of symbolic links followed when resolving a path before giving up, like Linux does
+const MAX_FOLLOWED_SYMLINKS: usize = 40;
+
+/// Returns whether the given path goes up above the folder it is relative to, with `..`.
+///
+/// Absolute paths never do, as they are resolved from the installation root.
+fn goes_up(path: &Path) -> bool {
+    let mut depth = 0usize;
+
+    for component in path.components() {
+        match component {
+            Component::Prefix(..) | Component::RootDir => depth = 0,
+            Component::CurDir => (),
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+        }
+    }
+    false
+}
+
+/// Returns why the given entry of a data archive, to be extracted at the given absolute path of the installation
+/// root, can't be safely extracted from a repository with the given trust level, if it can't.
+fn find_unsafety<R: Read>(
+    entry: &Entry<R>,
+    abs_path: &Path,
+    trust: RepositoryTrust,
+) -> Result<Option<UnsafeEntry>, InstallErrorKind> {
+    let header = entry.header();
+    let entry_type = header.entry_type();
+
+    let path = entry.path().map_err(|_| InvalidPackageData)?;
+    if path
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return Ok(Some(UnsafeEntry::PathTraversal));
+    }
+
+    match entry.link_name().map_err(|_| InvalidPackageData)? {
+        // Relative symbolic links are resolved from the folder holding them
+        Some(target) if entry_type.is_symlink() => {
+            if goes_up(&abs_path.parent().unwrap_or(abs_path).join(&target)) {
+                return Ok(Some(UnsafeEntry::EscapingSymlink(target.into_owned())));
+            }
+        }
+        // Hard links are relative to the root of the archive
+        Some(target) if entry_type.is_hard_link() => {
+            if target.has_root() || goes_up(&target) {
+                return Ok(Some(UnsafeEntry::EscapingHardLink(target.into_owned())));
+            }
+        }
+        _ => (),
+    }
+
+    if trust == RepositoryTrust::Untrusted {
+        let mode = header.mode().map_err(|_| InvalidPackageData)?;
+
+        match entry_type {
+            EntryType::Block | EntryType::Char | EntryType::Fifo => {
+                return Ok(Some(UnsafeEntry::SpecialFile))
+            }
+            // The setgid bit of folders only makes their files inherit their group
+            _ if !entry_type.is_dir() && mode & SETID_BITS != 0 => {
+                return Ok(Some(UnsafeEntry::SetuidFile))
+            }
+            _ => (),
+        }
+    }
+    Ok(None)
+}
+
+/// Checks that the given entry of a data archive, to be extracted at the given absolute path of the installation
+/// root, can be safely extracted from a repository with the given trust level.
+pub(crate) fn check_entry<R: Read>(
+    entry: &Entry<R>,
+    abs_path: &Path,
+    trust: RepositoryTrust,
+) -> Result<(), InstallErrorKind> {
+    match find_unsafety(entry, abs_path, trust)? {
+        Some(unsafety) => Err(UnsafePackageEntry(abs_path.to_path_buf(), unsafety)),
+        None => Ok(()),
+    }
+}
+
+/// Pushes the components of the given path on the given stack of components left to resolve, the first one last
+fn push_components(pending: &mut Vec<OsString>, path: &Path) {
+    let components = path
+        .components()
+        .rev()
+        .filter_map(|component| match component {
+            Component::Prefix(..) | Component::RootDir | Component::CurDir => None,
+            Component::ParentDir | Component::Normal(_) => {
+                Some(component.as_os_str().to_os_string())
+            }
+        });
+    pending.extend(components);
+}
+
+/// Resolves the symbolic links of the given path of the installation root the way they are resolved from inside of
+/// it: absolute targets are followed from the root, and `..` never goes above it. Missing files are left as is.
+pub(crate) fn resolve_in_root(root: &Path, path: &Path) -> io::Result<PathBuf> {
+    let mut resolved = PathBuf::new();
+    let mut pending = Vec::new();
+    let mut followed = 0;
+
+    push_components(&mut pending, path.strip_prefix(root).unwrap_or(path));
+    while let Some(component) = pending.pop() {
+        if component == ".." {
+            resolved.pop();
+            continue;
+        }
+
+        let candidate = resolved.join(&component);
+        let host_path = root.join(&candidate);
+        match fs::symlink_metadata(&host_path) {
+            Ok(ref metadata) if metadata.file_type().is_symlink() => {
+                followed += 1;
+                if followed > MAX_FOLLOWED_SYMLINKS {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("too many levels of symbolic links in {}", path.display()),
+                    ));
+                }
+
+                let target = fs::read_link(&host_path)?;
+                if target.has_root() {
+                    resolved = PathBuf::new();
+                }
+                push_components(&mut pending, &target);
+            }
+            _ => resolved = candidate,
+        }
+    }
+    Ok(root.join(resolved))
+}
+
+/// Checks that the folder holding the given path of the installation root, once its symbolic links are resolved
+/// with [`resolve_in_root()`], lies in the given folder, already resolved
+pub(crate) fn ensure_inside(root: &Path, dir: &Path, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        if !resolve_in_root(root, parent)?.starts_with(dir) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} lies outside of {}", path.display(), dir.display()),
+            ));
+        }
+    }
+    Ok(())
+}
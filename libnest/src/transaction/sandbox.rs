@@ -0,0 +1,298 @@
+//! Isolation of the processes executing the `instructions.sh` scripts of packages.
+//!
+//! The sandboxed process is moved to its own mount, UTS, IPC and network namespaces, gets a private `/tmp`,
+//! is chrooted in the installation root, loses the capabilities it doesn't need to install files and is denied
+//! the system calls that could alter the host.
+
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+use std::ptr;
+
+// Opcodes of the BPF instructions used by the seccomp filter, from <linux/filter.h>
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_JMP_JGE_K: u16 = 0x35;
+const BPF_JMP_JSET_K: u16 = 0x45;
+const BPF_RET_K: u16 = 0x06;
+
+// Return values of the seccomp filter, from <linux/seccomp.h>
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+
+// Offsets of the fields of `struct seccomp_data`, from <linux/seccomp.h>. The lower half of the first argument
+// comes first on little-endian architectures.
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+const SECCOMP_DATA_ARG0_LOW: u32 = 16;
+
+// The number of the clone3() system call, the same on every architecture, from <asm/unistd.h>
+const SYS_CLONE3: libc::c_long = 435;
+
+// The flag of clone() creating a user namespace, from <linux/sched.h>
+const CLONE_NEWUSER: u32 = 0x1000_0000;
+
+// Operations of prctl() on the ambient capabilities, from <linux/prctl.h>
+const PR_CAP_AMBIENT: libc::c_int = 47;
+const PR_CAP_AMBIENT_CLEAR_ALL: libc::c_ulong = 4;
+
+// The version of the capability sets exchanged with capget() and capset(), from <linux/capability.h>
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// The highest capability number the sandbox tries to drop, above the last one known by Linux
+const MAX_CAPABILITY: libc::c_ulong = 63;
+
+/// The capabilities kept by the sandboxed process, which a script needs to set the owner and the permissions of the
+/// files it creates: `CAP_CHOWN`, `CAP_DAC_OVERRIDE`, `CAP_FOWNER`, `CAP_FSETID`, `CAP_SETGID`, `CAP_SETUID` and
+/// `CAP_SETFCAP`, from <linux/capability.h>
+const KEPT_CAPABILITIES: u64 = 1 << 0 | 1 << 1 | 1 << 3 | 1 << 4 | 1 << 6 | 1 << 7 | 1 << 31;
+
+/// The header given to capget() and capset(), `struct __user_cap_header_struct` of <linux/capability.h>
+#[repr(C)]
+struct CapabilityHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+/// Half of the capability sets exchanged with capget() and capset(), `struct __user_cap_data_struct` of
+/// <linux/capability.h>
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+struct CapabilityData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// System calls a script has no legitimate reason to perform, and which could alter the host
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_reboot,
+    libc::SYS_kexec_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_ptrace,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_sethostname,
+    libc::SYS_setdomainname,
+    libc::SYS_acct,
+    libc::SYS_quotactl,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_name_to_handle_at,
+    libc::SYS_mknodat,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_mknod,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_iopl,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_ioperm,
+];
+
+#[inline]
+fn bpf_stmt(code: u16, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+#[inline]
+fn bpf_jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+/// Builds the seccomp filter denying [`DENIED_SYSCALLS`] and the creation of user namespaces with `EPERM`, or
+/// `None` if the architecture isn't supported.
+///
+/// Each denied system call is tested by an instruction skipping the next one, which denies it, when it doesn't match.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn seccomp_filter() -> Option<Vec<libc::sock_filter>> {
+    let deny = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+    let mut filter = Vec::with_capacity(2 * DENIED_SYSCALLS.len() + 14);
+
+    // System calls made with another calling convention would bypass the filter
+    filter.push(bpf_stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH));
+    filter.push(bpf_jump(BPF_JMP_JEQ_K, AUDIT_ARCH, 1, 0));
+    filter.push(bpf_stmt(BPF_RET_K, deny));
+    filter.push(bpf_stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR));
+
+    // The x32 ABI shares the x86_64 architecture, but sets the bit 30 of the system call number
+    if cfg!(target_arch = "x86_64") {
+        filter.push(bpf_jump(BPF_JMP_JGE_K, 0x4000_0000, 0, 1));
+        filter.push(bpf_stmt(BPF_RET_K, deny));
+    }
+
+    for syscall in DENIED_SYSCALLS {
+        filter.push(bpf_jump(BPF_JMP_JEQ_K, *syscall as u32, 0, 1));
+        filter.push(bpf_stmt(BPF_RET_K, deny));
+    }
+
+    // The flags of clone3() are out of reach of the filter, in memory: it fails as if it didn't exist, for the C
+    // library to fall back on clone(), whose flags can be checked
+    filter.push(bpf_jump(BPF_JMP_JEQ_K, SYS_CLONE3 as u32, 0, 1));
+    filter.push(bpf_stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32));
+
+    // A user namespace would give back the capabilities dropped, within it
+    filter.push(bpf_jump(BPF_JMP_JEQ_K, libc::SYS_clone as u32, 0, 3));
+    filter.push(bpf_stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG0_LOW));
+    filter.push(bpf_jump(BPF_JMP_JSET_K, CLONE_NEWUSER, 0, 1));
+    filter.push(bpf_stmt(BPF_RET_K, deny));
+
+    filter.push(bpf_stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    Some(filter)
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn seccomp_filter() -> Option<Vec<libc::sock_filter>> {
+    None
+}
+
+#[inline]
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Drops the capabilities of the calling process but [`KEPT_CAPABILITIES`], from its bounding, ambient, effective,
+/// permitted and inheritable sets, so that they can't be regained when executing a program.
+///
+/// This must be done before the seccomp filter is installed, as dropping capabilities from the bounding set
+/// requires `CAP_SETPCAP`. Nothing is allocated, so that it can be called after a fork.
+unsafe fn drop_capabilities() -> io::Result<()> {
+    for capability in 0..=MAX_CAPABILITY {
+        if KEPT_CAPABILITIES & (1 << capability) != 0 {
+            continue;
+        }
+        // Capabilities unknown to the running kernel are reported as invalid
+        if libc::prctl(libc::PR_CAPBSET_DROP, capability, 0, 0, 0) == -1 {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() != Some(libc::EINVAL) {
+                return Err(error);
+            }
+        }
+    }
+
+    // Kernels older than 4.3 have no ambient capabilities
+    if libc::prctl(PR_CAP_AMBIENT, PR_CAP_AMBIENT_CLEAR_ALL, 0, 0, 0) == -1 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::EINVAL) {
+            return Err(error);
+        }
+    }
+
+    let mut header = CapabilityHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let mut data = [CapabilityData::default(); 2];
+    check(libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()) as libc::c_int)?;
+    for (i, half) in data.iter_mut().enumerate() {
+        let kept = (KEPT_CAPABILITIES >> (32 * i)) as u32;
+
+        half.effective &= kept;
+        half.permitted &= kept;
+        half.inheritable &= kept;
+    }
+    check(libc::syscall(libc::SYS_capset, &mut header, data.as_ptr()) as libc::c_int)
+}
+
+fn path_to_cstring(path: &Path) -> Option<CString> {
+    CString::new(path.as_os_str().as_bytes()).ok()
+}
+
+/// Returns a [`Command`] executing the given program of the given root folder in a sandbox, or `None`
+/// if the sandbox isn't supported on this platform.
+///
+/// The sandbox is set up when the command is spawned: spawning it fails if the sandbox can't be set up.
+pub(crate) fn sandboxed_command(root: &Path, program: &Path) -> Option<Command> {
+    let filter = seccomp_filter()?;
+    let root_path = path_to_cstring(root)?;
+    let tmp_path = root.join("tmp");
+    let tmp_path = if tmp_path.is_dir() {
+        Some(path_to_cstring(&tmp_path)?)
+    } else {
+        None
+    };
+    let tmpfs = CString::new("tmpfs").unwrap();
+    let slash = CString::new("/").unwrap();
+
+    let mut cmd = Command::new(program);
+
+    // Everything needed by the closure is allocated beforehand, as allocating after a fork isn't safe
+    let pre_exec = move || -> io::Result<()> {
+        unsafe {
+            check(libc::unshare(
+                libc::CLONE_NEWNS | libc::CLONE_NEWUTS | libc::CLONE_NEWIPC | libc::CLONE_NEWNET,
+            ))?;
+
+            // Prevent the mounts made in the sandbox from propagating to the host
+            check(libc::mount(
+                ptr::null(),
+                slash.as_ptr(),
+                ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                ptr::null(),
+            ))?;
+
+            if let Some(tmp_path) = &tmp_path {
+                check(libc::mount(
+                    tmpfs.as_ptr(),
+                    tmp_path.as_ptr(),
+                    tmpfs.as_ptr(),
+                    libc::MS_NOSUID | libc::MS_NODEV,
+                    ptr::null(),
+                ))?;
+            }
+
+            check(libc::chroot(root_path.as_ptr()))?;
+            check(libc::chdir(slash.as_ptr()))?;
+
+            drop_capabilities()?;
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+
+            let program = libc::sock_fprog {
+                len: filter.len() as libc::c_ushort,
+                filter: filter.as_ptr() as *mut libc::sock_filter,
+            };
+            check(libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER,
+                &program as *const libc::sock_fprog,
+            ))?;
+        }
+        Ok(())
+    };
+
+    unsafe {
+        cmd.pre_exec(pre_exec);
+    }
+    Some(cmd)
+}
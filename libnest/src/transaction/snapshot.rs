@@ -0,0 +1,137 @@
+//! Snapshots of the filesystem taken before the transactions, so that the system can be brought back to its
+//! previous state as a whole.
+//!
+//! With the `btrfs` backend, the root must be a btrfs subvolume: read-only snapshots of it are created in the
+//! configured folder, and restoring one makes a writable copy of it the default subvolume, which is booted into on
+//! the next restart. With the `command` backend, the configured commands take and restore the snapshots.
+
+use std::fs;
+use std::process::Command;
+
+use chrono::Utc;
+use failure::ResultExt;
+use log::info;
+
+use crate::chroot::Chroot;
+use crate::config::{Config, SnapshotBackend};
+use crate::lock_file::LockFileOwnership;
+
+use super::{SnapshotError, SnapshotErrorKind::*};
+
+/// Runs the given program with the given arguments, failing if it doesn't exit successfully
+fn run<S: AsRef<str>>(program: &str, args: &[S]) -> Result<(), SnapshotError> {
+    let output = Command::new(program)
+        .args(args.iter().map(AsRef::as_ref))
+        .output()
+        .context(CannotExecuteCommand(program.to_string()))?;
+
+    if !output.status.success() {
+        return Err(FailureExitStatus(program.to_string(), output).into());
+    }
+    Ok(())
+}
+
+/// Runs the given configured command, where `{name}` and `{root}` are replaced by the given name of the snapshot
+/// and by the installation root
+fn run_command(
+    config: &Config,
+    command: Option<&str>,
+    purpose: &'static str,
+    name: &str,
+) -> Result<(), SnapshotError> {
+    let root = config.paths().root().display().to_string();
+    let args: Vec<_> = command
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|arg| arg.replace("{name}", name).replace("{root}", &root))
+        .collect();
+
+    match args.split_first() {
+        Some((program, args)) => run(program, args),
+        None => Err(MissingCommand(purpose).into()),
+    }
+}
+
+/// Takes a snapshot of the system with the configured backend, returning its name, or [`None`] if snapshots
+/// are disabled.
+///
+/// Snapshots are named after the date they are taken at.
+pub fn take_snapshot(
+    config: &Config,
+    _: &LockFileOwnership,
+) -> Result<Option<String>, SnapshotError> {
+    let backend = match config.snapshot().backend() {
+        Some(backend) => backend,
+        None => return Ok(None),
+    };
+    let name = format!("nest-{}", Utc::now().format("%Y%m%d-%H%M%S"));
+
+    match backend {
+        SnapshotBackend::Btrfs => {
+            let root = config.paths().root();
+            let directory = root.with_content(config.snapshot().directory());
+            let snapshot = directory.join(&name).display().to_string();
+
+            fs::create_dir_all(&directory).context(CannotCreateDirectory)?;
+            run(
+                "btrfs",
+                &[
+                    "subvolume",
+                    "snapshot",
+                    "-r",
+                    root.display().to_string().as_str(),
+                    snapshot.as_str(),
+                ],
+            )?;
+        }
+        SnapshotBackend::Command => {
+            run_command(config, config.snapshot().create_command(), "create", &name)?
+        }
+    }
+    info!("took the snapshot {} of the system", name);
+    Ok(Some(name))
+}
+
+/// Restores the snapshot of the given name with the configured backend.
+///
+/// Returns whether the system is only brought back to the snapshot on the next restart, as it is with the `btrfs`
+/// backend.
+pub fn restore_snapshot(
+    config: &Config,
+    _: &LockFileOwnership,
+    name: &str,
+) -> Result<bool, SnapshotError> {
+    let on_restart = match config.snapshot().backend() {
+        Some(SnapshotBackend::Btrfs) => {
+            let directory = config
+                .paths()
+                .root()
+                .with_content(config.snapshot().directory());
+            let snapshot = directory.join(name).display().to_string();
+            let restored = directory
+                .join(format!("{}-restored", name))
+                .display()
+                .to_string();
+
+            // The snapshot is kept read-only, so that it can be restored again
+            run(
+                "btrfs",
+                &[
+                    "subvolume",
+                    "snapshot",
+                    snapshot.as_str(),
+                    restored.as_str(),
+                ],
+            )?;
+            run("btrfs", &["subvolume", "set-default", restored.as_str()])?;
+            true
+        }
+        Some(SnapshotBackend::Command) => {
+            run_command(config, config.snapshot().restore_command(), "restore", name)?;
+            false
+        }
+        None => return Err(SnapshotsDisabled.into()),
+    };
+    info!("restored the snapshot {} of the system", name);
+    Ok(on_restart)
+}
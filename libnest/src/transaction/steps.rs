@@ -0,0 +1,157 @@
+//! Ordering of the steps needed to apply transactions, separating what has to be done from doing it.
+//!
+//! The packages are downloaded first, then verified, and only then are the transactions applied, in order:
+//! a package that can't be downloaded or verified doesn't leave the system half-modified.
+
+use crate::config::Config;
+use crate::lock_file::LockFileOwnership;
+use crate::package::PackageID;
+
+use super::download::PackageDownload;
+use super::extract::unexplorable_package;
+use super::{
+    InstallError, InstallTransaction, ReinstallTransaction, RemoveTransaction, Transaction,
+    UpgradeTransaction,
+};
+
+/// A step of the application of transactions
+#[derive(Clone, Hash, Debug)]
+pub enum TransactionStep {
+    /// Download a package missing from the download cache
+    Download(PackageDownload),
+
+    /// Verify the checksum and the signature of a downloaded package, before it is installed
+    Verify(PackageID),
+
+    /// Run the instructions of a package, extract its files and register it as installed
+    Install(InstallTransaction),
+
+    /// Remove the old version of a package and install the new one, as a single step
+    Upgrade(UpgradeTransaction),
+
+    /// Remove the files of a package and extract them again
+    Reinstall(ReinstallTransaction),
+
+    /// Run the instructions of a package, remove its files and unregister it
+    Remove(RemoveTransaction),
+}
+
+impl TransactionStep {
+    /// Returns the transaction applied by the step, if it isn't a download or a verification
+    pub fn transaction(&self) -> Option<Transaction<'static, 'static>> {
+        match self {
+            TransactionStep::Install(install) => Some(Transaction::Install(install.clone())),
+            TransactionStep::Upgrade(upgrade) => Some(Transaction::Upgrade(upgrade.clone())),
+            TransactionStep::Reinstall(reinstall) => {
+                Some(Transaction::Reinstall(reinstall.clone()))
+            }
+            TransactionStep::Remove(remove) => Some(Transaction::Remove(remove.clone())),
+            TransactionStep::Download(_) | TransactionStep::Verify(_) => None,
+        }
+    }
+}
+
+/// The ordered steps to apply a list of transactions
+#[derive(Clone, Hash, Debug, Default)]
+pub struct TransactionSteps {
+    steps: Vec<TransactionStep>,
+}
+
+impl TransactionSteps {
+    /// Computes the steps to apply the given transactions, in order.
+    ///
+    /// The packages that aren't downloaded yet are downloaded first, then all the packages to install are
+    /// verified, and the transactions are finally applied in the given order. Pulls are ignored.
+    pub fn from(
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+        transactions: &[Transaction],
+    ) -> Self {
+        let downloaded_packages = config.downloaded_packages_cache(lock_ownership);
+        let available_packages = config.available_packages_cache(lock_ownership);
+        let mut downloads = Vec::new();
+        let mut verifications = Vec::new();
+        let mut applications = Vec::new();
+
+        for transaction in transactions {
+            let (download, step) = match transaction {
+                Transaction::Install(install) => (
+                    install.associated_download(),
+                    TransactionStep::Install(install.clone()),
+                ),
+                Transaction::Upgrade(upgrade) => (
+                    upgrade.associated_download(),
+                    TransactionStep::Upgrade(upgrade.clone()),
+                ),
+                Transaction::Reinstall(reinstall) => (
+                    reinstall.associated_download(),
+                    TransactionStep::Reinstall(reinstall.clone()),
+                ),
+                Transaction::Remove(remove) => {
+                    applications.push(TransactionStep::Remove(remove.clone()));
+                    continue;
+                }
+                Transaction::Pull(_) => continue,
+            };
+
+            // Packages seeded by their repository may be downloaded with their torrent
+            let download = match available_packages.package_torrent(download.target()) {
+                Ok(Some(torrent)) => download.with_torrent(torrent),
+                _ => download,
+            };
+
+            // And those whose repository advertises the checksums of their segments, from several mirrors at once
+            let download = match available_packages.package_segments(download.target()) {
+                Ok(Some(segments)) => download.with_segments(segments),
+                _ => download,
+            };
+
+            verifications.push(TransactionStep::Verify(download.target().clone()));
+            if !downloaded_packages.has_package(download.target()) {
+                downloads.push(TransactionStep::Download(download));
+            }
+            applications.push(step);
+        }
+
+        let mut steps = downloads;
+        steps.extend(verifications);
+        steps.extend(applications);
+        TransactionSteps { steps }
+    }
+
+    /// Returns the steps, in the order they should be performed
+    #[inline]
+    pub fn steps(&self) -> &[TransactionStep] {
+        &self.steps
+    }
+
+    /// Returns the packages to download
+    pub fn downloads(&self) -> impl Iterator<Item = &PackageDownload> {
+        self.steps.iter().filter_map(|step| match step {
+            TransactionStep::Download(download) => Some(download),
+            _ => None,
+        })
+    }
+
+    /// Returns the transactions applied by the steps, in order
+    pub fn transactions(&self) -> Vec<Transaction<'static, 'static>> {
+        self.steps
+            .iter()
+            .filter_map(TransactionStep::transaction)
+            .collect()
+    }
+}
+
+/// Verifies the checksum and the signature of the given downloaded package, as performed by a
+/// [`TransactionStep::Verify`] step
+pub fn verify_package(
+    config: &Config,
+    lock_ownership: &LockFileOwnership,
+    package: &PackageID,
+) -> Result<(), InstallError> {
+    config
+        .downloaded_packages_cache(lock_ownership)
+        .explore_package(package)
+        .map_err(unexplorable_package)?;
+    Ok(())
+}
@@ -2,12 +2,16 @@ use failure::Error;
 
 use crate::config::Config;
 use crate::lock_file::LockFileOwnership;
-use crate::package::{NPFExplorer, PackageID};
+use crate::package::PackageID;
 
+use super::config_files::{find_modified_config_files, ModifiedConfigFiles};
 use super::download::PackageDownload;
-use super::extract::extract_package;
+use super::extract::{extract_package, unexplorable_package};
+use super::journal::Journal;
+use super::plan::TransactionPlan;
+use super::progress::ProgressHandler;
 use super::remove::remove_package;
-use super::{InstallError, InstallErrorKind::*, RemoveError, RemoveErrorKind::*};
+use super::{InstallError, RemoveError, RemoveErrorKind::*};
 
 /// Structure representing an upgrade transaction
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
@@ -32,55 +36,138 @@ impl UpgradeTransaction {
         &self.new
     }
 
-    /// Get the download associated to this transaction
+    /// Returns true if the new target is older than the old one
+    pub fn is_downgrade(&self) -> bool {
+        self.new.version() < self.old.version()
+    }
+
+    /// Get the download associated to this transaction.
+    ///
+    /// The old target is used as a delta base, unless this is a downgrade: repositories only publish deltas
+    /// from older versions to newer ones.
     pub fn associated_download(&self) -> PackageDownload {
-        PackageDownload::from(self.new_target().clone())
+        let download = PackageDownload::from(self.new_target().clone());
+
+        if self.is_downgrade() {
+            download
+        } else {
+            download.with_delta_base(self.old_target().clone())
+        }
     }
 
     fn remove_old_package(
         &self,
         config: &Config,
         lock_ownership: &LockFileOwnership,
+        modified: &ModifiedConfigFiles,
+        journal: &mut Journal,
+        progress: &dyn ProgressHandler,
     ) -> Result<(), RemoveError> {
-        let npf_path = config
-            .paths()
-            .downloaded()
-            .join(self.old_target().repository().as_str())
-            .join(self.old_target().category().as_str())
-            .join(self.old_target().name().as_str())
-            .join(format!(
-                "{}-{}.nest",
-                self.old_target().name(),
-                self.old_target().version()
-            ));
-
-        let npf_explorer = NPFExplorer::from(&npf_path).map_err(|_| InvalidCachedPackageFile)?;
-
-        remove_package(config, lock_ownership, npf_explorer, self.old_target())
+        let downloaded_packages = config.downloaded_packages_cache(lock_ownership);
+        let npf_explorer = downloaded_packages
+            .explore_package(self.old_target())
+            .map_err(|_| InvalidCachedPackageFile)?;
+
+        remove_package(
+            config,
+            lock_ownership,
+            npf_explorer,
+            self.old_target(),
+            modified,
+            journal,
+            progress,
+        )
     }
 
     fn install_new_package(
         &self,
         config: &Config,
         lock_ownership: &LockFileOwnership,
+        modified: &ModifiedConfigFiles,
+        journal: &mut Journal,
+        progress: &dyn ProgressHandler,
     ) -> Result<(), InstallError> {
         let downloaded_packages = config.downloaded_packages_cache(lock_ownership);
         let npf_explorer = downloaded_packages
             .explore_package(self.new_target())
-            .map_err(|_| InvalidPackageFile)?;
+            .map_err(unexplorable_package)?;
+
+        extract_package(
+            config,
+            lock_ownership,
+            npf_explorer,
+            self.new_target(),
+            modified,
+            journal,
+            progress,
+        )
+    }
+
+    /// Finds the configuration files of the old package modified locally, which must be kept.
+    ///
+    /// The configuration files are those of both versions of the package, as a file may become a configuration
+    /// file in the new version.
+    fn find_modified_config_files(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<ModifiedConfigFiles, Error> {
+        let downloaded_packages = config.downloaded_packages_cache(lock_ownership);
+        let old_npf = downloaded_packages
+            .explore_package(self.old_target())
+            .map_err(|_| InvalidCachedPackageFile)?;
+        let new_npf = downloaded_packages
+            .explore_package(self.new_target())
+            .map_err(unexplorable_package)?;
+
+        Ok(find_modified_config_files(
+            config,
+            lock_ownership,
+            self.old_target(),
+            &[old_npf.manifest(), new_npf.manifest()],
+        )?)
+    }
 
-        extract_package(config, lock_ownership, npf_explorer, self.new_target())
+    /// Computes the changes the upgrade would make to the system, without making them
+    pub fn plan(
+        &self,
+        config: &Config,
+        lock_ownership: &LockFileOwnership,
+    ) -> Result<TransactionPlan, Error> {
+        let mut plan = TransactionPlan::default();
+        plan.plan_removal(config, lock_ownership, self.old_target())?;
+        plan.plan_installation(config, lock_ownership, self.new_target())?;
+        Ok(plan)
     }
 
-    /// Perform the upgrade transaction
+    /// Perform the upgrade transaction, reporting its progress to the given [`ProgressHandler`].
+    ///
+    /// Both the removal of the old package and the installation of the new one are journaled
+    /// together: if any of them fails, the old package is restored.
+    ///
+    /// Configuration files modified locally are kept, and their new version, if it differs, is installed
+    /// next to them with the `.nest-new` suffix.
+    /// Nothing is done if the configuration is in dry-run mode.
     pub fn perform(
         &self,
         config: &Config,
         lock_ownership: &LockFileOwnership,
+        progress: &dyn ProgressHandler,
     ) -> Result<(), Error> {
-        self.remove_old_package(config, lock_ownership)?;
-        self.install_new_package(config, lock_ownership)?;
+        if config.dry_run() {
+            return Ok(());
+        }
+
+        let modified = self.find_modified_config_files(config, lock_ownership)?;
+        let mut journal = Journal::create(config.paths().journal()).map_err(JournalError)?;
 
-        Ok(())
+        let res: Result<(), Error> = self
+            .remove_old_package(config, lock_ownership, &modified, &mut journal, progress)
+            .map_err(Error::from)
+            .and_then(|_| {
+                self.install_new_package(config, lock_ownership, &modified, &mut journal, progress)
+                    .map_err(Error::from)
+            });
+        journal.conclude(res, |e| JournalError(e).into())
     }
 }
@@ -0,0 +1,241 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error, ResultExt};
+use libnest::cache::alternatives::{AlternativeLink, Alternatives};
+use libnest::cache::query::PackageQuery;
+use libnest::config::Config;
+use libnest::lock_file::LockFileOwnership;
+use libnest::transaction::Transaction;
+use serde_json::json;
+
+use super::output::{json_output, print_json};
+
+/// Returns the target the given link points to
+fn current_target<'a>(alternatives: &'a Alternatives, link: &Path) -> Option<&'a Path> {
+    alternatives
+        .link(link)
+        .and_then(AlternativeLink::current)
+        .map(|candidate| candidate.target())
+}
+
+/// Finds the given link among the alternatives
+fn find_link<'a>(
+    alternatives: &'a Alternatives,
+    link: &Path,
+) -> Result<&'a AlternativeLink, Error> {
+    alternatives.link(link).ok_or_else(|| {
+        format_err!(
+            "unable to find alternative '{}', no installed package offers a target for it",
+            link.display()
+        )
+    })
+}
+
+/// Saves the alternatives and points the given link to its current target
+fn apply_alternative(
+    config: &Config,
+    alternatives: &Alternatives,
+    link: &Path,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    if config.dry_run() {
+        println!("Dry run, the alternatives weren't changed.");
+        return Ok(());
+    }
+
+    alternatives.save_to_cache(config.paths().alternatives(), lock_file_ownership)?;
+    alternatives
+        .apply(link, config.paths().root())
+        .with_context(|_| format!("unable to update alternative '{}'", link.display()))?;
+
+    if let Some(target) = current_target(alternatives, link) {
+        println!(
+            "{} now points to {}",
+            link.display().to_string().bold(),
+            target.display()
+        );
+    }
+    Ok(())
+}
+
+pub fn alternatives_list(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let _lock_file_ownership = config.acquire_lock_file_ownership(false)?;
+    let alternatives = config.alternatives()?;
+
+    let links: Vec<_> = match matches.value_of("LINK") {
+        Some(link) => {
+            let link = Path::new(link);
+            vec![(link, find_link(&alternatives, link)?)]
+        }
+        None => alternatives.links().collect(),
+    };
+
+    if json_output() {
+        print_json(&json!(links
+            .iter()
+            .map(|(link, alternative)| json!({
+                "link": link,
+                "mode": if alternative.selected().is_some() { "manual" } else { "auto" },
+                "current": alternative.current().map(|candidate| candidate.target()),
+                "candidates": alternative
+                    .candidates()
+                    .iter()
+                    .map(|candidate| json!({
+                        "target": candidate.target(),
+                        "provider": candidate.provider().to_string(),
+                        "priority": candidate.priority(),
+                    }))
+                    .collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>()));
+        return Ok(());
+    }
+
+    for (link, alternative) in links {
+        println!(
+            "{} ({})",
+            link.display().to_string().bold(),
+            if alternative.selected().is_some() {
+                "manual"
+            } else {
+                "auto"
+            }
+        );
+
+        let current = alternative.current().map(|candidate| candidate.target());
+        for candidate in alternative.candidates() {
+            println!(
+                "  {} {} ({}, priority {})",
+                if Some(candidate.target()) == current {
+                    "*".green().bold()
+                } else {
+                    " ".normal()
+                },
+                candidate.target().display(),
+                candidate.provider(),
+                candidate.priority()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn alternatives_set(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let mut alternatives = config.alternatives()?;
+    let link = Path::new(matches.value_of("LINK").unwrap());
+    let target = matches.value_of("TARGET").unwrap();
+
+    // The target is either given by its path, or by the package offering it
+    let target = if target.starts_with('/') {
+        PathBuf::from(target)
+    } else {
+        let query = PackageQuery::parse(target)?;
+        let candidates = find_link(&alternatives, link)?
+            .candidates()
+            .iter()
+            .filter(|candidate| query.matches(candidate.provider()))
+            .collect();
+
+        query
+            .select_one(candidates, |candidate| candidate.provider().clone())?
+            .target()
+            .to_path_buf()
+    };
+
+    alternatives.select(link, &target)?;
+    apply_alternative(config, &alternatives, link, &lock_file_ownership)
+}
+
+pub fn alternatives_auto(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let mut alternatives = config.alternatives()?;
+    let link = Path::new(matches.value_of("LINK").unwrap());
+
+    alternatives.set_auto(link)?;
+    apply_alternative(config, &alternatives, link, &lock_file_ownership)
+}
+
+/// Registers the targets offered by the packages installed by the given transactions, unregisters those of the
+/// packages they removed, and points the links they affect to their new target.
+///
+/// Links whose selected target was removed are set back to automatic, and links no installed package offers a
+/// target for anymore are removed.
+pub fn update_alternatives(
+    config: &Config,
+    transactions: &[Transaction],
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    if config.dry_run() {
+        return Ok(());
+    }
+
+    let installed = config.installed_packages_cache(lock_file_ownership);
+    let original = config.alternatives()?;
+    let mut alternatives = original.clone();
+    let mut links = BTreeSet::new();
+
+    for transaction in transactions {
+        let (removed, added) = match transaction {
+            Transaction::Install(install) => (None, Some(install.target())),
+            Transaction::Remove(remove) => (Some(remove.target()), None),
+            Transaction::Upgrade(upgrade) => {
+                (Some(upgrade.old_target()), Some(upgrade.new_target()))
+            }
+            Transaction::Reinstall(reinstall) => {
+                (Some(reinstall.target()), Some(reinstall.target()))
+            }
+            Transaction::Pull(_) => (None, None),
+        };
+
+        if let Some(id) = removed {
+            links.extend(alternatives.unregister(id));
+        }
+        if let Some(id) = added {
+            let log = installed
+                .package_log(id)
+                .with_context(|_| format!("unable to load the log of '{}'", id))?;
+            links.extend(alternatives.register(id, log.alternatives()));
+        }
+    }
+    if links.is_empty() {
+        return Ok(());
+    }
+
+    for link in &links {
+        if alternatives.repair(link) {
+            println!(
+                "The target selected for {} was removed, it is automatic again.",
+                link.display().to_string().bold()
+            );
+        }
+    }
+    alternatives
+        .save_to_cache(config.paths().alternatives(), lock_file_ownership)
+        .context("unable to save the alternatives")?;
+
+    for link in &links {
+        alternatives
+            .apply(link, config.paths().root())
+            .with_context(|_| format!("unable to update alternative '{}'", link.display()))?;
+
+        let target = current_target(&alternatives, link);
+        if current_target(&original, link) != target {
+            match target {
+                Some(target) => println!(
+                    "{} now points to {}",
+                    link.display().to_string().bold(),
+                    target.display()
+                ),
+                None => println!(
+                    "{} was removed, no installed package offers a target for it anymore",
+                    link.display().to_string().bold()
+                ),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error, ResultExt};
+use libnest::cache::available::{Severity, Vulnerability};
+use libnest::cache::keys::Keystore;
+use libnest::config::Config;
+use libnest::lock_file::LockFileOwnership;
+use libnest::transaction::PullTransaction;
+use serde_json::{json, Value};
+
+use super::operations::download::Download;
+use super::output::{json_output, print_json};
+use super::pull::verify_metadata;
+
+/// Fetches the security advisories of a repository, replacing those fetched previously
+fn fetch_advisories(
+    config: &Config,
+    pull: &mut PullTransaction,
+    keystore: &Keystore,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let repo = *pull.target_repository();
+    let mirrors = config.ranked_mirrors(repo.config());
+    let last_serial = config
+        .available_packages_cache(lock_file_ownership)
+        .repository_index(repo.name())?
+        .serial();
+
+    verify_metadata(config, pull, &mirrors, keystore, last_serial).context(format_err!(
+        "unable to verify the metadata of repository '{}'",
+        repo.name()
+    ))?;
+
+    Download::from("api/advisories", config.network())
+        .with_repository(repo.config())?
+        .perform_with_mirrors(&mut pull.writer(), &mirrors)?;
+    pull.save_advisories_to_cache(config, lock_file_ownership)
+}
+
+fn vulnerability_json(vulnerability: &Vulnerability) -> Value {
+    let advisory = vulnerability.advisory();
+
+    json!({
+        "package": vulnerability.package().to_string(),
+        "advisory": advisory.id(),
+        "severity": advisory.severity().map(|severity| severity.to_string()),
+        "summary": advisory.summary(),
+        "fixed": advisory.fixed().map(ToString::to_string),
+        "url": advisory.url(),
+    })
+}
+
+/// Prints the given vulnerability on a single line
+fn print_vulnerability(vulnerability: &Vulnerability) {
+    let advisory = vulnerability.advisory();
+    let severity = match advisory.severity() {
+        Some(severity @ Severity::Critical) | Some(severity @ Severity::High) => {
+            format!("[{}]", severity).red().bold()
+        }
+        Some(severity @ Severity::Medium) => format!("[{}]", severity).yellow(),
+        Some(severity) => format!("[{}]", severity).normal(),
+        None => "[unknown severity]".normal(),
+    };
+    let fix = match advisory.fixed() {
+        Some(version) => format!("fixed in {}", version.to_string().green()),
+        None => String::from("not fixed yet"),
+    };
+
+    println!(
+        "{} {} {} {}, {}",
+        vulnerability.package(),
+        advisory.id().bold(),
+        severity,
+        advisory.summary(),
+        fix
+    );
+}
+
+pub fn audit(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let offline = matches.is_present("offline");
+
+    // Fetching the advisories modifies the cache of available packages
+    let lock_file_ownership = if offline {
+        config.acquire_shared_lock_file_ownership(true)?
+    } else {
+        config.acquire_lock_file_ownership(true)?
+    };
+
+    let mut repositories = config.repositories();
+    repositories.sort_by(|a, b| a.name().cmp(b.name()));
+
+    // A repository whose advisories can't be fetched is audited with those fetched previously, if any
+    if !offline {
+        let keystore = config.keystore()?;
+
+        for repository in &repositories {
+            let mut pull = PullTransaction::from(*repository);
+            if let Err(e) = fetch_advisories(config, &mut pull, &keystore, &lock_file_ownership) {
+                eprintln!(
+                    "Unable to fetch the security advisories of repository '{}': {}",
+                    repository.name(),
+                    e
+                );
+            }
+        }
+    }
+
+    let cache = config.available_packages_cache(&lock_file_ownership);
+    if !repositories
+        .iter()
+        .any(|repository| cache.has_advisories(repository.name()))
+    {
+        return Err(format_err!(
+            "no security advisories were fetched, the system can't be audited"
+        ));
+    }
+
+    let installed = config
+        .installed_packages_cache(&lock_file_ownership)
+        .packages()?;
+    let vulnerabilities = cache.vulnerabilities(&installed)?;
+
+    if json_output() {
+        print_json(&Value::Array(
+            vulnerabilities.iter().map(vulnerability_json).collect(),
+        ));
+    } else if vulnerabilities.is_empty() {
+        println!("No installed package is affected by a known vulnerability.");
+    } else {
+        vulnerabilities.iter().for_each(print_vulnerability);
+    }
+
+    // Failing lets continuous integration pipelines reject systems with known vulnerabilities
+    let affected: HashSet<_> = vulnerabilities.iter().map(Vulnerability::package).collect();
+    match affected.len() {
+        0 => Ok(()),
+        1 => Err(format_err!(
+            "1 installed package is affected by a known vulnerability"
+        )),
+        n => Err(format_err!(
+            "{} installed packages are affected by known vulnerabilities",
+            n
+        )),
+    }
+}
@@ -0,0 +1,57 @@
+use std::io::Write;
+
+use clap::ArgMatches;
+use failure::Error;
+use libnest::config::Config;
+use libnest::transaction::{RemoveTransaction, Transaction};
+
+use super::output;
+use super::{
+    apply_transactions, check_pending_transactions, confirm_transactions, print_plans,
+    print_transactions,
+};
+
+pub fn autoremove(config: &Config, _: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    check_pending_transactions(config, &lock_file_ownership)?;
+    let mut graph = config.dependency_graph(&lock_file_ownership)?;
+    let original_graph = graph.clone();
+
+    let installed = config
+        .installed_packages_cache(&lock_file_ownership)
+        .packages()?;
+
+    let transactions: Vec<_> = graph
+        .orphan_packages(&installed)
+        .into_iter()
+        .filter(|id| installed.contains(id))
+        .map(|id| Transaction::Remove(RemoveTransaction::from(id)))
+        .collect();
+
+    if transactions.is_empty() {
+        writeln!(output::messages(), "No package is orphaned, quitting.")?;
+        return Ok(());
+    }
+
+    print_transactions(&transactions);
+
+    if config.dry_run() {
+        return print_plans(config, &transactions, &lock_file_ownership);
+    }
+
+    if !confirm_transactions(config, &transactions, &lock_file_ownership)? {
+        return Ok(());
+    }
+
+    // Drop the orphan nodes from the graph, which is saved once their packages are removed
+    graph.solve(config)?;
+
+    apply_transactions(
+        config,
+        &transactions,
+        &original_graph,
+        &graph,
+        None,
+        &lock_file_ownership,
+    )
+}
@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use indicatif::HumanBytes;
+use libnest::cache::downloaded::CachedPackage;
+use libnest::config::{parse_size, Config, DownloadCacheConfig};
+use libnest::lock_file::LockFileOwnership;
+use libnest::package::NPFExplorationErrorKind;
+use log::info;
+
+use super::ask_confirmation;
+use super::key::find_repository;
+
+/// Prints the packages evicted from the download cache, and how much space was freed
+fn print_evictions(evicted: &[CachedPackage], dry_run: bool) {
+    for package in evicted {
+        println!("    {} ({})", package.id(), HumanBytes(package.size()));
+    }
+
+    let freed: u64 = evicted.iter().map(CachedPackage::size).sum();
+    println!(
+        "{} {} package{} from the download cache, freeing {}.",
+        if dry_run { "Would evict" } else { "Evicted" },
+        evicted.len(),
+        if evicted.len() <= 1 { "" } else { "s" },
+        HumanBytes(freed)
+    );
+}
+
+/// Evicts packages from the download cache so that it respects the limits of the configuration, if any.
+///
+/// This is done after each transaction.
+pub fn enforce_download_cache_limits(
+    config: &Config,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    if config.dry_run() || !config.download_cache().is_limited() {
+        return Ok(());
+    }
+
+    let installed = config
+        .installed_packages_cache(lock_file_ownership)
+        .packages()?;
+    let evicted = config
+        .downloaded_packages_cache(lock_file_ownership)
+        .evict(config.download_cache(), &installed)?;
+    if !evicted.is_empty() {
+        let freed: u64 = evicted.iter().map(CachedPackage::size).sum();
+        info!(
+            "evicted {} package(s) from the download cache, freeing {}",
+            evicted.len(),
+            HumanBytes(freed)
+        );
+    }
+    Ok(())
+}
+
+/// Removes the files of the content store that aren't installed anymore.
+///
+/// This is done after each transaction.
+pub fn collect_store_garbage(
+    config: &Config,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    if config.dry_run() {
+        return Ok(());
+    }
+
+    let freed = config
+        .content_store(lock_file_ownership)
+        .collect_garbage()?;
+    if freed.objects() > 0 {
+        info!(
+            "removed {} unused file(s) from the content store, freeing {}",
+            freed.objects(),
+            HumanBytes(freed.size())
+        );
+    }
+    Ok(())
+}
+
+pub fn cache_clean(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let mut limits: DownloadCacheConfig = config.download_cache().clone();
+
+    if let Some(max_size) = matches.value_of("max-size") {
+        *limits.max_size_mut() =
+            Some(parse_size(max_size).ok_or_else(|| format_err!("invalid size '{}'", max_size))?);
+    }
+    if let Some(keep_versions) = matches.value_of("keep-versions") {
+        *limits.keep_versions_mut() = Some(keep_versions.parse()?);
+    }
+
+    if !limits.is_limited() {
+        println!("The download cache has no limit, nothing to clean.");
+        return Ok(());
+    }
+
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let cache = config.downloaded_packages_cache(&lock_file_ownership);
+    let installed = config
+        .installed_packages_cache(&lock_file_ownership)
+        .packages()?;
+    let evicted = if config.dry_run() {
+        cache.select_evictions(&limits, &installed)?
+    } else {
+        cache.evict(&limits, &installed)?
+    };
+
+    print_evictions(&evicted, config.dry_run());
+    Ok(())
+}
+
+pub fn cache_stats(config: &Config) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    let available = config.available_packages_cache(&lock_file_ownership);
+    let downloaded = config
+        .downloaded_packages_cache(&lock_file_ownership)
+        .packages()?;
+
+    // Repositories that are configured, pulled, or that packages were downloaded from
+    let mut repositories: BTreeMap<String, (usize, usize, u64)> = BTreeMap::new();
+    for repository in config.repositories() {
+        repositories.insert(repository.name().to_string(), (0, 0, 0));
+    }
+    for repository in available.repositories()? {
+        let count = available.repository_index(&repository)?.packages().count();
+
+        repositories.entry(repository.to_string()).or_default().0 = count;
+    }
+    for package in &downloaded {
+        let stats = repositories
+            .entry(package.id().repository().to_string())
+            .or_default();
+
+        stats.1 += 1;
+        stats.2 += package.size();
+    }
+
+    for (repository, (available, downloaded, size)) in &repositories {
+        println!("{}", repository.bold());
+        println!("    {:<12} {:>8} packages", "available", available);
+        println!(
+            "    {:<12} {:>8} packages, {}",
+            "downloaded",
+            downloaded,
+            HumanBytes(*size)
+        );
+    }
+
+    let total: u64 = downloaded.iter().map(CachedPackage::size).sum();
+    println!(
+        "{} downloaded package{}, {} in total.",
+        downloaded.len(),
+        if downloaded.len() <= 1 { "" } else { "s" },
+        HumanBytes(total)
+    );
+    if let Some(max_size) = config.download_cache().max_size() {
+        println!("The download cache is limited to {}.", HumanBytes(max_size));
+    }
+
+    let store = config.content_store(&lock_file_ownership).usage()?;
+    println!(
+        "{} installed file{} in the content store, {} in total.",
+        store.objects(),
+        if store.objects() <= 1 { "" } else { "s" },
+        HumanBytes(store.size())
+    );
+    Ok(())
+}
+
+pub fn cache_verify(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let purge = matches.is_present("purge");
+    let lock_file_ownership = if purge {
+        config.acquire_lock_file_ownership(true)?
+    } else {
+        config.acquire_shared_lock_file_ownership(true)?
+    };
+    let cache = config.downloaded_packages_cache(&lock_file_ownership);
+    let packages = cache.packages()?;
+
+    let mut corrupted = Vec::new();
+    let mut unverified = 0;
+    for package in &packages {
+        match cache.verify_package(package.id()) {
+            Ok(()) => (),
+            // Packages that can't be verified aren't corrupted, so they aren't purged
+            Err(ref e) if *e.kind() == NPFExplorationErrorKind::UnknownChecksum => {
+                println!(
+                    "{:>10} {} ({})",
+                    "unverified".yellow().bold(),
+                    package.id(),
+                    e
+                );
+                unverified += 1;
+            }
+            Err(e) => {
+                println!("{:>10} {} ({})", "corrupted".red().bold(), package.id(), e);
+                corrupted.push(package);
+            }
+        }
+    }
+
+    if corrupted.is_empty() {
+        let verified = packages.len() - unverified;
+        println!(
+            "{} downloaded package{} verified, no issue found.",
+            verified,
+            if verified <= 1 { "" } else { "s" }
+        );
+        return Ok(());
+    }
+
+    if purge {
+        if config.dry_run() {
+            println!("Dry run, the corrupted packages weren't removed.");
+        } else {
+            for package in &corrupted {
+                cache.remove_package(package.id())?;
+            }
+            println!(
+                "{} corrupted package{} removed from the download cache.",
+                corrupted.len(),
+                if corrupted.len() <= 1 { "" } else { "s" }
+            );
+        }
+        Ok(())
+    } else {
+        Err(format_err!(
+            "{} corrupted package{} found, use --purge to remove them",
+            corrupted.len(),
+            if corrupted.len() <= 1 { "" } else { "s" }
+        ))
+    }
+}
+
+pub fn cache_clear(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let repository = match matches.value_of("REPOSITORY") {
+        Some(name) => Some(find_repository(config, name)?),
+        None => None,
+    };
+
+    // Both caches are cleared unless only one of them is selected
+    let clear_available = matches.is_present("available") || !matches.is_present("downloaded");
+    let clear_downloaded = matches.is_present("downloaded") || !matches.is_present("available");
+
+    let caches = match (clear_available, clear_downloaded) {
+        (true, true) => "available and downloaded packages",
+        (true, false) => "available packages",
+        _ => "downloaded packages",
+    };
+    let question = match &repository {
+        Some(repository) => format!(
+            "Would you like to clear the {} of {}?",
+            caches,
+            repository.name()
+        ),
+        None => format!(
+            "Would you like to clear the {} of all repositories?",
+            caches
+        ),
+    };
+    if !ask_confirmation(config, &question, false)? {
+        println!("The caches weren't cleared.");
+        return Ok(());
+    }
+    if config.dry_run() {
+        println!("Dry run, the caches weren't cleared.");
+        return Ok(());
+    }
+
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    if clear_available {
+        let cache = config.available_packages_cache(&lock_file_ownership);
+
+        match &repository {
+            Some(repository) => cache.erase_repository(repository)?,
+            None => cache.erase()?,
+        }
+    }
+    if clear_downloaded {
+        let cache = config.downloaded_packages_cache(&lock_file_ownership);
+
+        match &repository {
+            Some(repository) => cache.erase_repository(repository)?,
+            None => cache.erase()?,
+        }
+    }
+    println!("The {} were cleared.", caches);
+    Ok(())
+}
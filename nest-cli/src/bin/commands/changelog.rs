@@ -0,0 +1,87 @@
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use libnest::cache::changelog::PackageChangelog;
+use libnest::cache::query::PackageQuery;
+use libnest::config::Config;
+use libnest::package::ChangelogEntry;
+use serde_json::{json, Value};
+
+use super::output::{json_output, print_json};
+
+fn entry_json(entry: &ChangelogEntry) -> Value {
+    json!({
+        "version": entry.version().to_string(),
+        "date": entry.date().map(|date| date.to_string()),
+        "changes": entry.changes(),
+    })
+}
+
+fn changelog_json(changelog: &PackageChangelog) -> Value {
+    json!({
+        "installed": changelog.installed().map(ToString::to_string),
+        "candidate": changelog.candidate().to_string(),
+        "entries": changelog.entries().iter().map(entry_json).collect::<Vec<_>>(),
+    })
+}
+
+/// Prints the changes between the installed and the candidate versions of a package
+fn print_changelog(changelog: &PackageChangelog) {
+    match changelog.installed() {
+        Some(installed) => println!(
+            "{} {} {}",
+            installed,
+            "->".bold(),
+            changelog.candidate().version().to_string().green()
+        ),
+        None => println!(
+            "{} {}",
+            changelog.candidate(),
+            "(not installed)".bright_black()
+        ),
+    }
+
+    if changelog.is_up_to_date() {
+        println!("    The installed version is up to date.");
+    } else if changelog.entries().is_empty() {
+        println!("    No changelog is available for this version.");
+    }
+
+    for entry in changelog.entries() {
+        match entry.date() {
+            Some(date) => println!("  {} ({})", entry.version().to_string().bold(), date),
+            None => println!("  {}", entry.version().to_string().bold()),
+        }
+        for change in entry.changes() {
+            println!("    - {}", change);
+        }
+    }
+}
+
+pub fn changelog(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+
+    let mut changelogs = Vec::new();
+    for target in matches.values_of("PACKAGE").unwrap() {
+        let query = PackageQuery::parse(target)?;
+        let found = PackageChangelog::query(config, &lock_file_ownership, &query)?;
+        if found.is_empty() {
+            return Err(format_err!("no package found matching '{}'", target));
+        }
+        changelogs.extend(found);
+    }
+
+    if json_output() {
+        print_json(&Value::Array(
+            changelogs.iter().map(changelog_json).collect(),
+        ));
+    } else {
+        for (i, changelog) in changelogs.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            print_changelog(changelog);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeSet;
+
+use clap::{App, ArgMatches, Shell};
+use failure::Error;
+use libnest::cache::listing::{PackageListing, PackageListingSource};
+use libnest::config::Config;
+
+/// The subcommands, and their aliases, whose arguments are completed with the names of the available packages
+const PACKAGE_SUBCOMMANDS: &[&str] = &[
+    "install",
+    "add",
+    "reinstall",
+    "uninstall",
+    "remove",
+    "files",
+    "info",
+    "changelog",
+    "hold",
+    "unhold",
+    "why",
+    "rdepends",
+    "graph",
+];
+
+/// Prints the completion script of the given shell for the given command line interface. On top of the static
+/// completion of the subcommands and options, the arguments of the subcommands taking packages are completed
+/// with the names listed by `nest package-names`.
+pub fn completions(mut app: App, matches: &ArgMatches) {
+    let shell = match matches.value_of("SHELL") {
+        Some("zsh") => Shell::Zsh,
+        Some("fish") => Shell::Fish,
+        _ => Shell::Bash,
+    };
+
+    let mut script = Vec::new();
+    app.gen_completions_to("nest", shell, &mut script);
+    let script = String::from_utf8_lossy(&script);
+    let subcommands = PACKAGE_SUBCOMMANDS.join(" ");
+
+    match shell {
+        Shell::Zsh => print!("{}", zsh_completions(&script)),
+        Shell::Fish => {
+            print!("{}", script);
+            println!(
+                "complete -c nest -n \"__fish_seen_subcommand_from {}\" -f -a \"(nest package-names 2>/dev/null)\"",
+                subcommands
+            );
+        }
+        _ => {
+            print!("{}", script);
+            print!(
+                r#"
+_nest_with_packages() {{
+    _nest "$@"
+
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" word i
+    [[ ${{cur}} == -* ]] && return 0
+
+    for (( i = 1; i < COMP_CWORD; i++ )); do
+        word="${{COMP_WORDS[i]}}"
+        [[ ${{word}} == -* ]] && continue
+        case " {} " in
+            *" ${{word}} "*)
+                COMPREPLY=( $(compgen -W "$(nest package-names 2>/dev/null)" -- "${{cur}}") )
+                ;;
+        esac
+        return 0
+    done
+}}
+
+complete -F _nest_with_packages -o bashdefault -o default nest
+"#,
+                subcommands
+            );
+        }
+    }
+}
+
+/// Completes the package arguments of the zsh completion script generated by clap with the names of the
+/// available packages, instead of files
+fn zsh_completions(script: &str) -> String {
+    let mut completions = String::new();
+
+    for line in script.lines() {
+        if line.contains(":PACKAGE -- ") || line.contains(":TARGET -- ") {
+            completions.push_str(&line.replace(":_files'", ":_nest_packages'"));
+        } else {
+            completions.push_str(line);
+        }
+        completions.push('\n');
+
+        if line.starts_with("#compdef") {
+            completions.push_str(
+                r#"
+_nest_packages() {
+    local -a packages
+    packages=(${(f)"$(nest package-names 2>/dev/null)"})
+    _describe 'package' packages
+}
+"#,
+            );
+        }
+    }
+    completions
+}
+
+/// Lists the names of the available packages, one per line, for the completion scripts
+pub fn package_names(config: &Config) -> Result<(), Error> {
+    // Completion must never hang, so the lock file isn't waited for
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(false)?;
+    let packages = PackageListing::from(
+        config,
+        &lock_file_ownership,
+        PackageListingSource::Available,
+    )
+    .perform()?;
+
+    let names: BTreeSet<_> = packages
+        .iter()
+        .map(|package| format!("{}/{}", package.id().category(), package.id().name()))
+        .collect();
+    for name in names {
+        println!("{}", name);
+    }
+    Ok(())
+}
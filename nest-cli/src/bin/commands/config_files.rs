@@ -0,0 +1,143 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use clap::ArgMatches;
+use colored::*;
+use failure::Error;
+use libnest::config::{Config, ConfigFile};
+use toml::Value;
+
+/// The files the configuration is loaded from, opened to be modified by the commands managing it
+pub struct ConfigFiles {
+    files: Vec<ConfigFile>,
+    fragments_dir: Option<&'static Path>,
+}
+
+impl ConfigFiles {
+    /// Loads the files the configuration is made of.
+    ///
+    /// When a configuration file is given explicitly, it is the only one modified, as fragments aren't merged over it.
+    pub fn load(matches: &ArgMatches) -> Result<Self, Error> {
+        let (paths, fragments_dir) = if matches.occurrences_of("config") > 0 {
+            (
+                vec![PathBuf::from(matches.value_of("config").unwrap())],
+                None,
+            )
+        } else {
+            (Config::files()?, Config::fragments_dir())
+        };
+
+        Ok(ConfigFiles {
+            files: paths
+                .iter()
+                .map(ConfigFile::load)
+                .collect::<Result<_, _>>()?,
+            fragments_dir,
+        })
+    }
+
+    /// Returns the files, in the order they are merged
+    #[inline]
+    pub fn files_mut(&mut self) -> &mut Vec<ConfigFile> {
+        &mut self.files
+    }
+
+    /// Returns the directory holding the fragments merged over the configuration file, or [`None`] if they aren't
+    /// used
+    #[inline]
+    pub fn fragments_dir(&self) -> Option<&'static Path> {
+        self.fragments_dir
+    }
+
+    /// Returns the last file defining the given repository, whose settings override those of the previous ones
+    pub fn defining_repository(&mut self, name: &str) -> Option<&mut ConfigFile> {
+        self.files
+            .iter_mut()
+            .rev()
+            .find(|file| file.defines_repository(name))
+    }
+
+    /// Returns the last file defining the mirrors of the given repository, or else the last one defining it
+    pub fn defining_mirrors(&mut self, name: &str) -> Option<&mut ConfigFile> {
+        if self.files.iter().any(|file| file.defines_mirrors(name)) {
+            self.files
+                .iter_mut()
+                .rev()
+                .find(|file| file.defines_mirrors(name))
+        } else {
+            self.defining_repository(name)
+        }
+    }
+
+    /// Sets the given dotted key to the given value in the last file defining it, or else in the configuration file.
+    ///
+    /// The files are left untouched if the key isn't that of a setting, or if the value doesn't have the expected
+    /// type.
+    pub fn set(&mut self, key: &str, value: Value) -> Result<(), Error> {
+        let index = self
+            .files
+            .iter()
+            .rposition(|file| file.get(key).is_some())
+            .unwrap_or(0);
+
+        let mut files = self.files.clone();
+        files[index].set(key, value);
+        Config::from_files(&files)?.check_key(key)?;
+
+        self.files = files;
+        Ok(())
+    }
+
+    /// Checks that the modified files still make up a valid configuration, then writes them back, or only shows
+    /// how they would change in dry-run mode.
+    ///
+    /// Returns whether the files were written.
+    pub fn save(&self, config: &Config) -> Result<bool, Error> {
+        Config::from_files(&self.files)?;
+
+        for file in self.files.iter().filter(|file| file.is_modified()) {
+            if config.dry_run() {
+                show_diff(file)?;
+                println!(
+                    "Dry run, {} wasn't modified.",
+                    file.path().display().to_string().bold()
+                );
+            } else {
+                file.save()?;
+                println!("{} was updated.", file.path().display().to_string().bold());
+            }
+        }
+        Ok(!config.dry_run())
+    }
+}
+
+/// Prints the differences between the original content of the given file and its modified one.
+///
+/// The differences are shown if `diff` is available, but aren't required.
+fn show_diff(file: &ConfigFile) -> Result<(), Error> {
+    let path = file.path().display().to_string();
+    let original = if file.original().is_some() {
+        file.path()
+    } else {
+        Path::new("/dev/null")
+    };
+
+    if let Ok(mut child) = Command::new("diff")
+        .arg("-u")
+        .arg("--label")
+        .arg(&path)
+        .arg("--label")
+        .arg(&path)
+        .arg(original)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(file.to_toml()?.as_bytes());
+        }
+        let _ = child.wait();
+    }
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use libnest::config::Config;
+use toml::value::{Table, Value};
+
+use super::config_files::ConfigFiles;
+
+/// Parses a value given on the command line as a TOML value, or as a string if it isn't one so that strings don't
+/// have to be quoted
+fn parse_value(value: &str) -> Value {
+    match toml::from_str::<Table>(&format!("value = {}", value)) {
+        Ok(mut table) if table.len() == 1 => table.remove("value").unwrap(),
+        _ => Value::String(value.to_string()),
+    }
+}
+
+/// Returns the key with its dashes replaced by underscores, as the settings are named, if it has any
+fn with_underscores(key: &str) -> Option<String> {
+    if key.contains('-') {
+        Some(key.replace('-', "_"))
+    } else {
+        None
+    }
+}
+
+pub fn config_get(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let key = matches.value_of("KEY").unwrap();
+    let value = config
+        .get(key)
+        .or_else(|| with_underscores(key).and_then(|key| config.get(&key)));
+
+    match value {
+        Some(Value::String(value)) => println!("{}", value),
+        Some(value @ Value::Table(_)) => print!("{}", toml::to_string(&value)?),
+        Some(value) => println!("{}", value),
+        None => {
+            return Err(match config.suggest_key(key) {
+                Some(suggestion) => format_err!(
+                    "`{}` isn't set or isn't a setting, did you mean `{}`?",
+                    key,
+                    suggestion
+                ),
+                None => format_err!("`{}` isn't set or isn't a setting", key),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn config_set(
+    config: &Config,
+    mut files: ConfigFiles,
+    matches: &ArgMatches,
+) -> Result<(), Error> {
+    let mut key = matches.value_of("KEY").unwrap().to_string();
+    let value = parse_value(matches.value_of("VALUE").unwrap());
+
+    if let Err(e) = files.set(&key, value.clone()) {
+        match with_underscores(&key) {
+            Some(normalized) if files.set(&normalized, value.clone()).is_ok() => key = normalized,
+            _ => return Err(e),
+        }
+    }
+
+    if files.save(config)? {
+        println!("{} set to {}.", key.bold(), value);
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::path::Path;
+use std::process::Command;
+
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use libnest::chroot::Chroot;
+use libnest::config::Config;
+use serde_json::json;
+
+use super::ask_confirmation;
+use super::output::{json_output, print_json};
+
+pub fn config_merge(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let root = config.paths().root();
+
+    let mut merges = config
+        .installed_packages_cache(&lock_file_ownership)
+        .pending_merges(root)?;
+
+    if let Some(paths) = matches.values_of("PATH") {
+        let paths: Vec<_> = paths.map(Path::new).collect();
+        for path in &paths {
+            if !merges.iter().any(|merge| merge.path() == *path) {
+                return Err(format_err!(
+                    "'{}' has no new version waiting to be merged",
+                    path.display()
+                ));
+            }
+        }
+        merges.retain(|merge| paths.contains(&merge.path()));
+    }
+
+    if matches.is_present("list") {
+        if json_output() {
+            print_json(&json!(merges
+                .iter()
+                .map(|merge| json!({
+                    "path": merge.path(),
+                    "new_path": merge.new_path(),
+                    "package": merge.package().to_string(),
+                }))
+                .collect::<Vec<_>>()));
+        } else {
+            for merge in &merges {
+                println!("{} ({})", merge.path().display(), merge.package());
+            }
+        }
+        return Ok(());
+    }
+
+    if merges.is_empty() {
+        println!("No configuration file to merge.");
+        return Ok(());
+    }
+
+    for merge in &merges {
+        let path = merge.path().display().to_string();
+
+        let use_new = if matches.is_present("use-new") {
+            true
+        } else if matches.is_present("keep-local") {
+            false
+        } else {
+            println!(
+                "{} has a new version, from {}:",
+                path.bold(),
+                merge.package().to_string().bold()
+            );
+
+            // The differences are shown if `diff` is available, but aren't required to merge the file
+            let _ = Command::new("diff")
+                .arg("-u")
+                .arg(root.with_content(merge.path()))
+                .arg(root.with_content(merge.new_path()))
+                .status();
+
+            if ask_confirmation(
+                config,
+                &format!("Replace {} with its new version?", path),
+                false,
+            )? {
+                true
+            } else if ask_confirmation(
+                config,
+                &format!("Keep {} as is, and discard its new version?", path),
+                false,
+            )? {
+                false
+            } else {
+                println!("{} is left to merge.", path);
+                continue;
+            }
+        };
+
+        if config.dry_run() {
+            println!("Dry run, {} wasn't modified.", path);
+            continue;
+        }
+        if use_new {
+            merge.accept(root)?;
+            println!("{} was replaced with its new version.", path.bold());
+        } else {
+            merge.discard(root)?;
+            println!("The new version of {} was discarded.", path.bold());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use colored::*;
+use failure::Error;
+use libnest::config::{Config, CONFIG_VERSION};
+
+use super::config_files::ConfigFiles;
+
+pub fn config_migrate(config: &Config, mut files: ConfigFiles) -> Result<(), Error> {
+    let mut migrated = false;
+
+    for file in files.files_mut() {
+        let steps = file.migrate()?;
+
+        migrated |= file.is_modified();
+        for step in steps {
+            println!("{}: {}", file.path().display().to_string().bold(), step);
+        }
+    }
+
+    if !migrated {
+        println!(
+            "The configuration already uses the current layout (version {}).",
+            CONFIG_VERSION
+        );
+        return Ok(());
+    }
+
+    if files.save(config)? {
+        println!(
+            "The configuration now uses the current layout (version {}).",
+            CONFIG_VERSION
+        );
+    }
+    Ok(())
+}
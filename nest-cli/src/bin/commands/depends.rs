@@ -0,0 +1,82 @@
+use clap::ArgMatches;
+use colored::*;
+use failure::Error;
+use libnest::cache::depgraph::{DependencyGraph, NodeID, NodeKind};
+use libnest::cache::query::PackageQuery;
+use libnest::config::Config;
+
+/// Finds the node of the installed package matching the given target
+pub(super) fn find_installed_package(
+    graph: &DependencyGraph,
+    target: &str,
+) -> Result<NodeID, Error> {
+    let query = PackageQuery::parse(target)?;
+    let found = graph.find_package_nodes(&query);
+
+    query.select_one(found, |node_id| match graph.nodes()[node_id].kind() {
+        NodeKind::Package { id, .. } => id.clone(),
+        NodeKind::Group { .. } => unreachable!(),
+    })
+}
+
+pub fn why(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    let graph = config.dependency_graph(&lock_file_ownership)?;
+
+    let node_id = find_installed_package(&graph, matches.value_of("PACKAGE").unwrap())?;
+    let target = &graph.nodes()[&node_id];
+    let chains = graph.requirement_chains(node_id);
+
+    if chains.is_empty() {
+        println!(
+            "{} isn't required by any explicitly installed package.",
+            target.to_string().bold()
+        );
+        return Ok(());
+    }
+
+    for chain in chains {
+        if chain.len() == 1 {
+            println!("{} is explicitly installed", target.to_string().bold());
+        } else {
+            let chain: Vec<_> = chain
+                .iter()
+                .map(|node_id| graph.nodes()[node_id].to_string())
+                .collect();
+
+            println!("{}", chain.join(" -> "));
+        }
+    }
+    Ok(())
+}
+
+pub fn rdepends(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    let graph = config.dependency_graph(&lock_file_ownership)?;
+
+    let node_id = find_installed_package(&graph, matches.value_of("PACKAGE").unwrap())?;
+    let target = &graph.nodes()[&node_id];
+    let dependents = graph.reverse_dependencies(node_id, matches.is_present("recursive"));
+
+    let mut packages: Vec<_> = dependents
+        .into_iter()
+        .filter(|dependent| *dependent != graph.root_id())
+        .map(|dependent| {
+            let node = graph.nodes()[&dependent].to_string();
+            if graph.is_explicitly_required(dependent) {
+                format!("{} {}", node, "(explicitly installed)".green())
+            } else {
+                node
+            }
+        })
+        .collect();
+    packages.sort();
+
+    if packages.is_empty() {
+        println!("No package depends on {}.", target.to_string().bold());
+    }
+    for package in packages {
+        println!("{}", package);
+    }
+    Ok(())
+}
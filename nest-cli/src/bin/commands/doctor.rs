@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+
+use colored::*;
+use failure::{format_err, Error};
+use libnest::cache::depgraph::NodeKind;
+use libnest::config::{Config, ConfigIssueKind};
+use libnest::lock_file::LockFileOwnership;
+use libnest::transaction::{Journal, PendingTransactions};
+
+/// The problems found by `nest doctor`
+#[derive(Default)]
+struct Diagnosis {
+    issues: usize,
+}
+
+impl Diagnosis {
+    /// Reports a problem that must be fixed
+    fn issue(&mut self, kind: &str, description: String) {
+        self.issues += 1;
+        println!(
+            "{} {}",
+            format!("{:>20.20}", kind).red().bold(),
+            description
+        );
+    }
+
+    /// Reports something that isn't a problem by itself, but may explain one
+    fn warning(&self, kind: &str, description: String) {
+        println!(
+            "{} {}",
+            format!("{:>20.20}", kind).yellow().bold(),
+            description
+        );
+    }
+}
+
+/// Checks the configuration
+fn check_config(config: &Config, diagnosis: &mut Diagnosis) {
+    for issue in config.validate() {
+        match issue.kind() {
+            // Keys are asked for when pulling, so repositories without any aren't broken
+            ConfigIssueKind::MissingKeys => {
+                diagnosis.warning(&issue.kind().to_string(), issue.to_string())
+            }
+            kind => diagnosis.issue(&kind.to_string(), issue.to_string()),
+        }
+    }
+}
+
+/// Checks that no process left the lock file behind
+fn check_lock_file(config: &Config, diagnosis: &Diagnosis) {
+    let path = config.paths().lock_file();
+
+    if let Some(pid) = LockFileOwnership::holder(path) {
+        diagnosis.warning(
+            "lock file held",
+            format!("{}: held by process {}", path.display(), pid),
+        );
+    } else if let Some(pid) = LockFileOwnership::stale_holder(path) {
+        diagnosis.warning(
+            "stale lock file",
+            format!(
+                "{}: process {} exited without releasing it, it may have been interrupted",
+                path.display(),
+                pid
+            ),
+        );
+    }
+}
+
+/// Checks that no transaction was interrupted, and that the cache of installed packages matches the dependency graph
+fn check_database(
+    config: &Config,
+    lock_file_ownership: &LockFileOwnership,
+    diagnosis: &mut Diagnosis,
+) -> Result<(), Error> {
+    if PendingTransactions::load(config, lock_file_ownership)?.is_some() {
+        diagnosis.issue(
+            "pending transactions",
+            "interrupted transactions are pending, run `nest resume` or `nest resume --rollback`"
+                .to_string(),
+        );
+    }
+    if Journal::load(config.paths().journal())?.is_some() {
+        diagnosis.issue(
+            "interrupted",
+            "a transaction was interrupted, it is rolled back by the next one".to_string(),
+        );
+    }
+
+    let installed_packages = config.installed_packages_cache(lock_file_ownership);
+    let installed = installed_packages.packages()?;
+    for package in &installed {
+        if let Err(e) = installed_packages.package_log(package) {
+            diagnosis.issue(
+                "corrupted log",
+                format!(
+                    "{}: the log of its installed files can't be read ({})",
+                    package, e
+                ),
+            );
+        }
+    }
+
+    let graph = match config.dependency_graph(lock_file_ownership) {
+        Ok(graph) => graph,
+        Err(e) => {
+            diagnosis.issue(
+                "corrupted graph",
+                format!("{}: {}", config.paths().depgraph().display(), e),
+            );
+            return Ok(());
+        }
+    };
+
+    let mut in_graph = HashSet::new();
+    for node in graph.nodes().values() {
+        if let NodeKind::Package { id, .. } = node.kind() {
+            in_graph.insert(id.clone());
+        }
+    }
+
+    let installed_set: HashSet<_> = installed.iter().cloned().collect();
+    let mut missing: Vec<_> = in_graph.difference(&installed_set).collect();
+    missing.sort();
+    for package in missing {
+        diagnosis.issue(
+            "not installed",
+            format!("{}: in the dependency graph, but not installed", package),
+        );
+    }
+    for package in installed.iter().filter(|id| !in_graph.contains(id)) {
+        diagnosis.warning(
+            "not in graph",
+            format!(
+                "{}: installed, but not in the dependency graph, it is removed by `nest autoremove`",
+                package
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// Checks the downloaded packages against the checksums advertised by their repository
+fn check_caches(
+    config: &Config,
+    lock_file_ownership: &LockFileOwnership,
+    diagnosis: &mut Diagnosis,
+) -> Result<(), Error> {
+    let cache = config.downloaded_packages_cache(lock_file_ownership);
+
+    for package in cache.packages()? {
+        if let Err(e) = cache.verify_package(package.id()) {
+            diagnosis.issue(
+                "corrupted package",
+                format!(
+                    "{}: {}, run `nest cache verify --purge` to remove it",
+                    package.id(),
+                    e
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn doctor(config: &Config) -> Result<(), Error> {
+    let mut diagnosis = Diagnosis::default();
+
+    check_config(config, &mut diagnosis);
+    check_lock_file(config, &diagnosis);
+
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    check_database(config, &lock_file_ownership, &mut diagnosis)?;
+    check_caches(config, &lock_file_ownership, &mut diagnosis)?;
+
+    if diagnosis.issues == 0 {
+        println!("No issue found.");
+        Ok(())
+    } else {
+        Err(format_err!(
+            "{} issue{} found",
+            diagnosis.issues,
+            if diagnosis.issues <= 1 { "" } else { "s" }
+        ))
+    }
+}
@@ -0,0 +1,235 @@
+//! Categorization of the errors of the commands, so that scripts can tell why a command failed from its exit code.
+//!
+//! The libraries report errors as chains of failures, from the most general context to the root cause. The
+//! category of an error is the one of the deepest failure of its chain that can be categorized, and each
+//! category has its own exit code.
+
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use failure::{Context, Error, Fail};
+use libnest::cache::{
+    AlternativesError, AlternativesErrorKind, CacheError, CacheErrorKind, DependencyGraphError,
+    DependencyGraphErrorKind, PackageQueryError, PackageQueryErrorKind,
+};
+use libnest::lock_file::{LockFileError, LockFileErrorKind};
+use libnest::package::{NPFExplorationError, NPFExplorationErrorKind};
+use libnest::repository::{MetadataError, MetadataErrorKind};
+use libnest::solver::{SolverError, SolverErrorKind};
+use libnest::transaction::{InstallError, InstallErrorKind, RemoveError, RemoveErrorKind};
+
+use super::spec::{
+    EXIT_FAILURE, EXIT_IO, EXIT_LOCK, EXIT_NETWORK, EXIT_RESOLUTION, EXIT_VERIFICATION,
+};
+
+/// An error of a command, flattened into a single message and sorted into a category.
+///
+/// Commands can also fail with a [`NestError`] directly, to categorize a failure that isn't described by an error
+/// type of the libraries.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum NestError {
+    /// The mirrors of a repository couldn't be reached, or a download failed
+    Network(String),
+
+    /// No set of packages fulfills the requirements
+    Resolution(String),
+
+    /// The signature or the checksum of a package or of the metadata of a repository couldn't be verified
+    Verification(String),
+
+    /// The lock file couldn't be acquired
+    Lock(String),
+
+    /// A file couldn't be read or written
+    Io(String),
+
+    /// Any other failure, like invalid arguments
+    Other(String),
+}
+
+/// Returns the kind of the given failure if it is either the given error type or a context of the given kind
+macro_rules! kind_of {
+    ($fail:expr, $Error:ident, $ErrorKind:ident) => {
+        $fail
+            .downcast_ref::<$Error>()
+            .map($Error::kind)
+            .or_else(|| {
+                $fail
+                    .downcast_ref::<Context<$ErrorKind>>()
+                    .map(Context::get_context)
+            })
+    };
+}
+
+/// Returns the category of the given failure, as a [`NestError`] without message, if it can be categorized
+fn categorize(fail: &dyn Fail) -> Option<NestError> {
+    let category = |constructor: fn(String) -> NestError| Some(constructor(String::new()));
+
+    if let Some(error) = fail.downcast_ref::<NestError>() {
+        return Some(error.with_message(String::new()));
+    }
+    if fail.downcast_ref::<curl::Error>().is_some() {
+        return category(NestError::Network);
+    }
+    if fail.downcast_ref::<io::Error>().is_some() {
+        return category(NestError::Io);
+    }
+    if kind_of!(fail, LockFileError, LockFileErrorKind).is_some() {
+        return category(NestError::Lock);
+    }
+    if kind_of!(fail, MetadataError, MetadataErrorKind).is_some() {
+        return category(NestError::Verification);
+    }
+
+    match kind_of!(fail, NPFExplorationError, NPFExplorationErrorKind) {
+        Some(NPFExplorationErrorKind::ChecksumMismatch)
+        | Some(NPFExplorationErrorKind::UnknownChecksum)
+        | Some(NPFExplorationErrorKind::MissingSignature)
+        | Some(NPFExplorationErrorKind::InvalidSignature) => {
+            return category(NestError::Verification)
+        }
+        _ => (),
+    }
+    match kind_of!(fail, InstallError, InstallErrorKind) {
+        Some(InstallErrorKind::UnverifiedPackage(_)) => return category(NestError::Verification),
+        Some(InstallErrorKind::ExtractError(_))
+        | Some(InstallErrorKind::LogCreationError(_))
+        | Some(InstallErrorKind::JournalError(_)) => return category(NestError::Io),
+        _ => (),
+    }
+    match kind_of!(fail, RemoveError, RemoveErrorKind) {
+        Some(RemoveErrorKind::LogFileLoadError(_))
+        | Some(RemoveErrorKind::FileRemoveError(_))
+        | Some(RemoveErrorKind::LogFileRemoveError)
+        | Some(RemoveErrorKind::JournalError(_)) => return category(NestError::Io),
+        _ => (),
+    }
+    match kind_of!(fail, CacheError, CacheErrorKind) {
+        Some(CacheErrorKind::CacheLoadError)
+        | Some(CacheErrorKind::CacheWriteError)
+        | Some(CacheErrorKind::CacheClearError) => return category(NestError::Io),
+        _ => (),
+    }
+    if let Some(AlternativesErrorKind::LinkUpdateError(_)) =
+        kind_of!(fail, AlternativesError, AlternativesErrorKind)
+    {
+        return category(NestError::Io);
+    }
+    match kind_of!(fail, SolverError, SolverErrorKind) {
+        Some(SolverErrorKind::Unsatisfiable(_)) | Some(SolverErrorKind::SearchLimitReached) => {
+            return category(NestError::Resolution)
+        }
+        _ => (),
+    }
+    match kind_of!(fail, DependencyGraphError, DependencyGraphErrorKind) {
+        Some(DependencyGraphErrorKind::RequirementSolvingError)
+        | Some(DependencyGraphErrorKind::ConflictingPackages)
+        | Some(DependencyGraphErrorKind::AmbiguousProvider(..)) => {
+            return category(NestError::Resolution)
+        }
+        _ => (),
+    }
+    match kind_of!(fail, PackageQueryError, PackageQueryErrorKind) {
+        Some(PackageQueryErrorKind::NoMatchingPackage(_))
+        | Some(PackageQueryErrorKind::AmbiguousPackage(..)) => {
+            return category(NestError::Resolution)
+        }
+        _ => (),
+    }
+    None
+}
+
+/// Returns the message of the given failure, completed with the packages a query could designate when it is
+/// ambiguous
+fn describe(fail: &dyn Fail) -> String {
+    match kind_of!(fail, PackageQueryError, PackageQueryErrorKind) {
+        Some(PackageQueryErrorKind::AmbiguousPackage(_, candidates)) => format!(
+            "{}, it could designate any of {}",
+            fail,
+            candidates
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        _ => fail.to_string(),
+    }
+}
+
+impl NestError {
+    /// Returns the message of the error
+    #[inline]
+    pub fn message(&self) -> &str {
+        match self {
+            NestError::Network(message)
+            | NestError::Resolution(message)
+            | NestError::Verification(message)
+            | NestError::Lock(message)
+            | NestError::Io(message)
+            | NestError::Other(message) => message,
+        }
+    }
+
+    /// Returns an error of the same category with the given message
+    pub fn with_message(&self, message: String) -> Self {
+        match self {
+            NestError::Network(_) => NestError::Network(message),
+            NestError::Resolution(_) => NestError::Resolution(message),
+            NestError::Verification(_) => NestError::Verification(message),
+            NestError::Lock(_) => NestError::Lock(message),
+            NestError::Io(_) => NestError::Io(message),
+            NestError::Other(_) => NestError::Other(message),
+        }
+    }
+
+    /// Returns the name of the category of the error, as printed in the JSON output
+    pub fn category(&self) -> &'static str {
+        match self {
+            NestError::Network(_) => "network",
+            NestError::Resolution(_) => "resolution",
+            NestError::Verification(_) => "verification",
+            NestError::Lock(_) => "lock",
+            NestError::Io(_) => "io",
+            NestError::Other(_) => "other",
+        }
+    }
+
+    /// Returns the exit code of a command failing with this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            NestError::Network(_) => EXIT_NETWORK,
+            NestError::Resolution(_) => EXIT_RESOLUTION,
+            NestError::Verification(_) => EXIT_VERIFICATION,
+            NestError::Lock(_) => EXIT_LOCK,
+            NestError::Io(_) => EXIT_IO,
+            NestError::Other(_) => EXIT_FAILURE,
+        }
+    }
+}
+
+impl From<&Error> for NestError {
+    /// Flattens the chain of the given error into a single message, and categorizes it after its deepest failure
+    /// that can be categorized
+    fn from(error: &Error) -> Self {
+        let message = error
+            .iter_chain()
+            .map(describe)
+            .collect::<Vec<_>>()
+            .join(": ");
+
+        error
+            .iter_chain()
+            .filter_map(categorize)
+            .last()
+            .unwrap_or_else(|| NestError::Other(String::new()))
+            .with_message(message)
+    }
+}
+
+impl Display for NestError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl Fail for NestError {}
@@ -0,0 +1,61 @@
+use clap::ArgMatches;
+use failure::{format_err, Error};
+use libnest::cache::files::PackageFiles;
+use libnest::cache::query::PackageQuery;
+use libnest::config::Config;
+use libnest::transaction::PackageDownload;
+use serde_json::json;
+
+use super::operations::download::download_packages;
+use super::output::{json_output, print_json};
+
+pub fn files(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let download = matches.is_present("download");
+
+    // Downloading the package file modifies the cache of downloaded packages
+    let lock_file_ownership = if download {
+        config.acquire_lock_file_ownership(true)?
+    } else {
+        config.acquire_shared_lock_file_ownership(true)?
+    };
+
+    let target = matches.value_of("PACKAGE").unwrap();
+    let id = PackageQuery::parse(target)?
+        .resolve_one(config, &lock_file_ownership)?
+        .id()
+        .clone();
+
+    let mut package_files = PackageFiles::query(config, &lock_file_ownership, &id)?;
+    if package_files.is_none() && download {
+        download_packages(config, std::iter::once(PackageDownload::from(id.clone())))?;
+        package_files = PackageFiles::query(config, &lock_file_ownership, &id)?;
+    }
+    let package_files = package_files.ok_or_else(|| {
+        format_err!(
+            "the files of '{}' are unknown until it is downloaded, use --download to download it",
+            id
+        )
+    })?;
+
+    if json_output() {
+        print_json(&json!({
+            "package": id.to_string(),
+            "files": package_files
+                .files()
+                .iter()
+                .map(|file| json!({ "path": file.path(), "type": file.file_type() }))
+                .collect::<Vec<_>>(),
+        }));
+        return Ok(());
+    }
+
+    for file in package_files.files() {
+        // Directories are printed with a trailing slash, like `ls -p` does
+        if file.file_type().is_dir() && file.path().parent().is_some() {
+            println!("{}/", file.path().display());
+        } else {
+            println!("{}", file.path().display());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::str::FromStr;
+
+use clap::ArgMatches;
+use failure::{format_err, Error};
+use libnest::cache::depgraph::GroupName;
+use libnest::config::Config;
+
+use super::depends::find_installed_package;
+
+pub fn graph(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    let graph = config.dependency_graph(&lock_file_ownership)?;
+
+    let target = matches.value_of("TARGET").unwrap_or("@root");
+    let node_id = if target.starts_with('@') {
+        let group = GroupName::from_str(target)?;
+
+        *graph
+            .node_names()
+            .get(&group.into())
+            .ok_or_else(|| format_err!("unable to find the group '{}'", target))?
+    } else {
+        find_installed_package(&graph, target)?
+    };
+
+    let max_depth = match matches.value_of("depth") {
+        Some(depth) => Some(depth.parse()?),
+        None => None,
+    };
+    let export = graph.export(config, node_id, max_depth, matches.is_present("optional"))?;
+
+    match matches.value_of("format") {
+        Some("json") => println!("{}", export.to_json()),
+        _ => print!("{}", export.to_dot()),
+    }
+    Ok(())
+}
@@ -61,7 +61,7 @@ pub fn group_remove(config: &Config, matches: &ArgMatches) -> Result<(), Error>
 }
 
 pub fn group_list(config: &Config) -> Result<(), Error> {
-    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
 
     let graph = config.scratch_dependency_graph(&lock_file_ownership)?;
 
@@ -0,0 +1,20 @@
+use clap::ArgMatches;
+
+use super::spec::CommandSpec;
+
+/// Prints the help of the given command or of the given subcommands, or its manual page
+pub fn help(spec: &CommandSpec, matches: &ArgMatches) {
+    if matches.is_present("man") {
+        print!("{}", spec.man_page());
+        return;
+    }
+
+    let mut args = vec![spec.name];
+    if let Some(subcommands) = matches.values_of("SUBCOMMAND") {
+        args.extend(subcommands);
+    }
+    args.push("--help");
+
+    // Clap prints the help of the last subcommand and exits, or fails if one of them doesn't exist
+    spec.app().get_matches_from(args);
+}
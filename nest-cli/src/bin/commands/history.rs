@@ -0,0 +1,52 @@
+use colored::*;
+use failure::Error;
+use libnest::cache::history::HistoryAction;
+use libnest::config::Config;
+
+pub fn history(config: &Config) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    let entries = config.history(&lock_file_ownership).entries()?;
+
+    if entries.is_empty() {
+        println!("No transaction has been performed yet.");
+        return Ok(());
+    }
+
+    for entry in entries {
+        let title = format!(
+            "#{} {}",
+            entry.id(),
+            entry.date().format("%Y-%m-%d %H:%M:%S UTC")
+        );
+        match entry.undoes() {
+            Some(id) => println!("{} (undo of #{})", title.bold(), id),
+            None => println!("{}", title.bold()),
+        }
+        if let Some(snapshot) = entry.snapshot() {
+            println!("    {:>9.9} {}", "snapshot".cyan(), snapshot);
+        }
+
+        for action in entry.actions() {
+            match action {
+                HistoryAction::Install(id) => println!("    {:>9.9} {}", "install".green(), id),
+                HistoryAction::Remove(id) => println!("    {:>9.9} {}", "remove".red(), id),
+                HistoryAction::Upgrade { from, to } if to.version() < from.version() => println!(
+                    "    {:>9.9} {} -> {}",
+                    "downgrade".magenta(),
+                    from,
+                    to.version()
+                ),
+                HistoryAction::Upgrade { from, to } => println!(
+                    "    {:>9.9} {} -> {}",
+                    "upgrade".yellow(),
+                    from,
+                    to.version()
+                ),
+                HistoryAction::Reinstall(id) => {
+                    println!("    {:>9.9} {}", "reinstall".blue(), id)
+                }
+            }
+        }
+    }
+    Ok(())
+}
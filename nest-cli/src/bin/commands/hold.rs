@@ -0,0 +1,89 @@
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use libnest::cache::depgraph::NodeKind;
+use libnest::cache::query::PackageQuery;
+use libnest::config::Config;
+use libnest::package::PackageShortName;
+
+use super::depends::find_installed_package;
+
+pub fn hold(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let graph = config.dependency_graph(&lock_file_ownership)?;
+    let mut held = config.held_packages()?;
+
+    let targets = match matches.values_of_lossy("PACKAGE") {
+        Some(targets) => targets,
+        None => {
+            for package in held.packages() {
+                println!("{}", package);
+            }
+            return Ok(());
+        }
+    };
+
+    let mut changed = false;
+    for target in &targets {
+        let node_id = find_installed_package(&graph, target)?;
+
+        if let NodeKind::Package { id, .. } = graph.nodes()[&node_id].kind() {
+            let name = PackageShortName::from(id.category().clone(), id.name().clone());
+
+            if held.hold(name.clone()) {
+                println!("{} is now held", id.to_string().bold());
+                changed = true;
+            } else {
+                println!("{} is already held", name.to_string().bold());
+            }
+        }
+    }
+
+    if changed {
+        if config.dry_run() {
+            println!("Dry run, the held packages weren't saved.");
+        } else {
+            held.save_to_cache(config.paths().held(), &lock_file_ownership)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn unhold(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let mut held = config.held_packages()?;
+
+    for target in &matches.values_of_lossy("PACKAGE").unwrap() {
+        let query = PackageQuery::parse(target)?;
+        let found: Vec<_> = held
+            .packages()
+            .filter(|package| query.matches_short_name(package))
+            .cloned()
+            .collect();
+
+        if found.is_empty() {
+            return Err(format_err!(
+                "unable to find a held package matching '{}'",
+                target
+            ));
+        } else if found.len() > 1 && !query.is_pattern() {
+            for package in found {
+                println!("{}", package);
+            }
+            return Err(format_err!("unable to select a best match"));
+        }
+
+        // Patterns release all the held packages they match
+        for package in found {
+            held.unhold(&package);
+            println!("{} is no longer held", package.to_string().bold());
+        }
+    }
+
+    if config.dry_run() {
+        println!("Dry run, the held packages weren't saved.");
+    } else {
+        held.save_to_cache(config.paths().held(), &lock_file_ownership)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use indicatif::HumanBytes;
+use libnest::cache::depgraph::NodeKind;
+use libnest::cache::info::PackageInfo;
+use libnest::cache::query::PackageQuery;
+use libnest::config::Config;
+use serde_json::{json, Value};
+
+use super::output::{json_output, print_json};
+
+/// Returns the given items as a sorted, comma-separated list, or "none" if there isn't any
+fn format_list<T: ToString>(items: impl Iterator<Item = T>) -> String {
+    let mut items: Vec<_> = items.map(|item| item.to_string()).collect();
+    items.sort();
+
+    if items.is_empty() {
+        String::from("none")
+    } else {
+        items.join(", ")
+    }
+}
+
+fn required_by_name(kind: &NodeKind) -> String {
+    match kind {
+        NodeKind::Package { id, .. } => id.to_string(),
+        NodeKind::Group { name } => name.as_str().to_string(),
+    }
+}
+
+fn info_json(info: &PackageInfo) -> Value {
+    let manifest = info.manifest();
+    let metadata = manifest.metadata();
+
+    let mut dependencies: Vec<_> = manifest
+        .dependencies()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    dependencies.sort();
+
+    json!({
+        "repository": info.id().repository().to_string(),
+        "category": info.id().category().to_string(),
+        "name": info.id().name().to_string(),
+        "version": info.id().version().to_string(),
+        "description": metadata.description(),
+        "architecture": manifest.architecture().to_string(),
+        "installed": info.installed(),
+        "explicitly_installed": info.explicitly_installed(),
+        "dependencies": dependencies,
+        "required_by": info.required_by().iter().map(required_by_name).collect::<Vec<_>>(),
+        "installed_size": info.installed_size(),
+        "license": metadata.license().as_ref().map(ToString::to_string),
+        "licenses": metadata.licenses().iter().map(ToString::to_string).collect::<Vec<_>>(),
+        "maintainer": metadata.maintainer(),
+        "upstream_url": metadata.upstream_url().as_ref().map(|url| url.as_str()),
+    })
+}
+
+pub fn info(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    let target = matches.value_of("PACKAGE").unwrap();
+    let query = PackageQuery::parse(target)?;
+
+    let infos = PackageInfo::query(config, &lock_file_ownership, &query)?;
+    if infos.is_empty() {
+        return Err(format_err!("no package found matching '{}'", target));
+    }
+
+    if json_output() {
+        print_json(&Value::Array(infos.iter().map(info_json).collect()));
+        return Ok(());
+    }
+
+    for (i, info) in infos.iter().enumerate() {
+        let manifest = info.manifest();
+        let metadata = manifest.metadata();
+        let status = match (info.installed(), info.explicitly_installed()) {
+            (true, true) => " [explicitly installed]".cyan(),
+            (true, false) => " [installed]".cyan(),
+            _ => "".normal(),
+        };
+
+        if i > 0 {
+            println!();
+        }
+        println!(
+            "{}::{}/{} {}{}",
+            info.id().repository(),
+            info.id().category(),
+            info.id().name().as_str().bold(),
+            info.id().version().to_string().green(),
+            status
+        );
+        println!("    {:<16}{}", "Description", metadata.description());
+        println!("    {:<16}{}", "Architecture", manifest.architecture());
+        println!(
+            "    {:<16}{}",
+            "Dependencies",
+            format_list(manifest.dependencies().iter())
+        );
+        if info.installed() {
+            println!(
+                "    {:<16}{}",
+                "Required by",
+                format_list(info.required_by().iter().map(required_by_name))
+            );
+        }
+        println!(
+            "    {:<16}{}",
+            "Installed size",
+            info.installed_size().map_or_else(
+                || String::from(
+                    "unknown, the package isn't downloaded and its size isn't advertised"
+                ),
+                |size| HumanBytes(size).to_string()
+            )
+        );
+        match metadata.license() {
+            Some(license) => println!("    {:<16}{}", "License", license),
+            None => println!(
+                "    {:<16}{}",
+                "Licenses",
+                format_list(metadata.licenses().iter())
+            ),
+        }
+        println!("    {:<16}{}", "Maintainer", metadata.maintainer());
+        println!(
+            "    {:<16}{}",
+            "Upstream URL",
+            metadata
+                .upstream_url()
+                .as_ref()
+                .map_or("none", |url| url.as_str())
+        );
+    }
+    Ok(())
+}
@@ -1,47 +1,177 @@
+use std::io::Write;
+use std::str::FromStr;
+
 use clap::ArgMatches;
-use failure::{format_err, Error};
-use libnest::cache::available::AvailablePackagesCacheQueryStrategy;
-use libnest::cache::depgraph::{DependencyGraphDiff, RequirementKind, RequirementManagementMethod};
+use failure::{format_err, Error, ResultExt};
+use libnest::cache::available::{AvailablePackagesCacheQueryStrategy, QueryResult};
+use libnest::cache::depgraph::{
+    DependencyGraph, DependencyGraphDiff, GroupName, NodeKind, RequirementKind,
+    RequirementManagementMethod,
+};
+use libnest::cache::query::PackageQuery;
 use libnest::config::Config;
-use libnest::package::{HardPackageRequirement, SoftPackageRequirement};
+use libnest::lock_file::LockFileOwnership;
+use libnest::package::{
+    HardPackageRequirement, PackageFullName, PackageRequirement, PackageShortName,
+    SoftPackageRequirement, TrustedKey,
+};
 use libnest::transaction::Transaction;
 
-use super::operations::download::download_packages;
-use super::{ask_confirmation, print_transactions, process_transactions};
+use super::output;
+use super::{
+    apply_transactions, check_pending_transactions, choose_provider, confirm_downgrades,
+    confirm_transactions, index_local_repository, print_plans, print_transactions,
+    save_dependency_graph, solve_interactively,
+};
+
+/// Returns the keys trusted by the configured repositories, that local NPFs must be signed with
+fn trusted_keys(config: &Config) -> Result<Vec<TrustedKey>, Error> {
+    let keystore = config.keystore()?;
+
+    Ok(config
+        .repositories()
+        .into_iter()
+        .flat_map(|repository| keystore.trusted_keys(repository.name(), repository.config()))
+        .collect())
+}
+
+/// Adds the given local NPFs to the caches, and returns the targets installing them
+fn add_local_packages(
+    config: &Config,
+    matches: &ArgMatches,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<Vec<String>, Error> {
+    let trusted_keys = if matches.is_present("require-signature") {
+        Some(trusted_keys(config)?)
+    } else {
+        None
+    };
+    let downloaded = config.downloaded_packages_cache(lock_file_ownership);
+    let mut targets = Vec::new();
+
+    for npf_path in matches.values_of_lossy("from-file").unwrap_or_default() {
+        let id = downloaded
+            .add_local_package(&npf_path, trusted_keys.as_ref().map(Vec::as_slice))
+            .with_context(|_| format_err!("unable to add the local package '{}'", npf_path))?;
+        let full_name: PackageFullName = id.clone().into();
+
+        writeln!(output::messages(), "Added {} from {}", id, npf_path)?;
+        targets.push(format!("{}#={}", full_name, id.version()));
+    }
+    Ok(targets)
+}
 
 pub fn install(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
     let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    check_pending_transactions(config, &lock_file_ownership)?;
+    index_local_repository(config, &lock_file_ownership)?;
 
     let mut graph = config.dependency_graph(&lock_file_ownership)?;
     let original_graph = graph.clone();
 
+    let mut target_names = matches.values_of_lossy("PACKAGE").unwrap_or_default();
+    target_names.extend(add_local_packages(config, matches, &lock_file_ownership)?);
+
+    // The packages given on the command line, whose optional dependencies may be installed too
+    let mut targets: Vec<QueryResult> = Vec::new();
+
     {
         let packages_cache = config.available_packages_cache(&lock_file_ownership);
 
-        for target in &matches.values_of_lossy("PACKAGE").unwrap() {
-            let requirement = SoftPackageRequirement::parse(&target)?;
+        for target in &target_names {
+            // Groups defined by the repositories are installed as a unit
+            if target.starts_with('@') {
+                let name = GroupName::from_str(target)?;
 
-            let matched_packages = packages_cache
-                .query(&requirement)
-                .set_strategy(AvailablePackagesCacheQueryStrategy::BestMatch)
-                .perform()?;
-            if matched_packages.len() > 1 {
-                for pkg in matched_packages {
-                    println!("{}", pkg.manifest().name());
+                graph
+                    .require_group(config, &name)
+                    .with_context(|_| format_err!("unable to install group '{}'", target))?;
+                continue;
+            }
+
+            // Its version requirement may follow its name after a `#`, or directly, like `coreutils>=1.2`
+            let query = PackageQuery::parse(&target)?;
+
+            let matched_packages = query.available(
+                config,
+                &lock_file_ownership,
+                AvailablePackagesCacheQueryStrategy::BestMatch,
+            )?;
+
+            // The target may be a virtual package, in which case the package providing it is installed
+            let virtual_name = query.requirement().and_then(|requirement| {
+                let category = requirement.category().clone()?;
+                Some(PackageShortName::from(category, requirement.name().clone()))
+            });
+            if let (true, Some(virtual_name)) = (matched_packages.is_empty(), virtual_name) {
+                let providers = packages_cache.providers(&virtual_name)?;
+
+                if !providers.is_empty() {
+                    // Virtual packages are provided without a version, which thus can't be required
+                    if query.has_version_requirement() {
+                        return Err(format_err!(
+                            "unable to install '{}': {} is a virtual package, which can't be given a version requirement",
+                            target,
+                            virtual_name
+                        ));
+                    }
+                    let provider = choose_provider(config, &virtual_name, &providers)?;
+
+                    writeln!(
+                        output::messages(),
+                        "Using {} to provide {}",
+                        provider,
+                        virtual_name
+                    )?;
+                    targets.extend(
+                        providers
+                            .into_iter()
+                            .filter(|result| result.full_name() == provider),
+                    );
+                    graph.node_add_requirement(
+                        graph.root_id(),
+                        RequirementKind::Package {
+                            package_req: HardPackageRequirement::from(
+                                provider,
+                                query.version_requirement().clone(),
+                            )
+                            .into(),
+                        },
+                        RequirementManagementMethod::Static,
+                    );
+                    continue;
                 }
-                return Err(format_err!("unable to select a best match"));
-            } else if matched_packages.is_empty() {
-                return Err(format_err!(
-                    "no package found for requirement '{}'",
-                    &target
-                ));
             }
-            let matched_package = &matched_packages[0];
+
+            let matched_package = &query.select_one(matched_packages, QueryResult::id)?;
+
+            // The new requirement replaces the previous ones on the same package and slot, so that its version can
+            // change while the other slots are left untouched
+            let full_name = matched_package.full_name();
+            let slot = query.slot().cloned();
+            let previous: Vec<_> = graph.nodes()[&graph.root_id()]
+                .requirements()
+                .iter()
+                .filter(
+                    |requirement_id| match graph.requirements()[requirement_id].kind() {
+                        RequirementKind::Package { package_req } => {
+                            package_req.matches_full_name_precisely(&full_name)
+                                && *package_req.slot() == slot
+                        }
+                        RequirementKind::Group { .. } => false,
+                    },
+                )
+                .cloned()
+                .collect();
+            for requirement_id in previous {
+                graph.remove_requirement(requirement_id);
+            }
 
             let package_req = HardPackageRequirement::from(
                 matched_package.full_name(),
-                requirement.version_requirement().clone(),
-            );
+                query.version_requirement().clone(),
+            )
+            .with_slot(slot);
             graph.node_add_requirement(
                 graph.root_id(),
                 RequirementKind::Package {
@@ -49,55 +179,133 @@ pub fn install(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
                 },
                 RequirementManagementMethod::Static,
             );
+            targets.push(matched_package.clone());
+        }
+    }
+
+    if matches.is_present("with-optional") {
+        for target in &targets {
+            let mut optional_dependencies: Vec<_> =
+                target.manifest().optional_dependencies().iter().collect();
+            optional_dependencies.sort();
+
+            for dependency in optional_dependencies {
+                let kind = RequirementKind::Package {
+                    package_req: dependency.clone(),
+                };
+
+                if !graph.node_has_requirement(&graph.nodes()[&graph.root_id()], &kind) {
+                    writeln!(
+                        output::messages(),
+                        "Including {}, an optional dependency of {}",
+                        dependency,
+                        target.full_name()
+                    )?;
+                    graph.node_add_requirement(
+                        graph.root_id(),
+                        kind,
+                        RequirementManagementMethod::Static,
+                    );
+                }
+            }
         }
     }
 
-    graph.solve(&config)?;
+    solve_interactively(config, &mut graph, |graph, config| graph.solve(config))?;
 
     let transactions = DependencyGraphDiff::new().perform(&original_graph, &graph);
 
     if transactions.is_empty() {
-        println!("No transactions are required, quitting.");
-        graph.save_to_cache(config.paths().depgraph(), &lock_file_ownership)?;
+        writeln!(
+            output::messages(),
+            "No transactions are required, quitting."
+        )?;
+        if !config.dry_run() {
+            save_dependency_graph(config, &graph, &lock_file_ownership)?;
+        }
         return Ok(());
     }
 
     print_transactions(&transactions);
 
-    if !ask_confirmation(
-        format!(
-            "Would you like to apply th{} transaction{}?",
-            if transactions.len() <= 1 { "is" } else { "ese" },
-            if transactions.len() <= 1 { "" } else { "s" },
-        )
-        .as_str(),
-        true,
-    )? {
-        println!(
-            "Transaction{} cancelled.",
-            if transactions.len() <= 1 { "" } else { "s" }
-        );
+    if config.dry_run() {
+        return print_plans(config, &transactions, &lock_file_ownership);
+    }
+
+    if !confirm_downgrades(config, &transactions)? {
+        writeln!(output::messages(), "Nothing was downgraded.")?;
+        return Ok(());
+    }
+
+    if !confirm_transactions(config, &transactions, &lock_file_ownership)? {
         return Ok(());
     }
 
-    println!("Downloading packages...");
-    let downloaded = config.downloaded_packages_cache(&lock_file_ownership);
-    download_packages(
+    apply_transactions(
         config,
-        transactions.iter().filter_map(|trans| match trans {
-            Transaction::Install(install) if !downloaded.has_package(install.target()) => {
-                Some(install.associated_download())
-            }
-            Transaction::Upgrade(upgrade) if !downloaded.has_package(upgrade.new_target()) => {
-                Some(upgrade.associated_download())
-            }
-            _ => None,
-        }),
+        &transactions,
+        &original_graph,
+        &graph,
+        None,
+        &lock_file_ownership,
     )?;
 
-    process_transactions(config, &transactions, &lock_file_ownership)?;
+    print_optional_dependencies(config, &graph, &transactions, &lock_file_ownership)
+}
+
+/// Returns whether a package matching the given requirement is in the given graph
+fn is_in_graph(graph: &DependencyGraph, requirement: &PackageRequirement) -> bool {
+    graph.nodes().values().any(|node| match node.kind() {
+        NodeKind::Package { id, .. } => requirement.matches(id),
+        NodeKind::Group { .. } => false,
+    })
+}
+
+/// Prints the optional dependencies and the suggestions of the newly installed packages that aren't installed
+fn print_optional_dependencies(
+    config: &Config,
+    graph: &DependencyGraph,
+    transactions: &[Transaction],
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let available = config.available_packages_cache(lock_file_ownership);
+
+    for transaction in transactions {
+        if let Transaction::Install(install) = transaction {
+            let target = install.target();
+            let manifest = available
+                .query(&SoftPackageRequirement::from_id(target))
+                .perform()?
+                .into_iter()
+                .find(|result| result.id() == *target)
+                .map(|result| result.manifest().clone());
 
-    graph.save_to_cache(config.paths().depgraph(), &lock_file_ownership)?;
+            if let Some(manifest) = manifest {
+                let lists = [
+                    ("Optional dependencies", manifest.optional_dependencies()),
+                    ("Suggested packages", manifest.suggests()),
+                ];
 
+                for (title, requirements) in lists.iter() {
+                    let mut missing: Vec<_> = requirements
+                        .iter()
+                        .filter(|requirement| !is_in_graph(graph, requirement))
+                        .map(ToString::to_string)
+                        .collect();
+                    missing.sort();
+
+                    if !missing.is_empty() {
+                        writeln!(
+                            output::messages(),
+                            "{} of {}: {}",
+                            title,
+                            target,
+                            missing.join(", ")
+                        )?;
+                    }
+                }
+            }
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,211 @@
+use std::io::Cursor;
+
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use libnest::cache::keys::Keystore;
+use libnest::config::Config;
+use libnest::package::TrustedKey;
+use libnest::repository::Repository;
+
+use super::ask_confirmation;
+use super::operations::download::Download;
+
+/// Finds the repository with the given name
+pub(super) fn find_repository<'a>(
+    config: &'a Config,
+    name: &str,
+) -> Result<Repository<'a, 'a>, Error> {
+    config
+        .repositories()
+        .into_iter()
+        .find(|repository| repository.name() == name)
+        .ok_or_else(|| format_err!("unable to find repository '{}'", name))
+}
+
+/// Asks the user whether the given key should be trusted to sign the packages of the given repository,
+/// unless its fingerprint is already trusted by the configuration
+fn confirm_trust(
+    config: &Config,
+    repository: &Repository,
+    key: &TrustedKey,
+) -> Result<bool, Error> {
+    if repository.config().has_trusted_fingerprint(key) {
+        return Ok(true);
+    }
+
+    println!(
+        "The key {} is not trusted yet to sign the packages of {}.",
+        key.fingerprint().bold(),
+        repository.name().bold()
+    );
+    ask_confirmation(config, "Would you like to trust this key?", false)
+}
+
+/// Imports the given key for the given repository, asking for confirmation if needed.
+///
+/// Returns true if the key was imported.
+fn import_key(
+    config: &Config,
+    repository: &Repository,
+    keystore: &mut Keystore,
+    key: TrustedKey,
+) -> Result<bool, Error> {
+    if keystore
+        .trusted_keys(repository.name(), repository.config())
+        .contains(&key)
+    {
+        println!(
+            "The key {} is already trusted for {}.",
+            key.fingerprint().bold(),
+            repository.name().bold()
+        );
+        return Ok(false);
+    }
+
+    if !confirm_trust(config, repository, &key)? {
+        println!("The key wasn't imported.");
+        return Ok(false);
+    }
+
+    if config.dry_run() {
+        println!("Dry run, the key wasn't imported.");
+        return Ok(false);
+    }
+
+    keystore.import(repository.name(), key);
+    println!(
+        "The key {} is now trusted for {}.",
+        key.fingerprint().bold(),
+        repository.name().bold()
+    );
+    Ok(true)
+}
+
+pub fn key_import(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let repository = find_repository(config, matches.value_of("REPOSITORY").unwrap())?;
+    let key = TrustedKey::parse(matches.value_of("KEY").unwrap())?;
+
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let mut keystore = config.keystore()?;
+
+    if import_key(config, &repository, &mut keystore, key)? {
+        keystore.save_to_cache(config.paths().keys(), &lock_file_ownership)?;
+    }
+    Ok(())
+}
+
+pub fn key_list(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let keystore = config.keystore()?;
+    let mut repositories = config.repositories();
+
+    repositories.sort_by(|a, b| a.name().cmp(b.name()));
+    if let Some(name) = matches.value_of("REPOSITORY") {
+        repositories.retain(|repository| repository.name() == name);
+        if repositories.is_empty() {
+            return Err(format_err!("unable to find repository '{}'", name));
+        }
+    }
+
+    for repository in repositories {
+        println!("{}", repository.name().bold());
+
+        for key in repository.config().trusted_keys() {
+            let status = if keystore.is_revoked(repository.name(), key) {
+                "revoked".red()
+            } else {
+                "configured".green()
+            };
+            println!("    {} {}", key.fingerprint(), status);
+        }
+
+        for stored in keystore.keys(repository.name()) {
+            if repository.config().trusted_keys().contains(stored.key()) {
+                continue;
+            }
+
+            let status = match stored.revoked_at() {
+                Some(revoked_at) => format!("revoked on {}", revoked_at.format("%Y-%m-%d")).red(),
+                None => format!("imported on {}", stored.imported_at().format("%Y-%m-%d")).green(),
+            };
+            println!("    {} {}", stored.key().fingerprint(), status);
+        }
+    }
+    Ok(())
+}
+
+pub fn key_revoke(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let repository = find_repository(config, matches.value_of("REPOSITORY").unwrap())?;
+    let fingerprint = matches.value_of("FINGERPRINT").unwrap();
+
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let mut keystore = config.keystore()?;
+
+    let key = keystore
+        .find(repository.name(), fingerprint)
+        .map(|stored| *stored.key())
+        .or_else(|| {
+            repository
+                .config()
+                .trusted_keys()
+                .iter()
+                .find(|key| key.fingerprint().eq_ignore_ascii_case(fingerprint))
+                .cloned()
+        })
+        .ok_or_else(|| {
+            format_err!(
+                "no key with fingerprint {} for repository '{}'",
+                fingerprint,
+                repository.name()
+            )
+        })?;
+
+    if config.dry_run() {
+        println!("Dry run, the key wasn't revoked.");
+        return Ok(());
+    }
+
+    if keystore.revoke_key(repository.name(), key) {
+        keystore.save_to_cache(config.paths().keys(), &lock_file_ownership)?;
+        println!(
+            "The key {} is no longer trusted for {}.",
+            key.fingerprint().bold(),
+            repository.name().bold()
+        );
+    } else {
+        println!("The key {} was already revoked.", key.fingerprint().bold());
+    }
+    Ok(())
+}
+
+/// Makes sure the given repository has a trusted key, fetching the repository's key from its mirrors
+/// and asking whether it should be trusted if it doesn't.
+///
+/// This is done when a repository is first used, so that its packages can be verified.
+pub fn trust_on_first_use(
+    config: &Config,
+    repository: &Repository,
+    keystore: &mut Keystore,
+) -> Result<bool, Error> {
+    if repository.config().allow_unsigned()
+        || !keystore
+            .trusted_keys(repository.name(), repository.config())
+            .is_empty()
+    {
+        return Ok(false);
+    }
+
+    let mut data = Cursor::new(Vec::new());
+    Download::from("api/key", config.network())
+        .with_repository(repository.config())?
+        .perform_with_mirrors(&mut data, &config.ranked_mirrors(repository.config()))?;
+
+    let key = TrustedKey::parse(String::from_utf8_lossy(data.get_ref()).trim())?;
+    if keystore.is_revoked(repository.name(), &key) {
+        return Err(format_err!(
+            "the key announced by repository '{}' was revoked",
+            repository.name()
+        ));
+    }
+    import_key(config, repository, keystore, key)
+}
@@ -0,0 +1,71 @@
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use libnest::cache::licenses::PackageLicense;
+use libnest::config::Config;
+use serde_json::{json, Value};
+
+use super::output::{json_output, print_json};
+
+fn license_json(license: &PackageLicense) -> Value {
+    json!({
+        "package": license.id().to_string(),
+        "license": license.license().map(ToString::to_string),
+        "licenses": license.licenses().iter().map(ToString::to_string).collect::<Vec<_>>(),
+        "denied": license.denied(),
+    })
+}
+
+/// Returns the license expression of the package, or else the licenses listed in its manifest
+fn describe(license: &PackageLicense) -> String {
+    match license.license() {
+        Some(expression) => expression.to_string(),
+        None if license.licenses().is_empty() => String::from("unknown"),
+        None => license
+            .licenses()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+pub fn license(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+
+    let mut licenses = PackageLicense::query(config, &lock_file_ownership)?;
+    if matches.is_present("denied") {
+        licenses.retain(PackageLicense::is_denied);
+    }
+
+    if json_output() {
+        print_json(&Value::Array(licenses.iter().map(license_json).collect()));
+    } else {
+        for license in &licenses {
+            if license.is_denied() {
+                println!(
+                    "{} {} {}",
+                    license.id(),
+                    describe(license),
+                    format!("[denied: {}]", license.denied().join(", ")).red()
+                );
+            } else {
+                println!("{} {}", license.id(), describe(license));
+            }
+        }
+    }
+
+    // Failing lets scripts check that the system complies with the license policy
+    match licenses
+        .iter()
+        .filter(|license| license.is_denied())
+        .count()
+    {
+        0 => Ok(()),
+        1 => Err(format_err!("1 installed package is under a denied license")),
+        n => Err(format_err!(
+            "{} installed packages are under a denied license",
+            n
+        )),
+    }
+}
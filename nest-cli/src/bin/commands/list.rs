@@ -1,43 +1,105 @@
+use std::convert::TryFrom;
+
 use clap::ArgMatches;
 use failure::Error;
 
-use libnest::cache::depgraph::{NodeKind, RequirementManagementMethod};
+use libnest::cache::listing::{ListedPackage, PackageListing, PackageListingSource};
 use libnest::config::Config;
+use libnest::package::{CategoryName, PackageFullName, RepositoryName};
+use serde_json::json;
+
+use super::output::{json_output, print_json};
+
+fn listed_json(package: &ListedPackage) -> serde_json::Value {
+    let id = package.id();
+
+    json!({
+        "repository": id.repository().to_string(),
+        "category": id.category().to_string(),
+        "name": id.name().to_string(),
+        "version": id.version().to_string(),
+        "installed_version": package.installed_version().map(ToString::to_string),
+        "explicitly_installed": package.explicitly_installed(),
+        "upgrade": package.upgrade().map(ToString::to_string),
+    })
+}
+
+/// Splits a prefix like `stable::sys-devel/gcc` into the repository, the category and the beginning of the name of
+/// the packages to list, each of them being optional
+fn parse_prefix(
+    prefix: &str,
+) -> Result<(Option<RepositoryName>, Option<CategoryName>, &str), Error> {
+    let (repository, rest) = match prefix.find("::") {
+        Some(pos) => (
+            Some(RepositoryName::try_from(&prefix[..pos])?),
+            &prefix[pos + 2..],
+        ),
+        None => (None, prefix),
+    };
+    let (category, name) = match rest.find('/') {
+        Some(pos) => (
+            Some(CategoryName::try_from(&rest[..pos])?),
+            &rest[pos + 1..],
+        ),
+        None => (None, rest),
+    };
+    Ok((repository, category, name))
+}
 
 pub fn list(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
-    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
-    let depgraph = config.dependency_graph(&lock_file_ownership)?;
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
 
-    let mut packages = Vec::new();
+    let available = matches.is_present("available");
+    let orphaned = matches.is_present("orphaned");
+    let upgradable = matches.is_present("upgradable");
 
-    if matches.is_present("with-deps") {
-        packages = depgraph
-            .packages()
-            .map(|name| format!("{}", name))
-            .collect();
+    let source = if available {
+        PackageListingSource::Available
     } else {
-        for (_, req) in depgraph.requirements() {
-            if let RequirementManagementMethod::Static = req.management_method() {
-                let node = depgraph
-                    .nodes()
-                    .get(&req.fulfilling_node_id().unwrap())
-                    .unwrap();
-
-                if let NodeKind::Package { id } = node.kind() {
-                    packages.push(format!(
-                        "{}::{}/{}",
-                        id.repository(),
-                        id.category(),
-                        id.name()
-                    ));
-                }
-            }
+        PackageListingSource::Installed
+    };
+
+    // Without any other filter, only the explicitly installed packages are listed
+    let explicit_only = !available && !orphaned && !upgradable && !matches.is_present("with-deps");
+
+    let mut listing = PackageListing::from(config, &lock_file_ownership, source)
+        .set_explicit_only(explicit_only)
+        .set_orphaned_only(orphaned)
+        .set_upgradable_only(upgradable);
+    if let Some(repository) = matches.value_of("repository") {
+        listing = listing.set_repository(RepositoryName::try_from(repository)?);
+    }
+    if let Some(category) = matches.value_of("category") {
+        listing = listing.set_category(CategoryName::try_from(category)?);
+    }
+    if let Some(prefix) = matches.value_of("PREFIX") {
+        let (repository, category, name) = parse_prefix(prefix)?;
+
+        if let Some(repository) = repository {
+            listing = listing.set_repository(repository);
         }
+        if let Some(category) = category {
+            listing = listing.set_category(category);
+        }
+        listing = listing.set_name_prefix(name);
     }
-    packages.sort();
+    let packages = listing.perform()?;
 
-    for p in packages {
-        println!("{}", p);
+    if json_output() {
+        print_json(&json!(packages.iter().map(listed_json).collect::<Vec<_>>()));
+        return Ok(());
+    }
+
+    for package in packages {
+        let full_name: PackageFullName = package.id().clone().into();
+
+        match (package.installed_version(), package.upgrade()) {
+            (Some(installed_version), Some(upgrade)) if upgradable => {
+                println!("{} {} -> {}", full_name, installed_version, upgrade)
+            }
+            (_, _) if available => println!("{} {}", full_name, package.id().version()),
+            _ => println!("{}", full_name),
+        }
     }
     Ok(())
 }
@@ -0,0 +1,141 @@
+//! Logging of the operations of the libraries, on the console and in the log file.
+//!
+//! The console only shows the warnings by default, more with each `-v` and only the errors with `-q`. The log
+//! file records at least the informational messages, whatever the verbosity of the console, and is rotated once
+//! it grows larger than configured.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::Local;
+use clap::ArgMatches;
+use colored::*;
+use libnest::config::{Config, LogConfig};
+use log::{debug, warn, Level, LevelFilter, Log, Metadata, Record};
+
+/// The least important level of the messages recorded in the log file
+const LOG_FILE_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// A logger printing the messages on stderr and appending them to the log file
+struct NestLogger {
+    console_level: LevelFilter,
+    file_level: LevelFilter,
+    file: Mutex<Option<File>>,
+}
+
+impl Log for NestLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.console_level || metadata.level() <= self.file_level
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= self.console_level {
+            let level = match record.level() {
+                Level::Error => "error".red().bold(),
+                Level::Warn => "warning".yellow().bold(),
+                Level::Info => "info".bold(),
+                Level::Debug => "debug".bright_black(),
+                Level::Trace => "trace".bright_black(),
+            };
+            eprintln!("{}: {}", level, record.args());
+        }
+
+        if record.level() <= self.file_level {
+            if let Some(file) = self.file.lock().expect("the log file is poisoned").as_mut() {
+                let _ = writeln!(
+                    file,
+                    "{} {:<5} [{}] {}: {}",
+                    Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    record.level(),
+                    std::process::id(),
+                    record.target(),
+                    record.args()
+                );
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().expect("the log file is poisoned").as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Returns the path of the given rotated log file, `0` being the current log file
+fn rotated_path(path: &Path, rotation: usize) -> PathBuf {
+    if rotation == 0 {
+        path.to_path_buf()
+    } else {
+        let mut rotated = path.as_os_str().to_os_string();
+        rotated.push(format!(".{}", rotation));
+        PathBuf::from(rotated)
+    }
+}
+
+/// Rotates the log file if it is larger than configured, then opens it in append mode
+fn open_log_file(path: &Path, config: &LogConfig) -> Option<File> {
+    let size = fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+
+    if size >= config.max_size() {
+        // The oldest rotated file is overwritten by the one before it
+        for rotation in (0..config.rotations()).rev() {
+            let _ = fs::rename(
+                rotated_path(path, rotation),
+                rotated_path(path, rotation + 1),
+            );
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+/// Sets up the logging according to the `-v` and `-q` options and to the configuration.
+///
+/// The log file is left out if it can't be opened, like when Nest isn't run as root.
+pub fn init_logging(config: &Config, matches: &ArgMatches) {
+    let console_level = if matches.is_present("quiet") {
+        LevelFilter::Error
+    } else {
+        match matches.occurrences_of("v") {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    };
+    let file_level = console_level.max(LOG_FILE_LEVEL);
+
+    let logger = NestLogger {
+        console_level,
+        file_level,
+        file: Mutex::new(open_log_file(config.paths().log_file(), config.log())),
+    };
+
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(console_level.max(file_level));
+        debug!(
+            "running '{}'",
+            std::env::args().collect::<Vec<_>>().join(" ")
+        );
+    }
+}
+
+/// Warns about the configuration files written for an older layout of the configuration, and about their keys that
+/// don't match any setting, once the logging is set up
+pub fn report_config_warnings(config: &Config) {
+    for path in config.outdated_files() {
+        warn!(
+            "{}: written for an older layout of the configuration, run `nest config migrate` to update it",
+            path.display()
+        );
+    }
+    for key in config.unknown_keys() {
+        warn!("{}", key);
+    }
+}
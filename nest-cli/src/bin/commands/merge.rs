@@ -1,13 +1,15 @@
 use failure::{format_err, Error, ResultExt};
 use libnest::cache::depgraph::DependencyGraphDiff;
 use libnest::config::Config;
-use libnest::transaction::Transaction;
 
-use super::operations::download::download_packages;
-use super::{ask_confirmation, print_transactions, process_transactions};
+use super::{
+    apply_transactions, check_pending_transactions, confirm_transactions, print_plans,
+    print_transactions, save_dependency_graph,
+};
 
 pub fn merge(config: &Config) -> Result<(), Error> {
     let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    check_pending_transactions(config, &lock_file_ownership)?;
 
     let graph = config
         .scratch_dependency_graph(&lock_file_ownership)
@@ -18,46 +20,30 @@ pub fn merge(config: &Config) -> Result<(), Error> {
 
     if transactions.is_empty() {
         println!("No transactions are required, quitting.");
-        graph.save_to_cache(config.paths().depgraph(), &lock_file_ownership)?;
+        if !config.dry_run() {
+            save_dependency_graph(config, &graph, &lock_file_ownership)?;
+        }
         return Ok(());
     }
 
     print_transactions(&transactions);
 
-    if !ask_confirmation(
-        format!(
-            "Would you like to apply th{} transaction{}?",
-            if transactions.len() <= 1 { "is" } else { "ese" },
-            if transactions.len() <= 1 { "" } else { "s" },
-        )
-        .as_str(),
-        true,
-    )? {
-        println!(
-            "Transaction{} cancelled.",
-            if transactions.len() <= 1 { "" } else { "s" }
-        );
+    if config.dry_run() {
+        return print_plans(config, &transactions, &lock_file_ownership);
+    }
+
+    if !confirm_transactions(config, &transactions, &lock_file_ownership)? {
         return Ok(());
     }
 
-    println!("Downloading packages...");
-    let downloaded = config.downloaded_packages_cache(&lock_file_ownership);
-    download_packages(
+    apply_transactions(
         config,
-        transactions.iter().filter_map(|trans| match trans {
-            Transaction::Install(install) if !downloaded.has_package(install.target()) => {
-                Some(install.associated_download())
-            }
-            Transaction::Upgrade(upgrade) if !downloaded.has_package(upgrade.new_target()) => {
-                Some(upgrade.associated_download())
-            }
-            _ => None,
-        }),
+        &transactions,
+        &original_graph,
+        &graph,
+        None,
+        &lock_file_ownership,
     )?;
 
-    process_transactions(config, &transactions, &lock_file_ownership)?;
-
-    graph.save_to_cache(config.paths().depgraph(), &lock_file_ownership)?;
-
     Ok(())
 }
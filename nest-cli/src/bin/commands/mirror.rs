@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use colored::*;
+use failure::Error;
+use libnest::cache::mirrors::MirrorHealth;
+use libnest::config::Config;
+
+use super::operations::download::Download;
+
+/// Time after which a mirror that couldn't be reached is considered unreachable
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn mirror_ping(config: &Config) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    let mut mirrors_health = config.mirrors_health()?;
+    for repository in config.repositories() {
+        println!("{}", repository.name().bold());
+
+        let download =
+            Download::from("api/pull", config.network()).with_repository(repository.config())?;
+
+        for mirror in repository.config().mirrors() {
+            let mut health = MirrorHealth::measure(mirror, PING_TIMEOUT);
+
+            if health.is_reachable() {
+                *health.throughput_mut() = download.measure_throughput(mirror).ok();
+            }
+
+            match (health.latency(), health.throughput()) {
+                (Some(latency), Some(throughput)) => println!(
+                    "    {} {:>6}ms {:>10}KiB/s",
+                    mirror.as_str(),
+                    latency.as_millis(),
+                    throughput / 1024
+                ),
+                (Some(latency), None) => println!(
+                    "    {} {:>6}ms {:>16}",
+                    mirror.as_str(),
+                    latency.as_millis(),
+                    "unknown"
+                ),
+                (None, _) => println!("    {} {}", mirror.as_str(), "unreachable".red()),
+            }
+            mirrors_health.update(mirror, health);
+        }
+    }
+
+    mirrors_health.save_to_cache(config.paths().mirrors(), &lock_file_ownership)?;
+    println!("Mirrors will now be tried from the fastest to the slowest.");
+    Ok(())
+}
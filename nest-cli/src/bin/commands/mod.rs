@@ -1,34 +1,126 @@
+mod alternatives;
+mod audit;
+mod autoremove;
+mod cache;
+mod changelog;
+mod completions;
+mod config_files;
+mod config_keys;
+mod config_merge;
+mod config_migrate;
+mod depends;
+mod doctor;
+pub mod errors;
+mod files;
+mod graph;
 mod group;
+mod help;
+mod history;
+mod hold;
+mod info;
 mod install;
+mod key;
+mod license;
 mod list;
+mod logging;
 mod merge;
+mod mirror;
 pub mod operations;
+pub mod output;
+mod owns;
 mod pull;
+mod reinstall;
+mod repository;
 mod requirement;
+mod resume;
+mod rollback;
+mod search;
+pub mod spec;
+mod sysroot;
+mod undo;
 mod uninstall;
 mod upgrade;
+mod verify;
 
+pub use self::alternatives::{alternatives_auto, alternatives_list, alternatives_set};
+pub use self::audit::audit;
+pub use self::autoremove::autoremove;
+pub use self::cache::{cache_clean, cache_clear, cache_stats, cache_verify};
+use self::cache::{collect_store_garbage, enforce_download_cache_limits};
+pub use self::changelog::changelog;
+pub use self::completions::{completions, package_names};
+pub use self::config_files::ConfigFiles;
+pub use self::config_keys::{config_get, config_set};
+pub use self::config_merge::config_merge;
+pub use self::config_migrate::config_migrate;
+pub use self::depends::{rdepends, why};
+pub use self::doctor::doctor;
+pub use self::files::files;
+pub use self::graph::graph;
 pub use self::group::{group_add, group_list, group_remove};
+pub use self::help::help;
+pub use self::history::history;
+pub use self::hold::{hold, unhold};
+pub use self::info::info;
 pub use self::install::install;
+pub use self::key::{key_import, key_list, key_revoke};
+pub use self::license::license;
 pub use self::list::list;
+pub use self::logging::{init_logging, report_config_warnings};
 pub use self::merge::merge;
+pub use self::mirror::mirror_ping;
+use self::operations::download::{download_packages, download_size};
 use self::operations::install::install_package;
+use self::operations::progress::PackageProgress;
+use self::operations::reinstall::reinstall_package;
 use self::operations::uninstall::uninstall_package;
 use self::operations::upgrade::upgrade_package;
+pub use self::owns::owns;
 pub use self::pull::pull;
+pub use self::reinstall::reinstall;
+pub use self::repository::{
+    repository_add, repository_add_mirror, repository_disable, repository_enable, repository_list,
+    repository_remove, repository_remove_mirror,
+};
 pub use self::requirement::{requirement_add, requirement_remove};
+pub use self::resume::resume;
+pub use self::rollback::rollback;
+pub use self::search::search;
+pub use self::sysroot::{set_sysroot, sysroot_create, sysroot_list, sysroot_remove};
+pub use self::undo::undo;
 pub use self::uninstall::uninstall;
 pub use self::upgrade::upgrade;
+pub use self::verify::verify;
 
+use clap::ArgMatches;
 use colored::*;
-use failure::{Error, ResultExt};
+use failure::{format_err, Context, Error, ResultExt};
+use indicatif::HumanBytes;
+use serde_json::{json, Value};
 use std::io::{self, Write};
+use std::time::Duration;
 
-use libnest::config::Config;
-use libnest::lock_file::LockFileOwnership;
-use libnest::transaction::Transaction;
+use libnest::cache::available::QueryResult;
+use libnest::cache::depgraph::DependencyGraph;
+use libnest::cache::query::PackageQuery;
+use libnest::cache::world::World;
+use libnest::cache::DependencyGraphErrorKind;
+use libnest::config::{parse_size, Config, ConfirmationPolicy};
+use libnest::lock_file::{LockFileOwnership, LockWait};
+use libnest::package::{PackageFullName, PackageID, PackageShortName};
+use libnest::transaction::{
+    find_file_collisions, rollback_interrupted_transaction, take_snapshot, verify_package, Hook,
+    PackageDownload, PendingTransactions, Transaction, TransactionPlan, TransactionStep,
+    TransactionSteps, TriggeredHooks,
+};
 
 pub fn print_transactions(transactions: &[Transaction]) {
+    if output::json_output() {
+        let transactions: Vec<_> = transactions.iter().map(transaction_json).collect();
+        output::print_json(&json!({ "transactions": transactions }));
+        return;
+    }
+
     println!(
         "{}",
         format!(
@@ -40,34 +132,491 @@ pub fn print_transactions(transactions: &[Transaction]) {
     );
     println!();
     for transaction in transactions {
-        println!(
-            "{}",
-            match transaction {
-                Transaction::Pull(p) => {
-                    format!("{:>8.8} {}", "pull".cyan(), p.target_repository().name()).bold()
-                }
-                Transaction::Install(i) => {
-                    format!("{:>8.8} {}", "install".green(), i.target()).bold()
-                }
-                Transaction::Remove(r) => format!("{:>8.8} {}", "remove".red(), r.target()).bold(),
-                Transaction::Upgrade(u) => {
-                    format!("{:>8.8} {}", "upgrade".yellow(), u.new_target()).bold()
-                }
+        println!("{}", format_transaction(transaction));
+    }
+}
+
+fn format_transaction(transaction: &Transaction) -> ColoredString {
+    match transaction {
+        Transaction::Pull(p) => {
+            format!("{:>9.9} {}", "pull".cyan(), p.target_repository().name()).bold()
+        }
+        Transaction::Install(i) => format!("{:>9.9} {}", "install".green(), i.target()).bold(),
+        Transaction::Remove(r) => format!("{:>9.9} {}", "remove".red(), r.target()).bold(),
+        Transaction::Upgrade(u) if u.is_downgrade() => {
+            format!("{:>9.9} {}", "downgrade".magenta(), u.new_target()).bold()
+        }
+        Transaction::Upgrade(u) => format!("{:>9.9} {}", "upgrade".yellow(), u.new_target()).bold(),
+        Transaction::Reinstall(r) => format!("{:>9.9} {}", "reinstall".blue(), r.target()).bold(),
+    }
+}
+
+fn format_step(step: &TransactionStep) -> ColoredString {
+    match step {
+        TransactionStep::Download(download) => {
+            format!("{:>9.9} {}", "download".cyan(), download.target()).normal()
+        }
+        TransactionStep::Verify(id) => format!("{:>9.9} {}", "verify".cyan(), id).normal(),
+        _ => format_transaction(&step.transaction().expect("the step applies a transaction")),
+    }
+}
+
+fn step_json(step: &TransactionStep) -> Value {
+    match step {
+        TransactionStep::Download(download) => json!({
+            "action": "download",
+            "target": download.target().to_string(),
+        }),
+        TransactionStep::Verify(id) => json!({
+            "action": "verify",
+            "target": id.to_string(),
+        }),
+        _ => transaction_json(&step.transaction().expect("the step applies a transaction")),
+    }
+}
+
+fn transaction_json(transaction: &Transaction) -> Value {
+    match transaction {
+        Transaction::Pull(p) => json!({
+            "action": "pull",
+            "repository": p.target_repository().name(),
+        }),
+        Transaction::Install(i) => json!({
+            "action": "install",
+            "target": i.target().to_string(),
+        }),
+        Transaction::Remove(r) => json!({
+            "action": "remove",
+            "target": r.target().to_string(),
+        }),
+        Transaction::Upgrade(u) => json!({
+            "action": if u.is_downgrade() { "downgrade" } else { "upgrade" },
+            "target": u.new_target().to_string(),
+            "previous": u.old_target().to_string(),
+        }),
+        Transaction::Reinstall(r) => json!({
+            "action": "reinstall",
+            "target": r.target().to_string(),
+        }),
+    }
+}
+
+/// Asks the user to confirm the downgrades among the given transactions, if any.
+///
+/// Returns true if there is no downgrade, or if they were confirmed.
+pub fn confirm_downgrades(config: &Config, transactions: &[Transaction]) -> Result<bool, Error> {
+    let downgrades: Vec<_> = transactions
+        .iter()
+        .filter_map(|transaction| match transaction {
+            Transaction::Upgrade(upgrade) if upgrade.is_downgrade() => Some(upgrade),
+            _ => None,
+        })
+        .collect();
+
+    if downgrades.is_empty() {
+        return Ok(true);
+    }
+
+    let mut prompt = output::prompt();
+
+    writeln!(prompt)?;
+    for downgrade in &downgrades {
+        writeln!(
+            prompt,
+            "{} will be downgraded to version {}.",
+            downgrade.old_target().to_string().bold(),
+            downgrade.new_target().version()
+        )?;
+    }
+    ask_confirmation(
+        config,
+        format!(
+            "Would you really like to downgrade th{} package{}?",
+            if downgrades.len() <= 1 { "is" } else { "ese" },
+            if downgrades.len() <= 1 { "" } else { "s" },
+        )
+        .as_str(),
+        false,
+    )
+}
+
+/// Formats the given disk usage change, in bytes, with its sign
+fn format_size_change(added: u64, removed: u64) -> String {
+    if added >= removed {
+        format!("+{}", HumanBytes(added - removed))
+    } else {
+        format!("-{}", HumanBytes(removed - added))
+    }
+}
+
+/// Returns the target of the given transaction along with the size of the package it installs, or removes for
+/// removals, according to its plan, if it is known
+fn package_size<'a>(
+    transaction: &'a Transaction,
+    plan: &TransactionPlan,
+) -> Option<(&'a PackageID, Option<u64>)> {
+    let (target, size) = match transaction {
+        Transaction::Install(install) => (install.target(), plan.installed_size()),
+        Transaction::Remove(remove) => (remove.target(), plan.removed_size()),
+        Transaction::Upgrade(upgrade) => (upgrade.new_target(), plan.installed_size()),
+        Transaction::Reinstall(reinstall) => (reinstall.target(), plan.installed_size()),
+        _ => return None,
+    };
+    Some((
+        target,
+        Some(size).filter(|_| plan.unknown_sizes().is_empty()),
+    ))
+}
+
+/// Prints a summary of the changes the given transactions would make to the system: the size of each package,
+/// the packages removed, the size of the packages to download and the change of the disk usage.
+///
+/// The size of the packages that aren't downloaded yet is only known if their repository advertises it, the
+/// others aren't part of the disk usage. With the JSON output, the summary is printed as a single document.
+fn print_transaction_summary(
+    config: &Config,
+    transactions: &[Transaction],
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let mut removed_packages = Vec::new();
+    let mut package_sizes = Vec::new();
+    let mut downloads = 0;
+    let mut downloads_size = Some(0);
+    let mut unknown_sizes = 0;
+    let mut installed_size = 0;
+    let mut removed_size = 0;
+
+    for transaction in transactions {
+        let plan = match transaction {
+            Transaction::Install(install) => install.plan(config, lock_file_ownership)?,
+            Transaction::Remove(remove) => {
+                removed_packages.push(remove.target().to_string());
+                remove.plan(config, lock_file_ownership)?
+            }
+            Transaction::Upgrade(upgrade) => upgrade.plan(config, lock_file_ownership)?,
+            Transaction::Reinstall(reinstall) => reinstall.plan(config, lock_file_ownership)?,
+            _ => continue,
+        };
+
+        for id in plan.downloads() {
+            let size = download_size(config, &PackageDownload::from(id.clone())).ok();
+            downloads_size = downloads_size.and_then(|total: u64| Some(total + size?));
+        }
+        downloads += plan.downloads().len();
+        unknown_sizes += plan.unknown_sizes().len();
+        package_sizes.extend(package_size(transaction, &plan));
+
+        // The files of reinstalled packages replace the ones already on disk
+        if let Transaction::Reinstall(_) = transaction {
+            continue;
+        }
+        installed_size += plan.installed_size();
+        removed_size += plan.removed_size();
+    }
+
+    if output::json_output() {
+        output::print_json(&json!({
+            "transaction_summary": {
+                "removed_packages": removed_packages,
+                "package_sizes": package_sizes
+                    .iter()
+                    .map(|(target, size)| json!({ "package": target.to_string(), "size": size }))
+                    .collect::<Vec<_>>(),
+                "downloads": downloads,
+                "download_size": downloads_size,
+                "installed_size": installed_size,
+                "removed_size": removed_size,
+                "unknown_sizes": unknown_sizes,
             }
+        }));
+        return Ok(());
+    }
+
+    let package_sizes: Vec<_> = package_sizes
+        .iter()
+        .map(|(target, size)| match size {
+            Some(size) => format!("{} ({})", target, HumanBytes(*size)),
+            None => format!("{} (unknown)", target),
+        })
+        .collect();
+
+    println!();
+    if !removed_packages.is_empty() {
+        println!(
+            "{:>18} {}",
+            "Removed packages:".bold(),
+            removed_packages.join(", ")
+        );
+    }
+    if !package_sizes.is_empty() {
+        println!(
+            "{:>18} {}",
+            "Package sizes:".bold(),
+            package_sizes.join(", ")
+        );
+    }
+    if downloads > 0 {
+        println!(
+            "{:>18} {} ({} package{})",
+            "Download size:".bold(),
+            downloads_size.map_or_else(
+                || "unknown".to_string(),
+                |size| HumanBytes(size).to_string()
+            ),
+            downloads,
+            if downloads <= 1 { "" } else { "s" }
         );
     }
+
+    let disk_usage = format_size_change(installed_size, removed_size);
+    if unknown_sizes > 0 && installed_size == 0 && removed_size == 0 {
+        println!(
+            "{:>18} unknown until the package{} downloaded",
+            "Disk usage:".bold(),
+            if unknown_sizes <= 1 { " is" } else { "s are" }
+        );
+    } else if unknown_sizes > 0 {
+        println!(
+            "{:>18} {}, plus the content of the package{} whose size is unknown",
+            "Disk usage:".bold(),
+            disk_usage,
+            if unknown_sizes <= 1 { "" } else { "s" }
+        );
+    } else {
+        println!("{:>18} {}", "Disk usage:".bold(), disk_usage);
+    }
+    Ok(())
+}
+
+/// Prints a summary of the given transactions, and asks the user whether they should be applied.
+///
+/// Returns true if they were confirmed.
+pub fn confirm_transactions(
+    config: &Config,
+    transactions: &[Transaction],
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<bool, Error> {
+    print_transaction_summary(config, transactions, lock_file_ownership)?;
+    ask_transactions_confirmation(config, transactions)
+}
+
+/// Asks the user whether the given transactions should be applied, telling them they were cancelled otherwise.
+///
+/// Returns true if they were confirmed.
+pub fn ask_transactions_confirmation(
+    config: &Config,
+    transactions: &[Transaction],
+) -> Result<bool, Error> {
+    if !ask_confirmation(
+        config,
+        format!(
+            "Would you like to apply th{} transaction{}?",
+            if transactions.len() <= 1 { "is" } else { "ese" },
+            if transactions.len() <= 1 { "" } else { "s" },
+        )
+        .as_str(),
+        true,
+    )? {
+        writeln!(
+            output::messages(),
+            "Transaction{} cancelled.",
+            if transactions.len() <= 1 { "" } else { "s" }
+        )?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Prints the changes the given transactions would make to the system, without making them
+pub fn print_plans(
+    config: &Config,
+    transactions: &[Transaction],
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let mut documents = Vec::new();
+
+    for transaction in transactions {
+        let plan = match transaction {
+            Transaction::Install(install) => install.plan(config, lock_file_ownership)?,
+            Transaction::Remove(remove) => remove.plan(config, lock_file_ownership)?,
+            Transaction::Upgrade(upgrade) => upgrade.plan(config, lock_file_ownership)?,
+            Transaction::Reinstall(reinstall) => reinstall.plan(config, lock_file_ownership)?,
+            _ => continue,
+        };
+
+        if output::json_output() {
+            let downloads: Vec<_> = plan
+                .downloads()
+                .iter()
+                .map(|id| {
+                    let size = download_size(config, &PackageDownload::from(id.clone())).ok();
+                    json!({ "package": id.to_string(), "size": size })
+                })
+                .collect();
+            let instructions: Vec<_> = plan
+                .instructions()
+                .iter()
+                .map(|(id, function)| json!({ "package": id.to_string(), "function": function }))
+                .collect();
+
+            let mut document = transaction_json(transaction);
+            document["downloads"] = json!(downloads);
+            document["instructions"] = json!(instructions);
+            document["removed_files"] = json!(plan.removed_files());
+            document["created_files"] = json!(plan.created_files());
+            document["installed_size"] = json!(plan.installed_size());
+            document["removed_size"] = json!(plan.removed_size());
+            document["unknown_sizes"] = json!(plan
+                .unknown_sizes()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>());
+            documents.push(document);
+            continue;
+        }
+
+        println!("\n{}", format_transaction(transaction));
+        for id in plan.downloads() {
+            let download = PackageDownload::from(id.clone());
+            match download_size(config, &download) {
+                Ok(size) => println!(
+                    "    {:>9.9} {} ({})",
+                    "download".cyan(),
+                    id,
+                    HumanBytes(size)
+                ),
+                Err(_) => println!("    {:>9.9} {} (unknown size)", "download".cyan(), id),
+            }
+        }
+        for (id, function) in plan.instructions() {
+            println!("    {:>9.9} {} of {}", "run".magenta(), function, id);
+        }
+        for path in plan.removed_files() {
+            println!("    {:>9.9} {}", "remove".red(), path.display());
+        }
+        for path in plan.created_files() {
+            println!("    {:>9.9} {}", "create".green(), path.display());
+        }
+        for id in plan.unknown_sizes() {
+            println!(
+                "    {:>9.9} {} (unknown until downloaded)",
+                "size".bold(),
+                id
+            );
+        }
+        if plan.unknown_sizes().is_empty() && (plan.installed_size() > 0 || plan.removed_size() > 0)
+        {
+            println!(
+                "    {:>9.9} {}",
+                "size".bold(),
+                format_size_change(plan.installed_size(), plan.removed_size())
+            );
+        }
+    }
+
+    let hooks = find_triggered_hooks(config, transactions, lock_file_ownership)?;
+    let hooks = hooks
+        .pre_transaction()
+        .iter()
+        .chain(hooks.post_transaction());
+
+    let steps = TransactionSteps::from(config, lock_file_ownership, transactions);
+
+    if output::json_output() {
+        let hooks: Vec<_> = hooks.map(|hook| hook.name()).collect();
+        let steps: Vec<_> = steps.steps().iter().map(step_json).collect();
+        output::print_json(&json!({ "plans": documents, "hooks": hooks, "steps": steps }));
+        return Ok(());
+    }
+
+    for hook in hooks {
+        println!("\n{:>9.9} {}", "hook".magenta().bold(), hook.name().bold());
+    }
+
+    println!("\nSteps:");
+    for (i, step) in steps.steps().iter().enumerate() {
+        println!("{:>4}. {}", i + 1, format_step(step));
+    }
+
+    println!("\nDry run, no transaction has been applied.");
+    Ok(())
+}
+
+/// Overrides the confirmation policy of the configuration according to the `--yes` and `--no-confirm` options
+pub fn set_confirmation_policy(config: &mut Config, matches: &ArgMatches) {
+    if matches.is_present("yes") {
+        *config.confirmation_mut().policy_mut() = ConfirmationPolicy::Yes;
+    } else if matches.is_present("no-confirm") {
+        *config.confirmation_mut().policy_mut() = ConfirmationPolicy::Default;
+    }
+}
+
+/// Sets how to behave when the lock file is held by another instance of Nest, according to the `--wait` and
+/// `--timeout` options, telling the user which process is waited for
+pub fn set_lock_wait(config: &mut Config, matches: &ArgMatches) -> Result<(), Error> {
+    if !matches.is_present("wait") {
+        return Ok(());
+    }
+
+    *config.lock_wait_mut() = match matches.value_of("timeout") {
+        Some(timeout) => {
+            let seconds = timeout
+                .parse::<u64>()
+                .with_context(|_| format_err!("invalid timeout '{}'", timeout))?;
+            LockWait::Timeout(Duration::from_secs(seconds))
+        }
+        None => LockWait::Forever,
+    };
+
+    if let Some(pid) = LockFileOwnership::holder(config.paths().lock_file()) {
+        writeln!(
+            output::messages(),
+            "Waiting for the lock file, held by process {}...",
+            pid.to_string().bold()
+        )?;
+    }
+    Ok(())
+}
+
+/// Overrides the maximum download rate of the configuration according to the `--limit-rate` option, which
+/// replaces those of the repositories too
+pub fn set_download_rate(config: &mut Config, matches: &ArgMatches) -> Result<(), Error> {
+    if let Some(rate) = matches.value_of("limit-rate") {
+        *config.network_mut().max_download_rate_mut() =
+            Some(parse_size(rate).ok_or_else(|| format_err!("invalid rate '{}'", rate))?);
+
+        for repository in config.repositories_config_mut().values_mut() {
+            *repository.network_mut().max_download_rate_mut() = None;
+        }
+    }
+    Ok(())
 }
 
-pub fn ask_confirmation(question: &str, default: bool) -> Result<bool, Error> {
+/// Asks the user the given yes/no question, unless the configuration's confirmation policy answers it.
+pub fn ask_confirmation(config: &Config, question: &str, default: bool) -> Result<bool, Error> {
     let hint = if default {
         format!("{}/{}", "Yes".green().bold(), "no".red().bold())
     } else {
         format!("{}/{}", "yes".green().bold(), "No".red().bold())
     };
 
-    print!("\n{} [{}] ", question.bold(), hint);
+    let mut prompt = output::prompt();
+
+    write!(prompt, "\n{} [{}] ", question.bold(), hint)?;
+
+    // The answer is still printed, so that the output tells what was done in the user's name
+    let answer = match config.confirmation().policy() {
+        ConfirmationPolicy::Ask => None,
+        ConfirmationPolicy::Yes => Some(true),
+        ConfirmationPolicy::Default => Some(default),
+    };
+    if let Some(answer) = answer {
+        writeln!(prompt, "{}", if answer { "yes" } else { "no" })?;
+        return Ok(answer);
+    }
+
     loop {
-        io::stdout().flush()?;
+        prompt.flush()?;
 
         let mut input = String::new();
         io::stdin().read_line(&mut input).context("stdin")?;
@@ -76,27 +625,610 @@ pub fn ask_confirmation(question: &str, default: bool) -> Result<bool, Error> {
             "" => return Ok(default),
             "y" | "yes" => return Ok(true),
             "n" | "no" => return Ok(false),
-            _ => print!("Please type \"yes\" or \"no\". [{}] ", hint),
+            _ => write!(prompt, "Please type \"yes\" or \"no\". [{}] ", hint)?,
         }
     }
 }
 
-pub fn process_transactions(
+/// Asks the user which of the given packages should provide the given virtual package
+pub fn select_provider(
+    config: &Config,
+    virtual_name: &PackageShortName,
+    providers: &[PackageFullName],
+) -> Result<PackageFullName, Error> {
+    let mut prompt = output::prompt();
+
+    writeln!(
+        prompt,
+        "\n{}",
+        format!("Several packages provide {}:", virtual_name).bold()
+    )?;
+    for (i, provider) in providers.iter().enumerate() {
+        writeln!(prompt, "    {:>2}) {}", i + 1, provider)?;
+    }
+
+    write!(prompt, "{} [1] ", "Which one would you like to use?".bold())?;
+
+    // Without asking the user, the first provider is used
+    if config.confirmation().policy() != ConfirmationPolicy::Ask {
+        writeln!(prompt, "1")?;
+        return Ok(providers[0].clone());
+    }
+
+    loop {
+        prompt.flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).context("stdin")?;
+
+        let choice = match input.trim() {
+            "" => Some(1),
+            input => input.parse::<usize>().ok(),
+        };
+        match choice {
+            Some(choice) if choice >= 1 && choice <= providers.len() => {
+                return Ok(providers[choice - 1].clone())
+            }
+            _ => write!(
+                prompt,
+                "Please type a number between 1 and {}. [1] ",
+                providers.len()
+            )?,
+        }
+    }
+}
+
+/// Selects the package providing the given virtual package among the given ones: the most preferred one according
+/// to the configuration, the only one, or the one chosen by the user.
+pub fn choose_provider(
+    config: &Config,
+    virtual_name: &PackageShortName,
+    providers: &[QueryResult],
+) -> Result<PackageFullName, Error> {
+    let preferred = config.providers().get(virtual_name).and_then(|preferred| {
+        preferred.iter().find_map(|preferred| {
+            providers
+                .iter()
+                .find(|provider| provider.manifest().short_name() == *preferred)
+        })
+    });
+
+    match (preferred, providers) {
+        (Some(provider), _) | (None, [provider]) => Ok(provider.full_name()),
+        _ => {
+            let names: Vec<_> = providers.iter().map(QueryResult::full_name).collect();
+            select_provider(config, virtual_name, &names)
+        }
+    }
+}
+
+/// Parses a package given on the command line, as a regular expression matched against the full names of the
+/// packages if `regex` is true
+pub fn parse_target(target: &str, regex: bool) -> Result<PackageQuery, Error> {
+    if regex {
+        PackageQuery::regex(target)
+    } else {
+        PackageQuery::parse(target)
+    }
+}
+
+/// Expands the given pattern to the installed packages it matches, sorted, listing them and asking the user to
+/// confirm the selection.
+///
+/// [`None`] is returned if the user declined.
+pub fn expand_installed_pattern(
+    config: &Config,
+    query: &PackageQuery,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<Option<Vec<PackageID>>, Error> {
+    let installed = query.installed(config, lock_file_ownership)?;
+    if installed.is_empty() {
+        return Err(format_err!(
+            "unable to find an installed package matching '{}'",
+            query.as_str()
+        ));
+    }
+
+    let mut prompt = output::prompt();
+
+    writeln!(
+        prompt,
+        "'{}' matches the following installed package{}:",
+        query.as_str(),
+        if installed.len() <= 1 { "" } else { "s" }
+    )?;
+    for id in &installed {
+        writeln!(prompt, "    {}", id.to_string().bold())?;
+    }
+
+    let question = format!(
+        "Would you like to select th{} package{}?",
+        if installed.len() <= 1 { "is" } else { "ese" },
+        if installed.len() <= 1 { "" } else { "s" },
+    );
+    if ask_confirmation(config, &question, true)? {
+        Ok(Some(installed))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Solves the given graph with the given function, asking the user to choose between the packages providing the
+/// virtual packages required, when several of them can.
+pub fn solve_interactively<'a, F>(
+    config: &Config,
+    graph: &mut DependencyGraph<'a>,
+    solve: F,
+) -> Result<(), Error>
+where
+    F: Fn(&mut DependencyGraph<'a>, &Config) -> Result<(), Error>,
+{
+    let mut config = config.clone();
+
+    loop {
+        let mut attempt = graph.clone();
+
+        let err = match solve(&mut attempt, &config) {
+            Ok(()) => {
+                *graph = attempt;
+                return Ok(());
+            }
+            Err(err) => err,
+        };
+
+        let ambiguity = err
+            .downcast_ref::<Context<DependencyGraphErrorKind>>()
+            .and_then(|context| match context.get_context() {
+                DependencyGraphErrorKind::AmbiguousProvider(virtual_name, providers) => {
+                    Some((virtual_name.clone(), providers.clone()))
+                }
+                _ => None,
+            });
+
+        match ambiguity {
+            Some((virtual_name, providers)) => {
+                let provider = select_provider(&config, &virtual_name, &providers)?;
+
+                config
+                    .providers_mut()
+                    .entry(virtual_name)
+                    .or_insert_with(Vec::new)
+                    .insert(0, provider.into());
+            }
+            None => return Err(err),
+        }
+    }
+}
+
+/// Refuses the given transactions if they would leave files owned by several packages, reporting each of them,
+/// as a JSON document with the JSON output
+fn check_file_collisions(
+    config: &Config,
+    transactions: &[Transaction],
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let collisions = find_file_collisions(config, lock_file_ownership, transactions)
+        .context("unable to check the files of the packages for collisions")?;
+
+    if collisions.is_empty() {
+        return Ok(());
+    }
+
+    if output::json_output() {
+        let collisions: Vec<_> = collisions
+            .iter()
+            .map(|collision| {
+                json!({
+                    "path": collision.path(),
+                    "package": collision.package().to_string(),
+                    "owner": collision.owner().to_string(),
+                })
+            })
+            .collect();
+        output::print_json(&json!({ "collisions": collisions }));
+    } else {
+        for collision in &collisions {
+            println!(
+                "{:>10} {} (from {}, already owned by {})",
+                "collision".red().bold(),
+                collision.path().display(),
+                collision.package(),
+                collision.owner(),
+            );
+        }
+    }
+    Err(format_err!(
+        "{} file{} would be owned by several packages, the transactions were aborted",
+        collisions.len(),
+        if collisions.len() <= 1 { "" } else { "s" }
+    ))
+}
+
+/// Loads the hooks declared in the configuration and returns those triggered by the given transactions, along
+/// with the built-in hooks keeping the changed kernels bootable
+fn find_triggered_hooks(
     config: &Config,
     transactions: &[Transaction],
     lock_file_ownership: &LockFileOwnership,
+) -> Result<TriggeredHooks, Error> {
+    let hooks = Hook::load_all(config.paths().hooks()).context("unable to load the hooks")?;
+    Ok(
+        TriggeredHooks::find(config, lock_file_ownership, hooks, transactions)
+            .context("unable to find the hooks triggered by the transactions")?,
+    )
+}
+
+fn run_hooks(config: &Config, hooks: &[Hook]) -> Result<(), Error> {
+    let mut messages = output::messages();
+
+    for hook in hooks {
+        writeln!(
+            messages,
+            "{} {}",
+            "Running hook".bold(),
+            hook.action().description().unwrap_or_else(|| hook.name())
+        )?;
+        hook.execute(config.paths().root())
+            .with_context(|_| format!("hook '{}' failed", hook.name()))?;
+    }
+    Ok(())
+}
+
+/// Saves the given dependency graph, along with the world set made of its explicit requirements
+pub fn save_dependency_graph(
+    config: &Config,
+    graph: &DependencyGraph,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    graph.save_to_cache(config.paths().depgraph(), lock_file_ownership)?;
+    World::from_graph(graph).save_to_cache(config.paths().world(), lock_file_ownership)
+}
+
+/// Applies the given transactions, step by step: downloads the missing packages, verifies all the packages
+/// to install, takes a snapshot of the system if configured to, records the transactions as pending, and only
+/// then processes them. Once they are all applied, the given dependency graph replaces the original one.
+pub fn apply_transactions(
+    config: &Config,
+    transactions: &[Transaction],
+    original_graph: &DependencyGraph,
+    graph: &DependencyGraph,
+    undoes: Option<usize>,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    check_pending_transactions(config, lock_file_ownership)?;
+
+    let transactions = prepare_transactions(config, transactions, lock_file_ownership)?;
+
+    // The snapshot is taken before the transactions are recorded as pending, so that restoring it leaves none
+    let snapshot = take_snapshot(config, lock_file_ownership)
+        .context("unable to take a snapshot of the system")?;
+    if let Some(name) = &snapshot {
+        writeln!(
+            output::messages(),
+            "Took the snapshot {} of the system.",
+            name.bold()
+        )?;
+    }
+
+    let mut pending = PendingTransactions::create(
+        config,
+        &transactions,
+        original_graph,
+        graph,
+        undoes,
+        lock_file_ownership,
+    )
+    .context("unable to record the pending transactions")?;
+    if let Some(name) = snapshot {
+        pending
+            .record_snapshot(name)
+            .context("unable to record the pending transactions")?;
+    }
+    process_transactions(config, pending, lock_file_ownership)
+}
+
+/// Fails if transactions were interrupted: they must be resumed or rolled back before new ones are computed, as
+/// the installed packages may not match the dependency graph anymore
+pub fn check_pending_transactions(
+    config: &Config,
+    lock_file_ownership: &LockFileOwnership,
 ) -> Result<(), Error> {
-    for transaction in transactions.iter() {
-        match transaction {
+    if PendingTransactions::load(config, lock_file_ownership)?.is_some() {
+        return Err(format_err!(
+            "interrupted transactions are pending, run `nest resume` or `nest resume --rollback` first"
+        ));
+    }
+    Ok(())
+}
+
+/// Adds the new and modified NPFs of the folder of the local repository to the caches, so that they can be
+/// installed like the packages of the other repositories
+pub fn index_local_repository(
+    config: &Config,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let indexed = config
+        .downloaded_packages_cache(lock_file_ownership)
+        .index_local_overlay(config.paths().local_repository())?;
+
+    let mut messages = output::messages();
+    for id in indexed {
+        writeln!(messages, "Indexed {} from the local repository", id)?;
+    }
+    Ok(())
+}
+
+/// Downloads the packages the given transactions are missing and verifies all the packages they install,
+/// before any change is made to the system. Returns the transactions to apply, in order.
+pub fn prepare_transactions(
+    config: &Config,
+    transactions: &[Transaction],
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<Vec<Transaction<'static, 'static>>, Error> {
+    let steps = TransactionSteps::from(config, lock_file_ownership, transactions);
+
+    if steps.downloads().next().is_some() {
+        writeln!(output::messages(), "Downloading packages...")?;
+        download_packages(config, steps.downloads().cloned())?;
+    }
+
+    for step in steps.steps() {
+        if let TransactionStep::Verify(id) = step {
+            verify_package(config, lock_file_ownership, id)
+                .with_context(|_| format!("unable to verify package '{}'", id))?;
+        }
+    }
+
+    Ok(steps.transactions())
+}
+
+/// Performs the remaining transactions of the given record, surrounded by the hooks they trigger, marking each
+/// of them as done as soon as it is applied.
+///
+/// Once they are all applied, they are recorded in the history, along with the dependency graph they were
+/// computed from and the number of the history entry they undo, if any, and the dependency graph they lead to
+/// is saved. If one of them fails, the record is kept so that the transactions can be resumed or rolled back.
+pub fn process_transactions(
+    config: &Config,
+    mut pending: PendingTransactions,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    if rollback_interrupted_transaction(config, lock_file_ownership)
+        .context("unable to roll back the interrupted transaction")?
+    {
+        writeln!(
+            output::messages(),
+            "An interrupted transaction has been rolled back."
+        )?;
+    }
+
+    let transactions = pending.remaining_transactions();
+    check_file_collisions(config, &transactions, lock_file_ownership)?;
+
+    let hooks = find_triggered_hooks(config, &transactions, lock_file_ownership)?;
+    run_hooks(config, hooks.pre_transaction())?;
+
+    let previous_size: u64 = transactions
+        .iter()
+        .filter_map(|transaction| match transaction {
+            Transaction::Remove(remove) => Some(remove.target()),
+            Transaction::Upgrade(upgrade) => Some(upgrade.old_target()),
+            Transaction::Reinstall(reinstall) => Some(reinstall.target()),
+            _ => None,
+        })
+        .filter_map(|id| installed_package_size(config, id, lock_file_ownership))
+        .sum();
+
+    let progress = PackageProgress::new(transactions.len());
+    let result: Result<(), Error> = try {
+        for transaction in transactions.iter() {
+            match transaction {
+                Transaction::Install(install) => {
+                    install_package(config, install, &lock_file_ownership, &progress)?
+                }
+                Transaction::Upgrade(upgrade) => {
+                    upgrade_package(config, upgrade, &lock_file_ownership, &progress)?
+                }
+                Transaction::Remove(remove) => {
+                    uninstall_package(config, remove, &lock_file_ownership, &progress)?
+                }
+                Transaction::Reinstall(reinstall) => {
+                    reinstall_package(config, reinstall, &lock_file_ownership, &progress)?
+                }
+                _ => unimplemented!(),
+            };
+            pending
+                .mark_done(transaction)
+                .context("unable to record the progress of the transactions")?;
+        }
+    };
+    progress.finish();
+
+    if result.is_err() {
+        writeln!(
+            output::messages(),
+            "The remaining transactions can be retried with `nest resume`, or the applied ones undone with `nest resume --rollback`."
+        )?;
+    }
+    result?;
+
+    let actions = pending.done_actions();
+    if !actions.is_empty() {
+        config
+            .history(lock_file_ownership)
+            .record(
+                actions,
+                &pending.previous_dependency_graph(lock_file_ownership)?,
+                pending.undoes(),
+                pending.snapshot().map(str::to_string),
+                lock_file_ownership,
+            )
+            .context("unable to record the transactions in the history")?;
+    }
+    save_dependency_graph(
+        config,
+        &pending.next_dependency_graph(lock_file_ownership)?,
+        lock_file_ownership,
+    )?;
+    pending
+        .clear()
+        .context("unable to clear the record of the pending transactions")?;
+
+    alternatives::update_alternatives(config, &transactions, lock_file_ownership)
+        .context("unable to update the alternatives")?;
+    run_hooks(config, hooks.post_transaction())?;
+    enforce_download_cache_limits(config, lock_file_ownership)
+        .context("unable to clean the download cache")?;
+    collect_store_garbage(config, lock_file_ownership)
+        .context("unable to clean the content store")?;
+    print_processed_summary(config, &transactions, previous_size, lock_file_ownership);
+    report_pending_merges(config, &transactions, lock_file_ownership)
+}
+
+/// Returns the size of the given installed package, as recorded when it was installed or, for packages
+/// installed before sizes were recorded, as currently on disk
+fn installed_package_size(
+    config: &Config,
+    id: &PackageID,
+    lock_file_ownership: &LockFileOwnership,
+) -> Option<u64> {
+    let log = config
+        .installed_packages_cache(lock_file_ownership)
+        .package_log(id)
+        .ok()?;
+    Some(
+        log.installed_size()
+            .unwrap_or_else(|| log.disk_usage(config.paths().root())),
+    )
+}
+
+/// Prints what the given transactions, which were just applied, did: the number of packages installed,
+/// upgraded and removed, and the change of the disk usage, given the size of the packages they replaced
+/// or removed
+fn print_processed_summary(
+    config: &Config,
+    transactions: &[Transaction],
+    previous_size: u64,
+    lock_file_ownership: &LockFileOwnership,
+) {
+    let mut installed = 0;
+    let mut upgraded = 0;
+    let mut removed = 0;
+    let mut reinstalled = 0;
+    let mut new_size = 0;
+
+    for transaction in transactions {
+        let target = match transaction {
             Transaction::Install(install) => {
-                install_package(config, install, &lock_file_ownership)?
+                installed += 1;
+                install.target()
             }
             Transaction::Upgrade(upgrade) => {
-                upgrade_package(config, upgrade, &lock_file_ownership)?
+                upgraded += 1;
+                upgrade.new_target()
+            }
+            Transaction::Reinstall(reinstall) => {
+                reinstalled += 1;
+                reinstall.target()
             }
-            Transaction::Remove(remove) => uninstall_package(config, remove, &lock_file_ownership)?,
-            _ => unimplemented!(),
+            Transaction::Remove(_) => {
+                removed += 1;
+                continue;
+            }
+            _ => continue,
         };
+        new_size += installed_package_size(config, target, lock_file_ownership).unwrap_or(0);
+    }
+
+    if output::json_output() {
+        output::print_json(&json!({
+            "summary": {
+                "installed": installed,
+                "upgraded": upgraded,
+                "removed": removed,
+                "reinstalled": reinstalled,
+                "disk_usage_change": new_size as i64 - previous_size as i64,
+            }
+        }));
+        return;
+    }
+
+    // The first count names what is counted: "2 packages upgraded, 1 removed"
+    let counts: Vec<_> = [
+        (installed, "installed"),
+        (upgraded, "upgraded"),
+        (reinstalled, "reinstalled"),
+        (removed, "removed"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count > 0)
+    .enumerate()
+    .map(|(i, (count, action))| match i {
+        0 if *count <= 1 => format!("{} package {}", count, action),
+        0 => format!("{} packages {}", count, action),
+        _ => format!("{} {}", count, action),
+    })
+    .collect();
+    if counts.is_empty() {
+        return;
+    }
+
+    println!(
+        "{}, disk usage {}.",
+        counts.join(", "),
+        format_size_change(new_size, previous_size)
+    );
+}
+
+/// Lists the configuration files kept by the given upgrades because they were modified locally, whose new
+/// version is waiting to be merged
+fn report_pending_merges(
+    config: &Config,
+    transactions: &[Transaction],
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let upgraded: Vec<_> = transactions
+        .iter()
+        .filter_map(|transaction| match transaction {
+            Transaction::Upgrade(upgrade) => Some(upgrade.new_target()),
+            _ => None,
+        })
+        .collect();
+    if upgraded.is_empty() || config.dry_run() {
+        return Ok(());
+    }
+
+    let merges: Vec<_> = config
+        .installed_packages_cache(lock_file_ownership)
+        .pending_merges(config.paths().root())?
+        .into_iter()
+        .filter(|merge| upgraded.contains(&merge.package()))
+        .collect();
+    if merges.is_empty() {
+        return Ok(());
+    }
+
+    let mut messages = output::messages();
+
+    writeln!(
+        messages,
+        "The following configuration file{} modified locally {} kept, along with {} new version:",
+        if merges.len() <= 1 { "" } else { "s" },
+        if merges.len() <= 1 { "was" } else { "were" },
+        if merges.len() <= 1 { "its" } else { "their" },
+    )?;
+    for merge in &merges {
+        writeln!(
+            messages,
+            "    {} (new version in {})",
+            merge.path().display().to_string().bold(),
+            merge.new_path().display()
+        )?;
     }
+    writeln!(
+        messages,
+        "Run `nest config-merge` to merge {}.",
+        if merges.len() <= 1 { "it" } else { "them" }
+    )?;
     Ok(())
 }
@@ -1,22 +1,138 @@
-use std::io::{Seek, SeekFrom, Write};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::iter::Iterator;
-use std::sync::mpsc::channel;
+use std::slice;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
 
-use curl::easy::Easy;
 use failure::{format_err, Error, ResultExt};
-use libnest::config::{Config, MirrorUrl};
-use libnest::transaction::PackageDownload;
-use threadpool::ThreadPool;
+use indicatif::MultiProgress;
+use libnest::config::{
+    Config, ConfigError, Credentials, MirrorUrl, NetworkConfig, RepositoryConfig, TlsConfig,
+};
+use libnest::package::{PackageID, RepositoryName, SegmentChecksums};
+use libnest::repository::Repository;
+use libnest::transaction::{
+    DownloadCancellation, DownloadScheduler, DownloadThrottle, FetchRequest, Fetcher, Fetchers,
+    PackageDownload, ProgressHandler, TransactionPhase,
+};
+use log::debug;
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+use super::super::errors::NestError;
+use super::fetch::{fetchers, TorrentFetcher};
+use super::progress::DownloadProgress;
+
+/// The number of connections used to download a package in segments, unless the configuration says otherwise
+const DEFAULT_SEGMENT_CONNECTIONS: usize = 4;
+
+#[derive(Clone)]
 pub struct Download<'a> {
     target_route: &'a str,
+    network: Cow<'a, NetworkConfig>,
+    fetchers: Fetchers,
+    credentials: Option<Credentials>,
+    tls: Option<&'a TlsConfig>,
+    cancellation: Option<&'a DownloadCancellation>,
+    throttle: Option<DownloadThrottle>,
+    progress: Option<(&'a dyn ProgressHandler, &'a PackageID)>,
 }
 
 impl<'a> Download<'a> {
-    /// Creates a download from a given route
-    pub fn from(target_route: &'a str) -> Self {
-        Download { target_route }
+    /// Creates a download from a given route, using the given network settings and the transports supported
+    /// by Nest.
+    ///
+    /// The download is limited to the maximum download rate of the settings, if any.
+    pub fn from(target_route: &'a str, network: &'a NetworkConfig) -> Self {
+        Download {
+            target_route,
+            network: Cow::Borrowed(network),
+            fetchers: fetchers(),
+            credentials: None,
+            tls: None,
+            cancellation: None,
+            throttle: network.max_download_rate().map(DownloadThrottle::from),
+            progress: None,
+        }
+    }
+
+    /// Connects to the mirrors using the credentials, the TLS settings and the network settings of the given
+    /// repository, the latter overriding the ones the download was created with.
+    ///
+    /// The download is limited to the maximum download rate of the resulting settings, if any.
+    pub fn with_repository(
+        mut self,
+        repository: &'a RepositoryConfig,
+    ) -> Result<Self, ConfigError> {
+        self.credentials = repository.load_credentials()?;
+        self.tls = Some(repository.tls());
+        self.network = Cow::Owned(self.network.overridden_by(repository.network()));
+        self.throttle = self.network.max_download_rate().map(DownloadThrottle::from);
+        Ok(self)
+    }
+
+    /// Makes the download fail as soon as possible once the given flag is raised
+    pub fn with_cancellation(mut self, cancellation: &'a DownloadCancellation) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Limits the download with the given throttle, shared with other downloads so that their combined rate
+    /// is limited
+    pub fn with_throttle(mut self, throttle: &DownloadThrottle) -> Self {
+        self.throttle = Some(throttle.clone());
+        self
+    }
+
+    /// Reports the progress of the download to the given [`ProgressHandler`], as the download of the given package
+    pub fn with_progress(
+        mut self,
+        progress: &'a dyn ProgressHandler,
+        package: &'a PackageID,
+    ) -> Self {
+        self.progress = Some((progress, package));
+        self
+    }
+
+    /// Returns whether the download is performed in segments when their checksums are known, as its network
+    /// settings allow more than one connection
+    fn uses_segments(&self) -> bool {
+        self.network
+            .segment_connections()
+            .unwrap_or(DEFAULT_SEGMENT_CONNECTIONS)
+            > 1
+    }
+
+    /// Returns whether the download was asked to stop
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .map_or(false, |cancellation| cancellation.is_cancelled())
+    }
+
+    /// Returns the request to fetch the file to download from the given mirror, starting at the given offset
+    fn request(&self, mirror: &MirrorUrl, offset: u64) -> Result<FetchRequest<'_>, Error> {
+        let mut request =
+            FetchRequest::from(mirror.join(self.target_route)?, &self.network).with_offset(offset);
+
+        if let Some(credentials) = &self.credentials {
+            request = request.with_credentials(credentials);
+        }
+        if let Some(tls) = self.tls {
+            request = request.with_tls(tls);
+        }
+        if let Some(cancellation) = self.cancellation {
+            request = request.with_cancellation(cancellation);
+        }
+        if let Some(throttle) = &self.throttle {
+            request = request.with_throttle(throttle);
+        }
+        if let Some((progress, package)) = self.progress {
+            request = request.with_progress(progress, package);
+        }
+        Ok(request)
     }
 
     /// Performs the download, using any of the specified mirrors
@@ -28,37 +144,279 @@ impl<'a> Download<'a> {
     where
         W: Write + Seek,
     {
-        let mut curl = Easy::new();
-        curl.follow_location(true)?;
-        curl.fail_on_error(true)?;
-        curl.progress(true)?;
+        self.perform_from_offset(writer, mirrors, 0)
+    }
+
+    /// Resumes the download of a partially downloaded file, using any of the specified mirrors.
+    ///
+    /// The data already in the file is kept and only the remaining bytes are requested. If none
+    /// of the mirrors support range requests, the file is truncated and downloaded again from the start.
+    pub fn resume_with_mirrors(&self, file: &mut File, mirrors: &[MirrorUrl]) -> Result<(), Error> {
+        let offset = file.seek(SeekFrom::End(0))?;
+
+        if offset > 0 && self.perform_from_offset(file, mirrors, offset).is_ok() {
+            return Ok(());
+        }
+
+        // Keep the data already downloaded if the download was cancelled, to resume it later
+        if self.is_cancelled() {
+            return Err(format_err!("the download was cancelled"));
+        }
+        file.set_len(0)?;
+        self.perform_from_offset(file, mirrors, 0)
+    }
+
+    /// Downloads the file from the given mirror, starting at the given offset and writing it at the same position
+    fn fetch_from<W>(&self, writer: &mut W, mirror: &MirrorUrl, offset: u64) -> Result<(), Error>
+    where
+        W: Write + Seek,
+    {
+        writer.seek(SeekFrom::Start(offset))?;
+
+        let request = self.request(mirror, offset)?;
+        self.fetchers.fetch(&request, writer)
+    }
+
+    /// Downloads the file in segments with the given checksums, from several mirrors at once, writing each segment
+    /// to the given file once verified.
+    ///
+    /// The connections are spread over the mirrors, in order. A connection failing to download a segment is
+    /// closed, and the segment is downloaded by the other ones. The segments the file already holds are kept,
+    /// so that an interrupted download is resumed.
+    pub fn perform_segmented(
+        &self,
+        file: &mut File,
+        mirrors: &[MirrorUrl],
+        segments: &SegmentChecksums,
+    ) -> Result<(), Error> {
+        if !segments.is_consistent() {
+            return Err(format_err!(
+                "the checksums of the segments don't match the size of the file"
+            ));
+        }
+
+        let mut missing = VecDeque::new();
+        let mut received = 0;
+        for index in 0..segments.len() {
+            let (offset, length) = segments.segment(index).unwrap();
+            let mut data = vec![0; length as usize];
+
+            file.seek(SeekFrom::Start(offset))?;
+            if file.read_exact(&mut data).is_ok() && segments.verify(index, &data) {
+                received += length;
+            } else {
+                missing.push_back(index);
+            }
+        }
+        file.set_len(segments.size())?;
+
+        let connections = self
+            .network
+            .segment_connections()
+            .unwrap_or(DEFAULT_SEGMENT_CONNECTIONS)
+            .min(missing.len());
+        debug!(
+            "downloading {} segment(s) of {} with {} connection(s)",
+            missing.len(),
+            self.target_route,
+            connections
+        );
+
+        // The progress is reported for the whole file, not for each segment
+        let download = Download {
+            progress: None,
+            ..self.clone()
+        };
+        let report_progress = |received| {
+            if let Some((progress, package)) = self.progress {
+                progress.download_progress(package, received, Some(segments.size()));
+            }
+        };
+        report_progress(received);
+
+        let missing = Mutex::new(missing);
+        let state = Mutex::new((file, received, None));
+        thread::scope(|scope| {
+            for mirror in mirrors.iter().cycle().take(connections) {
+                let download = &download;
+                let missing = &missing;
+                let state = &state;
+                let report_progress = &report_progress;
+
+                scope.spawn(move || {
+                    while !download.is_cancelled() {
+                        let index = match missing.lock().unwrap().pop_front() {
+                            Some(index) => index,
+                            None => return,
+                        };
+                        let (offset, length) = segments.segment(index).unwrap();
+
+                        let mut data = Vec::with_capacity(length as usize);
+                        let res: Result<_, Error> = try {
+                            let request = download.request(mirror, offset)?.with_length(length);
+
+                            download.fetchers.fetch(&request, &mut data)?;
+                            if !segments.verify(index, &data) {
+                                Err(format_err!("the segment doesn't match its checksum"))?;
+                            }
+                        };
+                        if let Err(e) = res {
+                            debug!(
+                                "failed to download the segment {} of {} from {}: {}",
+                                index,
+                                download.target_route,
+                                mirror.as_str(),
+                                e
+                            );
+                            missing.lock().unwrap().push_back(index);
+                            return;
+                        }
+
+                        let mut state = state.lock().unwrap();
+                        let (file, received, error) = &mut *state;
+                        let res: Result<_, std::io::Error> = try {
+                            file.seek(SeekFrom::Start(offset))?;
+                            file.write_all(&data)?;
+                        };
+                        if let Err(e) = res {
+                            error.get_or_insert(e);
+                            return;
+                        }
+                        *received += length;
+                        report_progress(*received);
+                    }
+                });
+            }
+        });
+
+        let (file, mut received, error) = state.into_inner().unwrap();
+        if let Some(e) = error {
+            return Err(e.into());
+        }
+
+        // The segments given back by a failing connection once the others were done are downloaded from the first
+        // mirror sending them
+        for index in missing.into_inner().unwrap() {
+            if self.is_cancelled() {
+                return Err(format_err!("the download was cancelled"));
+            }
+            let (offset, length) = segments.segment(index).unwrap();
+            let data = mirrors
+                .iter()
+                .find_map(|mirror| {
+                    let mut data = Vec::with_capacity(length as usize);
+                    let request = download.request(mirror, offset).ok()?.with_length(length);
+
+                    download.fetchers.fetch(&request, &mut data).ok()?;
+                    if segments.verify(index, &data) {
+                        Some(data)
+                    } else {
+                        None
+                    }
+                })
+                .ok_or_else(|| NestError::Network(String::from("no working mirror found")))?;
+
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&data)?;
+            received += length;
+            report_progress(received);
+        }
+
+        if self.is_cancelled() {
+            Err(format_err!("the download was cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the size of the file to download, as announced by the first mirror answering
+    pub fn fetch_size(&self, mirrors: &[MirrorUrl]) -> Result<u64, Error> {
+        mirrors
+            .iter()
+            .find_map(|mirror| {
+                self.request(mirror, 0)
+                    .and_then(|request| self.fetchers.fetch_size(&request))
+                    .ok()
+            })
+            .ok_or_else(|| {
+                NestError::Network(String::from("no mirror announced the size of the file")).into()
+            })
+    }
+
+    /// Measures the throughput of the given mirror, in bytes per second, by performing the download from it
+    pub fn measure_throughput(&self, mirror: &MirrorUrl) -> Result<u64, Error> {
+        let mut data = Cursor::new(Vec::new());
+
+        let start = Instant::now();
+        self.perform_with_mirrors(&mut data, slice::from_ref(mirror))?;
+        let elapsed = start.elapsed().as_millis().max(1) as u64;
+
+        Ok(data.get_ref().len() as u64 * 1000 / elapsed)
+    }
+
+    /// Performs the download starting at the given offset, using any of the specified mirrors.
+    ///
+    /// When a mirror fails in the middle of the download, the next one resumes it from the data already
+    /// received. If it can't, because it doesn't support range requests, it downloads the file again from the
+    /// given offset.
+    fn perform_from_offset<W>(
+        &self,
+        writer: &mut W,
+        mirrors: &[MirrorUrl],
+        offset: u64,
+    ) -> Result<(), Error>
+    where
+        W: Write + Seek,
+    {
+        // The position up to which the data received from the previous mirrors was written
+        let mut received = offset;
 
         let succeeded = mirrors.iter().any(|mirror| {
-            let res: Result<_, Error> = try {
-                // Overwrite any data from a previous failed attempt
-                writer.seek(SeekFrom::Start(0))?;
+            if self.is_cancelled() {
+                return false;
+            }
 
-                let url = mirror.join(self.target_route)?;
-                curl.url(url.as_str())?;
+            let mut res = self.fetch_from(writer, mirror, received);
+            let position = |writer: &mut W| writer.stream_position().unwrap_or(offset);
 
-                let mut transfer = curl.transfer();
-                transfer.write_function(|data| Ok(writer.write(data).unwrap_or(0)))?;
-                transfer.perform()?;
-            };
+            // Mirrors that don't support range requests fail before receiving anything
+            if res.is_err()
+                && received > offset
+                && position(writer) == received
+                && !self.is_cancelled()
+            {
+                res = self.fetch_from(writer, mirror, offset);
+            }
+
+            if let Err(e) = &res {
+                debug!(
+                    "failed to download {} from {}: {}",
+                    self.target_route,
+                    mirror.as_str(),
+                    e
+                );
+                // The data received from the previous mirrors is kept even if this one started over
+                received = received.max(position(writer));
+            }
             res.is_ok()
         });
 
-        if !succeeded {
-            Err(format_err!("no working mirror found"))
+        if self.is_cancelled() {
+            Err(format_err!("the download was cancelled"))
+        } else if !succeeded {
+            Err(NestError::Network(String::from("no working mirror found")).into())
         } else {
             Ok(())
         }
     }
 }
 
-pub fn download_package(config: &Config, package_download: &PackageDownload) -> Result<(), Error> {
-    // Find the repository hosting the package
-    let repo = config
+/// Finds the configuration of the repository hosting the given package
+fn package_repository<'a>(
+    config: &'a Config,
+    package_download: &PackageDownload,
+) -> Result<Repository<'a, 'a>, Error> {
+    config
         .repositories()
         .into_iter()
         .find(|repository| repository.name() == **package_download.target().repository())
@@ -67,27 +425,176 @@ pub fn download_package(config: &Config, package_download: &PackageDownload) ->
                 "unable to find repository '{}'",
                 package_download.target().repository()
             )
-        })?;
+        })
+}
 
-    // Build the target route
-    let target_url = format!(
+/// Returns the route to download the given package from a mirror
+fn package_route(package_download: &PackageDownload) -> String {
+    format!(
         "api/p/{}/{}/{}/download",
         package_download.target().category(),
         package_download.target().name(),
         package_download.target().version(),
-    );
+    )
+}
 
-    // Download the package archive
-    let download = Download::from(&target_url);
-    download
-        .perform_with_mirrors(
-            &mut package_download.create_download_file(config)?,
-            &repo.config().mirrors(),
+/// Returns the route to download the delta between the base package and the target package from a mirror, if any
+fn delta_route(package_download: &PackageDownload) -> Option<String> {
+    package_download.delta_base().map(|base| {
+        format!(
+            "api/p/{}/{}/{}/delta/{}",
+            package_download.target().category(),
+            package_download.target().name(),
+            package_download.target().version(),
+            base.version(),
         )
-        .context(format_err!(
-            "unable to download package from repository '{}'",
-            repo.name()
-        ))?;
+    })
+}
+
+/// Downloads the target package as a delta against the NPF of its base package, and applies it
+fn download_delta(
+    config: &Config,
+    repo: &Repository,
+    package_download: &PackageDownload,
+    progress: &dyn ProgressHandler,
+    cancellation: &DownloadCancellation,
+    throttle: Option<&DownloadThrottle>,
+) -> Result<(), Error> {
+    let target_url = delta_route(package_download)
+        .ok_or_else(|| format_err!("the download has no delta base"))?;
+
+    let mut download = Download::from(&target_url, config.network())
+        .with_repository(repo.config())?
+        .with_cancellation(cancellation)
+        .with_progress(progress, package_download.target());
+    if let Some(throttle) = throttle {
+        download = download.with_throttle(throttle);
+    }
+    download.resume_with_mirrors(
+        &mut package_download.create_delta_file(config)?,
+        &config.ranked_mirrors(repo.config()),
+    )?;
+    package_download.apply_delta(config)
+}
+
+/// Downloads the target package with its torrent, using the BitTorrent client of the configuration.
+///
+/// Any data downloaded previously from the mirrors is discarded, and so is the data of a failed attempt.
+fn download_torrent(
+    config: &Config,
+    package_download: &PackageDownload,
+    progress: &dyn ProgressHandler,
+    cancellation: &DownloadCancellation,
+) -> Result<(), Error> {
+    let torrent = package_download
+        .torrent()
+        .ok_or_else(|| format_err!("the download has no torrent"))?;
+    let request = FetchRequest::from(torrent.clone(), config.network())
+        .with_cancellation(cancellation)
+        .with_progress(progress, package_download.target());
+
+    let mut file = package_download.open_partial_download_file(config)?;
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    if let Err(e) = TorrentFetcher::from(config.torrent().command(), config.paths().downloaded())
+        .fetch(&request, &mut file)
+    {
+        debug!(
+            "failed to download {} with its torrent: {}",
+            package_download.target(),
+            e
+        );
+        file.set_len(0)?;
+        return Err(e);
+    }
+    package_download.complete_download(config)
+}
+
+/// Returns the size of the given package, without downloading it
+pub fn download_size(config: &Config, package_download: &PackageDownload) -> Result<u64, Error> {
+    let repo = package_repository(config, package_download)?;
+    let target_url = package_route(package_download);
+
+    Download::from(&target_url, config.network())
+        .with_repository(repo.config())?
+        .fetch_size(&config.ranked_mirrors(repo.config()))
+}
+
+/// Downloads the given package, reporting its progress to the given [`ProgressHandler`] and stopping early
+/// if the given flag is raised. The download is limited by the given throttle instead of the maximum download
+/// rate of the configuration and of its repository, if any.
+pub fn download_package(
+    config: &Config,
+    package_download: &PackageDownload,
+    progress: &dyn ProgressHandler,
+    cancellation: &DownloadCancellation,
+    throttle: Option<&DownloadThrottle>,
+) -> Result<(), Error> {
+    let target = package_download.target();
+
+    progress.phase_started(target, TransactionPhase::Download);
+    let res = perform_package_download(config, package_download, progress, cancellation, throttle);
+    progress.phase_finished(target, TransactionPhase::Download);
+    res
+}
+
+/// Does the actual work of [`download_package()`]
+fn perform_package_download(
+    config: &Config,
+    package_download: &PackageDownload,
+    progress: &dyn ProgressHandler,
+    cancellation: &DownloadCancellation,
+    throttle: Option<&DownloadThrottle>,
+) -> Result<(), Error> {
+    let repo = package_repository(config, package_download)?;
+
+    // Prefer downloading a delta when upgrading, falling back to the full NPF if none is available
+    if package_download.can_use_delta(config)
+        && download_delta(
+            config,
+            &repo,
+            package_download,
+            progress,
+            cancellation,
+            throttle,
+        )
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    // Then prefer the swarm of the packages seeded by their repository, to spare the mirrors
+    if config.torrent().enabled()
+        && package_download.torrent().is_some()
+        && download_torrent(config, package_download, progress, cancellation).is_ok()
+    {
+        return Ok(());
+    }
+
+    let target_url = package_route(package_download);
+
+    // Download the package archive, resuming any previously interrupted download, in segments from several mirrors
+    // at once if its repository advertises their checksums
+    let mut download = Download::from(&target_url, config.network())
+        .with_repository(repo.config())?
+        .with_cancellation(cancellation)
+        .with_progress(progress, package_download.target());
+    if let Some(throttle) = throttle {
+        download = download.with_throttle(throttle);
+    }
+    let mut file = package_download.open_partial_download_file(config)?;
+    let mirrors = config.ranked_mirrors(repo.config());
+    match package_download.segments() {
+        Some(segments) if download.uses_segments() => {
+            download.perform_segmented(&mut file, &mirrors, segments)
+        }
+        _ => download.resume_with_mirrors(&mut file, &mirrors),
+    }
+    .context(format_err!(
+        "unable to download package from repository '{}'",
+        repo.name()
+    ))?;
+    package_download.complete_download(config)?;
 
     Ok(())
 }
@@ -96,25 +603,78 @@ pub fn download_packages(
     config: &Config,
     downloads: impl Iterator<Item = PackageDownload>,
 ) -> Result<(), Error> {
-    let pool = ThreadPool::new(num_cpus::get());
-    let (sender, receiver) = channel();
-    let mut n = 0;
-
-    for download in downloads {
-        let sender = sender.clone();
-        let config = config.clone();
-        pool.execute(move || {
-            let result = download_package(&config, &download);
-            sender
-                .send(result)
-                .expect("cannot communicate with main thread");
-        });
-        n += 1;
+    let downloads: Vec<_> = downloads.collect();
+    if downloads.is_empty() {
+        return Ok(());
     }
-    receiver
-        .into_iter()
-        .take(n)
-        .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(())
+    let max_parallel_downloads = config
+        .network()
+        .max_parallel_downloads()
+        .unwrap_or_else(num_cpus::get);
+
+    // The downloads share a single throttle, so that their combined rate is limited, except for those from the
+    // repositories with their own rate, which share the throttle of their repository
+    let throttle = config
+        .network()
+        .max_download_rate()
+        .map(DownloadThrottle::from);
+    let mut repository_throttles = HashMap::new();
+    let mut repository_limits = Vec::new();
+
+    for repository in config.repositories() {
+        let network = config
+            .network()
+            .overridden_by(repository.config().network());
+
+        if network.max_download_rate() != config.network().max_download_rate() {
+            repository_throttles.insert(
+                repository.name().to_string(),
+                network.max_download_rate().map(DownloadThrottle::from),
+            );
+        }
+        if let (Some(limit), Ok(name)) = (
+            repository.config().network().max_parallel_downloads(),
+            RepositoryName::parse(repository.name()),
+        ) {
+            repository_limits.push((name, limit));
+        }
+    }
+
+    // Create one progress bar per package, updated by the thread downloading it
+    let multi_progress = MultiProgress::new();
+    let progress = Arc::new(DownloadProgress::from(
+        &multi_progress,
+        downloads.iter().map(PackageDownload::target),
+    ));
+
+    let scheduler = repository_limits.into_iter().fold(
+        DownloadScheduler::from(downloads, max_parallel_downloads),
+        |scheduler, (name, limit)| scheduler.with_repository_limit(name, limit),
+    );
+    let config = config.clone();
+    let cancellation = scheduler.cancellation().clone();
+    let download_progress = progress.clone();
+    let handle = thread::spawn(move || {
+        let result = scheduler.perform(move |download| {
+            let throttle = repository_throttles
+                .get(download.target().repository().as_str())
+                .unwrap_or(&throttle);
+
+            download_package(
+                &config,
+                download,
+                download_progress.as_ref(),
+                &cancellation,
+                throttle.as_ref(),
+            )
+        });
+
+        // Cancelled downloads never finish their progress bar, which would block the main thread
+        progress.finish();
+        result
+    });
+
+    multi_progress.join_and_clear()?;
+    handle.join().expect("the download thread panicked")
 }
@@ -0,0 +1,156 @@
+use std::fs::{self, DirBuilder, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use failure::{format_err, Error, ResultExt};
+use libnest::transaction::{FetchRequest, Fetcher, Fetchers, HttpFetcher};
+
+/// Returns the transports supported by Nest: local mirrors, and HTTP(S) mirrors through the download engine of
+/// libnest
+pub fn fetchers() -> Fetchers {
+    Fetchers::new().with_fetcher(&["http", "https"], HttpFetcher::new())
+}
+
+/// The number of torrents downloaded so far by this process, to give each of them its own folder
+static TORRENT_DOWNLOADS: AtomicUsize = AtomicUsize::new(0);
+
+/// The prefix of the folders torrents are downloaded in, which can't be mistaken for a repository of the cache
+const TORRENT_DIR_PREFIX: &str = ".torrent-";
+
+/// A [`Fetcher`] downloading the file of a torrent, given by its magnet URI, with an external BitTorrent client
+#[derive(Clone, Debug)]
+pub struct TorrentFetcher {
+    command: String,
+    download_dir: PathBuf,
+}
+
+impl TorrentFetcher {
+    /// Creates a [`TorrentFetcher`] running the given command, where `{uri}` and `{dir}` are replaced by the magnet
+    /// URI of the torrent and by the folder to download it in, which is created in the given folder
+    pub fn from(command: &str, download_dir: &Path) -> Self {
+        TorrentFetcher {
+            command: command.to_string(),
+            download_dir: download_dir.to_path_buf(),
+        }
+    }
+
+    /// Creates the folder a torrent is downloaded in, only accessible to the current user.
+    ///
+    /// The creation fails if the path exists already, so that the folder can't be one prepared by someone else.
+    fn create_torrent_dir(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.download_dir)?;
+
+        let dir = self.download_dir.join(format!(
+            "{}{}-{}",
+            TORRENT_DIR_PREFIX,
+            process::id(),
+            TORRENT_DOWNLOADS.fetch_add(1, Ordering::SeqCst)
+        ));
+        DirBuilder::new().mode(0o700).create(&dir)?;
+        Ok(dir)
+    }
+
+    /// Returns the regular files in the given folder and in its subfolders
+    fn files_in(dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                files.extend(Self::files_in(&path)?);
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Runs the BitTorrent client to download the torrent of the given request in the given folder, killing it
+    /// if the request is cancelled
+    fn download(&self, request: &FetchRequest, dir: &Path) -> Result<(), Error> {
+        let args: Vec<_> = self
+            .command
+            .split_whitespace()
+            .map(|arg| {
+                arg.replace("{uri}", request.url().as_str())
+                    .replace("{dir}", &dir.display().to_string())
+            })
+            .collect();
+        let (program, args) = args
+            .split_first()
+            .ok_or_else(|| format_err!("the torrent command is empty"))?;
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|_| program.clone())?;
+
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if request.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format_err!("the download was cancelled"));
+            }
+            thread::sleep(Duration::from_millis(100));
+        };
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format_err!("{} failed ({})", program, status))
+        }
+    }
+}
+
+impl Fetcher for TorrentFetcher {
+    fn fetch_size(&self, request: &FetchRequest) -> Result<u64, Error> {
+        Err(format_err!(
+            "{}: the size of a torrent is only known once downloaded",
+            request.url()
+        ))
+    }
+
+    fn fetch(&self, request: &FetchRequest, writer: &mut dyn Write) -> Result<(), Error> {
+        if request.length().is_some() {
+            return Err(format_err!(
+                "{}: a torrent can only be downloaded as a whole",
+                request.url()
+            ));
+        }
+
+        let dir = self
+            .create_torrent_dir()
+            .with_context(|_| self.download_dir.display().to_string())?;
+
+        let res: Result<_, Error> = try {
+            self.download(request, &dir)?;
+
+            let files = Self::files_in(&dir)?;
+            if files.len() != 1 {
+                Err(format_err!(
+                    "the torrent {} doesn't hold a single file",
+                    request.url()
+                ))?;
+            }
+
+            let mut file = File::open(&files[0])?;
+            let len = file.metadata()?.len();
+            file.seek(SeekFrom::Start(request.offset()))?;
+            io::copy(&mut file, writer)?;
+            request.report_progress(len, Some(len));
+        };
+        let _ = fs::remove_dir_all(&dir);
+        res
+    }
+}
@@ -3,15 +3,19 @@ use libnest::config::Config;
 use libnest::lock_file::LockFileOwnership;
 use libnest::transaction::InstallTransaction;
 
+use super::progress::PackageProgress;
+
 pub fn install_package(
     config: &Config,
     trans: &InstallTransaction,
     ownership: &LockFileOwnership,
+    progress: &PackageProgress,
 ) -> Result<(), Error> {
+    progress.start_transaction(&format!("Installing {}", trans.target()));
     trans
-        .extract(&config, ownership)
+        .extract(&config, ownership, progress)
         .context(format_err!("unable to extract package"))?;
 
-    println!("Successfully installed {}", trans.target());
+    progress.println(&format!("Successfully installed {}", trans.target()));
     Ok(())
 }
@@ -1,4 +1,7 @@
 pub mod download;
+pub mod fetch;
 pub mod install;
+pub mod progress;
+pub mod reinstall;
 pub mod uninstall;
 pub mod upgrade;
@@ -0,0 +1,174 @@
+//! Rendering of the progress reported by libnest's transactions, with progress bars.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use libnest::package::PackageID;
+use libnest::transaction::{ProgressHandler, TransactionPhase};
+
+use super::super::output;
+
+/// The progress bar of a download, along with when it started and from which position, to compute its speed
+struct DownloadBar {
+    progress_bar: ProgressBar,
+    started: Mutex<Option<(Instant, u64)>>,
+}
+
+/// Renders the progress of parallel downloads, with one progress bar per package showing its speed and
+/// the estimated time left
+pub struct DownloadProgress {
+    bars: HashMap<PackageID, DownloadBar>,
+}
+
+impl DownloadProgress {
+    /// Creates a [`DownloadProgress`] with one progress bar per given package, all added to the given
+    /// [`MultiProgress`]
+    pub fn from<'a, I>(multi_progress: &MultiProgress, packages: I) -> Self
+    where
+        I: Iterator<Item = &'a PackageID>,
+    {
+        let bars = packages
+            .map(|package| {
+                let progress_bar = multi_progress.add(ProgressBar::new(0));
+                progress_bar.set_style(
+                    ProgressStyle::default_bar()
+                        .template("{prefix:40!} [{bar:30}] {bytes:>10}/{total_bytes:10} {msg:>12} {eta:>4}")
+                        .progress_chars("=> "),
+                );
+                progress_bar.set_prefix(&package.to_string());
+                let bar = DownloadBar {
+                    progress_bar,
+                    started: Mutex::new(None),
+                };
+                (package.clone(), bar)
+            })
+            .collect();
+        DownloadProgress { bars }
+    }
+
+    /// Finishes all the progress bars, including those of the downloads that never ended
+    pub fn finish(&self) {
+        for bar in self.bars.values() {
+            bar.progress_bar.finish();
+        }
+    }
+}
+
+impl ProgressHandler for DownloadProgress {
+    fn phase_finished(&self, package: &PackageID, phase: TransactionPhase) {
+        if let (TransactionPhase::Download, Some(bar)) = (phase, self.bars.get(package)) {
+            bar.progress_bar.finish();
+        }
+    }
+
+    fn download_progress(&self, package: &PackageID, downloaded: u64, total: Option<u64>) {
+        let bar = match self.bars.get(package) {
+            Some(bar) => bar,
+            None => return,
+        };
+
+        // The speed only accounts for the data received since the download started, not the resumed part
+        let (started, from) = *bar
+            .started
+            .lock()
+            .expect("the download progress is poisoned")
+            .get_or_insert((Instant::now(), downloaded));
+        let elapsed = started.elapsed().as_millis() as u64;
+        if elapsed > 0 {
+            let speed = downloaded.saturating_sub(from) * 1000 / elapsed;
+            bar.progress_bar
+                .set_message(&format!("{}/s", HumanBytes(speed)));
+        }
+
+        if let Some(total) = total {
+            bar.progress_bar.set_length(total);
+        }
+        bar.progress_bar.set_position(downloaded);
+    }
+}
+
+/// Renders the progress of the transactions applied one after the other: the phase the current one is in,
+/// and the progression of the extraction or the removal of the files of its package.
+///
+/// When there are multiple transactions, the progress bar is nested under a line showing which transaction is
+/// being applied.
+pub struct PackageProgress {
+    progress_bar: ProgressBar,
+    current: AtomicUsize,
+    total: usize,
+}
+
+impl PackageProgress {
+    /// Creates a [`PackageProgress`] for the given number of transactions
+    pub fn new(total: usize) -> Self {
+        let template = if total > 1 {
+            "{prefix}\n{msg:>20} [{bar:40}] {pos:>6}/{len:6} files"
+        } else {
+            "{msg:>20} [{bar:40}] {pos:>6}/{len:6} files"
+        };
+
+        let progress_bar = ProgressBar::new(0);
+        progress_bar.set_style(
+            ProgressStyle::default_bar()
+                .template(template)
+                .progress_chars("=> "),
+        );
+        PackageProgress {
+            progress_bar,
+            current: AtomicUsize::new(0),
+            total,
+        }
+    }
+
+    /// Starts the next transaction, described by the given message
+    pub fn start_transaction(&self, description: &str) {
+        let current = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+
+        self.progress_bar
+            .set_prefix(&format!("[{}/{}] {}", current, self.total, description));
+        self.progress_bar.set_message("");
+        self.progress_bar.set_length(0);
+        self.progress_bar.set_position(0);
+    }
+
+    /// Prints the given message above the progress bar
+    pub fn println(&self, message: &str) {
+        // Hidden progress bars, when the output isn't a terminal, drop the messages printed through them, and stdout
+        // only carries JSON documents with the JSON output
+        if self.progress_bar.is_hidden() {
+            let _ = writeln!(output::messages(), "{}", message);
+        } else {
+            self.progress_bar.println(message);
+        }
+    }
+
+    /// Removes the progress bar
+    pub fn finish(&self) {
+        self.progress_bar.finish_and_clear();
+    }
+}
+impl ProgressHandler for PackageProgress {
+    fn phase_started(&self, _package: &PackageID, phase: TransactionPhase) {
+        let message = match phase {
+            TransactionPhase::Download => "downloading",
+            TransactionPhase::Instructions => "running instructions",
+            TransactionPhase::Extraction => "extracting",
+            TransactionPhase::Removal => "removing",
+        };
+        self.progress_bar.set_message(message);
+    }
+
+    fn extraction_progress(&self, _package: &PackageID, extracted: usize, total: usize) {
+        self.progress_bar.set_length(total as u64);
+        self.progress_bar.set_position(extracted as u64);
+    }
+
+    fn removal_progress(&self, _package: &PackageID, removed: usize, total: usize) {
+        self.progress_bar.set_length(total as u64);
+        self.progress_bar.set_position(removed as u64);
+    }
+}
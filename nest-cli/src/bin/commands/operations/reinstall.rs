@@ -0,0 +1,21 @@
+use failure::{format_err, Error, ResultExt};
+use libnest::config::Config;
+use libnest::lock_file::LockFileOwnership;
+use libnest::transaction::ReinstallTransaction;
+
+use super::progress::PackageProgress;
+
+pub fn reinstall_package(
+    config: &Config,
+    trans: &ReinstallTransaction,
+    ownership: &LockFileOwnership,
+    progress: &PackageProgress,
+) -> Result<(), Error> {
+    progress.start_transaction(&format!("Reinstalling {}", trans.target()));
+    trans
+        .perform(&config, ownership, progress)
+        .context(format_err!("unable to extract package"))?;
+
+    progress.println(&format!("Successfully reinstalled {}", trans.target()));
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! Machine-readable output of the commands, enabled with `--json`.
+//!
+//! When enabled, the commands supporting it print their results as JSON documents on stdout, one per line,
+//! and errors are printed the same way instead of on stderr.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use failure::Error;
+use serde_json::{json, Value};
+
+use super::errors::NestError;
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the JSON output
+pub fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns whether the results should be printed as JSON documents
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::SeqCst)
+}
+
+/// Prints the given JSON document on a single line
+pub fn print_json(value: &Value) {
+    println!("{}", value);
+}
+
+/// Prints the given error, its causes and its category as a JSON document
+pub fn print_json_error(error: &Error) {
+    let fail = error.as_fail();
+    let categorized = NestError::from(error);
+
+    print_json(&json!({
+        "error": fail.to_string(),
+        "causes": fail.iter_causes().map(ToString::to_string).collect::<Vec<_>>(),
+        "category": categorized.category(),
+        "exit_code": categorized.exit_code(),
+    }));
+}
+
+/// Returns where the messages telling the user what is being done should be printed: stderr when the JSON output
+/// is enabled, so that stdout only carries JSON documents, stdout otherwise
+pub fn messages() -> Box<dyn Write> {
+    if json_output() {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+/// Returns where the questions asked to the user should be printed, like the other [`messages()`]
+pub fn prompt() -> Box<dyn Write> {
+    messages()
+}
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use libnest::config::Config;
+use libnest::package::PackageID;
+use serde_json::json;
+
+use super::output::{json_output, print_json};
+
+pub fn owns(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    let installed_packages = config.installed_packages_cache(&lock_file_ownership);
+    let available_packages = config.available_packages_cache(&lock_file_ownership);
+    let remote = matches.is_present("remote");
+
+    if remote {
+        let pulled = available_packages
+            .repositories()?
+            .iter()
+            .any(|repository| available_packages.has_file_lists(repository));
+        if !pulled {
+            return Err(format_err!(
+                "no file list was pulled, run `nest pull --files` first"
+            ));
+        }
+    }
+
+    let mut documents = Vec::new();
+    let mut unowned = Vec::new();
+
+    for pattern in matches.values_of("PATH").unwrap() {
+        let owned_files: Vec<(PathBuf, PackageID)> = if remote {
+            available_packages
+                .file_providers(pattern)?
+                .into_iter()
+                .map(|file| (file.path().to_path_buf(), file.package().clone()))
+                .collect()
+        } else {
+            installed_packages
+                .owners(pattern)?
+                .into_iter()
+                .map(|file| (file.path().to_path_buf(), file.package().clone()))
+                .collect()
+        };
+
+        if owned_files.is_empty() {
+            unowned.push(pattern);
+        }
+        for (path, package) in owned_files {
+            if json_output() {
+                documents.push(json!({
+                    "path": path,
+                    "package": package.to_string(),
+                }));
+            } else {
+                println!(
+                    "{} is {} by {}",
+                    path.display(),
+                    if remote { "provided" } else { "owned" },
+                    package.to_string().bold()
+                );
+            }
+        }
+    }
+
+    if json_output() {
+        print_json(&json!(documents));
+    }
+
+    let error = if remote {
+        "no available package provides"
+    } else {
+        "no installed package owns"
+    };
+    match unowned.as_slice() {
+        [] => Ok(()),
+        patterns => Err(format_err!("{} '{}'", error, patterns.join("', '"))),
+    }
+}
@@ -1,70 +1,419 @@
+use std::collections::VecDeque;
+use std::io::{Cursor, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use clap::ArgMatches;
+use colored::*;
 use failure::{format_err, Error, ResultExt};
 use indicatif::{ProgressBar, ProgressStyle};
-use libnest::config::Config;
+use libnest::cache::available::RepositoryDiff;
+use libnest::cache::keys::Keystore;
+use libnest::config::{Config, MirrorUrl, RepositoryConfig};
+use libnest::lock_file::LockFileOwnership;
 use libnest::transaction::{PullTransaction, Transaction};
+use serde_json::{json, Value};
 
+use super::errors::NestError;
+use super::key::trust_on_first_use;
 use super::operations::download::Download;
-use super::{ask_confirmation, print_transactions};
+use super::output::{self, json_output, print_json};
+use super::{ask_transactions_confirmation, print_transactions};
+
+/// Fetches the data at the given route of the mirrors of a repository
+fn fetch(
+    config: &Config,
+    repository: &RepositoryConfig,
+    route: &str,
+    mirrors: &[MirrorUrl],
+) -> Result<Vec<u8>, Error> {
+    let mut data = Cursor::new(Vec::new());
+
+    Download::from(route, config.network())
+        .with_repository(repository)?
+        .perform_with_mirrors(&mut data, mirrors)?;
+    Ok(data.into_inner())
+}
+
+/// Fetches the current serial number of the journal of a repository
+fn fetch_serial(
+    config: &Config,
+    repository: &RepositoryConfig,
+    mirrors: &[MirrorUrl],
+) -> Result<u64, Error> {
+    Ok(
+        String::from_utf8_lossy(&fetch(config, repository, "api/serial", mirrors)?)
+            .trim()
+            .parse()?,
+    )
+}
+
+/// Fetches and verifies the signed metadata of a repository, unless its packages are allowed to be unsigned
+pub(super) fn verify_metadata(
+    config: &Config,
+    pull: &mut PullTransaction,
+    mirrors: &[MirrorUrl],
+    keystore: &Keystore,
+    last_serial: Option<u64>,
+) -> Result<(), Error> {
+    let repo = *pull.target_repository();
+
+    if repo.config().allow_unsigned() {
+        return Ok(());
+    }
+
+    let timestamp = fetch(config, repo.config(), "api/timestamp", mirrors)?;
+    let snapshot = fetch(config, repo.config(), "api/snapshot", mirrors)?;
+    let keys = keystore.trusted_keys(repo.name(), repo.config());
+    pull.verify_metadata(&timestamp, &snapshot, &keys, last_serial)
+}
+
+/// Pulls only the changes made to a repository since the last pull, if the repository was pulled before
+/// and its mirrors still have the journal since then.
+///
+/// Returns false if a full pull is needed.
+fn pull_changes(
+    config: &Config,
+    pull: &mut PullTransaction,
+    mirrors: &[MirrorUrl],
+    last_serial: Option<u64>,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<bool, Error> {
+    let repo = *pull.target_repository();
+
+    if let Some(serial) = last_serial {
+        let res: Result<_, Error> = try {
+            Download::from(&format!("api/pull/since/{}", serial), config.network())
+                .with_repository(repo.config())?
+                .perform_with_mirrors(&mut pull.writer(), mirrors)?;
+            pull.save_changes_to_cache(config, lock_file_ownership)?;
+        };
+        return Ok(res.is_ok());
+    }
+    Ok(false)
+}
+
+/// Pulls the file lists of the packages of a repository, replacing those pulled previously
+fn pull_file_lists(
+    config: &Config,
+    pull: &mut PullTransaction,
+    mirrors: &[MirrorUrl],
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let repo = *pull.target_repository();
+
+    Download::from("api/files", config.network())
+        .with_repository(repo.config())?
+        .perform_with_mirrors(&mut pull.writer(), mirrors)?;
+    pull.save_file_lists_to_cache(config, lock_file_ownership)
+}
 
-pub fn pull(config: &Config) -> Result<(), Error> {
-    let transactions: Vec<_> = config
-        .repositories()
+/// The outcome of the pull of a repository
+struct RepositoryPull {
+    repository: String,
+    result: Result<RepositoryDiff, Error>,
+    file_lists_error: Option<Error>,
+}
+
+impl RepositoryPull {
+    /// Returns the outcome as a JSON document
+    fn json(&self) -> Value {
+        let mut document = match &self.result {
+            Ok(diff) => json!({
+                "repository": self.repository,
+                "status": if diff.is_empty() { "unchanged" } else { "updated" },
+                "new": diff.added(),
+                "updated": diff.updated(),
+                "removed": diff.removed(),
+            }),
+            Err(e) => json!({
+                "repository": self.repository,
+                "status": "failed",
+                "error": error_chain(e),
+            }),
+        };
+        if let Some(e) = &self.file_lists_error {
+            document["file_lists_error"] = json!(error_chain(e));
+        }
+        document
+    }
+
+    /// Prints the outcome on a single line
+    fn print(&self) {
+        match &self.result {
+            Ok(diff) if diff.is_empty() => println!("{}: up to date", self.repository),
+            Ok(diff) => println!(
+                "{}: {} new, {} updated, {} removed",
+                self.repository,
+                diff.added(),
+                diff.updated(),
+                diff.removed()
+            ),
+            Err(e) => println!("{}: {}", self.repository, error_chain(e).red()),
+        }
+        if let Some(e) = &self.file_lists_error {
+            println!(
+                "Unable to pull the file lists of repository '{}': {}",
+                self.repository,
+                error_chain(e)
+            );
+        }
+    }
+}
+
+/// Formats the given error along with its causes
+fn error_chain(error: &Error) -> String {
+    error
+        .iter_chain()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Pulls a repository, returning the number of packages added, updated and removed since the last pull
+fn pull_repository(
+    config: &Config,
+    pull: &mut PullTransaction,
+    keystore: &Keystore,
+    with_file_lists: bool,
+    file_lists_error: &mut Option<Error>,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<RepositoryDiff, Error> {
+    let repo = *pull.target_repository();
+    let mirrors = config.ranked_mirrors(repo.config());
+    let cache = config.available_packages_cache(lock_file_ownership);
+    let previous_index = cache.repository_index(repo.name())?;
+    let last_serial = previous_index.serial();
+
+    verify_metadata(config, pull, &mirrors, keystore, last_serial).context(format_err!(
+        "unable to verify the metadata of repository '{}'",
+        repo.name()
+    ))?;
+
+    // The file lists are optional, failing to pull them doesn't prevent using the repository
+    if with_file_lists {
+        if let Err(e) = pull_file_lists(config, pull, &mirrors, lock_file_ownership) {
+            *file_lists_error = Some(e);
+        }
+    }
+
+    // Nothing to pull if the signed metadata says the repository didn't change
+    if pull.snapshot().is_some() && pull.serial() == last_serial {
+        return Ok(RepositoryDiff::default());
+    }
+
+    // Fall back to pulling all the packages if the changes since the last pull can't be applied
+    if !pull_changes(config, pull, &mirrors, last_serial, lock_file_ownership)? {
+        // The serial is fetched first so that changes made during the pull are pulled again next time
+        if pull.snapshot().is_none() {
+            *pull.serial_mut() = fetch_serial(config, repo.config(), &mirrors).ok();
+        }
+
+        Download::from("api/pull", config.network())
+            .with_repository(repo.config())?
+            .perform_with_mirrors(&mut pull.writer(), &mirrors)
+            .context(format_err!("unable to pull repository '{}'", repo.name()))?;
+        pull.save_to_cache(config, lock_file_ownership)?;
+    }
+
+    cache.repository_index(repo.name())?.diff(&previous_index)
+}
+
+pub fn pull(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let with_file_lists = matches.is_present("files");
+
+    // All the repositories are pulled unless some are given
+    let mut repositories = config.repositories();
+    if let Some(names) = matches.values_of("REPOSITORY") {
+        let names: Vec<_> = names.collect();
+
+        for name in &names {
+            if !config.repositories_config().contains_key(*name) {
+                return Err(format_err!("unable to find repository '{}'", name));
+            }
+        }
+        repositories.retain(|repository| names.contains(&repository.name()));
+    }
+    repositories.sort_by(|a, b| a.name().cmp(b.name()));
+
+    let transactions: Vec<_> = repositories
         .into_iter()
         .map(|repository| Transaction::Pull(PullTransaction::from(repository)))
         .collect();
 
     if transactions.is_empty() {
-        println!("No repository to pull, quitting.");
+        writeln!(output::messages(), "No repository to pull, quitting.")?;
         return Ok(());
     }
 
     print_transactions(&transactions);
 
-    if !ask_confirmation(
-        format!(
-            "Would you like to apply th{} transaction{}?",
-            if transactions.len() <= 1 { "is" } else { "ese" },
-            if transactions.len() <= 1 { "" } else { "s" },
-        )
-        .as_str(),
-        true,
-    )? {
-        println!(
-            "Transaction{} cancelled.",
-            if transactions.len() <= 1 { "" } else { "s" }
-        );
+    if !ask_transactions_confirmation(config, &transactions)? {
         return Ok(());
     }
 
+    let jobs = match matches.value_of("jobs") {
+        Some(jobs) => jobs
+            .parse::<usize>()
+            .ok()
+            .filter(|jobs| *jobs > 0)
+            .ok_or_else(|| format_err!("invalid number of jobs '{}'", jobs))?,
+        None => config
+            .network()
+            .max_parallel_downloads()
+            .unwrap_or_else(num_cpus::get),
+    };
+
     let progress_bar = ProgressBar::new(transactions.len() as u64);
     progress_bar.set_style(ProgressStyle::default_bar().template("[{pos:>3}/{len:3}] {bar:80}"));
 
-    let mut transactions = transactions;
-    let download = Download::from("api/pull");
+    let names: VecDeque<_> = transactions
+        .iter()
+        .filter_map(|transaction| match transaction {
+            Transaction::Pull(pull) => Some(pull.target_repository().name().to_string()),
+            _ => None,
+        })
+        .collect();
+    let workers = jobs.min(names.len());
 
-    {
+    let mut pulls = {
         let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
 
-        for pull in transactions.iter_mut() {
-            if let Transaction::Pull(pull) = pull {
-                let repo = *pull.target_repository();
+        // Repositories pulled for the first time have no trusted key to verify their metadata and packages yet
+        let mut keystore = config.keystore()?;
+        let mut imported = false;
+
+        for transaction in &transactions {
+            if let Transaction::Pull(pull) = transaction {
+                match trust_on_first_use(config, pull.target_repository(), &mut keystore) {
+                    Ok(true) => imported = true,
+                    Ok(false) => (),
+                    Err(e) => progress_bar.println(format!(
+                        "Unable to fetch the key of repository '{}': {}",
+                        pull.target_repository().name(),
+                        e
+                    )),
+                }
+            }
+        }
+        if imported {
+            keystore.save_to_cache(config.paths().keys(), &lock_file_ownership)?;
+        }
+
+        // The repositories are pulled by a pool of threads, each one pulling the next repository of the queue
+        // until it is empty. Each repository has its own index file, so they can be saved concurrently.
+        let config = Arc::new(config.clone());
+        let keystore = Arc::new(keystore);
+        let lock_file_ownership = Arc::new(lock_file_ownership);
+        let queue = Arc::new(Mutex::new(names));
+        let (sender, receiver) = mpsc::channel();
 
-                progress_bar.println(format!("Pulling {}...", repo.name()).as_str());
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                let config = config.clone();
+                let keystore = keystore.clone();
+                let lock_file_ownership = lock_file_ownership.clone();
+                let queue = queue.clone();
+                let sender = sender.clone();
+                let progress_bar = progress_bar.clone();
 
-                download
-                    .perform_with_mirrors(&mut pull.writer(), repo.config().mirrors())
-                    .context(format_err!("unable to pull repository '{}'", repo.name()))?;
-                pull.save_to_cache(config, &lock_file_ownership)?;
+                thread::spawn(move || loop {
+                    let name = queue
+                        .lock()
+                        .expect("the pull queue is poisoned")
+                        .pop_front();
+                    let name = match name {
+                        Some(name) => name,
+                        None => return,
+                    };
+                    let repository = config
+                        .repositories()
+                        .into_iter()
+                        .find(|repository| repository.name() == name)
+                        .expect("the pulled repository isn't configured");
 
-                progress_bar.inc(1);
-            }
+                    progress_bar.println(format!("Pulling {}...", name).as_str());
+
+                    let mut pull = PullTransaction::from(repository);
+                    let mut file_lists_error = None;
+                    let result = pull_repository(
+                        &config,
+                        &mut pull,
+                        &keystore,
+                        with_file_lists,
+                        &mut file_lists_error,
+                        &lock_file_ownership,
+                    );
+
+                    progress_bar.inc(1);
+                    let _ = sender.send(RepositoryPull {
+                        repository: name,
+                        result,
+                        file_lists_error,
+                    });
+                })
+            })
+            .collect();
+        drop(sender);
+
+        for handle in handles {
+            handle.join().expect("a pull thread panicked");
         }
-    }
+        receiver.into_iter().collect::<Vec<_>>()
+    };
     progress_bar.finish_and_clear();
-    println!(
-        "Successfully pulled {} repositor{}",
-        transactions.len(),
-        if transactions.len() <= 1 { "y" } else { "ies" }
-    );
+    pulls.sort_by(|a, b| a.repository.cmp(&b.repository));
+
+    let failed = pulls.iter().filter(|pull| pull.result.is_err()).count();
+
+    if json_output() {
+        print_json(&json!({
+            "repositories": pulls.iter().map(RepositoryPull::json).collect::<Vec<_>>(),
+            "pulled": pulls.len() - failed,
+            "failed": failed,
+        }));
+    } else {
+        for pull in &pulls {
+            pull.print();
+        }
+        if pulls.len() > failed {
+            println!(
+                "Successfully pulled {} repositor{}",
+                pulls.len() - failed,
+                if pulls.len() - failed <= 1 {
+                    "y"
+                } else {
+                    "ies"
+                }
+            );
+        }
+    }
+
+    if failed > 0 {
+        let message = format!(
+            "unable to pull {} repositor{}",
+            failed,
+            if failed <= 1 { "y" } else { "ies" }
+        );
+
+        // The failure keeps the category of the pulls' errors if they all share it, so scripts can still tell
+        // a network issue from a verification failure
+        let errors: Vec<_> = pulls
+            .iter()
+            .filter_map(|pull| pull.result.as_ref().err())
+            .map(NestError::from)
+            .collect();
+        let error = match errors.split_first() {
+            Some((first, others))
+                if others
+                    .iter()
+                    .all(|error| error.category() == first.category()) =>
+            {
+                first.with_message(message)
+            }
+            _ => NestError::Other(message),
+        };
+        return Err(error.into());
+    }
     Ok(())
 }
@@ -0,0 +1,49 @@
+use clap::ArgMatches;
+use failure::Error;
+use libnest::cache::depgraph::NodeKind;
+use libnest::config::Config;
+use libnest::transaction::{ReinstallTransaction, Transaction};
+
+use super::depends::find_installed_package;
+use super::{
+    apply_transactions, check_pending_transactions, confirm_transactions, print_plans,
+    print_transactions,
+};
+
+pub fn reinstall(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    check_pending_transactions(config, &lock_file_ownership)?;
+    let graph = config.dependency_graph(&lock_file_ownership)?;
+
+    let mut transactions = Vec::new();
+    for target in &matches.values_of_lossy("PACKAGE").unwrap() {
+        let node_id = find_installed_package(&graph, target)?;
+
+        if let NodeKind::Package { id, .. } = graph.nodes()[&node_id].kind() {
+            let transaction = Transaction::Reinstall(ReinstallTransaction::from(id.clone()));
+            if !transactions.contains(&transaction) {
+                transactions.push(transaction);
+            }
+        }
+    }
+
+    print_transactions(&transactions);
+
+    if config.dry_run() {
+        return print_plans(config, &transactions, &lock_file_ownership);
+    }
+
+    if !confirm_transactions(config, &transactions, &lock_file_ownership)? {
+        return Ok(());
+    }
+
+    // The cached package files are reused, only the missing ones are downloaded again
+    apply_transactions(
+        config,
+        &transactions,
+        &graph,
+        &graph,
+        None,
+        &lock_file_ownership,
+    )
+}
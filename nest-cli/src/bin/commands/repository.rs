@@ -0,0 +1,204 @@
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error, ResultExt};
+use libnest::config::{parse_mirror_url, Config, ConfigFile, MirrorUrl};
+use libnest::package::RepositoryName;
+
+use super::config_files::ConfigFiles;
+
+/// Parses the name of a repository given on the command line
+fn parse_name(matches: &ArgMatches) -> Result<RepositoryName, Error> {
+    Ok(RepositoryName::parse(matches.value_of("NAME").unwrap())?)
+}
+
+/// Parses the URL of a mirror given on the command line
+fn parse_mirror(repr: &str) -> Result<MirrorUrl, Error> {
+    Ok(parse_mirror_url(repr).with_context(|_| format!("invalid mirror URL '{}'", repr))?)
+}
+
+/// Checks that the given repository is configured, returning its mirrors
+fn configured_mirrors(config: &Config, name: &RepositoryName) -> Result<Vec<MirrorUrl>, Error> {
+    config
+        .repositories_config()
+        .get(name.as_str())
+        .map(|repository| repository.mirrors().clone())
+        .ok_or_else(|| format_err!("unable to find repository '{}'", name))
+}
+
+pub fn repository_list(config: &Config) -> Result<(), Error> {
+    let mut repositories: Vec<_> = config.repositories_config().iter().collect();
+    repositories.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (name, repository) in repositories {
+        let state = if repository.disabled() {
+            "disabled".red()
+        } else {
+            "enabled".green()
+        };
+
+        println!(
+            "{} ({}, priority {})",
+            name.bold(),
+            state,
+            repository.priority()
+        );
+        for mirror in repository.mirrors() {
+            println!("    {}", mirror.as_str());
+        }
+    }
+    Ok(())
+}
+
+pub fn repository_add(
+    config: &Config,
+    mut files: ConfigFiles,
+    matches: &ArgMatches,
+) -> Result<(), Error> {
+    let name = parse_name(matches)?;
+    let mirrors = matches
+        .values_of("MIRROR")
+        .unwrap()
+        .map(parse_mirror)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if config.repositories_config().contains_key(name.as_str()) {
+        return Err(format_err!("the repository '{}' already exists", name));
+    }
+
+    if matches.is_present("fragment") {
+        let fragments_dir = files.fragments_dir().ok_or_else(|| {
+            format_err!("fragments are only used with the default configuration file")
+        })?;
+        let mut fragment = ConfigFile::load(fragments_dir.join(format!("{}.toml", name)))?;
+
+        fragment.add_repository(name.as_str(), &mirrors);
+        files.files_mut().push(fragment);
+    } else {
+        files.files_mut()[0].add_repository(name.as_str(), &mirrors);
+    }
+
+    if files.save(config)? {
+        println!("The repository {} was added.", name.as_str().bold());
+    }
+    Ok(())
+}
+
+pub fn repository_remove(
+    config: &Config,
+    mut files: ConfigFiles,
+    matches: &ArgMatches,
+) -> Result<(), Error> {
+    let name = parse_name(matches)?;
+
+    let mut removed = false;
+    for file in files.files_mut() {
+        removed |= file.remove_repository(name.as_str());
+    }
+    if !removed {
+        return Err(format_err!("unable to find repository '{}'", name));
+    }
+
+    if files.save(config)? {
+        println!("The repository {} was removed.", name.as_str().bold());
+    }
+    Ok(())
+}
+
+/// Enables or disables the given repository
+fn set_disabled(
+    config: &Config,
+    mut files: ConfigFiles,
+    matches: &ArgMatches,
+    disabled: bool,
+) -> Result<(), Error> {
+    let name = parse_name(matches)?;
+    configured_mirrors(config, &name)?;
+
+    files
+        .defining_repository(name.as_str())
+        .ok_or_else(|| format_err!("unable to find repository '{}'", name))?
+        .set_disabled(name.as_str(), disabled);
+
+    if files.save(config)? {
+        println!(
+            "The repository {} is {}.",
+            name.as_str().bold(),
+            if disabled { "disabled" } else { "enabled" }
+        );
+    }
+    Ok(())
+}
+
+pub fn repository_enable(
+    config: &Config,
+    files: ConfigFiles,
+    matches: &ArgMatches,
+) -> Result<(), Error> {
+    set_disabled(config, files, matches, false)
+}
+
+pub fn repository_disable(
+    config: &Config,
+    files: ConfigFiles,
+    matches: &ArgMatches,
+) -> Result<(), Error> {
+    set_disabled(config, files, matches, true)
+}
+
+pub fn repository_add_mirror(
+    config: &Config,
+    mut files: ConfigFiles,
+    matches: &ArgMatches,
+) -> Result<(), Error> {
+    let name = parse_name(matches)?;
+    let mirror = parse_mirror(matches.value_of("MIRROR").unwrap())?;
+    let mut mirrors = configured_mirrors(config, &name)?;
+
+    if mirrors.contains(&mirror) {
+        return Err(format_err!(
+            "{} is already a mirror of '{}'",
+            mirror.as_str(),
+            name
+        ));
+    }
+    mirrors.push(mirror);
+
+    files
+        .defining_mirrors(name.as_str())
+        .ok_or_else(|| format_err!("unable to find repository '{}'", name))?
+        .set_mirrors(name.as_str(), &mirrors);
+
+    if files.save(config)? {
+        println!("The mirror was added to {}.", name.as_str().bold());
+    }
+    Ok(())
+}
+
+pub fn repository_remove_mirror(
+    config: &Config,
+    mut files: ConfigFiles,
+    matches: &ArgMatches,
+) -> Result<(), Error> {
+    let name = parse_name(matches)?;
+    let mirror = parse_mirror(matches.value_of("MIRROR").unwrap())?;
+    let mut mirrors = configured_mirrors(config, &name)?;
+
+    if !mirrors.contains(&mirror) {
+        return Err(format_err!(
+            "{} isn't a mirror of '{}'",
+            mirror.as_str(),
+            name
+        ));
+    }
+    mirrors.retain(|other| *other != mirror);
+
+    files
+        .defining_mirrors(name.as_str())
+        .ok_or_else(|| format_err!("unable to find repository '{}'", name))?
+        .set_mirrors(name.as_str(), &mirrors);
+
+    if files.save(config)? {
+        println!("The mirror was removed from {}.", name.as_str().bold());
+    }
+    Ok(())
+}
@@ -2,10 +2,11 @@ use std::str::FromStr;
 
 use clap::ArgMatches;
 use failure::{format_err, Error};
-use libnest::cache::available::AvailablePackagesCacheQueryStrategy;
+use libnest::cache::available::{AvailablePackagesCacheQueryStrategy, QueryResult};
 use libnest::cache::depgraph::{GroupName, RequirementKind, RequirementManagementMethod};
+use libnest::cache::query::PackageQuery;
 use libnest::config::Config;
-use libnest::package::{HardPackageRequirement, SoftPackageRequirement};
+use libnest::package::HardPackageRequirement;
 
 pub fn requirement_add(
     config: &Config,
@@ -26,31 +27,19 @@ pub fn requirement_add(
         .node_names()
         .get(&group.clone().into())
         .ok_or_else(|| format_err!("Unknown group"))?;
-    let packages_cache = config.available_packages_cache(&lock_file_ownership);
-
     for target in &matches.values_of_lossy("PACKAGE").unwrap() {
-        let requirement = SoftPackageRequirement::parse(&target)?;
-
-        let matched_packages = packages_cache
-            .query(&requirement)
-            .set_strategy(AvailablePackagesCacheQueryStrategy::BestMatch)
-            .perform()?;
-        if matched_packages.len() > 1 {
-            for pkg in matched_packages {
-                println!("{}", pkg.full_name());
-            }
-            return Err(format_err!("unable to select a best match"));
-        } else if matched_packages.is_empty() {
-            return Err(format_err!(
-                "no package found for requirement '{}'",
-                &target
-            ));
-        }
-        let matched_package = &matched_packages[0];
+        let query = PackageQuery::parse(&target)?;
+
+        let matched_packages = query.available(
+            config,
+            &lock_file_ownership,
+            AvailablePackagesCacheQueryStrategy::BestMatch,
+        )?;
+        let matched_package = query.select_one(matched_packages, QueryResult::id)?;
 
         let package_req = HardPackageRequirement::from(
             matched_package.full_name(),
-            requirement.version_requirement().clone(),
+            query.version_requirement().clone(),
         );
 
         println!("Adding requirement {} to group {}...", package_req, *group);
@@ -91,12 +80,15 @@ pub fn requirement_remove(
         .ok_or_else(|| format_err!("Unknown group"))?;
 
     {
-        let packages_cache = config.available_packages_cache(&lock_file_ownership);
-
         for target in &matches.values_of_lossy("PACKAGE").unwrap() {
-            let requirement = SoftPackageRequirement::parse(&target)?;
-
-            let matches = packages_cache.query(&requirement).perform()?;
+            let query = PackageQuery::parse(&target)?;
+
+            // The package may come from any of the repositories offering it
+            let matches = query.available(
+                config,
+                &lock_file_ownership,
+                AvailablePackagesCacheQueryStrategy::AllMatchesUnsorted,
+            )?;
 
             let group_node = graph.nodes().get(&group_id).unwrap().clone();
 
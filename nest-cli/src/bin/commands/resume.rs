@@ -0,0 +1,138 @@
+use std::io::Write;
+
+use clap::ArgMatches;
+use failure::{Error, ResultExt};
+use libnest::config::Config;
+use libnest::lock_file::LockFileOwnership;
+use libnest::transaction::{rollback_interrupted_transaction, PendingTransactions};
+
+use super::output;
+use super::{
+    apply_transactions, confirm_transactions, prepare_transactions, print_plans,
+    print_transactions, process_transactions,
+};
+
+pub fn resume(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+
+    let pending = match PendingTransactions::load(config, &lock_file_ownership)? {
+        Some(pending) => pending,
+        None => {
+            writeln!(
+                output::messages(),
+                "No interrupted transaction to resume, quitting."
+            )?;
+            return Ok(());
+        }
+    };
+
+    // The package that was being processed when Nest was interrupted is brought back to its previous state first
+    if !config.dry_run()
+        && rollback_interrupted_transaction(config, &lock_file_ownership)
+            .context("unable to roll back the interrupted transaction")?
+    {
+        writeln!(
+            output::messages(),
+            "An interrupted transaction has been rolled back."
+        )?;
+    }
+
+    if matches.is_present("rollback") {
+        return rollback(config, pending, &lock_file_ownership);
+    }
+
+    let transactions = pending.remaining_transactions();
+    if !transactions.is_empty() {
+        writeln!(
+            output::messages(),
+            "Resuming the interrupted transactions.\n"
+        )?;
+        print_transactions(&transactions);
+
+        if config.dry_run() {
+            return print_plans(config, &transactions, &lock_file_ownership);
+        }
+
+        if !confirm_transactions(config, &transactions, &lock_file_ownership)? {
+            return Ok(());
+        }
+
+        prepare_transactions(config, &transactions, &lock_file_ownership)?;
+    }
+
+    process_transactions(config, pending, &lock_file_ownership)
+}
+
+/// Undoes the interrupted transactions that were applied, and discards the remaining ones
+fn rollback(
+    config: &Config,
+    pending: PendingTransactions,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let transactions = pending.rollback_transactions();
+
+    if !transactions.is_empty() {
+        writeln!(
+            output::messages(),
+            "Rolling back the interrupted transactions.\n"
+        )?;
+        print_transactions(&transactions);
+
+        if config.dry_run() {
+            return print_plans(config, &transactions, lock_file_ownership);
+        }
+
+        if !confirm_transactions(config, &transactions, lock_file_ownership)? {
+            return Ok(());
+        }
+    } else if config.dry_run() {
+        writeln!(
+            output::messages(),
+            "No interrupted transaction was applied, the remaining ones would be discarded."
+        )?;
+        return Ok(());
+    }
+
+    // The dependency graph isn't saved until all the transactions are applied, so it's still the original one
+    let graph = config.dependency_graph(lock_file_ownership)?;
+
+    // The applied transactions are recorded on their own, so that the rollback undoes them. They are not marked
+    // as undoing anything, even if they were, so that the entry they would have undone can still be undone.
+    let actions = pending.done_actions();
+    let undoes = if actions.is_empty() {
+        None
+    } else {
+        let entry = config
+            .history(lock_file_ownership)
+            .record(
+                actions,
+                &graph,
+                None,
+                pending.snapshot().map(str::to_string),
+                lock_file_ownership,
+            )
+            .context("unable to record the transactions in the history")?;
+        Some(entry.id())
+    };
+
+    pending
+        .clear()
+        .context("unable to clear the record of the pending transactions")?;
+
+    if transactions.is_empty() {
+        writeln!(
+            output::messages(),
+            "No interrupted transaction was applied, the remaining ones were discarded."
+        )?;
+        return Ok(());
+    }
+
+    apply_transactions(
+        config,
+        &transactions,
+        &graph,
+        &graph,
+        undoes,
+        lock_file_ownership,
+    )
+}
@@ -0,0 +1,82 @@
+use std::io::Write;
+
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error, ResultExt};
+use libnest::config::Config;
+use libnest::transaction::restore_snapshot;
+
+use super::output;
+use super::{ask_confirmation, check_pending_transactions};
+
+pub fn rollback(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    check_pending_transactions(config, &lock_file_ownership)?;
+    let entries = config.history(&lock_file_ownership).entries()?;
+
+    // The most recent entry with a snapshot is restored by default
+    let entry = match matches.value_of("ENTRY") {
+        Some(id) => {
+            let id = id
+                .parse::<usize>()
+                .with_context(|_| format!("invalid history entry '{}'", id))?;
+            entries
+                .into_iter()
+                .find(|entry| entry.id() == id)
+                .ok_or_else(|| format_err!("no history entry #{}", id))?
+        }
+        None => match entries
+            .into_iter()
+            .rev()
+            .find(|entry| entry.snapshot().is_some())
+        {
+            Some(entry) => entry,
+            None => {
+                writeln!(
+                    output::messages(),
+                    "No snapshot was taken before the recorded transactions, quitting."
+                )?;
+                return Ok(());
+            }
+        },
+    };
+    let snapshot = entry.snapshot().ok_or_else(|| {
+        format_err!(
+            "no snapshot was taken before the transactions of #{}",
+            entry.id()
+        )
+    })?;
+
+    writeln!(
+        output::messages(),
+        "Restoring the snapshot {} taken before the transactions of #{}, performed on {}.",
+        snapshot.bold(),
+        entry.id(),
+        entry.date().format("%Y-%m-%d %H:%M:%S UTC")
+    )?;
+    writeln!(
+        output::messages(),
+        "All the changes made to the system since then will be lost."
+    )?;
+
+    if config.dry_run() {
+        return Ok(());
+    }
+
+    if !ask_confirmation(config, "Would you like to restore this snapshot?", false)? {
+        writeln!(output::messages(), "Rollback cancelled.")?;
+        return Ok(());
+    }
+
+    if restore_snapshot(config, &lock_file_ownership, snapshot)
+        .context("unable to restore the snapshot")?
+    {
+        writeln!(
+            output::messages(),
+            "The snapshot will be booted into on the next restart."
+        )?;
+    } else {
+        writeln!(output::messages(), "The snapshot has been restored.")?;
+    }
+    Ok(())
+}
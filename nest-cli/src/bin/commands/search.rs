@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use clap::ArgMatches;
+use colored::*;
+use failure::Error;
+use libnest::cache::available::SearchMode;
+use libnest::config::Config;
+use libnest::package::PackageFullName;
+use serde_json::{json, Value};
+
+use super::output::{json_output, print_json};
+
+pub fn search(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    let pattern = matches.value_of("PATTERN").unwrap();
+
+    let mode = if matches.is_present("regex") {
+        SearchMode::Regex
+    } else if matches.is_present("fuzzy") {
+        SearchMode::Fuzzy
+    } else if matches.is_present("full-text") {
+        SearchMode::FullText
+    } else {
+        SearchMode::Substring
+    };
+
+    let results = config
+        .available_packages_cache(&lock_file_ownership)
+        .search(pattern)
+        .set_mode(mode)
+        .perform()?;
+
+    let installed: HashMap<PackageFullName, _> = config
+        .installed_packages_cache(&lock_file_ownership)
+        .packages()?
+        .into_iter()
+        .map(|id| (id.clone().into(), id.version().clone()))
+        .collect();
+
+    let mut found = false;
+    let mut documents = Vec::new();
+    for result in results {
+        let package = result.package();
+        let installed_version = installed.get(&package.full_name());
+
+        if matches.is_present("installed-only") && installed_version.is_none() {
+            continue;
+        }
+        found = true;
+
+        let latest_version = package
+            .versions()
+            .keys()
+            .max()
+            .map_or_else(String::new, |version| version.to_string());
+
+        if json_output() {
+            documents.push(json!({
+                "repository": package.repository().to_string(),
+                "category": package.category().to_string(),
+                "name": package.name().to_string(),
+                "version": latest_version,
+                "installed": installed_version.map(ToString::to_string),
+                "description": package.metadata().description(),
+            }));
+            continue;
+        }
+
+        let status = match installed_version {
+            Some(version) => format!(" [installed: {}]", version).cyan(),
+            None => "".normal(),
+        };
+        println!(
+            "{}::{}/{} {}{}",
+            package.repository(),
+            package.category(),
+            package.name().as_str().bold(),
+            latest_version.green(),
+            status
+        );
+        println!("    {}", package.metadata().description());
+    }
+
+    if json_output() {
+        print_json(&Value::Array(documents));
+    } else if !found {
+        println!("No package found.");
+    }
+    Ok(())
+}
@@ -0,0 +1,1527 @@
+//! Declarative specification of the command line interfaces of `nest` and `finest`.
+//!
+//! Both the argument parsers and the manual pages are generated from these specifications, so that the
+//! subcommands, their options and the exit codes are described in a single place.
+
+use clap::{crate_authors, crate_version, App, AppSettings, Arg, SubCommand};
+
+/// The exit code of a successful command
+pub const EXIT_SUCCESS: i32 = 0;
+
+/// The exit code of a failed command whose failure doesn't fall in any of the categories below
+pub const EXIT_FAILURE: i32 = 1;
+
+/// The exit code of a command that failed to reach the mirrors of a repository
+pub const EXIT_NETWORK: i32 = 2;
+
+/// The exit code of a command that failed to find packages fulfilling the requirements
+pub const EXIT_RESOLUTION: i32 = 3;
+
+/// The exit code of a command that failed to verify the signature or checksum of a package or of some metadata
+pub const EXIT_VERIFICATION: i32 = 4;
+
+/// The exit code of a command that failed to acquire the lock file
+pub const EXIT_LOCK: i32 = 5;
+
+/// The exit code of a command that failed to read or write a file
+pub const EXIT_IO: i32 = 6;
+
+/// The exit codes of the commands, along with their meaning
+pub const EXIT_CODES: &[(i32, &str)] = &[
+    (EXIT_SUCCESS, "The command succeeded."),
+    (
+        EXIT_FAILURE,
+        "The arguments are invalid, or the command failed for another reason than those below. The error is printed on the standard error output, or as a JSON document on the standard output with --json.",
+    ),
+    (
+        EXIT_NETWORK,
+        "No mirror of a repository could be reached, or a download failed.",
+    ),
+    (
+        EXIT_RESOLUTION,
+        "No set of packages fulfills the requirements, because a package is missing or because of conflicting requirements.",
+    ),
+    (
+        EXIT_VERIFICATION,
+        "The signature or the checksum of a package, or of the metadata of a repository, could not be verified.",
+    ),
+    (
+        EXIT_LOCK,
+        "The lock file is held by another instance of Nest, and it wasn't released in time.",
+    ),
+    (
+        EXIT_IO,
+        "A file could not be read or written, on the system or in the cache.",
+    ),
+];
+
+const TEMPLATE: &str = "{usage}\n\n{about}\n\nOPTIONS\n{flags}\n\nSUBCOMMANDS\n{subcommands}";
+
+/// The specification of an argument, either a flag, an option taking a value or a positional argument
+#[derive(Copy, Clone, Debug)]
+pub struct ArgSpec {
+    /// The name of the argument, used to retrieve its value
+    pub name: &'static str,
+    /// The short flag of the argument, if any
+    pub short: Option<&'static str>,
+    /// The long flag of the argument, if any. Arguments with neither a short nor a long flag are positional.
+    pub long: Option<&'static str>,
+    /// The name of the value of the argument shown in the help, if it takes one
+    pub value_name: Option<&'static str>,
+    /// The description of the argument
+    pub help: &'static str,
+    /// Whether the argument takes a value
+    pub takes_value: bool,
+    /// Whether the argument may be given multiple times, or take multiple values
+    pub multiple: bool,
+    /// Whether the argument is required
+    pub required: bool,
+    /// The arguments making this one optional when one of them is given, if it is required
+    pub required_unless: &'static [&'static str],
+    /// Whether the argument may be given after any subcommand
+    pub global: bool,
+    /// The default value of the argument, if any
+    pub default_value: Option<&'static str>,
+    /// The only values the argument accepts, if not empty
+    pub possible_values: &'static [&'static str],
+    /// The arguments this one can't be used with
+    pub conflicts_with: &'static [&'static str],
+    /// The arguments this one must be used with
+    pub requires: &'static [&'static str],
+    /// The number of values taken by each occurrence of the argument, if fixed
+    pub number_of_values: Option<u64>,
+}
+
+/// The specification of a command or of one of its subcommands
+#[derive(Copy, Clone, Debug)]
+pub struct CommandSpec {
+    /// The name of the command
+    pub name: &'static str,
+    /// The description of the command
+    pub about: &'static str,
+    /// The usage shown in the help, generated by default
+    pub usage: Option<&'static str>,
+    /// The other names of the command
+    pub aliases: &'static [&'static str],
+    /// The arguments of the command
+    pub args: &'static [ArgSpec],
+    /// The subcommands of the command, one of which is required if not empty
+    pub subcommands: &'static [CommandSpec],
+    /// Whether the command is left out of the help and of the manual page
+    pub hidden: bool,
+}
+
+const ARG: ArgSpec = ArgSpec {
+    name: "",
+    short: None,
+    long: None,
+    value_name: None,
+    help: "",
+    takes_value: false,
+    multiple: false,
+    required: false,
+    required_unless: &[],
+    global: false,
+    default_value: None,
+    possible_values: &[],
+    conflicts_with: &[],
+    requires: &[],
+    number_of_values: None,
+};
+
+const COMMAND: CommandSpec = CommandSpec {
+    name: "",
+    about: "",
+    usage: None,
+    aliases: &[],
+    args: &[],
+    subcommands: &[],
+    hidden: false,
+};
+
+const VERBOSE: ArgSpec = ArgSpec {
+    name: "v",
+    short: Some("v"),
+    multiple: true,
+    help: "Set the level of verbosity",
+    ..ARG
+};
+
+const QUIET: ArgSpec = ArgSpec {
+    name: "quiet",
+    short: Some("q"),
+    long: Some("quiet"),
+    help: "Only print the errors",
+    conflicts_with: &["v"],
+    ..ARG
+};
+
+const CONFIG: ArgSpec = ArgSpec {
+    name: "config",
+    short: Some("c"),
+    long: Some("config"),
+    help: "Use the given path as the only configuration file, without merging the fragments of /etc/nest/config.d/",
+    takes_value: true,
+    default_value: Some("/etc/nest/config.toml"),
+    ..ARG
+};
+
+const CHROOT: ArgSpec = ArgSpec {
+    name: "chroot",
+    long: Some("chroot"),
+    help: "Use the current configuration but operate on the given folder, as if it was the root folder",
+    takes_value: true,
+    ..ARG
+};
+
+const YES: ArgSpec = ArgSpec {
+    name: "yes",
+    short: Some("y"),
+    long: Some("yes"),
+    global: true,
+    help: "Answer \"yes\" to every question, without asking",
+    ..ARG
+};
+
+const NO_CONFIRM: ArgSpec = ArgSpec {
+    name: "no-confirm",
+    long: Some("no-confirm"),
+    global: true,
+    conflicts_with: &["yes"],
+    help: "Answer every question with its default answer, without asking",
+    ..ARG
+};
+
+const WAIT: ArgSpec = ArgSpec {
+    name: "wait",
+    long: Some("wait"),
+    help: "Wait for the other instance of Nest holding the lock file to release it, instead of failing",
+    ..ARG
+};
+
+const TIMEOUT: ArgSpec = ArgSpec {
+    name: "timeout",
+    long: Some("timeout"),
+    help: "Stop waiting for the lock file after the given number of seconds",
+    takes_value: true,
+    value_name: Some("SECONDS"),
+    requires: &["wait"],
+    ..ARG
+};
+
+const LIMIT_RATE: ArgSpec = ArgSpec {
+    name: "limit-rate",
+    long: Some("limit-rate"),
+    help: "Limit the download rate to the given number of bytes per second, which may be followed by a unit (e.g. 500K or 2M)",
+    takes_value: true,
+    value_name: Some("RATE"),
+    ..ARG
+};
+
+const PULL_JOBS: ArgSpec = ArgSpec {
+    name: "jobs",
+    short: Some("j"),
+    long: Some("jobs"),
+    help: "Number of repositories pulled at the same time, the maximum number of parallel downloads by default",
+    takes_value: true,
+    value_name: Some("N"),
+    ..ARG
+};
+
+const HELP: CommandSpec = CommandSpec {
+    name: "help",
+    about: "Prints this message or the help of the given subcommand(s)",
+    args: &[
+        ArgSpec {
+            name: "SUBCOMMAND",
+            help: "Subcommand to show the help of",
+            multiple: true,
+            ..ARG
+        },
+        ArgSpec {
+            name: "man",
+            long: Some("man"),
+            conflicts_with: &["SUBCOMMAND"],
+            help: "Print the manual page, in the roff format",
+            ..ARG
+        },
+    ],
+    ..COMMAND
+};
+
+const RESUME: CommandSpec = CommandSpec {
+    name: "resume",
+    about: "Resume the transactions that were interrupted, or roll them back",
+    args: &[ArgSpec {
+        name: "rollback",
+        long: Some("rollback"),
+        help: "Undo the interrupted transactions that were applied instead of performing the remaining ones",
+        ..ARG
+    }],
+    ..COMMAND
+};
+
+/// The specification of `nest`
+pub static NEST: CommandSpec = CommandSpec {
+    name: "nest",
+    about: "Raven-OS's package manager.",
+    usage: Some("nest [OPTION]... SUBCOMMAND [SUBCOMMAND OPTIONS]..."),
+    args: &[
+        VERBOSE,
+        QUIET,
+        CONFIG,
+        CHROOT,
+        ArgSpec {
+            name: "root",
+            long: Some("root"),
+            help: "Install packages in the given folder, using the configuration and the caches of the host",
+            takes_value: true,
+            conflicts_with: &["chroot"],
+            ..ARG
+        },
+        ArgSpec {
+            name: "sysroot",
+            long: Some("sysroot"),
+            help: "Operate on the sysroot of the given target triple instead of the system",
+            takes_value: true,
+            value_name: Some("TARGET"),
+            conflicts_with: &["root"],
+            ..ARG
+        },
+        ArgSpec {
+            name: "dry-run",
+            long: Some("dry-run"),
+            global: true,
+            help: "Show what would be done, without modifying the system",
+            ..ARG
+        },
+        ArgSpec {
+            name: "json",
+            long: Some("json"),
+            global: true,
+            help: "Print the results and the errors as JSON documents, one per line",
+            ..ARG
+        },
+        YES,
+        NO_CONFIRM,
+        WAIT,
+        TIMEOUT,
+        LIMIT_RATE,
+    ],
+    subcommands: &[
+        CommandSpec {
+            name: "pull",
+            about: "Pull repositories and update the local cache",
+            args: &[
+                ArgSpec {
+                    name: "REPOSITORY",
+                    help: "Repositories to pull (all of them by default)",
+                    multiple: true,
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "files",
+                    long: Some("files"),
+                    help: "Also pull the lists of the files shipped by the packages, used by `nest owns --remote`",
+                    ..ARG
+                },
+                PULL_JOBS,
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "install",
+            aliases: &["add"],
+            about: "Download and install the given packages [alias: add]",
+            args: &[
+                ArgSpec {
+                    name: "PACKAGE",
+                    help: "Packages or @groups to install",
+                    multiple: true,
+                    required: true,
+                    required_unless: &["from-file"],
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "from-file",
+                    long: Some("from-file"),
+                    value_name: Some("NPF"),
+                    help: "Install a locally built NPF, added to the caches as a package of the 'local' repository",
+                    takes_value: true,
+                    multiple: true,
+                    number_of_values: Some(1),
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "require-signature",
+                    long: Some("require-signature"),
+                    help: "Refuse the local NPFs that aren't signed by a key trusted by one of the repositories",
+                    requires: &["from-file"],
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "with-optional",
+                    long: Some("with-optional"),
+                    help: "Also install the optional dependencies of the given packages",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "reinstall",
+            about: "Extract the given installed packages again and run their instructions, restoring their files",
+            args: &[ArgSpec {
+                name: "PACKAGE",
+                help: "Installed packages to reinstall",
+                multiple: true,
+                required: true,
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "upgrade",
+            aliases: &["update"],
+            about: "Upgrade all installed packages, or the given ones [alias: update]",
+            args: &[
+                ArgSpec {
+                    name: "PACKAGE",
+                    help: "Installed packages to upgrade, which may be glob patterns, keeping the others at their current version",
+                    multiple: true,
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "regex",
+                    long: Some("regex"),
+                    short: Some("r"),
+                    requires: &["PACKAGE"],
+                    help: "Interpret the packages as regular expressions matched against their full name",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "uninstall",
+            aliases: &["remove"],
+            about: "Uninstall the given packages [alias: remove]",
+            args: &[
+                ArgSpec {
+                    name: "PACKAGE",
+                    help: "Packages or @groups to uninstall, which may be glob patterns",
+                    multiple: true,
+                    required: true,
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "regex",
+                    long: Some("regex"),
+                    short: Some("r"),
+                    help: "Interpret the packages as regular expressions matched against their full name",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "force",
+                    long: Some("force"),
+                    short: Some("f"),
+                    help: "Uninstall the packages even if other installed packages depend on them",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "autoremove",
+            about: "Uninstall the packages that are no longer required by any explicitly installed package",
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "history",
+            about: "List the transactions performed on the system",
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "undo",
+            about: "Revert the most recent transactions",
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "rollback",
+            about: "Bring the whole system back to a snapshot taken before some transactions",
+            args: &[
+                ArgSpec {
+                    name: "snapshot",
+                    long: Some("snapshot"),
+                    help: "Restore the filesystem snapshot taken before the transactions of the given history entry, or of the most recent one with a snapshot",
+                    required: true,
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "ENTRY",
+                    help: "The number of the history entry, as listed by `nest history`",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        RESUME,
+        CommandSpec {
+            name: "mirror",
+            about: "Operate on the mirrors of the repositories",
+            subcommands: &[CommandSpec {
+                name: "ping",
+                about: "Measure the speed of all mirrors, so that the fastest ones are used first",
+                ..COMMAND
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "cache",
+            about: "Manage Nest's caches",
+            subcommands: &[
+                CommandSpec {
+                    name: "stats",
+                    about: "Show the number of available and downloaded packages of each repository, and the size of the download cache",
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "verify",
+                    about: "Check the downloaded packages against the checksums advertised by their repository",
+                    args: &[ArgSpec {
+                        name: "purge",
+                        long: Some("purge"),
+                        help: "Remove the corrupted packages from the download cache",
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "clear",
+                    about: "Clear the caches of available and downloaded packages",
+                    args: &[
+                        ArgSpec {
+                            name: "REPOSITORY",
+                            help: "Only clear the caches of the given repository",
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "available",
+                            long: Some("available"),
+                            help: "Only clear the cache of available packages",
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "downloaded",
+                            long: Some("downloaded"),
+                            help: "Only clear the cache of downloaded packages",
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "clean",
+                    about: "Evict packages from the download cache to respect its limits",
+                    args: &[
+                        ArgSpec {
+                            name: "max-size",
+                            long: Some("max-size"),
+                            takes_value: true,
+                            help: "Maximum size of the download cache, overriding the configuration (e.g. 2G)",
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "keep-versions",
+                            long: Some("keep-versions"),
+                            takes_value: true,
+                            help: "Number of versions of each package to keep, overriding the configuration",
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "key",
+            about: "Manage the keys trusted to sign the packages of the repositories",
+            subcommands: &[
+                CommandSpec {
+                    name: "import",
+                    about: "Trust the given key to sign the packages of the given repository",
+                    args: &[
+                        ArgSpec {
+                            name: "REPOSITORY",
+                            help: "Repository the key signs the packages of",
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "KEY",
+                            help: "Base64-encoded Ed25519 public key",
+                            required: true,
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "list",
+                    about: "List the trusted and revoked keys of each repository",
+                    args: &[ArgSpec {
+                        name: "REPOSITORY",
+                        help: "Only list the keys of the given repository",
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "revoke",
+                    about: "Stop trusting the key with the given fingerprint",
+                    args: &[
+                        ArgSpec {
+                            name: "REPOSITORY",
+                            help: "Repository the key signs the packages of",
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "FINGERPRINT",
+                            help: "Fingerprint of the key, as shown by 'nest key list'",
+                            required: true,
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "config",
+            about: "Manage Nest's configuration files",
+            subcommands: &[
+                CommandSpec {
+                    name: "get",
+                    about: "Print the value of a setting, as the configuration files and the defaults define it",
+                    args: &[ArgSpec {
+                        name: "KEY",
+                        help: "Dotted key of the setting, like 'network.max_parallel_downloads'",
+                        required: true,
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "set",
+                    about: "Set a setting in the last configuration file defining it, or else in the configuration file",
+                    args: &[
+                        ArgSpec {
+                            name: "KEY",
+                            help: "Dotted key of the setting, like 'network.max_parallel_downloads'",
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "VALUE",
+                            help: "New value of the setting, in TOML syntax, strings not needing to be quoted",
+                            required: true,
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "migrate",
+                    about: "Rewrite the configuration files written for an older layout of the configuration, without their comments",
+                    ..COMMAND
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "repository",
+            about: "Manage the repositories and their mirrors, writing the changes back to the configuration",
+            subcommands: &[
+                CommandSpec {
+                    name: "list",
+                    about: "List the configured repositories, whether they are enabled and their mirrors",
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "add",
+                    about: "Add a repository with the given mirrors",
+                    args: &[
+                        ArgSpec {
+                            name: "NAME",
+                            help: "Name of the repository",
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "MIRROR",
+                            help: "URL of a mirror of the repository",
+                            required: true,
+                            multiple: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "fragment",
+                            long: Some("fragment"),
+                            help: "Write the repository to its own fragment in the config.d folder",
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "remove",
+                    about: "Remove the given repository from every configuration file defining it",
+                    args: &[ArgSpec {
+                        name: "NAME",
+                        help: "Name of the repository",
+                        required: true,
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "enable",
+                    about: "Enable the given repository",
+                    args: &[ArgSpec {
+                        name: "NAME",
+                        help: "Name of the repository",
+                        required: true,
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "disable",
+                    about: "Disable the given repository, which is then ignored without losing its settings",
+                    args: &[ArgSpec {
+                        name: "NAME",
+                        help: "Name of the repository",
+                        required: true,
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "add-mirror",
+                    about: "Add a mirror to the given repository",
+                    args: &[
+                        ArgSpec {
+                            name: "NAME",
+                            help: "Name of the repository",
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "MIRROR",
+                            help: "URL of the mirror",
+                            required: true,
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "remove-mirror",
+                    about: "Remove a mirror from the given repository",
+                    args: &[
+                        ArgSpec {
+                            name: "NAME",
+                            help: "Name of the repository",
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "MIRROR",
+                            help: "URL of the mirror",
+                            required: true,
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "sysroot",
+            about: "Manage the sysroots used for cross-compilation, populated with --sysroot",
+            subcommands: &[
+                CommandSpec {
+                    name: "create",
+                    about: "Create an empty sysroot for the given target triple",
+                    args: &[ArgSpec {
+                        name: "TARGET",
+                        help: "Target triple of the sysroot, like aarch64-linux-gnu",
+                        required: true,
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "list",
+                    about: "List the sysroots and their location",
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "remove",
+                    about: "Remove the sysroot of the given target triple and all the packages installed in it",
+                    args: &[ArgSpec {
+                        name: "TARGET",
+                        help: "Target triple of the sysroot",
+                        required: true,
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "list",
+            about: "List the explicitly installed packages, or the packages matching the given filters",
+            args: &[
+                ArgSpec {
+                    name: "with-deps",
+                    long: Some("with-deps"),
+                    help: "Include the dependencies of installed packages",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "available",
+                    long: Some("available"),
+                    short: Some("a"),
+                    conflicts_with: &["with-deps", "orphaned"],
+                    help: "List the most recent version of the available packages instead of the installed ones",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "orphaned",
+                    long: Some("orphaned"),
+                    help: "Only list the installed packages no longer required by any explicitly installed package",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "upgradable",
+                    long: Some("upgradable"),
+                    short: Some("u"),
+                    help: "Only list the installed packages for which a more recent version is available",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "repository",
+                    long: Some("repository"),
+                    short: Some("r"),
+                    takes_value: true,
+                    value_name: Some("REPOSITORY"),
+                    help: "Only list the packages of the given repository",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "category",
+                    long: Some("category"),
+                    takes_value: true,
+                    value_name: Some("CATEGORY"),
+                    help: "Only list the packages of the given category",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "PREFIX",
+                    help: "Only list the packages starting with the given prefix, like 'sys-devel/' or 'stable::sys-devel/gcc'",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "config-merge",
+            about: "Merge the configuration files kept by upgrades with their new version",
+            args: &[
+                ArgSpec {
+                    name: "PATH",
+                    help: "Configuration files to merge (all of them by default)",
+                    multiple: true,
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "list",
+                    long: Some("list"),
+                    short: Some("l"),
+                    help: "Only list the configuration files waiting to be merged",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "use-new",
+                    long: Some("use-new"),
+                    conflicts_with: &["list", "keep-local"],
+                    help: "Replace the configuration files with their new version, without asking",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "keep-local",
+                    long: Some("keep-local"),
+                    conflicts_with: &["list"],
+                    help: "Keep the configuration files as they are and discard their new version, without asking",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "files",
+            about: "List the files shipped by the given package, installed or available",
+            args: &[
+                ArgSpec {
+                    name: "PACKAGE",
+                    help: "Package whose files to list",
+                    required: true,
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "download",
+                    long: Some("download"),
+                    help: "Download the package if it is neither installed nor downloaded, as its files are unknown until then",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "info",
+            about: "Show the details of the given package, installed or available",
+            args: &[ArgSpec {
+                name: "PACKAGE",
+                help: "Package to show",
+                required: true,
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "changelog",
+            about: "Show the changes made by the available versions of the given packages since the installed ones",
+            args: &[ArgSpec {
+                name: "PACKAGE",
+                help: "Packages to show the changes of",
+                multiple: true,
+                required: true,
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "license",
+            about: "Report the licenses of the installed packages, flagging those under a denied license",
+            args: &[ArgSpec {
+                name: "denied",
+                long: Some("denied"),
+                help: "Only report the packages under a denied license",
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "audit",
+            about: "Report the installed packages affected by the security advisories of their repository",
+            args: &[ArgSpec {
+                name: "offline",
+                long: Some("offline"),
+                help: "Use the security advisories fetched previously instead of fetching them again",
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "owns",
+            about: "Show the installed packages owning the given files",
+            args: &[
+                ArgSpec {
+                    name: "remote",
+                    long: Some("remote"),
+                    help: "Look for the available packages providing the files instead, according to the file lists pulled with `nest pull --files`",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "PATH",
+                    help: "Absolute paths of the files, which may contain wildcards (?, *, ** and [...])",
+                    multiple: true,
+                    required: true,
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "search",
+            about: "Search the available packages by name, category or description",
+            args: &[
+                ArgSpec {
+                    name: "PATTERN",
+                    help: "Text to look for",
+                    required: true,
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "regex",
+                    long: Some("regex"),
+                    short: Some("r"),
+                    help: "Interpret the pattern as a regular expression",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "fuzzy",
+                    long: Some("fuzzy"),
+                    short: Some("z"),
+                    conflicts_with: &["regex"],
+                    help: "Match packages containing the characters of the pattern in the same order",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "full-text",
+                    long: Some("full-text"),
+                    short: Some("t"),
+                    conflicts_with: &["regex", "fuzzy"],
+                    help: "Match packages containing all the words of the pattern, using the search index",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "installed-only",
+                    long: Some("installed-only"),
+                    short: Some("i"),
+                    help: "Only show installed packages",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "hold",
+            about: "Hold the given installed packages at their current version, or list the held packages",
+            args: &[ArgSpec {
+                name: "PACKAGE",
+                help: "Installed packages to hold",
+                multiple: true,
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "unhold",
+            about: "Let the given held packages be upgraded again",
+            args: &[ArgSpec {
+                name: "PACKAGE",
+                help: "Held packages to release",
+                multiple: true,
+                required: true,
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "alternatives",
+            about: "Manage the links shared by several packages, like /usr/bin/cc, and the targets they point to",
+            subcommands: &[
+                CommandSpec {
+                    name: "list",
+                    about: "List the alternatives and their candidates, the current one being marked with a '*'",
+                    args: &[ArgSpec {
+                        name: "LINK",
+                        help: "Only list the candidates of this link",
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "set",
+                    about: "Point the given link to the given target, until it is set back to automatic",
+                    args: &[
+                        ArgSpec {
+                            name: "LINK",
+                            help: "Path of the link, like /usr/bin/cc",
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "TARGET",
+                            help: "Path of the target, or the installed package offering it",
+                            required: true,
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "auto",
+                    about: "Point the given link to the target with the highest priority again",
+                    args: &[ArgSpec {
+                        name: "LINK",
+                        help: "Path of the link, like /usr/bin/cc",
+                        required: true,
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "why",
+            about: "Show the chains of dependencies leading from explicitly installed packages to the given one",
+            args: &[ArgSpec {
+                name: "PACKAGE",
+                help: "Installed package to explain",
+                required: true,
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "rdepends",
+            about: "List the installed packages depending on the given one",
+            args: &[
+                ArgSpec {
+                    name: "PACKAGE",
+                    help: "Installed package to look for the dependents of",
+                    required: true,
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "recursive",
+                    long: Some("recursive"),
+                    short: Some("r"),
+                    help: "Also list the packages depending on them, and so on",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "graph",
+            about: "Export the dependency graph of the given package or group in the DOT or JSON format",
+            args: &[
+                ArgSpec {
+                    name: "TARGET",
+                    help: "Installed package or group to export, @root by default",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "format",
+                    long: Some("format"),
+                    short: Some("f"),
+                    takes_value: true,
+                    possible_values: &["dot", "json"],
+                    default_value: Some("dot"),
+                    help: "Format of the exported graph",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "depth",
+                    long: Some("depth"),
+                    short: Some("d"),
+                    takes_value: true,
+                    help: "Only export the dependencies up to the given distance from the target",
+                    ..ARG
+                },
+                ArgSpec {
+                    name: "optional",
+                    long: Some("optional"),
+                    help: "Include the optional dependencies of the packages, whether they are installed or not",
+                    ..ARG
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "doctor",
+            about: "Check the configuration, the lock file, the cache of installed packages and the downloaded packages for problems",
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "verify",
+            about: "Check the installed files for modifications",
+            args: &[ArgSpec {
+                name: "package",
+                long: Some("package"),
+                short: Some("p"),
+                takes_value: true,
+                multiple: true,
+                number_of_values: Some(1),
+                help: "Only check the files of the given packages",
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "completions",
+            about: "Generate the completion script of the given shell",
+            args: &[ArgSpec {
+                name: "SHELL",
+                help: "Shell to generate the completion script of",
+                possible_values: &["bash", "zsh", "fish"],
+                required: true,
+                ..ARG
+            }],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "package-names",
+            about: "List the names of the available packages, for the completion scripts",
+            hidden: true,
+            ..COMMAND
+        },
+        HELP,
+    ],
+    ..COMMAND
+};
+
+/// The specification of `finest`
+pub static FINEST: CommandSpec = CommandSpec {
+    name: "finest",
+    about: "Raven-OS's package manager.",
+    usage: Some("finest [OPTION]... SUBCOMMAND [SUBCOMMAND OPTIONS]..."),
+    args: &[
+        VERBOSE, QUIET, CONFIG, CHROOT, YES, NO_CONFIRM, WAIT, TIMEOUT, LIMIT_RATE,
+    ],
+    subcommands: &[
+        CommandSpec {
+            name: "pull",
+            about: "Pull repositories and update the local cache",
+            args: &[
+                ArgSpec {
+                    name: "REPOSITORY",
+                    help: "Repositories to pull (all of them by default)",
+                    multiple: true,
+                    ..ARG
+                },
+                PULL_JOBS,
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "group",
+            about: "Operate on groups",
+            subcommands: &[
+                CommandSpec {
+                    name: "add",
+                    about: "Create new groups",
+                    args: &[
+                        ArgSpec {
+                            name: "GROUP",
+                            help: "Groups to create",
+                            multiple: true,
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "PARENT",
+                            long: Some("parent"),
+                            help: "Parent group of the groups to create",
+                            takes_value: true,
+                            default_value: Some("@root"),
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "remove",
+                    about: "Remove existing groups",
+                    args: &[ArgSpec {
+                        name: "GROUP",
+                        help: "Groups to remove",
+                        multiple: true,
+                        required: true,
+                        ..ARG
+                    }],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "list",
+                    about: "List existing groups",
+                    ..COMMAND
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "requirement",
+            about: "Operate on requirements",
+            subcommands: &[
+                CommandSpec {
+                    name: "add",
+                    about: "Add new requirements",
+                    args: &[
+                        ArgSpec {
+                            name: "PACKAGE",
+                            help: "Requirements to add",
+                            multiple: true,
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "PARENT",
+                            long: Some("parent"),
+                            help: "Parent group of the requirements to add",
+                            takes_value: true,
+                            default_value: Some("@root"),
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+                CommandSpec {
+                    name: "remove",
+                    about: "Remove existing requirements",
+                    args: &[
+                        ArgSpec {
+                            name: "PACKAGE",
+                            help: "Requirements to remove",
+                            multiple: true,
+                            required: true,
+                            ..ARG
+                        },
+                        ArgSpec {
+                            name: "PARENT",
+                            long: Some("parent"),
+                            help: "Parent group of the requirements to remove",
+                            takes_value: true,
+                            default_value: Some("@root"),
+                            ..ARG
+                        },
+                    ],
+                    ..COMMAND
+                },
+            ],
+            ..COMMAND
+        },
+        CommandSpec {
+            name: "merge",
+            about: "Merge the scratch dependency graph with the regular dependency graph",
+            ..COMMAND
+        },
+        RESUME,
+        HELP,
+    ],
+    ..COMMAND
+};
+
+impl ArgSpec {
+    /// Returns whether the argument is positional, that is, neither a flag nor an option
+    fn is_positional(&self) -> bool {
+        self.short.is_none() && self.long.is_none()
+    }
+
+    /// Builds the clap argument matching this specification
+    fn arg(&self) -> Arg<'static, 'static> {
+        let mut arg = Arg::with_name(self.name)
+            .help(self.help)
+            .multiple(self.multiple)
+            .required(self.required)
+            .global(self.global);
+
+        if let Some(short) = self.short {
+            arg = arg.short(short);
+        }
+        if let Some(long) = self.long {
+            arg = arg.long(long);
+        }
+        if self.takes_value {
+            arg = arg.takes_value(true);
+        }
+        if let Some(value_name) = self.value_name {
+            arg = arg.value_name(value_name);
+        }
+        if let Some(default_value) = self.default_value {
+            arg = arg.default_value(default_value);
+        }
+        if !self.possible_values.is_empty() {
+            arg = arg.possible_values(self.possible_values);
+        }
+        if !self.conflicts_with.is_empty() {
+            arg = arg.conflicts_with_all(self.conflicts_with);
+        }
+        if !self.requires.is_empty() {
+            arg = arg.requires_all(self.requires);
+        }
+        if !self.required_unless.is_empty() {
+            arg = arg.required_unless_one(self.required_unless);
+        }
+        if let Some(number_of_values) = self.number_of_values {
+            arg = arg.number_of_values(number_of_values);
+        }
+        arg
+    }
+
+    /// Returns how the argument is written on the command line, as shown in the manual page
+    fn synopsis(&self) -> String {
+        if self.is_positional() {
+            let name = format!("{}{}", self.name, if self.multiple { "..." } else { "" });
+            return if self.required && self.required_unless.is_empty() {
+                format!("<{}>", name)
+            } else {
+                format!("[{}]", name)
+            };
+        }
+
+        let mut flags = Vec::new();
+        if let Some(short) = self.short {
+            flags.push(format!("\\fB\\-{}\\fR", short));
+        }
+        if let Some(long) = self.long {
+            flags.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+        }
+        let mut synopsis = flags.join(", ");
+        if self.takes_value {
+            let value_name = self
+                .value_name
+                .map(str::to_string)
+                .unwrap_or_else(|| self.name.to_uppercase());
+            synopsis.push_str(&format!(" \\fI{}\\fR", escape(&value_name)));
+        }
+        synopsis
+    }
+
+    /// Returns the description of the argument, along with its default and possible values, as shown in the
+    /// manual page
+    fn description(&self) -> String {
+        let mut description = self.help.to_string();
+        if !self.possible_values.is_empty() {
+            description.push_str(&format!(
+                " [possible values: {}]",
+                self.possible_values.join(", ")
+            ));
+        }
+        if let Some(default_value) = self.default_value {
+            description.push_str(&format!(" [default: {}]", default_value));
+        }
+        escape(&description)
+    }
+}
+
+impl CommandSpec {
+    /// Builds the clap application matching this specification, with its subcommands
+    pub fn app(&self) -> App<'static, 'static> {
+        App::new(self.name)
+            .template(TEMPLATE)
+            .usage(self.usage.unwrap_or(self.name))
+            .about(self.about)
+            .version(crate_version!())
+            .author(crate_authors!())
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .setting(AppSettings::VersionlessSubcommands)
+            .setting(AppSettings::ColoredHelp)
+            .setting(AppSettings::DisableHelpSubcommand)
+            .args(&self.args.iter().map(ArgSpec::arg).collect::<Vec<_>>())
+            .subcommands(self.subcommands.iter().map(CommandSpec::subcommand))
+    }
+
+    /// Builds the clap subcommand matching this specification, with its own subcommands
+    fn subcommand(&self) -> App<'static, 'static> {
+        let mut subcommand = SubCommand::with_name(self.name)
+            .about(self.about)
+            .args(&self.args.iter().map(ArgSpec::arg).collect::<Vec<_>>())
+            .subcommands(self.subcommands.iter().map(CommandSpec::subcommand));
+
+        for alias in self.aliases {
+            subcommand = subcommand.alias(*alias);
+        }
+        if !self.subcommands.is_empty() {
+            subcommand = subcommand.setting(AppSettings::SubcommandRequiredElseHelp);
+        }
+        if self.hidden {
+            subcommand = subcommand.setting(AppSettings::Hidden);
+        }
+        subcommand
+    }
+
+    /// Generates the manual page of the command, in the roff format
+    pub fn man_page(&self) -> String {
+        let mut page = format!(
+            ".TH {} 8 \"\" \"{} {}\" \"Raven-OS\"\n",
+            self.name.to_uppercase(),
+            self.name,
+            crate_version!()
+        );
+        page.push_str(&format!(
+            ".SH NAME\n{} \\- {}\n",
+            self.name,
+            escape(self.about)
+        ));
+        page.push_str(&format!(
+            ".SH SYNOPSIS\n{}\n",
+            escape(self.usage.unwrap_or(self.name))
+        ));
+
+        page.push_str(".SH OPTIONS\n");
+        write_man_args(&mut page, self.args);
+
+        page.push_str(".SH COMMANDS\n");
+        for subcommand in self.subcommands {
+            subcommand.write_man_command(&mut page, self.name);
+        }
+
+        page.push_str(".SH \"EXIT STATUS\"\n");
+        for (code, meaning) in EXIT_CODES {
+            page.push_str(&format!(".TP\n{}\n{}\n", code, escape(meaning)));
+        }
+        page
+    }
+
+    /// Writes the section of the manual page describing this subcommand of the given command, and its own
+    /// subcommands
+    fn write_man_command(&self, page: &mut String, parent: &str) {
+        if self.hidden {
+            return;
+        }
+
+        let path = format!("{} {}", parent, self.name);
+        let mut synopsis = path.clone();
+        if self.args.iter().any(|arg| !arg.is_positional()) {
+            synopsis.push_str(" [OPTIONS]");
+        }
+        for arg in self.args.iter().filter(|arg| arg.is_positional()) {
+            synopsis.push(' ');
+            synopsis.push_str(&arg.synopsis());
+        }
+        if !self.subcommands.is_empty() {
+            synopsis.push_str(" SUBCOMMAND");
+        }
+
+        page.push_str(&format!(
+            ".SS \"{}\"\n{}\n",
+            escape(&synopsis),
+            escape(self.about)
+        ));
+        write_man_args(page, self.args);
+
+        for subcommand in self.subcommands {
+            subcommand.write_man_command(page, &path);
+        }
+    }
+}
+
+/// Writes the description of the given flags and options to the given manual page
+fn write_man_args(page: &mut String, args: &[ArgSpec]) {
+    for arg in args.iter().filter(|arg| !arg.is_positional()) {
+        page.push_str(&format!(".TP\n{}\n{}\n", arg.synopsis(), arg.description()));
+    }
+}
+
+/// Escapes the given text so that it is printed as is in a manual page
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+
+    // A line starting with a dot or an apostrophe would be taken for a request
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs;
+
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error, ResultExt};
+use libnest::config::{Config, TargetTriple};
+use serde_json::json;
+
+use super::ask_confirmation;
+use super::output::{json_output, print_json};
+
+/// Finds the existing sysroot of the given target triple
+fn find_sysroot(config: &Config, target: &str) -> Result<TargetTriple, Error> {
+    let target = TargetTriple::parse(target)?;
+
+    if config.sysroots()?.contains(&target) {
+        Ok(target)
+    } else {
+        Err(format_err!(
+            "unable to find sysroot '{}', use 'nest sysroot create {}' to create it",
+            target,
+            target
+        ))
+    }
+}
+
+/// Makes the given configuration operate on the sysroot given with `--sysroot`, if any
+pub fn set_sysroot(config: &mut Config, matches: &ArgMatches) -> Result<(), Error> {
+    if let Some(target) = matches.value_of("sysroot") {
+        let target = find_sysroot(config, target)?;
+        *config = config.for_sysroot(&target);
+    }
+    Ok(())
+}
+
+pub fn sysroot_create(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let target = TargetTriple::parse(matches.value_of("TARGET").unwrap())?;
+    if config.sysroots()?.contains(&target) {
+        return Err(format_err!("the sysroot of '{}' already exists", target));
+    }
+
+    let sysroot_config = config.for_sysroot(&target);
+    let root = sysroot_config.paths().root();
+    if config.dry_run() {
+        println!("Dry run, the sysroot wasn't created.");
+        return Ok(());
+    }
+
+    fs::create_dir_all(root).with_context(|_| root.display().to_string())?;
+    println!(
+        "Created the sysroot of {} in {}, populate it with 'nest --sysroot {} install <package>...'.",
+        target.as_ref().bold(),
+        root.display(),
+        target
+    );
+    Ok(())
+}
+
+pub fn sysroot_list(config: &Config) -> Result<(), Error> {
+    let targets = config.sysroots()?;
+
+    if json_output() {
+        print_json(&json!(targets
+            .iter()
+            .map(|target| json!({
+                "target": target.to_string(),
+                "architecture": target.architecture().to_string(),
+                "path": config.for_sysroot(target).paths().root(),
+            }))
+            .collect::<Vec<_>>()));
+        return Ok(());
+    }
+
+    for target in targets {
+        println!(
+            "{} {}",
+            target.as_ref().bold(),
+            config.for_sysroot(&target).paths().root().display()
+        );
+    }
+    Ok(())
+}
+
+pub fn sysroot_remove(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let target = find_sysroot(config, matches.value_of("TARGET").unwrap())?;
+    let sysroot_config = config.for_sysroot(&target);
+
+    // Holding the lock file of the sysroot ensures no other instance is modifying it
+    let _lock_file_ownership = sysroot_config.acquire_lock_file_ownership(true)?;
+
+    let question = format!(
+        "Would you like to remove the sysroot of {} and all the packages installed in it?",
+        target
+    );
+    if !ask_confirmation(config, &question, false)? {
+        println!("The sysroot wasn't removed.");
+        return Ok(());
+    }
+
+    if config.dry_run() {
+        println!("Dry run, the sysroot wasn't removed.");
+        return Ok(());
+    }
+
+    let root = sysroot_config.paths().root();
+    fs::remove_dir_all(root).with_context(|_| root.display().to_string())?;
+    println!("Removed the sysroot of {}.", target.as_ref().bold());
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::io::Write;
+
+use clap::ArgMatches;
+use failure::Error;
+use libnest::cache::depgraph::DependencyGraphDiff;
+use libnest::config::Config;
+
+use super::output;
+use super::{
+    apply_transactions, check_pending_transactions, confirm_transactions, print_plans,
+    print_transactions,
+};
+
+pub fn undo(config: &Config, _: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    check_pending_transactions(config, &lock_file_ownership)?;
+    let history = config.history(&lock_file_ownership);
+
+    let entry = match history.last_undoable_entry()? {
+        Some(entry) => entry,
+        None => {
+            writeln!(
+                output::messages(),
+                "No transaction can be undone, quitting."
+            )?;
+            return Ok(());
+        }
+    };
+
+    let original_graph = config.dependency_graph(&lock_file_ownership)?;
+    let graph = history.previous_dependency_graph(&entry)?;
+
+    let transactions = DependencyGraphDiff::new().perform(&original_graph, &graph);
+
+    if transactions.is_empty() {
+        writeln!(
+            output::messages(),
+            "No transactions are required, quitting."
+        )?;
+        return Ok(());
+    }
+
+    writeln!(
+        output::messages(),
+        "Undoing the transactions performed on {}.\n",
+        entry.date().format("%Y-%m-%d %H:%M:%S UTC")
+    )?;
+    print_transactions(&transactions);
+
+    if config.dry_run() {
+        return print_plans(config, &transactions, &lock_file_ownership);
+    }
+
+    if !confirm_transactions(config, &transactions, &lock_file_ownership)? {
+        return Ok(());
+    }
+
+    apply_transactions(
+        config,
+        &transactions,
+        &original_graph,
+        &graph,
+        Some(entry.id()),
+        &lock_file_ownership,
+    )?;
+
+    Ok(())
+}
@@ -1,42 +1,111 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::str::FromStr;
+
 use clap::ArgMatches;
 use failure::{format_err, Error};
-use libnest::cache::depgraph::{DependencyGraphDiff, RequirementKind};
+use libnest::cache::available::{AvailablePackagesCacheQueryStrategy, QueryResult};
+use libnest::cache::depgraph::{DependencyGraphDiff, GroupName, NodeKind, RequirementKind};
 use libnest::config::Config;
-use libnest::package::SoftPackageRequirement;
+use libnest::package::PackageFullName;
 
-use super::{ask_confirmation, print_transactions, process_transactions};
+use super::output;
+use super::{
+    apply_transactions, check_pending_transactions, confirm_transactions, expand_installed_pattern,
+    parse_target, print_plans, print_transactions, save_dependency_graph,
+};
 
 pub fn uninstall(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
     let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    check_pending_transactions(config, &lock_file_ownership)?;
 
     let mut graph = config.dependency_graph(&lock_file_ownership)?;
     let original_graph = graph.clone();
+    let mut targets = Vec::new();
+    let regex = matches.is_present("regex");
 
     {
-        let packages_cache = config.available_packages_cache(&lock_file_ownership);
-
         for target in &matches.values_of_lossy("PACKAGE").unwrap() {
-            let requirement = SoftPackageRequirement::parse(&target)?;
+            // The packages of a group are uninstalled along with it, unless something else requires them
+            if target.starts_with('@') {
+                let kind = RequirementKind::Group {
+                    name: GroupName::from_str(target)?,
+                };
+
+                if !graph.node_has_requirement(&graph.nodes()[&graph.root_id()], &kind) {
+                    return Err(format_err!(
+                        "unable to find an installed group matching '{}'",
+                        &target
+                    ));
+                }
+                graph.node_remove_requirement(graph.root_id(), kind);
+                continue;
+            }
 
-            let matches = packages_cache.query(&requirement).perform()?;
+            let query = parse_target(&target, regex)?;
 
-            let root_node = graph.nodes().get(&graph.root_id()).unwrap().clone();
+            // Patterns are expanded to the installed packages they match, while a package may come from any of the
+            // repositories offering it
+            let candidates: Vec<PackageFullName> = if query.is_pattern() {
+                match expand_installed_pattern(config, &query, &lock_file_ownership)? {
+                    Some(installed) => installed.into_iter().map(Into::into).collect(),
+                    None => {
+                        writeln!(output::messages(), "Uninstallation cancelled.")?;
+                        return Ok(());
+                    }
+                }
+            } else {
+                query
+                    .available(
+                        config,
+                        &lock_file_ownership,
+                        AvailablePackagesCacheQueryStrategy::AllMatchesUnsorted,
+                    )?
+                    .iter()
+                    .map(QueryResult::full_name)
+                    .collect()
+            };
+
+            // Without a slot, the package is uninstalled from all of the slots it was installed in
+            let slot = query.slot().cloned();
+
+            let mut found = false;
+            for full_name in candidates {
+                let requirement_ids: Vec<_> = graph.nodes()[&graph.root_id()]
+                    .requirements()
+                    .iter()
+                    .cloned()
+                    .filter(
+                        |requirement_id| match graph.requirements()[requirement_id].kind() {
+                            RequirementKind::Package { package_req } => {
+                                package_req.matches_full_name_precisely(&full_name)
+                                    && (slot.is_none() || *package_req.slot() == slot)
+                            }
+                            RequirementKind::Group { .. } => false,
+                        },
+                    )
+                    .collect();
+
+                if !requirement_ids.is_empty() {
+                    for requirement_id in requirement_ids {
+                        graph.remove_requirement(requirement_id);
+                    }
+                    targets.push((full_name, slot.clone()));
+                    found = true;
 
-            let found = matches.iter().any(|pkg| {
-                root_node.requirements().iter().any(|req_id| {
-                    let req = graph.requirements().get(req_id).unwrap();
-                    if let RequirementKind::Package { package_req } = req.kind() {
-                        let full_name = pkg.full_name();
-                        if package_req.matches_full_name_precisely(&full_name) {
-                            graph.remove_requirement(*req_id);
-                            return true;
-                        }
+                    // Only patterns designate several packages
+                    if !query.is_pattern() {
+                        break;
                     }
-                    false
-                })
-            });
+                }
+            }
 
-            if !found {
+            if !found && query.is_pattern() {
+                return Err(format_err!(
+                    "none of the packages matching '{}' was explicitly installed",
+                    &target
+                ));
+            } else if !found {
                 return Err(format_err!(
                     "unable to find an installed package matching '{}'",
                     &target
@@ -45,37 +114,87 @@ pub fn uninstall(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
         }
     }
 
+    // Refuse to uninstall packages that other installed packages still depend on, unless forced or uninstalled too.
+    // Dependents that are no longer reachable from the root are orphaned by this uninstallation, and removed with it.
+    let target_names: Vec<PackageFullName> = targets.iter().map(|(name, _)| name.clone()).collect();
+    for (target, slot) in &targets {
+        let orphans: HashSet<_> = graph.orphan_nodes().into_iter().collect();
+        let node_ids: Vec<_> = graph
+            .get_package_node_ids(target)
+            .into_iter()
+            .filter(|node_id| match graph.nodes()[node_id].kind() {
+                NodeKind::Package {
+                    slot: node_slot, ..
+                } => slot.as_ref().map_or(true, |slot| slot == node_slot),
+                NodeKind::Group { .. } => false,
+            })
+            .collect();
+
+        for node_id in node_ids {
+            let dependents: Vec<_> = graph
+                .dependent_nodes(node_id)
+                .into_iter()
+                .filter(|dependent_id| !orphans.contains(dependent_id))
+                .filter(|dependent_id| match graph.nodes()[dependent_id].kind() {
+                    NodeKind::Package { id, .. } => !target_names.contains(&id.clone().into()),
+                    NodeKind::Group { .. } => true,
+                })
+                .collect();
+
+            if dependents.is_empty() {
+                continue;
+            }
+
+            if matches.is_present("force") {
+                graph.remove_node(node_id);
+            } else {
+                let dependents: Vec<_> = dependents
+                    .iter()
+                    .map(|dependent_id| graph.nodes()[dependent_id].to_string())
+                    .collect();
+
+                return Err(format_err!(
+                    "unable to uninstall '{}': it is required by {} (use --force to uninstall it anyway)",
+                    graph.nodes()[&node_id],
+                    dependents.join(", ")
+                ));
+            }
+        }
+    }
+
     graph.solve(&config)?;
 
     let transactions = DependencyGraphDiff::new().perform(&original_graph, &graph);
 
     if transactions.is_empty() {
-        println!("No transactions are required, quitting.");
-        graph.save_to_cache(config.paths().depgraph(), &lock_file_ownership)?;
+        writeln!(
+            output::messages(),
+            "No transactions are required, quitting."
+        )?;
+        if !config.dry_run() {
+            save_dependency_graph(config, &graph, &lock_file_ownership)?;
+        }
         return Ok(());
     }
 
     print_transactions(&transactions);
 
-    if !ask_confirmation(
-        format!(
-            "Would you like to apply th{} transaction{}?",
-            if transactions.len() <= 1 { "is" } else { "ese" },
-            if transactions.len() <= 1 { "" } else { "s" },
-        )
-        .as_str(),
-        true,
-    )? {
-        println!(
-            "Transaction{} cancelled.",
-            if transactions.len() <= 1 { "" } else { "s" }
-        );
-        return Ok(());
+    if config.dry_run() {
+        return print_plans(config, &transactions, &lock_file_ownership);
     }
 
-    process_transactions(config, &transactions, &lock_file_ownership)?;
+    if !confirm_transactions(config, &transactions, &lock_file_ownership)? {
+        return Ok(());
+    }
 
-    graph.save_to_cache(config.paths().depgraph(), &lock_file_ownership)?;
+    apply_transactions(
+        config,
+        &transactions,
+        &original_graph,
+        &graph,
+        None,
+        &lock_file_ownership,
+    )?;
 
     Ok(())
 }
@@ -1,62 +1,170 @@
+use std::collections::HashSet;
+use std::io::Write;
+
 use clap::ArgMatches;
-use failure::Error;
-use libnest::cache::depgraph::DependencyGraphDiff;
+use colored::*;
+use failure::{format_err, Error};
+use libnest::cache::available::AvailablePackagesCacheQueryStrategy;
+use libnest::cache::depgraph::{DependencyGraph, DependencyGraphDiff, NodeKind};
 use libnest::config::Config;
-use libnest::transaction::Transaction;
+use libnest::lock_file::LockFileOwnership;
+use libnest::package::{PackageFullName, SoftPackageRequirement};
+
+use super::output;
+use super::{
+    apply_transactions, check_pending_transactions, confirm_downgrades, confirm_transactions,
+    expand_installed_pattern, index_local_repository, parse_target, print_plans,
+    print_transactions, save_dependency_graph, solve_interactively,
+};
+
+/// Prints the held packages of the given graph for which a more recent version is available
+fn print_held_upgrades(
+    config: &Config,
+    graph: &DependencyGraph,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<(), Error> {
+    let held = config.held_packages()?;
+    let available = config.available_packages_cache(lock_file_ownership);
+
+    let mut ids: Vec<_> = graph
+        .nodes()
+        .values()
+        .filter_map(|node| match node.kind() {
+            NodeKind::Package { id, .. } if held.is_held(id) => Some(id),
+            _ => None,
+        })
+        .collect();
+    ids.sort();
+
+    for id in ids {
+        let latest = available
+            .query(&SoftPackageRequirement::from_id(id).any_version())
+            .set_strategy(AvailablePackagesCacheQueryStrategy::BestMatch)
+            .perform()?
+            .into_iter()
+            .next();
+
+        if let Some(latest) = latest {
+            if latest.id().version() > id.version() {
+                writeln!(
+                    output::messages(),
+                    "{:>10} {} (held, {} is available)",
+                    "skip".yellow().bold(),
+                    id,
+                    latest.id().version()
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the names of the installed packages designated by the given targets, expanding the patterns after
+/// confirmation.
+///
+/// [`None`] is returned if the user declined the expansion of a pattern.
+fn select_targets(
+    config: &Config,
+    targets: &[String],
+    regex: bool,
+    lock_file_ownership: &LockFileOwnership,
+) -> Result<Option<HashSet<PackageFullName>>, Error> {
+    let mut selected = HashSet::new();
 
-use super::operations::download::download_packages;
-use super::{ask_confirmation, print_transactions, process_transactions};
+    for target in targets {
+        let query = parse_target(target, regex)?;
+
+        let installed = if query.is_pattern() {
+            match expand_installed_pattern(config, &query, lock_file_ownership)? {
+                Some(installed) => installed,
+                None => return Ok(None),
+            }
+        } else {
+            query.installed(config, lock_file_ownership)?
+        };
+        if installed.is_empty() {
+            return Err(format_err!(
+                "unable to find an installed package matching '{}'",
+                target
+            ));
+        }
+        selected.extend(installed.into_iter().map(Into::into));
+    }
+    Ok(Some(selected))
+}
 
-pub fn upgrade(config: &Config, _: &ArgMatches) -> Result<(), Error> {
+pub fn upgrade(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
     let lock_file_ownership = config.acquire_lock_file_ownership(true)?;
+    check_pending_transactions(config, &lock_file_ownership)?;
+    index_local_repository(config, &lock_file_ownership)?;
     let mut graph = config.dependency_graph(&lock_file_ownership)?;
     let original_graph = graph.clone();
 
-    graph.update(config)?;
+    // Only the given packages are upgraded, if any, the others keeping their current version
+    let selected = match matches.values_of_lossy("PACKAGE") {
+        Some(targets) => {
+            let regex = matches.is_present("regex");
+
+            match select_targets(config, &targets, regex, &lock_file_ownership)? {
+                Some(selected) => Some(selected),
+                None => {
+                    writeln!(output::messages(), "Upgrade cancelled.")?;
+                    return Ok(());
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Bring the explicit requirements in line with the world set, which may have been edited by hand
+    let world = config.world(&graph)?;
+    graph.apply_world(config, &world)?;
+
+    solve_interactively(config, &mut graph, |graph, config| match &selected {
+        Some(selected) => graph.update_packages(config, |id| {
+            let full_name: PackageFullName = id.clone().into();
+            selected.contains(&full_name)
+        }),
+        None => graph.update(config),
+    })?;
+    print_held_upgrades(config, &graph, &lock_file_ownership)?;
 
     let transactions = DependencyGraphDiff::new().perform(&original_graph, &graph);
 
     if transactions.is_empty() {
-        println!("All the given requirements are already satisfied, quitting.");
+        writeln!(
+            output::messages(),
+            "All the given requirements are already satisfied, quitting."
+        )?;
+        if !config.dry_run() {
+            save_dependency_graph(config, &graph, &lock_file_ownership)?;
+        }
         return Ok(());
     }
 
     print_transactions(&transactions);
 
-    if !ask_confirmation(
-        format!(
-            "Would you like to apply th{} transaction{}?",
-            if transactions.len() <= 1 { "is" } else { "ese" },
-            if transactions.len() <= 1 { "" } else { "s" },
-        )
-        .as_str(),
-        true,
-    )? {
-        println!(
-            "Transaction{} cancelled.",
-            if transactions.len() <= 1 { "" } else { "s" }
-        );
+    if config.dry_run() {
+        return print_plans(config, &transactions, &lock_file_ownership);
+    }
+
+    if !confirm_downgrades(config, &transactions)? {
+        writeln!(output::messages(), "Nothing was downgraded.")?;
+        return Ok(());
+    }
+
+    if !confirm_transactions(config, &transactions, &lock_file_ownership)? {
         return Ok(());
     }
 
-    println!("Downloading packages...");
-    let downloaded = config.downloaded_packages_cache(&lock_file_ownership);
-    download_packages(
+    apply_transactions(
         config,
-        transactions.iter().filter_map(|trans| match trans {
-            Transaction::Install(install) if !downloaded.has_package(install.target()) => {
-                Some(install.associated_download())
-            }
-            Transaction::Upgrade(upgrade) if !downloaded.has_package(upgrade.new_target()) => {
-                Some(upgrade.associated_download())
-            }
-            _ => None,
-        }),
+        &transactions,
+        &original_graph,
+        &graph,
+        None,
+        &lock_file_ownership,
     )?;
 
-    process_transactions(config, &transactions, &lock_file_ownership)?;
-
-    graph.save_to_cache(config.paths().depgraph(), &lock_file_ownership)?;
-
     Ok(())
 }
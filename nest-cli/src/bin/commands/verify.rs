@@ -0,0 +1,63 @@
+use clap::ArgMatches;
+use colored::*;
+use failure::{format_err, Error};
+use libnest::cache::installed::verify::FileIssueKind;
+use libnest::cache::query::PackageQuery;
+use libnest::config::Config;
+
+pub fn verify(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let lock_file_ownership = config.acquire_shared_lock_file_ownership(true)?;
+    let installed_packages = config.installed_packages_cache(&lock_file_ownership);
+
+    let mut packages = installed_packages.packages()?;
+
+    if let Some(targets) = matches.values_of_lossy("package") {
+        let queries = targets
+            .iter()
+            .map(|target| PackageQuery::parse(target))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (target, query) in targets.iter().zip(&queries) {
+            if !packages.iter().any(|id| query.matches(id)) {
+                return Err(format_err!(
+                    "unable to find an installed package matching '{}'",
+                    target
+                ));
+            }
+        }
+
+        packages.retain(|id| queries.iter().any(|query| query.matches(id)));
+    }
+
+    let issues = installed_packages.verify(config.paths().root(), &packages)?;
+
+    for issue in &issues {
+        let kind = format!("{:>20.20}", issue.kind().to_string());
+        println!(
+            "{} {} ({})",
+            match issue.kind() {
+                FileIssueKind::Missing | FileIssueKind::TypeChanged => kind.red(),
+                FileIssueKind::Modified | FileIssueKind::PermissionsChanged => kind.yellow(),
+                FileIssueKind::Extraneous => kind.cyan(),
+            }
+            .bold(),
+            issue.path().display(),
+            issue.package(),
+        );
+    }
+
+    if issues.is_empty() {
+        println!(
+            "{} installed package{} verified, no issue found.",
+            packages.len(),
+            if packages.len() <= 1 { "" } else { "s" }
+        );
+        Ok(())
+    } else {
+        Err(format_err!(
+            "{} issue{} found",
+            issues.len(),
+            if issues.len() <= 1 { "" } else { "s" }
+        ))
+    }
+}
@@ -1,133 +1,41 @@
 #![feature(try_blocks)]
 
-use clap::{crate_authors, crate_name, crate_version, App, AppSettings, Arg, SubCommand};
 use libnest::config;
 
 pub mod commands;
 
+use commands::errors::NestError;
+use commands::spec::FINEST;
+
 fn main() {
-    let matches = App::new(crate_name!())
-        .template("{usage}\n\n{about}\n\nOPTIONS\n{flags}\n\nSUBCOMMANDS\n{subcommands}")
-        .usage("finest [OPTION]... SUBCOMMAND [SUBCOMMAND OPTIONS]...")
-        .about("Raven-OS's package manager.")
-        .version(crate_version!())
-        .author(crate_authors!())
-        .setting(AppSettings::SubcommandRequiredElseHelp)
-        .setting(AppSettings::VersionlessSubcommands)
-        .setting(AppSettings::ColoredHelp)
-        .arg(
-            Arg::with_name("v")
-                .short("v")
-                .multiple(true)
-                .help("Set the level of verbosity"),
-        )
-        .arg(
-            Arg::with_name("config")
-                .short("c")
-                .long("config")
-                .help("Use the given path as a configuration file")
-                .takes_value(true)
-                .default_value("/etc/nest/config.toml")
-        )
-        .arg(
-            Arg::with_name("chroot")
-                .long("chroot")
-                .help("Use the current configuration but operate on the given folder, as if it was the root folder")
-                .takes_value(true)
-        )
-        .subcommand(
-            SubCommand::with_name("pull").about("Pull repositories and update the local cache"),
-        )
-        .subcommand(
-            SubCommand::with_name("group")
-                .setting(AppSettings::SubcommandRequiredElseHelp)
-                .about("Operate on groups")
-                .subcommand(
-                    SubCommand::with_name("add")
-                        .about("Create new groups")
-                        .arg(
-                            Arg::with_name("GROUP")
-                                .help("Groups to create")
-                                .multiple(true)
-                                .required(true)
-                        )
-                        .arg(
-                            Arg::with_name("PARENT")
-                                .long("parent")
-                                .help("Parent group of the groups to create")
-                                .takes_value(true)
-                                .default_value("@root")
-                        )
-                )
-                .subcommand(
-                    SubCommand::with_name("remove")
-                        .about("Remove existing groups")
-                        .arg(
-                            Arg::with_name("GROUP")
-                                .help("Groups to remove")
-                                .multiple(true)
-                                .required(true),
-                        )
-                )
-                .subcommand(
-                    SubCommand::with_name("list")
-                        .about("List existing groups")
-                )
-        )
-        .subcommand(
-            SubCommand::with_name("requirement")
-                .setting(AppSettings::SubcommandRequiredElseHelp)
-                .about("Operate on requirements")
-                .subcommand(
-                    SubCommand::with_name("add")
-                        .about("Add new requirements")
-                        .arg(
-                            Arg::with_name("PACKAGE")
-                                .help("Requirements to add")
-                                .multiple(true)
-                                .required(true),
-                        )
-                        .arg(
-                            Arg::with_name("PARENT")
-                                .long("parent")
-                                .help("Parent group of the requirements to add")
-                                .takes_value(true)
-                                .default_value("@root")
-                        )
-                )
-                .subcommand(
-                    SubCommand::with_name("remove")
-                        .about("Remove existing requirements")
-                        .arg(
-                            Arg::with_name("PACKAGE")
-                                .help("Requirements to remove")
-                                .multiple(true)
-                                .required(true),
-                        )
-                        .arg(
-                            Arg::with_name("PARENT")
-                                .long("parent")
-                                .help("Parent group of the requirements to add")
-                                .takes_value(true)
-                                .default_value("@root")
-                        )
-                )
-        )
-        .subcommand(
-            SubCommand::with_name("merge")
-                .about("Merge the scratch dependency graph with the regular dependency graph")
-        )
-        .get_matches();
+    let matches = FINEST.app().get_matches();
+
+    // Help and manual pages are generated without any configuration
+    if let ("help", Some(matches)) = matches.subcommand() {
+        return commands::help(&FINEST, matches);
+    }
 
     let result: Result<(), failure::Error> = try {
-        let mut config = config::Config::load_from(matches.value_of("config").unwrap())?;
+        // Configuration fragments are only merged when using the default configuration file
+        let mut config = if matches.occurrences_of("config") > 0 {
+            let mut config = config::Config::load_from(matches.value_of("config").unwrap())?;
+            config.override_from_env()?;
+            config
+        } else {
+            config::Config::load()?
+        };
 
         if let Some(chroot_path) = matches.value_of("chroot") {
             *config.paths_mut() = config.paths().chroot(chroot_path);
         }
+        commands::set_confirmation_policy(&mut config, &matches);
+        commands::set_lock_wait(&mut config, &matches)?;
+        commands::set_download_rate(&mut config, &matches)?;
+        commands::init_logging(&config, &matches);
+        commands::report_config_warnings(&config);
 
         match matches.subcommand() {
-            ("pull", _) => commands::pull(&config),
+            ("pull", Some(matches)) => commands::pull(&config, &matches),
             ("group", Some(sub_matches)) => match sub_matches.subcommand() {
                 ("add", Some(cmd_matches)) => commands::group_add(
                     &config,
@@ -152,6 +60,7 @@ fn main() {
                 _ => unimplemented!(),
             },
             ("merge", _) => commands::merge(&config),
+            ("resume", Some(matches)) => commands::resume(&config, &matches),
             _ => unimplemented!(),
         }?;
     };
@@ -159,13 +68,9 @@ fn main() {
     if let Err(e) = result {
         use std::process::exit;
 
-        let fail = e.as_fail();
-        eprint!("error: {}", fail);
-        for cause in fail.iter_causes() {
-            eprint!(": {}", cause);
-        }
-        eprintln!();
-
-        exit(1);
+        // The exit code tells scripts which kind of failure occurred
+        let error = NestError::from(&e);
+        eprintln!("error: {}", error);
+        exit(error.exit_code());
     }
 }
@@ -1,94 +1,143 @@
 #![feature(try_blocks)]
 
-use clap::{crate_authors, crate_name, crate_version, App, AppSettings, Arg, SubCommand};
 use libnest::config;
 
 pub mod commands;
 
+use commands::errors::NestError;
+use commands::spec::NEST;
+
 fn main() {
-    let matches = App::new(crate_name!())
-        .template("{usage}\n\n{about}\n\nOPTIONS\n{flags}\n\nSUBCOMMANDS\n{subcommands}")
-        .usage("nest [OPTION]... SUBCOMMAND [SUBCOMMAND OPTIONS]...")
-        .about("Raven-OS's package manager.")
-        .version(crate_version!())
-        .author(crate_authors!())
-        .setting(AppSettings::SubcommandRequiredElseHelp)
-        .setting(AppSettings::VersionlessSubcommands)
-        .setting(AppSettings::ColoredHelp)
-        .arg(
-            Arg::with_name("v")
-                .short("v")
-                .multiple(true)
-                .help("Set the level of verbosity"),
-        )
-        .arg(
-            Arg::with_name("config")
-                .short("c")
-                .long("config")
-                .help("Use the given path as a configuration file")
-                .takes_value(true)
-                .default_value("/etc/nest/config.toml")
-        )
-        .arg(
-            Arg::with_name("chroot")
-                .long("chroot")
-                .help("Use the current configuration but operate on the given folder, as if it was the root folder")
-                .takes_value(true)
-        )
-        .subcommand(
-            SubCommand::with_name("pull").about("Pull repositories and update the local cache"),
-        )
-        .subcommand(
-            SubCommand::with_name("install")
-                .alias("add")
-                .about("Download and install the given packages [alias: add]")
-                .arg(
-                    Arg::with_name("PACKAGE")
-                        .help("Packages to install")
-                        .multiple(true)
-                        .required(true),
-                )
-        )
-        .subcommand(
-            SubCommand::with_name("upgrade")
-                .alias("update")
-                .about("Upgrade all installed packages [alias: update]")
-        )
-        .subcommand(
-            SubCommand::with_name("uninstall")
-                .alias("remove")
-                .about("Uninstall the given packages [alias: remove]")
-                .arg(
-                    Arg::with_name("PACKAGE")
-                        .help("Packages to uninstall")
-                        .multiple(true)
-                        .required(true),
-                )
-        )
-        .subcommand(
-            SubCommand::with_name("list")
-                .about("List installed packages")
-                .arg(
-                    Arg::with_name("with-deps")
-                        .long("with-deps")
-                        .help("Include the dependencies of installed packages")
-                )
-        )
-        .get_matches();
+    let matches = NEST.app().get_matches();
+
+    // Help, manual pages and completion scripts are generated without any configuration
+    match matches.subcommand() {
+        ("help", Some(matches)) => return commands::help(&NEST, matches),
+        ("completions", Some(matches)) => return commands::completions(NEST.app(), matches),
+        _ => (),
+    }
+
+    if matches.is_present("json") {
+        commands::output::set_json_output(true);
+        colored::control::set_override(false);
+    }
 
     let result: Result<(), failure::Error> = try {
-        let mut config = config::Config::load_from(matches.value_of("config").unwrap())?;
+        // Configuration fragments are only merged when using the default configuration file
+        let mut config = if matches.occurrences_of("config") > 0 {
+            let mut config = config::Config::load_from(matches.value_of("config").unwrap())?;
+            config.override_from_env()?;
+            config
+        } else {
+            config::Config::load()?
+        };
 
         if let Some(chroot_path) = matches.value_of("chroot") {
             *config.paths_mut() = config.paths().chroot(chroot_path);
         }
+        if let Some(root_path) = matches.value_of("root") {
+            *config.paths_mut() = config.paths().with_install_root(root_path);
+        }
+        *config.dry_run_mut() = matches.is_present("dry-run");
+        commands::set_confirmation_policy(&mut config, &matches);
+        commands::set_lock_wait(&mut config, &matches)?;
+        commands::set_download_rate(&mut config, &matches)?;
+        commands::set_sysroot(&mut config, &matches)?;
+        commands::init_logging(&config, &matches);
+        commands::report_config_warnings(&config);
 
         match matches.subcommand() {
-            ("pull", _) => commands::pull(&config),
+            ("pull", Some(matches)) => commands::pull(&config, &matches),
             ("install", Some(matches)) => commands::install(&config, &matches),
             ("upgrade", Some(matches)) => commands::upgrade(&config, &matches),
+            ("reinstall", Some(matches)) => commands::reinstall(&config, &matches),
             ("uninstall", Some(matches)) => commands::uninstall(&config, &matches),
+            ("autoremove", Some(matches)) => commands::autoremove(&config, &matches),
             ("list", Some(matches)) => commands::list(&config, &matches),
+            ("history", _) => commands::history(&config),
+            ("undo", Some(matches)) => commands::undo(&config, &matches),
+            ("resume", Some(matches)) => commands::resume(&config, &matches),
+            ("rollback", Some(matches)) => commands::rollback(&config, &matches),
+            ("package-names", _) => commands::package_names(&config),
+            ("search", Some(matches)) => commands::search(&config, &matches),
+            ("config-merge", Some(matches)) => commands::config_merge(&config, &matches),
+            ("files", Some(matches)) => commands::files(&config, &matches),
+            ("info", Some(matches)) => commands::info(&config, &matches),
+            ("changelog", Some(matches)) => commands::changelog(&config, &matches),
+            ("license", Some(matches)) => commands::license(&config, &matches),
+            ("audit", Some(matches)) => commands::audit(&config, &matches),
+            ("owns", Some(matches)) => commands::owns(&config, &matches),
+            ("verify", Some(matches)) => commands::verify(&config, &matches),
+            ("doctor", _) => commands::doctor(&config),
+            ("graph", Some(matches)) => commands::graph(&config, &matches),
+            ("hold", Some(matches)) => commands::hold(&config, &matches),
+            ("unhold", Some(matches)) => commands::unhold(&config, &matches),
+            ("alternatives", Some(sub_matches)) => match sub_matches.subcommand() {
+                ("list", Some(matches)) => commands::alternatives_list(&config, &matches),
+                ("set", Some(matches)) => commands::alternatives_set(&config, &matches),
+                ("auto", Some(matches)) => commands::alternatives_auto(&config, &matches),
+                _ => unimplemented!(),
+            },
+            ("why", Some(matches)) => commands::why(&config, &matches),
+            ("rdepends", Some(matches)) => commands::rdepends(&config, &matches),
+            ("mirror", Some(sub_matches)) => match sub_matches.subcommand() {
+                ("ping", _) => commands::mirror_ping(&config),
+                _ => unimplemented!(),
+            },
+            ("config", Some(sub_matches)) => match sub_matches.subcommand() {
+                ("get", Some(matches)) => commands::config_get(&config, &matches),
+                ("set", Some(set_matches)) => commands::config_set(
+                    &config,
+                    commands::ConfigFiles::load(&matches)?,
+                    &set_matches,
+                ),
+                ("migrate", _) => {
+                    commands::config_migrate(&config, commands::ConfigFiles::load(&matches)?)
+                }
+                _ => unimplemented!(),
+            },
+            ("repository", Some(sub_matches)) => {
+                let files = commands::ConfigFiles::load(&matches)?;
+                match sub_matches.subcommand() {
+                    ("list", _) => commands::repository_list(&config),
+                    ("add", Some(matches)) => commands::repository_add(&config, files, &matches),
+                    ("remove", Some(matches)) => {
+                        commands::repository_remove(&config, files, &matches)
+                    }
+                    ("enable", Some(matches)) => {
+                        commands::repository_enable(&config, files, &matches)
+                    }
+                    ("disable", Some(matches)) => {
+                        commands::repository_disable(&config, files, &matches)
+                    }
+                    ("add-mirror", Some(matches)) => {
+                        commands::repository_add_mirror(&config, files, &matches)
+                    }
+                    ("remove-mirror", Some(matches)) => {
+                        commands::repository_remove_mirror(&config, files, &matches)
+                    }
+                    _ => unimplemented!(),
+                }
+            }
+            ("cache", Some(sub_matches)) => match sub_matches.subcommand() {
+                ("stats", _) => commands::cache_stats(&config),
+                ("verify", Some(matches)) => commands::cache_verify(&config, &matches),
+                ("clear", Some(matches)) => commands::cache_clear(&config, &matches),
+                ("clean", Some(matches)) => commands::cache_clean(&config, &matches),
+                _ => unimplemented!(),
+            },
+            ("key", Some(sub_matches)) => match sub_matches.subcommand() {
+                ("import", Some(matches)) => commands::key_import(&config, &matches),
+                ("list", Some(matches)) => commands::key_list(&config, &matches),
+                ("revoke", Some(matches)) => commands::key_revoke(&config, &matches),
+                _ => unimplemented!(),
+            },
+            ("sysroot", Some(sub_matches)) => match sub_matches.subcommand() {
+                ("create", Some(matches)) => commands::sysroot_create(&config, &matches),
+                ("list", _) => commands::sysroot_list(&config),
+                ("remove", Some(matches)) => commands::sysroot_remove(&config, &matches),
+                _ => unimplemented!(),
+            },
             _ => unimplemented!(),
         }?;
     };
@@ -96,13 +145,13 @@ fn main() {
     if let Err(e) = result {
         use std::process::exit;
 
-        let fail = e.as_fail();
-        eprint!("error: {}", fail);
-        for cause in fail.iter_causes() {
-            eprint!(": {}", cause);
+        // The exit code tells scripts which kind of failure occurred
+        let error = NestError::from(&e);
+        if commands::output::json_output() {
+            commands::output::print_json_error(&e);
+        } else {
+            eprintln!("error: {}", error);
         }
-        eprintln!();
-
-        exit(1);
+        exit(error.exit_code());
     }
 }
@@ -1,3 +1,4 @@
+import io
 import json
 import os
 import shutil
@@ -22,6 +23,9 @@ class Package:
             maintainer: str = "nest-tests@raven-os.org",
             licenses: List[str] = None,
             upstream_url: str = None,
+            license: str = None,
+            manifest_version: str = None,
+            extra_fields: Dict[str, Any] = None,
     ):
         self.name = name
         self.category = category
@@ -32,8 +36,12 @@ class Package:
         self.maintainer = maintainer
         self.licenses = licenses or ["gpl_v3"]
         self.upstream_url = upstream_url or "https://google.com"
+        self.license = license
+        self.manifest_version = manifest_version
+        self.extra_fields = extra_fields or {}
         self.dependencies = {}
         self.files = {}
+        self.instructions = None
 
     def full_name(self) -> str:
         return f"tests::{self.category}/{self.name}"
@@ -45,18 +53,43 @@ class Package:
         self.dependencies[dependency.full_name()] = version_requirement
         return self
 
-    def add_file(self, path, with_content=None, from_reader=None) -> 'Package':
-        if not (with_content ^ from_reader):
+    def add_file(self, path: str, with_content: bytes = None, from_reader=None, mode: int = 0o644) -> 'Package':
+        if (with_content is None) == (from_reader is None):
             raise ValueError("Invalid arguments: exactly one of 'with_content' and 'from_reader' must be used")
-        # self.files[path] =
+        content = with_content if with_content is not None else from_reader.read()
+        self.files[path] = (tarfile.REGTYPE, content, mode)
         return self
 
     def add_symlink(self, path: str, target: str) -> 'Package':
+        self.files[path] = (tarfile.SYMTYPE, target, 0o777)
+        return self
+
+    def add_hardlink(self, path: str, target: str) -> 'Package':
+        self.files[path] = (tarfile.LNKTYPE, target, 0o644)
+        return self
+
+    def set_instructions(self, script: str) -> 'Package':
+        self.instructions = script
         return self
 
     def add_directory(self, path: str) -> 'Package':
+        self.files[path] = (tarfile.DIRTYPE, None, 0o755)
         return self
 
+    def _create_data(self, path: str):
+        with tarfile.open(path, "w:gz") as tar:
+            for name, (kind, data, mode) in self.files.items():
+                info = tarfile.TarInfo(name)
+                info.type = kind
+                info.mode = mode
+                if kind == tarfile.REGTYPE:
+                    info.size = len(data)
+                    tar.addfile(info, io.BytesIO(data))
+                else:
+                    if kind in (tarfile.SYMTYPE, tarfile.LNKTYPE):
+                        info.linkname = data
+                    tar.addfile(info)
+
     def _create_in(self, directory: str):
         directory = f"{directory}/{self.category}/{self.name}"
         os.makedirs(directory, exist_ok=True)
@@ -75,21 +108,37 @@ class Package:
             },
             "dependencies": self.dependencies
         }
+        if self.license is not None:
+            manifest["metadata"]["license"] = self.license
+        if self.manifest_version is not None:
+            manifest["manifest-version"] = self.manifest_version
+        manifest.update(self.extra_fields)
         manifest_path = f"{directory}/manifest.toml"
         with open(manifest_path, 'x') as f:
             toml.dump(manifest, f)
 
         files = [(manifest_path, "manifest.toml")]
 
+        if self.instructions is not None:
+            instructions_path = f"{directory}/instructions.sh"
+            with open(instructions_path, 'x') as f:
+                f.write(self.instructions)
+            files.append((instructions_path, "instructions.sh"))
+
         if self.kind == "effective":
-            with tarfile.open(f"{directory}/data.tar.gz", "w:gz") as tar:
-                pass
+            self._create_data(f"{directory}/data.tar.gz")
             files.append((f"{directory}/data.tar.gz", "data.tar.gz"))
 
-        with tarfile.open(f"{directory}/{self.name}-{self.version}.nest", "x") as tar:
+        npf_path = f"{directory}/{self.name}-{self.version}.nest"
+        with tarfile.open(npf_path, "x") as tar:
             for name, arcname in files:
                 tar.add(name, arcname=arcname)
                 os.remove(name)
+        return npf_path
+
+    def create_npf(self, directory: str) -> str:
+        """Creates the NPF of this package in the given directory, and returns its path"""
+        return self._create_in(directory)
 
 
 def _create_packages(packages: List[Package]):
@@ -134,9 +183,20 @@ def nest_server(packages: List[Package] = None):
             shutil.rmtree(f"{nest_server_path}/cache")
 
 
+def install_host_programs(chroot: str, programs: List[str]):
+    """Copies the given programs of the host in the given chroot, along with the libraries they are linked with"""
+    for program in programs:
+        ldd = subprocess.run(["ldd", program], capture_output=True, text=True).stdout
+        libraries = [word for word in ldd.split() if word.startswith('/')]
+        for path in [program] + libraries:
+            destination = f"{chroot}/{path}"
+            os.makedirs(os.path.dirname(destination), exist_ok=True)
+            shutil.copy2(os.path.realpath(path), destination)
+
+
 @contextmanager
 def create_config(entries: Dict[str, Dict[str, Any]] = None):
-    entries = entries or {"repositories": {"tests": {"mirrors": ["http://localhost:8000"]}}}
+    entries = entries or {"repositories": {"tests": {"mirrors": ["http://localhost:8000"], "allow_unsigned": True}}}
     path = tempfile.NamedTemporaryFile().name
     with open(path, 'w') as f:
         toml.dump(entries, f)
@@ -159,6 +219,14 @@ class _Depgraph:
     def groups(self):
         return filter(lambda name: name[0] == '@', self.data["node_names"])
 
+    def installed_versions(self) -> Dict[str, str]:
+        versions = {}
+        for node in self.data.get("nodes", {}).values():
+            if "Package" in node["kind"]:
+                full_name, version = node["kind"]["Package"]["id"].split('#', 1)
+                versions[full_name] = version
+        return versions
+
 
 class _Nest:
     def __init__(self, config: str = None, chroot: str = None):
@@ -174,8 +242,8 @@ class _Nest:
         cmd += args
         return subprocess.run(cmd, capture_output=True, input=input_str and input_str.encode())
 
-    def pull(self, confirm=True):
-        return self._run("pull", input_str="yes" if confirm else "no")
+    def pull(self, *args: str, confirm=True):
+        return self._run("pull", *args, input_str="yes" if confirm else "no")
 
     def install(self, *packages: str, confirm=True):
         return self._run("install", *packages, input_str="yes" if confirm else "no")
@@ -183,6 +251,27 @@ class _Nest:
     def uninstall(self, *packages: str, confirm=True):
         return self._run("uninstall", *packages, input_str="yes" if confirm else "no")
 
+    def upgrade(self, *packages: str, confirm=True):
+        return self._run("upgrade", *packages, input_str="yes" if confirm else "no")
+
+    def reinstall(self, *packages: str, confirm=True):
+        return self._run("reinstall", *packages, input_str="yes" if confirm else "no")
+
+    def autoremove(self, *args: str, confirm=True):
+        return self._run("autoremove", *args, input_str="yes" if confirm else "no")
+
+    def undo(self, *args: str, confirm=True):
+        return self._run("undo", *args, input_str="yes" if confirm else "no")
+
+    def resume(self, *args: str, confirm=True):
+        return self._run("resume", *args, input_str="yes" if confirm else "no")
+
+    def hold(self, *packages: str):
+        return self._run("hold", *packages)
+
+    def license(self, *args: str):
+        return self._run("license", *args)
+
     def list(self):
         pass
 
@@ -0,0 +1,37 @@
+#!/usr/bin/env python3.7
+
+"""
+The system calls denied by the sandbox of instructions.sh should fail with EPERM, without stopping the script
+"""
+
+from nesttests import *
+import os
+
+chroot = os.getenv("NEST_CHROOT")
+install_host_programs(chroot, ["/bin/sh", "/bin/mknod"])
+
+sandboxed_package = Package(
+    name="sandboxed-package",
+    category="sys-apps",
+    version="1.0.0",
+    kind="effective",
+).set_instructions("""
+after_install() {
+    mknod /node c 1 3 2> /mknod-error
+    echo "$?" > /mknod-status
+}
+""")
+
+entries = {
+    "repositories": {"tests": {"mirrors": ["http://localhost:8000"], "allow_unsigned": True}},
+    "sandbox": {"required": True},
+}
+
+with nest_server(packages=[sandboxed_package]), create_config(entries=entries) as config_path:
+    nest = nest(config=config_path)
+    assert nest.pull().returncode == 0
+    assert nest.install("sandboxed-package", confirm=True).returncode == 0
+    assert "tests::sys-apps/sandboxed-package" in nest.depgraph().installed_packages()
+    assert not os.path.lexists(f"{chroot}/node")
+    assert open(f"{chroot}/mknod-status").read().strip() != "0"
+    assert "Operation not permitted" in open(f"{chroot}/mknod-error").read()
@@ -0,0 +1,55 @@
+#!/usr/bin/env python3.7
+
+"""
+When the most recent versions of the required packages lead to a dead end, the solver should backtrack to another
+version of one of them
+"""
+
+from nesttests import *
+
+library_v1 = Package(
+    name="library",
+    category="sys-libs",
+    version="1.0.0",
+    kind="virtual",
+)
+
+library_v2 = Package(
+    name="library",
+    category="sys-libs",
+    version="2.0.0",
+    kind="virtual",
+)
+
+recent_user = Package(
+    name="recent-user",
+    category="sys-apps",
+    version="1.0.0",
+    kind="virtual",
+).add_dependency(library_v2, ">=2.0.0")
+
+old_user_v1 = Package(
+    name="old-user",
+    category="sys-apps",
+    version="1.0.0",
+    kind="virtual",
+).add_dependency(library_v1, ">=1.0.0")
+
+old_user_v2 = Package(
+    name="old-user",
+    category="sys-apps",
+    version="2.0.0",
+    kind="virtual",
+).add_dependency(library_v1, "<2.0.0")
+
+packages = [library_v1, library_v2, recent_user, old_user_v1, old_user_v2]
+
+with nest_server(packages=packages), create_config() as config_path:
+    nest = nest(config=config_path)
+    assert nest.pull().returncode == 0
+    assert nest.install("recent-user", "old-user", confirm=True).returncode == 0
+
+    versions = nest.depgraph().installed_versions()
+    assert versions["tests::sys-apps/recent-user"] == "1.0.0"
+    assert versions["tests::sys-libs/library"] == "2.0.0"
+    assert versions["tests::sys-apps/old-user"] == "1.0.0"
@@ -0,0 +1,67 @@
+#!/usr/bin/env python3.7
+
+"""
+When an upgrade leads to a dead end because of a held package, the solver should keep the held package in its version
+and select versions of the other packages compatible with it
+"""
+
+from nesttests import *
+
+library_v1 = Package(
+    name="library",
+    category="sys-libs",
+    version="1.0.0",
+    kind="virtual",
+)
+
+library_v2 = Package(
+    name="library",
+    category="sys-libs",
+    version="2.0.0",
+    kind="virtual",
+)
+
+held_v1 = Package(
+    name="held-package",
+    category="sys-apps",
+    version="1.0.0",
+    kind="virtual",
+).add_dependency(library_v1, "<2.0.0")
+
+held_v2 = Package(
+    name="held-package",
+    category="sys-apps",
+    version="2.0.0",
+    kind="virtual",
+)
+
+user_v1 = Package(
+    name="library-user",
+    category="sys-apps",
+    version="1.0.0",
+    kind="virtual",
+).add_dependency(library_v1, ">=1.0.0")
+
+user_v2 = Package(
+    name="library-user",
+    category="sys-apps",
+    version="2.0.0",
+    kind="virtual",
+).add_dependency(library_v2, ">=2.0.0")
+
+with create_config() as config_path:
+    nest = nest(config=config_path)
+
+    with nest_server(packages=[library_v1, held_v1, user_v1]):
+        assert nest.pull().returncode == 0
+        assert nest.install("held-package", "library-user", confirm=True).returncode == 0
+        assert nest.hold("held-package").returncode == 0
+
+    with nest_server(packages=[library_v1, library_v2, held_v1, held_v2, user_v1, user_v2]):
+        assert nest.pull().returncode == 0
+        assert nest.upgrade(confirm=True).returncode == 0
+
+        versions = nest.depgraph().installed_versions()
+        assert versions["tests::sys-apps/held-package"] == "1.0.0"
+        assert versions["tests::sys-libs/library"] == "1.0.0"
+        assert versions["tests::sys-apps/library-user"] == "1.0.0"
@@ -0,0 +1,53 @@
+#!/usr/bin/env python3.7
+
+"""
+When the instructions of a package fail after its files were extracted, the transaction should be rolled back,
+restoring the files of the previous version of the package
+"""
+
+from nesttests import *
+import os
+
+chroot = os.getenv("NEST_CHROOT")
+install_host_programs(chroot, ["/bin/sh"])
+
+package_v1 = Package(
+    name="rolled-back-package",
+    category="sys-apps",
+    version="1.0.0",
+    kind="effective",
+).add_file("usr/share/rolled-back-package/version", with_content=b"1.0.0\n")
+
+package_v2 = Package(
+    name="rolled-back-package",
+    category="sys-apps",
+    version="2.0.0",
+    kind="effective",
+).add_file(
+    "usr/share/rolled-back-package/version", with_content=b"2.0.0\n"
+).add_file(
+    "usr/share/rolled-back-package/added", with_content=b"2.0.0\n"
+).set_instructions("""
+after_install() {
+    exit 1
+}
+""")
+
+version_path = f"{chroot}/usr/share/rolled-back-package/version"
+added_path = f"{chroot}/usr/share/rolled-back-package/added"
+
+with create_config() as config_path:
+    nest = nest(config=config_path)
+
+    with nest_server(packages=[package_v1]):
+        assert nest.pull().returncode == 0
+        assert nest.install("rolled-back-package", confirm=True).returncode == 0
+        assert open(version_path, 'rb').read() == b"1.0.0\n"
+
+    with nest_server(packages=[package_v1, package_v2]):
+        assert nest.pull().returncode == 0
+        assert nest.upgrade(confirm=True).returncode != 0
+
+        assert open(version_path, 'rb').read() == b"1.0.0\n"
+        assert not os.path.lexists(added_path)
+        assert nest.depgraph().installed_versions()["tests::sys-apps/rolled-back-package"] == "1.0.0"
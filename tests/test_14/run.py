@@ -0,0 +1,83 @@
+#!/usr/bin/env python3.7
+
+"""
+Pulling a repository should be refused if its metadata is tampered with, signed with an untrusted key, expired,
+or older than the metadata pulled previously
+"""
+
+from nesttests import *
+from datetime import datetime, timedelta, timezone
+from nacl.signing import SigningKey
+import base64
+import hashlib
+import json
+import os
+import shutil
+
+EXIT_VERIFICATION = 4
+
+repository_path = "/tmp/nest-tests-repository"
+key = SigningKey.generate()
+untrusted_key = SigningKey.generate()
+
+
+def sign(metadata: Dict[str, Any], with_key: SigningKey) -> bytes:
+    signed = json.dumps(metadata)
+    signature = with_key.sign(signed.encode()).signature
+    return json.dumps({"signed": signed, "signature": base64.b64encode(signature).decode()}).encode()
+
+
+def publish(serial: int, packages: bytes, expires_in: timedelta = timedelta(days=1),
+            with_key: SigningKey = key, tampered_packages: bytes = None):
+    """Writes the package list and the signed metadata of the repository at the given serial"""
+    expires = (datetime.now(timezone.utc) + expires_in).strftime("%Y-%m-%dT%H:%M:%SZ")
+    snapshot = sign({
+        "serial": serial,
+        "expires": expires,
+        "packages": hashlib.sha256(packages).hexdigest(),
+    }, with_key)
+    timestamp = sign({
+        "serial": serial,
+        "expires": expires,
+        "snapshot": hashlib.sha256(snapshot).hexdigest(),
+    }, with_key)
+
+    os.makedirs(f"{repository_path}/api", exist_ok=True)
+    for route, data in [("pull", tampered_packages or packages), ("snapshot", snapshot), ("timestamp", timestamp)]:
+        with open(f"{repository_path}/api/{route}", 'wb') as f:
+            f.write(data)
+
+
+packages = json.dumps([]).encode()
+config = {
+    "repositories": {
+        "tests": {
+            "mirrors": [repository_path],
+            "trusted_keys": [base64.b64encode(key.verify_key.encode()).decode()],
+        }
+    }
+}
+
+try:
+    with create_config(config) as config_path:
+        nest = nest(config=config_path)
+
+        publish(2, packages, tampered_packages=json.dumps([{}]).encode())
+        assert nest.pull().returncode == EXIT_VERIFICATION
+
+        publish(2, packages, with_key=untrusted_key)
+        assert nest.pull().returncode == EXIT_VERIFICATION
+
+        publish(2, packages, expires_in=timedelta(days=-1))
+        assert nest.pull().returncode == EXIT_VERIFICATION
+
+        publish(2, packages)
+        assert nest.pull().returncode == 0
+
+        publish(1, packages)
+        assert nest.pull().returncode == EXIT_VERIFICATION
+
+        publish(3, packages)
+        assert nest.pull().returncode == 0
+finally:
+    shutil.rmtree(repository_path, ignore_errors=True)
@@ -0,0 +1,71 @@
+#!/usr/bin/env python3.7
+
+"""
+Packages with an invalid license expression should be refused when pulling, and `nest license` should report the
+license of the installed packages, failing if one of them can't be complied with without a
+license of the deny-list
+"""
+
+from nesttests import *
+import json
+
+permissive_package = Package(
+    name="permissive-package",
+    category="sys-libs",
+    version="1.0.0",
+    kind="effective",
+    license="MIT",
+)
+
+dual_licensed_package = Package(
+    name="dual-licensed-package",
+    category="sys-libs",
+    version="1.0.0",
+    kind="effective",
+    license="MIT OR GPL-3.0-only",
+)
+
+denied_package = Package(
+    name="denied-package",
+    category="sys-libs",
+    version="1.0.0",
+    kind="effective",
+    license="GPL-3.0-only",
+)
+
+invalid_package = Package(
+    name="invalid-package",
+    category="sys-libs",
+    version="1.0.0",
+    kind="effective",
+    license="MIT AND",
+)
+
+config = {
+    "repositories": {"tests": {"mirrors": ["http://localhost:8000"], "allow_unsigned": True}},
+    "licenses": {"deny": ["GPL-3.0-only"]},
+}
+
+with create_config(config) as config_path:
+    nest = nest(config=config_path)
+
+    with nest_server(packages=[invalid_package]):
+        assert nest.pull().returncode != 0
+
+    with nest_server(packages=[permissive_package, dual_licensed_package, denied_package]):
+        assert nest.pull().returncode == 0
+        assert nest.install("permissive-package", "dual-licensed-package", confirm=True).returncode == 0
+
+        report = nest.license("--json")
+        assert report.returncode == 0
+        licenses = {entry["package"]: entry for entry in json.loads(report.stdout.splitlines()[0])}
+        assert licenses[permissive_package.package_id()]["license"] == "MIT"
+        assert licenses[dual_licensed_package.package_id()]["denied"] == []
+
+        assert nest.install("denied-package", confirm=True).returncode == 0
+
+        report = nest.license("--denied", "--json")
+        assert report.returncode != 0
+        licenses = json.loads(report.stdout.splitlines()[0])
+        assert [entry["package"] for entry in licenses] == [denied_package.package_id()]
+        assert licenses[0]["denied"] == ["GPL-3.0-only"]
@@ -0,0 +1,52 @@
+#!/usr/bin/env python3.7
+
+"""
+Packages should be designated by their name, category and repository, glob patterns and version requirements, a name
+matching several packages being refused unless it is a pattern
+"""
+
+from nesttests import *
+
+system_tool = Package(name="tool", category="sys-apps", version="1.0.0", kind="effective")
+development_tool = Package(name="tool", category="dev-util", version="1.0.0", kind="effective")
+library_v1 = Package(name="library", category="sys-libs", version="1.0.0", kind="effective")
+library_v2 = Package(name="library", category="sys-libs", version="2.0.0", kind="effective")
+module_a = Package(name="module-a", category="dev-python", version="1.0.0", kind="effective")
+module_b = Package(name="module-b", category="dev-python", version="1.0.0", kind="effective")
+
+packages = [system_tool, development_tool, library_v1, library_v2, module_a, module_b]
+
+with nest_server(packages=packages), create_config() as config_path:
+    nest = nest(config=config_path)
+    assert nest.pull().returncode == 0
+
+    # The name matches a package of each category
+    assert nest.install("tool", confirm=True).returncode != 0
+    assert list(nest.depgraph().installed_packages()) == []
+
+    assert nest.install("dev-util/tool", confirm=True).returncode == 0
+    installed = list(nest.depgraph().installed_packages())
+    assert development_tool.full_name() in installed
+    assert system_tool.full_name() not in installed
+
+    assert nest.install("tests::sys-libs/library#<2.0.0", confirm=True).returncode == 0
+    assert nest.depgraph().installed_versions()[library_v1.full_name()] == "1.0.0"
+
+    assert nest.install("library>=2.0.0", confirm=True).returncode == 0
+    assert nest.depgraph().installed_versions()[library_v2.full_name()] == "2.0.0"
+
+    assert nest.install("*/module-a", "dev-python/module-b", confirm=True).returncode == 0
+    installed = list(nest.depgraph().installed_packages())
+    assert module_a.full_name() in installed
+    assert module_b.full_name() in installed
+
+    # Patterns designate all the installed packages they match
+    assert nest.uninstall("dev-python/*", confirm=True).returncode == 0
+    installed = list(nest.depgraph().installed_packages())
+    assert module_a.full_name() not in installed
+    assert module_b.full_name() not in installed
+    assert library_v2.full_name() in installed
+
+    # Only one of the packages the name matches is installed
+    assert nest.uninstall("tool", confirm=True).returncode == 0
+    assert development_tool.full_name() not in nest.depgraph().installed_packages()
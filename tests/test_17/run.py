@@ -0,0 +1,45 @@
+#!/usr/bin/env python3.7
+
+"""
+Packages whose manifest has a more recent minor version should be installed, ignoring the fields Nest doesn't know
+about, while those whose manifest has another major version should be refused
+"""
+
+from nesttests import *
+import os
+import tempfile
+
+chroot = os.getenv("NEST_CHROOT")
+
+compatible_package = Package(
+    name="compatible-package",
+    category="sys-apps",
+    version="1.0.0",
+    kind="effective",
+    manifest_version="1.7",
+    extra_fields={"field-from-the-future": "ignored"},
+).add_file("usr/share/compatible-package/file", with_content=b"compatible\n")
+
+incompatible_package = Package(
+    name="incompatible-package",
+    category="sys-apps",
+    version="1.0.0",
+    kind="effective",
+    manifest_version="2.0",
+).add_file("usr/share/incompatible-package/file", with_content=b"incompatible\n")
+
+with tempfile.TemporaryDirectory() as directory, nest_server(), create_config() as config_path:
+    nest = nest(config=config_path)
+    assert nest.pull().returncode == 0
+
+    compatible_npf = compatible_package.create_npf(directory)
+    assert nest.install("--from-file", compatible_npf, confirm=True).returncode == 0
+    assert "local::sys-apps/compatible-package" in nest.depgraph().installed_packages()
+    assert open(f"{chroot}/usr/share/compatible-package/file", 'rb').read() == b"compatible\n"
+
+    incompatible_npf = incompatible_package.create_npf(directory)
+    result = nest.install("--from-file", incompatible_npf, confirm=True)
+    assert result.returncode != 0
+    assert b"upgrade Nest" in result.stderr
+    assert "local::sys-apps/incompatible-package" not in nest.depgraph().installed_packages()
+    assert not os.path.lexists(f"{chroot}/usr/share/incompatible-package/file")
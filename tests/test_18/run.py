@@ -0,0 +1,48 @@
+#!/usr/bin/env python3.7
+
+"""
+Installed files edited in place should be restored by a reinstallation, and shouldn't spread to the packages
+installed later with the same content
+"""
+
+from nesttests import *
+import os
+import subprocess
+
+chroot = os.getenv("NEST_CHROOT")
+
+content = b"the original content\n"
+
+edited_package = Package(
+    name="edited-package",
+    category="sys-apps",
+    version="1.0.0",
+    kind="effective",
+).add_file("usr/share/edited-package/file", with_content=content)
+
+same_content_package = Package(
+    name="same-content-package",
+    category="sys-apps",
+    version="1.0.0",
+    kind="effective",
+).add_file("usr/share/same-content-package/file", with_content=content)
+
+edited_path = f"{chroot}/usr/share/edited-package/file"
+same_content_path = f"{chroot}/usr/share/same-content-package/file"
+
+with nest_server(packages=[edited_package, same_content_package]), create_config() as config_path:
+    nest = nest(config=config_path)
+    assert nest.pull().returncode == 0
+    assert nest.install("edited-package", confirm=True).returncode == 0
+    assert open(edited_path, 'rb').read() == content
+
+    # The file is truncated and written again, keeping its inode
+    subprocess.run(["sudo", "tee", edited_path], input=b"edited\n", stdout=subprocess.DEVNULL, check=True)
+    assert open(edited_path, 'rb').read() == b"edited\n"
+
+    assert nest.install("same-content-package", confirm=True).returncode == 0
+    assert open(same_content_path, 'rb').read() == content
+
+    assert nest.reinstall("edited-package", confirm=True).returncode == 0
+    assert open(edited_path, 'rb').read() == content
+    assert open(same_content_path, 'rb').read() == content
@@ -0,0 +1,36 @@
+#!/usr/bin/env python3.7
+
+"""
+With --json, stdout should only carry JSON documents, even when a command has nothing to do
+"""
+
+from nesttests import *
+import json
+
+
+def assert_json_output(result):
+    assert result.returncode == 0
+    for line in result.stdout.splitlines():
+        json.loads(line)
+
+
+package = Package(
+    name="json-package",
+    category="sys-apps",
+    version="1.0.0",
+    kind="effective",
+)
+
+with nest_server(packages=[package]), create_config() as config_path:
+    nest = nest(config=config_path)
+    assert_json_output(nest.pull("--json"))
+
+    assert_json_output(nest.undo("--json"))
+    assert_json_output(nest.resume("--json"))
+
+    assert_json_output(nest.install("json-package", "--json", confirm=True))
+    assert package.full_name() in nest.depgraph().installed_packages()
+
+    assert_json_output(nest.install("json-package", "--json", confirm=True))
+    assert_json_output(nest.upgrade("--json", confirm=True))
+    assert_json_output(nest.autoremove("--json", confirm=True))
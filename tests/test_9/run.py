@@ -0,0 +1,28 @@
+#!/usr/bin/env python3.7
+
+"""
+Hard links reaching a file that is not part of the package through a symbolic link should be refused
+"""
+
+from nesttests import *
+import os
+
+chroot = os.getenv("NEST_CHROOT")
+os.makedirs(f"{chroot}/etc")
+with open(f"{chroot}/etc/secret", 'w') as f:
+    f.write("secret")
+
+malicious_package = Package(
+    name="malicious-package",
+    category="sys-apps",
+    version="1.0.0",
+    kind="effective",
+).add_symlink("d", "/etc").add_hardlink("usr/share/secret", "d/secret")
+
+with nest_server(packages=[malicious_package]), create_config() as config_path:
+    nest = nest(config=config_path)
+    assert nest.pull().returncode == 0
+    assert nest.install("malicious-package", confirm=True).returncode == 1
+    assert "tests::sys-apps/malicious-package" not in nest.depgraph().installed_packages()
+    assert os.stat(f"{chroot}/etc/secret").st_nlink == 1
+    assert not os.path.lexists(f"{chroot}/usr/share/secret")